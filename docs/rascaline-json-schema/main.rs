@@ -3,8 +3,17 @@ use std::path::PathBuf;
 use schemars::schema::RootSchema;
 
 use rascaline::calculators::SortedDistances;
+use rascaline::calculators::Composition;
 use rascaline::calculators::SphericalExpansionParameters;
 use rascaline::calculators::PowerSpectrumParameters;
+use rascaline::calculators::LambdaSoapParameters;
+use rascaline::calculators::ElectrostaticPotentialParameters;
+use rascaline::calculators::RadialDistributionParameters;
+use rascaline::calculators::AngularDistributionParameters;
+use rascaline::calculators::BondEnvironmentParameters;
+use rascaline::calculators::VoronoiParameters;
+use rascaline::calculators::GraphMomentsParameters;
+use rascaline::calculators::PipelineParameters;
 
 
 macro_rules! generate_schema {
@@ -31,6 +40,15 @@ fn save_schema(name: &str, schema: RootSchema) {
 
 fn main() {
     generate_schema!(SortedDistances);
+    generate_schema!(Composition);
     generate_schema!("SphericalExpansion", SphericalExpansionParameters);
     generate_schema!("SoapPowerSpectrum", PowerSpectrumParameters);
+    generate_schema!("LambdaSoap", LambdaSoapParameters);
+    generate_schema!("ElectrostaticPotential", ElectrostaticPotentialParameters);
+    generate_schema!("RadialDistribution", RadialDistributionParameters);
+    generate_schema!("AngularDistribution", AngularDistributionParameters);
+    generate_schema!("BondEnvironment", BondEnvironmentParameters);
+    generate_schema!("VoronoiEnvironment", VoronoiParameters);
+    generate_schema!("GraphMoments", GraphMomentsParameters);
+    generate_schema!("Pipeline", PipelineParameters);
 }
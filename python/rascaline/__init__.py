@@ -3,12 +3,28 @@ import os
 
 from pkg_resources import DistributionNotFound, get_distribution
 
+from .calculators import AngularDistribution  # noqa
+from .calculators import BondEnvironment  # noqa
 from .calculators import CalculatorBase  # noqa
+from .calculators import Composition  # noqa
+from .calculators import ElectrostaticPotential  # noqa
+from .calculators import GraphMoments  # noqa
+from .calculators import LambdaSoap  # noqa
+from .calculators import Pipeline  # noqa
+from .calculators import RadialDistribution  # noqa
 from .calculators import SoapPowerSpectrum  # noqa
 from .calculators import SortedDistances  # noqa
 from .calculators import SphericalExpansion  # noqa
+from .calculators import VoronoiEnvironment  # noqa
 from .descriptor import Descriptor, Indexes  # noqa
-from .log import set_logging_callback  # noqa
+from .info import (  # noqa
+    calculator_schema,
+    calculator_supports_gradients,
+    calculators_list,
+    has_feature,
+    library_version,
+)
+from .log import set_logging_callback, set_logging_level  # noqa
 from .profiling import Profiler  # noqa
 from .status import RascalError  # noqa
 from .systems import SystemBase  # noqa
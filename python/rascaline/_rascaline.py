@@ -21,6 +21,8 @@ RASCAL_INVALID_PARAMETER_ERROR = 1
 RASCAL_JSON_ERROR = 2
 RASCAL_UTF8_ERROR = 3
 RASCAL_CHEMFILES_ERROR = 4
+RASCAL_IO_ERROR = 5
+RASCAL_CANCELLED_ERROR = 6
 RASCAL_SYSTEM_ERROR = 128
 RASCAL_BUFFER_SIZE_ERROR = 254
 RASCAL_INTERNAL_ERROR = 255
@@ -29,10 +31,13 @@ RASCAL_LOG_LEVEL_WARN = 2
 RASCAL_LOG_LEVEL_INFO = 3
 RASCAL_LOG_LEVEL_DEBUG = 4
 RASCAL_LOG_LEVEL_TRACE = 5
+RASCAL_SYSTEM_VERSION = 2
 
 
 rascal_status_t = ctypes.c_int32
 rascal_logging_callback_t = CFUNCTYPE(None, ctypes.c_int32, ctypes.c_char_p)
+rascal_malloc_callback_t = CFUNCTYPE(ctypes.c_void_p, c_uintptr_t)
+rascal_free_callback_t = CFUNCTYPE(None, ctypes.c_void_p)
 
 
 class rascal_indexes_kind(enum.Enum):
@@ -41,10 +46,34 @@ class rascal_indexes_kind(enum.Enum):
     RASCAL_INDEXES_GRADIENT_SAMPLES = 2
 
 
+class rascal_memory_layout(enum.Enum):
+    RASCAL_MEMORY_LAYOUT_ROW_MAJOR = 0
+    RASCAL_MEMORY_LAYOUT_FEATURE_MAJOR = 1
+
+
+class rascal_parallelism(enum.Enum):
+    RASCAL_PARALLELISM_SAMPLES = 0
+    RASCAL_PARALLELISM_SYSTEMS = 1
+
+
+class rascal_random_projection(enum.Enum):
+    RASCAL_RANDOM_PROJECTION_GAUSSIAN = 0
+    RASCAL_RANDOM_PROJECTION_SPARSE = 1
+
+
+class rascal_sample_distance(enum.Enum):
+    RASCAL_SAMPLE_DISTANCE_EUCLIDEAN = 0
+    RASCAL_SAMPLE_DISTANCE_COSINE = 1
+
+
 class rascal_calculator_t(ctypes.Structure):
     pass
 
 
+class rascal_cancellation_t(ctypes.Structure):
+    pass
+
+
 class rascal_descriptor_t(ctypes.Structure):
     pass
 
@@ -55,11 +84,20 @@ class rascal_pair_t(ctypes.Structure):
         ("second", c_uintptr_t),
         ("distance", ctypes.c_double),
         ("vector", ctypes.c_double * 3),
+        ("cell_shift", ctypes.c_int32 * 3),
+    ]
+
+
+class rascal_triplet_t(ctypes.Structure):
+    _fields_ = [
+        ("first", c_uintptr_t),
+        ("second", c_uintptr_t),
     ]
 
 
 class rascal_system_t(ctypes.Structure):
     _fields_ = [
+        ("version", ctypes.c_uint32),
         ("user_data", ctypes.c_void_p),
         ("size", CFUNCTYPE(rascal_status_t, ctypes.c_void_p, POINTER(c_uintptr_t))),
         ("species", CFUNCTYPE(rascal_status_t, ctypes.c_void_p, POINTER(ndpointer(ctypes.c_int32, flags='C_CONTIGUOUS')))),
@@ -68,6 +106,11 @@ class rascal_system_t(ctypes.Structure):
         ("compute_neighbors", CFUNCTYPE(rascal_status_t, ctypes.c_void_p, ctypes.c_double)),
         ("pairs", CFUNCTYPE(rascal_status_t, ctypes.c_void_p, POINTER(ndpointer(rascal_pair_t, flags='C_CONTIGUOUS')), POINTER(c_uintptr_t))),
         ("pairs_containing", CFUNCTYPE(rascal_status_t, ctypes.c_void_p, c_uintptr_t, POINTER(ndpointer(rascal_pair_t, flags='C_CONTIGUOUS')), POINTER(c_uintptr_t))),
+        ("cell_shifts", CFUNCTYPE(rascal_status_t, ctypes.c_void_p, POINTER(ctypes.c_bool))),
+        ("data", CFUNCTYPE(rascal_status_t, ctypes.c_void_p, ctypes.c_char_p, POINTER(POINTER(ctypes.c_double)))),
+        ("triplets_containing", CFUNCTYPE(rascal_status_t, ctypes.c_void_p, c_uintptr_t, POINTER(ndpointer(rascal_triplet_t, flags='C_CONTIGUOUS')), POINTER(c_uintptr_t))),
+        ("pairs_with_cutoff", CFUNCTYPE(rascal_status_t, ctypes.c_void_p, ctypes.c_double, POINTER(ndpointer(rascal_pair_t, flags='C_CONTIGUOUS')), POINTER(c_uintptr_t))),
+        ("pairs_containing_with_cutoff", CFUNCTYPE(rascal_status_t, ctypes.c_void_p, ctypes.c_double, c_uintptr_t, POINTER(ndpointer(rascal_pair_t, flags='C_CONTIGUOUS')), POINTER(c_uintptr_t))),
     ]
 
 
@@ -93,6 +136,12 @@ class rascal_calculation_options_t(ctypes.Structure):
         ("use_native_system", ctypes.c_bool),
         ("selected_samples", rascal_indexes_t),
         ("selected_features", rascal_indexes_t),
+        ("threads", c_uintptr_t),
+        ("parallelism", ctypes.c_int),
+        ("check_finite", ctypes.c_bool),
+        ("deterministic", ctypes.c_bool),
+        ("neighbors_cutoff", ctypes.c_double),
+        ("lazy_gradients", ctypes.c_bool),
     ]
 
 
@@ -109,6 +158,54 @@ def setup_functions(lib):
     ]
     lib.rascal_set_logging_callback.restype = _check_rascal_status_t
 
+    lib.rascal_set_logging_level.argtypes = [
+        ctypes.c_int32
+    ]
+    lib.rascal_set_logging_level.restype = _check_rascal_status_t
+
+    lib.rascal_set_allocator.argtypes = [
+        rascal_malloc_callback_t,
+        rascal_free_callback_t
+    ]
+    lib.rascal_set_allocator.restype = _check_rascal_status_t
+
+    lib.rascal_free.argtypes = [
+        ctypes.c_void_p
+    ]
+    lib.rascal_free.restype = _check_rascal_status_t
+
+    lib.rascal_basic_system.argtypes = [
+        POINTER(ctypes.c_int32),
+        POINTER(ctypes.c_double),
+        c_uintptr_t,
+        POINTER(ctypes.c_double),
+        POINTER(rascal_system_t)
+    ]
+    lib.rascal_basic_system.restype = _check_rascal_status_t
+
+    lib.rascal_basic_system_free.argtypes = [
+        POINTER(rascal_system_t)
+    ]
+    lib.rascal_basic_system_free.restype = _check_rascal_status_t
+
+    lib.rascal_system_affected_centers.argtypes = [
+        POINTER(rascal_system_t),
+        ctypes.c_double,
+        POINTER(c_uintptr_t),
+        c_uintptr_t,
+        POINTER(c_uintptr_t),
+        POINTER(c_uintptr_t)
+    ]
+    lib.rascal_system_affected_centers.restype = _check_rascal_status_t
+
+    lib.rascal_basic_system_set_pairs.argtypes = [
+        POINTER(rascal_system_t),
+        ctypes.c_double,
+        POINTER(rascal_pair_t),
+        c_uintptr_t
+    ]
+    lib.rascal_basic_system_set_pairs.restype = _check_rascal_status_t
+
     lib.rascal_basic_systems_read.argtypes = [
         ctypes.c_char_p,
         POINTER(POINTER(rascal_system_t)),
@@ -148,6 +245,18 @@ def setup_functions(lib):
     ]
     lib.rascal_descriptor_gradients.restype = _check_rascal_status_t
 
+    lib.rascal_descriptor_layout.argtypes = [
+        POINTER(rascal_descriptor_t),
+        POINTER(ctypes.c_int)
+    ]
+    lib.rascal_descriptor_layout.restype = _check_rascal_status_t
+
+    lib.rascal_descriptor_set_layout.argtypes = [
+        POINTER(rascal_descriptor_t),
+        ctypes.c_int
+    ]
+    lib.rascal_descriptor_set_layout.restype = _check_rascal_status_t
+
     lib.rascal_descriptor_indexes.argtypes = [
         POINTER(rascal_descriptor_t),
         ctypes.c_int,
@@ -175,6 +284,96 @@ def setup_functions(lib):
     ]
     lib.rascal_descriptor_densify_values.restype = _check_rascal_status_t
 
+    lib.rascal_descriptor_save.argtypes = [
+        POINTER(rascal_descriptor_t),
+        ctypes.c_char_p
+    ]
+    lib.rascal_descriptor_save.restype = _check_rascal_status_t
+
+    lib.rascal_descriptor_load.argtypes = [
+        POINTER(rascal_descriptor_t),
+        ctypes.c_char_p
+    ]
+    lib.rascal_descriptor_load.restype = _check_rascal_status_t
+
+    lib.rascal_descriptor_to_buffer.argtypes = [
+        POINTER(rascal_descriptor_t),
+        POINTER(POINTER(ctypes.c_uint8)),
+        POINTER(c_uintptr_t)
+    ]
+    lib.rascal_descriptor_to_buffer.restype = _check_rascal_status_t
+
+    lib.rascal_descriptor_from_buffer.argtypes = [
+        POINTER(rascal_descriptor_t),
+        POINTER(ctypes.c_uint8),
+        c_uintptr_t
+    ]
+    lib.rascal_descriptor_from_buffer.restype = _check_rascal_status_t
+
+    lib.rascal_descriptor_write_npy.argtypes = [
+        POINTER(rascal_descriptor_t),
+        ctypes.c_char_p
+    ]
+    lib.rascal_descriptor_write_npy.restype = _check_rascal_status_t
+
+    lib.rascal_descriptor_feature_covariance.argtypes = [
+        POINTER(rascal_descriptor_t),
+        ctypes.c_bool,
+        POINTER(ctypes.c_double),
+        c_uintptr_t
+    ]
+    lib.rascal_descriptor_feature_covariance.restype = _check_rascal_status_t
+
+    lib.rascal_descriptor_uncorrelated_features.argtypes = [
+        POINTER(rascal_descriptor_t),
+        ctypes.c_double,
+        POINTER(ctypes.c_bool),
+        c_uintptr_t
+    ]
+    lib.rascal_descriptor_uncorrelated_features.restype = _check_rascal_status_t
+
+    lib.rascal_descriptor_farthest_point_sampling.argtypes = [
+        POINTER(rascal_descriptor_t),
+        c_uintptr_t,
+        ctypes.c_int,
+        ctypes.c_int64,
+        POINTER(c_uintptr_t)
+    ]
+    lib.rascal_descriptor_farthest_point_sampling.restype = _check_rascal_status_t
+
+    lib.rascal_descriptor_random_projection.argtypes = [
+        POINTER(rascal_descriptor_t),
+        c_uintptr_t,
+        ctypes.c_int,
+        ctypes.c_uint64
+    ]
+    lib.rascal_descriptor_random_projection.restype = _check_rascal_status_t
+
+    lib.rascal_descriptor_per_atom_values.argtypes = [
+        POINTER(rascal_descriptor_t),
+        c_uintptr_t,
+        POINTER(ctypes.c_double),
+        c_uintptr_t,
+        c_uintptr_t
+    ]
+    lib.rascal_descriptor_per_atom_values.restype = _check_rascal_status_t
+
+    lib.rascal_descriptor_scatter_gradients.argtypes = [
+        POINTER(rascal_descriptor_t),
+        c_uintptr_t,
+        POINTER(ctypes.c_double),
+        c_uintptr_t,
+        c_uintptr_t,
+        POINTER(ctypes.c_double)
+    ]
+    lib.rascal_descriptor_scatter_gradients.restype = _check_rascal_status_t
+
+    lib.rascal_descriptor_update.argtypes = [
+        POINTER(rascal_descriptor_t),
+        POINTER(rascal_descriptor_t)
+    ]
+    lib.rascal_descriptor_update.restype = _check_rascal_status_t
+
     lib.rascal_calculator.argtypes = [
         ctypes.c_char_p,
         ctypes.c_char_p
@@ -200,6 +399,12 @@ def setup_functions(lib):
     ]
     lib.rascal_calculator_parameters.restype = _check_rascal_status_t
 
+    lib.rascal_calculator_supports_gradients.argtypes = [
+        POINTER(rascal_calculator_t),
+        POINTER(ctypes.c_bool)
+    ]
+    lib.rascal_calculator_supports_gradients.restype = _check_rascal_status_t
+
     lib.rascal_calculator_features_count.argtypes = [
         POINTER(rascal_calculator_t),
         POINTER(c_uintptr_t)
@@ -215,6 +420,45 @@ def setup_functions(lib):
     ]
     lib.rascal_calculator_compute.restype = _check_rascal_status_t
 
+    lib.rascal_calculator_compute_shape.argtypes = [
+        POINTER(rascal_calculator_t),
+        POINTER(rascal_system_t),
+        c_uintptr_t,
+        rascal_calculation_options_t,
+        POINTER(c_uintptr_t),
+        POINTER(c_uintptr_t),
+        POINTER(c_uintptr_t)
+    ]
+    lib.rascal_calculator_compute_shape.restype = _check_rascal_status_t
+
+    lib.rascal_calculator_compute_into.argtypes = [
+        POINTER(rascal_calculator_t),
+        POINTER(rascal_descriptor_t),
+        POINTER(rascal_system_t),
+        c_uintptr_t,
+        rascal_calculation_options_t,
+        POINTER(ctypes.c_double),
+        c_uintptr_t,
+        POINTER(ctypes.c_double),
+        c_uintptr_t
+    ]
+    lib.rascal_calculator_compute_into.restype = _check_rascal_status_t
+
+    lib.rascal_cancellation.argtypes = [
+        POINTER(rascal_calculator_t)
+    ]
+    lib.rascal_cancellation.restype = POINTER(rascal_cancellation_t)
+
+    lib.rascal_cancellation_cancel.argtypes = [
+        POINTER(rascal_cancellation_t)
+    ]
+    lib.rascal_cancellation_cancel.restype = _check_rascal_status_t
+
+    lib.rascal_cancellation_free.argtypes = [
+        POINTER(rascal_cancellation_t)
+    ]
+    lib.rascal_cancellation_free.restype = _check_rascal_status_t
+
     lib.rascal_profiling_clear.argtypes = [
         
     ]
@@ -231,3 +475,33 @@ def setup_functions(lib):
         c_uintptr_t
     ]
     lib.rascal_profiling_get.restype = _check_rascal_status_t
+
+    lib.rascal_version.argtypes = [
+        
+    ]
+    lib.rascal_version.restype = ctypes.c_char_p
+
+    lib.rascal_calculators_list.argtypes = [
+        ctypes.c_char_p,
+        c_uintptr_t
+    ]
+    lib.rascal_calculators_list.restype = _check_rascal_status_t
+
+    lib.rascal_calculators_schema.argtypes = [
+        ctypes.c_char_p,
+        ctypes.c_char_p,
+        c_uintptr_t
+    ]
+    lib.rascal_calculators_schema.restype = _check_rascal_status_t
+
+    lib.rascal_calculators_supports_gradients.argtypes = [
+        ctypes.c_char_p,
+        POINTER(ctypes.c_bool)
+    ]
+    lib.rascal_calculators_supports_gradients.restype = _check_rascal_status_t
+
+    lib.rascal_has_feature.argtypes = [
+        ctypes.c_char_p,
+        POINTER(ctypes.c_bool)
+    ]
+    lib.rascal_has_feature.restype = _check_rascal_status_t
@@ -2,7 +2,12 @@
 import ctypes
 import json
 
-from ._rascaline import c_uintptr_t, rascal_calculation_options_t, rascal_system_t
+from ._rascaline import (
+    c_uintptr_t,
+    rascal_calculation_options_t,
+    rascal_parallelism,
+    rascal_system_t,
+)
 from .clib import _get_library
 from .descriptor import Descriptor, Indexes
 from .status import _check_rascal_pointer
@@ -20,10 +25,34 @@ def _convert_systems(systems):
         )
 
 
-def _options_to_c(use_native_system, samples, features):
+def _options_to_c(
+    use_native_system,
+    samples,
+    features,
+    threads,
+    parallelism,
+    check_finite,
+    deterministic,
+    neighbors_cutoff,
+    lazy_gradients,
+):
     ptr_int32 = ctypes.POINTER(ctypes.c_int32)
     c_options = rascal_calculation_options_t()
     c_options.use_native_system = bool(use_native_system)
+    c_options.threads = 0 if threads is None else int(threads)
+    c_options.check_finite = bool(check_finite)
+    c_options.deterministic = bool(deterministic)
+    c_options.neighbors_cutoff = 0.0 if neighbors_cutoff is None else neighbors_cutoff
+    c_options.lazy_gradients = bool(lazy_gradients)
+
+    if parallelism == "samples":
+        c_options.parallelism = rascal_parallelism.RASCAL_PARALLELISM_SAMPLES.value
+    elif parallelism == "systems":
+        c_options.parallelism = rascal_parallelism.RASCAL_PARALLELISM_SYSTEMS.value
+    else:
+        raise ValueError(
+            f"invalid parallelism '{parallelism}', expected 'samples' or 'systems'"
+        )
 
     # store data to keep alive here
     c_options.__keepalive = {}
@@ -120,6 +149,12 @@ class CalculatorBase:
             )
         )
 
+    def supports_gradients(self):
+        """Can this calculator compute gradients when requested?"""
+        supported = ctypes.c_bool()
+        self._lib.rascal_calculator_supports_gradients(self, supported)
+        return supported.value
+
     def features_count(self):
         """Get the default number of features this calculator will produce.
 
@@ -139,6 +174,12 @@ class CalculatorBase:
         use_native_system=True,
         selected_samples=None,
         selected_features=None,
+        threads=None,
+        parallelism="samples",
+        check_finite=False,
+        deterministic=False,
+        neighbors_cutoff=None,
+        lazy_gradients=False,
     ):
         """Compute features and gradients.
 
@@ -190,6 +231,49 @@ class CalculatorBase:
 
         :type selected_features: Optional[:py:class:`rascaline.descriptor.Indexes`]
 
+        :param threads: defaults to ``None``. Number of threads to use for this
+            calculation. Use ``None`` to use the default global thread pool,
+            which can be configured with the ``RAYON_NUM_THREADS`` environment
+            variable.
+
+        :type threads: Optional[int]
+
+        :param parallelism: defaults to ``"samples"``. Strategy used to run the
+            calculation in parallel. ``"samples"`` parallelizes the calculation
+            within each system, and works best for a few large systems.
+            ``"systems"`` parallelizes the calculation over all atomic
+            environments in all systems at once, and works best for many small
+            systems such as molecules, possibly mixed with larger ones.
+
+        :type parallelism: str
+
+        :param bool check_finite: defaults to ``False``. If ``True``, check that
+            all values and gradients are finite after the calculation, and raise
+            an error pointing to the first NaN or infinite entry otherwise. This
+            is intended for debugging, and makes the calculation slower.
+
+        :param bool deterministic: defaults to ``False``. If ``True``, make sure
+            the results do not depend on the number of threads or the
+            ``parallelism`` strategy, by always running floating point
+            reductions in the same order. This makes the calculation slower.
+
+        :param neighbors_cutoff: defaults to ``None``. If set, compute the
+            neighbor list of each system once with this cutoff, and filter it
+            when a smaller cutoff is requested instead of re-computing a
+            neighbor list for each cutoff. This is useful with a
+            :py:class:`rascaline.Pipeline` of calculators with different
+            cutoffs, and should be set to the largest cutoff. This requires
+            ``use_native_system=True``.
+
+        :type neighbors_cutoff: Optional[float]
+
+        :param bool lazy_gradients: defaults to ``False``. If ``True``, only
+            compute the values during the calculation, and compute the
+            gradients the first time they are accessed with
+            :py:attr:`rascaline.descriptor.Descriptor.gradients` or another
+            function needing them. This requires the calculator to compute
+            gradients.
+
         :return: the ``descriptor`` parameter or the new new descriptor if
                  ``descriptor`` was ``None``.
         """
@@ -204,6 +288,12 @@ class CalculatorBase:
             use_native_system=use_native_system,
             samples=selected_samples,
             features=selected_features,
+            threads=threads,
+            parallelism=parallelism,
+            check_finite=check_finite,
+            deterministic=deterministic,
+            neighbors_cutoff=neighbors_cutoff,
+            lazy_gradients=lazy_gradients,
         )
         self._lib.rascal_calculator_compute(
             self, descriptor, c_systems, c_systems._length_, c_options
@@ -242,6 +332,211 @@ class SortedDistances(CalculatorBase):
         super().__init__("sorted_distances", parameters)
 
 
+class Composition(CalculatorBase):
+    """Chemical composition of the structures.
+
+    With ``per_structure=True``, each structure is represented by the number of
+    atoms of each species it contains, with one sample for each species in the
+    structure. Otherwise, each atom is represented by a single value equal to
+    1, with one sample for each atom. Moving the species from the samples to
+    the features (with :py:func:`rascaline.Descriptor.densify`) gives a vector
+    of species counts for each structure, or a one-hot encoding of the species
+    for each atom.
+
+    For a full description of the hyper-parameters, see the corresponding
+    :ref:`documentation <composition>`.
+    """
+
+    def __init__(self, per_structure):
+        parameters = {"per_structure": per_structure}
+        super().__init__("composition", parameters)
+
+
+class RadialDistribution(CalculatorBase):
+    """Smoothed radial distribution of neighbors around each atom.
+
+    Each neighbor within the spherical ``cutoff`` contributes a normalized
+    gaussian of width ``smearing`` centered on its distance to the central
+    atom, multiplied by the cutoff function. The features are the values of
+    the resulting function on a regular grid of ``n_points`` points, separately
+    for each neighbor species.
+
+    For a full description of the hyper-parameters, see the corresponding
+    :ref:`documentation <radial-distribution>`.
+    """
+
+    def __init__(self, cutoff, n_points, smearing, cutoff_function, gradients):
+        parameters = {
+            "cutoff": cutoff,
+            "n_points": n_points,
+            "smearing": smearing,
+            "cutoff_function": cutoff_function,
+            "gradients": gradients,
+        }
+        super().__init__("radial_distribution", parameters)
+
+
+class AngularDistribution(CalculatorBase):
+    """Smoothed distribution of bond angles around each atom.
+
+    Each pair of distinct neighbors within the spherical ``cutoff`` contributes
+    a normalized gaussian of width ``smearing`` (in radians) centered on the
+    angle they form with the central atom, multiplied by the cutoff function
+    of both distances. The features are the values of the resulting function
+    on a regular grid of ``n_points`` points covering ``[0, π]``, separately
+    for each pair of neighbor species.
+
+    For a full description of the hyper-parameters, see the corresponding
+    :ref:`documentation <angular-distribution>`.
+    """
+
+    def __init__(self, cutoff, n_points, smearing, cutoff_function, gradients):
+        parameters = {
+            "cutoff": cutoff,
+            "n_points": n_points,
+            "smearing": smearing,
+            "cutoff_function": cutoff_function,
+            "gradients": gradients,
+        }
+        super().__init__("angular_distribution", parameters)
+
+
+class BondEnvironment(CalculatorBase):
+    """Smoothed radial distribution of atoms around the middle of each bond.
+
+    The samples are all pairs of atoms closer than ``bond_cutoff``. Each atom
+    within the spherical ``cutoff`` around the middle of the bond contributes a
+    normalized gaussian of width ``smearing`` centered on its distance to the
+    middle of the bond, multiplied by the cutoff function. The resulting
+    function is also multiplied by the cutoff function of the bond length. The
+    features are the values of this function on a regular grid of ``n_points``
+    points covering ``[0, cutoff]``.
+
+    For a full description of the hyper-parameters, see the corresponding
+    :ref:`documentation <bond-environment>`.
+    """
+
+    def __init__(
+        self, bond_cutoff, cutoff, n_points, smearing, cutoff_function, gradients
+    ):
+        parameters = {
+            "bond_cutoff": bond_cutoff,
+            "cutoff": cutoff,
+            "n_points": n_points,
+            "smearing": smearing,
+            "cutoff_function": cutoff_function,
+            "gradients": gradients,
+        }
+        super().__init__("bond_environment", parameters)
+
+
+class VoronoiEnvironment(CalculatorBase):
+    """Features computed from the Voronoi tessellation of the structures.
+
+    For each center and each neighbor species, this calculator computes the
+    number of faces of the Voronoi cell of the center shared with neighbors of
+    this species, the total area of these faces, and the volume of the Voronoi
+    cell of the center. The ``cutoff`` must be large enough to contain all the
+    neighbors sharing a face with each center.
+
+    For a full description of the hyper-parameters, see the corresponding
+    :ref:`documentation <voronoi>`.
+    """
+
+    def __init__(self, cutoff):
+        parameters = {"cutoff": cutoff}
+        super().__init__("voronoi", parameters)
+
+
+class GraphMoments(CalculatorBase):
+    """Walk counts in the graph of atoms connected by their neighbors list.
+
+    Each structure is represented by a weighted graph, where the weight of the
+    edge between two atoms is the cutoff function evaluated at their distance.
+    For each atom and each neighbor species, the features are the weighted
+    number of walks of length 1 to ``max_walk_length`` starting on this atom
+    and ending on an atom of the neighbor species.
+
+    For a full description of the hyper-parameters, see the corresponding
+    :ref:`documentation <graph-moments>`.
+    """
+
+    def __init__(self, cutoff, max_walk_length, cutoff_function, gradients):
+        parameters = {
+            "cutoff": cutoff,
+            "max_walk_length": max_walk_length,
+            "cutoff_function": cutoff_function,
+            "gradients": gradients,
+        }
+        super().__init__("graph_moments", parameters)
+
+
+class Pipeline(CalculatorBase):
+    """Run multiple calculators on the same systems, and concatenate their
+    features in a single descriptor.
+
+    All the ``calculators`` must use the same sample names, and either all or
+    none of them must compute gradients. The features of the resulting
+    descriptor are indexed by ``block`` (the position of the calculator in
+    ``calculators``) and ``feature`` (the index of the feature in the default
+    features of this calculator).
+
+    For a full description of the hyper-parameters, see the corresponding
+    :ref:`documentation <pipeline>`.
+
+    :param calculators: list of calculators to run, as instances of
+        :py:class:`rascaline.calculators.CalculatorBase`
+    """
+
+    def __init__(self, calculators):
+        parameters = {
+            "calculators": [
+                {
+                    "name": calculator.c_name,
+                    "parameters": json.loads(calculator.parameters),
+                }
+                for calculator in calculators
+            ]
+        }
+        super().__init__("pipeline", parameters)
+
+
+class ElectrostaticPotential(CalculatorBase):
+    """Electrostatic potential and electric field created on each atom by all
+    other atoms, computed from per-atom charges.
+
+    For periodic systems, the potential is computed with an Ewald summation;
+    and for non-periodic systems the Coulomb interaction between all pairs of
+    atoms is summed directly. The charges are taken from the per-atom data
+    named ``charges``.
+
+    For a full description of the hyper-parameters, see the corresponding
+    :ref:`documentation <electrostatic-potential>`.
+    """
+
+    def __init__(
+        self,
+        charges,
+        cutoff,
+        k_cutoff,
+        alpha,
+        gradients,
+        electric_field=None,
+    ):
+        parameters = {
+            "charges": charges,
+            "cutoff": cutoff,
+            "k_cutoff": k_cutoff,
+            "alpha": alpha,
+            "gradients": gradients,
+        }
+
+        if electric_field is not None:
+            parameters["electric_field"] = electric_field
+
+        super().__init__("electrostatic_potential", parameters)
+
+
 class SphericalExpansion(CalculatorBase):
     """Spherical expansion of Smooth Overlap of Atomic Positions (SOAP).
 
@@ -272,6 +567,14 @@ class SphericalExpansion(CalculatorBase):
         gradients,
         cutoff_function,
         radial_scaling=None,
+        center_atom_weight=None,
+        neighbor_weights=None,
+        neighbor_channels=None,
+        exclude_self_pairs=None,
+        first_shell_tolerance=None,
+        max_radial_per_angular=None,
+        spherical_harmonics=None,
+        kspace=None,
     ):
         parameters = {
             "cutoff": cutoff,
@@ -286,6 +589,30 @@ class SphericalExpansion(CalculatorBase):
         if radial_scaling is not None:
             parameters["radial_scaling"] = radial_scaling
 
+        if center_atom_weight is not None:
+            parameters["center_atom_weight"] = center_atom_weight
+
+        if neighbor_weights is not None:
+            parameters["neighbor_weights"] = neighbor_weights
+
+        if neighbor_channels is not None:
+            parameters["neighbor_channels"] = neighbor_channels
+
+        if exclude_self_pairs is not None:
+            parameters["exclude_self_pairs"] = exclude_self_pairs
+
+        if first_shell_tolerance is not None:
+            parameters["first_shell_tolerance"] = first_shell_tolerance
+
+        if max_radial_per_angular is not None:
+            parameters["max_radial_per_angular"] = max_radial_per_angular
+
+        if spherical_harmonics is not None:
+            parameters["spherical_harmonics"] = spherical_harmonics
+
+        if kspace is not None:
+            parameters["kspace"] = kspace
+
         super().__init__("spherical_expansion", parameters)
 
 
@@ -316,6 +643,13 @@ class SoapPowerSpectrum(CalculatorBase):
         gradients,
         cutoff_function,
         radial_scaling=None,
+        center_atom_weight=None,
+        neighbor_weights=None,
+        neighbor_channels=None,
+        exclude_self_pairs=None,
+        first_shell_tolerance=None,
+        max_radial_per_angular=None,
+        alchemical_compression=None,
     ):
         parameters = {
             "cutoff": cutoff,
@@ -330,4 +664,82 @@ class SoapPowerSpectrum(CalculatorBase):
         if radial_scaling is not None:
             parameters["radial_scaling"] = radial_scaling
 
+        if center_atom_weight is not None:
+            parameters["center_atom_weight"] = center_atom_weight
+
+        if neighbor_weights is not None:
+            parameters["neighbor_weights"] = neighbor_weights
+
+        if neighbor_channels is not None:
+            parameters["neighbor_channels"] = neighbor_channels
+
+        if exclude_self_pairs is not None:
+            parameters["exclude_self_pairs"] = exclude_self_pairs
+
+        if first_shell_tolerance is not None:
+            parameters["first_shell_tolerance"] = first_shell_tolerance
+
+        if max_radial_per_angular is not None:
+            parameters["max_radial_per_angular"] = max_radial_per_angular
+
+        if alchemical_compression is not None:
+            parameters["alchemical_compression"] = alchemical_compression
+
         super().__init__("soap_power_spectrum", parameters)
+
+
+class LambdaSoap(CalculatorBase):
+    """Equivariant lambda-SOAP features.
+
+    lambda-SOAP combines two :py:class:`SphericalExpansion` coefficients with
+    angular channels ``l1`` and ``l2`` using Clebsch-Gordan coefficients,
+    creating per-atom features transforming like spherical harmonics of degree
+    ``lambda`` under rotations. The ``mu`` feature index corresponds to the
+    ``m`` index of real spherical harmonics. ``lambda=1`` gives vectors (with
+    ``mu = 1, -1, 0`` corresponding to ``x``, ``y`` and ``z``), and
+    ``lambda=2`` gives symmetric traceless tensors. These features can be used
+    to learn vectorial or tensorial properties such as dipole moments or
+    polarizabilities directly.
+
+    See `this paper <https://doi.org/10.1103/PhysRevLett.120.036002>`_ for more
+    information on the lambda-SOAP representation.
+
+    For a full description of the hyper-parameters, see the corresponding
+    :ref:`documentation <lambda-soap>`.
+    """
+
+    def __init__(
+        self,
+        cutoff,
+        max_radial,
+        max_angular,
+        atomic_gaussian_width,
+        radial_basis,
+        gradients,
+        cutoff_function,
+        lambda_,
+        parity=None,
+        radial_scaling=None,
+        center_atom_weight=None,
+    ):
+        parameters = {
+            "cutoff": cutoff,
+            "max_radial": max_radial,
+            "max_angular": max_angular,
+            "atomic_gaussian_width": atomic_gaussian_width,
+            "radial_basis": radial_basis,
+            "gradients": gradients,
+            "cutoff_function": cutoff_function,
+            "lambda": lambda_,
+        }
+
+        if parity is not None:
+            parameters["parity"] = parity
+
+        if radial_scaling is not None:
+            parameters["radial_scaling"] = radial_scaling
+
+        if center_atom_weight is not None:
+            parameters["center_atom_weight"] = center_atom_weight
+
+        super().__init__("lambda_soap", parameters)
@@ -1,5 +1,15 @@
 # -*- coding: utf-8 -*-
-from ctypes import ARRAY, POINTER, c_char_p, c_double, c_int32, pointer
+from ctypes import (
+    ARRAY,
+    POINTER,
+    c_bool,
+    c_char_p,
+    c_double,
+    c_int,
+    c_int32,
+    c_uint8,
+    pointer,
+)
 
 import numpy as np
 
@@ -8,6 +18,9 @@ from ._rascaline import (
     rascal_densified_position_t,
     rascal_indexes_kind,
     rascal_indexes_t,
+    rascal_memory_layout,
+    rascal_random_projection,
+    rascal_sample_distance,
 )
 from .clib import _get_library
 from .status import _check_rascal_pointer
@@ -76,30 +89,62 @@ class Descriptor:
             self._lib.rascal_descriptor_free(self)
         self._as_parameter_ = 0
 
+    @property
+    def layout(self):
+        """Memory layout of the :py:attr:`Descriptor.values` and
+        :py:attr:`Descriptor.gradients` arrays, either ``"row-major"`` (the
+        default, all the features of a sample are contiguous in memory) or
+        ``"feature-major"`` (all the samples of a feature are contiguous in
+        memory).
+
+        Setting the layout converts the existing arrays, and the layout is kept
+        for all subsequent calculations using this descriptor.
+        """
+        layout = c_int()
+        self._lib.rascal_descriptor_layout(self, layout)
+        if layout.value == rascal_memory_layout.RASCAL_MEMORY_LAYOUT_FEATURE_MAJOR.value:
+            return "feature-major"
+        else:
+            return "row-major"
+
+    @layout.setter
+    def layout(self, layout):
+        if layout == "row-major":
+            c_layout = rascal_memory_layout.RASCAL_MEMORY_LAYOUT_ROW_MAJOR
+        elif layout == "feature-major":
+            c_layout = rascal_memory_layout.RASCAL_MEMORY_LAYOUT_FEATURE_MAJOR
+        else:
+            raise ValueError(
+                f"unknown layout '{layout}', expected 'row-major' or 'feature-major'"
+            )
+
+        self._lib.rascal_descriptor_set_layout(self, c_layout.value)
+
     @property
     def values(self):
         """Values stored in this descriptor by a calculator.
 
         The values are stored as a **read only** 2D numpy ndarray with
-        ``dtype=np.float64``.
+        ``dtype=np.float64``, using the memory layout given by
+        :py:attr:`Descriptor.layout`.
         """
         samples = c_uintptr_t()
         features = c_uintptr_t()
         data = POINTER(c_double)()
         self._lib.rascal_descriptor_values(self, data, samples, features)
 
-        return _ptr_to_ndarray(
-            ptr=data,
-            shape=(samples.value, features.value),
-            dtype=np.float64,
-        )
+        return self._array_with_layout(data, samples.value, features.value)
 
     @property
     def gradients(self):
         """Gradients stored in this descriptor by a calculator.
 
         The gradients are stored as a **read only** 2D numpy ndarray
-        with ``dtype=np.float64``, or ``None`` if no value was stored.
+        with ``dtype=np.float64``, or ``None`` if no value was stored. The
+        array uses the memory layout given by :py:attr:`Descriptor.layout`.
+
+        If the descriptor was computed with ``lazy_gradients=True``, the
+        gradients are computed the first time this property is accessed.
         """
         samples = c_uintptr_t()
         features = c_uintptr_t()
@@ -109,11 +154,22 @@ class Descriptor:
         if not data:
             return None
 
-        return _ptr_to_ndarray(
-            ptr=data,
-            shape=(samples.value, features.value),
-            dtype=np.float64,
-        )
+        return self._array_with_layout(data, samples.value, features.value)
+
+    def _array_with_layout(self, data, samples, features):
+        if self.layout == "feature-major":
+            array = _ptr_to_ndarray(
+                ptr=data,
+                shape=(features, samples),
+                dtype=np.float64,
+            )
+            return array.T
+        else:
+            return _ptr_to_ndarray(
+                ptr=data,
+                shape=(samples, features),
+                dtype=np.float64,
+            )
 
     def _indexes(self, kind):
         indexes = rascal_indexes_t()
@@ -164,7 +220,12 @@ class Descriptor:
         The data is stored as a :py:class:`rascaline.descriptor.Indexes` wrapping a
         **read only** 2D numpy ndarray with ``dtype=np.float64``. Each column of
         the array is named, and the names are available in ``Indexes.names``.
+
+        If the descriptor was computed with ``lazy_gradients=True``, accessing
+        ``gradients_samples`` computes the gradients.
         """
+        # compute lazy gradients (if any) to get the corresponding samples
+        self.gradients
         return self._indexes(rascal_indexes_kind.RASCAL_INDEXES_GRADIENT_SAMPLES)
 
     def densify(self, variables, requested=None):
@@ -289,10 +350,187 @@ class Descriptor:
             shape=(densified_positions_size.value,),
         ).copy()
 
-        self._lib.free(densified_positions)
+        self._lib.rascal_free(densified_positions)
 
         return result
 
+    def feature_covariance(self, centered=False):
+        """Compute the Gram matrix ``X^T X`` of the :py:attr:`values` ``X`` of
+        this descriptor, summing over all samples. This is faster than doing
+        the same operation with numpy for large descriptors, since the
+        computation is done in parallel over blocks of samples.
+
+        :param centered: if ``True``, the mean of each feature over the samples
+            is subtracted from the values first, as the first step of a
+            principal component analysis.
+        :type centered: bool
+
+        :return: a 2D numpy ndarray of size ``n_features x n_features``
+        """
+        n_features = self.values.shape[1]
+        covariance = np.zeros((n_features, n_features), dtype=np.float64)
+        self._lib.rascal_descriptor_feature_covariance(
+            self,
+            centered,
+            covariance.ctypes.data_as(POINTER(c_double)),
+            n_features,
+        )
+        return covariance
+
+    def uncorrelated_features(self, threshold):
+        """Select a subset of the features of this descriptor such that the
+        absolute value of the Pearson correlation between any pair of selected
+        features (over all samples) is at most ``threshold``. This can be used
+        to remove redundant features before using kernel methods.
+
+        The features are considered in order, and a feature is kept if it is
+        not correlated above ``threshold`` with any of the previously kept
+        features. Features with the same value for all samples are always
+        removed.
+
+        :param threshold: maximal absolute correlation between selected
+            features, between 0 and 1
+        :type threshold: float
+
+        :return: the selected features, as an :py:class:`Indexes`
+        """
+        n_features = self.values.shape[1]
+        selected = np.zeros(n_features, dtype=np.bool_)
+        self._lib.rascal_descriptor_uncorrelated_features(
+            self,
+            threshold,
+            selected.ctypes.data_as(POINTER(c_bool)),
+            n_features,
+        )
+        return self.features[selected]
+
+    def farthest_point_sampling(self, count, distance="euclidean", initial=None):
+        """Select ``count`` samples of this descriptor using farthest point
+        sampling over the rows of the :py:attr:`values`. This can be used to
+        pick the sparse points of a kernel model without copying the full
+        values array.
+
+        :param count: number of samples to select
+        :type count: int
+        :param distance: distance used to compare samples, either
+            ``"euclidean"`` or ``"cosine"``
+        :type distance: str
+        :param initial: index of the first sample to select. If ``None``, the
+            selection starts from the sample farthest from the mean of all
+            samples.
+        :type initial: Optional[int]
+
+        :return: the selected samples, as an :py:class:`Indexes` in the order
+            they were selected
+        """
+        if distance == "euclidean":
+            c_distance = rascal_sample_distance.RASCAL_SAMPLE_DISTANCE_EUCLIDEAN
+        elif distance == "cosine":
+            c_distance = rascal_sample_distance.RASCAL_SAMPLE_DISTANCE_COSINE
+        else:
+            raise ValueError(
+                f"unknown distance '{distance}', expected 'euclidean' or 'cosine'"
+            )
+
+        if initial is None:
+            initial = -1
+
+        selected = np.zeros(count, dtype=c_uintptr_t)
+        self._lib.rascal_descriptor_farthest_point_sampling(
+            self,
+            count,
+            c_distance.value,
+            initial,
+            selected.ctypes.data_as(POINTER(c_uintptr_t)),
+        )
+        return self.samples[selected]
+
+    def random_projection(self, width, projection="gaussian", seed=0):
+        """Compress the features of this descriptor to ``width`` new features,
+        by multiplying the values and gradients with a fixed random matrix.
+        This approximately preserves distances and dot products between
+        samples, and gives a cheap compressed representation for descriptors
+        with a very large number of features.
+
+        The projection matrix is generated from ``seed``, so descriptors
+        projected with the same parameters can be compared with one another.
+        After the projection, the features contain a single ``projection``
+        variable.
+
+        :param width: number of features after the projection
+        :type width: int
+        :param projection: kind of random matrix to use, either ``"gaussian"``
+            (dense matrix with normally distributed entries) or ``"sparse"``
+            (two thirds of the entries are zero)
+        :type projection: str
+        :param seed: seed of the random number generator used to create the
+            projection matrix
+        :type seed: int
+        """
+        if projection == "gaussian":
+            c_projection = rascal_random_projection.RASCAL_RANDOM_PROJECTION_GAUSSIAN
+        elif projection == "sparse":
+            c_projection = rascal_random_projection.RASCAL_RANDOM_PROJECTION_SPARSE
+        else:
+            raise ValueError(
+                f"unknown projection '{projection}', expected 'gaussian' or 'sparse'"
+            )
+
+        self._lib.rascal_descriptor_random_projection(
+            self, width, c_projection.value, seed
+        )
+
+    def save(self, path):
+        """Save this descriptor to the file at ``path``, overwriting any
+        existing file. The descriptor can be loaded back with
+        :py:func:`Descriptor.load`.
+
+        :param path: path of the file where to save the descriptor
+        :type path: str
+        """
+        self._lib.rascal_descriptor_save(self, str(path).encode("utf8"))
+
+    @staticmethod
+    def load(path):
+        """Load a descriptor previously saved with :py:func:`Descriptor.save`
+        from the file at ``path``.
+
+        :param path: path of the file containing the descriptor
+        :type path: str
+        """
+        descriptor = Descriptor()
+        descriptor._lib.rascal_descriptor_load(descriptor, str(path).encode("utf8"))
+        return descriptor
+
+    def to_bytes(self):
+        """Serialize this descriptor to a binary buffer in memory, which can be
+        loaded back with :py:func:`Descriptor.from_bytes`. This can be used to
+        send descriptors between processes without going through files.
+
+        :returns: the serialized descriptor as ``bytes``
+        """
+        buffer = POINTER(c_uint8)()
+        buffer_size = c_uintptr_t()
+        self._lib.rascal_descriptor_to_buffer(self, buffer, buffer_size)
+
+        try:
+            return bytes(buffer[: buffer_size.value])
+        finally:
+            self._lib.rascal_free(buffer)
+
+    @staticmethod
+    def from_bytes(data):
+        """Load a descriptor previously serialized with
+        :py:func:`Descriptor.to_bytes`.
+
+        :param data: the serialized descriptor
+        :type data: bytes
+        """
+        buffer = (c_uint8 * len(data)).from_buffer_copy(data)
+        descriptor = Descriptor()
+        descriptor._lib.rascal_descriptor_from_buffer(descriptor, buffer, len(data))
+        return descriptor
+
 
 def _ptr_to_ndarray(ptr, shape, dtype):
     assert len(shape) == 2
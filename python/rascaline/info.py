@@ -0,0 +1,50 @@
+# -*- coding: utf-8 -*-
+import ctypes
+import json
+
+from .clib import _get_library
+from .utils import _call_with_growing_buffer
+
+
+def library_version():
+    """Get the version of the rascaline shared library, as a string."""
+    return _get_library().rascal_version().decode("utf8")
+
+
+def calculators_list():
+    """Get the names of all the calculators available in rascaline."""
+    lib = _get_library()
+    names = _call_with_growing_buffer(
+        lambda buffer, bufflen: lib.rascal_calculators_list(buffer, bufflen)
+    )
+    return json.loads(names)
+
+
+def calculator_schema(name):
+    """Get the JSON schema describing the parameters of the calculator with
+    the given ``name``, as a Python dictionary."""
+    lib = _get_library()
+    schema = _call_with_growing_buffer(
+        lambda buffer, bufflen: lib.rascal_calculators_schema(
+            name.encode("utf8"), buffer, bufflen
+        )
+    )
+    return json.loads(schema)
+
+
+def calculator_supports_gradients(name):
+    """Check if the calculator with the given ``name`` can compute gradients,
+    without having to create a calculator first."""
+    supported = ctypes.c_bool()
+    _get_library().rascal_calculators_supports_gradients(
+        name.encode("utf8"), supported
+    )
+    return supported.value
+
+
+def has_feature(feature):
+    """Check if the optional ``feature`` (e.g. ``"chemfiles"``) was enabled
+    when compiling the rascaline shared library."""
+    enabled = ctypes.c_bool()
+    _get_library().rascal_has_feature(feature.encode("utf8"), enabled)
+    return enabled.value
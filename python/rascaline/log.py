@@ -43,6 +43,19 @@ def set_logging_callback(function):
     _set_logging_callback_impl(library, function)
 
 
+def set_logging_level(level):
+    """Only call the logging callback for events at ``level`` or above.
+
+    ``level`` should be one of the ``RASCAL_LOG_LEVEL_XXX`` constants, from
+    ``RASCAL_LOG_LEVEL_ERROR`` (least verbose) to ``RASCAL_LOG_LEVEL_TRACE``
+    (most verbose).
+    """
+    from .clib import _get_library
+
+    library = _get_library()
+    library.rascal_set_logging_level(level)
+
+
 def _set_logging_callback_impl(library, function):
     """Implementation of :py:func:`set_logging_callback`
 
@@ -1,4 +1,6 @@
 # -*- coding: utf-8 -*-
+import json
+
 from .clib import _get_library
 from .utils import _call_with_growing_buffer
 
@@ -56,3 +58,18 @@ class Profiler:
                 "short_table".encode("utf8"), b, s
             )
         )
+
+    def call_tree(self):
+        """
+        Get current profiling data as a call tree. The tree is represented as a
+        list of root nodes, each node being a dictionary containing the function
+        ``name``, the total ``elapsed`` time in seconds, the ``total_calls`` to
+        this function, the number of ``calls`` from the parent node, and the list
+        of ``children`` nodes.
+        """
+        data = _call_with_growing_buffer(
+            lambda b, s: self._lib.rascal_profiling_get(
+                "call_tree".encode("utf8"), b, s
+            )
+        )
+        return json.loads(data)["roots"]
@@ -4,7 +4,12 @@ from ctypes import POINTER, c_double, c_void_p, pointer
 
 import numpy as np
 
-from .._rascaline import c_uintptr_t, rascal_pair_t, rascal_system_t
+from .._rascaline import (
+    RASCAL_SYSTEM_VERSION,
+    c_uintptr_t,
+    rascal_pair_t,
+    rascal_system_t,
+)
 from ..status import _save_exception
 
 
@@ -47,6 +52,8 @@ class SystemBase:
         struct = rascal_system_t()
         self._keepalive["c_struct"] = struct
 
+        struct.version = RASCAL_SYSTEM_VERSION
+
         # user_data is a pointer to the PyObject `self`
         struct.user_data = ctypes.cast(pointer(ctypes.py_object(self)), c_void_p)
 
@@ -141,7 +148,7 @@ class SystemBase:
             """
             self = get_self(user_data)
 
-            pairs = np.array(self.pairs(), dtype=rascal_pair_t)
+            pairs = _pairs_to_array(self.pairs())
 
             count[0] = c_uintptr_t(len(pairs))
             data[0] = pairs.ctypes.data
@@ -157,7 +164,7 @@ class SystemBase:
             """
             self = get_self(user_data)
 
-            pairs = np.array(self.pairs_containing(center), dtype=rascal_pair_t)
+            pairs = _pairs_to_array(self.pairs_containing(center))
 
             count[0] = c_uintptr_t(len(pairs))
             data[0] = pairs.ctypes.data
@@ -167,6 +174,27 @@ class SystemBase:
             rascal_system_pairs_containing
         )
 
+        @catch_exceptions
+        def rascal_system_data(user_data, name, data):
+            """
+            Implementation of ``rascal_system_t::data`` using
+            :py:func:`SystemBase.data`.
+            """
+            self = get_self(user_data)
+
+            values = self.data(name.decode("utf8"))
+            if values is None:
+                data[0] = None
+                return
+
+            values = np.array(values, dtype=c_double)
+            assert values.shape == (self.size(),)
+
+            data[0] = values.ctypes.data_as(POINTER(c_double))
+            self._keepalive["data"] = values
+
+        struct.data = struct.data.__class__(rascal_system_data)
+
         return struct
 
     def size(self):
@@ -214,9 +242,11 @@ class SystemBase:
         computed by the last call :py:func:`SystemBase.compute_neighbors`
 
         Get all neighbor pairs in this system as a list of tuples ``(int, int,
-        float, (float, float, float))`` containing the indexes of the first and
-        second atom in the pair, the distance between the atoms, and the
-        wrapped between them. Alternatively, this function can return a numpy
+        float, (float, float, float), (int, int, int))`` containing the indexes
+        of the first and second atom in the pair, the distance between the
+        atoms, the wrapped vector between them, and the number of cell vectors
+        used to wrap this vector. The cell shift can be omitted for
+        non-periodic systems. Alternatively, this function can return a numpy
         array with ``dtype=rascal_pair_t``.
 
         The list of pair should only contain each pair once (and not twice as
@@ -239,3 +269,24 @@ class SystemBase:
         returned by ``pairs_containing(i)`` and ``pairs_containing(j)``.
         """
         raise NotImplementedError("System.pairs_containing method is not implemented")
+
+    def data(self, name):
+        """Get the per-atom data with the given ``name`` in this system.
+
+        This should return ``None`` if the system does not contain such data,
+        or a list or 1D numpy array containing one value for each atom in the
+        system (for example partial charges).
+
+        This function is optional, and the default implementation does not
+        provide any data.
+        """
+        return None
+
+
+def _pairs_to_array(pairs):
+    """Convert the pairs returned by ``SystemBase.pairs`` to a numpy array"""
+    if isinstance(pairs, np.ndarray):
+        return np.asarray(pairs, dtype=rascal_pair_t)
+
+    pairs = [pair if len(pair) == 5 else (*pair, (0, 0, 0)) for pair in pairs]
+    return np.array(pairs, dtype=rascal_pair_t)
@@ -103,7 +103,7 @@ def parse(file):
 def c_type_name(name):
     if name.startswith("rascal_"):
         # enums are represented as int
-        if name == "rascal_indexes_kind":
+        if name in ["rascal_indexes_kind", "rascal_parallelism"]:
             return "ctypes.c_int"
         else:
             return name
@@ -3,7 +3,7 @@ import unittest
 
 import numpy as np
 
-from rascaline import Indexes, RascalError, SortedDistances
+from rascaline import Composition, Indexes, RascalError, SortedDistances
 from rascaline.calculators import DummyCalculator
 
 from test_systems import TestSystem
@@ -36,9 +36,17 @@ class TestDummyCalculator(unittest.TestCase):
             """{"cutoff": 3.2, "delta": 12, "name": "foo", "gradients": true}""",
         )
 
+    def test_supports_gradients(self):
+        calculator = DummyCalculator(cutoff=3.2, delta=12, name="foo", gradients=False)
+        self.assertTrue(calculator.supports_gradients())
+
+        calculator = SortedDistances(cutoff=3.5, max_neighbors=4)
+        self.assertFalse(calculator.supports_gradients())
+
     def test_bad_parameters(self):
         message = (
-            'json error: invalid type: string "12", expected isize at line 1 column 29'
+            "invalid parameters for calculator 'dummy_calculator': invalid "
+            "parameter: expected an integer for 'delta', got \"12\""
         )
 
         with self.assertRaisesRegex(Exception, message):
@@ -271,6 +279,28 @@ class TestDummyCalculator(unittest.TestCase):
             "of the features of this calculator",
         )
 
+    def test_check_finite(self):
+        class OverflowSystem(TestSystem):
+            def positions(self):
+                return [[0, 0, 0], [0, 0, 1], [0, 0, 2], [0, 1e308, 1e308]]
+
+        system = OverflowSystem()
+        calculator = DummyCalculator(cutoff=3.2, delta=2, name="", gradients=False)
+        descriptor = calculator.compute(system, use_native_system=False)
+        # atom 3 contributes to the values of both atoms 2 and 3
+        self.assertTrue(np.isinf(descriptor.values[2, 1]))
+        self.assertTrue(np.isinf(descriptor.values[3, 1]))
+
+        with self.assertRaises(RascalError) as cm:
+            calculator.compute(system, use_native_system=False, check_finite=True)
+
+        self.assertEqual(
+            str(cm.exception),
+            "non-finite data in the output of calculator 'dummy_calculator': "
+            "internal error: got inf in values for sample (structure: 0, "
+            "center: 2) and feature (index_delta: 0, x_y_z: 1)",
+        )
+
     def test_features_count(self):
         calculator = DummyCalculator(cutoff=3.2, delta=2, name="", gradients=True)
         self.assertEqual(calculator.features_count(), 2)
@@ -287,3 +317,14 @@ class TestSortedDistances(unittest.TestCase):
         self.assertEqual(
             calculator.parameters, """{"cutoff": 3.5, "max_neighbors": 12}"""
         )
+
+
+class TestComposition(unittest.TestCase):
+    def test_name(self):
+        calculator = Composition(per_structure=True)
+        self.assertEqual(calculator.name, "composition")
+        self.assertEqual(calculator.c_name, "composition")
+
+    def test_parameters(self):
+        calculator = Composition(per_structure=False)
+        self.assertEqual(calculator.parameters, """{"per_structure": false}""")
@@ -1,4 +1,6 @@
 # -*- coding: utf-8 -*-
+import os
+import tempfile
 import unittest
 
 import numpy as np
@@ -74,6 +76,38 @@ class TestDummyDescriptor(unittest.TestCase):
         self.assertTrue(np.all(gradients[0] == (3, 4)))
         self.assertTrue(np.all(gradients[1] == (0, 1)))
 
+    def test_layout(self):
+        system = TestSystem()
+        calculator = DummyCalculator(cutoff=3.2, delta=12, name="", gradients=True)
+
+        descriptor = Descriptor()
+        self.assertEqual(descriptor.layout, "row-major")
+        descriptor.layout = "feature-major"
+        self.assertEqual(descriptor.layout, "feature-major")
+
+        calculator.compute(system, descriptor, use_native_system=False)
+        values = descriptor.values
+        self.assertEqual(values.shape, (4, 2))
+        self.assertTrue(values.flags.f_contiguous)
+        self.assertTrue(np.all(values[0] == (12, 1)))
+        self.assertTrue(np.all(values[3] == (15, 5)))
+
+        gradients = descriptor.gradients
+        self.assertEqual(gradients.shape, (18, 2))
+        self.assertTrue(gradients.flags.f_contiguous)
+        self.assertTrue(np.all(gradients[0] == (0, 1)))
+
+        descriptor.layout = "row-major"
+        self.assertTrue(descriptor.values.flags.c_contiguous)
+        self.assertTrue(np.all(descriptor.values[3] == (15, 5)))
+
+        with self.assertRaises(ValueError) as cm:
+            descriptor.layout = "diagonal"
+        self.assertEqual(
+            str(cm.exception),
+            "unknown layout 'diagonal', expected 'row-major' or 'feature-major'",
+        )
+
     def test_samples(self):
         system = TestSystem()
         calculator = DummyCalculator(cutoff=3.2, delta=12, name="", gradients=False)
@@ -167,6 +201,99 @@ class TestDummyDescriptor(unittest.TestCase):
         self.assertTrue(np.all(features[0] == [1, 0]))
         self.assertTrue(np.all(features[1] == [0, 1]))
 
+    def test_save_and_load(self):
+        system = TestSystem()
+        calculator = DummyCalculator(cutoff=3.2, delta=12, name="", gradients=True)
+        descriptor = calculator.compute(system, use_native_system=False)
+
+        with tempfile.TemporaryDirectory() as directory:
+            path = os.path.join(directory, "descriptor.json")
+            descriptor.save(path)
+            loaded = Descriptor.load(path)
+
+        self.assertTrue(np.all(loaded.values == descriptor.values))
+        self.assertTrue(np.all(loaded.gradients == descriptor.gradients))
+        self.assertEqual(loaded.samples.names, descriptor.samples.names)
+        self.assertTrue(np.all(loaded.samples == descriptor.samples))
+        self.assertTrue(np.all(loaded.features == descriptor.features))
+
+    def test_to_and_from_bytes(self):
+        system = TestSystem()
+        calculator = DummyCalculator(cutoff=3.2, delta=12, name="", gradients=True)
+        descriptor = calculator.compute(system, use_native_system=False)
+
+        data = descriptor.to_bytes()
+        self.assertTrue(data.startswith(b"RASCALDS"))
+
+        loaded = Descriptor.from_bytes(data)
+        self.assertTrue(np.all(loaded.values == descriptor.values))
+        self.assertTrue(np.all(loaded.gradients == descriptor.gradients))
+        self.assertTrue(np.all(loaded.samples == descriptor.samples))
+        self.assertTrue(np.all(loaded.features == descriptor.features))
+
+    def test_feature_covariance(self):
+        system = TestSystem()
+        calculator = DummyCalculator(cutoff=3.2, delta=12, name="", gradients=False)
+        descriptor = calculator.compute(system, use_native_system=False)
+
+        values = descriptor.values
+        covariance = descriptor.feature_covariance()
+        self.assertTrue(np.allclose(covariance, values.T @ values))
+
+        centered = values - values.mean(axis=0)
+        covariance = descriptor.feature_covariance(centered=True)
+        self.assertTrue(np.allclose(covariance, centered.T @ centered))
+
+    def test_uncorrelated_features(self):
+        system = TestSystem()
+        calculator = DummyCalculator(cutoff=3.2, delta=12, name="", gradients=False)
+        descriptor = calculator.compute(system, use_native_system=False)
+
+        selected = descriptor.uncorrelated_features(1.0)
+        self.assertEqual(selected.names, descriptor.features.names)
+        self.assertLessEqual(len(selected), 2)
+
+        with self.assertRaises(RascalError):
+            descriptor.uncorrelated_features(2.0)
+
+    def test_farthest_point_sampling(self):
+        system = TestSystem()
+        calculator = DummyCalculator(cutoff=3.2, delta=12, name="", gradients=False)
+        descriptor = calculator.compute(system, use_native_system=False)
+
+        selected = descriptor.farthest_point_sampling(3, initial=1)
+        self.assertEqual(selected.names, descriptor.samples.names)
+        self.assertEqual(len(selected), 3)
+        self.assertEqual(selected[0], descriptor.samples[1])
+        self.assertEqual(len(np.unique(selected)), 3)
+
+        selected = descriptor.farthest_point_sampling(2, distance="cosine")
+        self.assertEqual(len(selected), 2)
+
+        with self.assertRaises(ValueError):
+            descriptor.farthest_point_sampling(2, distance="manhattan")
+
+    def test_random_projection(self):
+        system = TestSystem()
+        calculator = DummyCalculator(cutoff=3.2, delta=12, name="", gradients=True)
+        descriptor = calculator.compute(system, use_native_system=False)
+        n_gradients = descriptor.gradients.shape[0]
+
+        descriptor.random_projection(5, seed=3)
+        self.assertEqual(descriptor.features.names, ("projection",))
+        self.assertEqual(descriptor.values.shape, (4, 5))
+        self.assertEqual(descriptor.gradients.shape, (n_gradients, 5))
+
+        other = calculator.compute(system, use_native_system=False)
+        other.random_projection(5, seed=3)
+        self.assertTrue(np.all(other.values == descriptor.values))
+
+        descriptor.random_projection(3, projection="sparse")
+        self.assertEqual(descriptor.values.shape, (4, 3))
+
+        with self.assertRaises(ValueError):
+            descriptor.random_projection(2, projection="uniform")
+
     def test_densify(self):
         def compute_descriptor():
             system = TestSystem()
@@ -16,3 +16,26 @@ class TestCMakePrefixPath(unittest.TestCase):
         self.assertTrue(
             os.path.isfile(os.path.join(cmake, "rascaline-config-version.cmake"))
         )
+
+
+class TestLibraryInfo(unittest.TestCase):
+    def test_library_version(self):
+        version = rascaline.library_version()
+        self.assertEqual(len(version.split(".")), 3)
+
+    def test_calculators_list(self):
+        names = rascaline.calculators_list()
+        self.assertIn("spherical_expansion", names)
+        self.assertIn("soap_power_spectrum", names)
+        self.assertEqual(names, sorted(names))
+
+    def test_calculator_metadata(self):
+        schema = rascaline.calculator_schema("sorted_distances")
+        self.assertIn("max_neighbors", schema["properties"])
+
+        self.assertTrue(rascaline.calculator_supports_gradients("dummy_calculator"))
+        self.assertFalse(rascaline.calculator_supports_gradients("sorted_distances"))
+
+    def test_has_feature(self):
+        self.assertIsInstance(rascaline.has_feature("chemfiles"), bool)
+        self.assertFalse(rascaline.has_feature("not-a-feature"))
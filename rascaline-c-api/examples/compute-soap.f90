@@ -0,0 +1,52 @@
+program compute_soap
+    use, intrinsic :: iso_c_binding
+    use rascaline
+    implicit none
+
+    type(rascal_calculator) :: calculator
+    type(rascal_descriptor) :: descriptor
+    type(rascal_system_t), pointer :: systems(:)
+    real(c_double), pointer :: values(:, :)
+    character(len=4096) :: path
+    character(len=*), parameter :: parameters = '{ &
+        &"cutoff": 5.0, &
+        &"max_radial": 6, &
+        &"max_angular": 4, &
+        &"atomic_gaussian_width": 0.3, &
+        &"gradients": false, &
+        &"radial_basis": {"Gto": {}}, &
+        &"cutoff_function": {"ShiftedCosine": {"width": 0.5}} &
+    &}'
+
+    ! load systems from command line arguments
+    if (command_argument_count() < 1) then
+        print '(a)', "error: expected a command line argument"
+        error stop 1
+    end if
+    call get_command_argument(1, path)
+    call rascal_basic_systems_read(trim(path), systems)
+
+    ! create the calculator with its name and parameters
+    call calculator%init("soap_power_spectrum", parameters)
+
+    ! create a new empty descriptor
+    call descriptor%init()
+
+    ! run the calculation, using the default options
+    call calculator%compute(descriptor, systems)
+
+    ! Transform the descriptor to dense representation,
+    ! with one sample for each atom-centered environment
+    call descriptor%densify(["species_neighbor_1"])
+
+    ! extract values from the descriptor. The array is seen as transposed from
+    ! Fortran, with shape (n_features, n_samples)
+    values => descriptor%values()
+
+    ! you can now use `values` as the input of a machine learning algorithm
+    print '(a, i0, a, i0)', "the value array shape is ", size(values, 2), " x ", size(values, 1)
+
+    call descriptor%free()
+    call calculator%free()
+    call rascal_basic_systems_free(systems)
+end program
@@ -0,0 +1,577 @@
+! Fortran interface to rascaline, built on top of the C API using the
+! `iso_c_binding` intrinsic module.
+!
+! This module provides two layers: direct bindings to the functions in
+! `rascaline.h` (prefixed with `rascal_c_`), and Fortran-friendly derived types
+! (`rascal_calculator`, `rascal_descriptor`) handling string conversion and
+! giving access to the descriptor data as Fortran arrays.
+!
+! All arrays shared with rascaline use the C (row-major) layout, which means
+! they appear transposed in Fortran. For example, the values array of a
+! descriptor is seen as `values(n_features, n_samples)` from Fortran, and atomic
+! positions should be given as `positions(3, n_atoms)`. Descriptors can use a
+! feature-major layout instead (see `rascal_descriptor%set_layout`), in which
+! case the values are seen as `values(n_samples, n_features)`.
+!
+! Functions which can fail take an optional `status` argument. If `status` is
+! present, it is set to the status code of the operation, and the error message
+! can be retrieved with `rascal_last_error()`. If `status` is not present, the
+! error message is printed and the program is stopped.
+module rascaline
+    use, intrinsic :: iso_c_binding
+    use, intrinsic :: iso_fortran_env, only: error_unit
+    implicit none
+    private
+
+    ! Status codes, see `rascal_status_t` in the C API
+    integer(c_int32_t), parameter, public :: RASCAL_SUCCESS = 0
+    integer(c_int32_t), parameter, public :: RASCAL_INVALID_PARAMETER_ERROR = 1
+    integer(c_int32_t), parameter, public :: RASCAL_JSON_ERROR = 2
+    integer(c_int32_t), parameter, public :: RASCAL_UTF8_ERROR = 3
+    integer(c_int32_t), parameter, public :: RASCAL_CHEMFILES_ERROR = 4
+    integer(c_int32_t), parameter, public :: RASCAL_IO_ERROR = 5
+    integer(c_int32_t), parameter, public :: RASCAL_CANCELLED_ERROR = 6
+    integer(c_int32_t), parameter, public :: RASCAL_SYSTEM_ERROR = 128
+    integer(c_int32_t), parameter, public :: RASCAL_BUFFER_SIZE_ERROR = 254
+    integer(c_int32_t), parameter, public :: RASCAL_INTERNAL_ERROR = 255
+
+    ! Values of `rascal_parallelism`
+    integer(c_int), parameter, public :: RASCAL_PARALLELISM_SAMPLES = 0
+    integer(c_int), parameter, public :: RASCAL_PARALLELISM_SYSTEMS = 1
+
+    ! Values of `rascal_indexes_kind`
+    integer(c_int), parameter, public :: RASCAL_INDEXES_FEATURES = 0
+    integer(c_int), parameter, public :: RASCAL_INDEXES_SAMPLES = 1
+    integer(c_int), parameter, public :: RASCAL_INDEXES_GRADIENT_SAMPLES = 2
+
+    ! Values of `rascal_memory_layout`
+    integer(c_int), parameter, public :: RASCAL_MEMORY_LAYOUT_ROW_MAJOR = 0
+    integer(c_int), parameter, public :: RASCAL_MEMORY_LAYOUT_FEATURE_MAJOR = 1
+
+    ! Current version of `rascal_system_t`
+    integer(c_int32_t), parameter, public :: RASCAL_SYSTEM_VERSION = 2
+
+    ! Mirror of `rascal_system_t`. Systems are usually created with
+    ! `rascal_basic_system` or `rascal_basic_systems_read`, but custom systems
+    ! can be implemented by setting the function pointers with `c_funloc`. The
+    ! optional `cell_shifts`, `data`, `triplets_containing`, `pairs_with_cutoff`
+    ! and `pairs_containing_with_cutoff` function pointers can be left as
+    ! `c_null_funptr`.
+    type, bind(c), public :: rascal_system_t
+        integer(c_int32_t) :: version = RASCAL_SYSTEM_VERSION
+        type(c_ptr) :: user_data = c_null_ptr
+        type(c_funptr) :: size = c_null_funptr
+        type(c_funptr) :: species = c_null_funptr
+        type(c_funptr) :: positions = c_null_funptr
+        type(c_funptr) :: cell = c_null_funptr
+        type(c_funptr) :: compute_neighbors = c_null_funptr
+        type(c_funptr) :: pairs = c_null_funptr
+        type(c_funptr) :: pairs_containing = c_null_funptr
+        type(c_funptr) :: cell_shifts = c_null_funptr
+        type(c_funptr) :: data = c_null_funptr
+        type(c_funptr) :: triplets_containing = c_null_funptr
+        type(c_funptr) :: pairs_with_cutoff = c_null_funptr
+        type(c_funptr) :: pairs_containing_with_cutoff = c_null_funptr
+    end type
+
+    ! Mirror of `rascal_indexes_t`
+    type, bind(c), public :: rascal_indexes_t
+        type(c_ptr) :: names = c_null_ptr
+        type(c_ptr) :: values = c_null_ptr
+        integer(c_size_t) :: size = 0
+        integer(c_size_t) :: count = 0
+    end type
+
+    ! Mirror of `rascal_calculation_options_t`. The default initialization
+    ! corresponds to the default options, computing all samples and features.
+    type, bind(c), public :: rascal_calculation_options_t
+        logical(c_bool) :: use_native_system = .false.
+        type(rascal_indexes_t) :: selected_samples
+        type(rascal_indexes_t) :: selected_features
+        integer(c_size_t) :: threads = 0
+        integer(c_int) :: parallelism = RASCAL_PARALLELISM_SAMPLES
+        logical(c_bool) :: check_finite = .false.
+        logical(c_bool) :: deterministic = .false.
+        real(c_double) :: neighbors_cutoff = 0.0_c_double
+    end type
+
+    ! A calculator, wrapping `rascal_calculator_t`
+    type, public :: rascal_calculator
+        type(c_ptr) :: ptr = c_null_ptr
+    contains
+        procedure :: init => calculator_init
+        procedure :: compute => calculator_compute
+        procedure :: free => calculator_free
+    end type
+
+    ! A descriptor, wrapping `rascal_descriptor_t`
+    type, public :: rascal_descriptor
+        type(c_ptr) :: ptr = c_null_ptr
+    contains
+        procedure :: init => descriptor_init
+        procedure :: values => descriptor_values
+        procedure :: gradients => descriptor_gradients
+        procedure :: layout => descriptor_layout
+        procedure :: set_layout => descriptor_set_layout
+        procedure :: densify => descriptor_densify
+        procedure :: free => descriptor_free
+    end type
+
+    public :: rascal_last_error
+    public :: rascal_basic_system, rascal_basic_system_free
+    public :: rascal_basic_systems_read, rascal_basic_systems_free
+
+    public :: rascal_c_last_error
+    public :: rascal_c_basic_system, rascal_c_basic_system_free
+    public :: rascal_c_basic_systems_read, rascal_c_basic_systems_free
+    public :: rascal_c_descriptor, rascal_c_descriptor_free
+    public :: rascal_c_descriptor_values, rascal_c_descriptor_gradients
+    public :: rascal_c_descriptor_layout, rascal_c_descriptor_set_layout
+    public :: rascal_c_descriptor_indexes, rascal_c_descriptor_densify
+    public :: rascal_c_calculator, rascal_c_calculator_free
+    public :: rascal_c_calculator_compute
+
+    interface
+        function rascal_c_last_error() bind(c, name="rascal_last_error")
+            import :: c_ptr
+            type(c_ptr) :: rascal_c_last_error
+        end function
+
+        function rascal_c_basic_system(species, positions, n_atoms, cell, system) &
+            bind(c, name="rascal_basic_system")
+            import :: c_int32_t, c_double, c_size_t, c_ptr, rascal_system_t
+            integer(c_int32_t), intent(in) :: species(*)
+            real(c_double), intent(in) :: positions(*)
+            integer(c_size_t), value :: n_atoms
+            type(c_ptr), value :: cell
+            type(rascal_system_t), intent(out) :: system
+            integer(c_int32_t) :: rascal_c_basic_system
+        end function
+
+        function rascal_c_basic_system_free(system) bind(c, name="rascal_basic_system_free")
+            import :: c_int32_t, rascal_system_t
+            type(rascal_system_t), intent(inout) :: system
+            integer(c_int32_t) :: rascal_c_basic_system_free
+        end function
+
+        function rascal_c_basic_systems_read(path, systems, count) &
+            bind(c, name="rascal_basic_systems_read")
+            import :: c_int32_t, c_char, c_ptr, c_size_t
+            character(kind=c_char), intent(in) :: path(*)
+            type(c_ptr), intent(out) :: systems
+            integer(c_size_t), intent(out) :: count
+            integer(c_int32_t) :: rascal_c_basic_systems_read
+        end function
+
+        function rascal_c_basic_systems_free(systems, count) bind(c, name="rascal_basic_systems_free")
+            import :: c_int32_t, c_ptr, c_size_t
+            type(c_ptr), value :: systems
+            integer(c_size_t), value :: count
+            integer(c_int32_t) :: rascal_c_basic_systems_free
+        end function
+
+        function rascal_c_descriptor() bind(c, name="rascal_descriptor")
+            import :: c_ptr
+            type(c_ptr) :: rascal_c_descriptor
+        end function
+
+        function rascal_c_descriptor_free(descriptor) bind(c, name="rascal_descriptor_free")
+            import :: c_int32_t, c_ptr
+            type(c_ptr), value :: descriptor
+            integer(c_int32_t) :: rascal_c_descriptor_free
+        end function
+
+        function rascal_c_descriptor_values(descriptor, data, samples, features) &
+            bind(c, name="rascal_descriptor_values")
+            import :: c_int32_t, c_ptr, c_size_t
+            type(c_ptr), value :: descriptor
+            type(c_ptr), intent(out) :: data
+            integer(c_size_t), intent(out) :: samples
+            integer(c_size_t), intent(out) :: features
+            integer(c_int32_t) :: rascal_c_descriptor_values
+        end function
+
+        function rascal_c_descriptor_gradients(descriptor, data, gradient_samples, features) &
+            bind(c, name="rascal_descriptor_gradients")
+            import :: c_int32_t, c_ptr, c_size_t
+            type(c_ptr), value :: descriptor
+            type(c_ptr), intent(out) :: data
+            integer(c_size_t), intent(out) :: gradient_samples
+            integer(c_size_t), intent(out) :: features
+            integer(c_int32_t) :: rascal_c_descriptor_gradients
+        end function
+
+        function rascal_c_descriptor_layout(descriptor, layout) &
+            bind(c, name="rascal_descriptor_layout")
+            import :: c_int32_t, c_int, c_ptr
+            type(c_ptr), value :: descriptor
+            integer(c_int), intent(out) :: layout
+            integer(c_int32_t) :: rascal_c_descriptor_layout
+        end function
+
+        function rascal_c_descriptor_set_layout(descriptor, layout) &
+            bind(c, name="rascal_descriptor_set_layout")
+            import :: c_int32_t, c_int, c_ptr
+            type(c_ptr), value :: descriptor
+            integer(c_int), value :: layout
+            integer(c_int32_t) :: rascal_c_descriptor_set_layout
+        end function
+
+        function rascal_c_descriptor_indexes(descriptor, kind, indexes) &
+            bind(c, name="rascal_descriptor_indexes")
+            import :: c_int32_t, c_int, c_ptr, rascal_indexes_t
+            type(c_ptr), value :: descriptor
+            integer(c_int), value :: kind
+            type(rascal_indexes_t), intent(out) :: indexes
+            integer(c_int32_t) :: rascal_c_descriptor_indexes
+        end function
+
+        function rascal_c_descriptor_densify(descriptor, variables, variables_count, requested, requested_size) &
+            bind(c, name="rascal_descriptor_densify")
+            import :: c_int32_t, c_ptr, c_size_t
+            type(c_ptr), value :: descriptor
+            type(c_ptr), intent(in) :: variables(*)
+            integer(c_size_t), value :: variables_count
+            type(c_ptr), value :: requested
+            integer(c_size_t), value :: requested_size
+            integer(c_int32_t) :: rascal_c_descriptor_densify
+        end function
+
+        function rascal_c_calculator(name, parameters) bind(c, name="rascal_calculator")
+            import :: c_ptr, c_char
+            character(kind=c_char), intent(in) :: name(*)
+            character(kind=c_char), intent(in) :: parameters(*)
+            type(c_ptr) :: rascal_c_calculator
+        end function
+
+        function rascal_c_calculator_free(calculator) bind(c, name="rascal_calculator_free")
+            import :: c_int32_t, c_ptr
+            type(c_ptr), value :: calculator
+            integer(c_int32_t) :: rascal_c_calculator_free
+        end function
+
+        function rascal_c_calculator_compute(calculator, descriptor, systems, systems_count, options) &
+            bind(c, name="rascal_calculator_compute")
+            import :: c_int32_t, c_ptr, c_size_t, rascal_system_t, rascal_calculation_options_t
+            type(c_ptr), value :: calculator
+            type(c_ptr), value :: descriptor
+            type(rascal_system_t), intent(inout) :: systems(*)
+            integer(c_size_t), value :: systems_count
+            type(rascal_calculation_options_t), value :: options
+            integer(c_int32_t) :: rascal_c_calculator_compute
+        end function
+
+        function c_strlen(string) bind(c, name="strlen")
+            import :: c_ptr, c_size_t
+            type(c_ptr), value :: string
+            integer(c_size_t) :: c_strlen
+        end function
+    end interface
+
+contains
+
+    ! Get the last error message that was created on the current thread
+    function rascal_last_error() result(message)
+        character(len=:), allocatable :: message
+        type(c_ptr) :: c_message
+
+        c_message = rascal_c_last_error()
+        message = c_to_f_string(c_message)
+    end function
+
+    ! Create a new system from the atomic `species(n_atoms)` and
+    ! `positions(3, n_atoms)`, using the neighbor list implementation in
+    ! rascaline. `cell(3, 3)` contains the unit cell vectors as columns, and
+    ! should not be given for non-periodic systems. The system must be released
+    ! with `rascal_basic_system_free`.
+    subroutine rascal_basic_system(species, positions, system, cell, status)
+        integer(c_int32_t), intent(in) :: species(:)
+        real(c_double), intent(in) :: positions(:, :)
+        type(rascal_system_t), intent(out) :: system
+        real(c_double), intent(in), target, optional :: cell(3, 3)
+        integer, intent(out), optional :: status
+        type(c_ptr) :: c_cell
+
+        if (size(positions, 1) /= 3 .or. size(positions, 2) /= size(species)) then
+            call handle_status(RASCAL_INVALID_PARAMETER_ERROR, status, &
+                "positions must have a shape of (3, n_atoms) in rascal_basic_system")
+            return
+        end if
+
+        c_cell = c_null_ptr
+        if (present(cell)) c_cell = c_loc(cell)
+
+        call handle_status(rascal_c_basic_system(                               &
+            species, positions, int(size(species), c_size_t), c_cell, system    &
+        ), status)
+    end subroutine
+
+    ! Release the memory associated with a system created by
+    ! `rascal_basic_system`
+    subroutine rascal_basic_system_free(system, status)
+        type(rascal_system_t), intent(inout) :: system
+        integer, intent(out), optional :: status
+
+        call handle_status(rascal_c_basic_system_free(system), status)
+    end subroutine
+
+    ! Read all structures in the file at `path` using chemfiles, and store
+    ! them in `systems`. The systems must be released with
+    ! `rascal_basic_systems_free`.
+    subroutine rascal_basic_systems_read(path, systems, status)
+        character(len=*), intent(in) :: path
+        type(rascal_system_t), pointer, intent(out) :: systems(:)
+        integer, intent(out), optional :: status
+        type(c_ptr) :: c_systems
+        integer(c_size_t) :: count
+        integer(c_int32_t) :: c_status
+
+        systems => null()
+        c_status = rascal_c_basic_systems_read(f_to_c_string(path), c_systems, count)
+        if (c_status == RASCAL_SUCCESS) then
+            call c_f_pointer(c_systems, systems, [count])
+        end if
+        call handle_status(c_status, status)
+    end subroutine
+
+    ! Release the memory associated with systems created by
+    ! `rascal_basic_systems_read`
+    subroutine rascal_basic_systems_free(systems, status)
+        type(rascal_system_t), pointer, intent(inout) :: systems(:)
+        integer, intent(out), optional :: status
+
+        if (.not. associated(systems)) then
+            if (present(status)) status = RASCAL_SUCCESS
+            return
+        end if
+
+        if (size(systems) == 0) then
+            if (present(status)) status = RASCAL_SUCCESS
+            systems => null()
+            return
+        end if
+
+        call handle_status(rascal_c_basic_systems_free(                 &
+            c_loc(systems(1)), int(size(systems), c_size_t)             &
+        ), status)
+        systems => null()
+    end subroutine
+
+    ! Create a new calculator with the given `name` and `parameters` (as a
+    ! JSON string)
+    subroutine calculator_init(self, name, parameters, status)
+        class(rascal_calculator), intent(inout) :: self
+        character(len=*), intent(in) :: name
+        character(len=*), intent(in) :: parameters
+        integer, intent(out), optional :: status
+
+        self%ptr = rascal_c_calculator(f_to_c_string(name), f_to_c_string(parameters))
+        if (c_associated(self%ptr)) then
+            if (present(status)) status = RASCAL_SUCCESS
+        else
+            call handle_status(RASCAL_INVALID_PARAMETER_ERROR, status, rascal_last_error())
+        end if
+    end subroutine
+
+    ! Run a calculation on the given `systems`, storing the data in
+    ! `descriptor`. If `options` is not given, all samples and features are
+    ! computed.
+    subroutine calculator_compute(self, descriptor, systems, options, status)
+        class(rascal_calculator), intent(in) :: self
+        type(rascal_descriptor), intent(inout) :: descriptor
+        type(rascal_system_t), intent(inout) :: systems(:)
+        type(rascal_calculation_options_t), intent(in), optional :: options
+        integer, intent(out), optional :: status
+        type(rascal_calculation_options_t) :: c_options
+
+        if (present(options)) c_options = options
+
+        call handle_status(rascal_c_calculator_compute(                                 &
+            self%ptr, descriptor%ptr, systems, int(size(systems), c_size_t), c_options  &
+        ), status)
+    end subroutine
+
+    ! Release the memory associated with this calculator
+    subroutine calculator_free(self, status)
+        class(rascal_calculator), intent(inout) :: self
+        integer, intent(out), optional :: status
+
+        call handle_status(rascal_c_calculator_free(self%ptr), status)
+        self%ptr = c_null_ptr
+    end subroutine
+
+    ! Create a new empty descriptor
+    subroutine descriptor_init(self)
+        class(rascal_descriptor), intent(inout) :: self
+
+        self%ptr = rascal_c_descriptor()
+    end subroutine
+
+    ! Get a pointer to the values stored in this descriptor, as an array of
+    ! shape `(n_features, n_samples)`, or `(n_samples, n_features)` if the
+    ! descriptor uses a feature-major layout. The data is owned by the
+    ! descriptor, and the pointer is invalidated by any further operation on
+    ! the descriptor.
+    function descriptor_values(self, status) result(values)
+        class(rascal_descriptor), intent(in) :: self
+        integer, intent(out), optional :: status
+        real(c_double), pointer :: values(:, :)
+        type(c_ptr) :: data
+        integer(c_size_t) :: samples, features
+        integer(c_int) :: layout
+        integer(c_int32_t) :: c_status
+
+        values => null()
+        c_status = rascal_c_descriptor_layout(self%ptr, layout)
+        if (c_status == RASCAL_SUCCESS) then
+            c_status = rascal_c_descriptor_values(self%ptr, data, samples, features)
+        end if
+        if (c_status == RASCAL_SUCCESS .and. c_associated(data)) then
+            if (layout == RASCAL_MEMORY_LAYOUT_FEATURE_MAJOR) then
+                call c_f_pointer(data, values, [samples, features])
+            else
+                call c_f_pointer(data, values, [features, samples])
+            end if
+        end if
+        call handle_status(c_status, status)
+    end function
+
+    ! Get a pointer to the gradients stored in this descriptor, as an array of
+    ! shape `(n_features, n_gradient_samples)` (or `(n_gradient_samples,
+    ! n_features)` if the descriptor uses a feature-major layout), or a
+    ! disassociated pointer if this descriptor does not contain gradients. The
+    ! data is owned by the descriptor, and the pointer is invalidated by any
+    ! further operation on the descriptor.
+    function descriptor_gradients(self, status) result(gradients)
+        class(rascal_descriptor), intent(in) :: self
+        integer, intent(out), optional :: status
+        real(c_double), pointer :: gradients(:, :)
+        type(c_ptr) :: data
+        integer(c_size_t) :: samples, features
+        integer(c_int) :: layout
+        integer(c_int32_t) :: c_status
+
+        gradients => null()
+        c_status = rascal_c_descriptor_layout(self%ptr, layout)
+        if (c_status == RASCAL_SUCCESS) then
+            c_status = rascal_c_descriptor_gradients(self%ptr, data, samples, features)
+        end if
+        if (c_status == RASCAL_SUCCESS .and. c_associated(data)) then
+            if (layout == RASCAL_MEMORY_LAYOUT_FEATURE_MAJOR) then
+                call c_f_pointer(data, gradients, [samples, features])
+            else
+                call c_f_pointer(data, gradients, [features, samples])
+            end if
+        end if
+        call handle_status(c_status, status)
+    end function
+
+    ! Get the memory layout of the arrays in this descriptor, one of the
+    ! `RASCAL_MEMORY_LAYOUT_XXX` values
+    function descriptor_layout(self, status) result(layout)
+        class(rascal_descriptor), intent(in) :: self
+        integer, intent(out), optional :: status
+        integer(c_int) :: layout
+
+        layout = RASCAL_MEMORY_LAYOUT_ROW_MAJOR
+        call handle_status(rascal_c_descriptor_layout(self%ptr, layout), status)
+    end function
+
+    ! Set the memory layout of the arrays in this descriptor, converting the
+    ! existing arrays if needed. The layout is kept for all subsequent
+    ! calculations using this descriptor.
+    subroutine descriptor_set_layout(self, layout, status)
+        class(rascal_descriptor), intent(inout) :: self
+        integer(c_int), intent(in) :: layout
+        integer, intent(out), optional :: status
+
+        call handle_status(rascal_c_descriptor_set_layout(self%ptr, layout), status)
+    end subroutine
+
+    ! Make this descriptor dense along the given `variables`, see
+    ! `rascal_descriptor_densify` in the C API for more information.
+    subroutine descriptor_densify(self, variables, status)
+        class(rascal_descriptor), intent(inout) :: self
+        character(len=*), intent(in) :: variables(:)
+        integer, intent(out), optional :: status
+        character(kind=c_char), allocatable, target :: buffer(:, :)
+        type(c_ptr), allocatable :: c_variables(:)
+        integer :: i, j, length
+
+        ! store all the variables as NULL-terminated strings in the columns of
+        ! a single buffer, and get pointers to the start of each column
+        allocate(buffer(len(variables) + 1, size(variables)))
+        allocate(c_variables(size(variables)))
+        do i = 1, size(variables)
+            length = len_trim(variables(i))
+            do j = 1, length
+                buffer(j, i) = variables(i)(j:j)
+            end do
+            buffer(length + 1, i) = c_null_char
+            c_variables(i) = c_loc(buffer(1, i))
+        end do
+
+        call handle_status(rascal_c_descriptor_densify(                                     &
+            self%ptr, c_variables, int(size(variables), c_size_t), c_null_ptr, 0_c_size_t   &
+        ), status)
+    end subroutine
+
+    ! Release the memory associated with this descriptor
+    subroutine descriptor_free(self, status)
+        class(rascal_descriptor), intent(inout) :: self
+        integer, intent(out), optional :: status
+
+        call handle_status(rascal_c_descriptor_free(self%ptr), status)
+        self%ptr = c_null_ptr
+    end subroutine
+
+    ! Forward `c_status` to `status` if it is present, or stop the program with
+    ! the last error message (or the given `message`) in case of error
+    subroutine handle_status(c_status, status, message)
+        integer(c_int32_t), intent(in) :: c_status
+        integer, intent(out), optional :: status
+        character(len=*), intent(in), optional :: message
+
+        if (present(status)) then
+            status = int(c_status)
+            return
+        end if
+
+        if (c_status /= RASCAL_SUCCESS) then
+            if (present(message)) then
+                write(error_unit, '(a, a)') "rascaline error: ", message
+            else
+                write(error_unit, '(a, a)') "rascaline error: ", rascal_last_error()
+            end if
+            error stop 1
+        end if
+    end subroutine
+
+    ! Convert a Fortran string to a NULL-terminated C string
+    function f_to_c_string(string) result(c_string)
+        character(len=*), intent(in) :: string
+        character(kind=c_char, len=len_trim(string) + 1) :: c_string
+
+        c_string = trim(string) // c_null_char
+    end function
+
+    ! Convert a NULL-terminated C string to a Fortran string
+    function c_to_f_string(c_string) result(string)
+        type(c_ptr), intent(in) :: c_string
+        character(len=:), allocatable :: string
+        character(kind=c_char), pointer :: chars(:)
+        integer :: i, length
+
+        if (.not. c_associated(c_string)) then
+            string = ""
+            return
+        end if
+
+        length = int(c_strlen(c_string))
+        call c_f_pointer(c_string, chars, [length])
+        allocate(character(len=length) :: string)
+        do i = 1, length
+            string(i:i) = chars(i)
+        end do
+    end function
+end module
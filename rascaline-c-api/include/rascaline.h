@@ -38,6 +38,17 @@
  */
 #define RASCAL_CHEMFILES_ERROR 4
 
+/**
+ * Status code used for errors while reading or writing files
+ */
+#define RASCAL_IO_ERROR 5
+
+/**
+ * Status code used when a calculation was cancelled with
+ * `rascal_cancellation_cancel`
+ */
+#define RASCAL_CANCELLED_ERROR 6
+
 /**
  * Status code used for errors coming from the system implementation if we
  * don't have a more specific status
@@ -87,6 +98,12 @@
  */
 #define RASCAL_LOG_LEVEL_TRACE 5
 
+/**
+ * Current version of the `rascal_system_t` struct. The `version` field of all
+ * systems should be set to this value.
+ */
+#define RASCAL_SYSTEM_VERSION 2
+
 /**
  * The different kinds of indexes that can exist on a `rascal_descriptor_t`
  */
@@ -107,10 +124,85 @@ typedef enum rascal_indexes_kind {
 } rascal_indexes_kind;
 
 /**
- * Opaque type representing a `Calculator`
+ * Memory layout of the values and gradients arrays in a `rascal_descriptor_t`
+ */
+typedef enum rascal_memory_layout {
+  /**
+   * Row-major layout, where all the features of a given sample are
+   * contiguous in memory. The element at `(sample, feature)` is at
+   * `data[sample * n_features + feature]`.
+   */
+  RASCAL_MEMORY_LAYOUT_ROW_MAJOR = 0,
+  /**
+   * Column-major layout, where all the samples of a given feature are
+   * contiguous in memory. The element at `(sample, feature)` is at
+   * `data[feature * n_samples + sample]`.
+   */
+  RASCAL_MEMORY_LAYOUT_FEATURE_MAJOR = 1,
+} rascal_memory_layout;
+
+/**
+ * Strategy used to run a calculation in parallel
+ */
+typedef enum rascal_parallelism {
+  /**
+   * Parallelize the calculation inside each system, handling one system
+   * after the other. This works best for a few large systems.
+   */
+  RASCAL_PARALLELISM_SAMPLES = 0,
+  /**
+   * Parallelize the calculation over all atomic environments in all
+   * systems at once, balancing the work between threads. This works best
+   * for datasets containing a lot of small systems.
+   */
+  RASCAL_PARALLELISM_SYSTEMS = 1,
+} rascal_parallelism;
+
+/**
+ * Kind of random matrix used by `rascal_descriptor_random_projection`
+ */
+typedef enum rascal_random_projection {
+  /**
+   * Dense projection, with entries drawn from a normal distribution
+   */
+  RASCAL_RANDOM_PROJECTION_GAUSSIAN = 0,
+  /**
+   * Sparse projection, with two thirds of the entries equal to zero
+   */
+  RASCAL_RANDOM_PROJECTION_SPARSE = 1,
+} rascal_random_projection;
+
+/**
+ * Distance between samples used by `rascal_descriptor_farthest_point_sampling`
+ */
+typedef enum rascal_sample_distance {
+  /**
+   * Euclidean distance between the values of the samples
+   */
+  RASCAL_SAMPLE_DISTANCE_EUCLIDEAN = 0,
+  /**
+   * Cosine distance `1 - x·y / (|x| |y|)` between the values of the samples
+   */
+  RASCAL_SAMPLE_DISTANCE_COSINE = 1,
+} rascal_sample_distance;
+
+/**
+ * Opaque type representing a `Calculator`.
+ *
+ * All functions taking a calculator can be called concurrently from multiple
+ * threads, including multiple calculations using the same calculator. Each
+ * calculation running at the same time uses a separate copy of the
+ * calculator, and the copies are kept around to be re-used by later
+ * calculations.
  */
 typedef struct rascal_calculator_t rascal_calculator_t;
 
+/**
+ * Opaque type representing a `CancellationHandle`, which can be used to
+ * cancel calculations running with a given calculator from another thread.
+ */
+typedef struct rascal_cancellation_t rascal_cancellation_t;
+
 /**
  * Opaque type representing a `Descriptor`.
  */
@@ -137,8 +229,26 @@ typedef int32_t rascal_status_t;
  */
 typedef void (*rascal_logging_callback_t)(int32_t level, const char *message);
 
+/**
+ * Callback function type used to allocate memory for buffers returned by
+ * rascaline to the caller. The function receives the requested size in bytes
+ * and should return a pointer to a suitably aligned block of memory, or
+ * `NULL` if the allocation failed.
+ */
+typedef void *(*rascal_malloc_callback_t)(uintptr_t size);
+
+/**
+ * Callback function type used to release memory previously allocated with the
+ * corresponding `rascal_malloc_callback_t`.
+ */
+typedef void (*rascal_free_callback_t)(void *ptr);
+
 /**
  * Pair of atoms coming from a neighbor list
+ *
+ * The `cell_shift` field was added in version 0.2 of the C API, changing the
+ * size and layout of this struct. Code compiled against older headers must be
+ * re-compiled, and should check the version given by `rascal_version()`.
  */
 typedef struct rascal_pair_t {
   /**
@@ -158,8 +268,28 @@ typedef struct rascal_pair_t {
    * cell as required by periodic boundary conditions.
    */
   double vector[3];
+  /**
+   * number of unit cell vectors that have to be added to the difference of
+   * the positions of the two atoms to get `vector`. This should be set to
+   * `{0, 0, 0}` for non-periodic systems.
+   */
+  int32_t cell_shift[3];
 } rascal_pair_t;
 
+/**
+ * Triplet of atoms `first-center-second` around a given center
+ */
+typedef struct rascal_triplet_t {
+  /**
+   * index of the first neighbor of the center
+   */
+  uintptr_t first;
+  /**
+   * index of the second neighbor of the center
+   */
+  uintptr_t second;
+} rascal_triplet_t;
+
 /**
  * A `rascal_system_t` deals with the storage of atoms and related information,
  * as well as the computation of neighbor lists.
@@ -180,8 +310,22 @@ typedef struct rascal_pair_t {
  * setting `user_data` to the actual data storage, and setting all function
  * pointers to the correct functions. For an example of code doing this, see
  * the `SystemBase` class in the Python interface to rascaline.
+ *
+ * The `version` field must be set to `RASCAL_SYSTEM_VERSION`. New optional
+ * function pointers may be added at the end of this struct in future versions
+ * of rascaline. rascaline uses the `version` of each system to know the size
+ * of the struct the caller allocated: it only reads the function pointers
+ * that exist in this version, and uses this size to step through arrays of
+ * systems. Code compiled against older headers can then be used with newer
+ * versions of rascaline. The function pointers marked as optional below can
+ * be set to `NULL`, in which case rascaline falls back to a default
+ * behavior.
  */
 typedef struct rascal_system_t {
+  /**
+   * Version of this struct, which should be set to `RASCAL_SYSTEM_VERSION`
+   */
+  uint32_t version;
   /**
    * User-provided data should be stored here, it will be passed as the
    * first parameter to all function pointers below.
@@ -241,6 +385,68 @@ typedef struct rascal_system_t {
    * `pairs_containing(j)`.
    */
   rascal_status_t (*pairs_containing)(const void *user_data, uintptr_t center, const struct rascal_pair_t **pairs, uintptr_t *count);
+  /**
+   * Optional. This function should set `*has_cell_shifts` to `true` if the
+   * `cell_shift` field of the pairs returned by `pairs` and
+   * `pairs_containing` is set, and to `false` otherwise.
+   *
+   * If this function is `NULL`, the cell shifts are assumed to be set.
+   */
+  rascal_status_t (*cell_shifts)(const void *user_data, bool *has_cell_shifts);
+  /**
+   * Optional. This function should set `*data` to a pointer to the first
+   * element of a contiguous array containing the per-atom data with the
+   * given `name` (for example partial charges), or to `NULL` if this system
+   * does not contain such data. The array should contain
+   * `rascal_system_t::size()` elements.
+   *
+   * If this function is `NULL`, the system does not contain any per-atom
+   * data.
+   */
+  rascal_status_t (*data)(const void *user_data, const char *name, const double **data);
+  /**
+   * Optional. This function should set `*triplets` to a pointer to the first
+   * element of a contiguous array containing all triplets `i-center-j`
+   * around the atom with index `center`; and `*count` to the size of the
+   * array/the number of triplets. Each triplet should be included once
+   * (either as `i-j` or `j-i`), including triplets where `i == j`, and only
+   * contain atoms from the pairs returned by `pairs_containing(center)`.
+   * This function is only valid to call after a call to
+   * `compute_neighbors`.
+   *
+   * If this function is `NULL`, rascaline builds the triplets from the pairs
+   * returned by `pairs_containing`.
+   */
+  rascal_status_t (*triplets_containing)(const void *user_data, uintptr_t center, const struct rascal_triplet_t **triplets, uintptr_t *count);
+  /**
+   * Optional, since version 2. This function should set `*pairs` to a
+   * pointer to the first element of a contiguous array containing all pairs
+   * in this system for the given `cutoff`; and `*count` to the size of the
+   * array/the number of pairs. The same restrictions on the list of pairs as
+   * `rascal_system_t::pairs` applies.
+   *
+   * If this function is not `NULL`, it is used instead of `pairs`, with the
+   * cutoff given to the last call to `compute_neighbors`.
+   */
+  rascal_status_t (*pairs_with_cutoff)(const void *user_data, double cutoff, const struct rascal_pair_t **pairs, uintptr_t *count);
+  /**
+   * Optional, since version 2. This function should set `*pairs` to a
+   * pointer to the first element of a contiguous array containing all pairs
+   * in this system containing the atom with index `center` for the given
+   * `cutoff`; and `*count` to the size of the array/the number of pairs. The
+   * same restrictions on the list of pairs as
+   * `rascal_system_t::pairs_containing` applies.
+   *
+   * If this function is not `NULL`, it is used instead of
+   * `pairs_containing`, with the cutoff given to the last call to
+   * `compute_neighbors`.
+   *
+   * If both `pairs_with_cutoff` and `pairs_containing_with_cutoff` are set,
+   * `compute_neighbors` can be `NULL`. This allows codes which can provide
+   * pairs on demand (for example from hierarchical neighbor data) to avoid
+   * building a complete neighbor list before each calculation.
+   */
+  rascal_status_t (*pairs_containing_with_cutoff)(const void *user_data, double cutoff, uintptr_t center, const struct rascal_pair_t **pairs, uintptr_t *count);
 } rascal_system_t;
 
 /**
@@ -314,6 +520,44 @@ typedef struct rascal_calculation_options_t {
    * features.
    */
   struct rascal_indexes_t selected_features;
+  /**
+   * Number of threads to use for this calculation. Set this to 0 to use
+   * the default global thread pool, which can be configured with the
+   * `RAYON_NUM_THREADS` environment variable.
+   */
+  uintptr_t threads;
+  /**
+   * Strategy used to run the calculation in parallel. The default
+   * (`RASCAL_PARALLELISM_SAMPLES`) parallelizes over samples within each
+   * system.
+   */
+  enum rascal_parallelism parallelism;
+  /**
+   * Check that all values and gradients are finite after the calculation,
+   * returning an error pointing to the first NaN or infinite entry
+   * otherwise. This is intended for debugging.
+   */
+  bool check_finite;
+  /**
+   * Make sure the results of the calculation do not depend on the number
+   * of threads or the parallelization strategy, at the cost of a slower
+   * calculation.
+   */
+  bool deterministic;
+  /**
+   * Compute the neighbor list of each system once with this cutoff, and
+   * filter it when a smaller cutoff is requested, instead of re-computing
+   * a neighbor list for each cutoff. Set this to 0 to disable this
+   * behavior. This requires `use_native_system`.
+   */
+  double neighbors_cutoff;
+  /**
+   * Only compute the values during the calculation, and compute the
+   * gradients on the first call to `rascal_descriptor_gradients` (or any
+   * other function needing them). This requires the calculator to compute
+   * gradients.
+   */
+  bool lazy_gradients;
 } rascal_calculation_options_t;
 
 #ifdef __cplusplus
@@ -330,10 +574,184 @@ const char *rascal_last_error(void);
 /**
  * Set the given ``callback`` function as the global logging callback. This
  * function will be called on all log events. If a logging callback was already
- * set, it is replaced by the new one.
+ * set, it is replaced by the new one. If ``callback`` is `NULL`, log events
+ * are discarded.
  */
 rascal_status_t rascal_set_logging_callback(rascal_logging_callback_t callback);
 
+/**
+ * Set the most verbose log level for which the logging callback will be
+ * called. ``level`` must be one of `RASCAL_LOG_LEVEL_ERROR`,
+ * `RASCAL_LOG_LEVEL_WARN` `RASCAL_LOG_LEVEL_INFO`, `RASCAL_LOG_LEVEL_DEBUG`,
+ * or `RASCAL_LOG_LEVEL_TRACE`.
+ *
+ * By default, the level is `RASCAL_LOG_LEVEL_INFO`, or
+ * `RASCAL_LOG_LEVEL_DEBUG` if rascaline was compiled in debug mode.
+ */
+rascal_status_t rascal_set_logging_level(int32_t level);
+
+/**
+ * Set the functions used to allocate and release the buffers created by
+ * rascaline and handed over to the caller, i.e. the buffers which must be
+ * released with `rascal_free`: the array returned by
+ * `rascal_descriptor_densify_values` and the buffer returned by
+ * `rascal_descriptor_to_buffer`. This is useful to integrate rascaline with
+ * code using a custom memory pool.
+ *
+ * Memory which stays owned by rascaline is not allocated with these
+ * functions. This includes the values and gradients arrays of a descriptor
+ * (which are accessed through `rascal_descriptor_values` and
+ * `rascal_descriptor_gradients` but released by `rascal_descriptor_free`) and
+ * the message returned by `rascal_last_error`.
+ *
+ * If both `malloc` and `free` are `NULL`, the default allocator (the C
+ * standard library `malloc` and `free`) is restored. The allocator can not be
+ * changed while buffers allocated with the current one are still alive: all of
+ * them must be released with `rascal_free` before calling this function
+ * again.
+ *
+ * @param malloc function used to allocate memory, or `NULL`
+ * @param free function used to release memory allocated with `malloc`, or
+ *             `NULL`
+ *
+ * @returns The status code of this operation. If the status is not
+ *          `RASCAL_SUCCESS`, you can use `rascal_last_error()` to get the full
+ *          error message.
+ */
+rascal_status_t rascal_set_allocator(rascal_malloc_callback_t malloc, rascal_free_callback_t free);
+
+/**
+ * Release memory allocated by rascaline and handed over to the caller, using
+ * the `free` function registered with `rascal_set_allocator`.
+ *
+ * This is the only way to release such memory: calling the `free` function
+ * given to `rascal_set_allocator` directly would prevent changing the
+ * allocator later. If `ptr` is `NULL`, this function does nothing.
+ *
+ * @param ptr pointer to memory allocated by rascaline, or `NULL`
+ *
+ * @returns The status code of this operation. If the status is not
+ *          `RASCAL_SUCCESS`, you can use `rascal_last_error()` to get the full
+ *          error message.
+ */
+rascal_status_t rascal_free(void *ptr);
+
+/**
+ * Create a new `rascal_system_t` from raw arrays of atomic `species` and
+ * `positions`, using the neighbor list implementation in rascaline.
+ *
+ * `species` should contain `n_atoms` integers, and `positions` `3 x n_atoms`
+ * values, with the positions of each atom stored contiguously (`x0 y0 z0 x1
+ * y1 z1 ...`). `cell` should contain the 9 values of the unit cell matrix in
+ * row major order (`ax ay az bx by bz cx cy cz`), or be `NULL` for
+ * non-periodic systems. The cell vectors must form a right-handed basis, and
+ * must not be coplanar. The data is copied, and the arrays can be released
+ * after this function returns.
+ *
+ * The memory allocated by this function must be released using
+ * `rascal_basic_system_free`.
+ *
+ * The `system` is filled with the current `RASCAL_SYSTEM_VERSION` of
+ * `rascal_system_t`, and must point to a struct of the corresponding size.
+ *
+ * @param species pointer to the first element of the atomic species array
+ * @param positions pointer to the first element of the positions array
+ * @param n_atoms number of atoms in the system
+ * @param cell pointer to the first element of the unit cell matrix, or `NULL`
+ * @param system pointer to a `rascal_system_t` which will be filled by this
+ *               function
+ *
+ * @returns The status code of this operation. If the status is not
+ *          `RASCAL_SUCCESS`, you can use `rascal_last_error()` to get the full
+ *          error message.
+ */
+rascal_status_t rascal_basic_system(const int32_t *species,
+                                    const double *positions,
+                                    uintptr_t n_atoms,
+                                    const double *cell,
+                                    struct rascal_system_t *system);
+
+/**
+ * Release memory allocated by `rascal_basic_system`.
+ *
+ * This function is only valid to call with a system created by
+ * `rascal_basic_system`. All the fields of the `system` are set to `NULL`
+ * after this call. If `system` is NULL, this function does nothing.
+ *
+ * @param system pointer to a system created with `rascal_basic_system`
+ *
+ * @returns The status code of this operation. If the status is not
+ *          `RASCAL_SUCCESS`, you can use `rascal_last_error()` to get the full
+ *          error message.
+ */
+rascal_status_t rascal_basic_system_free(struct rascal_system_t *system);
+
+/**
+ * Get the list of centers in `system` whose atomic environment (up to the
+ * given `cutoff`) contains any of the `moved` atoms. The `moved` atoms are
+ * always part of the list, which is sorted.
+ *
+ * The descriptor only needs to be re-computed for these centers after the
+ * `moved` atoms changed positions, and the result can then be merged in the
+ * previous descriptor with `rascal_descriptor_update`. Since atoms can both
+ * enter and leave the environment of a center, this function should be
+ * called with the positions before and after the move, and the union of both
+ * lists used for the calculation.
+ *
+ * @param system pointer to an existing system
+ * @param cutoff spherical cutoff used by the calculator
+ * @param moved pointer to the first element of an array containing the
+ *              indexes of the moved atoms
+ * @param moved_count number of moved atoms
+ * @param centers pointer to the first element of an array of size at least
+ *                the number of atoms in the system, which will be filled
+ *                with the affected centers
+ * @param count `*count` will be set to the number of affected centers
+ *
+ * @returns The status code of this operation. If the status is not
+ *          `RASCAL_SUCCESS`, you can use `rascal_last_error()` to get the full
+ *          error message.
+ */
+rascal_status_t rascal_system_affected_centers(struct rascal_system_t *system,
+                                               double cutoff,
+                                               const uintptr_t *moved,
+                                               uintptr_t moved_count,
+                                               uintptr_t *centers,
+                                               uintptr_t *count);
+
+/**
+ * Use the given `pairs` as the neighbor list of a `system` created by
+ * `rascal_basic_system` or `rascal_basic_systems_read`, for calculations
+ * using the given `cutoff`.
+ *
+ * This allows codes which already maintain a neighbor list to avoid
+ * re-computing it in rascaline. Calculations with a smaller cutoff will
+ * filter these pairs, and calculations with a larger cutoff will fail. The same restrictions as for
+ * `rascal_system_t::pairs` apply to the list of pairs: it should contain each
+ * pair only once, should not contain self pairs and should only contain pairs
+ * with a distance below the cutoff. The pairs are copied, and the array can be
+ * released after this function returns.
+ *
+ * This function is only valid to call with a system created by
+ * `rascal_basic_system` or `rascal_basic_systems_read`. For other systems, the
+ * neighbor list is directly provided by the `pairs` and `pairs_containing`
+ * functions of `rascal_system_t`.
+ *
+ * @param system pointer to a system created with `rascal_basic_system` or
+ *               `rascal_basic_systems_read`
+ * @param cutoff spherical cutoff used to create the list of pairs
+ * @param pairs pointer to the first element of an array of pairs
+ * @param count number of pairs in the array
+ *
+ * @returns The status code of this operation. If the status is not
+ *          `RASCAL_SUCCESS`, you can use `rascal_last_error()` to get the full
+ *          error message.
+ */
+rascal_status_t rascal_basic_system_set_pairs(struct rascal_system_t *system,
+                                              double cutoff,
+                                              const struct rascal_pair_t *pairs,
+                                              uintptr_t count);
+
 /**
  * Read all structures in the file at the given `path` using
  * [chemfiles](https://chemfiles.org/), and convert them to an array of
@@ -411,7 +829,8 @@ rascal_status_t rascal_descriptor_free(struct rascal_descriptor_t *descriptor);
  * This function sets `*data` to a pointer containing the address of first
  * element of the 2D array containing the values, `*samples` to the size of the
  * first axis of this array and `*features` to the size of the second axis of
- * the array. The array is stored using a row-major layout.
+ * the array. The array is stored using the layout of the descriptor, as
+ * returned by `rascal_descriptor_layout` (row-major by default).
  *
  * @param descriptor pointer to an existing descriptor
  * @param data pointer to a pointer to a double, will be set to the address of
@@ -437,11 +856,17 @@ rascal_status_t rascal_descriptor_values(struct rascal_descriptor_t *descriptor,
  * This function sets `*data` to to a pointer containing the address of the
  * first element of the 2D array containing the gradients, `*gradient_samples`
  * to the size of the first axis of this array and `*features` to the size of
- * the second axis of the array. The array is stored using a row-major layout.
+ * the second axis of the array. The array is stored using the layout of the
+ * descriptor, as returned by `rascal_descriptor_layout` (row-major by
+ * default).
  *
  * If this descriptor does not contain gradient data, `*data` is set to `NULL`,
  * while `*gradient_samples` and `*features` are set to 0.
  *
+ * If the descriptor was computed with `lazy_gradients`, the gradients (and
+ * the corresponding gradient samples) are computed by the first call to this
+ * function.
+ *
  * @param descriptor pointer to an existing descriptor
  * @param data pointer to a pointer to a double, will be set to the address of
  *             the first element in the gradients array
@@ -459,6 +884,39 @@ rascal_status_t rascal_descriptor_gradients(struct rascal_descriptor_t *descript
                                             uintptr_t *gradient_samples,
                                             uintptr_t *features);
 
+/**
+ * Get the memory layout used by the values and gradients arrays of this
+ * `descriptor`.
+ *
+ * @param descriptor pointer to an existing descriptor
+ * @param layout pointer to a `rascal_memory_layout`, will be set to the
+ *               layout of the descriptor arrays
+ *
+ * @returns The status code of this operation. If the status is not
+ *          `RASCAL_SUCCESS`, you can use `rascal_last_error()` to get the full
+ *          error message.
+ */
+rascal_status_t rascal_descriptor_layout(const struct rascal_descriptor_t *descriptor,
+                                         enum rascal_memory_layout *layout);
+
+/**
+ * Set the memory layout used by the values and gradients arrays of this
+ * `descriptor`, converting the existing arrays if needed.
+ *
+ * The layout is kept for all subsequent calculations using this descriptor.
+ * Pointers previously obtained with `rascal_descriptor_values` or
+ * `rascal_descriptor_gradients` are invalidated by this function.
+ *
+ * @param descriptor pointer to an existing descriptor
+ * @param layout the new memory layout
+ *
+ * @returns The status code of this operation. If the status is not
+ *          `RASCAL_SUCCESS`, you can use `rascal_last_error()` to get the full
+ *          error message.
+ */
+rascal_status_t rascal_descriptor_set_layout(struct rascal_descriptor_t *descriptor,
+                                             enum rascal_memory_layout layout);
+
 /**
  * Get the values associated with one of the `indexes` in the given
  * `descriptor`.
@@ -472,6 +930,8 @@ rascal_status_t rascal_descriptor_gradients(struct rascal_descriptor_t *descript
  *
  * If this `descriptor` does not contain gradient data, and `indexes` is
  * `RASCAL_INDEXES_GRADIENTS`, all members of `indexes` are set to `NULL` or 0.
+ * This is also the case for descriptors computed with `lazy_gradients` until
+ * the gradients are computed by `rascal_descriptor_gradients`.
  *
  * @param descriptor pointer to an existing descriptor
  * @param kind type of indexes requested
@@ -485,6 +945,56 @@ rascal_status_t rascal_descriptor_indexes(const struct rascal_descriptor_t *desc
                                           enum rascal_indexes_kind kind,
                                           struct rascal_indexes_t *indexes);
 
+/**
+ * Get the number of variables names in one of the `indexes` of the given
+ * `descriptor`, i.e. the size of the arrays to pass to
+ * `rascal_descriptor_indexes_names`.
+ *
+ * If this `descriptor` does not contain gradient data, and `kind` is
+ * `RASCAL_INDEXES_GRADIENT_SAMPLES`, `count` is set to 0.
+ *
+ * @param descriptor pointer to an existing descriptor
+ * @param kind type of indexes requested
+ * @param count pointer to an integer that will be set to the number of names
+ *
+ * @returns The status code of this operation. If the status is not
+ *          `RASCAL_SUCCESS`, you can use `rascal_last_error()` to get the full
+ *          error message.
+ */
+rascal_status_t rascal_descriptor_indexes_names_count(const struct rascal_descriptor_t *descriptor,
+                                                      enum rascal_indexes_kind kind,
+                                                      uintptr_t *count);
+
+/**
+ * Get the names of the variables in one of the `indexes` of the given
+ * `descriptor`, together with the length of each name.
+ *
+ * `names` and `lengths` must point to caller-allocated arrays containing
+ * exactly `count` elements, where `count` is the value given by
+ * `rascal_descriptor_indexes_names_count`. This function sets each entry in
+ * `names` to a **read only** NULL-terminated string, and the corresponding
+ * entry in `lengths` to the length of this string in bytes, not including
+ * the NULL terminator. This is intended for bindings in languages where
+ * NULL-terminated strings are not easy to handle.
+ *
+ * @param descriptor pointer to an existing descriptor
+ * @param kind type of indexes requested
+ * @param names pointer to an array of `count` strings, which will be filled by
+ *              this function
+ * @param lengths pointer to an array of `count` integers, which will be
+ *                filled by this function
+ * @param count number of elements in the `names` and `lengths` arrays
+ *
+ * @returns The status code of this operation. If the status is not
+ *          `RASCAL_SUCCESS`, you can use `rascal_last_error()` to get the full
+ *          error message.
+ */
+rascal_status_t rascal_descriptor_indexes_names(const struct rascal_descriptor_t *descriptor,
+                                                enum rascal_indexes_kind kind,
+                                                const char **names,
+                                                uintptr_t *lengths,
+                                                uintptr_t count);
+
 /**
  * Make the given `descriptor` dense along the given `variables`.
  *
@@ -560,12 +1070,13 @@ rascal_status_t rascal_descriptor_densify(struct rascal_descriptor_t *descriptor
  * to its documentation for more information.
  *
  * If this descriptor contains gradients, `densified_positions` will point to
- * an array allocated with `malloc` containing the changes made to the values,
- * which can be used to reconstruct the change to make to the gradients. The
- * size of this array will be stored in `densified_positions_count`.
+ * an array allocated with the allocator set by `rascal_set_allocator`
+ * (`malloc` by default) containing the changes made to the values, which can
+ * be used to reconstruct the change to make to the gradients. The size of this
+ * array will be stored in `densified_positions_count`.
  *
- * Users of this function are expected to `free` the corresponding memory when
- * they no longer need it.
+ * Users of this function are expected to release the corresponding memory
+ * with `rascal_free` when they no longer need it.
  *
  * This is an advanced function most users should not need to use, used to
  * implement backward propagation without having to densify the full gradient
@@ -579,6 +1090,359 @@ rascal_status_t rascal_descriptor_densify_values(struct rascal_descriptor_t *des
                                                  struct rascal_densified_position_t **densified_positions,
                                                  uintptr_t *densified_positions_count);
 
+/**
+ * Save the given `descriptor` to the file at `path`, creating the file if it
+ * does not exist and overwriting it otherwise.
+ *
+ * The data is stored in the same format as `Descriptor::save` in the Rust
+ * API, and can be loaded back with `rascal_descriptor_load`.
+ *
+ * @param descriptor pointer to an existing descriptor
+ * @param path NULL-terminated path of the file where to save the descriptor
+ *
+ * @returns The status code of this operation. If the status is not
+ *          `RASCAL_SUCCESS`, you can use `rascal_last_error()` to get the full
+ *          error message.
+ */
+rascal_status_t rascal_descriptor_save(const struct rascal_descriptor_t *descriptor,
+                                       const char *path);
+
+/**
+ * Load data from the file at `path` into the given `descriptor`, replacing
+ * any data already stored in the descriptor.
+ *
+ * The file should have been created with `rascal_descriptor_save` or
+ * `Descriptor::save` in the Rust API.
+ *
+ * @param descriptor pointer to an existing descriptor
+ * @param path NULL-terminated path of the file containing the descriptor
+ *
+ * @returns The status code of this operation. If the status is not
+ *          `RASCAL_SUCCESS`, you can use `rascal_last_error()` to get the full
+ *          error message.
+ */
+rascal_status_t rascal_descriptor_load(struct rascal_descriptor_t *descriptor, const char *path);
+
+/**
+ * Serialize the full `descriptor` (values, gradients and all the indexes) to
+ * a binary buffer in memory, which can be loaded back with
+ * `rascal_descriptor_from_buffer`. This is useful to send a descriptor to
+ * other processes (for example with MPI) without going through the
+ * filesystem.
+ *
+ * The buffer starts with 8 magic bytes (`"RASCALDS"`) and the format version
+ * as a little-endian 32-bit integer, followed by the descriptor data in the
+ * same format as `Descriptor::save_cbor` in the Rust API.
+ *
+ * `*buffer` will be set to memory allocated with the allocator set by
+ * `rascal_set_allocator`, which must be released with `rascal_free` when no
+ * longer needed.
+ *
+ * @param descriptor pointer to an existing descriptor
+ * @param buffer pointer to a pointer to bytes, will be set to the address of
+ *               the newly allocated buffer
+ * @param buffer_size pointer to a single integer, will be set to the size of
+ *                    the buffer in bytes
+ *
+ * @returns The status code of this operation. If the status is not
+ *          `RASCAL_SUCCESS`, you can use `rascal_last_error()` to get the full
+ *          error message.
+ */
+rascal_status_t rascal_descriptor_to_buffer(const struct rascal_descriptor_t *descriptor,
+                                            uint8_t **buffer,
+                                            uintptr_t *buffer_size);
+
+/**
+ * Load data from a `buffer` created by `rascal_descriptor_to_buffer` into
+ * the given `descriptor`, replacing any data already stored in the
+ * descriptor.
+ *
+ * @param descriptor pointer to an existing descriptor
+ * @param buffer pointer to the first byte of the buffer
+ * @param buffer_size size of the buffer in bytes
+ *
+ * @returns The status code of this operation. If the status is not
+ *          `RASCAL_SUCCESS`, you can use `rascal_last_error()` to get the full
+ *          error message.
+ */
+rascal_status_t rascal_descriptor_from_buffer(struct rascal_descriptor_t *descriptor,
+                                              const uint8_t *buffer,
+                                              uintptr_t buffer_size);
+
+/**
+ * Write all the arrays in the given `descriptor` to separate files in
+ * numpy's `.npy` format, inside `directory`. The directory is created if it
+ * does not exist already.
+ *
+ * This creates `values.npy`, `samples.npy` and `features.npy`, as well as
+ * `gradients.npy` and `gradients_samples.npy` if the descriptor contains
+ * gradients. Indexes are stored as structured arrays, with one field of 32-bit
+ * integers for each variable.
+ *
+ * @param descriptor pointer to an existing descriptor
+ * @param directory NULL-terminated path of the directory where to write the
+ *                  files
+ *
+ * @returns The status code of this operation. If the status is not
+ *          `RASCAL_SUCCESS`, you can use `rascal_last_error()` to get the full
+ *          error message.
+ */
+rascal_status_t rascal_descriptor_write_npy(const struct rascal_descriptor_t *descriptor,
+                                            const char *directory);
+
+/**
+ * Compute the Gram matrix `X^T X` of the values `X` of this `descriptor`,
+ * summing over all samples, in the `covariance` array of size `n_features x
+ * n_features`.
+ *
+ * If `centered` is `true`, the mean of each feature over the samples is
+ * subtracted from the values before computing the product. This is the first
+ * step of a principal component analysis of the features.
+ *
+ * @param descriptor pointer to an existing descriptor
+ * @param centered should the features be centered before computing the
+ *                 covariance
+ * @param covariance pointer to the first element of an array of `n_features
+ *                   x n_features` values, which will be filled by this
+ *                   function
+ * @param n_features number of features in the descriptor
+ *
+ * @returns The status code of this operation. If the status is not
+ *          `RASCAL_SUCCESS`, you can use `rascal_last_error()` to get the full
+ *          error message.
+ */
+rascal_status_t rascal_descriptor_feature_covariance(const struct rascal_descriptor_t *descriptor,
+                                                     bool centered,
+                                                     double *covariance,
+                                                     uintptr_t n_features);
+
+/**
+ * Compute the dot product (linear kernel) between the values of all samples
+ * in `descriptor` and all samples in `other`, and write it in `kernel`.
+ *
+ * If `normalize` is `true`, the dot product of each pair of samples is divided
+ * by the norms of both samples, giving the cosine similarity between the
+ * samples. Both descriptors must have the same features.
+ *
+ * @param descriptor pointer to an existing descriptor
+ * @param other pointer to another existing descriptor
+ * @param normalize should the dot products be normalized
+ * @param kernel pointer to the first element of a row-major array of
+ *               `n_rows x n_columns` values, which will be filled by this
+ *               function
+ * @param n_rows number of samples in `descriptor`
+ * @param n_columns number of samples in `other`
+ *
+ * @returns The status code of this operation. If the status is not
+ *          `RASCAL_SUCCESS`, you can use `rascal_last_error()` to get the full
+ *          error message.
+ */
+rascal_status_t rascal_descriptor_dot(const struct rascal_descriptor_t *descriptor,
+                                      const struct rascal_descriptor_t *other,
+                                      bool normalize,
+                                      double *kernel,
+                                      uintptr_t n_rows,
+                                      uintptr_t n_columns);
+
+/**
+ * Compute a block of the dot product between the samples in `descriptor` and
+ * the samples in `other` (see `rascal_descriptor_dot`), and write it in
+ * caller-owned memory.
+ *
+ * The block contains the rows `row_start` to `row_end` (excluded) of the full
+ * kernel, i.e. samples in `descriptor`, and the columns `column_start` to
+ * `column_end` (excluded), i.e. samples in `other`. The value for row `i` and
+ * column `j` of the block is written in `output[i * output_stride + j]`,
+ * allowing to write the block directly inside a larger matrix. This can be
+ * used to assemble kernel matrices too large to fit in memory tile by tile.
+ *
+ * @param descriptor pointer to an existing descriptor
+ * @param other pointer to another existing descriptor
+ * @param normalize should the dot products be normalized
+ * @param row_start index of the first row in the block
+ * @param row_end index of one past the last row in the block
+ * @param column_start index of the first column in the block
+ * @param column_end index of one past the last column in the block
+ * @param output pointer to the first element of the block
+ * @param output_stride number of elements between the start of two
+ *                      consecutive rows in `output`, which must be at least
+ *                      `column_end - column_start`
+ *
+ * @returns The status code of this operation. If the status is not
+ *          `RASCAL_SUCCESS`, you can use `rascal_last_error()` to get the full
+ *          error message.
+ */
+rascal_status_t rascal_descriptor_dot_block(const struct rascal_descriptor_t *descriptor,
+                                            const struct rascal_descriptor_t *other,
+                                            bool normalize,
+                                            uintptr_t row_start,
+                                            uintptr_t row_end,
+                                            uintptr_t column_start,
+                                            uintptr_t column_end,
+                                            double *output,
+                                            uintptr_t output_stride);
+
+/**
+ * Select a subset of the features of this `descriptor` such that the absolute
+ * value of the correlation between any pair of selected features (over all
+ * samples) is at most `threshold`.
+ *
+ * The features are considered in order, and a feature is kept if it is not
+ * correlated above `threshold` with any previously kept feature. Features
+ * with the same value for all samples are always removed. On output,
+ * `selected[i]` is `true` if the feature `i` is kept and `false` otherwise.
+ *
+ * @param descriptor pointer to an existing descriptor
+ * @param threshold maximal absolute correlation between selected features,
+ *                  between 0 and 1
+ * @param selected pointer to the first element of an array of `n_features`
+ *                 values, which will be filled by this function
+ * @param n_features number of features in the descriptor
+ *
+ * @returns The status code of this operation. If the status is not
+ *          `RASCAL_SUCCESS`, you can use `rascal_last_error()` to get the full
+ *          error message.
+ */
+rascal_status_t rascal_descriptor_uncorrelated_features(const struct rascal_descriptor_t *descriptor,
+                                                        double threshold,
+                                                        bool *selected,
+                                                        uintptr_t n_features);
+
+/**
+ * Select `count` samples of this `descriptor` using farthest point sampling
+ * over the rows of the values, with the given `distance`.
+ *
+ * The selection starts from the sample at index `initial`, or from the sample
+ * farthest from the mean of all samples if `initial` is negative. The indexes
+ * (i.e. rows) of the selected samples are written to `selected`, in the order
+ * they were selected.
+ *
+ * @param descriptor pointer to an existing descriptor
+ * @param count number of samples to select
+ * @param distance distance to use to compare samples
+ * @param initial index of the first sample to select, or a negative value to
+ *                start from the sample farthest from the mean
+ * @param selected pointer to the first element of an array of `count`
+ *                 values, which will be filled by this function
+ *
+ * @returns The status code of this operation. If the status is not
+ *          `RASCAL_SUCCESS`, you can use `rascal_last_error()` to get the full
+ *          error message.
+ */
+rascal_status_t rascal_descriptor_farthest_point_sampling(const struct rascal_descriptor_t *descriptor,
+                                                          uintptr_t count,
+                                                          enum rascal_sample_distance distance,
+                                                          int64_t initial,
+                                                          uintptr_t *selected);
+
+/**
+ * Compress the features of this `descriptor` to `width` new features, by
+ * multiplying the values and gradients with a fixed random matrix of the
+ * given kind of `projection`.
+ *
+ * The projection matrix is generated from `seed`, so descriptors projected
+ * with the same parameters can be compared with one another. After the
+ * projection, the features contain a single `projection` variable, going from
+ * 0 to `width - 1`.
+ *
+ * @param descriptor pointer to an existing descriptor
+ * @param width number of features after the projection
+ * @param projection kind of random matrix to use
+ * @param seed seed of the random number generator used to create the matrix
+ *
+ * @returns The status code of this operation. If the status is not
+ *          `RASCAL_SUCCESS`, you can use `rascal_last_error()` to get the full
+ *          error message.
+ */
+rascal_status_t rascal_descriptor_random_projection(struct rascal_descriptor_t *descriptor,
+                                                    uintptr_t width,
+                                                    enum rascal_random_projection projection,
+                                                    uint64_t seed);
+
+/**
+ * Get the values of this `descriptor` for all the atoms in the given
+ * `structure`, in the `values` array of size `n_atoms x n_features`. The row
+ * `i` of `values` will contain the values for the atom `i`, and atoms without
+ * corresponding sample will be filled with zeros.
+ *
+ * This is the layout used by molecular dynamics engines for per-atom
+ * quantities (for example LAMMPS `compute` styles). The samples of the
+ * descriptor must be `[structure, center]`, and additional variables can be
+ * moved to the features with `rascal_descriptor_densify` if needed.
+ *
+ * @param descriptor pointer to an existing descriptor
+ * @param structure index of the structure to get values for
+ * @param values pointer to the first element of an array of `n_atoms x
+ *               n_features` values, which will be filled by this function
+ * @param n_atoms number of atoms in the structure
+ * @param n_features number of features in the descriptor
+ *
+ * @returns The status code of this operation. If the status is not
+ *          `RASCAL_SUCCESS`, you can use `rascal_last_error()` to get the full
+ *          error message.
+ */
+rascal_status_t rascal_descriptor_per_atom_values(const struct rascal_descriptor_t *descriptor,
+                                                  uintptr_t structure,
+                                                  double *values,
+                                                  uintptr_t n_atoms,
+                                                  uintptr_t n_features);
+
+/**
+ * Scatter the gradients of this `descriptor` for the given `structure` onto
+ * the atoms, weighting each feature of each atom with `weights`.
+ *
+ * `weights` should contain `n_atoms x n_features` values, typically the
+ * derivative of the energy with respect to the descriptor values for each
+ * atom. The `output` array of size `n_atoms x 3` will then be filled with the
+ * gradient of the energy with respect to the positions of all the atoms,
+ * i.e. the opposite of the forces:
+ *
+ * ```text
+ * output[j, α] = Σ_i Σ_f weights[i, f] * ∂values[i, f] / ∂r_j[α]
+ * ```
+ *
+ * The samples of the descriptor must be `[structure, center]`, see
+ * `rascal_descriptor_per_atom_values`.
+ *
+ * @param descriptor pointer to an existing descriptor, containing gradients
+ * @param structure index of the structure to scatter gradients for
+ * @param weights pointer to the first element of an array of `n_atoms x
+ *                n_features` weights
+ * @param n_atoms number of atoms in the structure
+ * @param n_features number of features in the descriptor
+ * @param output pointer to the first element of an array of `n_atoms x 3`
+ *               values, which will be filled by this function
+ *
+ * @returns The status code of this operation. If the status is not
+ *          `RASCAL_SUCCESS`, you can use `rascal_last_error()` to get the full
+ *          error message.
+ */
+rascal_status_t rascal_descriptor_scatter_gradients(const struct rascal_descriptor_t *descriptor,
+                                                    uintptr_t structure,
+                                                    const double *weights,
+                                                    uintptr_t n_atoms,
+                                                    uintptr_t n_features,
+                                                    double *output);
+
+/**
+ * Update the values and gradients of `descriptor` with the ones in
+ * `partial`, which must contain a subset of the samples in `descriptor`.
+ *
+ * This is intended to be used when only some atoms moved since the last
+ * calculation: the descriptor can be re-computed for the affected centers
+ * only (see `rascal_system_affected_centers`) using the `selected_samples`
+ * calculation option, and then merged into the full descriptor.
+ *
+ * @param descriptor pointer to an existing descriptor, which will be updated
+ * @param partial pointer to an existing descriptor, containing the new values
+ *
+ * @returns The status code of this operation. If the status is not
+ *          `RASCAL_SUCCESS`, you can use `rascal_last_error()` to get the full
+ *          error message.
+ */
+rascal_status_t rascal_descriptor_update(struct rascal_descriptor_t *descriptor,
+                                         const struct rascal_descriptor_t *partial);
+
 /**
  * Create a new calculator with the given `name` and `parameters`.
  *
@@ -658,6 +1522,22 @@ rascal_status_t rascal_calculator_parameters(const struct rascal_calculator_t *c
                                              char *parameters,
                                              uintptr_t bufflen);
 
+/**
+ * Check if this `calculator` can compute gradients, i.e. if setting the
+ * `gradients` parameter to `true` is supported. This does not tell if this
+ * specific calculator was created with gradients enabled.
+ *
+ * @param calculator pointer to an existing calculator
+ * @param supported pointer to a boolean, will be set to `true` if the
+ *                  calculator supports gradients and `false` otherwise
+ *
+ * @returns The status code of this operation. If the status is not
+ *          `RASCAL_SUCCESS`, you can use `rascal_last_error()` to get the full
+ *          error message.
+ */
+rascal_status_t rascal_calculator_supports_gradients(const struct rascal_calculator_t *calculator,
+                                                     bool *supported);
+
 /**
  * Get the default number of features this `calculator` will produce in the
  * `features` parameter.
@@ -680,6 +1560,11 @@ rascal_status_t rascal_calculator_features_count(const struct rascal_calculator_
  * Run a calculation with the given `calculator` on the given `systems`,
  * storing the resulting data in the `descriptor`.
  *
+ * This function can be called concurrently from multiple threads, as long as
+ * each thread uses a different `descriptor` and different `systems`. This
+ * includes using the same `calculator` from multiple threads, in which case
+ * the calculations run in parallel using separate copies of the calculator.
+ *
  * @param calculator pointer to an existing calculator
  * @param descriptor pointer to an existing descriptor for data storage
  * @param systems pointer to an array of systems implementation
@@ -690,12 +1575,177 @@ rascal_status_t rascal_calculator_features_count(const struct rascal_calculator_
  *          `RASCAL_SUCCESS`, you can use `rascal_last_error()` to get the full
  *          error message.
  */
-rascal_status_t rascal_calculator_compute(struct rascal_calculator_t *calculator,
+rascal_status_t rascal_calculator_compute(const struct rascal_calculator_t *calculator,
                                           struct rascal_descriptor_t *descriptor,
                                           struct rascal_system_t *systems,
                                           uintptr_t systems_count,
                                           struct rascal_calculation_options_t options);
 
+/**
+ * Run a calculation with the given `calculator` on the given `systems`, only
+ * computing the samples associated with the given structures and centers.
+ *
+ * This is equivalent to calling `rascal_calculator_compute` with
+ * `options.selected_samples` containing the `structure` and `center`
+ * variables, and can be used to only update the environments of atoms that
+ * moved (for example in molecular dynamics). `structures` and `centers`
+ * should be arrays containing `count` elements each, where
+ * `(structures[i], centers[i])` is a center to include in the calculation. If
+ * `centers` is `NULL`, all the samples associated with the given `structures`
+ * are computed instead.
+ *
+ * @param calculator pointer to an existing calculator
+ * @param descriptor pointer to an existing descriptor for data storage
+ * @param systems pointer to an array of systems implementation
+ * @param systems_count number of systems in `systems`
+ * @param structures array of `count` structure indexes to compute
+ * @param centers array of `count` center indexes to compute, or `NULL`
+ * @param count number of elements in `structures` and `centers`
+ * @param options options for this calculation. `options.selected_samples`
+ *                must not be set when using this function.
+ *
+ * @returns The status code of this operation. If the status is not
+ *          `RASCAL_SUCCESS`, you can use `rascal_last_error()` to get the full
+ *          error message.
+ */
+rascal_status_t rascal_calculator_compute_partial(const struct rascal_calculator_t *calculator,
+                                                  struct rascal_descriptor_t *descriptor,
+                                                  struct rascal_system_t *systems,
+                                                  uintptr_t systems_count,
+                                                  const uintptr_t *structures,
+                                                  const uintptr_t *centers,
+                                                  uintptr_t count,
+                                                  struct rascal_calculation_options_t options);
+
+/**
+ * Get the shape of the arrays that a call to `rascal_calculator_compute` with
+ * the same `systems` and `options` would produce, without running the
+ * calculation.
+ *
+ * This function sets `*samples` to the number of samples (first dimension of
+ * the values array), `*features` to the number of features (second dimension
+ * of both the values and gradients arrays), and `*gradient_samples` to the
+ * number of gradient samples (first dimension of the gradients array), or 0
+ * if this calculator does not compute gradients.
+ *
+ * This can be used to allocate the buffers given to
+ * `rascal_calculator_compute_into`.
+ *
+ * @param calculator pointer to an existing calculator
+ * @param systems pointer to an array of systems implementation
+ * @param systems_count number of systems in `systems`
+ * @param options options for this calculation
+ * @param samples pointer to a single integer, will be set to the number of
+ *                samples
+ * @param features pointer to a single integer, will be set to the number of
+ *                 features
+ * @param gradient_samples pointer to a single integer, will be set to the
+ *                         number of gradient samples
+ *
+ * @returns The status code of this operation. If the status is not
+ *          `RASCAL_SUCCESS`, you can use `rascal_last_error()` to get the full
+ *          error message.
+ */
+rascal_status_t rascal_calculator_compute_shape(const struct rascal_calculator_t *calculator,
+                                                struct rascal_system_t *systems,
+                                                uintptr_t systems_count,
+                                                struct rascal_calculation_options_t options,
+                                                uintptr_t *samples,
+                                                uintptr_t *features,
+                                                uintptr_t *gradient_samples);
+
+/**
+ * Run a calculation with the given `calculator` on the given `systems`,
+ * storing the values and gradients in buffers provided by the caller.
+ *
+ * The size of the buffers should be determined beforehand with
+ * `rascal_calculator_compute_shape`: `values` must contain at least
+ * `samples * features` elements, and `gradients` at least `gradient_samples *
+ * features` elements. The data is stored using a row-major layout. If this
+ * calculator does not compute gradients, `gradients` can be `NULL`.
+ *
+ * The size of the buffers is checked before running the calculation, and
+ * this function returns `RASCAL_BUFFER_SIZE_ERROR` without modifying
+ * `descriptor` if they are too small.
+ *
+ * This function does not avoid the allocation of the values and gradients
+ * by rascaline: the calculation is performed in `descriptor`, and the data is
+ * then copied once to the buffers. `descriptor` contains the full values,
+ * gradients and corresponding indexes after this call, and can be released
+ * with `rascal_descriptor_free` as soon as the buffers have been filled.
+ *
+ * @param calculator pointer to an existing calculator
+ * @param descriptor pointer to an existing descriptor for metadata storage
+ * @param systems pointer to an array of systems implementation
+ * @param systems_count number of systems in `systems`
+ * @param options options for this calculation
+ * @param values buffer for the values
+ * @param values_count number of `double` available in `values`
+ * @param gradients buffer for the gradients, or `NULL`
+ * @param gradients_count number of `double` available in `gradients`
+ *
+ * @returns The status code of this operation. If the status is not
+ *          `RASCAL_SUCCESS`, you can use `rascal_last_error()` to get the full
+ *          error message.
+ */
+rascal_status_t rascal_calculator_compute_into(const struct rascal_calculator_t *calculator,
+                                               struct rascal_descriptor_t *descriptor,
+                                               struct rascal_system_t *systems,
+                                               uintptr_t systems_count,
+                                               struct rascal_calculation_options_t options,
+                                               double *values,
+                                               uintptr_t values_count,
+                                               double *gradients,
+                                               uintptr_t gradients_count);
+
+/**
+ * Create a new cancellation handle for the given `calculator`.
+ *
+ * The handle can be used from any thread to cancel the calculations running
+ * with `calculator` using `rascal_cancellation_cancel`. This function can be
+ * called while a calculation is running. The handle stays valid even after
+ * the calculator is freed.
+ *
+ * All memory allocated by this function can be released using
+ * `rascal_cancellation_free`.
+ *
+ * @param calculator pointer to an existing calculator
+ *
+ * @returns A pointer to the newly allocated cancellation handle, or a `NULL`
+ *          pointer in case of error. In case of error, you can use
+ *          `rascal_last_error()` to get the error message.
+ */
+struct rascal_cancellation_t *rascal_cancellation(const struct rascal_calculator_t *calculator);
+
+/**
+ * Request all the calculations currently running with the calculator
+ * associated with this `cancellation` handle to stop as soon as possible.
+ *
+ * The cancelled calculations will return `RASCAL_CANCELLED_ERROR`. If no
+ * calculation is currently running, the next one will be cancelled instead.
+ *
+ * @param cancellation pointer to an existing cancellation handle
+ *
+ * @returns The status code of this operation. If the status is not
+ *          `RASCAL_SUCCESS`, you can use `rascal_last_error()` to get the full
+ *          error message.
+ */
+rascal_status_t rascal_cancellation_cancel(const struct rascal_cancellation_t *cancellation);
+
+/**
+ * Free the memory associated with a `cancellation` handle previously created
+ * with `rascal_cancellation`.
+ *
+ * If `cancellation` is `NULL`, this function does nothing.
+ *
+ * @param cancellation pointer to an existing cancellation handle, or `NULL`
+ *
+ * @returns The status code of this operation. If the status is not
+ *          `RASCAL_SUCCESS`, you can use `rascal_last_error()` to get the
+ *          full error message.
+ */
+rascal_status_t rascal_cancellation_free(struct rascal_cancellation_t *cancellation);
+
 /**
  * Clear all collected profiling data
  *
@@ -733,7 +1783,11 @@ rascal_status_t rascal_profiling_enable(bool enabled);
  * See also `rascal_profiling_enable` and `rascal_profiling_clear`.
  *
  * @param format in which format should the data be provided. `"table"`,
- *              `"short_table"` and `"json"` are currently supported
+ *              `"short_table"`, `"json"` and `"call_tree"` are currently
+ *              supported. `"call_tree"` gives the data as a JSON call tree,
+ *              where each node contains the function `name`, the total
+ *              `elapsed` time in seconds, the `total_calls` and the number of
+ *              `calls` from the parent node, and the list of `children`.
  * @param buffer pre-allocated buffer in which profiling data will be copied.
  *               If the buffer is too small, this function will return
  *               `RASCAL_BUFFER_SIZE_ERROR`
@@ -745,6 +1799,81 @@ rascal_status_t rascal_profiling_enable(bool enabled);
  */
 rascal_status_t rascal_profiling_get(const char *format, char *buffer, uintptr_t bufflen);
 
+/**
+ * Get the version of rascaline used to create the shared library, as a
+ * NULL-terminated `"major.minor.patch"` string.
+ *
+ * The returned string is owned by rascaline, and must not be freed.
+ *
+ * @returns the version of rascaline
+ */
+const char *rascal_version(void);
+
+/**
+ * Get the names of all the calculators that can be created with
+ * `rascal_calculator`, as a JSON array of strings. The names are sorted in
+ * alphabetical order.
+ *
+ * @param buffer pre-allocated buffer in which the JSON array will be copied.
+ *               If the buffer is too small, this function will return
+ *               `RASCAL_BUFFER_SIZE_ERROR`
+ * @param bufflen size of the `buffer`
+ *
+ * @returns The status code of this operation. If the status is not
+ *          `RASCAL_SUCCESS`, you can use `rascal_last_error()` to get the full
+ *          error message.
+ */
+rascal_status_t rascal_calculators_list(char *buffer, uintptr_t bufflen);
+
+/**
+ * Get the JSON schema describing the parameters of the calculator with the
+ * given `name`, as a JSON string. This can be used to discover the available
+ * parameters of calculators at runtime.
+ *
+ * @param name NULL-terminated name of the calculator, as returned by
+ *             `rascal_calculators_list`
+ * @param buffer pre-allocated buffer in which the JSON schema will be copied.
+ *               If the buffer is too small, this function will return
+ *               `RASCAL_BUFFER_SIZE_ERROR`
+ * @param bufflen size of the `buffer`
+ *
+ * @returns The status code of this operation. If the status is not
+ *          `RASCAL_SUCCESS`, you can use `rascal_last_error()` to get the full
+ *          error message.
+ */
+rascal_status_t rascal_calculators_schema(const char *name, char *buffer, uintptr_t bufflen);
+
+/**
+ * Check if the calculator with the given `name` can compute gradients, i.e.
+ * if it accepts a `gradients` parameter, without having to create a
+ * calculator first.
+ *
+ * @param name NULL-terminated name of the calculator, as returned by
+ *             `rascal_calculators_list`
+ * @param supported pointer to a boolean, will be set to `true` if the
+ *                  calculator supports gradients and `false` otherwise
+ *
+ * @returns The status code of this operation. If the status is not
+ *          `RASCAL_SUCCESS`, you can use `rascal_last_error()` to get the full
+ *          error message.
+ */
+rascal_status_t rascal_calculators_supports_gradients(const char *name, bool *supported);
+
+/**
+ * Check if the optional `feature` was enabled when compiling rascaline. The
+ * only optional feature is currently `"chemfiles"`, which is required to use
+ * `rascal_basic_systems_read`. Unknown features are reported as disabled.
+ *
+ * @param feature NULL-terminated name of the feature
+ * @param enabled pointer to a boolean, will be set to `true` if the feature
+ *                is enabled and `false` otherwise
+ *
+ * @returns The status code of this operation. If the status is not
+ *          `RASCAL_SUCCESS`, you can use `rascal_last_error()` to get the full
+ *          error message.
+ */
+rascal_status_t rascal_has_feature(const char *feature, bool *enabled);
+
 #ifdef __cplusplus
 } // extern "C"
 #endif // __cplusplus
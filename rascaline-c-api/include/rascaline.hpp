@@ -11,6 +11,7 @@
 #include <string>
 #include <vector>
 #include <mutex>
+#include <memory>
 #include <utility>
 #include <stdexcept>
 #include <exception>
@@ -30,8 +31,9 @@ namespace rascaline {
 /// Exception class for all error thrown by rascaline
 class RascalError : public std::runtime_error {
 public:
-    /// Create a new error with the given message
-    RascalError(std::string message): std::runtime_error(message) {}
+    /// Create a new error with the given message and status code
+    RascalError(std::string message, rascal_status_t status = RASCAL_INTERNAL_ERROR):
+        std::runtime_error(message), status_(status) {}
     ~RascalError() = default;
 
     /// RascalError is copy-constructible
@@ -42,6 +44,15 @@ public:
     RascalError& operator=(const RascalError&) = default;
     /// RascalError can be move-assigned
     RascalError& operator=(RascalError&&) = default;
+
+    /// Get the status code associated with this error, which can be used to
+    /// check the kind of error (e.g. `RASCAL_CANCELLED_ERROR`).
+    rascal_status_t status() const {
+        return status_;
+    }
+
+private:
+    rascal_status_t status_;
 };
 
 namespace details {
@@ -129,7 +140,7 @@ namespace details {
     /// with the latest error message if the status is not `RASCAL_SUCCESS`.
     inline void check_status(rascal_status_t status) {
         if (status > RASCAL_SUCCESS) {
-            throw RascalError(rascal_last_error());
+            throw RascalError(rascal_last_error(), status);
         } else if (status < RASCAL_SUCCESS) {
             // this error comes from C++, let's restore it and pass it up
             auto exception = GlobalExceptionsStore::extract_exception(status);
@@ -217,6 +228,16 @@ public:
     /// `System::pairs_containing(j)`.
     virtual const std::vector<rascal_pair_t>& pairs_containing(uintptr_t center) const = 0;
 
+    /// Get a pointer to the first element of a contiguous array containing the
+    /// per-atom data with the given `name` (for example partial charges), or
+    /// `nullptr` if this system does not contain such data. The array should
+    /// contain `System::size()` elements.
+    ///
+    /// The default implementation does not provide any data.
+    virtual const double* data(const std::string& name) const {
+        return nullptr;
+    }
+
     /// Convert a child instance of the `System` class to a `rascal_system_t` to
     /// be passed to the rascaline functions.
     ///
@@ -224,6 +245,8 @@ public:
     /// directly.
     rascal_system_t as_rascal_system_t() {
         return rascal_system_t {
+            // version
+            RASCAL_SYSTEM_VERSION,
             // user_data
             static_cast<void*>(this),
             // size
@@ -272,7 +295,21 @@ public:
                     *pairs = cpp_pairs.data();
                     *size = cpp_pairs.size();
                 );
-            }
+            },
+            // cell_shifts
+            nullptr,
+            // data
+            [](const void* self, const char* name, const double** data) {
+                RASCAL_SYSTEM_CATCH_EXCEPTIONS(
+                    *data = reinterpret_cast<const System*>(self)->data(name);
+                );
+            },
+            // triplets_containing
+            nullptr,
+            // pairs_with_cutoff
+            nullptr,
+            // pairs_containing_with_cutoff
+            nullptr,
         };
     }
 };
@@ -341,6 +378,108 @@ private:
 };
 
 
+/// A single system created from raw arrays of atomic species and positions,
+/// using the neighbor list implementation in rascaline. This is a convenience
+/// class for codes (such as molecular dynamics engines) that already store
+/// their atoms in contiguous arrays.
+class BasicSystem {
+public:
+    /// Create a new system from the atomic `species` and `positions`, copying
+    /// the data. `positions` should contain `3 x species.size()` values, with
+    /// the positions of each atom stored contiguously. The `cell` is the unit
+    /// cell matrix in row major order; a matrix full of zeros corresponds to a
+    /// non-periodic system.
+    ///
+    /// @throws RascalError if the data is not valid
+    BasicSystem(
+        const std::vector<int32_t>& species,
+        const std::vector<double>& positions,
+        System::CellMatrix cell = System::CellMatrix{}
+    ) {
+        if (positions.size() != 3 * species.size()) {
+            throw RascalError(
+                "expected 3 x n_atoms values in positions", RASCAL_INVALID_PARAMETER_ERROR
+            );
+        }
+
+        auto is_periodic = false;
+        for (const auto& row: cell) {
+            for (auto value: row) {
+                is_periodic = is_periodic || value != 0.0;
+            }
+        }
+
+        details::check_status(rascal_basic_system(
+            species.data(),
+            positions.data(),
+            species.size(),
+            is_periodic ? &cell[0][0] : nullptr,
+            &system_
+        ));
+    }
+
+    ~BasicSystem() {
+        details::check_status(rascal_basic_system_free(&system_));
+    }
+
+    /// BasicSystem is **NOT** copy-constructible
+    BasicSystem(const BasicSystem&) = delete;
+    /// BasicSystem can **NOT** be copy-assigned
+    BasicSystem& operator=(const BasicSystem&) = delete;
+
+    /// BasicSystem is move-constructible
+    BasicSystem(BasicSystem&& other) {
+        *this = std::move(other);
+    }
+
+    /// BasicSystem can be move-assigned
+    BasicSystem& operator=(BasicSystem&& other) {
+        this->~BasicSystem();
+        this->system_ = rascal_system_t{};
+
+        std::swap(this->system_, other.system_);
+
+        return *this;
+    }
+
+    /// Use the given `pairs` as the neighbor list of this system for
+    /// calculations using the given `cutoff` (or a smaller one), instead of
+    /// computing it in rascaline.
+    void set_pairs(double cutoff, const std::vector<rascal_pair_t>& pairs) {
+        details::check_status(rascal_basic_system_set_pairs(
+            &system_, cutoff, pairs.data(), pairs.size()
+        ));
+    }
+
+    /// Get the sorted list of centers whose atomic environment (up to the
+    /// given `cutoff`) contains any of the `moved` atoms.
+    std::vector<uintptr_t> affected_centers(double cutoff, const std::vector<uintptr_t>& moved) {
+        uintptr_t size = 0;
+        details::check_status(system_.size(system_.user_data, &size));
+
+        auto centers = std::vector<uintptr_t>(size, 0);
+        uintptr_t count = 0;
+        details::check_status(rascal_system_affected_centers(
+            &system_, cutoff, moved.data(), moved.size(), centers.data(), &count
+        ));
+        centers.resize(count);
+
+        return centers;
+    }
+
+    /// Get the underlying `rascal_system_t`.
+    ///
+    /// This is an advanced function that most users don't need to call
+    /// directly.
+    rascal_system_t* as_rascal_system_t() {
+        return &system_;
+    }
+
+private:
+    rascal_system_t system_ = {};
+};
+
+
 /// An `ArrayView` is view inside a rust-owned 2D array, similar to std::span
 /// for 2D arrays.
 ///
@@ -373,6 +512,30 @@ public:
     ArrayView(T* data, std::array<size_t, 2> shape):
         ArrayView(data, shape, false) {}
 
+    /// Create a new `ArrayView` pointing to `const` memory with the given
+    /// `shape` and `strides`.
+    ///
+    /// The element at index `i, j` is stored at `data[i * strides[0] + j *
+    /// strides[1]]`. The resulting `ArrayView` is only valid for as long as
+    /// `data` is.
+    ArrayView(const T* data, std::array<size_t, 2> shape, std::array<size_t, 2> strides):
+        ArrayView(data, shape, true)
+    {
+        strides_ = strides;
+    }
+
+    /// Create a new `ArrayView` pointing to non-`const` memory with the given
+    /// `shape` and `strides`.
+    ///
+    /// The element at index `i, j` is stored at `data[i * strides[0] + j *
+    /// strides[1]]`. The resulting `ArrayView` is only valid for as long as
+    /// `data` is.
+    ArrayView(T* data, std::array<size_t, 2> shape, std::array<size_t, 2> strides):
+        ArrayView(data, shape, false)
+    {
+        strides_ = strides;
+    }
+
     ~ArrayView() {
         // no memory to release
     }
@@ -389,7 +552,7 @@ public:
     /// Get a the value inside this `ArrayView` at index `i, j`
     T operator()(size_t i, size_t j) const {
         assert(i < shape_[0] && j < shape_[1]);
-        return data_[i * shape_[1] + j];
+        return data_[i * strides_[0] + j * strides_[1]];
     }
 
     /// Get the data pointer for this array, i.e. the pointer to the first
@@ -413,6 +576,12 @@ public:
         return shape_;
     }
 
+    /// Get the strides of this array, i.e. the distance (in number of
+    /// elements) between two consecutive elements along each axis
+    std::array<size_t, 2> strides() const {
+        return strides_;
+    }
+
     /// Check if this array is empty, i.e. if at least one of the shape element
     /// is 0.
     bool is_empty() const {
@@ -427,6 +596,7 @@ private:
     ArrayView(const T* data, std::array<size_t, 2> shape, bool is_const):
         data_(const_cast<T*>(data)),
         shape_(shape),
+        strides_({shape[1], 1}),
         is_const_(is_const)
     {
         if (shape_[0] != 0 && shape_[1] != 0) {
@@ -444,6 +614,7 @@ private:
     bool is_const_ = true;
     T* data_ = nullptr;
     std::array<size_t, 2> shape_ = {0};
+    std::array<size_t, 2> strides_ = {0};
 };
 
 
@@ -596,19 +767,20 @@ private:
     std::vector<std::string> names_;
 };
 
-/// Small wrapper around `malloc`'ed array, taking ownership of this array and
-/// `free`ing it on destruction
+/// Small wrapper around an array allocated by rascaline, taking ownership of
+/// this array and releasing it with `rascal_free` on destruction
 template<typename T>
 class MallocArray {
 public:
     /// Create a new `MallocArray` with the given pointer and size. The pointer
-    /// MUST have been allocated with either `malloc`, `calloc` or `realloc`.
+    /// MUST have been allocated by rascaline, using the allocator set with
+    /// `rascal_set_allocator`.
     MallocArray(T* data, size_t size): data_(data), size_(size) {}
 
     MallocArray(): MallocArray(nullptr, 0) {}
 
     ~MallocArray() {
-        std::free(data_);
+        rascal_free(data_);
     }
 
     MallocArray(const MallocArray&) = delete;
@@ -715,7 +887,7 @@ public:
             descriptor_, &data, &samples, &features
         ));
 
-        return ArrayView<double>(data, {samples, features});
+        return this->array_with_layout(data, samples, features);
     }
 
     /// Get the values stored inside this descriptor after a call to
@@ -731,7 +903,22 @@ public:
             descriptor_, &data, &samples, &features
         ));
 
-        return ArrayView<double>(data, {samples, features});
+        return this->array_with_layout(data, samples, features);
+    }
+
+    /// Get the memory layout used by the values and gradients arrays of this
+    /// descriptor.
+    rascal_memory_layout layout() const {
+        auto layout = RASCAL_MEMORY_LAYOUT_ROW_MAJOR;
+        details::check_status(rascal_descriptor_layout(descriptor_, &layout));
+        return layout;
+    }
+
+    /// Set the memory layout used by the values and gradients arrays of this
+    /// descriptor, converting the existing arrays if needed. The layout is
+    /// kept for all subsequent calculations using this descriptor.
+    void set_layout(rascal_memory_layout layout) {
+        details::check_status(rascal_descriptor_set_layout(descriptor_, layout));
     }
 
     /// Get metdata describing the samples/rows in `Descriptor::values`.
@@ -877,6 +1064,142 @@ public:
         );
     }
 
+    /// Save this descriptor to the file at `path`, overwriting any existing
+    /// file. The descriptor can be loaded back with `Descriptor::load`.
+    void save(const std::string& path) const {
+        details::check_status(rascal_descriptor_save(descriptor_, path.c_str()));
+    }
+
+    /// Load the descriptor stored in the file at `path` (created with
+    /// `Descriptor::save`) into this descriptor, replacing any existing data.
+    void load(const std::string& path) {
+        details::check_status(rascal_descriptor_load(descriptor_, path.c_str()));
+    }
+
+    /// Serialize this descriptor to a binary buffer in memory, which can be
+    /// loaded back with `Descriptor::from_buffer`.
+    MallocArray<uint8_t> to_buffer() const {
+        uint8_t* buffer = nullptr;
+        uintptr_t buffer_size = 0;
+        details::check_status(
+            rascal_descriptor_to_buffer(descriptor_, &buffer, &buffer_size)
+        );
+        return MallocArray<uint8_t>(buffer, buffer_size);
+    }
+
+    /// Load the descriptor serialized in `buffer` (created with
+    /// `Descriptor::to_buffer`) into this descriptor, replacing any existing
+    /// data.
+    void from_buffer(const uint8_t* buffer, size_t buffer_size) {
+        details::check_status(
+            rascal_descriptor_from_buffer(descriptor_, buffer, buffer_size)
+        );
+    }
+
+    /// Write all the arrays in this descriptor to separate `.npy` files
+    /// inside `directory`, creating the directory if needed.
+    void write_npy(const std::string& directory) const {
+        details::check_status(rascal_descriptor_write_npy(descriptor_, directory.c_str()));
+    }
+
+    /// Compute the `features().shape(0) x features().shape(0)` Gram matrix
+    /// `X^T X` of the values `X` of this descriptor, summing over all samples.
+    /// If `centered` is `true`, the mean of each feature is subtracted from
+    /// the values first.
+    std::vector<double> feature_covariance(bool centered = false) const {
+        auto n_features = this->values().shape()[1];
+        auto covariance = std::vector<double>(n_features * n_features, 0.0);
+        details::check_status(rascal_descriptor_feature_covariance(
+            descriptor_, centered, covariance.data(), n_features
+        ));
+        return covariance;
+    }
+
+    /// Select the features of this descriptor such that the absolute value of
+    /// the correlation between any pair of selected features is at most
+    /// `threshold`. Features with the same value for all samples are always
+    /// removed. This returns a vector with one entry per feature, set to `true`
+    /// if the feature is selected.
+    std::vector<bool> uncorrelated_features(double threshold) const {
+        auto n_features = this->values().shape()[1];
+        auto selected = std::unique_ptr<bool[]>(new bool[n_features]);
+        details::check_status(rascal_descriptor_uncorrelated_features(
+            descriptor_, threshold, selected.get(), n_features
+        ));
+        return std::vector<bool>(selected.get(), selected.get() + n_features);
+    }
+
+    /// Select `count` samples of this descriptor with farthest point sampling
+    /// over the rows of the values, using the given `distance`. The selection
+    /// starts from the sample at index `initial`, or from the sample farthest
+    /// from the mean if `initial` is negative. This returns the indexes (i.e.
+    /// rows) of the selected samples, in the order they were selected.
+    std::vector<size_t> farthest_point_sampling(
+        size_t count,
+        rascal_sample_distance distance = RASCAL_SAMPLE_DISTANCE_EUCLIDEAN,
+        int64_t initial = -1
+    ) const {
+        auto selected = std::vector<size_t>(count, 0);
+        details::check_status(rascal_descriptor_farthest_point_sampling(
+            descriptor_, count, distance, initial, selected.data()
+        ));
+        return selected;
+    }
+
+    /// Compress the features of this descriptor to `width` new features, by
+    /// multiplying the values and gradients with a fixed random matrix of the
+    /// given kind of `projection`, generated from `seed`.
+    void random_projection(
+        size_t width,
+        rascal_random_projection projection = RASCAL_RANDOM_PROJECTION_GAUSSIAN,
+        uint64_t seed = 0
+    ) {
+        details::check_status(rascal_descriptor_random_projection(
+            descriptor_, width, projection, seed
+        ));
+    }
+
+    /// Get the values of this descriptor for all the `n_atoms` atoms in the
+    /// given `structure`, as a row-major `n_atoms x n_features` array. Atoms
+    /// without corresponding sample are filled with zeros.
+    std::vector<double> per_atom_values(size_t structure, size_t n_atoms) const {
+        auto n_features = this->values().shape()[1];
+        auto values = std::vector<double>(n_atoms * n_features, 0.0);
+        details::check_status(rascal_descriptor_per_atom_values(
+            descriptor_, structure, values.data(), n_atoms, n_features
+        ));
+        return values;
+    }
+
+    /// Scatter the gradients of this descriptor for the given `structure` onto
+    /// the atoms, weighting each feature of each atom with `weights` (a
+    /// row-major `n_atoms x n_features` array). This returns a row-major
+    /// `n_atoms x 3` array, containing the opposite of the forces when
+    /// `weights` is the derivative of the energy with respect to the values.
+    std::vector<double> scatter_gradients(size_t structure, const std::vector<double>& weights, size_t n_atoms) const {
+        auto n_features = this->values().shape()[1];
+        if (weights.size() != n_atoms * n_features) {
+            throw RascalError(
+                "expected n_atoms x n_features values in weights", RASCAL_INVALID_PARAMETER_ERROR
+            );
+        }
+
+        auto output = std::vector<double>(n_atoms * 3, 0.0);
+        details::check_status(rascal_descriptor_scatter_gradients(
+            descriptor_, structure, weights.data(), n_atoms, n_features, output.data()
+        ));
+        return output;
+    }
+
+    /// Update the values and gradients of this descriptor with the ones in
+    /// `partial`, which must contain a subset of the samples in this
+    /// descriptor.
+    void update(const Descriptor& partial) {
+        details::check_status(rascal_descriptor_update(
+            descriptor_, partial.as_rascal_descriptor_t()
+        ));
+    }
+
     /// Get the underlying pointer to a `rascal_descriptor_t`.
     ///
     /// This is an advanced function that most users don't need to call
@@ -908,6 +1231,16 @@ private:
         return Indexes(std::move(names), std::move(array));
     }
 
+    /// Create an `ArrayView` for the values or gradients data, taking the
+    /// memory layout of this descriptor into account.
+    ArrayView<double> array_with_layout(double* data, size_t samples, size_t features) const {
+        if (this->layout() == RASCAL_MEMORY_LAYOUT_FEATURE_MAJOR) {
+            return ArrayView<double>(data, {samples, features}, {1, samples});
+        } else {
+            return ArrayView<double>(data, {samples, features});
+        }
+    }
+
     rascal_descriptor_t* descriptor_ = nullptr;
 };
 
@@ -928,6 +1261,37 @@ public:
     /// run the calculation on all features.
     SelectedIndexes selected_features = SelectedIndexes();
 
+    /// Number of threads to use for this calculation. Use 0 to use the
+    /// default global thread pool, which can be configured with the
+    /// `RAYON_NUM_THREADS` environment variable.
+    size_t threads = 0;
+
+    /// Strategy used to run the calculation in parallel, either over samples
+    /// within each system or over whole systems.
+    rascal_parallelism parallelism = RASCAL_PARALLELISM_SAMPLES;
+
+    /// Check that all values and gradients are finite after the calculation,
+    /// and throw an error pointing to the first NaN or infinite entry
+    /// otherwise. This is intended for debugging.
+    bool check_finite = false;
+
+    /// Make sure the results of the calculation do not depend on the number
+    /// of threads or the parallelization strategy, at the cost of a slower
+    /// calculation.
+    bool deterministic = false;
+
+    /// Compute the neighbor list of each system once with this cutoff, and
+    /// filter it when a smaller cutoff is requested, instead of re-computing
+    /// a neighbor list for each cutoff. Use 0 to disable this behavior. This
+    /// requires `use_native_system`.
+    double neighbors_cutoff = 0;
+
+    /// Only compute the values during the calculation, and compute the
+    /// gradients on the first call to `Descriptor::gradients` (or any other
+    /// function needing them). This requires the calculator to compute
+    /// gradients.
+    bool lazy_gradients = false;
+
     /// Convert this instance of `CalculationOptions` to a
     /// `rascal_calculation_options_t`.
     ///
@@ -936,6 +1300,12 @@ public:
     rascal_calculation_options_t as_rascal_calculation_options_t() const {
         auto options = rascal_calculation_options_t{};
         options.use_native_system = this->use_native_system;
+        options.threads = this->threads;
+        options.parallelism = this->parallelism;
+        options.check_finite = this->check_finite;
+        options.deterministic = this->deterministic;
+        options.neighbors_cutoff = this->neighbors_cutoff;
+        options.lazy_gradients = this->lazy_gradients;
 
         if (this->selected_samples.size() == 0) {
             std::memset(&options.selected_samples, 0, sizeof(rascal_indexes_t));
@@ -960,6 +1330,56 @@ public:
 };
 
 
+/// A `Cancellation` handle can be used to cancel calculations running with a
+/// `Calculator` from another thread. Such handles are created with
+/// `Calculator::cancellation`.
+class Cancellation final {
+public:
+    ~Cancellation() {
+        details::check_status(rascal_cancellation_free(this->cancellation_));
+    }
+
+    /// Cancellation is **NOT** copy-constructible
+    Cancellation(const Cancellation&) = delete;
+    /// Cancellation can **NOT** be copy-assigned
+    Cancellation& operator=(const Cancellation&) = delete;
+
+    /// Cancellation is move-constructible
+    Cancellation(Cancellation&& other) {
+        *this = std::move(other);
+    }
+
+    /// Cancellation can be move-assigned
+    Cancellation& operator=(Cancellation&& other) {
+        this->~Cancellation();
+        this->cancellation_ = nullptr;
+
+        std::swap(this->cancellation_, other.cancellation_);
+
+        return *this;
+    }
+
+    /// Request the calculation currently running with the corresponding
+    /// calculator to stop as soon as possible. The calculation will then throw
+    /// a `RascalError` with the `RASCAL_CANCELLED_ERROR` status. If no
+    /// calculation is running, the next one will be cancelled instead.
+    void cancel() const {
+        details::check_status(rascal_cancellation_cancel(this->cancellation_));
+    }
+
+private:
+    friend class Calculator;
+
+    explicit Cancellation(rascal_cancellation_t* cancellation): cancellation_(cancellation) {
+        if (this->cancellation_ == nullptr) {
+            throw RascalError(rascal_last_error());
+        }
+    }
+
+    rascal_cancellation_t* cancellation_ = nullptr;
+};
+
+
 /// The `Calculator` class implements the calculation of a given atomic scale
 /// representation. Specific implementation are registered globally, and
 /// requested at construction.
@@ -1045,6 +1465,16 @@ public:
         }
     }
 
+    /// Check if this calculator can compute gradients, i.e. if setting the
+    /// `gradients` parameter to `true` is supported.
+    bool supports_gradients() const {
+        bool supported = false;
+        details::check_status(rascal_calculator_supports_gradients(
+            calculator_, &supported
+        ));
+        return supported;
+    }
+
     /// Get the default number of features this calculator will produce.
     ///
     /// This number corresponds to the size of second dimension of the `values`
@@ -1108,6 +1538,70 @@ public:
         return descriptor;
     }
 
+    /// Run a calculation with this `calculator` on a single `system`, storing
+    /// the resulting data in the `descriptor`. Options for this calculation
+    /// can be passed in `options`.
+    void compute(BasicSystem& system, Descriptor& descriptor, CalculationOptions options = CalculationOptions()) const {
+        details::check_status(rascal_calculator_compute(
+            calculator_,
+            descriptor.as_rascal_descriptor_t(),
+            system.as_rascal_system_t(),
+            1,
+            options.as_rascal_calculation_options_t()
+        ));
+    }
+
+    /// Run a calculation with this `calculator` on a single `system`, and
+    /// return the resulting data in a new `Descriptor`. Options for this
+    /// calculation can be passed in `options`.
+    Descriptor compute(BasicSystem& system, CalculationOptions options = CalculationOptions()) const {
+        auto descriptor = Descriptor();
+        this->compute(system, descriptor, std::move(options));
+        return descriptor;
+    }
+
+    /// Run a calculation with this `calculator` on the given `systems`, only
+    /// computing the samples associated with the given `structures` and
+    /// `centers` (which must have the same size), and storing the resulting
+    /// data in the `descriptor`. If `centers` is empty, all the samples
+    /// associated with the given `structures` are computed.
+    void compute_partial(
+        std::vector<System*> systems,
+        Descriptor& descriptor,
+        const std::vector<uintptr_t>& structures,
+        const std::vector<uintptr_t>& centers = std::vector<uintptr_t>(),
+        CalculationOptions options = CalculationOptions()
+    ) const {
+        if (!centers.empty() && centers.size() != structures.size()) {
+            throw RascalError(
+                "structures and centers must have the same size", RASCAL_INVALID_PARAMETER_ERROR
+            );
+        }
+
+        auto rascal_systems = std::vector<rascal_system_t>();
+        for (auto& system: systems) {
+            assert(system != nullptr);
+            rascal_systems.push_back(system->as_rascal_system_t());
+        }
+
+        details::check_status(rascal_calculator_compute_partial(
+            calculator_,
+            descriptor.as_rascal_descriptor_t(),
+            rascal_systems.data(),
+            rascal_systems.size(),
+            structures.data(),
+            centers.empty() ? nullptr : centers.data(),
+            structures.size(),
+            options.as_rascal_calculation_options_t()
+        ));
+    }
+
+    /// Create a new `Cancellation` handle, which can be used to cancel
+    /// calculations running with this calculator from another thread.
+    Cancellation cancellation() const {
+        return Cancellation(rascal_cancellation(calculator_));
+    }
+
     /// Get the underlying pointer to a `rascal_calculator_t`.
     ///
     /// This is an advanced function that most users don't need to call
@@ -1186,6 +1680,72 @@ private:
     Profiler();
 };
 
+/// Get the version of rascaline used to create the shared library, as a
+/// `"major.minor.patch"` string
+inline std::string version() {
+    return std::string(rascal_version());
+}
+
+/// Get the names of all the calculators that can be created with the
+/// `Calculator` constructor, in alphabetical order
+inline std::vector<std::string> calculators_list() {
+    auto buffer = std::vector<char>(256, '\0');
+    while (true) {
+        auto status = rascal_calculators_list(&buffer[0], buffer.size());
+        if (status != RASCAL_BUFFER_SIZE_ERROR) {
+            details::check_status(status);
+            break;
+        }
+
+        // grow the buffer and retry
+        buffer.resize(buffer.size() * 2, '\0');
+    }
+
+    // the names are given as a JSON array of strings. Calculators names do not
+    // contain any quote, so we only need to extract the text between quotes.
+    auto names = std::vector<std::string>();
+    auto json = std::string(buffer.data());
+    auto start = json.find('"');
+    while (start != std::string::npos) {
+        auto end = json.find('"', start + 1);
+        names.emplace_back(json.substr(start + 1, end - start - 1));
+        start = json.find('"', end + 1);
+    }
+    return names;
+}
+
+/// Get the JSON schema describing the parameters of the calculator with the
+/// given `name`
+inline std::string calculator_schema(const std::string& name) {
+    auto buffer = std::vector<char>(1024, '\0');
+    while (true) {
+        auto status = rascal_calculators_schema(name.c_str(), &buffer[0], buffer.size());
+        if (status != RASCAL_BUFFER_SIZE_ERROR) {
+            details::check_status(status);
+            return std::string(buffer.data());
+        }
+
+        // grow the buffer and retry
+        buffer.resize(buffer.size() * 2, '\0');
+    }
+}
+
+/// Check if the calculator with the given `name` can compute gradients,
+/// without having to create a calculator first
+inline bool calculator_supports_gradients(const std::string& name) {
+    bool supported = false;
+    details::check_status(rascal_calculators_supports_gradients(name.c_str(), &supported));
+    return supported;
+}
+
+/// Check if the optional `feature` (e.g. `"chemfiles"`) was enabled when
+/// compiling rascaline
+inline bool has_feature(const std::string& feature) {
+    bool enabled = false;
+    details::check_status(rascal_has_feature(feature.c_str(), &enabled));
+    return enabled;
+}
+
 }
 
 #endif
@@ -1,30 +1,169 @@
+use std::collections::BTreeSet;
 use std::os::raw::c_char;
 use std::ffi::CStr;
-use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
 
-use rascaline::{Calculator, System, Error, CalculationOptions, SelectedIndexes};
-use rascaline::descriptor::IndexesBuilder;
+use rascaline::{Calculator, Error, CalculationOptions, CancellationHandle, Parallelism, SelectedIndexes};
+use rascaline::descriptor::{IndexesBuilder, IndexValue};
 
 use super::utils::copy_str_to_c;
 use super::{catch_unwind, rascal_status_t};
 
 use super::descriptor::{rascal_descriptor_t, rascal_indexes_t};
-use super::system::rascal_system_t;
+use super::system::{rascal_system_t, systems_from_c};
 
-/// Opaque type representing a `Calculator`
+/// Opaque type representing a `Calculator`.
+///
+/// All functions taking a calculator can be called concurrently from multiple
+/// threads, including multiple calculations using the same calculator. Each
+/// calculation running at the same time uses a separate copy of the
+/// calculator, and the copies are kept around to be re-used by later
+/// calculations.
 #[allow(non_camel_case_types)]
-pub struct rascal_calculator_t(Calculator);
+pub struct rascal_calculator_t {
+    /// name used to create the calculator, used to create more copies
+    registered_name: String,
+    /// name of the calculator, as given by `Calculator::name`
+    name: String,
+    /// parameters used to create the calculator
+    parameters: String,
+    /// can this calculator compute gradients?
+    supports_gradients: bool,
+    /// number of default features for this calculator
+    features_count: usize,
+    /// copies of the calculator which are not currently used by a calculation
+    idle: Mutex<Vec<Calculator>>,
+    /// calculations currently running with this calculator, used to cancel
+    /// them
+    running: Arc<Mutex<RunningCalculations>>,
+}
+
+/// Lock the given `mutex`, ignoring poisoning. A panic while the lock was held
+/// is reported to the caller as an internal error, and the data protected by
+/// the mutexes in this file stays valid afterward.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+impl rascal_calculator_t {
+    fn new(registered_name: &str, calculator: Calculator) -> rascal_calculator_t {
+        rascal_calculator_t {
+            registered_name: registered_name.into(),
+            name: calculator.name(),
+            parameters: calculator.parameters().into(),
+            supports_gradients: calculator.supports_gradients(),
+            features_count: calculator.default_features().count(),
+            idle: Mutex::new(vec![calculator]),
+            running: Arc::new(Mutex::new(RunningCalculations::default())),
+        }
+    }
+
+    /// Get a copy of the calculator to use in a calculation, re-using an idle
+    /// copy if possible and creating a new one otherwise. The copy is given
+    /// back when the returned value is dropped.
+    fn acquire(&self) -> Result<AcquiredCalculator<'_>, Error> {
+        let idle = lock(&self.idle).pop();
+        let calculator = match idle {
+            Some(calculator) => calculator,
+            None => Calculator::new(&self.registered_name, self.parameters.clone())?,
+        };
+
+        let id = lock(&self.running).start(calculator.cancellation_handle());
+        return Ok(AcquiredCalculator {
+            owner: self,
+            calculator: Some(calculator),
+            id: id,
+        });
+    }
+}
 
-impl Deref for rascal_calculator_t {
+/// A copy of the calculator in a `rascal_calculator_t`, used by a single
+/// calculation at the time.
+struct AcquiredCalculator<'a> {
+    owner: &'a rascal_calculator_t,
+    calculator: Option<Calculator>,
+    id: usize,
+}
+
+impl std::ops::Deref for AcquiredCalculator<'_> {
     type Target = Calculator;
-    fn deref(&self) -> &Self::Target {
-        &self.0
+
+    fn deref(&self) -> &Calculator {
+        self.calculator.as_ref().expect("calculator was already given back")
+    }
+}
+
+impl std::ops::DerefMut for AcquiredCalculator<'_> {
+    fn deref_mut(&mut self) -> &mut Calculator {
+        self.calculator.as_mut().expect("calculator was already given back")
+    }
+}
+
+impl Drop for AcquiredCalculator<'_> {
+    fn drop(&mut self) {
+        let was_cancelled = lock(&self.owner.running).finish(self.id);
+        if let Some(calculator) = self.calculator.take() {
+            // if the calculation was cancelled, the cancellation might have
+            // arrived after the end of the calculation, and would then cancel
+            // the next calculation using this copy. Drop it instead of
+            // re-using it.
+            if !was_cancelled {
+                lock(&self.owner.idle).push(calculator);
+            }
+        }
     }
 }
 
-impl DerefMut for rascal_calculator_t {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.0
+/// Calculations currently running with a given `rascal_calculator_t`
+#[derive(Default)]
+struct RunningCalculations {
+    /// identifier, cancellation handle and cancellation status of all the
+    /// running calculations
+    calculations: Vec<(usize, CancellationHandle, bool)>,
+    /// identifier of the next calculation
+    next_id: usize,
+    /// cancellation was requested while no calculation was running, the next
+    /// one should be cancelled
+    pending: bool,
+}
+
+impl RunningCalculations {
+    /// Register a new calculation using the calculator with the given
+    /// cancellation `handle`, and get the corresponding identifier
+    fn start(&mut self, handle: CancellationHandle) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        let cancelled = self.pending;
+        if cancelled {
+            handle.cancel();
+            self.pending = false;
+        }
+
+        self.calculations.push((id, handle, cancelled));
+        return id;
+    }
+
+    /// Remove the calculation with the given `id` from the running ones,
+    /// returning whether it was cancelled
+    fn finish(&mut self, id: usize) -> bool {
+        let position = self.calculations.iter().position(|(i, _, _)| *i == id);
+        match position {
+            Some(position) => self.calculations.swap_remove(position).2,
+            None => false,
+        }
+    }
+
+    /// Cancel all running calculations, or the next one if there are none
+    fn cancel(&mut self) {
+        if self.calculations.is_empty() {
+            self.pending = true;
+        }
+
+        for (_, handle, cancelled) in &mut self.calculations {
+            handle.cancel();
+            *cancelled = true;
+        }
     }
 }
 
@@ -58,7 +197,7 @@ pub unsafe extern fn rascal_calculator(name: *const c_char, parameters: *const c
         let name = CStr::from_ptr(name).to_str()?;
         let parameters = CStr::from_ptr(parameters).to_str()?;
         let calculator = Calculator::new(name, parameters.to_owned())?;
-        let boxed = Box::new(rascal_calculator_t(calculator));
+        let boxed = Box::new(rascal_calculator_t::new(name, calculator));
 
         *unwind_wrapper.0 = Box::into_raw(boxed);
         Ok(())
@@ -115,7 +254,7 @@ pub unsafe extern fn rascal_calculator_name(
 ) -> rascal_status_t {
     catch_unwind(|| {
         check_pointers!(calculator, name);
-        copy_str_to_c(&(*calculator).name(), name, bufflen)?;
+        copy_str_to_c(&(*calculator).name, name, bufflen)?;
         Ok(())
     })
 }
@@ -143,7 +282,30 @@ pub unsafe extern fn rascal_calculator_parameters(
 ) -> rascal_status_t {
     catch_unwind(|| {
         check_pointers!(calculator, parameters);
-        copy_str_to_c((*calculator).parameters(), parameters, bufflen)?;
+        copy_str_to_c(&(*calculator).parameters, parameters, bufflen)?;
+        Ok(())
+    })
+}
+
+/// Check if this `calculator` can compute gradients, i.e. if setting the
+/// `gradients` parameter to `true` is supported. This does not tell if this
+/// specific calculator was created with gradients enabled.
+///
+/// @param calculator pointer to an existing calculator
+/// @param supported pointer to a boolean, will be set to `true` if the
+///                  calculator supports gradients and `false` otherwise
+///
+/// @returns The status code of this operation. If the status is not
+///          `RASCAL_SUCCESS`, you can use `rascal_last_error()` to get the full
+///          error message.
+#[no_mangle]
+pub unsafe extern fn rascal_calculator_supports_gradients(
+    calculator: *const rascal_calculator_t,
+    supported: *mut bool,
+) -> rascal_status_t {
+    catch_unwind(|| {
+        check_pointers!(calculator, supported);
+        *supported = (*calculator).supports_gradients;
         Ok(())
     })
 }
@@ -168,11 +330,24 @@ pub unsafe extern fn rascal_calculator_features_count(
 ) -> rascal_status_t {
     catch_unwind(|| {
         check_pointers!(calculator, features);
-        *features = (*calculator).default_features().count();
+        *features = (*calculator).features_count;
         Ok(())
     })
 }
 
+#[repr(C)]
+#[allow(non_camel_case_types)]
+/// Strategy used to run a calculation in parallel
+pub enum rascal_parallelism {
+    /// Parallelize the calculation inside each system, handling one system
+    /// after the other. This works best for a few large systems.
+    RASCAL_PARALLELISM_SAMPLES = 0,
+    /// Parallelize the calculation over all atomic environments in all
+    /// systems at once, balancing the work between threads. This works best
+    /// for datasets containing a lot of small systems.
+    RASCAL_PARALLELISM_SYSTEMS = 1,
+}
+
 /// Options that can be set to change how a calculator operates.
 #[repr(C)]
 pub struct rascal_calculation_options_t {
@@ -188,6 +363,32 @@ pub struct rascal_calculation_options_t {
     /// `selected_features.names` to `NULL` to run the calculation on all
     /// features.
     selected_features: rascal_indexes_t,
+    /// Number of threads to use for this calculation. Set this to 0 to use
+    /// the default global thread pool, which can be configured with the
+    /// `RAYON_NUM_THREADS` environment variable.
+    threads: usize,
+    /// Strategy used to run the calculation in parallel. The default
+    /// (`RASCAL_PARALLELISM_SAMPLES`) parallelizes over samples within each
+    /// system.
+    parallelism: rascal_parallelism,
+    /// Check that all values and gradients are finite after the calculation,
+    /// returning an error pointing to the first NaN or infinite entry
+    /// otherwise. This is intended for debugging.
+    check_finite: bool,
+    /// Make sure the results of the calculation do not depend on the number
+    /// of threads or the parallelization strategy, at the cost of a slower
+    /// calculation.
+    deterministic: bool,
+    /// Compute the neighbor list of each system once with this cutoff, and
+    /// filter it when a smaller cutoff is requested, instead of re-computing
+    /// a neighbor list for each cutoff. Set this to 0 to disable this
+    /// behavior. This requires `use_native_system`.
+    neighbors_cutoff: f64,
+    /// Only compute the values during the calculation, and compute the
+    /// gradients on the first call to `rascal_descriptor_gradients` (or any
+    /// other function needing them). This requires the calculator to compute
+    /// gradients.
+    lazy_gradients: bool,
 }
 
 fn selected_indexes(selected: &rascal_indexes_t) -> Result<SelectedIndexes, Error> {
@@ -211,13 +412,29 @@ fn selected_indexes(selected: &rascal_indexes_t) -> Result<SelectedIndexes, Erro
         }
     }
 
-    let values = unsafe {
-        std::slice::from_raw_parts(selected.values.cast(), selected.size * selected.count)
-    };
+    if selected.size == 0 {
+        return Err(Error::InvalidParameter(
+            "selected indexes must contain at least one variable".into()
+        ));
+    }
 
     let mut builder = IndexesBuilder::new(names);
-    for chunk in values.chunks(selected.size) {
-        builder.add(chunk);
+    if selected.count != 0 {
+        if selected.values.is_null() {
+            return Err(Error::InvalidParameter(
+                "got a NULL pointer for the values of non-empty selected indexes".into()
+            ));
+        }
+
+        // IndexValue is `repr(transparent)`, so we can re-interpret the i32
+        // as IndexValue
+        let values = unsafe {
+            std::slice::from_raw_parts(selected.values.cast::<IndexValue>(), selected.size * selected.count)
+        };
+
+        for chunk in values.chunks(selected.size) {
+            builder.add(chunk);
+        }
     }
 
     return Ok(SelectedIndexes::Subset(builder.finish()));
@@ -227,6 +444,11 @@ fn selected_indexes(selected: &rascal_indexes_t) -> Result<SelectedIndexes, Erro
 /// Run a calculation with the given `calculator` on the given `systems`,
 /// storing the resulting data in the `descriptor`.
 ///
+/// This function can be called concurrently from multiple threads, as long as
+/// each thread uses a different `descriptor` and different `systems`. This
+/// includes using the same `calculator` from multiple threads, in which case
+/// the calculations run in parallel using separate copies of the calculator.
+///
 /// @param calculator pointer to an existing calculator
 /// @param descriptor pointer to an existing descriptor for data storage
 /// @param systems pointer to an array of systems implementation
@@ -238,7 +460,7 @@ fn selected_indexes(selected: &rascal_indexes_t) -> Result<SelectedIndexes, Erro
 ///          error message.
 #[no_mangle]
 pub unsafe extern fn rascal_calculator_compute(
-    calculator: *mut rascal_calculator_t,
+    calculator: *const rascal_calculator_t,
     descriptor: *mut rascal_descriptor_t,
     systems: *mut rascal_system_t,
     systems_count: usize,
@@ -252,18 +474,499 @@ pub unsafe extern fn rascal_calculator_compute(
         check_pointers!(calculator, descriptor, systems);
 
         // Create a Vec<Box<dyn System>> from the passed systems
-        let c_systems = std::slice::from_raw_parts_mut(systems, systems_count);
-        let mut systems = Vec::with_capacity(c_systems.len());
-        for system in c_systems {
-            systems.push(Box::new(system) as Box<dyn System>);
+        let mut systems = systems_from_c(systems, systems_count)?;
+
+        let options = calculation_options(&options)?;
+        (*calculator).acquire()?.compute(&mut systems, &mut *descriptor, options)
+    })
+}
+
+#[allow(clippy::doc_markdown, clippy::too_many_arguments)]
+/// Run a calculation with the given `calculator` on the given `systems`, only
+/// computing the samples associated with the given structures and centers.
+///
+/// This is equivalent to calling `rascal_calculator_compute` with
+/// `options.selected_samples` containing the `structure` and `center`
+/// variables, and can be used to only update the environments of atoms that
+/// moved (for example in molecular dynamics). `structures` and `centers`
+/// should be arrays containing `count` elements each, where
+/// `(structures[i], centers[i])` is a center to include in the calculation. If
+/// `centers` is `NULL`, all the samples associated with the given `structures`
+/// are computed instead.
+///
+/// @param calculator pointer to an existing calculator
+/// @param descriptor pointer to an existing descriptor for data storage
+/// @param systems pointer to an array of systems implementation
+/// @param systems_count number of systems in `systems`
+/// @param structures array of `count` structure indexes to compute
+/// @param centers array of `count` center indexes to compute, or `NULL`
+/// @param count number of elements in `structures` and `centers`
+/// @param options options for this calculation. `options.selected_samples`
+///                must not be set when using this function.
+///
+/// @returns The status code of this operation. If the status is not
+///          `RASCAL_SUCCESS`, you can use `rascal_last_error()` to get the full
+///          error message.
+#[no_mangle]
+pub unsafe extern fn rascal_calculator_compute_partial(
+    calculator: *const rascal_calculator_t,
+    descriptor: *mut rascal_descriptor_t,
+    systems: *mut rascal_system_t,
+    systems_count: usize,
+    structures: *const usize,
+    centers: *const usize,
+    count: usize,
+    options: rascal_calculation_options_t,
+) -> rascal_status_t {
+    catch_unwind(|| {
+        if systems_count == 0 {
+            log::warn!("0 systems given to rascal_calculator_compute_partial, we will do nothing");
+            return Ok(());
+        }
+        check_pointers!(calculator, descriptor, systems);
+
+        if !options.selected_samples.names.is_null() {
+            return Err(Error::InvalidParameter(
+                "selected samples can not be set in the options of rascal_calculator_compute_partial".into()
+            ));
         }
 
-        let options = CalculationOptions {
-            use_native_system: options.use_native_system,
-            selected_samples: selected_indexes(&options.selected_samples)?,
-            selected_features: selected_indexes(&options.selected_features)?,
+        let structures = if count == 0 {
+            &[]
+        } else {
+            check_pointers!(structures);
+            std::slice::from_raw_parts(structures, count)
+        };
+
+        // remove duplicated entries, which would otherwise create duplicated
+        // samples
+        let selected = if centers.is_null() {
+            let structures = structures.iter().collect::<BTreeSet<_>>();
+            let mut selected = IndexesBuilder::new(vec!["structure"]);
+            for &structure in structures {
+                selected.add(&[IndexValue::from(structure)]);
+            }
+            selected.finish()
+        } else {
+            let centers = if count == 0 { &[] } else { std::slice::from_raw_parts(centers, count) };
+            let pairs = structures.iter().zip(centers).collect::<BTreeSet<_>>();
+            let mut selected = IndexesBuilder::new(vec!["structure", "center"]);
+            for (&structure, &center) in pairs {
+                selected.add(&[IndexValue::from(structure), IndexValue::from(center)]);
+            }
+            selected.finish()
         };
 
-        (*calculator).compute(&mut systems, &mut *descriptor, options)
+        let mut systems = systems_from_c(systems, systems_count)?;
+
+        let mut options = calculation_options(&options)?;
+        options.selected_samples = SelectedIndexes::Subset(selected);
+        (*calculator).acquire()?.compute(&mut systems, &mut *descriptor, options)
+    })
+}
+
+/// Convert C calculation options to the Rust version
+fn calculation_options(options: &rascal_calculation_options_t) -> Result<CalculationOptions, Error> {
+    return Ok(CalculationOptions {
+        use_native_system: options.use_native_system,
+        selected_samples: selected_indexes(&options.selected_samples)?,
+        selected_features: selected_indexes(&options.selected_features)?,
+        threads: if options.threads == 0 { None } else { Some(options.threads) },
+        parallelism: match options.parallelism {
+            rascal_parallelism::RASCAL_PARALLELISM_SAMPLES => Parallelism::Samples,
+            rascal_parallelism::RASCAL_PARALLELISM_SYSTEMS => Parallelism::Systems,
+        },
+        check_finite: options.check_finite,
+        deterministic: options.deterministic,
+        neighbors_cutoff: if options.neighbors_cutoff == 0.0 { None } else { Some(options.neighbors_cutoff) },
+        hyper_gradients: false,
+        lazy_gradients: options.lazy_gradients,
+    });
+}
+
+#[allow(clippy::doc_markdown)]
+/// Get the shape of the arrays that a call to `rascal_calculator_compute` with
+/// the same `systems` and `options` would produce, without running the
+/// calculation.
+///
+/// This function sets `*samples` to the number of samples (first dimension of
+/// the values array), `*features` to the number of features (second dimension
+/// of both the values and gradients arrays), and `*gradient_samples` to the
+/// number of gradient samples (first dimension of the gradients array), or 0
+/// if this calculator does not compute gradients.
+///
+/// This can be used to allocate the buffers given to
+/// `rascal_calculator_compute_into`.
+///
+/// @param calculator pointer to an existing calculator
+/// @param systems pointer to an array of systems implementation
+/// @param systems_count number of systems in `systems`
+/// @param options options for this calculation
+/// @param samples pointer to a single integer, will be set to the number of
+///                samples
+/// @param features pointer to a single integer, will be set to the number of
+///                 features
+/// @param gradient_samples pointer to a single integer, will be set to the
+///                         number of gradient samples
+///
+/// @returns The status code of this operation. If the status is not
+///          `RASCAL_SUCCESS`, you can use `rascal_last_error()` to get the full
+///          error message.
+#[no_mangle]
+pub unsafe extern fn rascal_calculator_compute_shape(
+    calculator: *const rascal_calculator_t,
+    systems: *mut rascal_system_t,
+    systems_count: usize,
+    options: rascal_calculation_options_t,
+    samples: *mut usize,
+    features: *mut usize,
+    gradient_samples: *mut usize,
+) -> rascal_status_t {
+    catch_unwind(|| {
+        check_pointers!(calculator, samples, features, gradient_samples);
+        if systems_count != 0 {
+            check_pointers!(systems);
+        }
+
+        let mut systems = systems_from_c(systems, systems_count)?;
+
+        let options = calculation_options(&options)?;
+        let estimate = (*calculator).acquire()?.estimate_memory(&mut systems, options)?;
+
+        *samples = estimate.samples_count;
+        *features = estimate.features_count;
+        *gradient_samples = estimate.gradients_samples_count;
+
+        Ok(())
+    })
+}
+
+#[allow(clippy::doc_markdown)]
+/// Run a calculation with the given `calculator` on the given `systems`,
+/// storing the values and gradients in buffers provided by the caller.
+///
+/// The size of the buffers should be determined beforehand with
+/// `rascal_calculator_compute_shape`: `values` must contain at least
+/// `samples * features` elements, and `gradients` at least `gradient_samples *
+/// features` elements. The data is stored using a row-major layout. If this
+/// calculator does not compute gradients, `gradients` can be `NULL`.
+///
+/// The size of the buffers is checked before running the calculation, and
+/// this function returns `RASCAL_BUFFER_SIZE_ERROR` without modifying
+/// `descriptor` if they are too small.
+///
+/// This function does not avoid the allocation of the values and gradients
+/// by rascaline: the calculation is performed in `descriptor`, and the data is
+/// then copied once to the buffers. `descriptor` contains the full values,
+/// gradients and corresponding indexes after this call, and can be released
+/// with `rascal_descriptor_free` as soon as the buffers have been filled.
+///
+/// @param calculator pointer to an existing calculator
+/// @param descriptor pointer to an existing descriptor for metadata storage
+/// @param systems pointer to an array of systems implementation
+/// @param systems_count number of systems in `systems`
+/// @param options options for this calculation
+/// @param values buffer for the values
+/// @param values_count number of `double` available in `values`
+/// @param gradients buffer for the gradients, or `NULL`
+/// @param gradients_count number of `double` available in `gradients`
+///
+/// @returns The status code of this operation. If the status is not
+///          `RASCAL_SUCCESS`, you can use `rascal_last_error()` to get the full
+///          error message.
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub unsafe extern fn rascal_calculator_compute_into(
+    calculator: *const rascal_calculator_t,
+    descriptor: *mut rascal_descriptor_t,
+    systems: *mut rascal_system_t,
+    systems_count: usize,
+    options: rascal_calculation_options_t,
+    values: *mut f64,
+    values_count: usize,
+    gradients: *mut f64,
+    gradients_count: usize,
+) -> rascal_status_t {
+    catch_unwind(|| {
+        if systems_count == 0 {
+            log::warn!("0 systems given to rascal_calculator_compute_into, we will do nothing");
+            return Ok(());
+        }
+        check_pointers!(calculator, descriptor, systems);
+
+        let mut systems = systems_from_c(systems, systems_count)?;
+        let mut calculator = (*calculator).acquire()?;
+
+        // check the buffers before running the (potentially long) calculation
+        let estimate = calculator.estimate_memory(&mut systems, calculation_options(&options)?)?;
+        check_buffer(values, values_count, estimate.samples_count * estimate.features_count, "values")?;
+        if estimate.gradients_samples_count != 0 {
+            let count = estimate.gradients_samples_count * estimate.features_count;
+            check_buffer(gradients, gradients_count, count, "gradients")?;
+        }
+
+        let descriptor = &mut *descriptor;
+        calculator.compute(&mut systems, descriptor, calculation_options(&options)?)?;
+
+        copy_to_buffer(&descriptor.values, values, values_count, "values")?;
+        if let Some(array) = &descriptor.gradients {
+            copy_to_buffer(array, gradients, gradients_count, "gradients")?;
+        }
+
+        Ok(())
     })
 }
+
+/// Check that `buffer` is not NULL and contains at least `required` elements
+fn check_buffer(buffer: *mut f64, buffer_count: usize, required: usize, name: &str) -> Result<(), Error> {
+    if required == 0 {
+        return Ok(());
+    }
+
+    if buffer.is_null() {
+        return Err(Error::InvalidParameter(format!(
+            "got invalid NULL pointer for {} buffer", name
+        )));
+    }
+
+    if buffer_count < required {
+        return Err(Error::BufferSize(format!(
+            "{} buffer has space for {} elements, but {} are required",
+            name, buffer_count, required
+        )));
+    }
+
+    return Ok(());
+}
+
+/// Copy the data in `array` to the buffer at `buffer`, containing
+/// `buffer_count` elements
+unsafe fn copy_to_buffer(array: &ndarray::Array2<f64>, buffer: *mut f64, buffer_count: usize, name: &str) -> Result<(), Error> {
+    if array.is_empty() {
+        return Ok(());
+    }
+
+    check_buffer(buffer, buffer_count, array.len(), name)?;
+
+    if let Some(data) = array.as_slice() {
+        std::ptr::copy_nonoverlapping(data.as_ptr(), buffer, data.len());
+    } else {
+        // feature-major arrays, copy the data in row-major order
+        let output = std::slice::from_raw_parts_mut(buffer, array.len());
+        for (output, value) in output.iter_mut().zip(array) {
+            *output = *value;
+        }
+    }
+
+    return Ok(());
+}
+
+/// Opaque type representing a `CancellationHandle`, which can be used to
+/// cancel calculations running with a given calculator from another thread.
+#[allow(non_camel_case_types)]
+pub struct rascal_cancellation_t(Arc<Mutex<RunningCalculations>>);
+
+/// Create a new cancellation handle for the given `calculator`.
+///
+/// The handle can be used from any thread to cancel the calculations running
+/// with `calculator` using `rascal_cancellation_cancel`. This function can be
+/// called while a calculation is running. The handle stays valid even after
+/// the calculator is freed.
+///
+/// All memory allocated by this function can be released using
+/// `rascal_cancellation_free`.
+///
+/// @param calculator pointer to an existing calculator
+///
+/// @returns A pointer to the newly allocated cancellation handle, or a `NULL`
+///          pointer in case of error. In case of error, you can use
+///          `rascal_last_error()` to get the error message.
+#[no_mangle]
+pub unsafe extern fn rascal_cancellation(calculator: *const rascal_calculator_t) -> *mut rascal_cancellation_t {
+    let mut raw = std::ptr::null_mut();
+    let unwind_wrapper = std::panic::AssertUnwindSafe(&mut raw);
+    let status = catch_unwind(move || {
+        check_pointers!(calculator);
+        let handle = Arc::clone(&(*calculator).running);
+        *unwind_wrapper.0 = Box::into_raw(Box::new(rascal_cancellation_t(handle)));
+        Ok(())
+    });
+
+    if !status.is_success() {
+        return std::ptr::null_mut();
+    }
+
+    return raw;
+}
+
+/// Request all the calculations currently running with the calculator
+/// associated with this `cancellation` handle to stop as soon as possible.
+///
+/// The cancelled calculations will return `RASCAL_CANCELLED_ERROR`. If no
+/// calculation is currently running, the next one will be cancelled instead.
+///
+/// @param cancellation pointer to an existing cancellation handle
+///
+/// @returns The status code of this operation. If the status is not
+///          `RASCAL_SUCCESS`, you can use `rascal_last_error()` to get the full
+///          error message.
+#[no_mangle]
+pub unsafe extern fn rascal_cancellation_cancel(cancellation: *const rascal_cancellation_t) -> rascal_status_t {
+    catch_unwind(|| {
+        check_pointers!(cancellation);
+        lock(&(*cancellation).0).cancel();
+        Ok(())
+    })
+}
+
+/// Free the memory associated with a `cancellation` handle previously created
+/// with `rascal_cancellation`.
+///
+/// If `cancellation` is `NULL`, this function does nothing.
+///
+/// @param cancellation pointer to an existing cancellation handle, or `NULL`
+///
+/// @returns The status code of this operation. If the status is not
+///          `RASCAL_SUCCESS`, you can use `rascal_last_error()` to get the
+///          full error message.
+#[no_mangle]
+pub unsafe extern fn rascal_cancellation_free(cancellation: *mut rascal_cancellation_t) -> rascal_status_t {
+    catch_unwind(|| {
+        if !cancellation.is_null() {
+            let boxed = Box::from_raw(cancellation);
+            std::mem::drop(boxed);
+        }
+
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::CString;
+    use std::ptr::addr_of_mut;
+
+    use crate::descriptor::{rascal_descriptor, rascal_descriptor_free, rascal_descriptor_values};
+    use crate::system::{rascal_basic_system, rascal_basic_system_free, rascal_system_t};
+    use super::*;
+
+    /// Run a calculation with `calculator` on a single water molecule and
+    /// return the values
+    unsafe fn compute_water(calculator: *const rascal_calculator_t) -> Vec<f64> {
+        let species = [8, 1, 1];
+        let positions = [0.0, 0.0, 0.0, 0.0, 0.75, -0.5, 0.0, -0.75, -0.5];
+        let mut system = std::mem::zeroed::<rascal_system_t>();
+        assert!(rascal_basic_system(
+            species.as_ptr(), positions.as_ptr(), 3, std::ptr::null(), addr_of_mut!(system)
+        ).is_success());
+
+        let descriptor = rascal_descriptor();
+        let options = std::mem::zeroed::<rascal_calculation_options_t>();
+        assert!(rascal_calculator_compute(calculator, descriptor, addr_of_mut!(system), 1, options).is_success());
+
+        let mut data = std::ptr::null_mut();
+        let mut samples = 0;
+        let mut features = 0;
+        assert!(rascal_descriptor_values(descriptor, addr_of_mut!(data), addr_of_mut!(samples), addr_of_mut!(features)).is_success());
+        let values = std::slice::from_raw_parts(data, samples * features).to_vec();
+
+        assert!(rascal_descriptor_free(descriptor).is_success());
+        assert!(rascal_basic_system_free(addr_of_mut!(system)).is_success());
+
+        return values;
+    }
+
+    #[test]
+    fn concurrent_compute() {
+        let name = CString::new("soap_power_spectrum").unwrap();
+        let parameters = CString::new(r#"{
+            "cutoff": 3.0,
+            "max_radial": 4,
+            "max_angular": 3,
+            "atomic_gaussian_width": 0.3,
+            "gradients": true,
+            "radial_basis": {"Gto": {}},
+            "cutoff_function": {"ShiftedCosine": {"width": 0.5}}
+        }"#).unwrap();
+
+        unsafe {
+            let calculator = rascal_calculator(name.as_ptr(), parameters.as_ptr());
+            assert!(!calculator.is_null());
+
+            let expected = compute_water(calculator);
+
+            // raw pointers are not Send, so we share the calculator as an
+            // integer between threads
+            let address = calculator as usize;
+            let threads = (0..4).map(|_| {
+                std::thread::spawn(move || {
+                    compute_water(address as *const rascal_calculator_t)
+                })
+            }).collect::<Vec<_>>();
+
+            for thread in threads {
+                assert_eq!(thread.join().unwrap(), expected);
+            }
+
+            assert!(rascal_calculator_free(calculator).is_success());
+        }
+    }
+
+    #[test]
+    fn calculator_copies() {
+        let name = CString::new("dummy_calculator").unwrap();
+        let parameters = CString::new(r#"{
+            "cutoff": 3.0,
+            "delta": 4,
+            "name": "",
+            "gradients": false
+        }"#).unwrap();
+
+        unsafe {
+            let calculator = rascal_calculator(name.as_ptr(), parameters.as_ptr());
+            assert!(!calculator.is_null());
+
+            // multiple copies can be used at the same time, and the
+            // calculator metadata is still accessible
+            let first = (*calculator).acquire().unwrap();
+            let second = (*calculator).acquire().unwrap();
+
+            let mut buffer = vec![0 as c_char; 128];
+            assert!(rascal_calculator_name(calculator, buffer.as_mut_ptr(), buffer.len()).is_success());
+            assert_eq!(CStr::from_ptr(buffer.as_ptr()).to_str().unwrap(), "dummy test calculator with cutoff: 3 - delta: 4 - name:  - gradients: false");
+
+            std::mem::drop(first);
+            std::mem::drop(second);
+            assert_eq!(lock(&(*calculator).idle).len(), 2);
+
+            // cancelled copies are not re-used
+            let cancellation = rascal_cancellation(calculator);
+            assert!(!cancellation.is_null());
+
+            let first = (*calculator).acquire().unwrap();
+            assert!(rascal_cancellation_cancel(cancellation).is_success());
+            std::mem::drop(first);
+            assert_eq!(lock(&(*calculator).idle).len(), 1);
+
+            // without running calculation, the next one is cancelled
+            assert!(rascal_cancellation_cancel(cancellation).is_success());
+            let descriptor = rascal_descriptor();
+            let mut system = std::mem::zeroed::<rascal_system_t>();
+            let species = [8, 1, 1];
+            let positions = [0.0, 0.0, 0.0, 0.0, 0.75, -0.5, 0.0, -0.75, -0.5];
+            assert!(rascal_basic_system(
+                species.as_ptr(), positions.as_ptr(), 3, std::ptr::null(), addr_of_mut!(system)
+            ).is_success());
+
+            let options = || std::mem::zeroed::<rascal_calculation_options_t>();
+            let status = rascal_calculator_compute(calculator, descriptor, addr_of_mut!(system), 1, options());
+            assert_eq!(status.as_i32(), crate::RASCAL_CANCELLED_ERROR);
+            assert!(rascal_calculator_compute(calculator, descriptor, addr_of_mut!(system), 1, options()).is_success());
+
+            assert!(rascal_basic_system_free(addr_of_mut!(system)).is_success());
+            assert!(rascal_descriptor_free(descriptor).is_success());
+            assert!(rascal_cancellation_free(cancellation).is_success());
+            assert!(rascal_calculator_free(calculator).is_success());
+        }
+    }
+}
@@ -1,10 +1,13 @@
+use std::convert::TryFrom;
 use std::ops::{Deref, DerefMut};
 use std::os::raw::c_char;
 use std::ffi::CStr;
+use std::io::Write;
 
-use rascaline::descriptor::{Descriptor, IndexValue};
-use rascaline::Error;
+use rascaline::descriptor::{Descriptor, Indexes, IndexValue, MemoryLayout, SampleDistance, RandomProjection};
+use rascaline::{Error, ErrorContext};
 use super::{catch_unwind, rascal_status_t};
+use super::memory::allocate_array;
 
 /// Opaque type representing a `Descriptor`.
 #[allow(non_camel_case_types)]
@@ -65,7 +68,8 @@ pub unsafe extern fn rascal_descriptor_free(descriptor: *mut rascal_descriptor_t
 /// This function sets `*data` to a pointer containing the address of first
 /// element of the 2D array containing the values, `*samples` to the size of the
 /// first axis of this array and `*features` to the size of the second axis of
-/// the array. The array is stored using a row-major layout.
+/// the array. The array is stored using the layout of the descriptor, as
+/// returned by `rascal_descriptor_layout` (row-major by default).
 ///
 /// @param descriptor pointer to an existing descriptor
 /// @param data pointer to a pointer to a double, will be set to the address of
@@ -110,11 +114,17 @@ pub unsafe extern fn rascal_descriptor_values(
 /// This function sets `*data` to to a pointer containing the address of the
 /// first element of the 2D array containing the gradients, `*gradient_samples`
 /// to the size of the first axis of this array and `*features` to the size of
-/// the second axis of the array. The array is stored using a row-major layout.
+/// the second axis of the array. The array is stored using the layout of the
+/// descriptor, as returned by `rascal_descriptor_layout` (row-major by
+/// default).
 ///
 /// If this descriptor does not contain gradient data, `*data` is set to `NULL`,
 /// while `*gradient_samples` and `*features` are set to 0.
 ///
+/// If the descriptor was computed with `lazy_gradients`, the gradients (and
+/// the corresponding gradient samples) are computed by the first call to this
+/// function.
+///
 /// @param descriptor pointer to an existing descriptor
 /// @param data pointer to a pointer to a double, will be set to the address of
 ///             the first element in the gradients array
@@ -136,6 +146,7 @@ pub unsafe extern fn rascal_descriptor_gradients(
     catch_unwind(|| {
         check_pointers!(descriptor, data, gradient_samples, features);
 
+        (*descriptor).gradients()?;
         if let Some(ref mut array) = (*descriptor).gradients {
             *data = array.as_mut_ptr();
             let shape = array.shape();
@@ -151,6 +162,86 @@ pub unsafe extern fn rascal_descriptor_gradients(
     })
 }
 
+#[repr(C)]
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Memory layout of the values and gradients arrays in a `rascal_descriptor_t`
+pub enum rascal_memory_layout {
+    /// Row-major layout, where all the features of a given sample are
+    /// contiguous in memory. The element at `(sample, feature)` is at
+    /// `data[sample * n_features + feature]`.
+    RASCAL_MEMORY_LAYOUT_ROW_MAJOR = 0,
+    /// Column-major layout, where all the samples of a given feature are
+    /// contiguous in memory. The element at `(sample, feature)` is at
+    /// `data[feature * n_samples + sample]`.
+    RASCAL_MEMORY_LAYOUT_FEATURE_MAJOR = 1,
+}
+
+impl From<MemoryLayout> for rascal_memory_layout {
+    fn from(layout: MemoryLayout) -> rascal_memory_layout {
+        match layout {
+            MemoryLayout::RowMajor => rascal_memory_layout::RASCAL_MEMORY_LAYOUT_ROW_MAJOR,
+            MemoryLayout::FeatureMajor => rascal_memory_layout::RASCAL_MEMORY_LAYOUT_FEATURE_MAJOR,
+        }
+    }
+}
+
+impl From<rascal_memory_layout> for MemoryLayout {
+    fn from(layout: rascal_memory_layout) -> MemoryLayout {
+        match layout {
+            rascal_memory_layout::RASCAL_MEMORY_LAYOUT_ROW_MAJOR => MemoryLayout::RowMajor,
+            rascal_memory_layout::RASCAL_MEMORY_LAYOUT_FEATURE_MAJOR => MemoryLayout::FeatureMajor,
+        }
+    }
+}
+
+/// Get the memory layout used by the values and gradients arrays of this
+/// `descriptor`.
+///
+/// @param descriptor pointer to an existing descriptor
+/// @param layout pointer to a `rascal_memory_layout`, will be set to the
+///               layout of the descriptor arrays
+///
+/// @returns The status code of this operation. If the status is not
+///          `RASCAL_SUCCESS`, you can use `rascal_last_error()` to get the full
+///          error message.
+#[no_mangle]
+pub unsafe extern fn rascal_descriptor_layout(
+    descriptor: *const rascal_descriptor_t,
+    layout: *mut rascal_memory_layout,
+) -> rascal_status_t {
+    catch_unwind(|| {
+        check_pointers!(descriptor, layout);
+        *layout = (*descriptor).layout().into();
+        Ok(())
+    })
+}
+
+/// Set the memory layout used by the values and gradients arrays of this
+/// `descriptor`, converting the existing arrays if needed.
+///
+/// The layout is kept for all subsequent calculations using this descriptor.
+/// Pointers previously obtained with `rascal_descriptor_values` or
+/// `rascal_descriptor_gradients` are invalidated by this function.
+///
+/// @param descriptor pointer to an existing descriptor
+/// @param layout the new memory layout
+///
+/// @returns The status code of this operation. If the status is not
+///          `RASCAL_SUCCESS`, you can use `rascal_last_error()` to get the full
+///          error message.
+#[no_mangle]
+pub unsafe extern fn rascal_descriptor_set_layout(
+    descriptor: *mut rascal_descriptor_t,
+    layout: rascal_memory_layout,
+) -> rascal_status_t {
+    catch_unwind(|| {
+        check_pointers!(descriptor);
+        (*descriptor).set_layout(layout.into());
+        Ok(())
+    })
+}
+
 #[repr(C)]
 #[allow(non_camel_case_types)]
 /// The different kinds of indexes that can exist on a `rascal_descriptor_t`
@@ -164,6 +255,16 @@ pub enum rascal_indexes_kind {
     RASCAL_INDEXES_GRADIENT_SAMPLES = 2,
 }
 
+/// Get the indexes of the given `kind` in the `descriptor`, or `None` if the
+/// descriptor does not contain gradients and gradient samples are requested.
+fn indexes_of_kind<'a>(descriptor: &'a Descriptor, kind: &rascal_indexes_kind) -> Option<&'a Indexes> {
+    match kind {
+        rascal_indexes_kind::RASCAL_INDEXES_FEATURES => Some(&descriptor.features),
+        rascal_indexes_kind::RASCAL_INDEXES_SAMPLES => Some(&descriptor.samples),
+        rascal_indexes_kind::RASCAL_INDEXES_GRADIENT_SAMPLES => descriptor.gradients_samples.as_ref(),
+    }
+}
+
 /// Indexes representing metadata associated with either samples or features in
 /// a given descriptor.
 #[repr(C)]
@@ -194,6 +295,8 @@ pub struct rascal_indexes_t {
 ///
 /// If this `descriptor` does not contain gradient data, and `indexes` is
 /// `RASCAL_INDEXES_GRADIENTS`, all members of `indexes` are set to `NULL` or 0.
+/// This is also the case for descriptors computed with `lazy_gradients` until
+/// the gradients are computed by `rascal_descriptor_gradients`.
 ///
 /// @param descriptor pointer to an existing descriptor
 /// @param kind type of indexes requested
@@ -211,20 +314,14 @@ pub unsafe extern fn rascal_descriptor_indexes(
     catch_unwind(|| {
         check_pointers!(descriptor, indexes);
 
-        let rust_indexes = match kind {
-            rascal_indexes_kind::RASCAL_INDEXES_FEATURES => &(*descriptor).features,
-            rascal_indexes_kind::RASCAL_INDEXES_SAMPLES => &(*descriptor).samples,
-            rascal_indexes_kind::RASCAL_INDEXES_GRADIENT_SAMPLES => {
-                if let Some(indexes) = &(*descriptor).gradients_samples {
-                    indexes
-                } else {
-                    (*indexes).values = std::ptr::null();
-                    (*indexes).names = std::ptr::null();
-                    (*indexes).size = 0;
-                    (*indexes).count = 0;
-                    return Ok(());
-                }
-            }
+        let rust_indexes = if let Some(rust_indexes) = indexes_of_kind(&*descriptor, &kind) {
+            rust_indexes
+        } else {
+            (*indexes).values = std::ptr::null();
+            (*indexes).names = std::ptr::null();
+            (*indexes).size = 0;
+            (*indexes).count = 0;
+            return Ok(());
         };
 
         (*indexes).size = rust_indexes.size();
@@ -233,6 +330,7 @@ pub unsafe extern fn rascal_descriptor_indexes(
         if rust_indexes.count() == 0 {
             (*indexes).values = std::ptr::null();
         } else {
+            // IndexValue is `repr(transparent)`, so this is the same as an i32
             (*indexes).values = (&rust_indexes[0][0] as *const IndexValue).cast();
         }
 
@@ -246,6 +344,92 @@ pub unsafe extern fn rascal_descriptor_indexes(
     })
 }
 
+/// Get the number of variables names in one of the `indexes` of the given
+/// `descriptor`, i.e. the size of the arrays to pass to
+/// `rascal_descriptor_indexes_names`.
+///
+/// If this `descriptor` does not contain gradient data, and `kind` is
+/// `RASCAL_INDEXES_GRADIENT_SAMPLES`, `count` is set to 0.
+///
+/// @param descriptor pointer to an existing descriptor
+/// @param kind type of indexes requested
+/// @param count pointer to an integer that will be set to the number of names
+///
+/// @returns The status code of this operation. If the status is not
+///          `RASCAL_SUCCESS`, you can use `rascal_last_error()` to get the full
+///          error message.
+#[no_mangle]
+pub unsafe extern fn rascal_descriptor_indexes_names_count(
+    descriptor: *const rascal_descriptor_t,
+    kind: rascal_indexes_kind,
+    count: *mut usize,
+) -> rascal_status_t {
+    catch_unwind(|| {
+        check_pointers!(descriptor, count);
+        *count = indexes_of_kind(&*descriptor, &kind).map_or(0, Indexes::size);
+        Ok(())
+    })
+}
+
+/// Get the names of the variables in one of the `indexes` of the given
+/// `descriptor`, together with the length of each name.
+///
+/// `names` and `lengths` must point to caller-allocated arrays containing
+/// exactly `count` elements, where `count` is the value given by
+/// `rascal_descriptor_indexes_names_count`. This function sets each entry in
+/// `names` to a **read only** NULL-terminated string, and the corresponding
+/// entry in `lengths` to the length of this string in bytes, not including
+/// the NULL terminator. This is intended for bindings in languages where
+/// NULL-terminated strings are not easy to handle.
+///
+/// @param descriptor pointer to an existing descriptor
+/// @param kind type of indexes requested
+/// @param names pointer to an array of `count` strings, which will be filled by
+///              this function
+/// @param lengths pointer to an array of `count` integers, which will be
+///                filled by this function
+/// @param count number of elements in the `names` and `lengths` arrays
+///
+/// @returns The status code of this operation. If the status is not
+///          `RASCAL_SUCCESS`, you can use `rascal_last_error()` to get the full
+///          error message.
+#[no_mangle]
+pub unsafe extern fn rascal_descriptor_indexes_names(
+    descriptor: *const rascal_descriptor_t,
+    kind: rascal_indexes_kind,
+    names: *mut *const c_char,
+    lengths: *mut usize,
+    count: usize,
+) -> rascal_status_t {
+    catch_unwind(|| {
+        check_pointers!(descriptor);
+
+        let rust_names = match indexes_of_kind(&*descriptor, &kind) {
+            Some(rust_indexes) => rust_indexes.c_names(),
+            None => &[],
+        };
+
+        if count != rust_names.len() {
+            return Err(Error::BufferSize(format!(
+                "names buffer has space for {} elements, but the indexes contain {} names",
+                count, rust_names.len()
+            )));
+        }
+
+        if count == 0 {
+            return Ok(());
+        }
+
+        check_pointers!(names, lengths);
+        for (i, name) in rust_names.iter().enumerate() {
+            *names.add(i) = name.as_c_str().as_ptr();
+            *lengths.add(i) = name.as_c_str().to_bytes().len();
+        }
+
+        Ok(())
+    })
+}
+
 /// Make the given `descriptor` dense along the given `variables`.
 ///
 /// The `variable` array should contain the name of the variables as
@@ -325,6 +509,8 @@ pub unsafe extern fn rascal_descriptor_densify(
         let requested = if requested.is_null() {
             None
         } else {
+            // IndexValue is `repr(transparent)`, so we can re-interpret the
+            // i32 as IndexValue
             Some(ndarray::ArrayView2::from_shape_ptr(
                 [requested_size, variables_count], requested.cast::<IndexValue>()
             ))
@@ -358,12 +544,13 @@ pub struct rascal_densified_position_t {
 /// to its documentation for more information.
 ///
 /// If this descriptor contains gradients, `densified_positions` will point to
-/// an array allocated with `malloc` containing the changes made to the values,
-/// which can be used to reconstruct the change to make to the gradients. The
-/// size of this array will be stored in `densified_positions_count`.
+/// an array allocated with the allocator set by `rascal_set_allocator`
+/// (`malloc` by default) containing the changes made to the values, which can
+/// be used to reconstruct the change to make to the gradients. The size of this
+/// array will be stored in `densified_positions_count`.
 ///
-/// Users of this function are expected to `free` the corresponding memory when
-/// they no longer need it.
+/// Users of this function are expected to release the corresponding memory
+/// with `rascal_free` when they no longer need it.
 ///
 /// This is an advanced function most users should not need to use, used to
 /// implement backward propagation without having to densify the full gradient
@@ -390,6 +577,8 @@ pub unsafe extern fn rascal_descriptor_densify_values(
         let requested = if requested.is_null() {
             None
         } else {
+            // IndexValue is `repr(transparent)`, so we can re-interpret the
+            // i32 as IndexValue
             Some(ndarray::ArrayView2::from_shape_ptr(
                 [requested_size, variables_count], requested.cast::<IndexValue>()
             ))
@@ -402,17 +591,10 @@ pub unsafe extern fn rascal_descriptor_densify_values(
             return Ok(());
         }
 
+        *densified_positions = allocate_array(densified_positions_rust.len()).context(
+            "failed to allocate enough memory to store densified positions"
+        )?;
         *densified_positions_count = densified_positions_rust.len();
-        *densified_positions = libc::calloc(
-            densified_positions_rust.len(),
-            std::mem::size_of::<rascal_densified_position_t>()
-        ).cast();
-
-        if (*densified_positions).is_null() {
-            return Err(Error::BufferSize(
-                "failed to allocate enough memory to store densified positions".into()
-            ));
-        }
 
         for (old_sample_i, position) in densified_positions_rust.iter().enumerate() {
             if let Some(position) = position {
@@ -429,3 +611,646 @@ pub unsafe extern fn rascal_descriptor_densify_values(
         Ok(())
     })
 }
+
+/// Save the given `descriptor` to the file at `path`, creating the file if it
+/// does not exist and overwriting it otherwise.
+///
+/// The data is stored in the same format as `Descriptor::save` in the Rust
+/// API, and can be loaded back with `rascal_descriptor_load`.
+///
+/// @param descriptor pointer to an existing descriptor
+/// @param path NULL-terminated path of the file where to save the descriptor
+///
+/// @returns The status code of this operation. If the status is not
+///          `RASCAL_SUCCESS`, you can use `rascal_last_error()` to get the full
+///          error message.
+#[no_mangle]
+pub unsafe extern fn rascal_descriptor_save(
+    descriptor: *const rascal_descriptor_t,
+    path: *const c_char,
+) -> rascal_status_t {
+    catch_unwind(|| {
+        check_pointers!(descriptor, path);
+        let path = CStr::from_ptr(path).to_str()?;
+
+        let file = std::fs::File::create(path).with_context(|| format!(
+            "failed to create '{}'", path
+        ))?;
+        let mut writer = std::io::BufWriter::new(file);
+        (*descriptor).save(&mut writer).with_context(|| format!(
+            "failed to save descriptor to '{}'", path
+        ))?;
+        writer.flush()?;
+
+        Ok(())
+    })
+}
+
+/// Load data from the file at `path` into the given `descriptor`, replacing
+/// any data already stored in the descriptor.
+///
+/// The file should have been created with `rascal_descriptor_save` or
+/// `Descriptor::save` in the Rust API.
+///
+/// @param descriptor pointer to an existing descriptor
+/// @param path NULL-terminated path of the file containing the descriptor
+///
+/// @returns The status code of this operation. If the status is not
+///          `RASCAL_SUCCESS`, you can use `rascal_last_error()` to get the full
+///          error message.
+#[no_mangle]
+pub unsafe extern fn rascal_descriptor_load(
+    descriptor: *mut rascal_descriptor_t,
+    path: *const c_char,
+) -> rascal_status_t {
+    catch_unwind(|| {
+        check_pointers!(descriptor, path);
+        let path = CStr::from_ptr(path).to_str()?;
+
+        let file = std::fs::File::open(path).with_context(|| format!(
+            "failed to open '{}'", path
+        ))?;
+        (*descriptor).0 = Descriptor::load(std::io::BufReader::new(file)).with_context(|| format!(
+            "failed to load descriptor from '{}'", path
+        ))?;
+
+        Ok(())
+    })
+}
+
+/// Magic bytes at the start of buffers created by `rascal_descriptor_to_buffer`
+const BUFFER_MAGIC: &[u8; 8] = b"RASCALDS";
+/// Version of the format used by `rascal_descriptor_to_buffer`, to be
+/// incremented every time the format changes in an incompatible way
+const BUFFER_FORMAT_VERSION: u32 = 1;
+
+/// Serialize the full `descriptor` (values, gradients and all the indexes) to
+/// a binary buffer in memory, which can be loaded back with
+/// `rascal_descriptor_from_buffer`. This is useful to send a descriptor to
+/// other processes (for example with MPI) without going through the
+/// filesystem.
+///
+/// The buffer starts with 8 magic bytes (`"RASCALDS"`) and the format version
+/// as a little-endian 32-bit integer, followed by the descriptor data in the
+/// same format as `Descriptor::save_cbor` in the Rust API.
+///
+/// `*buffer` will be set to memory allocated with the allocator set by
+/// `rascal_set_allocator`, which must be released with `rascal_free` when no
+/// longer needed.
+///
+/// @param descriptor pointer to an existing descriptor
+/// @param buffer pointer to a pointer to bytes, will be set to the address of
+///               the newly allocated buffer
+/// @param buffer_size pointer to a single integer, will be set to the size of
+///                    the buffer in bytes
+///
+/// @returns The status code of this operation. If the status is not
+///          `RASCAL_SUCCESS`, you can use `rascal_last_error()` to get the full
+///          error message.
+#[no_mangle]
+pub unsafe extern fn rascal_descriptor_to_buffer(
+    descriptor: *const rascal_descriptor_t,
+    buffer: *mut *mut u8,
+    buffer_size: *mut usize,
+) -> rascal_status_t {
+    catch_unwind(|| {
+        check_pointers!(descriptor, buffer, buffer_size);
+
+        let mut data = Vec::new();
+        data.extend_from_slice(BUFFER_MAGIC);
+        data.extend_from_slice(&BUFFER_FORMAT_VERSION.to_le_bytes());
+        (*descriptor).save_cbor(&mut data).context("failed to serialize descriptor")?;
+
+        let allocated = allocate_array::<u8>(data.len()).context(
+            "failed to allocate enough memory to store the serialized descriptor"
+        )?;
+        std::ptr::copy_nonoverlapping(data.as_ptr(), allocated, data.len());
+
+        *buffer = allocated;
+        *buffer_size = data.len();
+
+        Ok(())
+    })
+}
+
+/// Load data from a `buffer` created by `rascal_descriptor_to_buffer` into
+/// the given `descriptor`, replacing any data already stored in the
+/// descriptor.
+///
+/// @param descriptor pointer to an existing descriptor
+/// @param buffer pointer to the first byte of the buffer
+/// @param buffer_size size of the buffer in bytes
+///
+/// @returns The status code of this operation. If the status is not
+///          `RASCAL_SUCCESS`, you can use `rascal_last_error()` to get the full
+///          error message.
+#[no_mangle]
+pub unsafe extern fn rascal_descriptor_from_buffer(
+    descriptor: *mut rascal_descriptor_t,
+    buffer: *const u8,
+    buffer_size: usize,
+) -> rascal_status_t {
+    catch_unwind(|| {
+        check_pointers!(descriptor, buffer);
+        let buffer = std::slice::from_raw_parts(buffer, buffer_size);
+
+        let header_size = BUFFER_MAGIC.len() + std::mem::size_of::<u32>();
+        if buffer.len() < header_size || !buffer.starts_with(BUFFER_MAGIC) {
+            return Err(Error::InvalidParameter(
+                "this buffer was not created by rascal_descriptor_to_buffer".into()
+            ));
+        }
+
+        let version = u32::from_le_bytes(
+            <[u8; 4]>::try_from(&buffer[BUFFER_MAGIC.len()..header_size]).expect("wrong slice size")
+        );
+        if version != BUFFER_FORMAT_VERSION {
+            return Err(Error::InvalidParameter(format!(
+                "unsupported descriptor buffer version {}, expected version {}",
+                version, BUFFER_FORMAT_VERSION
+            )));
+        }
+
+        (*descriptor).0 = Descriptor::load_cbor(&buffer[header_size..]).context(
+            "failed to deserialize descriptor"
+        )?;
+
+        Ok(())
+    })
+}
+
+/// Write all the arrays in the given `descriptor` to separate files in
+/// numpy's `.npy` format, inside `directory`. The directory is created if it
+/// does not exist already.
+///
+/// This creates `values.npy`, `samples.npy` and `features.npy`, as well as
+/// `gradients.npy` and `gradients_samples.npy` if the descriptor contains
+/// gradients. Indexes are stored as structured arrays, with one field of 32-bit
+/// integers for each variable.
+///
+/// @param descriptor pointer to an existing descriptor
+/// @param directory NULL-terminated path of the directory where to write the
+///                  files
+///
+/// @returns The status code of this operation. If the status is not
+///          `RASCAL_SUCCESS`, you can use `rascal_last_error()` to get the full
+///          error message.
+#[no_mangle]
+pub unsafe extern fn rascal_descriptor_write_npy(
+    descriptor: *const rascal_descriptor_t,
+    directory: *const c_char,
+) -> rascal_status_t {
+    catch_unwind(|| {
+        check_pointers!(descriptor, directory);
+        let directory = CStr::from_ptr(directory).to_str()?;
+        (*descriptor).write_npy(directory)?;
+        Ok(())
+    })
+}
+
+#[allow(clippy::doc_markdown)]
+/// Compute the Gram matrix `X^T X` of the values `X` of this `descriptor`,
+/// summing over all samples, in the `covariance` array of size `n_features x
+/// n_features`.
+///
+/// If `centered` is `true`, the mean of each feature over the samples is
+/// subtracted from the values before computing the product. This is the first
+/// step of a principal component analysis of the features.
+///
+/// @param descriptor pointer to an existing descriptor
+/// @param centered should the features be centered before computing the
+///                 covariance
+/// @param covariance pointer to the first element of an array of `n_features
+///                   x n_features` values, which will be filled by this
+///                   function
+/// @param n_features number of features in the descriptor
+///
+/// @returns The status code of this operation. If the status is not
+///          `RASCAL_SUCCESS`, you can use `rascal_last_error()` to get the full
+///          error message.
+#[no_mangle]
+pub unsafe extern fn rascal_descriptor_feature_covariance(
+    descriptor: *const rascal_descriptor_t,
+    centered: bool,
+    covariance: *mut f64,
+    n_features: usize,
+) -> rascal_status_t {
+    catch_unwind(|| {
+        check_pointers!(descriptor, covariance);
+        if n_features != (*descriptor).features.count() {
+            return Err(Error::InvalidParameter(format!(
+                "expected n_features to be {}, got {} in rascal_descriptor_feature_covariance",
+                (*descriptor).features.count(), n_features
+            )));
+        }
+
+        let result = (*descriptor).feature_covariance(centered);
+        let covariance = std::slice::from_raw_parts_mut(covariance, n_features * n_features);
+        covariance.copy_from_slice(result.as_slice().expect("covariance should be contiguous"));
+
+        Ok(())
+    })
+}
+
+/// Compute the dot product (linear kernel) between the values of all samples
+/// in `descriptor` and all samples in `other`, and write it in `kernel`.
+///
+/// If `normalize` is `true`, the dot product of each pair of samples is divided
+/// by the norms of both samples, giving the cosine similarity between the
+/// samples. Both descriptors must have the same features.
+///
+/// @param descriptor pointer to an existing descriptor
+/// @param other pointer to another existing descriptor
+/// @param normalize should the dot products be normalized
+/// @param kernel pointer to the first element of a row-major array of
+///               `n_rows x n_columns` values, which will be filled by this
+///               function
+/// @param n_rows number of samples in `descriptor`
+/// @param n_columns number of samples in `other`
+///
+/// @returns The status code of this operation. If the status is not
+///          `RASCAL_SUCCESS`, you can use `rascal_last_error()` to get the full
+///          error message.
+#[no_mangle]
+pub unsafe extern fn rascal_descriptor_dot(
+    descriptor: *const rascal_descriptor_t,
+    other: *const rascal_descriptor_t,
+    normalize: bool,
+    kernel: *mut f64,
+    n_rows: usize,
+    n_columns: usize,
+) -> rascal_status_t {
+    catch_unwind(|| {
+        check_pointers!(descriptor, other);
+        if n_rows != (*descriptor).samples.count() || n_columns != (*other).samples.count() {
+            return Err(Error::InvalidParameter(format!(
+                "expected the kernel to have shape ({}, {}), got ({}, {}) in rascal_descriptor_dot",
+                (*descriptor).samples.count(), (*other).samples.count(), n_rows, n_columns
+            )));
+        }
+
+        rascal_descriptor_dot_block_impl(
+            &*descriptor, &*other, normalize, 0..n_rows, 0..n_columns, kernel, n_columns
+        )
+    })
+}
+
+/// Compute a block of the dot product between the samples in `descriptor` and
+/// the samples in `other` (see `rascal_descriptor_dot`), and write it in
+/// caller-owned memory.
+///
+/// The block contains the rows `row_start` to `row_end` (excluded) of the full
+/// kernel, i.e. samples in `descriptor`, and the columns `column_start` to
+/// `column_end` (excluded), i.e. samples in `other`. The value for row `i` and
+/// column `j` of the block is written in `output[i * output_stride + j]`,
+/// allowing to write the block directly inside a larger matrix. This can be
+/// used to assemble kernel matrices too large to fit in memory tile by tile.
+///
+/// @param descriptor pointer to an existing descriptor
+/// @param other pointer to another existing descriptor
+/// @param normalize should the dot products be normalized
+/// @param row_start index of the first row in the block
+/// @param row_end index of one past the last row in the block
+/// @param column_start index of the first column in the block
+/// @param column_end index of one past the last column in the block
+/// @param output pointer to the first element of the block
+/// @param output_stride number of elements between the start of two
+///                      consecutive rows in `output`, which must be at least
+///                      `column_end - column_start`
+///
+/// @returns The status code of this operation. If the status is not
+///          `RASCAL_SUCCESS`, you can use `rascal_last_error()` to get the full
+///          error message.
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub unsafe extern fn rascal_descriptor_dot_block(
+    descriptor: *const rascal_descriptor_t,
+    other: *const rascal_descriptor_t,
+    normalize: bool,
+    row_start: usize,
+    row_end: usize,
+    column_start: usize,
+    column_end: usize,
+    output: *mut f64,
+    output_stride: usize,
+) -> rascal_status_t {
+    catch_unwind(|| {
+        check_pointers!(descriptor, other);
+        rascal_descriptor_dot_block_impl(
+            &*descriptor, &*other, normalize, row_start..row_end, column_start..column_end, output, output_stride
+        )
+    })
+}
+
+/// Shared implementation of `rascal_descriptor_dot` and
+/// `rascal_descriptor_dot_block`
+unsafe fn rascal_descriptor_dot_block_impl(
+    descriptor: &Descriptor,
+    other: &Descriptor,
+    normalize: bool,
+    rows: std::ops::Range<usize>,
+    columns: std::ops::Range<usize>,
+    output: *mut f64,
+    output_stride: usize,
+) -> Result<(), Error> {
+    let n_rows = rows.end.saturating_sub(rows.start);
+    let n_columns = columns.end.saturating_sub(columns.start);
+    if output_stride < n_columns {
+        return Err(Error::InvalidParameter(format!(
+            "output_stride ({}) must be at least the number of columns ({})",
+            output_stride, n_columns
+        )));
+    }
+
+    if n_rows == 0 || n_columns == 0 {
+        let mut empty = ndarray::Array2::zeros((n_rows, n_columns));
+        return descriptor.dot_into(other, rows, columns, normalize, empty.view_mut());
+    }
+
+    check_pointers!(output);
+    let output = ndarray::ArrayViewMut2::from_shape_ptr(
+        ndarray::ShapeBuilder::strides((n_rows, n_columns), (output_stride, 1)), output
+    );
+    return descriptor.dot_into(other, rows, columns, normalize, output);
+}
+
+#[allow(clippy::doc_markdown)]
+/// Select a subset of the features of this `descriptor` such that the absolute
+/// value of the correlation between any pair of selected features (over all
+/// samples) is at most `threshold`.
+///
+/// The features are considered in order, and a feature is kept if it is not
+/// correlated above `threshold` with any previously kept feature. Features
+/// with the same value for all samples are always removed. On output,
+/// `selected[i]` is `true` if the feature `i` is kept and `false` otherwise.
+///
+/// @param descriptor pointer to an existing descriptor
+/// @param threshold maximal absolute correlation between selected features,
+///                  between 0 and 1
+/// @param selected pointer to the first element of an array of `n_features`
+///                 values, which will be filled by this function
+/// @param n_features number of features in the descriptor
+///
+/// @returns The status code of this operation. If the status is not
+///          `RASCAL_SUCCESS`, you can use `rascal_last_error()` to get the full
+///          error message.
+#[no_mangle]
+pub unsafe extern fn rascal_descriptor_uncorrelated_features(
+    descriptor: *const rascal_descriptor_t,
+    threshold: f64,
+    selected: *mut bool,
+    n_features: usize,
+) -> rascal_status_t {
+    catch_unwind(|| {
+        check_pointers!(descriptor, selected);
+        let features = &(*descriptor).features;
+        if n_features != features.count() {
+            return Err(Error::InvalidParameter(format!(
+                "expected n_features to be {}, got {} in rascal_descriptor_uncorrelated_features",
+                features.count(), n_features
+            )));
+        }
+
+        let result = (*descriptor).uncorrelated_features(threshold)?;
+        let selected = std::slice::from_raw_parts_mut(selected, n_features);
+        selected.fill(false);
+        for feature in result.iter() {
+            let position = features.position(feature).expect("selected feature should be in the descriptor");
+            selected[position] = true;
+        }
+
+        Ok(())
+    })
+}
+
+#[repr(C)]
+#[allow(non_camel_case_types)]
+/// Distance between samples used by `rascal_descriptor_farthest_point_sampling`
+pub enum rascal_sample_distance {
+    /// Euclidean distance between the values of the samples
+    RASCAL_SAMPLE_DISTANCE_EUCLIDEAN = 0,
+    /// Cosine distance `1 - x·y / (|x| |y|)` between the values of the samples
+    RASCAL_SAMPLE_DISTANCE_COSINE = 1,
+}
+
+/// Select `count` samples of this `descriptor` using farthest point sampling
+/// over the rows of the values, with the given `distance`.
+///
+/// The selection starts from the sample at index `initial`, or from the sample
+/// farthest from the mean of all samples if `initial` is negative. The indexes
+/// (i.e. rows) of the selected samples are written to `selected`, in the order
+/// they were selected.
+///
+/// @param descriptor pointer to an existing descriptor
+/// @param count number of samples to select
+/// @param distance distance to use to compare samples
+/// @param initial index of the first sample to select, or a negative value to
+///                start from the sample farthest from the mean
+/// @param selected pointer to the first element of an array of `count`
+///                 values, which will be filled by this function
+///
+/// @returns The status code of this operation. If the status is not
+///          `RASCAL_SUCCESS`, you can use `rascal_last_error()` to get the full
+///          error message.
+#[no_mangle]
+pub unsafe extern fn rascal_descriptor_farthest_point_sampling(
+    descriptor: *const rascal_descriptor_t,
+    count: usize,
+    distance: rascal_sample_distance,
+    initial: i64,
+    selected: *mut usize,
+) -> rascal_status_t {
+    catch_unwind(|| {
+        check_pointers!(descriptor);
+        if count != 0 {
+            check_pointers!(selected);
+        }
+
+        let distance = match distance {
+            rascal_sample_distance::RASCAL_SAMPLE_DISTANCE_EUCLIDEAN => SampleDistance::Euclidean,
+            rascal_sample_distance::RASCAL_SAMPLE_DISTANCE_COSINE => SampleDistance::Cosine,
+        };
+        let initial = usize::try_from(initial).ok();
+
+        let samples = &(*descriptor).samples;
+        let result = (*descriptor).farthest_point_sampling(count, distance, initial)?;
+        if count != 0 {
+            let selected = std::slice::from_raw_parts_mut(selected, count);
+            for (output, sample) in selected.iter_mut().zip(result.iter()) {
+                *output = samples.position(sample).expect("selected sample should be in the descriptor");
+            }
+        }
+
+        Ok(())
+    })
+}
+
+#[repr(C)]
+#[allow(non_camel_case_types)]
+/// Kind of random matrix used by `rascal_descriptor_random_projection`
+pub enum rascal_random_projection {
+    /// Dense projection, with entries drawn from a normal distribution
+    RASCAL_RANDOM_PROJECTION_GAUSSIAN = 0,
+    /// Sparse projection, with two thirds of the entries equal to zero
+    RASCAL_RANDOM_PROJECTION_SPARSE = 1,
+}
+
+/// Compress the features of this `descriptor` to `width` new features, by
+/// multiplying the values and gradients with a fixed random matrix of the
+/// given kind of `projection`.
+///
+/// The projection matrix is generated from `seed`, so descriptors projected
+/// with the same parameters can be compared with one another. After the
+/// projection, the features contain a single `projection` variable, going from
+/// 0 to `width - 1`.
+///
+/// @param descriptor pointer to an existing descriptor
+/// @param width number of features after the projection
+/// @param projection kind of random matrix to use
+/// @param seed seed of the random number generator used to create the matrix
+///
+/// @returns The status code of this operation. If the status is not
+///          `RASCAL_SUCCESS`, you can use `rascal_last_error()` to get the full
+///          error message.
+#[no_mangle]
+pub unsafe extern fn rascal_descriptor_random_projection(
+    descriptor: *mut rascal_descriptor_t,
+    width: usize,
+    projection: rascal_random_projection,
+    seed: u64,
+) -> rascal_status_t {
+    catch_unwind(|| {
+        check_pointers!(descriptor);
+
+        let projection = match projection {
+            rascal_random_projection::RASCAL_RANDOM_PROJECTION_GAUSSIAN => RandomProjection::Gaussian,
+            rascal_random_projection::RASCAL_RANDOM_PROJECTION_SPARSE => RandomProjection::Sparse,
+        };
+
+        (*descriptor).random_projection(width, projection, seed)?;
+
+        Ok(())
+    })
+}
+
+#[allow(clippy::doc_markdown)]
+/// Get the values of this `descriptor` for all the atoms in the given
+/// `structure`, in the `values` array of size `n_atoms x n_features`. The row
+/// `i` of `values` will contain the values for the atom `i`, and atoms without
+/// corresponding sample will be filled with zeros.
+///
+/// This is the layout used by molecular dynamics engines for per-atom
+/// quantities (for example LAMMPS `compute` styles). The samples of the
+/// descriptor must be `[structure, center]`, and additional variables can be
+/// moved to the features with `rascal_descriptor_densify` if needed.
+///
+/// @param descriptor pointer to an existing descriptor
+/// @param structure index of the structure to get values for
+/// @param values pointer to the first element of an array of `n_atoms x
+///               n_features` values, which will be filled by this function
+/// @param n_atoms number of atoms in the structure
+/// @param n_features number of features in the descriptor
+///
+/// @returns The status code of this operation. If the status is not
+///          `RASCAL_SUCCESS`, you can use `rascal_last_error()` to get the full
+///          error message.
+#[no_mangle]
+pub unsafe extern fn rascal_descriptor_per_atom_values(
+    descriptor: *const rascal_descriptor_t,
+    structure: usize,
+    values: *mut f64,
+    n_atoms: usize,
+    n_features: usize,
+) -> rascal_status_t {
+    catch_unwind(|| {
+        check_pointers!(descriptor, values);
+        if n_features != (*descriptor).features.count() {
+            return Err(Error::InvalidParameter(format!(
+                "expected n_features to be {}, got {} in rascal_descriptor_per_atom_values",
+                (*descriptor).features.count(), n_features
+            )));
+        }
+
+        let per_atom = (*descriptor).per_atom_values(structure, n_atoms)?;
+        let values = std::slice::from_raw_parts_mut(values, n_atoms * n_features);
+        values.copy_from_slice(per_atom.as_slice().expect("per-atom values should be contiguous"));
+
+        Ok(())
+    })
+}
+
+#[allow(clippy::doc_markdown)]
+/// Scatter the gradients of this `descriptor` for the given `structure` onto
+/// the atoms, weighting each feature of each atom with `weights`.
+///
+/// `weights` should contain `n_atoms x n_features` values, typically the
+/// derivative of the energy with respect to the descriptor values for each
+/// atom. The `output` array of size `n_atoms x 3` will then be filled with the
+/// gradient of the energy with respect to the positions of all the atoms,
+/// i.e. the opposite of the forces:
+///
+/// ```text
+/// output[j, α] = Σ_i Σ_f weights[i, f] * ∂values[i, f] / ∂r_j[α]
+/// ```
+///
+/// The samples of the descriptor must be `[structure, center]`, see
+/// `rascal_descriptor_per_atom_values`.
+///
+/// @param descriptor pointer to an existing descriptor, containing gradients
+/// @param structure index of the structure to scatter gradients for
+/// @param weights pointer to the first element of an array of `n_atoms x
+///                n_features` weights
+/// @param n_atoms number of atoms in the structure
+/// @param n_features number of features in the descriptor
+/// @param output pointer to the first element of an array of `n_atoms x 3`
+///               values, which will be filled by this function
+///
+/// @returns The status code of this operation. If the status is not
+///          `RASCAL_SUCCESS`, you can use `rascal_last_error()` to get the full
+///          error message.
+#[no_mangle]
+pub unsafe extern fn rascal_descriptor_scatter_gradients(
+    descriptor: *const rascal_descriptor_t,
+    structure: usize,
+    weights: *const f64,
+    n_atoms: usize,
+    n_features: usize,
+    output: *mut f64,
+) -> rascal_status_t {
+    catch_unwind(|| {
+        check_pointers!(descriptor, weights, output);
+        let weights = ndarray::ArrayView2::from_shape_ptr([n_atoms, n_features], weights);
+
+        let scattered = (*descriptor).scatter_gradients(structure, weights)?;
+        let output = std::slice::from_raw_parts_mut(output, n_atoms * 3);
+        output.copy_from_slice(scattered.as_slice().expect("scattered gradients should be contiguous"));
+
+        Ok(())
+    })
+}
+
+/// Update the values and gradients of `descriptor` with the ones in
+/// `partial`, which must contain a subset of the samples in `descriptor`.
+///
+/// This is intended to be used when only some atoms moved since the last
+/// calculation: the descriptor can be re-computed for the affected centers
+/// only (see `rascal_system_affected_centers`) using the `selected_samples`
+/// calculation option, and then merged into the full descriptor.
+///
+/// @param descriptor pointer to an existing descriptor, which will be updated
+/// @param partial pointer to an existing descriptor, containing the new values
+///
+/// @returns The status code of this operation. If the status is not
+///          `RASCAL_SUCCESS`, you can use `rascal_last_error()` to get the full
+///          error message.
+#[no_mangle]
+pub unsafe extern fn rascal_descriptor_update(
+    descriptor: *mut rascal_descriptor_t,
+    partial: *const rascal_descriptor_t,
+) -> rascal_status_t {
+    catch_unwind(|| {
+        check_pointers!(descriptor, partial);
+        (*descriptor).update(&*partial)?;
+        Ok(())
+    })
+}
@@ -0,0 +1,117 @@
+use std::os::raw::c_char;
+use std::ffi::CStr;
+
+use rascaline::Calculator;
+
+use crate::{catch_unwind, rascal_status_t};
+use crate::utils::copy_str_to_c;
+
+/// Get the version of rascaline used to create the shared library, as a
+/// NULL-terminated `"major.minor.patch"` string.
+///
+/// The returned string is owned by rascaline, and must not be freed.
+///
+/// @returns the version of rascaline
+#[no_mangle]
+pub unsafe extern fn rascal_version() -> *const c_char {
+    return concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr().cast();
+}
+
+/// Get the names of all the calculators that can be created with
+/// `rascal_calculator`, as a JSON array of strings. The names are sorted in
+/// alphabetical order.
+///
+/// @param buffer pre-allocated buffer in which the JSON array will be copied.
+///               If the buffer is too small, this function will return
+///               `RASCAL_BUFFER_SIZE_ERROR`
+/// @param bufflen size of the `buffer`
+///
+/// @returns The status code of this operation. If the status is not
+///          `RASCAL_SUCCESS`, you can use `rascal_last_error()` to get the full
+///          error message.
+#[no_mangle]
+pub unsafe extern fn rascal_calculators_list(buffer: *mut c_char, bufflen: usize) -> rascal_status_t {
+    catch_unwind(|| {
+        check_pointers!(buffer);
+        let names = serde_json::to_string(&Calculator::registered_names())?;
+        copy_str_to_c(&names, buffer, bufflen)?;
+        Ok(())
+    })
+}
+
+/// Get the JSON schema describing the parameters of the calculator with the
+/// given `name`, as a JSON string. This can be used to discover the available
+/// parameters of calculators at runtime.
+///
+/// @param name NULL-terminated name of the calculator, as returned by
+///             `rascal_calculators_list`
+/// @param buffer pre-allocated buffer in which the JSON schema will be copied.
+///               If the buffer is too small, this function will return
+///               `RASCAL_BUFFER_SIZE_ERROR`
+/// @param bufflen size of the `buffer`
+///
+/// @returns The status code of this operation. If the status is not
+///          `RASCAL_SUCCESS`, you can use `rascal_last_error()` to get the full
+///          error message.
+#[no_mangle]
+pub unsafe extern fn rascal_calculators_schema(
+    name: *const c_char,
+    buffer: *mut c_char,
+    bufflen: usize,
+) -> rascal_status_t {
+    catch_unwind(|| {
+        check_pointers!(name, buffer);
+        let name = CStr::from_ptr(name).to_str()?;
+        let schema = Calculator::parameters_schema(name)?;
+        copy_str_to_c(&schema, buffer, bufflen)?;
+        Ok(())
+    })
+}
+
+/// Check if the calculator with the given `name` can compute gradients, i.e.
+/// if it accepts a `gradients` parameter, without having to create a
+/// calculator first.
+///
+/// @param name NULL-terminated name of the calculator, as returned by
+///             `rascal_calculators_list`
+/// @param supported pointer to a boolean, will be set to `true` if the
+///                  calculator supports gradients and `false` otherwise
+///
+/// @returns The status code of this operation. If the status is not
+///          `RASCAL_SUCCESS`, you can use `rascal_last_error()` to get the full
+///          error message.
+#[no_mangle]
+pub unsafe extern fn rascal_calculators_supports_gradients(
+    name: *const c_char,
+    supported: *mut bool,
+) -> rascal_status_t {
+    catch_unwind(|| {
+        check_pointers!(name, supported);
+        let name = CStr::from_ptr(name).to_str()?;
+        *supported = Calculator::registered_supports_gradients(name)?;
+        Ok(())
+    })
+}
+
+/// Check if the optional `feature` was enabled when compiling rascaline. The
+/// only optional feature is currently `"chemfiles"`, which is required to use
+/// `rascal_basic_systems_read`. Unknown features are reported as disabled.
+///
+/// @param feature NULL-terminated name of the feature
+/// @param enabled pointer to a boolean, will be set to `true` if the feature
+///                is enabled and `false` otherwise
+///
+/// @returns The status code of this operation. If the status is not
+///          `RASCAL_SUCCESS`, you can use `rascal_last_error()` to get the full
+///          error message.
+#[no_mangle]
+pub unsafe extern fn rascal_has_feature(feature: *const c_char, enabled: *mut bool) -> rascal_status_t {
+    catch_unwind(|| {
+        check_pointers!(feature, enabled);
+        *enabled = match CStr::from_ptr(feature).to_str()? {
+            "chemfiles" => rascaline::systems::CHEMFILES_SUPPORT,
+            _ => false,
+        };
+        Ok(())
+    })
+}
@@ -10,16 +10,21 @@ mod utils;
 mod status;
 pub use self::status::{catch_unwind, rascal_status_t};
 pub use self::status::{RASCAL_SUCCESS, RASCAL_INVALID_PARAMETER_ERROR, RASCAL_JSON_ERROR};
-pub use self::status::{RASCAL_UTF8_ERROR, RASCAL_CHEMFILES_ERROR, RASCAL_SYSTEM_ERROR};
-pub use self::status::{RASCAL_BUFFER_SIZE_ERROR, RASCAL_INTERNAL_ERROR};
+pub use self::status::{RASCAL_UTF8_ERROR, RASCAL_CHEMFILES_ERROR, RASCAL_IO_ERROR, RASCAL_SYSTEM_ERROR};
+pub use self::status::{RASCAL_CANCELLED_ERROR, RASCAL_BUFFER_SIZE_ERROR, RASCAL_INTERNAL_ERROR};
 
 mod logging;
 pub use self::logging::{RASCAL_LOG_LEVEL_ERROR, RASCAL_LOG_LEVEL_WARN, RASCAL_LOG_LEVEL_INFO};
 pub use self::logging::{RASCAL_LOG_LEVEL_DEBUG, RASCAL_LOG_LEVEL_TRACE};
-pub use self::logging::{rascal_logging_callback_t, rascal_set_logging_callback};
+pub use self::logging::{rascal_logging_callback_t, rascal_set_logging_callback, rascal_set_logging_level};
+
+mod memory;
+pub use self::memory::{rascal_malloc_callback_t, rascal_free_callback_t};
+pub use self::memory::{rascal_set_allocator, rascal_free};
 
 pub mod system;
 pub mod descriptor;
 pub mod calculator;
 
 pub mod profiling;
+pub mod info;
@@ -1,9 +1,12 @@
 use std::ffi::{CString};
 use std::sync::Mutex;
+use std::sync::atomic::{AtomicI32, Ordering};
 
 use log::{Record, Metadata};
 use lazy_static::lazy_static;
 
+use rascaline::Error;
+
 use crate::status::{rascal_status_t, catch_unwind};
 
 /// The "error" level designates very serious errors
@@ -44,44 +47,82 @@ lazy_static! {
     static ref GLOBAL_CALLBACK: Mutex<rascal_logging_callback_t> = Mutex::new(None);
 }
 
+/// Most verbose log level for which the callback is called, defaults to
+/// `RASCAL_LOG_LEVEL_DEBUG` in debug mode and `RASCAL_LOG_LEVEL_INFO` in
+/// release mode.
+static GLOBAL_LEVEL: AtomicI32 = AtomicI32::new(
+    if cfg!(debug_assertions) { RASCAL_LOG_LEVEL_DEBUG } else { RASCAL_LOG_LEVEL_INFO }
+);
+
+/// Convert one of the `RASCAL_LOG_LEVEL_XXX` constants to a `log::LevelFilter`
+fn level_filter(level: i32) -> log::LevelFilter {
+    match level {
+        RASCAL_LOG_LEVEL_ERROR => log::LevelFilter::Error,
+        RASCAL_LOG_LEVEL_WARN => log::LevelFilter::Warn,
+        RASCAL_LOG_LEVEL_INFO => log::LevelFilter::Info,
+        RASCAL_LOG_LEVEL_DEBUG => log::LevelFilter::Debug,
+        RASCAL_LOG_LEVEL_TRACE => log::LevelFilter::Trace,
+        _ => unreachable!("invalid log level {}", level),
+    }
+}
+
 /// Implementation of `log::Log` that forward all log messages to the global
 /// `rascal_logging_callback_t`.
 struct RascalLogger;
 
 /// Set the given ``callback`` function as the global logging callback. This
 /// function will be called on all log events. If a logging callback was already
-/// set, it is replaced by the new one.
+/// set, it is replaced by the new one. If ``callback`` is `NULL`, log events
+/// are discarded.
 #[no_mangle]
 pub unsafe extern fn rascal_set_logging_callback(callback: rascal_logging_callback_t) -> rascal_status_t {
     catch_unwind(|| {
         *GLOBAL_CALLBACK.lock().expect("mutex was poisoned") = callback;
         // we allow multiple sets of logger, therefore the result will be ignored
         let _ = log::set_boxed_logger(Box::new(RascalLogger));
+        log::set_max_level(level_filter(GLOBAL_LEVEL.load(Ordering::Relaxed)));
+
+        Ok(())
+    })
+}
 
-        if cfg!(debug_assertions) {
-            log::set_max_level(log::LevelFilter::Debug);
-        } else {
-            log::set_max_level(log::LevelFilter::Info);
+/// Set the most verbose log level for which the logging callback will be
+/// called. ``level`` must be one of `RASCAL_LOG_LEVEL_ERROR`,
+/// `RASCAL_LOG_LEVEL_WARN` `RASCAL_LOG_LEVEL_INFO`, `RASCAL_LOG_LEVEL_DEBUG`,
+/// or `RASCAL_LOG_LEVEL_TRACE`.
+///
+/// By default, the level is `RASCAL_LOG_LEVEL_INFO`, or
+/// `RASCAL_LOG_LEVEL_DEBUG` if rascaline was compiled in debug mode.
+#[no_mangle]
+pub unsafe extern fn rascal_set_logging_level(level: i32) -> rascal_status_t {
+    catch_unwind(|| {
+        if !(RASCAL_LOG_LEVEL_ERROR..=RASCAL_LOG_LEVEL_TRACE).contains(&level) {
+            return Err(Error::InvalidParameter(format!(
+                "invalid log level {}, expected a value between {} and {}",
+                level, RASCAL_LOG_LEVEL_ERROR, RASCAL_LOG_LEVEL_TRACE
+            )));
         }
 
+        GLOBAL_LEVEL.store(level, Ordering::Relaxed);
+        log::set_max_level(level_filter(level));
+
         Ok(())
     })
 }
 
 
 impl log::Log for RascalLogger {
-    fn enabled(&self, _: &Metadata) -> bool {
-       return true;
+    fn enabled(&self, metadata: &Metadata) -> bool {
+       return metadata.level() <= level_filter(GLOBAL_LEVEL.load(Ordering::Relaxed));
     }
 
     fn log(&self, record: &Record) {
         if self.enabled(record.metadata()) {
             let message = format!("{} -- {}", record.target(), record.args());
             let message_cstr = CString::new(message).unwrap();
-            unsafe {
-                match *(GLOBAL_CALLBACK.lock().expect("mutex was poisoned")) {
-                    Some(callback) => callback(record.level() as i32, message_cstr.as_ptr()),
-                    None => unreachable!("missing callback but RascalLogger is set as the global logger"),
+            if let Some(callback) = *GLOBAL_CALLBACK.lock().expect("mutex was poisoned") {
+                unsafe {
+                    callback(record.level() as i32, message_cstr.as_ptr());
                 }
             }
         }
@@ -101,5 +142,8 @@ mod tests {
         assert_eq!(RASCAL_LOG_LEVEL_INFO, log::Level::Info as i32);
         assert_eq!(RASCAL_LOG_LEVEL_DEBUG, log::Level::Debug as i32);
         assert_eq!(RASCAL_LOG_LEVEL_TRACE, log::Level::Trace as i32);
+
+        assert_eq!(level_filter(RASCAL_LOG_LEVEL_WARN), log::LevelFilter::Warn);
+        assert_eq!(level_filter(RASCAL_LOG_LEVEL_TRACE), log::LevelFilter::Trace);
     }
 }
@@ -0,0 +1,152 @@
+use std::os::raw::c_void;
+use std::sync::Mutex;
+
+use lazy_static::lazy_static;
+
+use rascaline::Error;
+
+use crate::status::{rascal_status_t, catch_unwind};
+
+/// Callback function type used to allocate memory for buffers returned by
+/// rascaline to the caller. The function receives the requested size in bytes
+/// and should return a pointer to a suitably aligned block of memory, or
+/// `NULL` if the allocation failed.
+#[allow(non_camel_case_types)]
+pub type rascal_malloc_callback_t = Option<unsafe extern fn(size: usize) -> *mut c_void>;
+
+/// Callback function type used to release memory previously allocated with the
+/// corresponding `rascal_malloc_callback_t`.
+#[allow(non_camel_case_types)]
+pub type rascal_free_callback_t = Option<unsafe extern fn(ptr: *mut c_void)>;
+
+/// Pair of allocation functions used for all buffers handed over to the
+/// caller, and number of buffers allocated with these functions which have not
+/// yet been released with `rascal_free`.
+struct Allocator {
+    malloc: unsafe extern fn(size: usize) -> *mut c_void,
+    free: unsafe extern fn(ptr: *mut c_void),
+    alive: usize,
+}
+
+unsafe extern fn default_malloc(size: usize) -> *mut c_void {
+    libc::malloc(size)
+}
+
+unsafe extern fn default_free(ptr: *mut c_void) {
+    libc::free(ptr);
+}
+
+// Mutex cannot use rust static
+// see https://stackoverflow.com/a/27826181
+lazy_static! {
+    static ref GLOBAL_ALLOCATOR: Mutex<Allocator> = Mutex::new(Allocator {
+        malloc: default_malloc,
+        free: default_free,
+        alive: 0,
+    });
+}
+
+/// Allocate a zero-initialized array of `count` elements of type `T` with the
+/// allocator registered through `rascal_set_allocator`. The returned pointer
+/// is `NULL` if `count` is 0.
+pub(crate) unsafe fn allocate_array<T>(count: usize) -> Result<*mut T, Error> {
+    if count == 0 {
+        return Ok(std::ptr::null_mut());
+    }
+
+    let size = count.checked_mul(std::mem::size_of::<T>()).ok_or_else(|| Error::BufferSize(format!(
+        "can not allocate an array of {} elements: the size overflows", count
+    )))?;
+
+    let mut allocator = GLOBAL_ALLOCATOR.lock().expect("mutex was poisoned");
+    let ptr = (allocator.malloc)(size).cast::<T>();
+    if ptr.is_null() {
+        return Err(Error::BufferSize(format!(
+            "failed to allocate {} bytes of memory", size
+        )));
+    }
+    allocator.alive += 1;
+
+    std::ptr::write_bytes(ptr, 0, count);
+    return Ok(ptr);
+}
+
+/// Set the functions used to allocate and release the buffers created by
+/// rascaline and handed over to the caller, i.e. the buffers which must be
+/// released with `rascal_free`: the array returned by
+/// `rascal_descriptor_densify_values` and the buffer returned by
+/// `rascal_descriptor_to_buffer`. This is useful to integrate rascaline with
+/// code using a custom memory pool.
+///
+/// Memory which stays owned by rascaline is not allocated with these
+/// functions. This includes the values and gradients arrays of a descriptor
+/// (which are accessed through `rascal_descriptor_values` and
+/// `rascal_descriptor_gradients` but released by `rascal_descriptor_free`) and
+/// the message returned by `rascal_last_error`.
+///
+/// If both `malloc` and `free` are `NULL`, the default allocator (the C
+/// standard library `malloc` and `free`) is restored. The allocator can not be
+/// changed while buffers allocated with the current one are still alive: all of
+/// them must be released with `rascal_free` before calling this function
+/// again.
+///
+/// @param malloc function used to allocate memory, or `NULL`
+/// @param free function used to release memory allocated with `malloc`, or
+///             `NULL`
+///
+/// @returns The status code of this operation. If the status is not
+///          `RASCAL_SUCCESS`, you can use `rascal_last_error()` to get the full
+///          error message.
+#[no_mangle]
+pub unsafe extern fn rascal_set_allocator(
+    malloc: rascal_malloc_callback_t,
+    free: rascal_free_callback_t,
+) -> rascal_status_t {
+    catch_unwind(|| {
+        let allocator = match (malloc, free) {
+            (Some(malloc), Some(free)) => Allocator { malloc, free, alive: 0 },
+            (None, None) => Allocator { malloc: default_malloc, free: default_free, alive: 0 },
+            _ => {
+                return Err(Error::InvalidParameter(
+                    "malloc and free must either both be set or both be NULL".into()
+                ));
+            }
+        };
+
+        let mut current = GLOBAL_ALLOCATOR.lock().expect("mutex was poisoned");
+        if current.alive != 0 {
+            return Err(Error::InvalidParameter(format!(
+                "can not change the allocator while {} buffer(s) allocated by \
+                rascaline are still alive, release them with rascal_free first",
+                current.alive
+            )));
+        }
+
+        *current = allocator;
+        Ok(())
+    })
+}
+
+/// Release memory allocated by rascaline and handed over to the caller, using
+/// the `free` function registered with `rascal_set_allocator`.
+///
+/// This is the only way to release such memory: calling the `free` function
+/// given to `rascal_set_allocator` directly would prevent changing the
+/// allocator later. If `ptr` is `NULL`, this function does nothing.
+///
+/// @param ptr pointer to memory allocated by rascaline, or `NULL`
+///
+/// @returns The status code of this operation. If the status is not
+///          `RASCAL_SUCCESS`, you can use `rascal_last_error()` to get the full
+///          error message.
+#[no_mangle]
+pub unsafe extern fn rascal_free(ptr: *mut c_void) -> rascal_status_t {
+    catch_unwind(|| {
+        if !ptr.is_null() {
+            let mut allocator = GLOBAL_ALLOCATOR.lock().expect("mutex was poisoned");
+            (allocator.free)(ptr);
+            allocator.alive = allocator.alive.saturating_sub(1);
+        }
+        Ok(())
+    })
+}
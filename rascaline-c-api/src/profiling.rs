@@ -16,7 +16,7 @@ use crate::utils::copy_str_to_c;
 #[no_mangle]
 pub unsafe extern fn rascal_profiling_clear() -> rascal_status_t {
     catch_unwind(|| {
-        time_graph::clear_collected_data();
+        rascaline::profiling::clear();
         Ok(())
     })
 }
@@ -41,7 +41,7 @@ pub unsafe extern fn rascal_profiling_clear() -> rascal_status_t {
 #[no_mangle]
 pub unsafe extern fn rascal_profiling_enable(enabled: bool) -> rascal_status_t {
     catch_unwind(|| {
-        time_graph::enable_data_collection(enabled);
+        rascaline::profiling::enable(enabled);
         Ok(())
     })
 }
@@ -51,7 +51,11 @@ pub unsafe extern fn rascal_profiling_enable(enabled: bool) -> rascal_status_t {
 /// See also `rascal_profiling_enable` and `rascal_profiling_clear`.
 ///
 /// @param format in which format should the data be provided. `"table"`,
-///              `"short_table"` and `"json"` are currently supported
+///              `"short_table"`, `"json"` and `"call_tree"` are currently
+///              supported. `"call_tree"` gives the data as a JSON call tree,
+///              where each node contains the function `name`, the total
+///              `elapsed` time in seconds, the `total_calls` and the number of
+///              `calls` from the parent node, and the list of `children`.
 /// @param buffer pre-allocated buffer in which profiling data will be copied.
 ///               If the buffer is too small, this function will return
 ///               `RASCAL_BUFFER_SIZE_ERROR`
@@ -79,8 +83,11 @@ pub unsafe extern fn rascal_profiling_get(
             "json" => {
                 time_graph::get_full_graph().as_json()
             },
+            "call_tree" => {
+                rascaline::profiling::report().to_json()
+            },
             format => return Err(Error::InvalidParameter(format!(
-                "invalid data format in rascal_profiling_get: {}, expected 'table', 'short_table' or 'json'",
+                "invalid data format in rascal_profiling_get: {}, expected 'table', 'short_table', 'json' or 'call_tree'",
                 format
             )))
         };
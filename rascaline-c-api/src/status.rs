@@ -45,6 +45,11 @@ pub const RASCAL_JSON_ERROR: i32 = 2;
 pub const RASCAL_UTF8_ERROR: i32 = 3;
 /// Status code used for error related to reading files with chemfiles
 pub const RASCAL_CHEMFILES_ERROR: i32 = 4;
+/// Status code used for errors while reading or writing files
+pub const RASCAL_IO_ERROR: i32 = 5;
+/// Status code used when a calculation was cancelled with
+/// `rascal_cancellation_cancel`
+pub const RASCAL_CANCELLED_ERROR: i32 = 6;
 /// Status code used for errors coming from the system implementation if we
 /// don't have a more specific status
 pub const RASCAL_SYSTEM_ERROR: i32 = 128;
@@ -61,15 +66,19 @@ impl From<Error> for rascal_status_t {
         LAST_ERROR_MESSAGE.with(|message| {
             *message.borrow_mut() = CString::new(format!("{}", error)).expect("error message contains a null byte");
         });
-        match error {
+        // use the root cause of the error to get the status code, ignoring any
+        // additional context
+        match error.root() {
             Error::InvalidParameter(_) => rascal_status_t(RASCAL_INVALID_PARAMETER_ERROR),
             Error::Json(_) => rascal_status_t(RASCAL_JSON_ERROR),
             Error::Utf8(_) => rascal_status_t(RASCAL_UTF8_ERROR),
             Error::Chemfiles(_) => rascal_status_t(RASCAL_CHEMFILES_ERROR),
+            Error::Io(_) => rascal_status_t(RASCAL_IO_ERROR),
+            Error::Cancelled => rascal_status_t(RASCAL_CANCELLED_ERROR),
             Error::BufferSize(_) => rascal_status_t(RASCAL_BUFFER_SIZE_ERROR),
             Error::External{status, ..} => {
-                if status < 0 {
-                    rascal_status_t(status)
+                if *status < 0 {
+                    rascal_status_t(*status)
                 } else {
                     rascal_status_t(RASCAL_SYSTEM_ERROR)
                 }
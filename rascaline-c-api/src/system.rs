@@ -1,15 +1,20 @@
+use std::convert::TryFrom;
 use std::os::raw::{c_char, c_void};
-use std::ffi::CStr;
+use std::ffi::{CStr, CString};
 
 use rascaline::types::{Vector3D, Matrix3};
-use rascaline::systems::{SimpleSystem, Pair, UnitCell};
-use rascaline::{Error, System};
+use rascaline::systems::{SimpleSystem, Pair, Triplet, UnitCell};
+use rascaline::{Error, ErrorContext, System};
 
 use crate::RASCAL_SYSTEM_ERROR;
 
 use super::{catch_unwind, rascal_status_t};
 
 /// Pair of atoms coming from a neighbor list
+///
+/// The `cell_shift` field was added in version 0.2 of the C API, changing the
+/// size and layout of this struct. Code compiled against older headers must be
+/// re-compiled, and should check the version given by `rascal_version()`.
 #[repr(C)]
 pub struct rascal_pair_t {
     /// index of the first atom in the pair
@@ -21,8 +26,25 @@ pub struct rascal_pair_t {
     /// vector from the first atom to the second atom, wrapped inside the unit
     /// cell as required by periodic boundary conditions.
     pub vector: [f64; 3],
+    /// number of unit cell vectors that have to be added to the difference of
+    /// the positions of the two atoms to get `vector`. This should be set to
+    /// `{0, 0, 0}` for non-periodic systems.
+    pub cell_shift: [i32; 3],
+}
+
+/// Triplet of atoms `first-center-second` around a given center
+#[repr(C)]
+pub struct rascal_triplet_t {
+    /// index of the first neighbor of the center
+    pub first: usize,
+    /// index of the second neighbor of the center
+    pub second: usize,
 }
 
+/// Current version of the `rascal_system_t` struct. The `version` field of all
+/// systems should be set to this value.
+pub const RASCAL_SYSTEM_VERSION: u32 = 2;
+
 /// A `rascal_system_t` deals with the storage of atoms and related information,
 /// as well as the computation of neighbor lists.
 ///
@@ -42,6 +64,16 @@ pub struct rascal_pair_t {
 /// setting `user_data` to the actual data storage, and setting all function
 /// pointers to the correct functions. For an example of code doing this, see
 /// the `SystemBase` class in the Python interface to rascaline.
+///
+/// The `version` field must be set to `RASCAL_SYSTEM_VERSION`. New optional
+/// function pointers may be added at the end of this struct in future versions
+/// of rascaline. rascaline uses the `version` of each system to know the size
+/// of the struct the caller allocated: it only reads the function pointers
+/// that exist in this version, and uses this size to step through arrays of
+/// systems. Code compiled against older headers can then be used with newer
+/// versions of rascaline. The function pointers marked as optional below can
+/// be set to `NULL`, in which case rascaline falls back to a default
+/// behavior.
 
 // Function pointers have type `Option<unsafe extern fn(XXX)>`, where `Option`
 // ensure that the `impl System for rascal_system_t` is forced to deal with the
@@ -51,6 +83,8 @@ pub struct rascal_pair_t {
 // the default C ABI on the current system.
 #[repr(C)]
 pub struct rascal_system_t {
+    /// Version of this struct, which should be set to `RASCAL_SYSTEM_VERSION`
+    version: u32,
     /// User-provided data should be stored here, it will be passed as the
     /// first parameter to all function pointers below.
     user_data: *mut c_void,
@@ -94,9 +128,184 @@ pub struct rascal_system_t {
     /// included both in the return of `pairs_containing(i)` and
     /// `pairs_containing(j)`.
     pairs_containing: Option<unsafe extern fn(user_data: *const c_void, center: usize, pairs: *mut *const rascal_pair_t, count: *mut usize) -> rascal_status_t>,
+    /// Optional. This function should set `*has_cell_shifts` to `true` if the
+    /// `cell_shift` field of the pairs returned by `pairs` and
+    /// `pairs_containing` is set, and to `false` otherwise.
+    ///
+    /// If this function is `NULL`, the cell shifts are assumed to be set.
+    cell_shifts: Option<unsafe extern fn(user_data: *const c_void, has_cell_shifts: *mut bool) -> rascal_status_t>,
+    /// Optional. This function should set `*data` to a pointer to the first
+    /// element of a contiguous array containing the per-atom data with the
+    /// given `name` (for example partial charges), or to `NULL` if this system
+    /// does not contain such data. The array should contain
+    /// `rascal_system_t::size()` elements.
+    ///
+    /// If this function is `NULL`, the system does not contain any per-atom
+    /// data.
+    data: Option<unsafe extern fn(user_data: *const c_void, name: *const c_char, data: *mut *const f64) -> rascal_status_t>,
+    /// Optional. This function should set `*triplets` to a pointer to the first
+    /// element of a contiguous array containing all triplets `i-center-j`
+    /// around the atom with index `center`; and `*count` to the size of the
+    /// array/the number of triplets. Each triplet should be included once
+    /// (either as `i-j` or `j-i`), including triplets where `i == j`, and only
+    /// contain atoms from the pairs returned by `pairs_containing(center)`.
+    /// This function is only valid to call after a call to
+    /// `compute_neighbors`.
+    ///
+    /// If this function is `NULL`, rascaline builds the triplets from the pairs
+    /// returned by `pairs_containing`.
+    triplets_containing: Option<unsafe extern fn(user_data: *const c_void, center: usize, triplets: *mut *const rascal_triplet_t, count: *mut usize) -> rascal_status_t>,
+    /// Optional, since version 2. This function should set `*pairs` to a
+    /// pointer to the first element of a contiguous array containing all pairs
+    /// in this system for the given `cutoff`; and `*count` to the size of the
+    /// array/the number of pairs. The same restrictions on the list of pairs as
+    /// `rascal_system_t::pairs` applies.
+    ///
+    /// If this function is not `NULL`, it is used instead of `pairs`, with the
+    /// cutoff given to the last call to `compute_neighbors`.
+    pairs_with_cutoff: Option<unsafe extern fn(user_data: *const c_void, cutoff: f64, pairs: *mut *const rascal_pair_t, count: *mut usize) -> rascal_status_t>,
+    /// Optional, since version 2. This function should set `*pairs` to a
+    /// pointer to the first element of a contiguous array containing all pairs
+    /// in this system containing the atom with index `center` for the given
+    /// `cutoff`; and `*count` to the size of the array/the number of pairs. The
+    /// same restrictions on the list of pairs as
+    /// `rascal_system_t::pairs_containing` applies.
+    ///
+    /// If this function is not `NULL`, it is used instead of
+    /// `pairs_containing`, with the cutoff given to the last call to
+    /// `compute_neighbors`.
+    ///
+    /// If both `pairs_with_cutoff` and `pairs_containing_with_cutoff` are set,
+    /// `compute_neighbors` can be `NULL`. This allows codes which can provide
+    /// pairs on demand (for example from hierarchical neighbor data) to avoid
+    /// building a complete neighbor list before each calculation.
+    pairs_containing_with_cutoff: Option<unsafe extern fn(user_data: *const c_void, cutoff: f64, center: usize, pairs: *mut *const rascal_pair_t, count: *mut usize) -> rascal_status_t>,
 }
 
-impl<'a> System for &'a mut rascal_system_t {
+impl rascal_system_t {
+    /// Get the size in bytes of a `rascal_system_t` with the given `version`,
+    /// checking that this version is supported by this version of rascaline
+    fn size_for_version(version: u32) -> Result<usize, Error> {
+        match version {
+            1 => {
+                // version 1 ends before `pairs_with_cutoff`. All fields after
+                // `version` are pointers, so there is no padding to account for
+                let system = std::mem::MaybeUninit::<rascal_system_t>::uninit();
+                let start = system.as_ptr() as usize;
+                let end = unsafe {
+                    std::ptr::addr_of!((*system.as_ptr()).pairs_with_cutoff) as usize
+                };
+                Ok(end - start)
+            },
+            RASCAL_SYSTEM_VERSION => Ok(std::mem::size_of::<rascal_system_t>()),
+            _ => Err(Error::InvalidParameter(format!(
+                "unsupported rascal_system_t version {}, it should be set to \
+                RASCAL_SYSTEM_VERSION ({})", version, RASCAL_SYSTEM_VERSION
+            ))),
+        }
+    }
+
+    /// Read the system at `ptr`, only reading the fields which exist in the
+    /// version of this system. The fields missing in older versions are set
+    /// to `NULL`. This returns the system and the size of the struct in the
+    /// caller's memory.
+    unsafe fn read(ptr: *const rascal_system_t) -> Result<(rascal_system_t, usize), Error> {
+        // `version` is the first field in all versions of the struct
+        let version = std::ptr::addr_of!((*ptr).version).read();
+        let size = rascal_system_t::size_for_version(version)?;
+
+        let mut system = rascal_system_t::null();
+        std::ptr::copy_nonoverlapping(
+            ptr.cast::<u8>(),
+            std::ptr::addr_of_mut!(system).cast::<u8>(),
+            size,
+        );
+
+        return Ok((system, size));
+    }
+
+    /// Get an empty system, with all function pointers set to `NULL`
+    fn null() -> rascal_system_t {
+        rascal_system_t {
+            version: RASCAL_SYSTEM_VERSION,
+            user_data: std::ptr::null_mut(),
+            size: None,
+            species: None,
+            positions: None,
+            cell: None,
+            compute_neighbors: None,
+            pairs: None,
+            pairs_containing: None,
+            cell_shifts: None,
+            data: None,
+            triplets_containing: None,
+            pairs_with_cutoff: None,
+            pairs_containing_with_cutoff: None,
+        }
+    }
+}
+
+/// Convert the array of `count` systems starting at `systems` to a vector of
+/// `Box<dyn System>`, checking that all systems have a supported version.
+///
+/// The caller might have been compiled against an older (and smaller) version
+/// of `rascal_system_t`, so this steps through the array using the size
+/// corresponding to the version of each system.
+pub(crate) unsafe fn systems_from_c(systems: *mut rascal_system_t, count: usize) -> Result<Vec<Box<dyn System>>, Error> {
+    let mut ptr = systems.cast::<u8>();
+    let mut result = Vec::with_capacity(count);
+    for i in 0..count {
+        let (system, size) = rascal_system_t::read(ptr.cast())
+            .with_context(|| format!("invalid system at index {}", i))?;
+        result.push(Box::new(CSystem::new(system)) as Box<dyn System>);
+        ptr = ptr.add(size);
+    }
+
+    return Ok(result);
+}
+
+/// Implementation of the `System` trait on top of a `rascal_system_t`. This
+/// keeps track of the cutoff given to the last call to `compute_neighbors`, to
+/// be able to use the `xxx_with_cutoff` functions.
+struct CSystem {
+    system: rascal_system_t,
+    cutoff: Option<f64>,
+}
+
+impl CSystem {
+    /// Wrap the given `system`, which should have been read with
+    /// `rascal_system_t::read`
+    fn new(system: rascal_system_t) -> CSystem {
+        CSystem { system, cutoff: None }
+    }
+
+    /// Get the `pairs_with_cutoff` function, if it is set
+    fn pairs_with_cutoff(&self) -> Option<unsafe extern fn(*const c_void, f64, *mut *const rascal_pair_t, *mut usize) -> rascal_status_t> {
+        self.system.pairs_with_cutoff
+    }
+
+    /// Get the `pairs_containing_with_cutoff` function, if it is set
+    fn pairs_containing_with_cutoff(&self) -> Option<unsafe extern fn(*const c_void, f64, usize, *mut *const rascal_pair_t, *mut usize) -> rascal_status_t> {
+        self.system.pairs_containing_with_cutoff
+    }
+
+    /// Get the cutoff given to the last call to `compute_neighbors`
+    fn cutoff(&self) -> Result<f64, Error> {
+        self.cutoff.ok_or_else(|| Error::Internal(
+            "compute_neighbors must be called before accessing the pairs".into()
+        ))
+    }
+}
+
+impl std::ops::Deref for CSystem {
+    type Target = rascal_system_t;
+
+    fn deref(&self) -> &rascal_system_t {
+        &self.system
+    }
+}
+
+impl System for CSystem {
     fn size(&self) -> Result<usize, Error> {
         let function = self.size.ok_or_else(|| Error::External {
             status: RASCAL_SYSTEM_ERROR,
@@ -197,16 +406,19 @@ impl<'a> System for &'a mut rascal_system_t {
             });
         }
 
-        let matrix = Matrix3::from(value);
-        if matrix == Matrix3::zero() {
-            Ok(UnitCell::infinite())
-        } else {
-            Ok(UnitCell::from(matrix))
-        }
+        return UnitCell::try_from(Matrix3::from(value));
     }
 
     fn compute_neighbors(&mut self, cutoff: f64) -> Result<(), Error> {
-        let function = self.compute_neighbors.ok_or_else(|| Error::External {
+        self.cutoff = Some(cutoff);
+
+        let lazy_pairs = self.pairs_with_cutoff().is_some() && self.pairs_containing_with_cutoff().is_some();
+        if lazy_pairs && self.system.compute_neighbors.is_none() {
+            // pairs will be requested directly with the cutoff
+            return Ok(());
+        }
+
+        let function = self.system.compute_neighbors.ok_or_else(|| Error::External {
             status: RASCAL_SYSTEM_ERROR,
             message: "rascal_system_t.compute_neighbors function is NULL".into(),
         })?;
@@ -224,40 +436,148 @@ impl<'a> System for &'a mut rascal_system_t {
     }
 
     fn pairs(&self) -> Result<&[Pair], Error> {
-        let function = self.pairs.ok_or_else(|| Error::External {
-            status: RASCAL_SYSTEM_ERROR,
-            message: "rascal_system_t.pairs function is NULL".into(),
-        })?;
+        let mut ptr = std::ptr::null();
+        let mut count = 0;
 
+        if let Some(function) = self.pairs_with_cutoff() {
+            let status = unsafe {
+                function(self.user_data, self.cutoff()?, &mut ptr, &mut count)
+            };
+            if !status.is_success() {
+                return Err(Error::External {
+                    status: status.as_i32(),
+                    message: "call to rascal_system_t.pairs_with_cutoff failed".into(),
+                });
+            }
+        } else {
+            let function = self.system.pairs.ok_or_else(|| Error::External {
+                status: RASCAL_SYSTEM_ERROR,
+                message: "rascal_system_t.pairs function is NULL".into(),
+            })?;
+
+            let status = unsafe {
+                function(self.user_data, &mut ptr, &mut count)
+            };
+            if !status.is_success() {
+                return Err(Error::External {
+                    status: status.as_i32(),
+                    message: "call to rascal_system_t.pairs failed".into(),
+                });
+            }
+        }
+
+        if ptr.is_null() && count != 0 {
+            return Err(Error::External {
+                status: RASCAL_SYSTEM_ERROR,
+                message: "rascal_system_t.pairs returned a NULL pointer with non zero size".into(),
+            });
+        }
+        unsafe {
+            // SAFETY: ptr is non null, and Pair / rascal_pair_t have the same layout
+            return Ok(std::slice::from_raw_parts(ptr.cast(), count));
+        }
+    }
+
+    fn pairs_containing(&self, center: usize) -> Result<&[Pair], Error> {
         let mut ptr = std::ptr::null();
         let mut count = 0;
+
+        if let Some(function) = self.pairs_containing_with_cutoff() {
+            let status = unsafe {
+                function(self.user_data, self.cutoff()?, center, &mut ptr, &mut count)
+            };
+            if !status.is_success() {
+                return Err(Error::External {
+                    status: status.as_i32(),
+                    message: "call to rascal_system_t.pairs_containing_with_cutoff failed".into(),
+                });
+            }
+        } else {
+            let function = self.system.pairs_containing.ok_or_else(|| Error::External {
+                status: RASCAL_SYSTEM_ERROR,
+                message: "rascal_system_t.pairs_containing function is NULL".into(),
+            })?;
+
+            let status = unsafe {
+                function(self.user_data, center, &mut ptr, &mut count)
+            };
+            if !status.is_success() {
+                return Err(Error::External {
+                    status: status.as_i32(),
+                    message: "call to rascal_system_t.pairs_containing failed".into(),
+                });
+            }
+        }
+
+        if ptr.is_null() && count != 0 {
+            return Err(Error::External {
+                status: RASCAL_SYSTEM_ERROR,
+                message: "rascal_system_t.pairs_containing returned a NULL pointer with non zero size".into(),
+            });
+        }
+        unsafe {
+            // SAFETY: ptr is non null, and Pair / rascal_pair_t have the same layout
+            return Ok(std::slice::from_raw_parts(ptr.cast(), count));
+        }
+    }
+
+    fn has_cell_shifts(&self) -> Result<bool, Error> {
+        let function = match self.cell_shifts {
+            Some(function) => function,
+            None => return Ok(true),
+        };
+
+        let mut value = true;
         let status = unsafe {
-            function(self.user_data, &mut ptr, &mut count)
+            function(self.user_data, &mut value)
         };
+
         if !status.is_success() {
             return Err(Error::External {
                 status: status.as_i32(),
-                message: "call to rascal_system_t.pairs failed".into(),
+                message: "call to rascal_system_t.cell_shifts failed".into(),
             });
         }
 
-        if ptr.is_null() && count != 0 {
+        return Ok(value);
+    }
+
+    fn data(&self, name: &str) -> Result<Option<&[f64]>, Error> {
+        let function = match self.data {
+            Some(function) => function,
+            None => return Ok(None),
+        };
+
+        let name = CString::new(name).map_err(|_| Error::InvalidParameter(format!(
+            "data name '{}' contains a NULL byte", name
+        )))?;
+
+        let mut ptr = std::ptr::null();
+        let status = unsafe {
+            function(self.user_data, name.as_ptr(), &mut ptr)
+        };
+
+        if !status.is_success() {
             return Err(Error::External {
-                status: RASCAL_SYSTEM_ERROR,
-                message: "rascal_system_t.pairs returned a NULL pointer with non zero size".into(),
+                status: status.as_i32(),
+                message: "call to rascal_system_t.data failed".into(),
             });
         }
+
+        if ptr.is_null() {
+            return Ok(None);
+        }
+
         unsafe {
-            // SAFETY: ptr is non null, and Pair / rascal_pair_t have the same layout
-            return Ok(std::slice::from_raw_parts(ptr.cast(), count));
+            return Ok(Some(std::slice::from_raw_parts(ptr, self.size()?)));
         }
     }
 
-    fn pairs_containing(&self, center: usize) -> Result<&[Pair], Error> {
-        let function = self.pairs_containing.ok_or_else(|| Error::External {
-            status: RASCAL_SYSTEM_ERROR,
-            message: "rascal_system_t.pairs_containing function is NULL".into(),
-        })?;
+    fn triplets_containing(&self, center: usize) -> Result<Option<&[Triplet]>, Error> {
+        let function = match self.triplets_containing {
+            Some(function) => function,
+            None => return Ok(None),
+        };
 
         let mut ptr = std::ptr::null();
         let mut count = 0;
@@ -268,19 +588,20 @@ impl<'a> System for &'a mut rascal_system_t {
         if !status.is_success() {
             return Err(Error::External {
                 status: status.as_i32(),
-                message: "call to rascal_system_t.pairs_containing failed".into(),
+                message: "call to rascal_system_t.triplets_containing failed".into(),
             });
         }
 
         if ptr.is_null() && count != 0 {
             return Err(Error::External {
                 status: RASCAL_SYSTEM_ERROR,
-                message: "rascal_system_t.pairs_containing returned a NULL pointer with non zero size".into(),
+                message: "rascal_system_t.triplets_containing returned a NULL pointer with non zero size".into(),
             });
         }
+
         unsafe {
-            // SAFETY: ptr is non null, and Pair / rascal_pair_t have the same layout
-            return Ok(std::slice::from_raw_parts(ptr.cast(), count));
+            // SAFETY: ptr is non null, and Triplet / rascal_triplet_t have the same layout
+            return Ok(Some(std::slice::from_raw_parts(ptr.cast(), count)));
         }
     }
 }
@@ -365,7 +686,24 @@ impl From<SimpleSystem> for rascal_system_t {
             })
         }
 
+        unsafe extern fn data(
+            this: *const c_void,
+            name: *const c_char,
+            data: *mut *const f64,
+        ) -> rascal_status_t {
+            catch_unwind(|| {
+                let name = CStr::from_ptr(name).to_str()?;
+                *data = match (*this.cast::<SimpleSystem>()).data(name)? {
+                    Some(values) => values.as_ptr(),
+                    None => std::ptr::null(),
+                };
+
+                Ok(())
+            })
+        }
+
         rascal_system_t {
+            version: RASCAL_SYSTEM_VERSION,
             user_data: Box::into_raw(Box::new(system)).cast(),
             size: Some(size),
             species: Some(species),
@@ -374,10 +712,230 @@ impl From<SimpleSystem> for rascal_system_t {
             compute_neighbors: Some(compute_neighbors),
             pairs: Some(pairs),
             pairs_containing: Some(pairs_containing),
+            cell_shifts: None,
+            data: Some(data),
+            triplets_containing: None,
+            pairs_with_cutoff: None,
+            pairs_containing_with_cutoff: None,
         }
     }
 }
 
+#[allow(clippy::doc_markdown)]
+/// Create a new `rascal_system_t` from raw arrays of atomic `species` and
+/// `positions`, using the neighbor list implementation in rascaline.
+///
+/// `species` should contain `n_atoms` integers, and `positions` `3 x n_atoms`
+/// values, with the positions of each atom stored contiguously (`x0 y0 z0 x1
+/// y1 z1 ...`). `cell` should contain the 9 values of the unit cell matrix in
+/// row major order (`ax ay az bx by bz cx cy cz`), or be `NULL` for
+/// non-periodic systems. The cell vectors must form a right-handed basis, and
+/// must not be coplanar. The data is copied, and the arrays can be released
+/// after this function returns.
+///
+/// The memory allocated by this function must be released using
+/// `rascal_basic_system_free`.
+///
+/// The `system` is filled with the current `RASCAL_SYSTEM_VERSION` of
+/// `rascal_system_t`, and must point to a struct of the corresponding size.
+///
+/// @param species pointer to the first element of the atomic species array
+/// @param positions pointer to the first element of the positions array
+/// @param n_atoms number of atoms in the system
+/// @param cell pointer to the first element of the unit cell matrix, or `NULL`
+/// @param system pointer to a `rascal_system_t` which will be filled by this
+///               function
+///
+/// @returns The status code of this operation. If the status is not
+///          `RASCAL_SUCCESS`, you can use `rascal_last_error()` to get the full
+///          error message.
+#[no_mangle]
+pub unsafe extern fn rascal_basic_system(
+    species: *const i32,
+    positions: *const f64,
+    n_atoms: usize,
+    cell: *const f64,
+    system: *mut rascal_system_t,
+) -> rascal_status_t {
+    catch_unwind(move || {
+        check_pointers!(system);
+        if n_atoms != 0 {
+            check_pointers!(species, positions);
+        }
+
+        let cell = if cell.is_null() {
+            UnitCell::infinite()
+        } else {
+            let cell = std::slice::from_raw_parts(cell, 9);
+            let matrix = Matrix3::new([
+                [cell[0], cell[1], cell[2]],
+                [cell[3], cell[4], cell[5]],
+                [cell[6], cell[7], cell[8]],
+            ]);
+
+            UnitCell::try_from(matrix)?
+        };
+
+        let mut simple_system = SimpleSystem::new(cell);
+        if n_atoms != 0 {
+            let species = std::slice::from_raw_parts(species, n_atoms);
+            let positions = std::slice::from_raw_parts(positions, 3 * n_atoms);
+            for (&species, position) in species.iter().zip(positions.chunks_exact(3)) {
+                if !position.iter().all(|x| x.is_finite()) {
+                    return Err(Error::InvalidParameter(format!(
+                        "got a non-finite position ({:?}) in rascal_basic_system", position
+                    )));
+                }
+                simple_system.add_atom(species, Vector3D::new(position[0], position[1], position[2]));
+            }
+        }
+
+        *system = simple_system.into();
+
+        Ok(())
+    })
+}
+
+/// Release memory allocated by `rascal_basic_system`.
+///
+/// This function is only valid to call with a system created by
+/// `rascal_basic_system`. All the fields of the `system` are set to `NULL`
+/// after this call. If `system` is NULL, this function does nothing.
+///
+/// @param system pointer to a system created with `rascal_basic_system`
+///
+/// @returns The status code of this operation. If the status is not
+///          `RASCAL_SUCCESS`, you can use `rascal_last_error()` to get the full
+///          error message.
+#[no_mangle]
+pub unsafe extern fn rascal_basic_system_free(system: *mut rascal_system_t) -> rascal_status_t {
+    catch_unwind(|| {
+        if !system.is_null() {
+            let (data, size) = rascal_system_t::read(system)?;
+            let user_data = data.user_data.cast::<SimpleSystem>();
+            if !user_data.is_null() {
+                std::mem::drop(Box::from_raw(user_data));
+            }
+
+            // only overwrite the fields existing in the version of `system`
+            let mut null = rascal_system_t::null();
+            null.version = data.version;
+            std::ptr::copy_nonoverlapping(
+                std::ptr::addr_of!(null).cast::<u8>(),
+                system.cast::<u8>(),
+                size,
+            );
+        }
+
+        Ok(())
+    })
+}
+
+#[allow(clippy::doc_markdown)]
+/// Get the list of centers in `system` whose atomic environment (up to the
+/// given `cutoff`) contains any of the `moved` atoms. The `moved` atoms are
+/// always part of the list, which is sorted.
+///
+/// The descriptor only needs to be re-computed for these centers after the
+/// `moved` atoms changed positions, and the result can then be merged in the
+/// previous descriptor with `rascal_descriptor_update`. Since atoms can both
+/// enter and leave the environment of a center, this function should be
+/// called with the positions before and after the move, and the union of both
+/// lists used for the calculation.
+///
+/// @param system pointer to an existing system
+/// @param cutoff spherical cutoff used by the calculator
+/// @param moved pointer to the first element of an array containing the
+///              indexes of the moved atoms
+/// @param moved_count number of moved atoms
+/// @param centers pointer to the first element of an array of size at least
+///                the number of atoms in the system, which will be filled
+///                with the affected centers
+/// @param count `*count` will be set to the number of affected centers
+///
+/// @returns The status code of this operation. If the status is not
+///          `RASCAL_SUCCESS`, you can use `rascal_last_error()` to get the full
+///          error message.
+#[no_mangle]
+pub unsafe extern fn rascal_system_affected_centers(
+    system: *mut rascal_system_t,
+    cutoff: f64,
+    moved: *const usize,
+    moved_count: usize,
+    centers: *mut usize,
+    count: *mut usize,
+) -> rascal_status_t {
+    catch_unwind(|| {
+        check_pointers!(system, centers, count);
+        let moved = if moved_count == 0 {
+            &[]
+        } else {
+            check_pointers!(moved);
+            std::slice::from_raw_parts(moved, moved_count)
+        };
+
+        let (system, _) = rascal_system_t::read(system)?;
+        let mut system = CSystem::new(system);
+        let affected = rascaline::systems::affected_centers(&mut system, cutoff, moved)?;
+        std::ptr::copy_nonoverlapping(affected.as_ptr(), centers, affected.len());
+        *count = affected.len();
+
+        Ok(())
+    })
+}
+
+/// Use the given `pairs` as the neighbor list of a `system` created by
+/// `rascal_basic_system` or `rascal_basic_systems_read`, for calculations
+/// using the given `cutoff`.
+///
+/// This allows codes which already maintain a neighbor list to avoid
+/// re-computing it in rascaline. Calculations with a smaller cutoff will
+/// filter these pairs, and calculations with a larger cutoff will fail. The same restrictions as for
+/// `rascal_system_t::pairs` apply to the list of pairs: it should contain each
+/// pair only once, should not contain self pairs and should only contain pairs
+/// with a distance below the cutoff. The pairs are copied, and the array can be
+/// released after this function returns.
+///
+/// This function is only valid to call with a system created by
+/// `rascal_basic_system` or `rascal_basic_systems_read`. For other systems, the
+/// neighbor list is directly provided by the `pairs` and `pairs_containing`
+/// functions of `rascal_system_t`.
+///
+/// @param system pointer to a system created with `rascal_basic_system` or
+///               `rascal_basic_systems_read`
+/// @param cutoff spherical cutoff used to create the list of pairs
+/// @param pairs pointer to the first element of an array of pairs
+/// @param count number of pairs in the array
+///
+/// @returns The status code of this operation. If the status is not
+///          `RASCAL_SUCCESS`, you can use `rascal_last_error()` to get the full
+///          error message.
+#[no_mangle]
+pub unsafe extern fn rascal_basic_system_set_pairs(
+    system: *mut rascal_system_t,
+    cutoff: f64,
+    pairs: *const rascal_pair_t,
+    count: usize,
+) -> rascal_status_t {
+    catch_unwind(|| {
+        check_pointers!(system);
+        let user_data = (*system).user_data.cast::<SimpleSystem>();
+        check_pointers!(user_data);
+
+        let pairs = if count == 0 {
+            Vec::new()
+        } else {
+            check_pointers!(pairs);
+            // rascal_pair_t and Pair have the same memory layout
+            std::slice::from_raw_parts(pairs.cast::<Pair>(), count).to_vec()
+        };
+
+        (*user_data).set_pairs(cutoff, pairs)?;
+
+        Ok(())
+    })
+}
+
 /// Read all structures in the file at the given `path` using
 /// [chemfiles](https://chemfiles.org/), and convert them to an array of
 /// `rascal_system_t`.
@@ -454,3 +1012,55 @@ pub unsafe extern fn rascal_basic_systems_free(systems: *mut rascal_system_t, co
         Ok(())
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use std::ptr::addr_of_mut;
+    use super::*;
+
+    #[test]
+    fn older_versions() {
+        let species = [8, 1, 1];
+        let positions = [0.0, 0.0, 0.0, 0.0, 0.75, -0.5, 0.0, -0.75, -0.5];
+
+        unsafe {
+            let mut water = std::mem::zeroed::<rascal_system_t>();
+            assert!(rascal_basic_system(
+                species.as_ptr(), positions.as_ptr(), 3, std::ptr::null(), addr_of_mut!(water)
+            ).is_success());
+
+            let mut hydrogen = std::mem::zeroed::<rascal_system_t>();
+            assert!(rascal_basic_system(
+                species[1..].as_ptr(), positions[3..].as_ptr(), 2, std::ptr::null(), addr_of_mut!(hydrogen)
+            ).is_success());
+
+            // create an array of systems using the smaller version 1 struct
+            let size = rascal_system_t::size_for_version(1).unwrap();
+            assert!(size < std::mem::size_of::<rascal_system_t>());
+            assert_eq!(size % std::mem::size_of::<u64>(), 0);
+
+            let mut buffer = vec![0_u64; 2 * size / std::mem::size_of::<u64>()];
+            let start = buffer.as_mut_ptr().cast::<u8>();
+            std::ptr::copy_nonoverlapping(std::ptr::addr_of!(water).cast::<u8>(), start, size);
+            std::ptr::copy_nonoverlapping(std::ptr::addr_of!(hydrogen).cast::<u8>(), start.add(size), size);
+            std::ptr::write(start.cast::<u32>(), 1);
+            std::ptr::write(start.add(size).cast::<u32>(), 1);
+
+            let systems = systems_from_c(start.cast(), 2).unwrap();
+            assert_eq!(systems[0].size().unwrap(), 3);
+            assert_eq!(systems[1].size().unwrap(), 2);
+            assert_eq!(systems[1].species().unwrap(), &[1, 1]);
+            std::mem::drop(systems);
+
+            std::ptr::write(start.add(size).cast::<u32>(), 3);
+            let error = systems_from_c(start.cast(), 2).err().unwrap();
+            assert_eq!(error.to_string(),
+                "invalid system at index 1: invalid parameter: unsupported \
+                rascal_system_t version 3, it should be set to RASCAL_SYSTEM_VERSION (2)"
+            );
+
+            assert!(rascal_basic_system_free(addr_of_mut!(water)).is_success());
+            assert!(rascal_basic_system_free(addr_of_mut!(hydrogen)).is_success());
+        }
+    }
+}
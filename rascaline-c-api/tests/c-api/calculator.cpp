@@ -1,6 +1,7 @@
 #include <vector>
 #include <string>
 #include <cstring>
+#include <algorithm>
 
 #include "rascaline.h"
 #include "catch.hpp"
@@ -135,6 +136,68 @@ TEST_CASE("calculator features count") {
     }
 }
 
+TEST_CASE("calculator supports gradients") {
+    SECTION("dummy_calculator") {
+        std::string HYPERS_JSON = R"({
+            "cutoff": 3.5,
+            "delta": 25,
+            "name": "bar",
+            "gradients": false
+        })";
+        auto* calculator = rascal_calculator("dummy_calculator", HYPERS_JSON.c_str());
+        REQUIRE(calculator != nullptr);
+
+        bool supported = false;
+        CHECK_SUCCESS(rascal_calculator_supports_gradients(calculator, &supported));
+        CHECK(supported);
+
+        rascal_calculator_free(calculator);
+    }
+
+    SECTION("sorted distances vector") {
+        std::string HYPERS_JSON = R"({
+            "cutoff": 3.5,
+            "max_neighbors": 25
+        })";
+        auto* calculator = rascal_calculator("sorted_distances", HYPERS_JSON.c_str());
+        REQUIRE(calculator != nullptr);
+
+        bool supported = true;
+        CHECK_SUCCESS(rascal_calculator_supports_gradients(calculator, &supported));
+        CHECK_FALSE(supported);
+
+        rascal_calculator_free(calculator);
+    }
+}
+
+TEST_CASE("library information") {
+    auto version = std::string(rascal_version());
+    CHECK(std::count(version.begin(), version.end(), '.') == 2);
+
+    char buffer[256] = {0};
+    CHECK(rascal_calculators_list(buffer, 4) == RASCAL_BUFFER_SIZE_ERROR);
+    CHECK_SUCCESS(rascal_calculators_list(buffer, sizeof(buffer)));
+    CHECK(buffer == std::string(R"(["angular_distribution","bond_environment","composition","dummy_calculator","electrostatic_potential","graph_moments","lambda_soap","pipeline","radial_distribution","soap_power_spectrum","sorted_distances","spherical_expansion","voronoi"])"));
+
+    char schema[4096] = {0};
+    CHECK(rascal_calculators_schema("sorted_distances", schema, 4) == RASCAL_BUFFER_SIZE_ERROR);
+    CHECK_SUCCESS(rascal_calculators_schema("sorted_distances", schema, sizeof(schema)));
+    CHECK(std::string(schema).find(R"("max_neighbors")") != std::string::npos);
+    CHECK(rascal_calculators_schema("not there", schema, sizeof(schema)) == RASCAL_INVALID_PARAMETER_ERROR);
+
+    bool supported = false;
+    CHECK_SUCCESS(rascal_calculators_supports_gradients("dummy_calculator", &supported));
+    CHECK(supported);
+    CHECK_SUCCESS(rascal_calculators_supports_gradients("sorted_distances", &supported));
+    CHECK_FALSE(supported);
+    CHECK(rascal_calculators_supports_gradients("not there", &supported) == RASCAL_INVALID_PARAMETER_ERROR);
+
+    bool enabled = true;
+    CHECK_SUCCESS(rascal_has_feature("not-a-feature", &enabled));
+    CHECK_FALSE(enabled);
+    CHECK_SUCCESS(rascal_has_feature("chemfiles", &enabled));
+}
+
 TEST_CASE("calculator creation errors") {
     const char* HYPERS_JSON = R"({
         "cutoff": "532",
@@ -145,7 +208,85 @@ TEST_CASE("calculator creation errors") {
     auto *calculator = rascal_calculator("dummy_calculator", HYPERS_JSON);
     CHECK(calculator == nullptr);
 
-    CHECK(std::string(rascal_last_error()) == "json error: invalid type: string \"532\", expected f64 at line 2 column 23");
+    CHECK(std::string(rascal_last_error()) == "invalid parameters for calculator 'dummy_calculator': invalid parameter: expected a number for 'cutoff', got \"532\"");
+}
+
+TEST_CASE("calculator cancellation") {
+    const char* HYPERS_JSON = R"({
+        "cutoff": 3.0,
+        "delta": 4,
+        "name": "",
+        "gradients": false
+    })";
+
+    auto* descriptor = rascal_descriptor();
+    REQUIRE(descriptor != nullptr);
+    auto* calculator = rascal_calculator("dummy_calculator", HYPERS_JSON);
+    REQUIRE(calculator != nullptr);
+    auto* cancellation = rascal_cancellation(calculator);
+    REQUIRE(cancellation != nullptr);
+
+    auto system = simple_system();
+    rascal_calculation_options_t options = {0};
+
+    CHECK_SUCCESS(rascal_cancellation_cancel(cancellation));
+    CHECK(rascal_calculator_compute(
+        calculator, descriptor, &system, 1, options
+    ) == RASCAL_CANCELLED_ERROR);
+    CHECK(std::string(rascal_last_error()) == "the calculation was cancelled");
+
+    // the next calculation runs normally
+    CHECK_SUCCESS(rascal_calculator_compute(
+        calculator, descriptor, &system, 1, options
+    ));
+
+    CHECK_SUCCESS(rascal_cancellation_free(cancellation));
+    rascal_calculator_free(calculator);
+    rascal_descriptor_free(descriptor);
+}
+
+TEST_CASE("check finite values") {
+    const char* HYPERS_JSON = R"({
+        "cutoff": 1.0,
+        "delta": 0,
+        "name": "",
+        "gradients": false
+    })";
+
+    auto* descriptor = rascal_descriptor();
+    REQUIRE(descriptor != nullptr);
+    auto* calculator = rascal_calculator("dummy_calculator", HYPERS_JSON);
+    REQUIRE(calculator != nullptr);
+
+    // the dummy calculator sums the positions, which overflows here
+    int32_t species[] = {1, 1};
+    double positions[] = {
+        0.0, 0.0, 0.0,
+        1e308, 1e308, 0.0,
+    };
+    rascal_system_t system = {0};
+    CHECK_SUCCESS(rascal_basic_system(species, positions, 2, nullptr, &system));
+
+    rascal_calculation_options_t options = {0};
+    CHECK_SUCCESS(rascal_calculator_compute(
+        calculator, descriptor, &system, 1, options
+    ));
+
+    options.check_finite = true;
+    CHECK(rascal_calculator_compute(
+        calculator, descriptor, &system, 1, options
+    ) == RASCAL_INTERNAL_ERROR);
+
+    auto expected = std::string(
+        "non-finite data in the output of calculator 'dummy_calculator': "
+        "internal error: got inf in values for sample (structure: 0, center: 1) "
+        "and feature (index_delta: 0, x_y_z: 1)"
+    );
+    CHECK(rascal_last_error() == expected);
+
+    CHECK_SUCCESS(rascal_basic_system_free(&system));
+    rascal_calculator_free(calculator);
+    rascal_descriptor_free(descriptor);
 }
 
 TEST_CASE("Compute descriptor") {
@@ -209,6 +350,33 @@ TEST_CASE("Compute descriptor") {
         }
     }
 
+    SECTION("Lazy gradients") {
+        auto system = simple_system();
+
+        rascal_calculation_options_t options = {0};
+        options.lazy_gradients = true;
+        CHECK_SUCCESS(rascal_calculator_compute(
+            calculator, descriptor, &system, 1, options
+        ));
+
+        rascal_indexes_t indexes = {0};
+        CHECK_SUCCESS(rascal_descriptor_indexes(descriptor, RASCAL_INDEXES_GRADIENT_SAMPLES, &indexes));
+        CHECK(indexes.count == 0);
+
+        double* data = nullptr;
+        uintptr_t shape[2] = {0};
+        CHECK_SUCCESS(rascal_descriptor_gradients(descriptor, &data, &shape[0], &shape[1]));
+        CHECK(shape[0] == 18);
+        CHECK(shape[1] == 2);
+        for (size_t i=0; i<shape[0]; i++) {
+            CHECK(data[i * shape[1] + 0] == 0);
+            CHECK(data[i * shape[1] + 1] == 1);
+        }
+
+        CHECK_SUCCESS(rascal_descriptor_indexes(descriptor, RASCAL_INDEXES_GRADIENT_SAMPLES, &indexes));
+        CHECK(indexes.count == 18);
+    }
+
     SECTION("Partial compute -- samples") {
         auto system = simple_system();
 
@@ -266,6 +434,55 @@ TEST_CASE("Compute descriptor") {
         }
     }
 
+    SECTION("Partial compute -- structures and centers") {
+        auto system = simple_system();
+
+        auto structures = std::vector<uintptr_t>{0, 0, 0};
+        auto centers = std::vector<uintptr_t>{3, 1, 3};
+
+        rascal_calculation_options_t options = {0};
+        CHECK_SUCCESS(rascal_calculator_compute_partial(
+            calculator, descriptor, &system, 1,
+            structures.data(), centers.data(), 3, options
+        ));
+
+        auto expected = std::vector<int32_t>{
+            0, 1, /**/ 0, 3,
+        };
+        check_indexes(descriptor, RASCAL_INDEXES_SAMPLES, {"structure", "center"}, expected, 2, 2);
+
+        double* data = nullptr;
+        uintptr_t shape[2] = {0};
+        CHECK_SUCCESS(rascal_descriptor_values(descriptor, &data, &shape[0], &shape[1]));
+        CHECK(shape[0] == 2);
+        CHECK(shape[1] == 2);
+
+        // all centers in the given structures
+        CHECK_SUCCESS(rascal_calculator_compute_partial(
+            calculator, descriptor, &system, 1,
+            structures.data(), nullptr, 1, options
+        ));
+
+        CHECK_SUCCESS(rascal_descriptor_values(descriptor, &data, &shape[0], &shape[1]));
+        CHECK(shape[0] == 4);
+        CHECK(shape[1] == 2);
+
+        // selected samples can not be used together with this function
+        auto names = std::vector<const char*>{"structure"};
+        auto samples = std::vector<int32_t>{0};
+        options.selected_samples.names = names.data();
+        options.selected_samples.values = samples.data();
+        options.selected_samples.count = 1;
+        options.selected_samples.size = 1;
+
+        auto status = rascal_calculator_compute_partial(
+            calculator, descriptor, &system, 1,
+            structures.data(), centers.data(), 3, options
+        );
+        CHECK(status != RASCAL_SUCCESS);
+        CHECK(std::string(rascal_last_error()) == "invalid parameter: selected samples can not be set in the options of rascal_calculator_compute_partial");
+    }
+
     SECTION("Partial compute -- features") {
         auto system = simple_system();
 
@@ -319,6 +536,67 @@ TEST_CASE("Compute descriptor") {
         }
     }
 
+    SECTION("Compute into user buffers") {
+        auto system = simple_system();
+        rascal_calculation_options_t options = {0};
+
+        uintptr_t samples = 0;
+        uintptr_t features = 0;
+        uintptr_t gradient_samples = 0;
+        CHECK_SUCCESS(rascal_calculator_compute_shape(
+            calculator, &system, 1, options, &samples, &features, &gradient_samples
+        ));
+        CHECK(samples == 4);
+        CHECK(features == 2);
+        CHECK(gradient_samples == 18);
+
+        auto values = std::vector<double>(samples * features);
+        auto gradients = std::vector<double>(gradient_samples * features);
+
+        // buffer is too small
+        CHECK(rascal_calculator_compute_into(
+            calculator, descriptor, &system, 1, options,
+            values.data(), values.size() - 1, gradients.data(), gradients.size()
+        ) == RASCAL_BUFFER_SIZE_ERROR);
+
+        // the calculation did not run
+        double* data = nullptr;
+        uintptr_t shape[2] = {0};
+        CHECK_SUCCESS(rascal_descriptor_values(descriptor, &data, &shape[0], &shape[1]));
+        CHECK(shape[0] == 0);
+
+        CHECK_SUCCESS(rascal_calculator_compute_into(
+            calculator, descriptor, &system, 1, options,
+            values.data(), values.size(), gradients.data(), gradients.size()
+        ));
+
+        auto expected = std::vector<double>{
+            4, 3, /**/ 5, 9, /**/ 6, 18, /**/ 7, 15,
+        };
+        CHECK(values == expected);
+
+        for (size_t i=0; i<gradient_samples; i++) {
+            CHECK(gradients[i * features + 0] == 0);
+            CHECK(gradients[i * features + 1] == 1);
+        }
+
+        // the descriptor contains the same data
+        rascal_indexes_t indexes = {0};
+        CHECK_SUCCESS(rascal_descriptor_indexes(
+            descriptor, RASCAL_INDEXES_SAMPLES, &indexes
+        ));
+        CHECK(indexes.count == 4);
+
+        CHECK_SUCCESS(rascal_descriptor_values(descriptor, &data, &shape[0], &shape[1]));
+        CHECK(shape[0] == samples);
+        CHECK(shape[1] == features);
+        CHECK(std::vector<double>(data, data + shape[0] * shape[1]) == expected);
+
+        CHECK_SUCCESS(rascal_descriptor_gradients(descriptor, &data, &shape[0], &shape[1]));
+        CHECK(shape[0] == gradient_samples);
+        CHECK(std::vector<double>(data, data + shape[0] * shape[1]) == gradients);
+    }
+
     SECTION("Partial compute -- empty") {
         auto system = simple_system();
 
@@ -415,6 +693,27 @@ TEST_CASE("Compute descriptor") {
         );
         CHECK(status != RASCAL_SUCCESS);
         CHECK(std::string(rascal_last_error()) == "invalid parameter: 'foo' in requested features is not part of the features of this calculator");
+
+        // NULL values for non-empty selected samples
+        std::memset(&options, 0, sizeof(rascal_calculation_options_t));
+        auto samples_names = std::vector<const char*> {
+            "structure", "center"
+        };
+        options.selected_samples.names = samples_names.data();
+        options.selected_samples.size = 2;
+        options.selected_samples.values = nullptr;
+        options.selected_samples.count = 3;
+
+        CHECK(rascal_calculator_compute(
+            calculator, descriptor, &system, 1, options
+        ) == RASCAL_INVALID_PARAMETER_ERROR);
+
+        // selected samples without any variable
+        options.selected_samples.size = 0;
+        options.selected_samples.count = 0;
+        CHECK(rascal_calculator_compute(
+            calculator, descriptor, &system, 1, options
+        ) == RASCAL_INVALID_PARAMETER_ERROR);
     }
 
     rascal_calculator_free(calculator);
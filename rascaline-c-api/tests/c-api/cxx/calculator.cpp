@@ -1,5 +1,6 @@
 #include <vector>
 #include <string>
+#include <algorithm>
 
 #include "rascaline.hpp"
 #include "catch.hpp"
@@ -89,6 +90,41 @@ TEST_CASE("calculator features count") {
     }
 }
 
+TEST_CASE("calculator supports gradients") {
+    std::string HYPERS_JSON = R"({
+        "cutoff": 3.5,
+        "delta": 25,
+        "name": "bar",
+        "gradients": false
+    })";
+    auto calculator = rascaline::Calculator("dummy_calculator", HYPERS_JSON);
+    CHECK(calculator.supports_gradients());
+
+    HYPERS_JSON = R"({
+        "cutoff": 3.5,
+        "max_neighbors": 25
+    })";
+    calculator = rascaline::Calculator("sorted_distances", HYPERS_JSON);
+    CHECK_FALSE(calculator.supports_gradients());
+}
+
+TEST_CASE("library information") {
+    auto version = rascaline::version();
+    CHECK(std::count(version.begin(), version.end(), '.') == 2);
+
+    auto expected = std::vector<std::string>{
+        "dummy_calculator", "soap_power_spectrum", "sorted_distances", "spherical_expansion"
+    };
+    CHECK(rascaline::calculators_list() == expected);
+
+    auto schema = rascaline::calculator_schema("sorted_distances");
+    CHECK(schema.find(R"("max_neighbors")") != std::string::npos);
+    CHECK(rascaline::calculator_supports_gradients("dummy_calculator"));
+    CHECK_FALSE(rascaline::calculator_supports_gradients("sorted_distances"));
+
+    CHECK_FALSE(rascaline::has_feature("not-a-feature"));
+}
+
 TEST_CASE("calculator creation errors") {
     const char* HYPERS_JSON = R"({
         "cutoff": "532",
@@ -99,10 +135,39 @@ TEST_CASE("calculator creation errors") {
 
     CHECK_THROWS_WITH(
         rascaline::Calculator("dummy_calculator", HYPERS_JSON),
-        "json error: invalid type: string \"532\", expected f64 at line 2 column 23"
+        "invalid parameters for calculator 'dummy_calculator': invalid parameter: expected a number for 'cutoff', got \"532\""
     );
 }
 
+TEST_CASE("calculator cancellation") {
+    const char* HYPERS_JSON = R"({
+        "cutoff": 3.0,
+        "delta": 4,
+        "name": "",
+        "gradients": false
+    })";
+
+    auto system = TestSystem();
+    auto systems = std::vector<rascaline::System*>();
+    systems.push_back(&system);
+    auto calculator = rascaline::Calculator("dummy_calculator", HYPERS_JSON);
+
+    auto cancellation = calculator.cancellation();
+    cancellation.cancel();
+
+    try {
+        calculator.compute(systems);
+        FAIL("the calculation should have been cancelled");
+    } catch (const rascaline::RascalError& e) {
+        CHECK(e.status() == RASCAL_CANCELLED_ERROR);
+        CHECK(std::string(e.what()) == "the calculation was cancelled");
+    }
+
+    // the next calculation runs normally
+    auto descriptor = calculator.compute(systems);
+    CHECK(descriptor.values().shape()[0] == 4);
+}
+
 TEST_CASE("Compute descriptor") {
     const char* HYPERS_JSON = R"({
         "cutoff": 3.0,
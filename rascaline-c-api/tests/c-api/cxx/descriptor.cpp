@@ -85,6 +85,30 @@ TEST_CASE("Descriptor") {
         CHECK(values(3, 1) == 15);
     }
 
+    SECTION("memory layout") {
+        auto descriptor = rascaline::Descriptor();
+        CHECK(descriptor.layout() == RASCAL_MEMORY_LAYOUT_ROW_MAJOR);
+
+        descriptor.set_layout(RASCAL_MEMORY_LAYOUT_FEATURE_MAJOR);
+        CHECK(descriptor.layout() == RASCAL_MEMORY_LAYOUT_FEATURE_MAJOR);
+
+        compute_descriptor(descriptor);
+        auto values = descriptor.values();
+        CHECK(values.shape() == std::array<size_t, 2>{4, 2});
+        CHECK(values.strides() == std::array<size_t, 2>{1, 4});
+
+        CHECK(values(0, 0) == 5);
+        CHECK(values(0, 1) == 3);
+        CHECK(values(3, 0) == 8);
+        CHECK(values(3, 1) == 15);
+        CHECK(values.data()[1] == 6);
+
+        descriptor.set_layout(RASCAL_MEMORY_LAYOUT_ROW_MAJOR);
+        values = descriptor.values();
+        CHECK(values.strides() == std::array<size_t, 2>{2, 1});
+        CHECK(values(3, 1) == 15);
+    }
+
     SECTION("gradient samples") {
         auto descriptor = rascaline::Descriptor();
 
@@ -183,4 +207,53 @@ TEST_CASE("Descriptor") {
         CHECK(densified_positions[3].new_sample == 0);
         CHECK(densified_positions[3].feature_block == 3);
     }
+
+    SECTION("buffer") {
+        auto descriptor = rascaline::Descriptor();
+        compute_descriptor(descriptor);
+
+        auto buffer = descriptor.to_buffer();
+        CHECK(buffer.size() > 12);
+
+        auto loaded = rascaline::Descriptor();
+        loaded.from_buffer(buffer.data(), buffer.size());
+        CHECK(loaded.values().shape() == std::array<size_t, 2>{4, 2});
+        CHECK(loaded.gradients().shape() == std::array<size_t, 2>{18, 2});
+    }
+
+    SECTION("per-atom data") {
+        auto descriptor = rascaline::Descriptor();
+        compute_descriptor(descriptor);
+
+        auto values = descriptor.per_atom_values(0, 4);
+        CHECK(values.size() == 8);
+        CHECK(values[0] == descriptor.values()(0, 0));
+        CHECK(values[7] == descriptor.values()(3, 1));
+
+        auto weights = std::vector<double>(8, 1.0);
+        auto output = descriptor.scatter_gradients(0, weights, 4);
+        CHECK(output.size() == 12);
+        CHECK_THROWS_AS(descriptor.scatter_gradients(0, {1.0}, 4), rascaline::RascalError);
+
+        // updating a descriptor with itself does not change anything
+        auto partial = rascaline::Descriptor();
+        compute_descriptor(partial);
+        descriptor.update(partial);
+        CHECK(descriptor.per_atom_values(0, 4) == values);
+    }
+
+    SECTION("partial calculation") {
+        auto calculator = rascaline::Calculator("dummy_calculator", HYPERS_JSON);
+        auto system = TestSystem();
+        auto systems = std::vector<rascaline::System*>{&system};
+
+        auto descriptor = rascaline::Descriptor();
+        calculator.compute_partial(systems, descriptor, {0, 0}, {1, 3});
+        CHECK(descriptor.values().shape() == std::array<size_t, 2>{2, 2});
+
+        CHECK_THROWS_AS(
+            calculator.compute_partial(systems, descriptor, {0, 0}, {1}),
+            rascaline::RascalError
+        );
+    }
 }
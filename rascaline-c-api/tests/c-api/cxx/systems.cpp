@@ -41,6 +41,42 @@ TEST_CASE("basic systems") {
     CHECK_THAT(cell[8], Catch::Matchers::WithinULP(7.84785, 10));
 }
 
+TEST_CASE("basic system from arrays") {
+    auto system = rascaline::BasicSystem(
+        {8, 1, 1},
+        {
+            0.0, 0.0, 0.0,
+            0.0, 0.75, -0.5,
+            0.0, -0.75, -0.5,
+        }
+    );
+
+    auto* raw = system.as_rascal_system_t();
+    uintptr_t size = 0;
+    raw->size(raw->user_data, &size);
+    CHECK(size == 3);
+
+    // both hydrogen atoms are in the environment of the oxygen
+    auto centers = system.affected_centers(1.0, {1});
+    CHECK(centers == std::vector<uintptr_t>{0, 1});
+
+    // the system can be moved around
+    auto moved = std::move(system);
+    CHECK(moved.as_rascal_system_t()->user_data != nullptr);
+    CHECK(system.as_rascal_system_t()->user_data == nullptr);
+
+    CHECK_THROWS_AS(rascaline::BasicSystem({8, 1}, {0.0, 0.0, 0.0}), rascaline::RascalError);
+
+    auto calculator = rascaline::Calculator("dummy_calculator", R"({
+        "cutoff": 3.0,
+        "delta": 4,
+        "name": "",
+        "gradients": false
+    })");
+    auto descriptor = calculator.compute(moved);
+    CHECK(descriptor.values().shape() == std::array<size_t, 2>{3, 2});
+}
+
 class BadSystem: public rascaline::System {
 public:
     uintptr_t size() const override {
@@ -1,3 +1,6 @@
+#include <cstdio>
+#include <string>
+
 #include "rascaline.h"
 #include "catch.hpp"
 #include "helpers.hpp"
@@ -87,6 +90,39 @@ TEST_CASE("rascal_descriptor_t") {
         rascal_descriptor_free(descriptor);
     }
 
+    SECTION("indexes names") {
+        auto* descriptor = rascal_descriptor();
+        REQUIRE(descriptor != nullptr);
+
+        uintptr_t count = 42;
+        CHECK_SUCCESS(rascal_descriptor_indexes_names_count(
+            descriptor, RASCAL_INDEXES_SAMPLES, &count
+        ));
+        CHECK(count == 0);
+
+        compute_descriptor(descriptor);
+        CHECK_SUCCESS(rascal_descriptor_indexes_names_count(
+            descriptor, RASCAL_INDEXES_SAMPLES, &count
+        ));
+        CHECK(count == 2);
+
+        const char* names[2] = {nullptr};
+        uintptr_t lengths[2] = {0};
+        CHECK_SUCCESS(rascal_descriptor_indexes_names(
+            descriptor, RASCAL_INDEXES_SAMPLES, names, lengths, 2
+        ));
+        CHECK(std::string(names[0], lengths[0]) == "structure");
+        CHECK(std::string(names[1], lengths[1]) == "center");
+
+        auto status = rascal_descriptor_indexes_names(
+            descriptor, RASCAL_INDEXES_SAMPLES, names, lengths, 1
+        );
+        CHECK(status != RASCAL_SUCCESS);
+        CHECK(std::string(rascal_last_error()) == "buffer is not big enough: names buffer has space for 1 elements, but the indexes contain 2 names");
+
+        rascal_descriptor_free(descriptor);
+    }
+
     SECTION("values") {
         auto* descriptor = rascal_descriptor();
         REQUIRE(descriptor != nullptr);
@@ -118,6 +154,47 @@ TEST_CASE("rascal_descriptor_t") {
         CHECK_SUCCESS(rascal_descriptor_free(descriptor));
     }
 
+    SECTION("memory layout") {
+        auto* descriptor = rascal_descriptor();
+        REQUIRE(descriptor != nullptr);
+
+        rascal_memory_layout layout = RASCAL_MEMORY_LAYOUT_FEATURE_MAJOR;
+        CHECK_SUCCESS(rascal_descriptor_layout(descriptor, &layout));
+        CHECK(layout == RASCAL_MEMORY_LAYOUT_ROW_MAJOR);
+
+        CHECK_SUCCESS(rascal_descriptor_set_layout(descriptor, RASCAL_MEMORY_LAYOUT_FEATURE_MAJOR));
+        CHECK_SUCCESS(rascal_descriptor_layout(descriptor, &layout));
+        CHECK(layout == RASCAL_MEMORY_LAYOUT_FEATURE_MAJOR);
+
+        compute_descriptor(descriptor);
+
+        double* data = nullptr;
+        uintptr_t shape[2] = {0};
+        CHECK_SUCCESS(rascal_descriptor_values(descriptor, &data, &shape[0], &shape[1]));
+        CHECK(shape[0] == 4);
+        CHECK(shape[1] == 2);
+
+        CHECK(data[0 * shape[0] + 0] == 5);
+        CHECK(data[0 * shape[0] + 1] == 6);
+        CHECK(data[0 * shape[0] + 2] == 7);
+        CHECK(data[0 * shape[0] + 3] == 8);
+
+        CHECK(data[1 * shape[0] + 0] == 3);
+        CHECK(data[1 * shape[0] + 1] == 9);
+        CHECK(data[1 * shape[0] + 2] == 18);
+        CHECK(data[1 * shape[0] + 3] == 15);
+
+        // converting back to row-major
+        CHECK_SUCCESS(rascal_descriptor_set_layout(descriptor, RASCAL_MEMORY_LAYOUT_ROW_MAJOR));
+        CHECK_SUCCESS(rascal_descriptor_values(descriptor, &data, &shape[0], &shape[1]));
+        CHECK(data[0 * shape[1] + 0] == 5);
+        CHECK(data[0 * shape[1] + 1] == 3);
+        CHECK(data[1 * shape[1] + 0] == 6);
+        CHECK(data[1 * shape[1] + 1] == 9);
+
+        CHECK_SUCCESS(rascal_descriptor_free(descriptor));
+    }
+
     SECTION("gradient samples") {
         auto* descriptor = rascal_descriptor();
         REQUIRE(descriptor != nullptr);
@@ -186,6 +263,291 @@ TEST_CASE("rascal_descriptor_t") {
         CHECK_SUCCESS(rascal_descriptor_free(descriptor));
     }
 
+    SECTION("save and load") {
+        auto* descriptor = rascal_descriptor();
+        REQUIRE(descriptor != nullptr);
+        compute_descriptor(descriptor);
+
+        CHECK_SUCCESS(rascal_descriptor_save(descriptor, "rascaline-descriptor-test.json"));
+
+        auto* loaded = rascal_descriptor();
+        REQUIRE(loaded != nullptr);
+        CHECK_SUCCESS(rascal_descriptor_load(loaded, "rascaline-descriptor-test.json"));
+
+        double* data = nullptr;
+        uintptr_t shape[2] = {0};
+        CHECK_SUCCESS(rascal_descriptor_values(loaded, &data, &shape[0], &shape[1]));
+        CHECK(shape[0] == 4);
+        CHECK(shape[1] == 2);
+
+        CHECK_SUCCESS(rascal_descriptor_gradients(loaded, &data, &shape[0], &shape[1]));
+        CHECK(shape[0] == 18);
+        CHECK(shape[1] == 2);
+
+        CHECK(rascal_descriptor_load(loaded, "not-there.json") == RASCAL_IO_ERROR);
+
+        std::remove("rascaline-descriptor-test.json");
+        CHECK_SUCCESS(rascal_descriptor_free(loaded));
+        CHECK_SUCCESS(rascal_descriptor_free(descriptor));
+    }
+
+    SECTION("to and from buffer") {
+        auto* descriptor = rascal_descriptor();
+        REQUIRE(descriptor != nullptr);
+        compute_descriptor(descriptor);
+
+        uint8_t* buffer = nullptr;
+        uintptr_t buffer_size = 0;
+        CHECK_SUCCESS(rascal_descriptor_to_buffer(descriptor, &buffer, &buffer_size));
+        REQUIRE(buffer != nullptr);
+        CHECK(buffer_size > 12);
+        CHECK(std::string(reinterpret_cast<const char*>(buffer), 8) == "RASCALDS");
+
+        auto* loaded = rascal_descriptor();
+        REQUIRE(loaded != nullptr);
+        CHECK_SUCCESS(rascal_descriptor_from_buffer(loaded, buffer, buffer_size));
+
+        double* data = nullptr;
+        uintptr_t shape[2] = {0};
+        CHECK_SUCCESS(rascal_descriptor_values(loaded, &data, &shape[0], &shape[1]));
+        CHECK(shape[0] == 4);
+        CHECK(shape[1] == 2);
+
+        CHECK_SUCCESS(rascal_descriptor_gradients(loaded, &data, &shape[0], &shape[1]));
+        CHECK(shape[0] == 18);
+        CHECK(shape[1] == 2);
+
+        // truncated buffers and invalid headers are rejected
+        CHECK(rascal_descriptor_from_buffer(loaded, buffer, 4) == RASCAL_INVALID_PARAMETER_ERROR);
+        CHECK(rascal_descriptor_from_buffer(loaded, buffer, 20) != RASCAL_SUCCESS);
+        buffer[8] = 42;
+        CHECK(rascal_descriptor_from_buffer(loaded, buffer, buffer_size) == RASCAL_INVALID_PARAMETER_ERROR);
+
+        CHECK_SUCCESS(rascal_free(buffer));
+        CHECK_SUCCESS(rascal_descriptor_free(loaded));
+        CHECK_SUCCESS(rascal_descriptor_free(descriptor));
+    }
+
+    SECTION("write npy") {
+        auto* descriptor = rascal_descriptor();
+        REQUIRE(descriptor != nullptr);
+        compute_descriptor(descriptor);
+
+        CHECK_SUCCESS(rascal_descriptor_write_npy(descriptor, "rascaline-npy-test"));
+
+        const char* files[] = {
+            "rascaline-npy-test/values.npy",
+            "rascaline-npy-test/samples.npy",
+            "rascaline-npy-test/features.npy",
+            "rascaline-npy-test/gradients.npy",
+            "rascaline-npy-test/gradients_samples.npy",
+        };
+        for (auto* path: files) {
+            auto* file = std::fopen(path, "rb");
+            REQUIRE(file != nullptr);
+            char magic[6] = {0};
+            CHECK(std::fread(magic, 1, 6, file) == 6);
+            CHECK(std::string(magic, 6) == "\x93NUMPY");
+            std::fclose(file);
+            std::remove(path);
+        }
+        std::remove("rascaline-npy-test");
+
+        CHECK_SUCCESS(rascal_descriptor_free(descriptor));
+    }
+
+    SECTION("feature covariance") {
+        auto* descriptor = rascal_descriptor();
+        REQUIRE(descriptor != nullptr);
+        compute_descriptor(descriptor);
+
+        double* values = nullptr;
+        uintptr_t shape[2] = {0};
+        CHECK_SUCCESS(rascal_descriptor_values(descriptor, &values, &shape[0], &shape[1]));
+        REQUIRE(shape[1] == 2);
+
+        double covariance[2][2] = {{0}};
+        CHECK_SUCCESS(rascal_descriptor_feature_covariance(descriptor, false, covariance[0], 2));
+        for (size_t i=0; i<2; i++) {
+            for (size_t j=0; j<2; j++) {
+                double expected = 0;
+                for (size_t sample=0; sample<shape[0]; sample++) {
+                    expected += values[sample * 2 + i] * values[sample * 2 + j];
+                }
+                CHECK(covariance[i][j] == Approx(expected));
+            }
+        }
+
+        CHECK_SUCCESS(rascal_descriptor_feature_covariance(descriptor, true, covariance[0], 2));
+        CHECK(covariance[0][1] == Approx(covariance[1][0]));
+
+        CHECK(rascal_descriptor_feature_covariance(descriptor, false, covariance[0], 3) == RASCAL_INVALID_PARAMETER_ERROR);
+
+        CHECK_SUCCESS(rascal_descriptor_free(descriptor));
+    }
+
+    SECTION("dot product") {
+        auto* descriptor = rascal_descriptor();
+        REQUIRE(descriptor != nullptr);
+        compute_descriptor(descriptor);
+
+        double* values = nullptr;
+        uintptr_t shape[2] = {0};
+        CHECK_SUCCESS(rascal_descriptor_values(descriptor, &values, &shape[0], &shape[1]));
+        REQUIRE(shape[0] == 4);
+        REQUIRE(shape[1] == 2);
+
+        double kernel[4][4] = {{0}};
+        CHECK_SUCCESS(rascal_descriptor_dot(descriptor, descriptor, false, kernel[0], 4, 4));
+        for (size_t i=0; i<4; i++) {
+            for (size_t j=0; j<4; j++) {
+                double expected = values[i * 2 + 0] * values[j * 2 + 0] + values[i * 2 + 1] * values[j * 2 + 1];
+                CHECK(kernel[i][j] == Approx(expected));
+            }
+        }
+
+        CHECK(rascal_descriptor_dot(descriptor, descriptor, false, kernel[0], 3, 4) == RASCAL_INVALID_PARAMETER_ERROR);
+
+        // assemble the normalized kernel block by block, in a larger array
+        double blocks[4][6] = {{0}};
+        CHECK_SUCCESS(rascal_descriptor_dot_block(descriptor, descriptor, true, 0, 3, 0, 4, &blocks[0][0], 6));
+        CHECK_SUCCESS(rascal_descriptor_dot_block(descriptor, descriptor, true, 3, 4, 0, 4, &blocks[3][0], 6));
+        for (size_t i=0; i<4; i++) {
+            CHECK(blocks[i][i] == Approx(1.0));
+            CHECK(blocks[i][4] == 0.0);
+            CHECK(blocks[i][5] == 0.0);
+        }
+
+        CHECK(rascal_descriptor_dot_block(descriptor, descriptor, true, 0, 5, 0, 4, &blocks[0][0], 6) == RASCAL_INVALID_PARAMETER_ERROR);
+        CHECK(rascal_descriptor_dot_block(descriptor, descriptor, true, 0, 4, 0, 4, &blocks[0][0], 3) == RASCAL_INVALID_PARAMETER_ERROR);
+
+        CHECK_SUCCESS(rascal_descriptor_free(descriptor));
+    }
+
+    SECTION("uncorrelated features") {
+        auto* descriptor = rascal_descriptor();
+        REQUIRE(descriptor != nullptr);
+        compute_descriptor(descriptor);
+
+        bool selected[2] = {false, false};
+        CHECK_SUCCESS(rascal_descriptor_uncorrelated_features(descriptor, 1.0, selected, 2));
+
+        CHECK(rascal_descriptor_uncorrelated_features(descriptor, 2.0, selected, 2) == RASCAL_INVALID_PARAMETER_ERROR);
+        CHECK(rascal_descriptor_uncorrelated_features(descriptor, 0.5, selected, 3) == RASCAL_INVALID_PARAMETER_ERROR);
+
+        CHECK_SUCCESS(rascal_descriptor_free(descriptor));
+    }
+
+    SECTION("random projection") {
+        auto* descriptor = rascal_descriptor();
+        REQUIRE(descriptor != nullptr);
+        compute_descriptor(descriptor);
+
+        CHECK_SUCCESS(rascal_descriptor_random_projection(
+            descriptor, 5, RASCAL_RANDOM_PROJECTION_GAUSSIAN, 42
+        ));
+
+        double* values = nullptr;
+        uintptr_t shape[2] = {0};
+        CHECK_SUCCESS(rascal_descriptor_values(descriptor, &values, &shape[0], &shape[1]));
+        CHECK(shape[0] == 4);
+        CHECK(shape[1] == 5);
+
+        CHECK_SUCCESS(rascal_descriptor_random_projection(
+            descriptor, 3, RASCAL_RANDOM_PROJECTION_SPARSE, 42
+        ));
+        CHECK_SUCCESS(rascal_descriptor_values(descriptor, &values, &shape[0], &shape[1]));
+        CHECK(shape[1] == 3);
+
+        auto status = rascal_descriptor_random_projection(
+            descriptor, 0, RASCAL_RANDOM_PROJECTION_SPARSE, 42
+        );
+        CHECK(status == RASCAL_INVALID_PARAMETER_ERROR);
+
+        CHECK_SUCCESS(rascal_descriptor_free(descriptor));
+    }
+
+    SECTION("farthest point sampling") {
+        auto* descriptor = rascal_descriptor();
+        REQUIRE(descriptor != nullptr);
+        compute_descriptor(descriptor);
+
+        uintptr_t selected[4] = {0};
+        CHECK_SUCCESS(rascal_descriptor_farthest_point_sampling(
+            descriptor, 4, RASCAL_SAMPLE_DISTANCE_EUCLIDEAN, 1, selected
+        ));
+        CHECK(selected[0] == 1);
+        for (size_t i=0; i<4; i++) {
+            CHECK(selected[i] < 4);
+            for (size_t j=0; j<i; j++) {
+                CHECK(selected[i] != selected[j]);
+            }
+        }
+
+        CHECK_SUCCESS(rascal_descriptor_farthest_point_sampling(
+            descriptor, 2, RASCAL_SAMPLE_DISTANCE_COSINE, -1, selected
+        ));
+
+        auto status = rascal_descriptor_farthest_point_sampling(
+            descriptor, 5, RASCAL_SAMPLE_DISTANCE_EUCLIDEAN, -1, selected
+        );
+        CHECK(status == RASCAL_INVALID_PARAMETER_ERROR);
+
+        CHECK_SUCCESS(rascal_descriptor_free(descriptor));
+    }
+
+    SECTION("per-atom data") {
+        auto* descriptor = rascal_descriptor();
+        REQUIRE(descriptor != nullptr);
+        compute_descriptor(descriptor);
+
+        double values[4][2] = {{0}};
+        CHECK_SUCCESS(rascal_descriptor_per_atom_values(descriptor, 0, values[0], 4, 2));
+        CHECK(values[0][0] == 5);
+        CHECK(values[0][1] == 3);
+        CHECK(values[3][0] == 8);
+        CHECK(values[3][1] == 15);
+
+        CHECK(rascal_descriptor_per_atom_values(descriptor, 0, values[0], 4, 3) == RASCAL_INVALID_PARAMETER_ERROR);
+
+        // the dummy calculator gradients are 0 for the first feature and 1
+        // for the second one
+        double weights[4][2] = {{1, 2}, {1, 2}, {1, 2}, {1, 2}};
+        double output[4][3] = {{0}};
+        CHECK_SUCCESS(rascal_descriptor_scatter_gradients(descriptor, 0, weights[0], 4, 2, output[0]));
+
+        rascal_indexes_t gradients_samples = {0};
+        CHECK_SUCCESS(rascal_descriptor_indexes(
+            descriptor, RASCAL_INDEXES_GRADIENT_SAMPLES, &gradients_samples
+        ));
+        double expected[4][3] = {{0}};
+        for (size_t i=0; i<gradients_samples.count; i++) {
+            auto atom = gradients_samples.values[i * gradients_samples.size + 1];
+            auto spatial = gradients_samples.values[i * gradients_samples.size + 2];
+            expected[atom][spatial] += 2;
+        }
+
+        for (size_t i=0; i<4; i++) {
+            for (size_t j=0; j<3; j++) {
+                CHECK(output[i][j] == expected[i][j]);
+            }
+        }
+
+        // updating a descriptor with itself does not change anything
+        auto* partial = rascal_descriptor();
+        REQUIRE(partial != nullptr);
+        compute_descriptor(partial);
+        CHECK_SUCCESS(rascal_descriptor_update(descriptor, partial));
+
+        double* data = nullptr;
+        uintptr_t shape[2] = {0};
+        CHECK_SUCCESS(rascal_descriptor_gradients(descriptor, &data, &shape[0], &shape[1]));
+        CHECK(shape[0] == 18);
+
+        CHECK_SUCCESS(rascal_descriptor_free(partial));
+        CHECK_SUCCESS(rascal_descriptor_free(descriptor));
+    }
+
     SECTION("densify") {
         auto* descriptor = rascal_descriptor();
         REQUIRE(descriptor != nullptr);
@@ -300,7 +662,7 @@ TEST_CASE("rascal_descriptor_t") {
             CHECK(densified_positions[3].new_sample == 0);
             CHECK(densified_positions[3].feature_block == 3);
 
-            free(densified_positions);
+            CHECK_SUCCESS(rascal_free(densified_positions));
         }
 
         rascal_descriptor_free(descriptor);
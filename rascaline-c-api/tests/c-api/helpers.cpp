@@ -10,6 +10,7 @@
 rascal_system_t simple_system() {
     rascal_system_t system;
     std::memset(&system, 0, sizeof(system));
+    system.version = RASCAL_SYSTEM_VERSION;
 
     system.size = [](const void* _, uintptr_t* size) {
         *size = 4;
@@ -67,4 +67,17 @@ TEST_CASE("Logging") {
         }
     }
     CHECK(event_found);
+
+    // warnings are filtered out when using a less verbose level
+    CHECK_SUCCESS(rascal_set_logging_level(RASCAL_LOG_LEVEL_ERROR));
+    RECORDED_LOG_EVENTS.clear();
+    run_calculation(hypers_log_warn);
+    CHECK(RECORDED_LOG_EVENTS.empty());
+
+    CHECK(rascal_set_logging_level(42) == RASCAL_INVALID_PARAMETER_ERROR);
+    CHECK_SUCCESS(rascal_set_logging_level(RASCAL_LOG_LEVEL_INFO));
+
+    // a NULL callback discards all log events
+    CHECK_SUCCESS(rascal_set_logging_callback(nullptr));
+    run_calculation(hypers_log_warn);
 }
@@ -0,0 +1,72 @@
+#include <cstdlib>
+#include <string>
+
+#include "rascaline.h"
+#include "catch.hpp"
+#include "helpers.hpp"
+
+static size_t ALLOCATED_BLOCKS = 0;
+static size_t FREED_BLOCKS = 0;
+
+static void* counting_malloc(uintptr_t size) {
+    ALLOCATED_BLOCKS += 1;
+    return std::malloc(size);
+}
+
+static void counting_free(void* ptr) {
+    FREED_BLOCKS += 1;
+    std::free(ptr);
+}
+
+TEST_CASE("Custom allocator") {
+    const char* HYPERS_JSON = R"({
+        "cutoff": 3.0,
+        "delta": 4,
+        "name": "",
+        "gradients": true
+    })";
+
+    CHECK_SUCCESS(rascal_set_allocator(counting_malloc, counting_free));
+    ALLOCATED_BLOCKS = 0;
+    FREED_BLOCKS = 0;
+
+    auto* descriptor = rascal_descriptor();
+    REQUIRE(descriptor != nullptr);
+    auto* calculator = rascal_calculator("dummy_calculator", HYPERS_JSON);
+    REQUIRE(calculator != nullptr);
+    auto system = simple_system();
+    rascal_calculation_options_t options = {0};
+    CHECK_SUCCESS(rascal_calculator_compute(
+        calculator, descriptor, &system, 1, options
+    ));
+
+    const char* variables[] = { "center" };
+    rascal_densified_position_t* densified_positions = nullptr;
+    uintptr_t densified_positions_count = 0;
+    CHECK_SUCCESS(rascal_descriptor_densify_values(
+        descriptor, variables, 1, NULL, 0, &densified_positions, &densified_positions_count
+    ));
+    CHECK(densified_positions_count == 4);
+    CHECK(ALLOCATED_BLOCKS == 1);
+
+    // the allocator can not change while buffers are alive
+    CHECK(rascal_set_allocator(nullptr, nullptr) == RASCAL_INVALID_PARAMETER_ERROR);
+    CHECK(std::string(rascal_last_error()) == "invalid parameter: can not change the allocator while 1 buffer(s) allocated by rascaline are still alive, release them with rascal_free first");
+
+    CHECK_SUCCESS(rascal_free(densified_positions));
+    CHECK(FREED_BLOCKS == 1);
+
+    // NULL pointers are ignored
+    CHECK_SUCCESS(rascal_free(nullptr));
+    CHECK(FREED_BLOCKS == 1);
+
+    // malloc and free must be set together
+    CHECK(rascal_set_allocator(counting_malloc, nullptr) == RASCAL_INVALID_PARAMETER_ERROR);
+    CHECK(rascal_set_allocator(nullptr, counting_free) == RASCAL_INVALID_PARAMETER_ERROR);
+
+    // restore the default allocator
+    CHECK_SUCCESS(rascal_set_allocator(nullptr, nullptr));
+
+    rascal_calculator_free(calculator);
+    rascal_descriptor_free(descriptor);
+}
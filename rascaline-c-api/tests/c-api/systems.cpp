@@ -1,3 +1,5 @@
+#include <cmath>
+
 #include "rascaline.h"
 #include "catch.hpp"
 #include "helpers.hpp"
@@ -47,6 +49,98 @@ TEST_CASE("basic systems") {
 }
 
 
+TEST_CASE("basic system from arrays") {
+    int32_t species[] = {8, 1, 1};
+    double positions[] = {
+        0.0, 0.0, 0.0,
+        0.0, 0.75, -0.5,
+        0.0, -0.75, -0.5,
+    };
+
+    rascal_system_t system = {0};
+    CHECK_SUCCESS(rascal_basic_system(species, positions, 3, nullptr, &system));
+
+    uintptr_t size = 0;
+    system.size(system.user_data, &size);
+    CHECK(size == 3);
+
+    CHECK(system.version == RASCAL_SYSTEM_VERSION);
+    CHECK(system.cell_shifts == nullptr);
+    CHECK(system.triplets_containing == nullptr);
+
+    const double* data = positions;
+    CHECK_SUCCESS(system.data(system.user_data, "charges", &data));
+    CHECK(data == nullptr);
+
+    const int32_t* system_species = nullptr;
+    system.species(system.user_data, &system_species);
+    CHECK(system_species[0] == 8);
+    CHECK(system_species[1] == 1);
+    CHECK(system_species[2] == 1);
+
+    double cell[9] = {1.0};
+    system.cell(system.user_data, cell);
+    for (size_t i=0; i<9; i++) {
+        CHECK(cell[i] == 0.0);
+    }
+
+    CHECK_SUCCESS(system.compute_neighbors(system.user_data, 1.0));
+    const rascal_pair_t* pairs = nullptr;
+    uintptr_t count = 0;
+    CHECK_SUCCESS(system.pairs(system.user_data, &pairs, &count));
+    CHECK(count == 2);
+
+    // use an external neighbor list, only containing one of the O-H pairs
+    rascal_pair_t external = {0, 1, 0.9013878188659973, {0.0, 0.75, -0.5}, {0, 0, 0}};
+    CHECK_SUCCESS(rascal_basic_system_set_pairs(&system, 1.0, &external, 1));
+    CHECK_SUCCESS(system.compute_neighbors(system.user_data, 1.0));
+    CHECK_SUCCESS(system.pairs(system.user_data, &pairs, &count));
+    CHECK(count == 1);
+    CHECK(pairs[0].first == 0);
+    CHECK(pairs[0].second == 1);
+
+    CHECK_SUCCESS(system.pairs_containing(system.user_data, 2, &pairs, &count));
+    CHECK(count == 0);
+
+    // the external pairs can not be used for a larger cutoff
+    CHECK(system.compute_neighbors(system.user_data, 2.0) == RASCAL_INVALID_PARAMETER_ERROR);
+
+    external.second = 3;
+    CHECK(rascal_basic_system_set_pairs(&system, 1.0, &external, 1) == RASCAL_INVALID_PARAMETER_ERROR);
+
+    uintptr_t moved[] = {1};
+    uintptr_t centers[3] = {0};
+    CHECK_SUCCESS(rascal_system_affected_centers(&system, 1.0, moved, 1, centers, &count));
+    CHECK(count == 2);
+    CHECK(centers[0] == 0);
+    CHECK(centers[1] == 1);
+
+    CHECK_SUCCESS(rascal_basic_system_free(&system));
+    CHECK(system.user_data == nullptr);
+
+    positions[0] = std::nan("");
+    CHECK(rascal_basic_system(species, positions, 3, nullptr, &system) == RASCAL_INVALID_PARAMETER_ERROR);
+    positions[0] = 0.0;
+
+    // the cell vectors are coplanar
+    double singular_cell[] = {
+        3.0, 0.0, 0.0,
+        0.0, 4.0, 0.0,
+        3.0, 4.0, 0.0,
+    };
+    CHECK(rascal_basic_system(species, positions, 3, singular_cell, &system) == RASCAL_INVALID_PARAMETER_ERROR);
+    CHECK_THAT(rascal_last_error(), Catch::Contains("the cell vectors are (close to being) coplanar"));
+
+    double left_handed_cell[] = {
+        0.0, 4.0, 0.0,
+        3.0, 0.0, 0.0,
+        0.0, 0.0, 5.0,
+    };
+    CHECK(rascal_basic_system(species, positions, 3, left_handed_cell, &system) == RASCAL_INVALID_PARAMETER_ERROR);
+    CHECK_THAT(rascal_last_error(), Catch::Contains("left-handed cells are not supported"));
+}
+
+
 TEST_CASE("systems errors") {
     const char* HYPERS_JSON = R"({
         "cutoff": 3.0,
@@ -63,13 +157,32 @@ TEST_CASE("systems errors") {
     rascal_system_t system = {0};
     rascal_calculation_options_t options = {0};
 
-    // default status code when function are not defined
+    // the version must be set
     auto status = rascal_calculator_compute(
         calculator, descriptor, &system, 1, options
     );
+    CHECK(status == RASCAL_INVALID_PARAMETER_ERROR);
+
+    std::string expected = "invalid system at index 0: invalid parameter: "
+        "unsupported rascal_system_t version 0, it should be set to "
+        "RASCAL_SYSTEM_VERSION (2)";
+    CHECK(rascal_last_error() == expected);
+
+    system.version = RASCAL_SYSTEM_VERSION + 1;
+    status = rascal_calculator_compute(
+        calculator, descriptor, &system, 1, options
+    );
+    CHECK(status == RASCAL_INVALID_PARAMETER_ERROR);
+
+    system.version = RASCAL_SYSTEM_VERSION;
+
+    // default status code when function are not defined
+    status = rascal_calculator_compute(
+        calculator, descriptor, &system, 1, options
+    );
     CHECK(status == RASCAL_SYSTEM_ERROR);
 
-    std::string expected = "error from external code (status 128): rascal_system_t.size function is NULL";
+    expected = "error from external code (status 128): rascal_system_t.size function is NULL";
     CHECK(rascal_last_error() == expected);
 
     system.size = [](const void* _, uintptr_t* size) {
@@ -86,3 +199,61 @@ TEST_CASE("systems errors") {
     rascal_descriptor_free(descriptor);
     rascal_calculator_free(calculator);
 }
+
+TEST_CASE("systems with pairs for a given cutoff") {
+    const char* HYPERS_JSON = R"({
+        "cutoff": 3.0,
+        "delta": 4,
+        "name": "",
+        "gradients": true
+    })";
+
+    auto* calculator = rascal_calculator("dummy_calculator", HYPERS_JSON);
+    REQUIRE(calculator != nullptr);
+    rascal_calculation_options_t options = {0};
+
+    // reference calculation, using compute_neighbors/pairs/pairs_containing
+    auto* reference = rascal_descriptor();
+    REQUIRE(reference != nullptr);
+    auto system = simple_system();
+    CHECK_SUCCESS(rascal_calculator_compute(calculator, reference, &system, 1, options));
+
+    // the same system, providing the pairs on demand
+    system.compute_neighbors = nullptr;
+    system.pairs = nullptr;
+    system.pairs_containing = nullptr;
+    system.pairs_with_cutoff = [](const void* user_data, double cutoff, const rascal_pair_t** pairs, uintptr_t* count) {
+        if (cutoff != 3.0) {
+            return -1;
+        }
+        return simple_system().pairs(user_data, pairs, count);
+    };
+    system.pairs_containing_with_cutoff = [](const void* user_data, double cutoff, uintptr_t center, const rascal_pair_t** pairs, uintptr_t* count) {
+        if (cutoff != 3.0) {
+            return -1;
+        }
+        return simple_system().pairs_containing(user_data, center, pairs, count);
+    };
+
+    auto* descriptor = rascal_descriptor();
+    REQUIRE(descriptor != nullptr);
+    CHECK_SUCCESS(rascal_calculator_compute(calculator, descriptor, &system, 1, options));
+
+    double* expected = nullptr;
+    uintptr_t expected_shape[2] = {0};
+    CHECK_SUCCESS(rascal_descriptor_gradients(reference, &expected, &expected_shape[0], &expected_shape[1]));
+
+    double* gradients = nullptr;
+    uintptr_t shape[2] = {0};
+    CHECK_SUCCESS(rascal_descriptor_gradients(descriptor, &gradients, &shape[0], &shape[1]));
+
+    REQUIRE(shape[0] == expected_shape[0]);
+    REQUIRE(shape[1] == expected_shape[1]);
+    for (size_t i=0; i<shape[0] * shape[1]; i++) {
+        CHECK(gradients[i] == expected[i]);
+    }
+
+    rascal_descriptor_free(descriptor);
+    rascal_descriptor_free(reference);
+    rascal_calculator_free(calculator);
+}
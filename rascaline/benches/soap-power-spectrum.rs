@@ -1,5 +1,7 @@
 #![allow(clippy::needless_return)]
 
+use std::collections::BTreeMap;
+
 use rascaline::calculators::CalculatorBase;
 use rascaline::calculators::{SoapPowerSpectrum, PowerSpectrumParameters};
 use rascaline::calculators::soap::{RadialBasis, CutoffFunction, RadialScaling};
@@ -45,12 +47,19 @@ fn run_soap_power_spectrum(
             let parameters = PowerSpectrumParameters {
                 max_radial,
                 max_angular,
+                max_radial_per_angular: None,
                 cutoff,
                 gradients,
                 atomic_gaussian_width: 0.3,
                 radial_basis: RadialBasis::Gto {},
                 cutoff_function: CutoffFunction::ShiftedCosine{ width: 0.5 },
                 radial_scaling: RadialScaling::None {},
+                center_atom_weight: 1.0,
+                neighbor_weights: None,
+                neighbor_channels: BTreeMap::new(),
+                exclude_self_pairs: false,
+                first_shell_tolerance: None,
+                alchemical_compression: None,
             };
             let mut calculator = SoapPowerSpectrum::new(parameters).unwrap();
 
@@ -1,8 +1,11 @@
 #![allow(clippy::needless_return)]
 
+use std::collections::BTreeMap;
+
 use rascaline::calculators::CalculatorBase;
 use rascaline::calculators::{SphericalExpansion, SphericalExpansionParameters};
 use rascaline::calculators::soap::{RadialBasis, CutoffFunction, RadialScaling};
+use rascaline::calculators::soap::SphericalHarmonicsConvention;
 
 use rascaline::{Descriptor, System};
 
@@ -43,12 +46,20 @@ fn run_spherical_expansion(mut group: BenchmarkGroup<WallTime>,
             let parameters = SphericalExpansionParameters {
                 max_radial,
                 max_angular,
+                max_radial_per_angular: None,
                 cutoff,
                 gradients,
                 atomic_gaussian_width: 0.3,
                 radial_basis: RadialBasis::Gto {},
                 cutoff_function: CutoffFunction::ShiftedCosine{ width: 0.5 },
                 radial_scaling: RadialScaling::None {},
+                center_atom_weight: 1.0,
+                neighbor_weights: None,
+                neighbor_channels: BTreeMap::new(),
+                exclude_self_pairs: false,
+                first_shell_tolerance: None,
+                spherical_harmonics: SphericalHarmonicsConvention::default(),
+                kspace: None,
             };
             let mut calculator = SphericalExpansion::new(parameters).unwrap();
 
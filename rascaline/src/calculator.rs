@@ -1,14 +1,60 @@
 use std::{collections::BTreeMap, convert::TryFrom};
+use std::any::{Any, TypeId};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
 
-use crate::{SimpleSystem, descriptor::{Descriptor, Indexes, IndexesBuilder}};
+use ndarray::{Array2, Array3, Axis};
+use thread_local::ThreadLocal;
+
+use crate::{SimpleSystem, descriptor::{Descriptor, Indexes, IndexesBuilder, IndexValue, SamplesBuilder}};
+use crate::descriptor::{BlockSparseDescriptor, SinglePrecisionDescriptor};
 use crate::systems::System;
-use crate::Error;
+use crate::{Error, ErrorContext};
 
-use crate::calculators::CalculatorBase;
+use crate::calculators::{CalculatorBase, GradientsCache};
 
 pub struct Calculator {
     implementation: Box<dyn CalculatorBase>,
     parameters: String,
+    /// Name used to register this calculator in `REGISTERED_CALCULATORS`, if
+    /// this calculator was created with `Calculator::new`.
+    registered_name: Option<&'static str>,
+    /// Flag set by `CancellationHandle::cancel` to request the current
+    /// calculation to stop
+    cancelled: Arc<AtomicBool>,
+    /// Temporary buffers used by the implementation, kept between calls to
+    /// `compute` to avoid re-allocating them every time
+    workspace: Arc<Workspace>,
+}
+
+/// Handle used to cancel calculations running with a `Calculator` from
+/// another thread, created with `Calculator::cancellation_handle`.
+#[derive(Clone, Debug)]
+pub struct CancellationHandle {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationHandle {
+    /// Request the calculation currently running with the corresponding
+    /// calculator to stop as soon as possible. The calculation will then
+    /// return `Error::Cancelled`. If no calculation is running, the next one
+    /// will be cancelled instead.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+}
+
+/// Data stored by `Calculator::save`, and read back by `Calculator::load`
+#[derive(serde::Serialize, serde::Deserialize)]
+struct SavedCalculator {
+    /// Name of the calculator in `REGISTERED_CALCULATORS`
+    name: String,
+    /// JSON-formatted hyper-parameters of the calculator
+    parameters: String,
+    /// Pre-computed state of the calculator, see `CalculatorBase::save_state`
+    state: Option<serde_json::Value>,
 }
 
 /// List of pre-selected indexes on which the user wants to run a calculation
@@ -133,7 +179,30 @@ impl SelectedIndexes {
     }
 }
 
+/// Strategy used to run a calculation in parallel
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Parallelism {
+    /// Parallelize the calculation inside each system (over pairs of atoms,
+    /// atomic environments, *etc.*), handling one system after the other. This
+    /// works best for a few large systems.
+    Samples,
+    /// Parallelize the calculation over all atomic environments in all
+    /// systems at once, balancing the work between threads using the number
+    /// of neighbors of each atom. This works best for datasets containing a
+    /// lot of systems, such as molecules, possibly mixed with a few larger
+    /// systems.
+    Systems,
+}
+
+impl Default for Parallelism {
+    fn default() -> Parallelism {
+        Parallelism::Samples
+    }
+}
+
 /// Parameters specific to a single call to `compute`
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Clone)]
 pub struct CalculationOptions {
     /// Copy the data from systems into native `SimpleSystem`. This can be
     /// faster than having to cross the FFI boundary too often.
@@ -142,6 +211,49 @@ pub struct CalculationOptions {
     pub selected_samples: SelectedIndexes,
     /// List of selected features on which to run the computation
     pub selected_features: SelectedIndexes,
+    /// Number of threads to use for this computation. If this is `None`, the
+    /// global rayon thread pool is used, which can be configured with the
+    /// `RAYON_NUM_THREADS` environment variable.
+    pub threads: Option<usize>,
+    /// Parallelization strategy to use for this computation
+    pub parallelism: Parallelism,
+    /// Check that all values and gradients are finite (i.e. neither NaN nor
+    /// infinite) after the calculation, and return an error pointing to the
+    /// first non-finite entry otherwise. This is intended for debugging, and
+    /// requires an additional pass over the data.
+    pub check_finite: bool,
+    /// Make sure the results of this computation do not depend on the number
+    /// of threads or the parallelization strategy, by always running floating
+    /// point reductions in the same order. This can make the calculation
+    /// slower and use more memory.
+    pub deterministic: bool,
+    /// Compute the neighbor list of each system once with this cutoff, and
+    /// filter it when a smaller cutoff is requested, instead of re-computing
+    /// a neighbor list for each cutoff. This is useful when running multiple
+    /// calculators with different cutoffs, for example in a pipeline, and
+    /// should be set to the largest cutoff. This requires `use_native_system`.
+    pub neighbors_cutoff: Option<f64>,
+    /// Also compute the gradients of the values with respect to the continuous
+    /// hyper-parameters of the calculator (see
+    /// [`CalculatorBase::hyper_parameters`]), storing them in
+    /// `Descriptor::hyper_gradients`. The gradients are computed with finite
+    /// differences (central differences, or one-sided differences close to
+    /// the boundary of the valid range of a hyper-parameter), running the
+    /// calculation again with the same options for displaced values of each
+    /// hyper-parameter. This requires the calculator to be created with
+    /// `Calculator::new`.
+    pub hyper_gradients: bool,
+    /// Do not compute the gradients with respect to atomic positions during
+    /// the calculation, and instead store a copy of the systems (including
+    /// their neighbor list) and the intermediate quantities needed for the
+    /// gradients in the descriptor. The gradients are then only computed on
+    /// the first call to [`Descriptor::gradients()`], for the samples and
+    /// features in the descriptor at this point. Calculators which can not
+    /// cache intermediate quantities (see
+    /// [`CalculatorBase::compute_caching_gradients`]) run the full calculation
+    /// again instead. This requires the calculator to compute gradients, and to
+    /// be created with `Calculator::new`.
+    pub lazy_gradients: bool,
 }
 
 impl Default for CalculationOptions {
@@ -150,16 +262,251 @@ impl Default for CalculationOptions {
             use_native_system: false,
             selected_samples: SelectedIndexes::All,
             selected_features: SelectedIndexes::All,
+            threads: None,
+            parallelism: Parallelism::Samples,
+            check_finite: false,
+            deterministic: false,
+            neighbors_cutoff: None,
+            hyper_gradients: false,
+            lazy_gradients: false,
         }
     }
 }
 
+/// Estimated memory usage of a calculation, as returned by
+/// `Calculator::estimate_memory`. All sizes are given in bytes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MemoryEstimate {
+    /// Number of samples (i.e. rows) in the values array
+    pub samples_count: usize,
+    /// Number of features (i.e. columns) in the values and gradients arrays
+    pub features_count: usize,
+    /// Number of gradients samples (i.e. rows) in the gradients array, or 0
+    /// if the calculator does not compute gradients
+    pub gradients_samples_count: usize,
+    /// Size of the values array
+    pub values: usize,
+    /// Size of the gradients array
+    pub gradients: usize,
+    /// Size of the samples, gradients samples and features indexes
+    pub indexes: usize,
+}
+
+impl MemoryEstimate {
+    /// Get the total estimated memory usage, in bytes
+    pub fn total(&self) -> usize {
+        return self.values + self.gradients + self.indexes;
+    }
+}
+
+/// Settings for running a calculation in parallel, taken from
+/// `CalculationOptions`
+#[derive(Clone, Default)]
+struct ParallelContext {
+    /// Thread pool requested by the user, if any
+    pool: Option<Arc<rayon::ThreadPool>>,
+    /// Parallelization strategy requested by the user
+    parallelism: Parallelism,
+    /// Did the user request deterministic results?
+    deterministic: bool,
+    /// Cancellation flags of the calculators running the calculation. There
+    /// can be more than one flag when a calculator uses another calculator
+    /// internally.
+    cancelled: Vec<Arc<AtomicBool>>,
+    /// Workspace of the calculator running the calculation
+    workspace: Option<Arc<Workspace>>,
+}
+
+/// Per-thread storage for the temporary buffers used by calculators, owned by
+/// a `Calculator` and reused across systems and calls to `compute`.
+///
+/// Buffers are identified by their type, calculators should define a type
+/// grouping all the buffers they need. A buffer is taken out of the workspace
+/// with `Workspace::take`, and given back to the workspace when the returned
+/// `WorkspaceBuffer` is dropped, including when returning early because of an
+/// error. The buffer is given back to the workspace of the thread dropping
+/// the `WorkspaceBuffer`.
+#[derive(Default)]
+pub(crate) struct Workspace {
+    buffers: ThreadLocal<RefCell<HashMap<TypeId, Box<dyn Any + Send>>>>,
+}
+
+impl Workspace {
+    /// Take the buffer of type `T` for the current thread out of this
+    /// workspace, creating a new one with `T::default()` if there is none.
+    pub fn take<T: Default + Send + 'static>(&self) -> WorkspaceBuffer<'_, T> {
+        let buffers = self.buffers.get_or(Default::default);
+        let buffer = buffers.borrow_mut().remove(&TypeId::of::<T>());
+        let buffer = match buffer {
+            Some(buffer) => *buffer.downcast::<T>().expect("wrong type in workspace"),
+            None => T::default(),
+        };
+
+        return WorkspaceBuffer {
+            workspace: self,
+            buffer: Some(buffer),
+        };
+    }
+
+    /// Give back a `buffer` to this workspace, to be re-used by the next call
+    /// to `Workspace::take` on the current thread.
+    fn give_back<T: Send + 'static>(&self, buffer: T) {
+        let buffers = self.buffers.get_or(Default::default);
+        buffers.borrow_mut().insert(TypeId::of::<T>(), Box::new(buffer));
+    }
+}
+
+/// A buffer taken out of a `Workspace`, which is given back to the workspace
+/// when dropped.
+pub(crate) struct WorkspaceBuffer<'a, T: Send + 'static> {
+    workspace: &'a Workspace,
+    /// The buffer, only `None` while dropping
+    buffer: Option<T>,
+}
+
+impl<T: Send + 'static> std::ops::Deref for WorkspaceBuffer<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.buffer.as_ref().expect("missing buffer")
+    }
+}
+
+impl<T: Send + 'static> std::ops::DerefMut for WorkspaceBuffer<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.buffer.as_mut().expect("missing buffer")
+    }
+}
+
+impl<T: Send + 'static> Drop for WorkspaceBuffer<'_, T> {
+    fn drop(&mut self) {
+        if let Some(buffer) = self.buffer.take() {
+            self.workspace.give_back(buffer);
+        }
+    }
+}
+
+thread_local! {
+    /// Parallel settings of the calculation currently running on this thread
+    static PARALLEL_CONTEXT: RefCell<ParallelContext> = RefCell::new(ParallelContext::default());
+}
+
+/// Run `function` inside the thread pool of the current calculation. All
+/// parallel code in calculators should go through this function, to respect
+/// the `CalculationOptions::threads` option.
+pub(crate) fn install<F, R>(function: F) -> R where F: FnOnce() -> R + Send, R: Send {
+    let pool = PARALLEL_CONTEXT.with(|context| context.borrow().pool.clone());
+    match pool {
+        Some(pool) => pool.install(function),
+        None => function(),
+    }
+}
+
+/// Get the parallelization strategy of the current calculation. Calculators
+/// should use this to decide how to distribute work between threads.
+pub(crate) fn parallelism() -> Parallelism {
+    PARALLEL_CONTEXT.with(|context| context.borrow().parallelism)
+}
+
+/// Check if the current calculation should produce results independent of
+/// the number of threads and parallelization strategy. Calculators should
+/// then run floating point reductions in a fixed order.
+pub(crate) fn deterministic() -> bool {
+    PARALLEL_CONTEXT.with(|context| context.borrow().deterministic)
+}
+
+/// Get the workspace of the calculator running the current calculation, or a
+/// new empty workspace if there is no such calculator (e.g. when using a
+/// `CalculatorBase` directly). Calculators should get the workspace once on
+/// the main thread, and share it with the worker threads if needed.
+pub(crate) fn workspace() -> Arc<Workspace> {
+    PARALLEL_CONTEXT.with(|context| context.borrow().workspace.clone()).unwrap_or_default()
+}
+
+/// Check if the user requested the current calculation to be cancelled.
+/// Calculators should call this function regularly (e.g. once per system) and
+/// propagate the error to stop the calculation early.
+pub(crate) fn check_cancelled() -> Result<(), Error> {
+    let cancelled = PARALLEL_CONTEXT.with(|context| {
+        context.borrow().cancelled.iter().any(|flag| flag.load(Ordering::SeqCst))
+    });
+
+    if cancelled {
+        return Err(Error::Cancelled);
+    }
+    return Ok(());
+}
+
+lazy_static::lazy_static!{
+    /// Thread pools created for `CalculationOptions::threads`, indexed by
+    /// their number of threads and re-used by all later calculations
+    static ref THREAD_POOLS: Mutex<HashMap<usize, Arc<rayon::ThreadPool>>> = Mutex::new(HashMap::new());
+}
+
+/// Get the thread pool with the given number of `threads`, creating it on the
+/// first call with this number of threads.
+fn thread_pool(threads: usize) -> Result<Arc<rayon::ThreadPool>, Error> {
+    let mut pools = THREAD_POOLS.lock().expect("poisoned mutex");
+    if let Some(pool) = pools.get(&threads) {
+        return Ok(Arc::clone(pool));
+    }
+
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+        .map_err(|e| Error::Internal(format!("failed to create thread pool: {}", e)))?;
+    let pool = Arc::new(pool);
+    pools.insert(threads, Arc::clone(&pool));
+    return Ok(pool);
+}
+
+/// Set the parallel context for calculations on the current thread, and
+/// restore the previous one when dropped.
+struct ParallelContextGuard {
+    previous: ParallelContext,
+}
+
+impl ParallelContextGuard {
+    /// Use a thread pool with the given number of `threads` (or the
+    /// thread pool of the enclosing calculation if `threads` is `None`), the
+    /// given `parallelism` strategy and `deterministic` setting.
+    fn new(threads: Option<usize>, parallelism: Parallelism, deterministic: bool) -> Result<ParallelContextGuard, Error> {
+        let pool = match threads {
+            Some(0) => {
+                return Err(Error::InvalidParameter(
+                    "the number of threads must be at least 1".into()
+                ));
+            }
+            Some(threads) => Some(thread_pool(threads)?),
+            None => PARALLEL_CONTEXT.with(|context| context.borrow().pool.clone()),
+        };
+
+        let (cancelled, workspace) = PARALLEL_CONTEXT.with(|context| {
+            let context = context.borrow();
+            (context.cancelled.clone(), context.workspace.clone())
+        });
+        let context = ParallelContext { pool, parallelism, deterministic, cancelled, workspace };
+        let previous = PARALLEL_CONTEXT.with(|current| current.replace(context));
+        return Ok(ParallelContextGuard { previous });
+    }
+}
+
+impl Drop for ParallelContextGuard {
+    fn drop(&mut self) {
+        let previous = std::mem::take(&mut self.previous);
+        PARALLEL_CONTEXT.with(|current| *current.borrow_mut() = previous);
+    }
+}
+
 impl From<Box<dyn CalculatorBase>> for Calculator {
     fn from(implementation: Box<dyn CalculatorBase>) -> Calculator {
         let parameters = implementation.get_parameters();
         Calculator {
             implementation: implementation,
             parameters: parameters,
+            registered_name: None,
+            cancelled: Arc::new(AtomicBool::new(false)),
+            workspace: Arc::new(Workspace::default()),
         }
     }
 }
@@ -175,8 +522,18 @@ impl Calculator {
     /// This function returns an error if there is no registered calculator with
     /// the given `name`, or if the parameters are invalid for this calculator.
     pub fn new(name: &str, parameters: String) -> Result<Calculator, Error> {
-        let creator = match REGISTERED_CALCULATORS.get(name) {
-            Some(creator) => creator,
+        let mut calculator = Calculator::create(name, parameters)?;
+        calculator.precompute().with_context(|| format!(
+            "invalid parameters for calculator '{}'", name
+        ))?;
+        return Ok(calculator);
+    }
+
+    /// Create a new calculator with the given `name` and `parameters`, without
+    /// creating its pre-computed state
+    fn create(name: &str, parameters: String) -> Result<Calculator, Error> {
+        let (registered_name, registered) = match REGISTERED_CALCULATORS.get_key_value(name) {
+            Some(entry) => entry,
             None => {
                 return Err(Error::InvalidParameter(
                     format!("unknown calculator with name '{}'", name)
@@ -184,12 +541,76 @@ impl Calculator {
             }
         };
 
+        let implementation = (registered.create)(&parameters).with_context(|| format!(
+            "invalid parameters for calculator '{}'", name
+        ))?;
+
         return Ok(Calculator {
-            implementation: creator(&parameters)?,
+            implementation: implementation,
             parameters: parameters,
+            registered_name: Some(registered_name),
+            cancelled: Arc::new(AtomicBool::new(false)),
+            workspace: Arc::new(Workspace::default()),
         })
     }
 
+    /// Save this calculator to the given `writer`, including any pre-computed
+    /// state (such as splined radial integrals) which can be expensive to
+    /// re-create. The calculator can then be re-created with
+    /// `Calculator::load`.
+    ///
+    /// # Errors
+    ///
+    /// This function returns an error if this calculator was not created with
+    /// `Calculator::new`, or if writing the data failed.
+    pub fn save<W: std::io::Write>(&self, writer: W) -> Result<(), Error> {
+        let name = self.registered_name.ok_or_else(|| Error::InvalidParameter(format!(
+            "can not save {}: only calculators created with Calculator::new can be saved",
+            self.name()
+        )))?;
+
+        let saved = SavedCalculator {
+            name: name.into(),
+            parameters: self.parameters.clone(),
+            state: self.save_state()?,
+        };
+
+        serde_json::to_writer(writer, &saved)?;
+        return Ok(());
+    }
+
+    /// Load a calculator previously saved with `Calculator::save` from the
+    /// given `reader`, restoring any pre-computed state instead of computing
+    /// it again.
+    pub fn load<R: std::io::Read>(reader: R) -> Result<Calculator, Error> {
+        let SavedCalculator { name, parameters, state } = serde_json::from_reader(reader)?;
+        let mut calculator = Calculator::create(&name, parameters)?;
+        if let Some(state) = state {
+            calculator.load_state(state)?;
+        }
+        calculator.precompute().with_context(|| format!(
+            "invalid parameters for calculator '{}'", name
+        ))?;
+        return Ok(calculator);
+    }
+
+    /// Get the pre-computed state of the underlying calculator implementation
+    pub(crate) fn save_state(&self) -> Result<Option<serde_json::Value>, Error> {
+        self.implementation.save_state()
+    }
+
+    /// Create the pre-computed state of the underlying calculator
+    /// implementation if needed
+    pub(crate) fn precompute(&mut self) -> Result<(), Error> {
+        self.implementation.precompute()
+    }
+
+    /// Restore the pre-computed state of the underlying calculator
+    /// implementation
+    pub(crate) fn load_state(&mut self, state: serde_json::Value) -> Result<(), Error> {
+        self.implementation.load_state(state)
+    }
+
     /// Get the name of this calculator
     pub fn name(&self) -> String {
         self.implementation.name()
@@ -206,11 +627,119 @@ impl Calculator {
         self.implementation.compute_gradients()
     }
 
+    /// Can this calculator compute gradients? This is `true` for calculators
+    /// which can compute gradients when requested in their parameters, even if
+    /// this specific instance does not.
+    pub fn supports_gradients(&self) -> bool {
+        self.implementation.supports_gradients()
+    }
+
+    /// Get the names of all the calculators that can be created with
+    /// `Calculator::new`, in alphabetical order
+    pub fn registered_names() -> Vec<&'static str> {
+        REGISTERED_CALCULATORS.keys().copied().collect()
+    }
+
+    /// Get the JSON schema describing the parameters of the registered
+    /// calculator with the given `name`, formatted as JSON.
+    pub fn parameters_schema(name: &str) -> Result<String, Error> {
+        let registered = REGISTERED_CALCULATORS.get(name).ok_or_else(|| Error::InvalidParameter(
+            format!("unknown calculator with name '{}'", name)
+        ))?;
+
+        let schema = serde_json::to_string(&(registered.schema)())?;
+        return Ok(schema);
+    }
+
+    /// Check if the registered calculator with the given `name` can compute
+    /// gradients, i.e. if it accepts a `gradients` parameter. This is the
+    /// same as `Calculator::supports_gradients`, without having to create a
+    /// calculator first.
+    ///
+    /// The `pipeline` calculator does not have a `gradients` parameter, since
+    /// gradients are requested separately for each of the sub-calculators.
+    pub fn registered_supports_gradients(name: &str) -> Result<bool, Error> {
+        let registered = REGISTERED_CALCULATORS.get(name).ok_or_else(|| Error::InvalidParameter(
+            format!("unknown calculator with name '{}'", name)
+        ))?;
+
+        let schema = (registered.schema)();
+        let supports_gradients = schema.schema.object.map_or(false, |object| {
+            object.properties.contains_key("gradients")
+        });
+        return Ok(supports_gradients);
+    }
+
     /// Get the default set of features for this calculator
     pub fn default_features(&self) -> Indexes {
         self.implementation.features()
     }
 
+    /// Get the samples builder of the underlying calculator implementation
+    pub(crate) fn samples_builder(&self) -> Box<dyn SamplesBuilder> {
+        self.implementation.samples_builder()
+    }
+
+    /// Estimate the memory that will be needed to run a calculation on the
+    /// given `systems` with the given `options`, without running the
+    /// calculation or allocating the arrays for values and gradients.
+    ///
+    /// Only the `selected_samples` and `selected_features` options influence
+    /// the estimate.
+    pub fn estimate_memory(
+        &self,
+        systems: &mut [Box<dyn System>],
+        options: CalculationOptions,
+    ) -> Result<MemoryEstimate, Error> {
+        let (samples, gradients_samples, features) = self.indexes(
+            systems,
+            options.selected_samples,
+            options.selected_features,
+        )?;
+
+        let gradients_samples_count = gradients_samples.as_ref().map_or(0, Indexes::count);
+
+        let value_size = std::mem::size_of::<f64>();
+        let index_size = std::mem::size_of::<IndexValue>();
+        let mut indexes = samples.count() * samples.size() + features.count() * features.size();
+        if let Some(gradients_samples) = &gradients_samples {
+            indexes += gradients_samples.count() * gradients_samples.size();
+        }
+
+        return Ok(MemoryEstimate {
+            samples_count: samples.count(),
+            features_count: features.count(),
+            gradients_samples_count: gradients_samples_count,
+            values: samples.count() * features.count() * value_size,
+            gradients: gradients_samples_count * features.count() * value_size,
+            indexes: indexes * index_size,
+        });
+    }
+
+    /// Get the samples, gradients samples (if this calculator computes
+    /// gradients) and features to use when running a calculation on the given
+    /// `systems`.
+    fn indexes(
+        &self,
+        systems: &mut [Box<dyn System>],
+        selected_samples: SelectedIndexes,
+        selected_features: SelectedIndexes,
+    ) -> Result<(Indexes, Option<Indexes>, Indexes), Error> {
+        let features = selected_features.into_features(&*self.implementation)?;
+        let samples = selected_samples.into_samples(&*self.implementation, systems)?;
+
+        let gradients_samples = if self.implementation.compute_gradients() {
+            let gradients = self.implementation.samples_builder()
+                .gradients_for(systems, &samples)?
+                .expect("this samples definition do not support gradients");
+            Some(gradients)
+        } else {
+            None
+        };
+
+        return Ok((samples, gradients_samples, features));
+    }
+
     /// Compute the descriptor for all the given `systems` and store it in
     /// `descriptor`
     ///
@@ -223,56 +752,666 @@ impl Calculator {
         descriptor: &mut Descriptor,
         options: CalculationOptions,
     ) -> Result<(), Error> {
+        let _guard = ParallelContextGuard::new(options.threads, options.parallelism, options.deterministic)?;
+        PARALLEL_CONTEXT.with(|context| {
+            let mut context = context.borrow_mut();
+            context.cancelled.push(Arc::clone(&self.cancelled));
+            context.workspace = Some(Arc::clone(&self.workspace));
+        });
+
+        let result = self.compute_impl(systems, descriptor, options);
+
+        // consume any cancellation request made during this calculation. The
+        // flag is checked by `compute_impl` before finishing, so requests
+        // arriving after the calculation completed successfully are ignored
+        self.cancelled.store(false, Ordering::SeqCst);
+        return result;
+    }
+
+    /// Compute the descriptor for all the given `systems`, directly storing it
+    /// as a [`BlockSparseDescriptor`] split along the given sample
+    /// `variables`.
+    ///
+    /// Each block is computed separately by running the calculator on the
+    /// samples of this block only, so neither the full dense descriptor nor
+    /// the zero-padded array created by [`Descriptor::densify`] are ever
+    /// allocated. The `options` are used for each block, with the selected
+    /// samples restricted to the samples of the block.
+    #[time_graph::instrument(name = "Calculator::compute_blocks")]
+    pub fn compute_blocks(
+        &mut self,
+        systems: &mut [Box<dyn System>],
+        variables: &[&str],
+        options: CalculationOptions,
+    ) -> Result<BlockSparseDescriptor, Error> {
+        let features = options.selected_features.into_features(&*self.implementation)?;
+        let samples = options.selected_samples.into_samples(&*self.implementation, systems)?;
+
+        let names = samples.names();
+        let mut variables_positions = Vec::new();
+        for variable in variables {
+            if let Some(position) = names.iter().position(|name| name == variable) {
+                variables_positions.push(position);
+            } else {
+                return Err(Error::InvalidParameter(format!(
+                    "can not split along '{}' which is not present in the samples: [{}]",
+                    variable, names.join(", ")
+                )));
+            }
+        }
+
+        let mut blocks_samples = BTreeMap::new();
+        for sample in &samples {
+            let key = variables_positions.iter().map(|&i| sample[i]).collect::<Vec<_>>();
+            blocks_samples.entry(key)
+                .or_insert_with(|| IndexesBuilder::new(names.clone()))
+                .add(sample);
+        }
+
+        let mut blocks = BlockSparseDescriptor::new(variables, features.clone());
+        for (_, block_samples) in blocks_samples {
+            let block_options = CalculationOptions {
+                selected_samples: SelectedIndexes::Subset(block_samples.finish()),
+                selected_features: SelectedIndexes::Subset(features.clone()),
+                ..options
+            };
+
+            let mut descriptor = Descriptor::new();
+            self.compute(systems, &mut descriptor, block_options)?;
+            blocks.extend(descriptor.into_blocks(variables)?);
+        }
+
+        return Ok(blocks);
+    }
+
+    /// Compute the descriptor for all the given `systems`, storing the values
+    /// and gradients in single precision.
+    ///
+    /// The calculation itself runs in double precision, on groups of systems
+    /// containing around `SINGLE_PRECISION_CHUNK_SAMPLES` samples at the time.
+    /// The results for each group are converted to single precision before
+    /// moving to the next group, so only the double precision arrays for a
+    /// single group are allocated at any given time. This halves the memory
+    /// used to store the descriptor compared to [`Calculator::compute`].
+    ///
+    /// `CalculationOptions::hyper_gradients` and
+    /// `CalculationOptions::lazy_gradients` are not supported by this
+    /// function.
+    #[time_graph::instrument(name = "Calculator::compute_single_precision")]
+    pub fn compute_single_precision(
+        &mut self,
+        systems: &mut [Box<dyn System>],
+        options: CalculationOptions,
+    ) -> Result<SinglePrecisionDescriptor, Error> {
+        if options.hyper_gradients || options.lazy_gradients {
+            return Err(Error::InvalidParameter(
+                "hyper_gradients and lazy_gradients can not be used with single precision calculations".into()
+            ));
+        }
+
+        let (samples, gradients_samples, features) = self.indexes(
+            systems,
+            options.selected_samples,
+            options.selected_features,
+        )?;
+
+        let structure = samples.names().iter().position(|&name| name == "structure").ok_or_else(|| Error::Internal(format!(
+            "missing 'structure' in the samples of calculator '{}'", self.name()
+        )))?;
+
+        let mut samples_per_system = vec![0; systems.len()];
+        for sample in &samples {
+            samples_per_system[sample[structure].usize()] += 1;
+        }
+
+        let mut values = Array2::zeros((samples.count(), features.count()));
+        let mut gradients = gradients_samples.as_ref().map(|gradients_samples| {
+            Array2::zeros((gradients_samples.count(), features.count()))
+        });
+
+        let mut all_samples = IndexesBuilder::new(samples.names());
+        let mut all_gradients_samples = IndexesBuilder::new(vec!["sample", "atom", "spatial"]);
+        let mut samples_count = 0;
+        let mut gradients_samples_count = 0;
+        let mut start = 0;
+        while start < systems.len() {
+            let mut stop = start;
+            let mut chunk_size = 0;
+            while stop < systems.len() && (stop == start || chunk_size + samples_per_system[stop] <= SINGLE_PRECISION_CHUNK_SAMPLES) {
+                chunk_size += samples_per_system[stop];
+                stop += 1;
+            }
+
+            // select the samples for this group of systems, with structure
+            // indexes starting at 0
+            let mut chunk_samples = IndexesBuilder::new(samples.names());
+            let mut chunk_samples_count = 0;
+            for sample in &samples {
+                let system = sample[structure].usize();
+                if system >= start && system < stop {
+                    let mut shifted = sample.to_vec();
+                    shifted[structure] = IndexValue::from(system - start);
+                    chunk_samples.add(&shifted);
+                    chunk_samples_count += 1;
+                }
+            }
+
+            if chunk_samples_count != 0 {
+                let chunk_options = CalculationOptions {
+                    selected_samples: SelectedIndexes::Subset(chunk_samples.finish()),
+                    selected_features: SelectedIndexes::Subset(features.clone()),
+                    ..options
+                };
+
+                let mut descriptor = Descriptor::new();
+                self.compute(&mut systems[start..stop], &mut descriptor, chunk_options)?;
+
+                let first_sample = samples_count;
+                for (sample, row) in descriptor.samples.iter().zip(descriptor.values.outer_iter()) {
+                    let mut shifted = sample.to_vec();
+                    shifted[structure] = IndexValue::from(sample[structure].usize() + start);
+                    values.row_mut(samples_count).assign(&row.mapv(|v| v as f32));
+                    all_samples.add(&shifted);
+                    samples_count += 1;
+                }
+
+                if let (Some(gradients), Some(chunk_gradients)) = (&mut gradients, &descriptor.gradients) {
+                    let chunk_gradients_samples = descriptor.gradients_samples.as_ref().expect("missing gradients samples");
+                    for (gradient_sample, row) in chunk_gradients_samples.iter().zip(chunk_gradients.outer_iter()) {
+                        if gradients_samples_count >= gradients.nrows() {
+                            return Err(Error::Internal(
+                                "the calculator produced more gradients samples than expected".into()
+                            ));
+                        }
+                        gradients.row_mut(gradients_samples_count).assign(&row.mapv(|v| v as f32));
+                        all_gradients_samples.add(&[
+                            IndexValue::from(gradient_sample[0].usize() + first_sample),
+                            gradient_sample[1],
+                            gradient_sample[2],
+                        ]);
+                        gradients_samples_count += 1;
+                    }
+                }
+            }
+
+            start = stop;
+        }
+
+        let gradients_samples = gradients.as_ref().map(|_| all_gradients_samples.finish());
+        if let Some(ref gradients) = gradients {
+            if gradients_samples_count != gradients.nrows() {
+                return Err(Error::Internal(
+                    "the calculator produced less gradients samples than expected".into()
+                ));
+            }
+        }
+
+        return Ok(SinglePrecisionDescriptor {
+            values: values,
+            samples: all_samples.finish(),
+            gradients: gradients,
+            gradients_samples: gradients_samples,
+            features: features,
+        });
+    }
+
+    /// Get a handle which can be used to cancel calculations running with
+    /// this calculator from another thread.
+    pub fn cancellation_handle(&self) -> CancellationHandle {
+        CancellationHandle {
+            cancelled: Arc::clone(&self.cancelled),
+        }
+    }
+
+    /// Implementation of `Calculator::compute`, running inside the parallel
+    /// context of the calculation
+    fn compute_impl(
+        &mut self,
+        systems: &mut [Box<dyn System>],
+        descriptor: &mut Descriptor,
+        options: CalculationOptions,
+    ) -> Result<(), Error> {
+        check_cancelled()?;
+
+        if let Some(cutoff) = options.neighbors_cutoff {
+            if !(cutoff > 0.0 && cutoff.is_finite()) {
+                return Err(Error::InvalidParameter(format!(
+                    "neighbors_cutoff must be a positive number, got {}", cutoff
+                )));
+            }
+
+            if !options.use_native_system && !options.lazy_gradients {
+                return Err(Error::InvalidParameter(
+                    "neighbors_cutoff can only be used with use_native_system".into()
+                ));
+            }
+        }
+
+        let lazy_gradients = if options.lazy_gradients {
+            Some(self.lazy_gradients_setup(options.neighbors_cutoff)?)
+        } else {
+            None
+        };
+
+        // copies of the native systems, kept in the descriptor to compute lazy
+        // gradients
+        let mut lazy_systems = Vec::new();
+
         let mut native_systems;
-        let systems = if options.use_native_system {
+        let systems = if options.use_native_system || lazy_gradients.is_some() {
+            let cutoff = match lazy_gradients {
+                Some((_, cutoff)) => cutoff,
+                None => options.neighbors_cutoff,
+            };
             native_systems = Vec::with_capacity(systems.len());
-            for system in systems {
-                native_systems.push(Box::new(SimpleSystem::try_from(&**system)?) as Box<dyn System>);
+            for (i_system, system) in systems.iter().enumerate() {
+                let mut native = SimpleSystem::try_from(&**system).with_context(|| format!(
+                    "failed to convert structure {} to a native system", i_system
+                ))?;
+
+                if let Some(cutoff) = cutoff {
+                    native.precompute_neighbors(cutoff).with_context(|| format!(
+                        "failed to compute the neighbors list for structure {}", i_system
+                    ))?;
+                }
+
+                if lazy_gradients.is_some() {
+                    lazy_systems.push(native.clone());
+                }
+                native_systems.push(Box::new(native) as Box<dyn System>);
             }
             &mut native_systems
         } else {
             systems
         };
 
-        let features = options.selected_features.into_features(&*self.implementation)?;
-        let samples = options.selected_samples.into_samples(&*self.implementation, systems)?;
+        let (samples, gradients_samples, features) = self.indexes(
+            systems,
+            options.selected_samples,
+            options.selected_features,
+        )?;
 
         time_graph::spanned!("Calculator::prepare", {
-            let builder = self.implementation.samples_builder();
-            if self.implementation.compute_gradients() {
-                let gradients = builder
-                    .gradients_for(systems, &samples)?
-                    .expect("this samples definition do not support gradients");
-                descriptor.prepare_gradients(samples, gradients, features);
-            } else {
-                descriptor.prepare(samples, features);
+            match gradients_samples {
+                Some(gradients_samples) if lazy_gradients.is_none() => {
+                    descriptor.prepare_gradients(samples, gradients_samples, features);
+                }
+                _ => descriptor.prepare(samples, features),
             }
         });
 
-        self.implementation.compute(systems, descriptor)?;
+        if let Some((registered_name, _)) = lazy_gradients {
+            let cache = self.compute_with_lazy_gradients(registered_name, systems, descriptor)?;
+            descriptor.pending_gradients = Some(Arc::new(PendingGradients {
+                name: registered_name,
+                parameters: self.parameters.clone(),
+                systems: lazy_systems,
+                cache: cache,
+                threads: options.threads,
+                parallelism: options.parallelism,
+                deterministic: options.deterministic,
+            }));
+        } else {
+            self.implementation.compute(systems, descriptor)?;
+        }
+        check_cancelled()?;
+
+        if options.hyper_gradients {
+            // the displaced calculations run on the systems converted above
+            let displaced_options = CalculationOptions {
+                use_native_system: false,
+                threads: options.threads,
+                parallelism: options.parallelism,
+                check_finite: options.check_finite,
+                deterministic: options.deterministic,
+                neighbors_cutoff: None,
+                ..Default::default()
+            };
+            self.compute_hyper_gradients(systems, descriptor, &displaced_options)?;
+        }
+
+        if options.check_finite {
+            check_finite(descriptor).with_context(|| format!(
+                "non-finite data in the output of calculator '{}'",
+                self.registered_name.map_or_else(|| self.name(), String::from)
+            ))?;
+        }
+
+        return Ok(());
+    }
+}
+
+/// Approximate number of samples computed at once in
+/// `Calculator::compute_single_precision`
+const SINGLE_PRECISION_CHUNK_SAMPLES: usize = 4096;
+
+/// Relative size of the displacement used to compute gradients with respect
+/// to hyper-parameters with finite differences
+const HYPER_GRADIENTS_DELTA: f64 = 1e-5;
+
+impl Calculator {
+    /// Compute the gradients of the values in `descriptor` with respect to the
+    /// hyper-parameters of this calculator, using finite differences. Each
+    /// hyper-parameter is displaced in the JSON parameters, and a new
+    /// calculator is created from the displaced parameters to re-compute the
+    /// values for the same samples and features, using the given `options`.
+    ///
+    /// Central finite differences are used when both displaced parameters are
+    /// valid, and one-sided finite differences when the parameter is close to
+    /// the boundary of its valid range (i.e. when creating a calculator with
+    /// one of the displaced parameters fails).
+    ///
+    /// Splined radial integrals are replaced by the corresponding
+    /// non-splined radial integral in the displaced calculators. This avoids
+    /// amplifying the interpolation error of the splines in the finite
+    /// differences, and prevents the splines for the displaced parameters from
+    /// being added to the global spline cache.
+    #[time_graph::instrument(name = "Calculator::hyper_gradients")]
+    fn compute_hyper_gradients(
+        &self,
+        systems: &mut [Box<dyn System>],
+        descriptor: &mut Descriptor,
+        options: &CalculationOptions,
+    ) -> Result<(), Error> {
+        let registered_name = self.registered_name.ok_or_else(|| Error::InvalidParameter(
+            "gradients with respect to hyper-parameters can only be computed for calculators created with Calculator::new".into()
+        ))?;
+
+        let hyper_parameters = self.implementation.hyper_parameters();
+        if hyper_parameters.is_empty() {
+            return Err(Error::InvalidParameter(format!(
+                "calculator '{}' does not support gradients with respect to hyper-parameters",
+                registered_name
+            )));
+        }
+
+        let mut parameters: serde_json::Value = serde_json::from_str(&self.parameters)?;
+        if let Some(gradients) = parameters.get_mut("gradients") {
+            // gradients with respect to positions are not needed here
+            *gradients = serde_json::Value::from(false);
+        }
+        if let Some(radial_basis) = parameters.get_mut("radial_basis") {
+            if radial_basis.get("SplinedGto").is_some() {
+                *radial_basis = serde_json::json!({"Gto": {}});
+            }
+        }
+
+        // Compute the values with the hyper-parameter at `pointer` set to
+        // `value`, or return `None` if the corresponding parameters are not
+        // valid for this calculator
+        let mut displaced_values = |pointer: &str, value: f64| -> Result<Option<Array2<f64>>, Error> {
+            let mut parameters = parameters.clone();
+            *parameters.pointer_mut(pointer).expect("missing hyper-parameter") = serde_json::Value::from(value);
+
+            let mut calculator = match Calculator::new(registered_name, parameters.to_string()) {
+                Ok(calculator) => calculator,
+                Err(Error::InvalidParameter(_)) => return Ok(None),
+                Err(error) => return Err(error),
+            };
+
+            let mut displaced = Descriptor::new();
+            let options = CalculationOptions {
+                selected_samples: SelectedIndexes::Subset(descriptor.samples.clone()),
+                selected_features: SelectedIndexes::Subset(descriptor.features.clone()),
+                hyper_gradients: false,
+                lazy_gradients: false,
+                ..options.clone()
+            };
+            calculator.compute(systems, &mut displaced, options).with_context(|| format!(
+                "failed to compute displaced descriptor for hyper-parameter '{}'", pointer
+            ))?;
+            return Ok(Some(displaced.values));
+        };
+
+        let shape = (hyper_parameters.len(), descriptor.samples.count(), descriptor.features.count());
+        let mut hyper_gradients = Array3::zeros(shape);
+        for (hyper_i, pointer) in hyper_parameters.iter().enumerate() {
+            let value = parameters.pointer(pointer).and_then(serde_json::Value::as_f64).ok_or_else(|| Error::Internal(format!(
+                "hyper-parameter '{}' is not a number in the parameters of calculator '{}'",
+                pointer, registered_name
+            )))?;
+
+            let delta = if value == 0.0 { HYPER_GRADIENTS_DELTA } else { HYPER_GRADIENTS_DELTA * value.abs() };
+
+            let forward = displaced_values(pointer, value + delta)?;
+            let backward = displaced_values(pointer, value - delta)?;
+            let gradient = match (forward, backward) {
+                (Some(forward), Some(backward)) => (forward - backward) / (2.0 * delta),
+                (Some(forward), None) => {
+                    let center = displaced_values(pointer, value)?.expect("invalid initial parameters");
+                    (forward - center) / delta
+                }
+                (None, Some(backward)) => {
+                    let center = displaced_values(pointer, value)?.expect("invalid initial parameters");
+                    (center - backward) / delta
+                }
+                (None, None) => {
+                    return Err(Error::InvalidParameter(format!(
+                        "can not compute the gradients with respect to hyper-parameter '{}': \
+                        the parameters are invalid when displacing it by {} in both directions",
+                        pointer, delta
+                    )));
+                }
+            };
+            hyper_gradients.index_axis_mut(Axis(0), hyper_i).assign(&gradient);
+        }
+
+        descriptor.hyper_gradients = Some(hyper_gradients);
+        descriptor.hyper_parameters = hyper_parameters;
+
         return Ok(());
     }
 }
 
+/// Data stored in a `Descriptor` computed with
+/// `CalculationOptions::lazy_gradients`, used to compute the gradients on the
+/// first call to `Descriptor::gradients`.
+#[derive(Debug)]
+pub(crate) struct PendingGradients {
+    /// Name of the calculator in `REGISTERED_CALCULATORS`
+    name: &'static str,
+    /// JSON-formatted parameters of the calculator, with gradients enabled
+    parameters: String,
+    /// Native copies of the systems used in the calculation, including their
+    /// neighbor list when the cutoff of the calculator is known
+    systems: Vec<SimpleSystem>,
+    /// Intermediate quantities cached by the calculator during the
+    /// calculation of the values, if the calculator supports it
+    cache: Option<Box<dyn GradientsCache>>,
+    /// Parallel settings of the initial calculation
+    threads: Option<usize>,
+    parallelism: Parallelism,
+    deterministic: bool,
+}
+
+impl PendingGradients {
+    /// Compute the gradients for the samples and features currently in
+    /// `descriptor`, returning the gradients samples and gradients array.
+    #[time_graph::instrument(name = "PendingGradients::compute")]
+    pub(crate) fn compute(&self, descriptor: &Descriptor) -> Result<(Indexes, Array2<f64>), Error> {
+        let mut calculator = Calculator::new(self.name, self.parameters.clone())?;
+        let mut systems = self.systems.iter()
+            .map(|system| Box::new(system.clone()) as Box<dyn System>)
+            .collect::<Vec<_>>();
+
+        if let Some(ref cache) = self.cache {
+            let _guard = ParallelContextGuard::new(self.threads, self.parallelism, self.deterministic)?;
+
+            let gradients_samples = calculator.samples_builder()
+                .gradients_for(&mut systems, &descriptor.samples)?
+                .expect("this samples definition do not support gradients");
+
+            let mut full = Descriptor::new();
+            full.set_layout(descriptor.layout());
+            full.prepare_gradients(descriptor.samples.clone(), gradients_samples, descriptor.features.clone());
+
+            let gradients_samples = full.gradients_samples.take().expect("missing gradients samples");
+            let mut gradients = full.gradients.take().expect("missing gradients");
+            cache.compute_gradients(
+                &descriptor.samples,
+                &gradients_samples,
+                &descriptor.features,
+                gradients.view_mut(),
+            ).context("failed to compute lazy gradients")?;
+
+            return Ok((gradients_samples, gradients));
+        }
+
+        let options = CalculationOptions {
+            selected_samples: SelectedIndexes::Subset(descriptor.samples.clone()),
+            selected_features: SelectedIndexes::Subset(descriptor.features.clone()),
+            threads: self.threads,
+            parallelism: self.parallelism,
+            deterministic: self.deterministic,
+            ..Default::default()
+        };
+
+        let mut full = Descriptor::new();
+        full.set_layout(descriptor.layout());
+        calculator.compute(&mut systems, &mut full, options).context("failed to compute lazy gradients")?;
+
+        match (full.gradients_samples, full.gradients) {
+            (Some(gradients_samples), Some(gradients)) => Ok((gradients_samples, gradients)),
+            _ => Err(Error::Internal(format!(
+                "calculator '{}' did not compute gradients", self.name
+            ))),
+        }
+    }
+}
+
+impl Calculator {
+    /// Check that this calculator can be used with
+    /// `CalculationOptions::lazy_gradients`, and get its registered name
+    /// together with the cutoff used to pre-compute the neighbor lists of the
+    /// systems (either `neighbors_cutoff` or the `cutoff` parameter of this
+    /// calculator, if any).
+    fn lazy_gradients_setup(&self, neighbors_cutoff: Option<f64>) -> Result<(&'static str, Option<f64>), Error> {
+        let registered_name = self.registered_name.ok_or_else(|| Error::InvalidParameter(
+            "lazy gradients can only be computed for calculators created with Calculator::new".into()
+        ))?;
+
+        if !self.gradients() {
+            return Err(Error::InvalidParameter(format!(
+                "lazy gradients require a calculator computing gradients, but calculator '{}' does not",
+                registered_name
+            )));
+        }
+
+        let parameters: serde_json::Value = serde_json::from_str(&self.parameters)?;
+        let cutoff = neighbors_cutoff.or_else(|| {
+            parameters.get("cutoff").and_then(serde_json::Value::as_f64)
+        });
+
+        return Ok((registered_name, cutoff));
+    }
+
+    /// Implementation of `Calculator::compute` for
+    /// `CalculationOptions::lazy_gradients`, computing the values in the
+    /// already prepared `descriptor` and returning the intermediate quantities
+    /// cached by the implementation to compute the gradients later, if any.
+    ///
+    /// If the implementation can not cache intermediate quantities for the
+    /// gradients, the values are computed with a copy of this calculator
+    /// without gradients, and the gradients will be computed by running the
+    /// full calculation again.
+    fn compute_with_lazy_gradients(
+        &mut self,
+        registered_name: &'static str,
+        systems: &mut [Box<dyn System>],
+        descriptor: &mut Descriptor,
+    ) -> Result<Option<Box<dyn GradientsCache>>, Error> {
+        let cache = self.implementation.compute_caching_gradients(systems, descriptor)?;
+        if cache.is_none() {
+            let mut parameters: serde_json::Value = serde_json::from_str(&self.parameters)?;
+            if let Some(gradients) = parameters.get_mut("gradients") {
+                *gradients = serde_json::Value::from(false);
+            }
+
+            let mut calculator = Calculator::new(registered_name, parameters.to_string())?;
+            calculator.implementation.compute(systems, descriptor)?;
+        }
+
+        return Ok(cache);
+    }
+}
+
+/// Check that all the values and gradients in `descriptor` are finite, and
+/// return an error describing the first non-finite entry otherwise.
+#[time_graph::instrument]
+fn check_finite(descriptor: &Descriptor) -> Result<(), Error> {
+    let non_finite = descriptor.values.indexed_iter().find(|(_, value)| !value.is_finite());
+    if let Some(((sample, feature), value)) = non_finite {
+        return Err(Error::Internal(format!(
+            "got {} in values for sample {} and feature {}",
+            value, format_index(&descriptor.samples, sample), format_index(&descriptor.features, feature)
+        )));
+    }
+
+    if let (Some(gradients), Some(gradients_samples)) = (&descriptor.gradients, &descriptor.gradients_samples) {
+        let non_finite = gradients.indexed_iter().find(|(_, value)| !value.is_finite());
+        if let Some(((sample, feature), value)) = non_finite {
+            return Err(Error::Internal(format!(
+                "got {} in gradients for gradient sample {} and feature {}",
+                value, format_index(gradients_samples, sample), format_index(&descriptor.features, feature)
+            )));
+        }
+    }
+
+    return Ok(());
+}
+
+/// Format the entry at position `i` in `indexes` as `(name: value, ...)`
+fn format_index(indexes: &Indexes, i: usize) -> String {
+    let values = indexes.names().iter()
+        .zip(&indexes[i])
+        .map(|(name, value)| format!("{}: {}", name, value))
+        .collect::<Vec<_>>();
+    return format!("({})", values.join(", "));
+}
+
 
 /// Registration of calculator implementations
-use crate::calculators::{DummyCalculator, SortedDistances};
+use crate::calculators::{DummyCalculator, SortedDistances, Composition};
 use crate::calculators::{SphericalExpansion, SphericalExpansionParameters};
 use crate::calculators::{SoapPowerSpectrum, PowerSpectrumParameters};
+use crate::calculators::{LambdaSoap, LambdaSoapParameters};
+use crate::calculators::{ElectrostaticPotential, ElectrostaticPotentialParameters};
+use crate::calculators::{RadialDistribution, RadialDistributionParameters};
+use crate::calculators::{AngularDistribution, AngularDistributionParameters};
+use crate::calculators::{BondEnvironment, BondEnvironmentParameters};
+use crate::calculators::{VoronoiEnvironment, VoronoiParameters};
+use crate::calculators::{GraphMoments, GraphMomentsParameters};
+use crate::calculators::{Pipeline, PipelineParameters};
 type CalculatorCreator = fn(&str) -> Result<Box<dyn CalculatorBase>, Error>;
 
+/// Entry in the calculator registry
+struct RegisteredCalculator {
+    /// Function creating a new calculator from JSON parameters
+    create: CalculatorCreator,
+    /// Function generating the JSON schema for the calculator parameters
+    schema: fn() -> schemars::schema::RootSchema,
+}
+
 macro_rules! add_calculator {
     ($map :expr, $name :literal, $type :ty) => (
-        $map.insert($name, (|json| {
-            let value = serde_json::from_str::<$type>(json)?;
-            Ok(Box::new(value))
-        }) as CalculatorCreator);
+        $map.insert($name, RegisteredCalculator {
+            create: |json| {
+                crate::calculators::validate_parameters::<$type>(json)?;
+                let value = serde_json::from_str::<$type>(json)?;
+                Ok(Box::new(value))
+            },
+            schema: || schemars::schema_for!($type),
+        });
     );
     ($map :expr, $name :literal, $type :ty, $parameters :ty) => (
-        $map.insert($name, (|json| {
-            let parameters = serde_json::from_str::<$parameters>(json)?;
-            Ok(Box::new(<$type>::new(parameters)?))
-        }) as CalculatorCreator);
+        $map.insert($name, RegisteredCalculator {
+            create: |json| {
+                crate::calculators::validate_parameters::<$parameters>(json)?;
+                let parameters = serde_json::from_str::<$parameters>(json)?;
+                Ok(Box::new(<$type>::new(parameters)?))
+            },
+            schema: || schemars::schema_for!($parameters),
+        });
     );
 }
 
@@ -280,12 +1419,21 @@ macro_rules! add_calculator {
 // first/last line to include
 // [calculator-registration]
 lazy_static::lazy_static!{
-    pub static ref REGISTERED_CALCULATORS: BTreeMap<&'static str, CalculatorCreator> = {
+    static ref REGISTERED_CALCULATORS: BTreeMap<&'static str, RegisteredCalculator> = {
         let mut map = BTreeMap::new();
         add_calculator!(map, "dummy_calculator", DummyCalculator);
+        add_calculator!(map, "composition", Composition);
         add_calculator!(map, "sorted_distances", SortedDistances);
+        add_calculator!(map, "electrostatic_potential", ElectrostaticPotential, ElectrostaticPotentialParameters);
+        add_calculator!(map, "radial_distribution", RadialDistribution, RadialDistributionParameters);
+        add_calculator!(map, "angular_distribution", AngularDistribution, AngularDistributionParameters);
+        add_calculator!(map, "bond_environment", BondEnvironment, BondEnvironmentParameters);
+        add_calculator!(map, "voronoi", VoronoiEnvironment, VoronoiParameters);
+        add_calculator!(map, "graph_moments", GraphMoments, GraphMomentsParameters);
         add_calculator!(map, "spherical_expansion", SphericalExpansion, SphericalExpansionParameters);
         add_calculator!(map, "soap_power_spectrum", SoapPowerSpectrum, PowerSpectrumParameters);
+        add_calculator!(map, "lambda_soap", LambdaSoap, LambdaSoapParameters);
+        add_calculator!(map, "pipeline", Pipeline, PipelineParameters);
         return map;
     };
 }
@@ -294,7 +1442,13 @@ lazy_static::lazy_static!{
 
 #[cfg(test)]
 mod tests {
-    use super::SelectedIndexes;
+    use ndarray::Axis;
+    use approx::assert_relative_eq;
+
+    use super::{Calculator, CalculationOptions, Parallelism, SelectedIndexes};
+
+    use crate::{SimpleSystem, System, Vector3D};
+    use crate::systems::UnitCell;
 
     use crate::calculators::{CalculatorBase, DummyCalculator};
     use crate::descriptor::{IndexesBuilder, IndexValue};
@@ -359,4 +1513,684 @@ mod tests {
         let indexes = selected.into_samples(&calculator, &mut systems).unwrap();
         assert_eq!(indexes, expected);
     }
+
+    #[test]
+    fn save_and_load() {
+        let parameters = r#"{
+            "cutoff": 3.5,
+            "max_radial": 4,
+            "max_angular": 3,
+            "atomic_gaussian_width": 0.3,
+            "gradients": false,
+            "radial_basis": {"SplinedGto": {"accuracy": 1e-8}},
+            "cutoff_function": {"ShiftedCosine": {"width": 0.5}}
+        }"#;
+        let mut calculator = Calculator::new("soap_power_spectrum", parameters.into()).unwrap();
+
+        let mut buffer = Vec::new();
+        calculator.save(&mut buffer).unwrap();
+
+        let mut loaded = Calculator::load(buffer.as_slice()).unwrap();
+        assert_eq!(loaded.name(), calculator.name());
+        assert_eq!(loaded.parameters(), calculator.parameters());
+
+        let mut systems = crate::systems::test_utils::test_systems(&["water", "methane"]);
+        let mut expected = crate::Descriptor::new();
+        calculator.compute(&mut systems, &mut expected, Default::default()).unwrap();
+
+        let mut descriptor = crate::Descriptor::new();
+        loaded.compute(&mut systems, &mut descriptor, Default::default()).unwrap();
+        assert_eq!(descriptor.values, expected.values);
+
+        // the saved spline can only be used with the same gaussian width
+        let mut saved: serde_json::Value = serde_json::from_slice(&buffer).unwrap();
+        saved["parameters"] = serde_json::Value::from(parameters.replace("0.3", "0.4"));
+        let error = Calculator::load(saved.to_string().as_bytes()).err().unwrap();
+        assert_eq!(error.to_string(), "invalid parameter: the saved spline was created with atomic_gaussian_width=0.3, but this spherical expansion uses 0.4");
+
+        // errors in the spline creation are reported when creating the calculator
+        let error = Calculator::new("soap_power_spectrum", parameters.replace("1e-8", "1e-300")).err().unwrap();
+        assert!(error.to_string().starts_with(
+            "invalid parameters for calculator 'soap_power_spectrum': internal error: failed to reach requested accuracy"
+        ));
+
+        // calculators without state can also be saved
+        let calculator = Calculator::new("dummy_calculator", r#"{
+            "cutoff": 1.0,
+            "delta": 0,
+            "name": "",
+            "gradients": false
+        }"#.into()).unwrap();
+
+        let mut buffer = Vec::new();
+        calculator.save(&mut buffer).unwrap();
+        let loaded = Calculator::load(buffer.as_slice()).unwrap();
+        assert_eq!(loaded.parameters(), calculator.parameters());
+    }
+
+    #[test]
+    fn estimate_memory() {
+        let mut calculator = Calculator::new("dummy_calculator", r#"{
+            "cutoff": 1.0,
+            "delta": 9,
+            "name": "",
+            "gradients": true
+        }"#.to_owned()).unwrap();
+
+        let mut systems = crate::systems::test_utils::test_systems(&["water", "methane"]);
+        let estimate = calculator.estimate_memory(&mut systems, Default::default()).unwrap();
+
+        let mut descriptor = crate::Descriptor::new();
+        calculator.compute(&mut systems, &mut descriptor, Default::default()).unwrap();
+
+        let gradients = descriptor.gradients.as_ref().unwrap();
+        assert_eq!(estimate.samples_count, descriptor.values.shape()[0]);
+        assert_eq!(estimate.features_count, descriptor.values.shape()[1]);
+        assert_eq!(estimate.gradients_samples_count, gradients.shape()[0]);
+        assert_eq!(estimate.values, descriptor.values.len() * std::mem::size_of::<f64>());
+        assert_eq!(estimate.gradients, gradients.len() * std::mem::size_of::<f64>());
+        assert!(estimate.total() > estimate.values + estimate.gradients);
+
+        // selected samples are taken into account
+        let mut selected = IndexesBuilder::new(vec!["structure"]);
+        selected.add(&[IndexValue::from(0)]);
+        let options = CalculationOptions {
+            selected_samples: SelectedIndexes::Subset(selected.finish()),
+            ..Default::default()
+        };
+        let estimate = calculator.estimate_memory(&mut systems, options).unwrap();
+        assert_eq!(estimate.samples_count, 3);
+    }
+
+    #[test]
+    fn save_unregistered() {
+        let calculator = Calculator::from(Box::new(DummyCalculator {
+            cutoff: 3.4, delta: 0, name: String::new(), gradients: false,
+        }) as Box<dyn CalculatorBase>);
+
+        let error = calculator.save(Vec::new()).unwrap_err();
+        assert!(error.to_string().ends_with(
+            "only calculators created with Calculator::new can be saved"
+        ));
+    }
+
+    #[test]
+    fn parallel_options() {
+        let mut calculator = Calculator::new("soap_power_spectrum", r#"{
+            "cutoff": 3.5,
+            "max_radial": 4,
+            "max_angular": 3,
+            "atomic_gaussian_width": 0.3,
+            "gradients": true,
+            "radial_basis": {"Gto": {}},
+            "cutoff_function": {"ShiftedCosine": {"width": 0.5}}
+        }"#.into()).unwrap();
+
+        let mut systems = crate::systems::test_utils::test_systems(&["water", "methane"]);
+        let mut expected = crate::Descriptor::new();
+        calculator.compute(&mut systems, &mut expected, Default::default()).unwrap();
+
+        for &threads in &[None, Some(1), Some(3)] {
+            for &parallelism in &[Parallelism::Samples, Parallelism::Systems] {
+                let options = CalculationOptions {
+                    threads: threads,
+                    parallelism: parallelism,
+                    ..Default::default()
+                };
+                let mut descriptor = crate::Descriptor::new();
+                calculator.compute(&mut systems, &mut descriptor, options).unwrap();
+                approx::assert_ulps_eq!(descriptor.values, expected.values);
+                approx::assert_ulps_eq!(descriptor.gradients.unwrap(), expected.gradients.as_ref().unwrap());
+            }
+        }
+
+        let options = CalculationOptions {
+            threads: Some(0),
+            ..Default::default()
+        };
+        let error = calculator.compute(&mut systems, &mut expected, options).unwrap_err();
+        assert_eq!(error.to_string(), "invalid parameter: the number of threads must be at least 1");
+
+        // thread pools are re-used between calculations
+        let pool = super::thread_pool(3).unwrap();
+        assert!(std::sync::Arc::ptr_eq(&pool, &super::thread_pool(3).unwrap()));
+        assert_eq!(pool.current_num_threads(), 3);
+    }
+
+    #[test]
+    fn deterministic() {
+        let mut calculator = Calculator::new("spherical_expansion", r#"{
+            "cutoff": 3.5,
+            "max_radial": 4,
+            "max_angular": 3,
+            "atomic_gaussian_width": 0.3,
+            "gradients": true,
+            "radial_basis": {"Gto": {}},
+            "cutoff_function": {"ShiftedCosine": {"width": 0.5}}
+        }"#.into()).unwrap();
+
+        let mut systems = crate::systems::test_utils::test_systems(&["water", "methane", "methane"]);
+        let options = CalculationOptions {
+            threads: Some(1),
+            deterministic: true,
+            ..Default::default()
+        };
+        let mut expected = crate::Descriptor::new();
+        calculator.compute(&mut systems, &mut expected, options).unwrap();
+
+        for &threads in &[None, Some(2), Some(5)] {
+            for &parallelism in &[Parallelism::Samples, Parallelism::Systems] {
+                let options = CalculationOptions {
+                    threads: threads,
+                    parallelism: parallelism,
+                    deterministic: true,
+                    ..Default::default()
+                };
+                let mut descriptor = crate::Descriptor::new();
+                calculator.compute(&mut systems, &mut descriptor, options).unwrap();
+
+                // the results should be bitwise identical
+                assert_eq!(descriptor.samples, expected.samples);
+                assert_eq!(descriptor.gradients_samples, expected.gradients_samples);
+                assert_eq!(descriptor.values, expected.values);
+                assert_eq!(descriptor.gradients, expected.gradients);
+            }
+        }
+    }
+
+    #[test]
+    fn hyper_gradients() {
+        let parameters = |width: f64, scale: f64| format!(r#"{{
+            "cutoff": 3.5,
+            "max_radial": 4,
+            "max_angular": 3,
+            "atomic_gaussian_width": {},
+            "gradients": true,
+            "radial_basis": {{"Gto": {{}}}},
+            "cutoff_function": {{"ShiftedCosine": {{"width": 0.5}}}},
+            "radial_scaling": {{"Willatt2018": {{"scale": {}, "rate": 0.8, "exponent": 2}}}}
+        }}"#, width, scale);
+
+        let mut calculator = Calculator::new("soap_power_spectrum", parameters(0.3, 1.5)).unwrap();
+        let mut systems = crate::systems::test_utils::test_systems(&["water"]);
+
+        let options = CalculationOptions {
+            hyper_gradients: true,
+            ..Default::default()
+        };
+        let mut descriptor = crate::Descriptor::new();
+        calculator.compute(&mut systems, &mut descriptor, options).unwrap();
+
+        assert_eq!(descriptor.hyper_parameters, [
+            "/atomic_gaussian_width",
+            "/cutoff_function/ShiftedCosine/width",
+            "/radial_scaling/Willatt2018/scale",
+            "/radial_scaling/Willatt2018/rate",
+        ]);
+        let hyper_gradients = descriptor.hyper_gradients.as_ref().unwrap();
+        assert_eq!(hyper_gradients.shape(), [4, descriptor.samples.count(), descriptor.features.count()]);
+
+        // compare with finite differences computed with a larger displacement
+        let delta = 1e-4;
+        let mut displaced = |width: f64, scale: f64| {
+            let mut calculator = Calculator::new("soap_power_spectrum", parameters(width, scale)).unwrap();
+            let mut displaced = crate::Descriptor::new();
+            calculator.compute(&mut systems, &mut displaced, Default::default()).unwrap();
+            displaced.values
+        };
+
+        let expected = (displaced(0.3 + delta, 1.5) - displaced(0.3 - delta, 1.5)) / (2.0 * delta);
+        approx::assert_relative_eq!(hyper_gradients.index_axis(Axis(0), 0), expected, max_relative = 1e-5, epsilon = 1e-8);
+
+        let expected = (displaced(0.3, 1.5 + delta) - displaced(0.3, 1.5 - delta)) / (2.0 * delta);
+        approx::assert_relative_eq!(hyper_gradients.index_axis(Axis(0), 2), expected, max_relative = 1e-5, epsilon = 1e-8);
+
+        // splined radial integrals and other options give the same gradients
+        let splined = parameters(0.3, 1.5).replace(r#"{"Gto": {}}"#, r#"{"SplinedGto": {"accuracy": 1e-8}}"#);
+        let mut calculator = Calculator::new("soap_power_spectrum", splined).unwrap();
+        let options = CalculationOptions {
+            hyper_gradients: true,
+            threads: Some(2),
+            parallelism: Parallelism::Systems,
+            ..Default::default()
+        };
+        let mut splined = crate::Descriptor::new();
+        calculator.compute(&mut systems, &mut splined, options).unwrap();
+        approx::assert_relative_eq!(
+            splined.hyper_gradients.as_ref().unwrap(), hyper_gradients,
+            max_relative = 1e-6, epsilon = 1e-8
+        );
+
+        // calculators without hyper-parameters
+        let mut calculator = Calculator::new("dummy_calculator", r#"{
+            "cutoff": 1.0,
+            "delta": 0,
+            "name": "",
+            "gradients": false
+        }"#.to_owned()).unwrap();
+        let options = CalculationOptions {
+            hyper_gradients: true,
+            ..Default::default()
+        };
+        let error = calculator.compute(&mut systems, &mut descriptor, options).unwrap_err();
+        assert_eq!(error.to_string(), "invalid parameter: calculator 'dummy_calculator' does not support gradients with respect to hyper-parameters");
+    }
+
+    #[test]
+    fn hyper_gradients_analytic() {
+        // for a single pair between different species, the spherical
+        // expansion of the first atom is `w(r) g(r)`, where `w` is the radial
+        // scaling function and `g` does not depend on the radial scaling
+        // parameters. This gives analytical expressions for the derivatives
+        // with respect to these parameters.
+        let (scale, rate, exponent) = (1.5, 0.8, 2);
+        let mut calculator = Calculator::new("spherical_expansion", format!(r#"{{
+            "cutoff": 3.5,
+            "max_radial": 4,
+            "max_angular": 3,
+            "atomic_gaussian_width": 0.3,
+            "gradients": false,
+            "radial_basis": {{"Gto": {{}}}},
+            "cutoff_function": {{"ShiftedCosine": {{"width": 0.5}}}},
+            "radial_scaling": {{"Willatt2018": {{"scale": {}, "rate": {}, "exponent": {}}}}}
+        }}"#, scale, rate, exponent)).unwrap();
+
+        let distance = 1.2;
+        let mut system = SimpleSystem::new(UnitCell::infinite());
+        system.add_atom(6, Vector3D::new(0.0, 0.0, 0.0));
+        system.add_atom(1, Vector3D::new(0.0, 0.0, distance));
+        let mut systems = vec![Box::new(system) as Box<dyn System>];
+
+        let options = CalculationOptions {
+            hyper_gradients: true,
+            ..Default::default()
+        };
+        let mut descriptor = crate::Descriptor::new();
+        calculator.compute(&mut systems, &mut descriptor, options).unwrap();
+
+        assert_eq!(descriptor.hyper_parameters, [
+            "/atomic_gaussian_width",
+            "/cutoff_function/ShiftedCosine/width",
+            "/radial_scaling/Willatt2018/scale",
+            "/radial_scaling/Willatt2018/rate",
+        ]);
+
+        let v = IndexValue::from;
+        let sample = descriptor.samples.position(&[v(0), v(0), v(6), v(1)]).unwrap();
+        let values = descriptor.values.index_axis(Axis(0), sample);
+        let hyper_gradients = descriptor.hyper_gradients.as_ref().unwrap();
+
+        // the pair is inside the cutoff function plateau
+        let width_gradient = hyper_gradients.index_axis(Axis(0), 1);
+        approx::assert_abs_diff_eq!(width_gradient.index_axis(Axis(0), sample), ndarray::Array1::zeros(values.len()), epsilon = 1e-9);
+
+        // w(r) = c / (c + x) with x = (r / r0)^m
+        let x = f64::powi(distance / scale, exponent);
+        let scale_factor = (exponent as f64) * x / (scale * (rate + x));
+        let rate_factor = x / (rate * (rate + x));
+
+        let scale_gradient = hyper_gradients.index_axis(Axis(0), 2);
+        approx::assert_relative_eq!(
+            scale_gradient.index_axis(Axis(0), sample), &values * scale_factor,
+            max_relative = 1e-6, epsilon = 1e-10
+        );
+
+        let rate_gradient = hyper_gradients.index_axis(Axis(0), 3);
+        approx::assert_relative_eq!(
+            rate_gradient.index_axis(Axis(0), sample), &values * rate_factor,
+            max_relative = 1e-6, epsilon = 1e-10
+        );
+    }
+
+    #[test]
+    fn lazy_gradients() {
+        let parameters = |gradients: bool| format!(r#"{{
+            "cutoff": 3.5,
+            "delta": 9,
+            "name": "",
+            "gradients": {}
+        }}"#, gradients);
+
+        let mut calculator = Calculator::new("dummy_calculator", parameters(true)).unwrap();
+        let mut systems = crate::systems::test_utils::test_systems(&["water", "methane"]);
+
+        let mut expected = crate::Descriptor::new();
+        calculator.compute(&mut systems, &mut expected, Default::default()).unwrap();
+
+        let options = CalculationOptions {
+            lazy_gradients: true,
+            ..Default::default()
+        };
+        let mut descriptor = crate::Descriptor::new();
+        calculator.compute(&mut systems, &mut descriptor, options).unwrap();
+
+        assert_eq!(descriptor.samples, expected.samples);
+        assert_eq!(descriptor.values, expected.values);
+        assert!(descriptor.gradients.is_none());
+        assert!(descriptor.gradients_samples.is_none());
+
+        let gradients = descriptor.gradients().unwrap().expect("missing gradients").clone();
+        assert_eq!(&gradients, expected.gradients.as_ref().unwrap());
+        assert_eq!(descriptor.gradients_samples, expected.gradients_samples);
+
+        let mut calculator = Calculator::new("dummy_calculator", parameters(false)).unwrap();
+        let options = CalculationOptions {
+            lazy_gradients: true,
+            ..Default::default()
+        };
+        let error = calculator.compute(&mut systems, &mut descriptor, options).unwrap_err();
+        assert_eq!(error.to_string(), "invalid parameter: lazy gradients require a calculator computing gradients, but calculator 'dummy_calculator' does not");
+    }
+
+    #[test]
+    fn lazy_gradients_cached() {
+        let mut calculator = Calculator::new("spherical_expansion", r#"{
+            "cutoff": 3.5,
+            "max_radial": 3,
+            "max_angular": 4,
+            "atomic_gaussian_width": 0.4,
+            "radial_basis": {"Gto": {}},
+            "cutoff_function": {"ShiftedCosine": {"width": 0.5}},
+            "radial_scaling": {"None": {}},
+            "gradients": true
+        }"#.to_owned()).unwrap();
+        let mut systems = crate::systems::test_utils::test_systems(&["water", "methane"]);
+
+        let mut expected = crate::Descriptor::new();
+        calculator.compute(&mut systems, &mut expected, Default::default()).unwrap();
+
+        let options = || CalculationOptions {
+            lazy_gradients: true,
+            ..Default::default()
+        };
+        let mut descriptor = crate::Descriptor::new();
+        calculator.compute(&mut systems, &mut descriptor, options()).unwrap();
+
+        assert_eq!(descriptor.samples, expected.samples);
+        for (value, expected) in descriptor.values.iter().zip(expected.values.iter()) {
+            assert_relative_eq!(value, expected, epsilon=1e-12, max_relative=1e-9);
+        }
+        assert!(descriptor.gradients.is_none());
+
+        let gradients = descriptor.gradients().unwrap().expect("missing gradients").clone();
+        assert_eq!(descriptor.gradients_samples, expected.gradients_samples);
+        for (value, expected) in gradients.iter().zip(expected.gradients.as_ref().unwrap().iter()) {
+            assert_relative_eq!(value, expected, epsilon=1e-12, max_relative=1e-9);
+        }
+
+        // gradients are computed before densification
+        let mut descriptor = crate::Descriptor::new();
+        calculator.compute(&mut systems, &mut descriptor, options()).unwrap();
+        descriptor.densify(&["species_neighbor"], None).unwrap();
+        expected.densify(&["species_neighbor"], None).unwrap();
+
+        assert_eq!(descriptor.samples, expected.samples);
+        assert_eq!(descriptor.features, expected.features);
+        assert_eq!(descriptor.gradients_samples, expected.gradients_samples);
+        let gradients = descriptor.gradients().unwrap().expect("missing gradients");
+        for (value, expected) in gradients.iter().zip(expected.gradients.as_ref().unwrap().iter()) {
+            assert_relative_eq!(value, expected, epsilon=1e-12, max_relative=1e-9);
+        }
+
+        // gradients are computed before saving or serializing the descriptor
+        let mut descriptor = crate::Descriptor::new();
+        calculator.compute(&mut systems, &mut descriptor, options()).unwrap();
+        let serialized = serde_json::to_string(&descriptor).unwrap();
+        assert!(descriptor.pending_gradients.is_some());
+
+        let mut buffer = Vec::new();
+        descriptor.save(&mut buffer).unwrap();
+        assert_eq!(buffer, serialized.as_bytes());
+
+        let loaded = crate::Descriptor::load(&*buffer).unwrap();
+        let gradients = descriptor.gradients().unwrap().expect("missing gradients");
+        assert_eq!(loaded.gradients.as_ref(), Some(gradients));
+        assert_eq!(loaded.gradients_samples, descriptor.gradients_samples);
+    }
+
+    #[test]
+    fn compute_single_precision() {
+        let mut calculator = Calculator::new("dummy_calculator", r#"{
+            "cutoff": 3.5,
+            "delta": 9,
+            "name": "",
+            "gradients": true
+        }"#.to_owned()).unwrap();
+        // enough systems to run the calculation in multiple chunks
+        let names = ["water", "methane"].iter().copied().cycle().take(2000).collect::<Vec<_>>();
+        let mut systems = crate::systems::test_utils::test_systems(&names);
+
+        let mut expected = crate::Descriptor::new();
+        calculator.compute(&mut systems, &mut expected, Default::default()).unwrap();
+        assert!(expected.samples.count() > super::SINGLE_PRECISION_CHUNK_SAMPLES);
+
+        let descriptor = calculator.compute_single_precision(&mut systems, Default::default()).unwrap();
+        assert_eq!(descriptor.samples, expected.samples);
+        assert_eq!(descriptor.features, expected.features);
+        assert_eq!(descriptor.values, expected.values.mapv(|v| v as f32));
+        assert_eq!(descriptor.gradients_samples, expected.gradients_samples);
+        assert_eq!(descriptor.gradients.unwrap(), expected.gradients.unwrap().mapv(|v| v as f32));
+
+        // selected samples are shifted to each chunk
+        let mut selected = IndexesBuilder::new(vec!["structure"]);
+        selected.add(&[IndexValue::from(3)]);
+        selected.add(&[IndexValue::from(1998)]);
+        let options = CalculationOptions {
+            selected_samples: SelectedIndexes::Subset(selected.finish()),
+            ..Default::default()
+        };
+        let descriptor = calculator.compute_single_precision(&mut systems, options).unwrap();
+        assert_eq!(descriptor.samples.count(), 8);
+        assert_eq!(descriptor.samples[0], [IndexValue::from(3), IndexValue::from(0)]);
+        assert_eq!(descriptor.samples[7], [IndexValue::from(1998), IndexValue::from(2)]);
+        let position = expected.samples.position(&descriptor.samples[7]).unwrap();
+        assert_eq!(descriptor.values.row(7), expected.values.row(position).mapv(|v| v as f32));
+
+        let options = CalculationOptions {
+            lazy_gradients: true,
+            ..Default::default()
+        };
+        let error = calculator.compute_single_precision(&mut systems, options).unwrap_err();
+        assert_eq!(error.to_string(), "invalid parameter: hyper_gradients and lazy_gradients can not be used with single precision calculations");
+    }
+
+    #[test]
+    fn compute_blocks() {
+        let mut calculator = Calculator::new("spherical_expansion", r#"{
+            "cutoff": 3.5,
+            "max_radial": 3,
+            "max_angular": 4,
+            "atomic_gaussian_width": 0.4,
+            "radial_basis": {"Gto": {}},
+            "cutoff_function": {"ShiftedCosine": {"width": 0.5}},
+            "radial_scaling": {"None": {}},
+            "gradients": true
+        }"#.to_owned()).unwrap();
+        let mut systems = crate::systems::test_utils::test_systems(&["water", "methane"]);
+
+        let mut descriptor = crate::Descriptor::new();
+        calculator.compute(&mut systems, &mut descriptor, Default::default()).unwrap();
+        let variables = ["species_center", "species_neighbor"];
+        let expected = descriptor.into_blocks(&variables).unwrap();
+
+        let blocks = calculator.compute_blocks(&mut systems, &variables, Default::default()).unwrap();
+        assert_eq!(blocks.variables(), variables);
+        assert_eq!(blocks.features(), expected.features());
+        assert_eq!(blocks.blocks().len(), expected.blocks().len());
+        for (block, expected) in blocks.blocks().iter().zip(expected.blocks()) {
+            assert_eq!(block.key, expected.key);
+            assert_eq!(block.samples, expected.samples);
+            assert_relative_eq!(block.values, expected.values, max_relative=1e-12);
+            assert_eq!(block.gradients_samples, expected.gradients_samples);
+            assert_relative_eq!(
+                block.gradients.as_ref().unwrap(),
+                expected.gradients.as_ref().unwrap(),
+                max_relative=1e-12
+            );
+        }
+
+        let error = calculator.compute_blocks(&mut systems, &["foo"], Default::default()).unwrap_err();
+        assert_eq!(error.to_string(), "invalid parameter: can not split along 'foo' which is not present in the samples: [structure, center, species_center, species_neighbor]");
+    }
+
+    #[test]
+    fn check_finite() {
+        let mut calculator = Calculator::new("dummy_calculator", r#"{
+            "cutoff": 1.0,
+            "delta": 0,
+            "name": "",
+            "gradients": false
+        }"#.to_owned()).unwrap();
+
+        // the dummy calculator sums the positions, which overflows here
+        let mut system = SimpleSystem::new(UnitCell::infinite());
+        system.add_atom(1, Vector3D::new(0.0, 0.0, 0.0));
+        system.add_atom(1, Vector3D::new(1e308, 1e308, 0.0));
+        let mut systems = vec![Box::new(system) as Box<dyn System>];
+
+        let mut descriptor = crate::Descriptor::new();
+        calculator.compute(&mut systems, &mut descriptor, Default::default()).unwrap();
+        assert!(descriptor.values[[1, 1]].is_infinite());
+
+        let options = CalculationOptions {
+            check_finite: true,
+            ..Default::default()
+        };
+        let error = calculator.compute(&mut systems, &mut descriptor, options).unwrap_err();
+        assert_eq!(error.to_string(),
+            "non-finite data in the output of calculator 'dummy_calculator': \
+            internal error: got inf in values for sample (structure: 0, center: 1) \
+            and feature (index_delta: 0, x_y_z: 1)"
+        );
+    }
+
+    #[test]
+    fn cancellation() {
+        let mut calculator = Calculator::new("soap_power_spectrum", r#"{
+            "cutoff": 3.0,
+            "max_radial": 4,
+            "max_angular": 3,
+            "atomic_gaussian_width": 0.3,
+            "gradients": false,
+            "radial_basis": {"Gto": {}},
+            "cutoff_function": {"ShiftedCosine": {"width": 0.5}}
+        }"#.to_owned()).unwrap();
+        let mut systems = crate::systems::test_utils::test_systems(&["water", "methane"]);
+
+        let handle = calculator.cancellation_handle();
+        handle.cancel();
+
+        let mut descriptor = crate::Descriptor::new();
+        let error = calculator.compute(&mut systems, &mut descriptor, Default::default()).unwrap_err();
+        assert!(matches!(error, crate::Error::Cancelled));
+        assert_eq!(error.to_string(), "the calculation was cancelled");
+
+        // the cancellation request is consumed by the cancelled calculation
+        calculator.compute(&mut systems, &mut descriptor, Default::default()).unwrap();
+        assert_eq!(descriptor.samples.count(), 24);
+    }
+
+    #[test]
+    fn capabilities() {
+        let names = Calculator::registered_names();
+        assert_eq!(names, ["angular_distribution", "bond_environment", "composition", "dummy_calculator", "electrostatic_potential", "graph_moments", "lambda_soap", "pipeline", "radial_distribution", "soap_power_spectrum", "sorted_distances", "spherical_expansion", "voronoi"]);
+
+        let calculator = Calculator::new("sorted_distances", r#"{
+            "cutoff": 3.5,
+            "max_neighbors": 5
+        }"#.to_owned()).unwrap();
+        assert!(!calculator.supports_gradients());
+
+        let calculator = Calculator::new("dummy_calculator", r#"{
+            "cutoff": 1.0,
+            "delta": 9,
+            "name": "",
+            "gradients": false
+        }"#.to_owned()).unwrap();
+        assert!(calculator.supports_gradients());
+        assert!(!calculator.gradients());
+    }
+
+    #[test]
+    fn registered_metadata() {
+        for name in Calculator::registered_names() {
+            let schema = Calculator::parameters_schema(name).unwrap();
+            let schema = serde_json::from_str::<serde_json::Value>(&schema).unwrap();
+            assert!(schema["$schema"].is_string());
+        }
+
+        let schema = Calculator::parameters_schema("sorted_distances").unwrap();
+        let schema = serde_json::from_str::<serde_json::Value>(&schema).unwrap();
+        assert_eq!(schema["title"], "SortedDistances");
+        assert!(schema["properties"]["cutoff"].is_object());
+        assert!(schema["properties"]["max_neighbors"].is_object());
+
+        assert!(!Calculator::registered_supports_gradients("sorted_distances").unwrap());
+        assert!(Calculator::registered_supports_gradients("dummy_calculator").unwrap());
+        assert!(Calculator::registered_supports_gradients("spherical_expansion").unwrap());
+
+        let error = Calculator::parameters_schema("not there").unwrap_err();
+        assert_eq!(error.to_string(), "invalid parameter: unknown calculator with name 'not there'");
+        assert!(Calculator::registered_supports_gradients("not there").is_err());
+    }
+
+    #[test]
+    fn install() {
+        let global = rayon::current_num_threads();
+        {
+            let _guard = super::ParallelContextGuard::new(Some(3), Parallelism::Systems, false).unwrap();
+            assert_eq!(super::install(rayon::current_num_threads), 3);
+            assert_eq!(super::parallelism(), Parallelism::Systems);
+            {
+                let _guard = super::ParallelContextGuard::new(Some(2), Parallelism::Samples, false).unwrap();
+                assert_eq!(super::install(rayon::current_num_threads), 2);
+                assert_eq!(super::parallelism(), Parallelism::Samples);
+            }
+            {
+                // nested calculations re-use the enclosing thread pool
+                let _guard = super::ParallelContextGuard::new(None, Parallelism::Samples, false).unwrap();
+                assert_eq!(super::install(rayon::current_num_threads), 3);
+            }
+            assert_eq!(super::install(rayon::current_num_threads), 3);
+            assert_eq!(super::parallelism(), Parallelism::Systems);
+        }
+        assert_eq!(super::install(rayon::current_num_threads), global);
+        assert_eq!(super::parallelism(), Parallelism::Samples);
+    }
+
+    #[test]
+    fn workspace() {
+        let workspace = super::Workspace::default();
+
+        let mut buffer = workspace.take::<Vec<f64>>();
+        assert!(buffer.is_empty());
+        buffer.resize(100, 1.0);
+        let pointer = buffer.as_ptr();
+        std::mem::drop(buffer);
+
+        // the allocation is re-used
+        let buffer = workspace.take::<Vec<f64>>();
+        assert_eq!(buffer.as_ptr(), pointer);
+        assert_eq!(buffer.len(), 100);
+
+        // taking a buffer which was not given back creates a new one
+        assert!(workspace.take::<Vec<f64>>().is_empty());
+
+        // buffers of different types are stored separately
+        std::mem::drop(buffer);
+        assert!(workspace.take::<Vec<usize>>().is_empty());
+        assert_eq!(workspace.take::<Vec<f64>>().len(), 100);
+
+        // buffers are given back when returning early
+        let failing = |fail: bool| if fail { Err(()) } else { Ok(()) };
+        let early_return = || -> Result<(), ()> {
+            let mut buffer = workspace.take::<Vec<usize>>();
+            buffer.resize(20, 0);
+            failing(true)?;
+            buffer.clear();
+            Ok(())
+        };
+        assert!(early_return().is_err());
+        assert_eq!(workspace.take::<Vec<usize>>().len(), 20);
+    }
 }
@@ -0,0 +1,375 @@
+use std::collections::BTreeMap;
+
+use super::CalculatorBase;
+use super::soap::CutoffFunction;
+
+use crate::descriptor::{Indexes, IndexesBuilder, IndexValue};
+use crate::descriptor::{SamplesBuilder, ThreeBodiesSpeciesSamples};
+use crate::{Descriptor, Error, ErrorContext, System, Vector3D};
+
+/// Parameters for the smoothed angular distribution calculator
+#[derive(Debug, Clone)]
+#[derive(serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+pub struct AngularDistributionParameters {
+    /// Spherical cutoff to use for atomic environments
+    pub cutoff: f64,
+    /// Number of points in the angular grid. The points are at the center of
+    /// `n_points` bins of equal size covering `[0, π]`.
+    pub n_points: usize,
+    /// Width (in radians) of the gaussian used to broaden each bond angle
+    pub smearing: f64,
+    /// Cutoff function used to smooth the behavior around the cutoff radius
+    pub cutoff_function: CutoffFunction,
+    /// Should we also compute gradients of the feature?
+    pub gradients: bool,
+}
+
+/// Smoothed distribution of bond angles around each atom.
+///
+/// Each pair of distinct neighbors `j` and `k` of a center `i` within the
+/// spherical cutoff contributes a normalized gaussian of width `smearing`
+/// centered on the angle `θ_jik`, multiplied by the cutoff function of both
+/// distances `r_ij` and `r_ik`. The features are the values of the resulting
+/// function on a regular grid of `n_points` points, separately for each pair of
+/// neighbor species.
+#[derive(Debug, Clone)]
+pub struct AngularDistribution {
+    parameters: AngularDistributionParameters,
+    /// Positions of the grid points
+    points: Vec<f64>,
+}
+
+impl AngularDistribution {
+    /// Create a new `AngularDistribution` calculator with the given parameters
+    pub fn new(parameters: AngularDistributionParameters) -> Result<AngularDistribution, Error> {
+        parameters.cutoff_function.validate()?;
+
+        if !(parameters.cutoff > 0.0 && parameters.cutoff.is_finite()) {
+            return Err(Error::InvalidParameter(format!(
+                "cutoff must be a positive number for the angular distribution, got {}",
+                parameters.cutoff
+            )));
+        }
+
+        if !(parameters.smearing > 0.0 && parameters.smearing.is_finite()) {
+            return Err(Error::InvalidParameter(format!(
+                "smearing must be a positive number for the angular distribution, got {}",
+                parameters.smearing
+            )));
+        }
+
+        if parameters.n_points == 0 {
+            return Err(Error::InvalidParameter(
+                "n_points must be at least 1 for the angular distribution".into()
+            ));
+        }
+
+        let delta = std::f64::consts::PI / parameters.n_points as f64;
+        let points = (0..parameters.n_points)
+            .map(|i| (i as f64 + 0.5) * delta)
+            .collect();
+
+        return Ok(AngularDistribution {
+            parameters: parameters,
+            points: points,
+        });
+    }
+}
+
+/// A neighbor of a given center, with the vector going from the center to
+/// the neighbor
+struct Neighbor {
+    atom: usize,
+    vector: Vector3D,
+    distance: f64,
+}
+
+/// Temporary buffers used by `AngularDistribution::compute`, kept in the
+/// calculator workspace between calls
+#[derive(Default)]
+struct Buffers {
+    values: Vec<f64>,
+    derivatives: Vec<f64>,
+    neighbors: Vec<Neighbor>,
+}
+
+impl CalculatorBase for AngularDistribution {
+    fn name(&self) -> String {
+        "angular distribution".into()
+    }
+
+    fn get_parameters(&self) -> String {
+        serde_json::to_string(&self.parameters).expect("failed to serialize to JSON")
+    }
+
+    fn features_names(&self) -> Vec<&str> {
+        vec!["point"]
+    }
+
+    fn features(&self) -> Indexes {
+        let mut features = IndexesBuilder::new(self.features_names());
+        for point in 0..self.parameters.n_points {
+            features.add(&[IndexValue::from(point)]);
+        }
+        return features.finish();
+    }
+
+    fn samples_builder(&self) -> Box<dyn SamplesBuilder> {
+        Box::new(ThreeBodiesSpeciesSamples::new(self.parameters.cutoff))
+    }
+
+    fn compute_gradients(&self) -> bool {
+        self.parameters.gradients
+    }
+
+    fn supports_gradients(&self) -> bool {
+        true
+    }
+
+    fn check_features(&self, indexes: &Indexes) -> Result<(), Error> {
+        assert_eq!(indexes.names(), self.features_names());
+        for value in indexes.iter() {
+            if value[0].usize() >= self.parameters.n_points {
+                return Err(Error::InvalidParameter(format!(
+                    "point index is too large for this angular distribution: \
+                    got {}, expected value lower than {}", value[0].usize(), self.parameters.n_points
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    #[time_graph::instrument(name = "AngularDistribution::compute")]
+    fn compute(&mut self, systems: &mut [Box<dyn System>], descriptor: &mut Descriptor) -> Result<(), Error> {
+        assert_eq!(descriptor.samples.names(), &["structure", "center", "species_center", "species_neighbor_1", "species_neighbor_2"]);
+        assert_eq!(descriptor.features.names(), &["point"]);
+
+        let cutoff = self.parameters.cutoff;
+        let sigma = self.parameters.smearing;
+        let normalization = 1.0 / (f64::sqrt(2.0 * std::f64::consts::PI) * sigma);
+
+        let points = descriptor.features.iter()
+            .map(|feature| self.points[feature[0].usize()])
+            .collect::<Vec<_>>();
+
+        // all requested samples, grouped by (structure, center)
+        let mut samples = BTreeMap::new();
+        for (sample_i, sample) in descriptor.samples.iter().enumerate() {
+            let species_1 = sample[3].i32();
+            let species_2 = sample[4].i32();
+            samples.entry((sample[0].usize(), sample[1].usize()))
+                .or_insert_with(BTreeMap::new)
+                .insert((species_1, species_2), sample_i);
+        }
+
+        // first row of the gradients for each (sample, atom)
+        let mut gradient_rows = BTreeMap::new();
+        if let Some(ref gradients_samples) = descriptor.gradients_samples {
+            for (row, gradient_sample) in gradients_samples.iter().enumerate() {
+                if gradient_sample[2].usize() == 0 {
+                    gradient_rows.insert((gradient_sample[0].usize(), gradient_sample[1].usize()), row);
+                }
+            }
+        }
+
+        let workspace = crate::calculator::workspace();
+        let mut buffers = workspace.take::<Buffers>();
+        let Buffers { values, derivatives, neighbors } = &mut *buffers;
+        values.clear();
+        values.resize(points.len(), 0.0);
+        derivatives.clear();
+        derivatives.resize(points.len(), 0.0);
+
+        for (i_system, system) in systems.iter_mut().enumerate() {
+            crate::calculator::check_cancelled()?;
+            system.compute_neighbors(cutoff).with_context(|| format!(
+                "failed to compute the neighbors list for structure {}", i_system
+            ))?;
+
+            let species = system.species()?;
+            for center in 0..system.size()? {
+                let center_samples = match samples.get(&(i_system, center)) {
+                    Some(center_samples) => center_samples,
+                    None => continue,
+                };
+
+                neighbors.clear();
+                neighbors.extend(system.pairs_containing(center)?.iter()
+                    // pairs between an atom and its own periodic images do
+                    // not have a well defined orientation in the neighbors
+                    // list, skip them
+                    .filter(|pair| pair.first != pair.second)
+                    .map(|pair| {
+                        if pair.first == center {
+                            Neighbor { atom: pair.second, vector: pair.vector, distance: pair.distance }
+                        } else {
+                            Neighbor { atom: pair.first, vector: -pair.vector, distance: pair.distance }
+                        }
+                    })
+                );
+
+                for (i_neighbor, neighbor_1) in neighbors.iter().enumerate() {
+                    for neighbor_2 in &neighbors[(i_neighbor + 1)..] {
+                        let (species_1, species_2) = (species[neighbor_1.atom], species[neighbor_2.atom]);
+                        let key = if species_1 < species_2 { (species_1, species_2) } else { (species_2, species_1) };
+                        let sample_i = match center_samples.get(&key) {
+                            Some(&sample_i) => sample_i,
+                            None => continue,
+                        };
+
+                        let u = neighbor_1.vector / neighbor_1.distance;
+                        let v = neighbor_2.vector / neighbor_2.distance;
+                        let cos_theta = f64::clamp(u * v, -1.0, 1.0);
+                        let theta = f64::acos(cos_theta);
+                        let sin_theta = f64::sqrt(1.0 - cos_theta * cos_theta);
+
+                        let fc_1 = self.parameters.cutoff_function.compute(neighbor_1.distance, cutoff);
+                        let fc_2 = self.parameters.cutoff_function.compute(neighbor_2.distance, cutoff);
+                        let weight = fc_1 * fc_2;
+
+                        for ((value, derivative), &point) in values.iter_mut().zip(derivatives.iter_mut()).zip(&points) {
+                            let delta = point - theta;
+                            let gaussian = normalization * f64::exp(-0.5 * delta * delta / (sigma * sigma));
+                            *value = gaussian;
+                            // derivative of the gaussian w.r.t. theta
+                            *derivative = gaussian * delta / (sigma * sigma);
+                        }
+
+                        let mut sample_values = descriptor.values.row_mut(sample_i);
+                        for (value, gaussian) in sample_values.iter_mut().zip(values.iter()) {
+                            *value += weight * gaussian;
+                        }
+
+                        if let Some(ref mut gradients) = descriptor.gradients {
+                            let dfc_1 = self.parameters.cutoff_function.derivative(neighbor_1.distance, cutoff);
+                            let dfc_2 = self.parameters.cutoff_function.derivative(neighbor_2.distance, cutoff);
+
+                            // gradients of the cutoff functions product w.r.t.
+                            // the two neighbor vectors
+                            let weight_grad_1 = dfc_1 * fc_2 * u;
+                            let weight_grad_2 = fc_1 * dfc_2 * v;
+
+                            // gradients of theta w.r.t. the two neighbor
+                            // vectors. The angle is not differentiable when the
+                            // three atoms are aligned, use a zero gradient in
+                            // this case.
+                            let (theta_grad_1, theta_grad_2) = if sin_theta > 1e-12 {
+                                (
+                                    -(v - cos_theta * u) / (sin_theta * neighbor_1.distance),
+                                    -(u - cos_theta * v) / (sin_theta * neighbor_2.distance),
+                                )
+                            } else {
+                                (Vector3D::zero(), Vector3D::zero())
+                            };
+
+                            let neighbor_grad_1 = [weight_grad_1, weight * theta_grad_1];
+                            let neighbor_grad_2 = [weight_grad_2, weight * theta_grad_2];
+                            let center_grad = [
+                                -(weight_grad_1 + weight_grad_2),
+                                -weight * (theta_grad_1 + theta_grad_2),
+                            ];
+
+                            let atoms = [
+                                (neighbor_1.atom, neighbor_grad_1),
+                                (neighbor_2.atom, neighbor_grad_2),
+                                (center, center_grad),
+                            ];
+                            for &(atom, [grad_weight, grad_theta]) in &atoms {
+                                let first_row = match gradient_rows.get(&(sample_i, atom)) {
+                                    Some(&row) => row,
+                                    None => continue,
+                                };
+
+                                for spatial in 0..3 {
+                                    let mut gradient = gradients.row_mut(first_row + spatial);
+                                    let iter = gradient.iter_mut().zip(values.iter()).zip(derivatives.iter());
+                                    for ((value, gaussian), derivative) in iter {
+                                        *value += grad_weight[spatial] * gaussian + grad_theta[spatial] * derivative;
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use crate::systems::test_utils::{test_system, test_systems};
+    use crate::{Calculator, Descriptor};
+    use crate::calculators::CalculatorBase;
+    use crate::calculators::soap::CutoffFunction;
+
+    use super::{AngularDistribution, AngularDistributionParameters};
+
+    fn parameters(gradients: bool) -> AngularDistributionParameters {
+        AngularDistributionParameters {
+            cutoff: 3.5,
+            n_points: 36,
+            smearing: 0.1,
+            cutoff_function: CutoffFunction::ShiftedCosine { width: 0.5 },
+            gradients: gradients,
+        }
+    }
+
+    fn calculator(parameters: AngularDistributionParameters) -> Calculator {
+        Calculator::from(Box::new(
+            AngularDistribution::new(parameters).unwrap()
+        ) as Box<dyn CalculatorBase>)
+    }
+
+    #[test]
+    fn values() {
+        let mut systems = test_systems(&["water"]);
+        let mut descriptor = Descriptor::new();
+        calculator(parameters(false)).compute(&mut systems, &mut descriptor, Default::default()).unwrap();
+
+        assert_eq!(descriptor.samples.names(), ["structure", "center", "species_center", "species_neighbor_1", "species_neighbor_2"]);
+        assert_eq!(descriptor.features.count(), 36);
+
+        // the integral of the distribution is the number of angles
+        let delta = std::f64::consts::PI / 36.0;
+        for (sample_i, sample) in descriptor.samples.iter().enumerate() {
+            let expected = match (sample[2].i32(), sample[3].i32(), sample[4].i32()) {
+                // H-O-H angle, and O-H-H angles
+                (123456, 1, 1) | (1, 1, 123456) => 1.0,
+                // samples with a single neighbor of the corresponding species
+                // do not contain any angle
+                (1, 1, 1) | (1, 123456, 123456) => 0.0,
+                _ => panic!("unexpected sample {:?}", sample),
+            };
+
+            let integral = descriptor.values.row(sample_i).sum() * delta;
+            assert_relative_eq!(integral, expected, max_relative = 1e-6, epsilon = 1e-12);
+        }
+
+        // the maximal value for the H-O-H angle is at the point closest to
+        // 104.1°
+        let sample_i = descriptor.samples.iter().position(|sample| sample[2].i32() == 123456).unwrap();
+        let row = descriptor.values.row(sample_i);
+        let max_point = (0..36).max_by(|&a, &b| row[a].partial_cmp(&row[b]).unwrap()).unwrap();
+        assert_eq!(max_point, 20);
+    }
+
+    #[test]
+    fn finite_differences() {
+        let calculator = calculator(parameters(true));
+        let system = test_system("water");
+        crate::calculators::tests_utils::finite_difference(calculator, system);
+    }
+
+    #[test]
+    fn invalid_parameters() {
+        let mut parameters = parameters(false);
+        parameters.smearing = -1.0;
+        let error = AngularDistribution::new(parameters).unwrap_err();
+        assert_eq!(error.to_string(), "invalid parameter: smearing must be a positive number for the angular distribution, got -1");
+    }
+}
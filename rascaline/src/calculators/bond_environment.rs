@@ -0,0 +1,455 @@
+use std::collections::BTreeMap;
+
+use super::CalculatorBase;
+use super::soap::CutoffFunction;
+
+use crate::descriptor::{Indexes, IndexesBuilder, IndexValue};
+use crate::descriptor::{SamplesBuilder, PairSamples};
+use crate::systems::UnitCell;
+use crate::{Descriptor, Error, ErrorContext, System, Vector3D};
+
+/// Parameters for the bond environment calculator
+#[derive(Debug, Clone)]
+#[derive(serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+pub struct BondEnvironmentParameters {
+    /// Pairs of atoms closer than this cutoff are considered to be bonded, and
+    /// will be included in the samples
+    pub bond_cutoff: f64,
+    /// Spherical cutoff around the middle of the bond to use for bond
+    /// environments
+    pub cutoff: f64,
+    /// Number of points in the radial grid. The points are at the center of
+    /// `n_points` bins of equal size covering `[0, cutoff]`.
+    pub n_points: usize,
+    /// Width of the gaussian used to broaden each distance
+    pub smearing: f64,
+    /// Cutoff function used to smooth the behavior around both the bond cutoff
+    /// and the environment cutoff
+    pub cutoff_function: CutoffFunction,
+    /// Should we also compute gradients of the feature?
+    pub gradients: bool,
+}
+
+/// Smoothed radial distribution of atoms around the middle of each bond.
+///
+/// The samples are all pairs of atoms `i` and `j` closer than `bond_cutoff`.
+/// Each atom `k` (including `i` and `j`) within the spherical cutoff around the
+/// middle of the bond contributes a normalized gaussian of width `smearing`
+/// centered on the distance between `k` and the middle of the bond, multiplied
+/// by the cutoff function. The resulting function is multiplied by the cutoff
+/// function of the bond length `r_ij` (using `bond_cutoff`), making the
+/// features go smoothly to zero when the bond breaks. The features are the
+/// values of this function on a regular grid of `n_points` points.
+#[derive(Debug, Clone)]
+pub struct BondEnvironment {
+    parameters: BondEnvironmentParameters,
+    /// Positions of the grid points
+    points: Vec<f64>,
+}
+
+impl BondEnvironment {
+    /// Create a new `BondEnvironment` calculator with the given parameters
+    pub fn new(parameters: BondEnvironmentParameters) -> Result<BondEnvironment, Error> {
+        parameters.cutoff_function.validate()?;
+
+        if !(parameters.bond_cutoff > 0.0 && parameters.bond_cutoff.is_finite()) {
+            return Err(Error::InvalidParameter(format!(
+                "bond_cutoff must be a positive number for the bond environment, got {}",
+                parameters.bond_cutoff
+            )));
+        }
+
+        if !(parameters.cutoff > 0.0 && parameters.cutoff.is_finite()) {
+            return Err(Error::InvalidParameter(format!(
+                "cutoff must be a positive number for the bond environment, got {}",
+                parameters.cutoff
+            )));
+        }
+
+        if !(parameters.smearing > 0.0 && parameters.smearing.is_finite()) {
+            return Err(Error::InvalidParameter(format!(
+                "smearing must be a positive number for the bond environment, got {}",
+                parameters.smearing
+            )));
+        }
+
+        if parameters.n_points == 0 {
+            return Err(Error::InvalidParameter(
+                "n_points must be at least 1 for the bond environment".into()
+            ));
+        }
+
+        let delta = parameters.cutoff / parameters.n_points as f64;
+        let points = (0..parameters.n_points)
+            .map(|i| (i as f64 + 0.5) * delta)
+            .collect();
+
+        return Ok(BondEnvironment {
+            parameters: parameters,
+            points: points,
+        });
+    }
+
+    /// Cutoff to use for the neighbors list around the first atom in the bond,
+    /// such that all atoms within `cutoff` of the middle of the bond are
+    /// included.
+    fn neighbors_cutoff(&self) -> f64 {
+        self.parameters.cutoff + 0.5 * self.parameters.bond_cutoff
+    }
+}
+
+/// Get all the lattice vectors of the `cell` shorter than `cutoff`, excluding
+/// the zero vector. These correspond to the periodic images of an atom, which
+/// do not have a well defined orientation in the neighbors list.
+fn lattice_vectors(cell: &UnitCell, cutoff: f64) -> Vec<Vector3D> {
+    let mut vectors = Vec::new();
+    if cell.is_infinite() {
+        return vectors;
+    }
+
+    let matrix = cell.matrix();
+    let distances = cell.distances_between_faces();
+    let max_n = [
+        (cutoff / distances[0]) as i32,
+        (cutoff / distances[1]) as i32,
+        (cutoff / distances[2]) as i32,
+    ];
+
+    for n1 in -max_n[0]..=max_n[0] {
+        for n2 in -max_n[1]..=max_n[1] {
+            for n3 in -max_n[2]..=max_n[2] {
+                if n1 == 0 && n2 == 0 && n3 == 0 {
+                    continue;
+                }
+
+                let vector = n1 as f64 * Vector3D::from(matrix[0])
+                    + n2 as f64 * Vector3D::from(matrix[1])
+                    + n3 as f64 * Vector3D::from(matrix[2]);
+                if vector.norm() < cutoff {
+                    vectors.push(vector);
+                }
+            }
+        }
+    }
+
+    return vectors;
+}
+
+impl CalculatorBase for BondEnvironment {
+    fn name(&self) -> String {
+        "bond environment".into()
+    }
+
+    fn get_parameters(&self) -> String {
+        serde_json::to_string(&self.parameters).expect("failed to serialize to JSON")
+    }
+
+    fn features_names(&self) -> Vec<&str> {
+        vec!["point"]
+    }
+
+    fn features(&self) -> Indexes {
+        let mut features = IndexesBuilder::new(self.features_names());
+        for point in 0..self.parameters.n_points {
+            features.add(&[IndexValue::from(point)]);
+        }
+        return features.finish();
+    }
+
+    fn samples_builder(&self) -> Box<dyn SamplesBuilder> {
+        Box::new(PairSamples::new(self.parameters.bond_cutoff, self.neighbors_cutoff()))
+    }
+
+    fn compute_gradients(&self) -> bool {
+        self.parameters.gradients
+    }
+
+    fn supports_gradients(&self) -> bool {
+        true
+    }
+
+    fn check_features(&self, indexes: &Indexes) -> Result<(), Error> {
+        assert_eq!(indexes.names(), self.features_names());
+        for value in indexes.iter() {
+            if value[0].usize() >= self.parameters.n_points {
+                return Err(Error::InvalidParameter(format!(
+                    "point index is too large for this bond environment: \
+                    got {}, expected value lower than {}", value[0].usize(), self.parameters.n_points
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    #[time_graph::instrument(name = "BondEnvironment::compute")]
+    fn compute(&mut self, systems: &mut [Box<dyn System>], descriptor: &mut Descriptor) -> Result<(), Error> {
+        assert_eq!(descriptor.samples.names(), &["structure", "atom_1", "atom_2"]);
+        assert_eq!(descriptor.features.names(), &["point"]);
+
+        let bond_cutoff = self.parameters.bond_cutoff;
+        let cutoff = self.parameters.cutoff;
+        let sigma = self.parameters.smearing;
+        let normalization = 1.0 / (f64::sqrt(2.0 * std::f64::consts::PI) * sigma);
+
+        let points = descriptor.features.iter()
+            .map(|feature| self.points[feature[0].usize()])
+            .collect::<Vec<_>>();
+
+        // all requested samples, grouped by structure
+        let mut samples = BTreeMap::new();
+        for (sample_i, sample) in descriptor.samples.iter().enumerate() {
+            samples.entry(sample[0].usize())
+                .or_insert_with(Vec::new)
+                .push((sample_i, sample[1].usize(), sample[2].usize()));
+        }
+
+        // first row of the gradients for each (sample, atom)
+        let mut gradient_rows = BTreeMap::new();
+        if let Some(ref gradients_samples) = descriptor.gradients_samples {
+            for (row, gradient_sample) in gradients_samples.iter().enumerate() {
+                if gradient_sample[2].usize() == 0 {
+                    gradient_rows.insert((gradient_sample[0].usize(), gradient_sample[1].usize()), row);
+                }
+            }
+        }
+
+        let mut environment = vec![0.0; points.len()];
+        let mut values = vec![0.0; points.len()];
+        let mut derivatives = vec![0.0; points.len()];
+        for (i_system, system) in systems.iter_mut().enumerate() {
+            let system_samples = match samples.get(&i_system) {
+                Some(system_samples) => system_samples,
+                None => continue,
+            };
+
+            crate::calculator::check_cancelled()?;
+            system.compute_neighbors(self.neighbors_cutoff()).with_context(|| format!(
+                "failed to compute the neighbors list for structure {}", i_system
+            ))?;
+
+            let lattice_vectors = lattice_vectors(&system.cell()?, self.neighbors_cutoff());
+            for &(sample_i, atom_1, atom_2) in system_samples {
+                // all atoms around the first atom in the bond, as (atom,
+                // vector from the first atom to this one). Periodic images of
+                // the first atom are taken from the unit cell instead of the
+                // neighbors list.
+                let neighbors = system.pairs_containing(atom_1)?.iter()
+                    .filter(|pair| pair.first != pair.second)
+                    .map(|pair| {
+                        if pair.first == atom_1 {
+                            (pair.second, pair.vector)
+                        } else {
+                            (pair.first, -pair.vector)
+                        }
+                    })
+                    .chain(std::iter::once((atom_1, Vector3D::zero())))
+                    .chain(lattice_vectors.iter().map(|&vector| (atom_1, vector)))
+                    .collect::<Vec<_>>();
+
+                // use the closest periodic image of the second atom to define
+                // the bond
+                let bond = neighbors.iter()
+                    .filter(|&&(atom, _)| atom == atom_2)
+                    .map(|&(_, vector)| vector)
+                    .min_by(|a, b| a.norm2().partial_cmp(&b.norm2()).expect("got NaN distance"));
+                let bond = match bond {
+                    Some(bond) if bond.norm() < bond_cutoff => bond,
+                    _ => continue,
+                };
+
+                let bond_length = bond.norm();
+                let bond_weight = self.parameters.cutoff_function.compute(bond_length, bond_cutoff);
+                let middle = 0.5 * bond;
+
+                environment.fill(0.0);
+
+                for &(atom, vector) in &neighbors {
+                    let vector = vector - middle;
+                    let distance = vector.norm();
+                    if distance >= cutoff {
+                        continue;
+                    }
+
+                    let fc = self.parameters.cutoff_function.compute(distance, cutoff);
+                    let dfc = self.parameters.cutoff_function.derivative(distance, cutoff);
+                    for ((value, derivative), &point) in values.iter_mut().zip(&mut derivatives).zip(&points) {
+                        let delta = point - distance;
+                        let gaussian = normalization * f64::exp(-0.5 * delta * delta / (sigma * sigma));
+                        *value = fc * gaussian;
+                        // derivative of the contribution w.r.t. the distance
+                        *derivative = dfc * gaussian + fc * gaussian * delta / (sigma * sigma);
+                    }
+
+                    for (value, contribution) in environment.iter_mut().zip(&values) {
+                        *value += contribution;
+                    }
+
+                    if let Some(ref mut gradients) = descriptor.gradients {
+                        // the distance is taken between this atom and the
+                        // middle of the bond, which depends on the positions
+                        // of both atoms in the bond
+                        let direction = if distance > 1e-12 {
+                            vector / distance
+                        } else {
+                            Vector3D::zero()
+                        };
+
+                        let atoms = [(atom, 1.0), (atom_1, -0.5), (atom_2, -0.5)];
+                        for &(gradient_atom, factor) in &atoms {
+                            let first_row = match gradient_rows.get(&(sample_i, gradient_atom)) {
+                                Some(&row) => row,
+                                None => continue,
+                            };
+
+                            for spatial in 0..3 {
+                                let mut gradient = gradients.row_mut(first_row + spatial);
+                                let factor = bond_weight * factor * direction[spatial];
+                                for (value, derivative) in gradient.iter_mut().zip(&derivatives) {
+                                    *value += factor * derivative;
+                                }
+                            }
+                        }
+                    }
+                }
+
+                let mut sample_values = descriptor.values.row_mut(sample_i);
+                for (value, contribution) in sample_values.iter_mut().zip(&environment) {
+                    *value += bond_weight * contribution;
+                }
+
+                if let Some(ref mut gradients) = descriptor.gradients {
+                    // gradients of the bond cutoff function
+                    let bond_weight_grad = self.parameters.cutoff_function.derivative(bond_length, bond_cutoff);
+                    let direction = bond / bond_length;
+
+                    for &(gradient_atom, sign) in &[(atom_2, 1.0), (atom_1, -1.0)] {
+                        let first_row = match gradient_rows.get(&(sample_i, gradient_atom)) {
+                            Some(&row) => row,
+                            None => continue,
+                        };
+
+                        for spatial in 0..3 {
+                            let mut gradient = gradients.row_mut(first_row + spatial);
+                            let factor = sign * bond_weight_grad * direction[spatial];
+                            for (value, contribution) in gradient.iter_mut().zip(&environment) {
+                                *value += factor * contribution;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use crate::systems::test_utils::{test_system, test_systems};
+    use crate::systems::{SimpleSystem, UnitCell};
+    use crate::{Calculator, Descriptor, System, Vector3D};
+    use crate::calculators::CalculatorBase;
+    use crate::calculators::soap::CutoffFunction;
+    use crate::descriptor::{IndexesBuilder, IndexValue};
+
+    use super::{BondEnvironment, BondEnvironmentParameters};
+
+    fn parameters(gradients: bool) -> BondEnvironmentParameters {
+        BondEnvironmentParameters {
+            bond_cutoff: 1.2,
+            cutoff: 3.0,
+            n_points: 60,
+            smearing: 0.1,
+            cutoff_function: CutoffFunction::ShiftedCosine { width: 0.5 },
+            gradients: gradients,
+        }
+    }
+
+    fn calculator(parameters: BondEnvironmentParameters) -> Calculator {
+        Calculator::from(Box::new(
+            BondEnvironment::new(parameters).unwrap()
+        ) as Box<dyn CalculatorBase>)
+    }
+
+    #[test]
+    fn values() {
+        let mut systems = test_systems(&["water"]);
+        let mut descriptor = Descriptor::new();
+        calculator(parameters(false)).compute(&mut systems, &mut descriptor, Default::default()).unwrap();
+
+        assert_eq!(descriptor.samples.names(), ["structure", "atom_1", "atom_2"]);
+        assert_eq!(descriptor.samples.count(), 2);
+        assert_eq!(descriptor.features.count(), 60);
+
+        // the integral of the distribution is the number of atoms around the
+        // bond, weighted by the cutoff functions
+        let bond_cutoff = CutoffFunction::ShiftedCosine { width: 0.5 };
+        let bond_length = f64::sqrt(0.75545 * 0.75545 + 0.58895 * 0.58895);
+        let delta = 3.0 / 60.0;
+        for sample_i in 0..2 {
+            let integral = descriptor.values.row(sample_i).sum() * delta;
+            let expected = 3.0 * bond_cutoff.compute(bond_length, 1.2);
+            assert_relative_eq!(integral, expected, max_relative = 1e-6);
+        }
+
+        // the two O-H bonds are equivalent
+        let values = &descriptor.values;
+        assert_relative_eq!(values.row(0), values.row(1), max_relative = 1e-12);
+    }
+
+    #[test]
+    fn finite_differences() {
+        let system = test_system("water");
+        crate::calculators::tests_utils::finite_difference(calculator(parameters(true)), system);
+
+        let system = test_system("methane");
+        crate::calculators::tests_utils::finite_difference(calculator(parameters(true)), system);
+    }
+
+    #[test]
+    fn compute_partial() {
+        let calculator = calculator(parameters(false));
+        let mut systems = test_systems(&["methane"]);
+
+        let mut samples = IndexesBuilder::new(vec!["structure", "atom_1", "atom_2"]);
+        samples.add(&[IndexValue::from(0), IndexValue::from(0), IndexValue::from(3)]);
+        samples.add(&[IndexValue::from(0), IndexValue::from(0), IndexValue::from(1)]);
+
+        let mut features = IndexesBuilder::new(vec!["point"]);
+        features.add(&[IndexValue::from(2)]);
+        features.add(&[IndexValue::from(12)]);
+        features.add(&[IndexValue::from(7)]);
+
+        crate::calculators::tests_utils::compute_partial(
+            calculator, &mut systems, samples.finish(), features.finish()
+        );
+    }
+
+    #[test]
+    fn periodic_images() {
+        // in a small cell, atoms are bonded to their own periodic images, but
+        // these pairs are not included in the samples
+        let mut system = SimpleSystem::new(UnitCell::cubic(1.0));
+        system.add_atom(1, Vector3D::new(0.0, 0.0, 0.0));
+        system.add_atom(1, Vector3D::new(0.5, 0.0, 0.0));
+
+        let mut descriptor = Descriptor::new();
+        let mut systems = vec![Box::new(system) as Box<dyn System>];
+        calculator(parameters(false)).compute(&mut systems, &mut descriptor, Default::default()).unwrap();
+
+        let samples = descriptor.samples.iter().collect::<Vec<_>>();
+        assert_eq!(samples, [[IndexValue::from(0), IndexValue::from(0), IndexValue::from(1)]]);
+        assert!(descriptor.values.iter().all(|v| v.is_finite()));
+    }
+
+    #[test]
+    fn invalid_parameters() {
+        let mut parameters = parameters(false);
+        parameters.bond_cutoff = -1.0;
+        let error = BondEnvironment::new(parameters).unwrap_err();
+        assert_eq!(error.to_string(), "invalid parameter: bond_cutoff must be a positive number for the bond environment, got -1");
+    }
+}
@@ -0,0 +1,132 @@
+use super::CalculatorBase;
+
+use crate::descriptor::{Indexes, IndexesBuilder, IndexValue};
+use crate::descriptor::{SamplesBuilder, StructureSpeciesSamples, AtomSpeciesSamples};
+use crate::{Descriptor, Error, System};
+
+#[derive(Debug, Clone)]
+#[derive(serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+/// Chemical composition of the structures.
+///
+/// With `per_structure`, each structure is represented by the number of atoms
+/// of each species it contains, with one sample for each species in the
+/// structure. Otherwise, each atom is represented by a single value equal to 1,
+/// with one sample for each atom. Moving the species from the samples to the
+/// features (with `Descriptor::densify`) gives a vector of species counts for
+/// each structure, or a one-hot encoding of the species for each atom.
+///
+/// This representation does not depend on the positions of the atoms, and can
+/// be used to fit a baseline energy for each species.
+pub struct Composition {
+    /// Compute the composition of whole structures, instead of a separate
+    /// value for each atom
+    per_structure: bool,
+}
+
+impl CalculatorBase for Composition {
+    fn name(&self) -> String {
+        "composition".into()
+    }
+
+    fn get_parameters(&self) -> String {
+        serde_json::to_string(self).expect("failed to serialize to JSON")
+    }
+
+    fn features_names(&self) -> Vec<&str> {
+        vec!["count"]
+    }
+
+    fn features(&self) -> Indexes {
+        let mut features = IndexesBuilder::new(self.features_names());
+        features.add(&[IndexValue::from(0)]);
+        return features.finish();
+    }
+
+    fn samples_builder(&self) -> Box<dyn SamplesBuilder> {
+        if self.per_structure {
+            Box::new(StructureSpeciesSamples)
+        } else {
+            Box::new(AtomSpeciesSamples)
+        }
+    }
+
+    fn compute_gradients(&self) -> bool {
+        false
+    }
+
+    fn check_features(&self, indexes: &Indexes) -> Result<(), Error> {
+        assert_eq!(indexes.names(), self.features_names());
+        for value in indexes.iter() {
+            if value[0].isize() != 0 {
+                return Err(Error::InvalidParameter(format!(
+                    "invalid feature for composition: got {}, expected 0", value[0].isize()
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    #[time_graph::instrument(name = "Composition::compute")]
+    fn compute(&mut self, systems: &mut [Box<dyn System>], descriptor: &mut Descriptor) -> Result<(), Error> {
+        for (sample_i, sample) in descriptor.samples.iter().enumerate() {
+            let value = if self.per_structure {
+                let species = systems[sample[0].usize()].species()?;
+                species.iter().filter(|&&s| s == sample[1].i32()).count() as f64
+            } else {
+                1.0
+            };
+
+            descriptor.values[[sample_i, 0]] = value;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::array;
+
+    use crate::systems::test_utils::test_systems;
+    use crate::descriptor::IndexValue;
+    use crate::{Descriptor, Calculator};
+
+    fn v(i: i32) -> IndexValue { IndexValue::from(i) }
+
+    #[test]
+    fn per_structure() {
+        let mut calculator = Calculator::new("composition", r#"{
+            "per_structure": true
+        }"#.to_owned()).unwrap();
+
+        let mut systems = test_systems(&["methane", "water"]);
+        let mut descriptor = Descriptor::new();
+        calculator.compute(&mut systems, &mut descriptor, Default::default()).unwrap();
+
+        assert_eq!(descriptor.samples.names(), ["structure", "species"]);
+        assert_eq!(descriptor.samples.iter().collect::<Vec<_>>(), vec![
+            &[v(0), v(1)], &[v(0), v(6)], &[v(1), v(1)], &[v(1), v(123456)],
+        ]);
+        assert_eq!(descriptor.values, array![[4.0], [1.0], [2.0], [1.0]]);
+
+        descriptor.densify(&["species"], None).unwrap();
+        assert_eq!(descriptor.values, array![[4.0, 1.0, 0.0], [2.0, 0.0, 1.0]]);
+    }
+
+    #[test]
+    fn per_atom() {
+        let mut calculator = Calculator::new("composition", r#"{
+            "per_structure": false
+        }"#.to_owned()).unwrap();
+
+        let mut systems = test_systems(&["water"]);
+        let mut descriptor = Descriptor::new();
+        calculator.compute(&mut systems, &mut descriptor, Default::default()).unwrap();
+
+        assert_eq!(descriptor.samples.names(), ["structure", "center", "species_center"]);
+        assert_eq!(descriptor.values, array![[1.0], [1.0], [1.0]]);
+
+        descriptor.densify(&["species_center"], None).unwrap();
+        assert_eq!(descriptor.values, array![[0.0, 1.0], [1.0, 0.0], [1.0, 0.0]]);
+    }
+}
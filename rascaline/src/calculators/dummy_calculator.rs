@@ -55,6 +55,10 @@ impl CalculatorBase for DummyCalculator {
         self.gradients
     }
 
+    fn supports_gradients(&self) -> bool {
+        true
+    }
+
     fn check_features(&self, indexes: &Indexes) -> Result<(), Error> {
         assert_eq!(indexes.names(), self.features_names());
         let first = [IndexValue::from(1), IndexValue::from(0)];
@@ -93,14 +97,11 @@ impl CalculatorBase for DummyCalculator {
 
                     let positions = system.positions()?;
                     let mut sum = positions[center][0] + positions[center][1] + positions[center][2];
-                    for pair in system.pairs()? {
-                        if pair.first == center {
-                            sum += positions[pair.second][0] + positions[pair.second][1] + positions[pair.second][2];
-                        }
-
-                        if pair.second == center {
-                            sum += positions[pair.first][0] + positions[pair.first][1] + positions[pair.first][2];
-                        }
+                    for pair in system.pairs_containing(center)? {
+                        // pairs between an atom and its own periodic image
+                        // are included twice in `pairs_containing`
+                        let neighbor = if pair.first == center { pair.second } else { pair.first };
+                        sum += positions[neighbor][0] + positions[neighbor][1] + positions[neighbor][2];
                     }
 
                     descriptor.values[[sample_i, feature_i]] = sum;
@@ -0,0 +1,588 @@
+use std::f64::consts::PI;
+
+use ndarray::Array2;
+
+use super::CalculatorBase;
+
+use crate::descriptor::{Indexes, IndexesBuilder, IndexValue, SamplesBuilder};
+use crate::math::special::erfc;
+use crate::{Descriptor, Error, ErrorContext, Matrix3, System, Vector3D};
+use crate::systems::UnitCell;
+
+/// Parameters for the electrostatic potential calculator
+#[derive(Debug, Clone)]
+#[derive(serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+pub struct ElectrostaticPotentialParameters {
+    /// Name of the per-atom data (see `System::data`) containing the charge of
+    /// each atom
+    pub charges: String,
+    /// Cutoff for the real space part of the Ewald summation
+    pub cutoff: f64,
+    /// Cutoff for the reciprocal space part of the Ewald summation
+    pub k_cutoff: f64,
+    /// Ewald splitting parameter α: the real space part of the interaction
+    /// decays as `erfc(α r) / r`, and the reciprocal space part as
+    /// `exp(-k^2 / 4α^2) / k^2`. Good values are typically around `3 /
+    /// cutoff`, together with `k_cutoff` around `6 α`.
+    pub alpha: f64,
+    /// Should we also compute the electric field created by all other atoms on
+    /// each atom?
+    #[serde(default)]
+    pub electric_field: bool,
+    /// Should we also compute gradients of the feature?
+    pub gradients: bool,
+}
+
+/// Electrostatic potential (and optionally electric field) created by all
+/// other atoms on each atom, computed from per-atom charges.
+///
+/// For periodic systems, the potential is computed with an Ewald summation,
+/// using a neutralizing background if the system is not charge neutral. For
+/// non-periodic systems, the Coulomb interaction between all pairs of atoms is
+/// summed directly. The potential is given in units of charge over distance,
+/// without any Coulomb constant.
+///
+/// The features are indexed by `component`: 0 is the potential and 1, 2 and 3
+/// are the x, y and z components of the electric field, if requested.
+#[derive(Debug, Clone)]
+pub struct ElectrostaticPotential {
+    parameters: ElectrostaticPotentialParameters,
+}
+
+impl ElectrostaticPotential {
+    /// Create a new `ElectrostaticPotential` calculator with the given
+    /// parameters
+    pub fn new(parameters: ElectrostaticPotentialParameters) -> Result<ElectrostaticPotential, Error> {
+        let check_positive = |name, value: f64| {
+            if !(value > 0.0 && value.is_finite()) {
+                return Err(Error::InvalidParameter(format!(
+                    "{} must be a positive number for the electrostatic potential, got {}",
+                    name, value
+                )));
+            }
+            return Ok(());
+        };
+
+        check_positive("cutoff", parameters.cutoff)?;
+        check_positive("k_cutoff", parameters.k_cutoff)?;
+        check_positive("alpha", parameters.alpha)?;
+
+        return Ok(ElectrostaticPotential { parameters });
+    }
+
+    /// Get the charges of all atoms in `system` (the structure at index
+    /// `i_system`)
+    fn charges<'a>(&self, i_system: usize, system: &'a dyn System) -> Result<&'a [f64], Error> {
+        let name = &self.parameters.charges;
+        let charges = system.data(name)?.ok_or_else(|| Error::InvalidParameter(format!(
+            "missing per-atom data '{}' for the charges in structure {}", name, i_system
+        )))?;
+
+        if charges.len() != system.size()? {
+            return Err(Error::InvalidParameter(format!(
+                "expected {} values in per-atom data '{}' for structure {}, got {}",
+                system.size()?, name, i_system, charges.len()
+            )));
+        }
+
+        return Ok(charges);
+    }
+
+    /// Compute the sum of `erfc(α |L|) / |L|` over all lattice vectors `L` of
+    /// the cell (excluding `L = 0`) inside the real space cutoff
+    fn self_images_sum(&self, cell: &UnitCell) -> f64 {
+        let alpha = self.parameters.alpha;
+        let cutoff = self.parameters.cutoff;
+        let matrix = cell.matrix();
+        let distances = cell.distances_between_faces();
+        let max_n = [
+            (cutoff / distances[0]) as i32,
+            (cutoff / distances[1]) as i32,
+            (cutoff / distances[2]) as i32,
+        ];
+
+        let mut sum = 0.0;
+        for n1 in -max_n[0]..=max_n[0] {
+            for n2 in -max_n[1]..=max_n[1] {
+                for n3 in -max_n[2]..=max_n[2] {
+                    if n1 == 0 && n2 == 0 && n3 == 0 {
+                        continue;
+                    }
+
+                    let vector = n1 as f64 * Vector3D::from(matrix[0])
+                        + n2 as f64 * Vector3D::from(matrix[1])
+                        + n3 as f64 * Vector3D::from(matrix[2]);
+                    let distance = vector.norm();
+                    if distance < cutoff {
+                        sum += erfc(alpha * distance) / distance;
+                    }
+                }
+            }
+        }
+
+        return sum;
+    }
+
+    /// Compute the potential, field and their gradients for all atoms in the
+    /// given system
+    fn compute_system(&self, i_system: usize, system: &mut dyn System, gradients: bool) -> Result<SystemElectrostatics, Error> {
+        let n_atoms = system.size()?;
+        let mut result = SystemElectrostatics::new(n_atoms, gradients);
+
+        let cell = system.cell()?;
+        if cell.is_infinite() {
+            let positions = system.positions()?;
+            let charges = self.charges(i_system, system)?;
+            for first in 0..n_atoms {
+                for second in (first + 1)..n_atoms {
+                    let vector = positions[second] - positions[first];
+                    result.add_pair(first, second, vector, charges, 0.0);
+                }
+            }
+            return Ok(result);
+        }
+
+        let alpha = self.parameters.alpha;
+        system.compute_neighbors(self.parameters.cutoff).with_context(|| format!(
+            "failed to compute the neighbors list for structure {}", i_system
+        ))?;
+
+        let charges = self.charges(i_system, system)?;
+        for pair in system.pairs()? {
+            // interactions of atoms with their own periodic images are
+            // handled separately below
+            if pair.first != pair.second {
+                result.add_pair(pair.first, pair.second, pair.vector, charges, alpha);
+            }
+        }
+
+        // the interaction of an atom with its own periodic images does not
+        // depend on the positions, and the corresponding field vanishes
+        let self_images = self.self_images_sum(&cell);
+        for (potential, &charge) in result.potential.iter_mut().zip(charges) {
+            *potential += charge * self_images;
+        }
+
+        let volume = cell.volume();
+        let positions = system.positions()?;
+        for k in cell.reciprocal_half_space(self.parameters.k_cutoff) {
+            let k2 = k.norm2();
+            // factor 2 since we only include one of k and -k
+            let prefactor = 2.0 * 4.0 * PI / volume * f64::exp(-0.25 * k2 / (alpha * alpha)) / k2;
+            let kk = k.tensorial(&k);
+
+            // sum over all atoms of q_j cos(k·r_j) and q_j sin(k·r_j)
+            let phases = positions.iter().map(|&position| f64::sin_cos(k * position)).collect::<Vec<_>>();
+            let (mut cos_sum, mut sin_sum) = (0.0, 0.0);
+            for (&(sin, cos), &charge) in phases.iter().zip(charges) {
+                cos_sum += charge * cos;
+                sin_sum += charge * sin;
+            }
+
+            for (center, &(sin_c, cos_c)) in phases.iter().enumerate() {
+                // Σ_j q_j cos(k·(r_c - r_j)) and Σ_j q_j sin(k·(r_c - r_j))
+                let cos_all = cos_c * cos_sum + sin_c * sin_sum;
+                let sin_all = sin_c * cos_sum - cos_c * sin_sum;
+
+                result.potential[center] += prefactor * cos_all;
+                result.field[center] += prefactor * sin_all * k;
+
+                if let Some(ref mut potential_gradients) = result.potential_gradients {
+                    let field_gradients = result.field_gradients.as_mut().expect("missing field gradients");
+                    for (atom, &(sin_a, cos_a)) in phases.iter().enumerate() {
+                        if atom == center {
+                            potential_gradients[[center, atom]] -= prefactor * sin_all * k;
+                            let cos_others = cos_all - charges[center];
+                            field_gradients[[center, atom]] += prefactor * cos_others * kk;
+                        } else {
+                            let cos_ca = cos_c * cos_a + sin_c * sin_a;
+                            let sin_ca = sin_c * cos_a - cos_c * sin_a;
+                            potential_gradients[[center, atom]] += prefactor * charges[atom] * sin_ca * k;
+                            field_gradients[[center, atom]] -= prefactor * charges[atom] * cos_ca * kk;
+                        }
+                    }
+                }
+            }
+        }
+
+        // remove the interaction of each gaussian charge with itself, and the
+        // interaction with the neutralizing background
+        let total_charge = charges.iter().sum::<f64>();
+        let background = PI * total_charge / (volume * alpha * alpha);
+        for (potential, &charge) in result.potential.iter_mut().zip(charges) {
+            *potential -= 2.0 * alpha / f64::sqrt(PI) * charge + background;
+        }
+
+        return Ok(result);
+    }
+}
+
+/// Electrostatic potential, electric field and the corresponding gradients
+/// for all atoms in a system
+struct SystemElectrostatics {
+    potential: Vec<f64>,
+    field: Vec<Vector3D>,
+    /// Gradients of the potential on atom `i` w.r.t. the position of atom `j`
+    /// stored at `[i, j]`
+    potential_gradients: Option<Array2<Vector3D>>,
+    /// Gradients of the field on atom `i` w.r.t. the position of atom `j`,
+    /// stored at `[i, j]`, with `matrix[α][β] = ∂E_α / ∂r_β`
+    field_gradients: Option<Array2<Matrix3>>,
+}
+
+impl SystemElectrostatics {
+    fn new(n_atoms: usize, gradients: bool) -> SystemElectrostatics {
+        let (potential_gradients, field_gradients) = if gradients {
+            (
+                Some(Array2::from_elem((n_atoms, n_atoms), Vector3D::zero())),
+                Some(Array2::from_elem((n_atoms, n_atoms), Matrix3::zero())),
+            )
+        } else {
+            (None, None)
+        };
+
+        return SystemElectrostatics {
+            potential: vec![0.0; n_atoms],
+            field: vec![Vector3D::zero(); n_atoms],
+            potential_gradients: potential_gradients,
+            field_gradients: field_gradients,
+        };
+    }
+
+    /// Add the real space contribution of the pair between `first` and
+    /// `second`, where `vector` goes from `first` to `second`. `alpha` is the
+    /// Ewald splitting parameter, using `alpha = 0` gives the bare Coulomb
+    /// interaction.
+    fn add_pair(&mut self, first: usize, second: usize, vector: Vector3D, charges: &[f64], alpha: f64) {
+        let distance = vector.norm();
+        let direction = vector / distance;
+
+        // f(r) = erfc(α r) / r and its first two derivatives
+        let erfc_r = erfc(alpha * distance);
+        let gaussian = 2.0 * alpha / f64::sqrt(PI) * f64::exp(-alpha * alpha * distance * distance);
+        let f = erfc_r / distance;
+        let df = -erfc_r / (distance * distance) - gaussian / distance;
+        let d2f = 2.0 * erfc_r / (distance * distance * distance)
+            + gaussian * (2.0 / (distance * distance) + 2.0 * alpha * alpha);
+
+        // gradient of f(|v|) w.r.t. v, and the corresponding hessian
+        let gradient = df * direction;
+        let uu = direction.tensorial(&direction);
+        let hessian = d2f * uu + df / distance * (Matrix3::one() - uu);
+
+        for &(center, neighbor, sign) in &[(first, second, 1.0), (second, first, -1.0)] {
+            let charge = charges[neighbor];
+            self.potential[center] += charge * f;
+            self.field[center] += sign * charge * gradient;
+
+            if let Some(ref mut potential_gradients) = self.potential_gradients {
+                potential_gradients[[center, center]] -= sign * charge * gradient;
+                potential_gradients[[center, neighbor]] += sign * charge * gradient;
+            }
+
+            if let Some(ref mut field_gradients) = self.field_gradients {
+                field_gradients[[center, center]] -= charge * hessian;
+                field_gradients[[center, neighbor]] += charge * hessian;
+            }
+        }
+    }
+}
+
+impl CalculatorBase for ElectrostaticPotential {
+    fn name(&self) -> String {
+        "electrostatic potential".into()
+    }
+
+    fn get_parameters(&self) -> String {
+        serde_json::to_string(&self.parameters).expect("failed to serialize to JSON")
+    }
+
+    fn features_names(&self) -> Vec<&str> {
+        vec!["component"]
+    }
+
+    fn features(&self) -> Indexes {
+        let mut features = IndexesBuilder::new(self.features_names());
+        features.add(&[IndexValue::from(0)]);
+        if self.parameters.electric_field {
+            for spatial in 1..=3 {
+                features.add(&[IndexValue::from(spatial)]);
+            }
+        }
+        return features.finish();
+    }
+
+    fn samples_builder(&self) -> Box<dyn SamplesBuilder> {
+        Box::new(LongRangeSamples)
+    }
+
+    fn compute_gradients(&self) -> bool {
+        self.parameters.gradients
+    }
+
+    fn supports_gradients(&self) -> bool {
+        true
+    }
+
+    fn check_features(&self, indexes: &Indexes) -> Result<(), Error> {
+        assert_eq!(indexes.names(), self.features_names());
+        let max_component = if self.parameters.electric_field { 3 } else { 0 };
+        for value in indexes.iter() {
+            let component = value[0].isize();
+            if component < 0 || component > max_component {
+                return Err(Error::InvalidParameter(format!(
+                    "invalid component for electrostatic potential: expected value \
+                    between 0 and {}, got {}", max_component, component
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    #[time_graph::instrument(name = "ElectrostaticPotential::compute")]
+    fn compute(&mut self, systems: &mut [Box<dyn System>], descriptor: &mut Descriptor) -> Result<(), Error> {
+        assert_eq!(descriptor.samples.names(), &["structure", "center", "species_center"]);
+
+        let components = descriptor.features.iter()
+            .map(|feature| feature[0].usize())
+            .collect::<Vec<_>>();
+
+        let gradients = descriptor.gradients.is_some();
+        for (i_system, system) in systems.iter_mut().enumerate() {
+            crate::calculator::check_cancelled()?;
+            let result = self.compute_system(i_system, &mut **system, gradients)?;
+
+            for (sample_i, sample) in descriptor.samples.iter().enumerate() {
+                if sample[0].usize() != i_system {
+                    continue;
+                }
+
+                let center = sample[1].usize();
+                for (feature_i, &component) in components.iter().enumerate() {
+                    descriptor.values[[sample_i, feature_i]] = if component == 0 {
+                        result.potential[center]
+                    } else {
+                        result.field[center][component - 1]
+                    };
+                }
+            }
+
+            if let Some(ref mut descriptor_gradients) = descriptor.gradients {
+                let gradients_samples = descriptor.gradients_samples.as_ref().expect("missing gradients samples");
+                let potential_gradients = result.potential_gradients.as_ref().expect("missing potential gradients");
+                let field_gradients = result.field_gradients.as_ref().expect("missing field gradients");
+
+                for (row, gradient_sample) in gradients_samples.iter().enumerate() {
+                    let sample = &descriptor.samples[gradient_sample[0].usize()];
+                    if sample[0].usize() != i_system {
+                        continue;
+                    }
+
+                    let center = sample[1].usize();
+                    let atom = gradient_sample[1].usize();
+                    let spatial = gradient_sample[2].usize();
+
+                    for (feature_i, &component) in components.iter().enumerate() {
+                        descriptor_gradients[[row, feature_i]] = if component == 0 {
+                            potential_gradients[[center, atom]][spatial]
+                        } else {
+                            field_gradients[[center, atom]][component - 1][spatial]
+                        };
+                    }
+                }
+            }
+        }
+
+        return Ok(());
+    }
+}
+
+/// Samples for long-range features: one sample for each atom, with gradients
+/// with respect to the positions of all atoms in the same structure.
+struct LongRangeSamples;
+
+impl SamplesBuilder for LongRangeSamples {
+    fn names(&self) -> Vec<&str> {
+        vec!["structure", "center", "species_center"]
+    }
+
+    fn samples(&self, systems: &mut [Box<dyn System>]) -> Result<Indexes, Error> {
+        let mut indexes = IndexesBuilder::new(self.names());
+        for (i_system, system) in systems.iter().enumerate() {
+            for (center, &species) in system.species()?.iter().enumerate() {
+                indexes.add(&[
+                    IndexValue::from(i_system), IndexValue::from(center), IndexValue::from(species)
+                ]);
+            }
+        }
+        return Ok(indexes.finish());
+    }
+
+    fn gradients_for(&self, systems: &mut [Box<dyn System>], samples: &Indexes) -> Result<Option<Indexes>, Error> {
+        assert_eq!(samples.names(), self.names());
+
+        let mut gradients = IndexesBuilder::new(vec!["sample", "atom", "spatial"]);
+        for (i_sample, sample) in samples.iter().enumerate() {
+            let system = sample[0].usize();
+            for atom in 0..systems[system].size()? {
+                for spatial in 0..3 {
+                    gradients.add(&[
+                        IndexValue::from(i_sample), IndexValue::from(atom), IndexValue::from(spatial)
+                    ]);
+                }
+            }
+        }
+
+        return Ok(Some(gradients.finish()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use crate::systems::test_utils::test_system;
+    use crate::systems::{SimpleSystem, UnitCell};
+    use crate::{Calculator, Descriptor, System, Vector3D};
+    use crate::calculators::CalculatorBase;
+
+    use super::{ElectrostaticPotential, ElectrostaticPotentialParameters};
+
+    fn parameters(gradients: bool) -> ElectrostaticPotentialParameters {
+        ElectrostaticPotentialParameters {
+            charges: "charge".into(),
+            cutoff: 4.0,
+            k_cutoff: 6.0,
+            alpha: 0.8,
+            electric_field: true,
+            gradients: gradients,
+        }
+    }
+
+    fn calculator(parameters: ElectrostaticPotentialParameters) -> Calculator {
+        Calculator::from(Box::new(
+            ElectrostaticPotential::new(parameters).unwrap()
+        ) as Box<dyn CalculatorBase>)
+    }
+
+    #[test]
+    fn madelung_constant() {
+        // NaCl rock salt structure in a cubic cell
+        let mut system = SimpleSystem::new(UnitCell::cubic(2.0));
+        let mut charges = Vec::new();
+        for &(x, y, z) in &[(0.0, 0.0, 0.0), (0.0, 1.0, 1.0), (1.0, 0.0, 1.0), (1.0, 1.0, 0.0)] {
+            system.add_atom(11, Vector3D::new(x, y, z));
+            charges.push(1.0);
+            system.add_atom(17, Vector3D::new(x + 1.0, y, z));
+            charges.push(-1.0);
+        }
+        system.set_data("charge", charges).unwrap();
+
+        let mut parameters = parameters(false);
+        parameters.cutoff = 5.0;
+        parameters.alpha = 1.2;
+        parameters.k_cutoff = 14.0;
+
+        let mut systems = vec![Box::new(system) as Box<dyn System>];
+        let mut descriptor = Descriptor::new();
+        calculator(parameters).compute(&mut systems, &mut descriptor, Default::default()).unwrap();
+
+        for (sample_i, sample) in descriptor.samples.iter().enumerate() {
+            let charge = if sample[2].i32() == 11 { 1.0 } else { -1.0 };
+            // Madelung constant of NaCl, with nearest neighbors at distance 1
+            assert_relative_eq!(descriptor.values[[sample_i, 0]], -1.747564594633 * charge, max_relative = 1e-8);
+
+            // the field vanishes by symmetry
+            for component in 1..4 {
+                assert_relative_eq!(descriptor.values[[sample_i, component]], 0.0, epsilon = 1e-10);
+            }
+        }
+    }
+
+    #[test]
+    fn non_periodic() {
+        let positions = [
+            Vector3D::new(0.0, 0.0, 0.0),
+            Vector3D::new(0.0, 0.75545, -0.58895),
+            Vector3D::new(0.0, -0.75545, -0.58895),
+        ];
+        let charges = [-0.8, 0.4, 0.4];
+
+        let mut system = SimpleSystem::new(UnitCell::infinite());
+        system.add_atom(8, positions[0]);
+        system.add_atom(1, positions[1]);
+        system.add_atom(1, positions[2]);
+        system.set_data("charge", charges.to_vec()).unwrap();
+
+        let mut systems = vec![Box::new(system) as Box<dyn System>];
+        let mut descriptor = Descriptor::new();
+        calculator(parameters(false)).compute(&mut systems, &mut descriptor, Default::default()).unwrap();
+
+        for center in 0..3 {
+            let mut potential = 0.0;
+            let mut field = Vector3D::zero();
+            for other in 0..3 {
+                if other != center {
+                    let vector = positions[center] - positions[other];
+                    potential += charges[other] / vector.norm();
+                    field += charges[other] / (vector.norm() * vector.norm2()) * vector;
+                }
+            }
+
+            assert_relative_eq!(descriptor.values[[center, 0]], potential, max_relative = 1e-12);
+            for spatial in 0..3 {
+                assert_relative_eq!(descriptor.values[[center, spatial + 1]], field[spatial], max_relative = 1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn finite_differences() {
+        let calculator = calculator(parameters(true));
+
+        let mut system = SimpleSystem::new(UnitCell::cubic(4.5));
+        system.add_atom(8, Vector3D::new(0.0, 0.0, 0.0));
+        system.add_atom(1, Vector3D::new(0.1, 0.75545, -0.58895));
+        system.add_atom(1, Vector3D::new(0.0, -0.75545, -0.48895));
+        system.add_atom(11, Vector3D::new(2.0, 1.5, 1.2));
+        system.set_data("charge", vec![-0.8, 0.4, 0.4, 0.7]).unwrap();
+        crate::calculators::tests_utils::finite_difference(calculator, system);
+    }
+
+    #[test]
+    fn finite_differences_non_periodic() {
+        let calculator = calculator(parameters(true));
+
+        let mut system = SimpleSystem::new(UnitCell::infinite());
+        system.add_atom(8, Vector3D::new(0.0, 0.0, 0.0));
+        system.add_atom(1, Vector3D::new(0.1, 0.75545, -0.58895));
+        system.add_atom(1, Vector3D::new(0.0, -0.75545, -0.48895));
+        system.set_data("charge", vec![-0.8, 0.4, 0.4]).unwrap();
+        crate::calculators::tests_utils::finite_difference(calculator, system);
+    }
+
+    #[test]
+    fn missing_charges() {
+        let mut systems = vec![Box::new(test_system("water")) as Box<dyn System>];
+        let mut descriptor = Descriptor::new();
+        let error = calculator(parameters(false))
+            .compute(&mut systems, &mut descriptor, Default::default())
+            .unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "invalid parameter: missing per-atom data 'charge' for the charges in structure 0"
+        );
+    }
+
+    #[test]
+    fn invalid_parameters() {
+        let mut parameters = parameters(false);
+        parameters.alpha = -1.0;
+        let error = ElectrostaticPotential::new(parameters).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "invalid parameter: alpha must be a positive number for the electrostatic potential, got -1"
+        );
+    }
+}
@@ -0,0 +1,360 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use ndarray::{Array1, Array2};
+
+use super::CalculatorBase;
+use super::soap::CutoffFunction;
+
+use crate::descriptor::{Indexes, IndexesBuilder, IndexValue, SamplesBuilder};
+use crate::{Descriptor, Error, ErrorContext, System, Vector3D};
+
+/// Parameters for the graph moments calculator
+#[derive(Debug, Clone)]
+#[derive(serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+pub struct GraphMomentsParameters {
+    /// Spherical cutoff used to define the edges of the graph
+    pub cutoff: f64,
+    /// Maximal length of the walks to count
+    pub max_walk_length: usize,
+    /// Cutoff function used to smoothly turn off the edges of the graph
+    /// around the cutoff radius
+    pub cutoff_function: CutoffFunction,
+    /// Should we also compute gradients of the feature?
+    pub gradients: bool,
+}
+
+/// Walk counts in the graph of atoms connected by their neighbors list.
+///
+/// Each structure is represented by a weighted graph, where the weight of the
+/// edge between atoms `i` and `j` is the cutoff function evaluated at `r_ij`
+/// (summed over all periodic images of `j`). The features associated with an
+/// atom `i` and a neighbor species `s` are the (weighted) number of walks of
+/// length `k = 1 ... max_walk_length` starting at `i` and ending on an atom of
+/// species `s`, i.e. `Σ_j (A^k)_ij δ(s_j, s)` where `A` is the adjacency
+/// matrix of the graph.
+#[derive(Debug, Clone)]
+pub struct GraphMoments {
+    parameters: GraphMomentsParameters,
+}
+
+impl GraphMoments {
+    /// Create a new `GraphMoments` calculator with the given parameters
+    pub fn new(parameters: GraphMomentsParameters) -> Result<GraphMoments, Error> {
+        parameters.cutoff_function.validate()?;
+
+        if !(parameters.cutoff > 0.0 && parameters.cutoff.is_finite()) {
+            return Err(Error::InvalidParameter(format!(
+                "cutoff must be a positive number for graph moments, got {}",
+                parameters.cutoff
+            )));
+        }
+
+        if parameters.max_walk_length == 0 {
+            return Err(Error::InvalidParameter(
+                "max_walk_length must be at least 1 for graph moments".into()
+            ));
+        }
+
+        return Ok(GraphMoments {
+            parameters: parameters,
+        });
+    }
+}
+
+/// An edge of the graph, with the data required to compute gradients
+struct Edge {
+    first: usize,
+    second: usize,
+    /// derivative of the edge weight w.r.t. the distance
+    derivative: f64,
+    /// unit vector from the first to the second atom
+    direction: Vector3D,
+}
+
+impl CalculatorBase for GraphMoments {
+    fn name(&self) -> String {
+        "graph moments".into()
+    }
+
+    fn get_parameters(&self) -> String {
+        serde_json::to_string(&self.parameters).expect("failed to serialize to JSON")
+    }
+
+    fn features_names(&self) -> Vec<&str> {
+        vec!["walk_length"]
+    }
+
+    fn features(&self) -> Indexes {
+        let mut features = IndexesBuilder::new(self.features_names());
+        for length in 1..=self.parameters.max_walk_length {
+            features.add(&[IndexValue::from(length)]);
+        }
+        return features.finish();
+    }
+
+    fn samples_builder(&self) -> Box<dyn SamplesBuilder> {
+        Box::new(GraphSamples)
+    }
+
+    fn compute_gradients(&self) -> bool {
+        self.parameters.gradients
+    }
+
+    fn supports_gradients(&self) -> bool {
+        true
+    }
+
+    fn check_features(&self, indexes: &Indexes) -> Result<(), Error> {
+        assert_eq!(indexes.names(), self.features_names());
+        for value in indexes.iter() {
+            let length = value[0].usize();
+            if length == 0 || length > self.parameters.max_walk_length {
+                return Err(Error::InvalidParameter(format!(
+                    "invalid walk length for graph moments: got {}, expected a \
+                    value between 1 and {}", length, self.parameters.max_walk_length
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    #[time_graph::instrument(name = "GraphMoments::compute")]
+    fn compute(&mut self, systems: &mut [Box<dyn System>], descriptor: &mut Descriptor) -> Result<(), Error> {
+        assert_eq!(descriptor.samples.names(), &["structure", "center", "species_center", "species_neighbor"]);
+        assert_eq!(descriptor.features.names(), &["walk_length"]);
+
+        let cutoff = self.parameters.cutoff;
+        let lengths = descriptor.features.iter()
+            .map(|feature| feature[0].usize())
+            .collect::<Vec<_>>();
+        let max_length = lengths.iter().copied().max().unwrap_or(0);
+
+        // first row of the gradients for each (sample, atom)
+        let mut gradient_rows = BTreeMap::new();
+        if let Some(ref gradients_samples) = descriptor.gradients_samples {
+            for (row, gradient_sample) in gradients_samples.iter().enumerate() {
+                if gradient_sample[2].usize() == 0 {
+                    gradient_rows.insert((gradient_sample[0].usize(), gradient_sample[1].usize()), row);
+                }
+            }
+        }
+
+        for (i_system, system) in systems.iter_mut().enumerate() {
+            crate::calculator::check_cancelled()?;
+            system.compute_neighbors(cutoff).with_context(|| format!(
+                "failed to compute the neighbors list for structure {}", i_system
+            ))?;
+
+            let n_atoms = system.size()?;
+            let species = system.species()?;
+
+            let mut adjacency = Array2::from_elem((n_atoms, n_atoms), 0.0);
+            let mut edges = Vec::new();
+            for pair in system.pairs()? {
+                if pair.first == pair.second {
+                    // an atom is not connected to its own periodic images
+                    continue;
+                }
+
+                let weight = self.parameters.cutoff_function.compute(pair.distance, cutoff);
+                adjacency[[pair.first, pair.second]] += weight;
+                adjacency[[pair.second, pair.first]] += weight;
+
+                edges.push(Edge {
+                    first: pair.first,
+                    second: pair.second,
+                    derivative: self.parameters.cutoff_function.derivative(pair.distance, cutoff),
+                    direction: pair.vector / pair.distance,
+                });
+            }
+
+            // powers[m] = A^m
+            let mut powers = vec![Array2::eye(n_atoms)];
+            for m in 1..=max_length {
+                let power = powers[m - 1].dot(&adjacency);
+                powers.push(power);
+            }
+
+            // walks[s][m] = A^m δ(s_j, s), the number of walks of length m
+            // starting on each atom and ending on atoms of species s
+            let mut walks = BTreeMap::new();
+            for &neighbor_species in species.iter().collect::<BTreeSet<_>>() {
+                let mask = species.iter()
+                    .map(|&s| if s == neighbor_species { 1.0 } else { 0.0 })
+                    .collect::<Array1<f64>>();
+                let species_walks = powers.iter().map(|power| power.dot(&mask)).collect::<Vec<_>>();
+                walks.insert(neighbor_species, species_walks);
+            }
+
+            for (sample_i, sample) in descriptor.samples.iter().enumerate() {
+                if sample[0].usize() != i_system {
+                    continue;
+                }
+                let center = sample[1].usize();
+                let species_walks = &walks[&sample[3].i32()];
+
+                for (feature_i, &length) in lengths.iter().enumerate() {
+                    descriptor.values[[sample_i, feature_i]] = species_walks[length][center];
+                }
+
+                if let Some(ref mut gradients) = descriptor.gradients {
+                    // d(A^k v)_i = Σ_m Σ_ab (A^m)_ia dA_ab (A^(k-1-m) v)_b
+                    for (feature_i, &length) in lengths.iter().enumerate() {
+                        for m in 0..length {
+                            let left = &powers[m];
+                            let right = &species_walks[length - 1 - m];
+                            for edge in &edges {
+                                let (a, b) = (edge.first, edge.second);
+                                let factor = edge.derivative * (
+                                    left[[center, a]] * right[b] + left[[center, b]] * right[a]
+                                );
+                                if factor == 0.0 {
+                                    continue;
+                                }
+
+                                // the distance depends on the positions of
+                                // both atoms in the edge
+                                for &(atom, sign) in &[(b, 1.0), (a, -1.0)] {
+                                    let first_row = match gradient_rows.get(&(sample_i, atom)) {
+                                        Some(&row) => row,
+                                        None => continue,
+                                    };
+
+                                    for spatial in 0..3 {
+                                        gradients[[first_row + spatial, feature_i]] += sign * factor * edge.direction[spatial];
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        return Ok(());
+    }
+}
+
+/// Samples for graph moments: one sample for each atom and each species in
+/// the same structure, with gradients with respect to the positions of all
+/// atoms in the structure.
+struct GraphSamples;
+
+impl SamplesBuilder for GraphSamples {
+    fn names(&self) -> Vec<&str> {
+        vec!["structure", "center", "species_center", "species_neighbor"]
+    }
+
+    fn samples(&self, systems: &mut [Box<dyn System>]) -> Result<Indexes, Error> {
+        let mut indexes = IndexesBuilder::new(self.names());
+        for (i_system, system) in systems.iter().enumerate() {
+            let species = system.species()?;
+            let all_species = species.iter().collect::<BTreeSet<_>>();
+
+            for (center, &species_center) in species.iter().enumerate() {
+                for &&species_neighbor in &all_species {
+                    indexes.add(&[
+                        IndexValue::from(i_system),
+                        IndexValue::from(center),
+                        IndexValue::from(species_center),
+                        IndexValue::from(species_neighbor),
+                    ]);
+                }
+            }
+        }
+
+        return Ok(indexes.finish());
+    }
+
+    fn gradients_for(&self, systems: &mut [Box<dyn System>], samples: &Indexes) -> Result<Option<Indexes>, Error> {
+        assert_eq!(samples.names(), self.names());
+
+        let mut gradients = IndexesBuilder::new(vec!["sample", "atom", "spatial"]);
+        for (i_sample, sample) in samples.iter().enumerate() {
+            let system = sample[0].usize();
+            for atom in 0..systems[system].size()? {
+                for spatial in 0..3 {
+                    gradients.add(&[
+                        IndexValue::from(i_sample), IndexValue::from(atom), IndexValue::from(spatial)
+                    ]);
+                }
+            }
+        }
+
+        return Ok(Some(gradients.finish()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use crate::systems::test_utils::{test_system, test_systems};
+    use crate::{Calculator, Descriptor};
+    use crate::calculators::CalculatorBase;
+    use crate::calculators::soap::CutoffFunction;
+
+    use super::{GraphMoments, GraphMomentsParameters};
+
+    fn calculator(parameters: GraphMomentsParameters) -> Calculator {
+        Calculator::from(Box::new(
+            GraphMoments::new(parameters).unwrap()
+        ) as Box<dyn CalculatorBase>)
+    }
+
+    #[test]
+    fn walk_counts() {
+        // only the O-H bonds are inside the cutoff, and the cutoff function is
+        // equal to 1 for them
+        let mut calculator = calculator(GraphMomentsParameters {
+            cutoff: 1.2,
+            max_walk_length: 3,
+            cutoff_function: CutoffFunction::ShiftedCosine { width: 0.1 },
+            gradients: false,
+        });
+
+        let mut systems = test_systems(&["water"]);
+        let mut descriptor = Descriptor::new();
+        calculator.compute(&mut systems, &mut descriptor, Default::default()).unwrap();
+
+        assert_eq!(descriptor.samples.count(), 6);
+        for (sample_i, sample) in descriptor.samples.iter().enumerate() {
+            let expected = match (sample[2].i32(), sample[3].i32()) {
+                // O-H, O-H-O, O-H-O-H
+                (123456, 1) => [2.0, 0.0, 4.0],
+                // H-O, H-O-H, H-O-H-O
+                (1, 123456) => [1.0, 0.0, 2.0],
+                // walks coming back to the same species have even lengths
+                (123456, 123456) | (1, 1) => [0.0, 2.0, 0.0],
+                _ => panic!("unexpected sample {:?}", sample),
+            };
+
+            for (value, expected) in descriptor.values.row(sample_i).iter().zip(&expected) {
+                assert_relative_eq!(value, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn finite_differences() {
+        let calculator = calculator(GraphMomentsParameters {
+            cutoff: 2.0,
+            max_walk_length: 4,
+            cutoff_function: CutoffFunction::ShiftedCosine { width: 0.8 },
+            gradients: true,
+        });
+        let system = test_system("water");
+        crate::calculators::tests_utils::finite_difference(calculator, system);
+    }
+
+    #[test]
+    fn invalid_parameters() {
+        let error = GraphMoments::new(GraphMomentsParameters {
+            cutoff: 2.0,
+            max_walk_length: 0,
+            cutoff_function: CutoffFunction::ShiftedCosine { width: 0.8 },
+            gradients: false,
+        }).unwrap_err();
+        assert_eq!(error.to_string(), "invalid parameter: max_walk_length must be at least 1 for graph moments");
+    }
+}
@@ -1,3 +1,5 @@
+use ndarray::ArrayViewMut2;
+
 use crate::descriptor::{Descriptor, Indexes, SamplesBuilder};
 
 use crate::{Error, System};
@@ -5,6 +7,9 @@ use crate::{Error, System};
 #[cfg(test)]
 pub(crate) mod tests_utils;
 
+mod validation;
+pub(crate) use self::validation::validate_parameters;
+
 /// TODO: docs
 ///
 /// `std::panic::RefUnwindSafe` is a required super-trait to enable passing
@@ -25,6 +30,20 @@ pub trait CalculatorBase: std::panic::RefUnwindSafe {
     fn samples_builder(&self) -> Box<dyn SamplesBuilder>;
     /// Does this calculator compute gradients?
     fn compute_gradients(&self) -> bool;
+    /// Can this calculator compute gradients, when requested in its
+    /// parameters? The default implementation returns `false`.
+    fn supports_gradients(&self) -> bool {
+        false
+    }
+
+    /// Get the continuous hyper-parameters of this calculator for which
+    /// gradients can be computed with `CalculationOptions::hyper_gradients`,
+    /// as JSON pointers into the parameters of this calculator (for example
+    /// `/atomic_gaussian_width`). The default implementation returns an empty
+    /// list.
+    fn hyper_parameters(&self) -> Vec<String> {
+        Vec::new()
+    }
 
     /// Check that the given indexes are valid feature indexes for this
     /// Calculator. This is used by to ensure only valid features are requested
@@ -59,6 +78,59 @@ pub trait CalculatorBase: std::panic::RefUnwindSafe {
         Ok(())
     }
 
+    /// Get the pre-computed state of this Calculator (for example splines),
+    /// which can be expensive to re-create, serialized as JSON. This is used
+    /// by `Calculator::save`.
+    ///
+    /// The default implementation returns `Ok(None)`, for calculators without
+    /// such state.
+    fn save_state(&self) -> Result<Option<serde_json::Value>, Error> {
+        Ok(None)
+    }
+
+    /// Create the pre-computed state of this Calculator (for example splines)
+    /// if it does not exist yet, so that errors in its creation are reported
+    /// when creating the calculator instead of during the first calculation.
+    /// This is called by `Calculator::new`, and by `Calculator::load` after
+    /// `load_state`.
+    ///
+    /// The default implementation does nothing, for calculators without such
+    /// state.
+    fn precompute(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Restore the pre-computed state of this Calculator from the output of
+    /// `save_state`. This is used by `Calculator::load`.
+    ///
+    /// The default implementation returns an error, since calculators without
+    /// state should never be given any state to load.
+    fn load_state(&mut self, state: serde_json::Value) -> Result<(), Error> {
+        let _ = state;
+        Err(Error::InvalidParameter(format!(
+            "{} does not have any pre-computed state to load", self.name()
+        )))
+    }
+
+    /// Compute the values in `descriptor` (which does not contain gradients),
+    /// and return the intermediate quantities needed to compute the gradients
+    /// later, for any subset of the samples and features in `descriptor`. This
+    /// is used by `CalculationOptions::lazy_gradients`, and is only called on
+    /// calculators computing gradients.
+    ///
+    /// The default implementation returns `Ok(None)` without computing
+    /// anything. The values are then computed by a copy of this calculator
+    /// without gradients, and the gradients by running the full calculation
+    /// again.
+    fn compute_caching_gradients(
+        &mut self,
+        systems: &mut [Box<dyn System>],
+        descriptor: &mut Descriptor,
+    ) -> Result<Option<Box<dyn GradientsCache>>, Error> {
+        let _ = (systems, descriptor);
+        Ok(None)
+    }
+
     /// Core implementation of the descriptor.
     ///
     /// This function should compute the descriptor only for samples in
@@ -70,12 +142,55 @@ pub trait CalculatorBase: std::panic::RefUnwindSafe {
     fn compute(&mut self, systems: &mut [Box<dyn System>], descriptor: &mut Descriptor) -> Result<(), Error>;
 }
 
+/// Intermediate quantities kept by a calculator between the calculation of the
+/// values and the calculation of the gradients when using
+/// `CalculationOptions::lazy_gradients`, see
+/// [`CalculatorBase::compute_caching_gradients`].
+pub trait GradientsCache: std::fmt::Debug + Send + Sync + std::panic::RefUnwindSafe {
+    /// Compute the gradients for the given `samples`, `gradients_samples` and
+    /// `features`, storing them in `gradients`, which is initialized to zero.
+    /// The samples and features are a subset of the ones used to create this
+    /// cache.
+    fn compute_gradients(
+        &self,
+        samples: &Indexes,
+        gradients_samples: &Indexes,
+        features: &Indexes,
+        gradients: ArrayViewMut2<f64>,
+    ) -> Result<(), Error>;
+}
+
 mod sorted_distances;
 pub use self::sorted_distances::SortedDistances;
 
 mod dummy_calculator;
 pub use self::dummy_calculator::DummyCalculator;
 
+mod composition;
+pub use self::composition::Composition;
+
+mod radial_distribution;
+pub use self::radial_distribution::{RadialDistribution, RadialDistributionParameters};
+
+mod angular_distribution;
+pub use self::angular_distribution::{AngularDistribution, AngularDistributionParameters};
+
+mod bond_environment;
+pub use self::bond_environment::{BondEnvironment, BondEnvironmentParameters};
+
+mod voronoi;
+pub use self::voronoi::{VoronoiEnvironment, VoronoiParameters};
+
+mod graph_moments;
+pub use self::graph_moments::{GraphMoments, GraphMomentsParameters};
+
+mod electrostatic;
+pub use self::electrostatic::{ElectrostaticPotential, ElectrostaticPotentialParameters};
+
+mod pipeline;
+pub use self::pipeline::{Pipeline, PipelineParameters, PipelineCalculator};
+
 pub mod soap;
 pub use self::soap::{SphericalExpansion, SphericalExpansionParameters};
 pub use self::soap::{SoapPowerSpectrum, PowerSpectrumParameters};
+pub use self::soap::{LambdaSoap, LambdaSoapParameters};
@@ -0,0 +1,414 @@
+use std::collections::BTreeSet;
+
+use super::CalculatorBase;
+
+use crate::descriptor::{Indexes, IndexesBuilder, IndexValue, SamplesBuilder};
+use crate::{CalculationOptions, Calculator, SelectedIndexes};
+use crate::{Descriptor, Error, ErrorContext, System};
+
+/// A single calculator inside a pipeline
+#[derive(Debug, Clone)]
+#[derive(serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+pub struct PipelineCalculator {
+    /// Name of the calculator, as used with `Calculator::new`
+    pub name: String,
+    /// Hyper-parameters of the calculator
+    pub parameters: serde_json::Value,
+}
+
+/// Parameters for a pipeline of calculators
+#[derive(Debug, Clone)]
+#[derive(serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+pub struct PipelineParameters {
+    /// List of calculators to run, in order. Each calculator corresponds to a
+    /// different block of features in the output.
+    pub calculators: Vec<PipelineCalculator>,
+}
+
+/// Run multiple calculators on the same systems, and concatenate their
+/// features in a single descriptor.
+///
+/// All calculators in the pipeline must use the same sample names, and either
+/// all of them or none of them must compute gradients. The samples of the
+/// pipeline are the union of the samples of all calculators, and the values
+/// are set to zero for samples which are not part of a given calculator
+/// samples. The features are indexed by `block` (the index of the calculator
+/// in the pipeline) and `feature` (the index of the feature in the default
+/// features of this calculator).
+pub struct Pipeline {
+    parameters: PipelineParameters,
+    calculators: Vec<Calculator>,
+}
+
+impl Pipeline {
+    /// Create a new `Pipeline` with the given parameters
+    pub fn new(parameters: PipelineParameters) -> Result<Pipeline, Error> {
+        if parameters.calculators.is_empty() {
+            return Err(Error::InvalidParameter(
+                "a pipeline must contain at least one calculator".into()
+            ));
+        }
+
+        let mut calculators = Vec::new();
+        for (block, calculator) in parameters.calculators.iter().enumerate() {
+            let created = Calculator::new(&calculator.name, calculator.parameters.to_string())
+                .with_context(|| format!("failed to create calculator {} in the pipeline", block))?;
+            calculators.push(created);
+        }
+
+        let first = &calculators[0];
+        for calculator in &calculators[1..] {
+            let samples_names = first.samples_builder().names().join(", ");
+            let other_names = calculator.samples_builder().names().join(", ");
+            if samples_names != other_names {
+                return Err(Error::InvalidParameter(format!(
+                    "all calculators in a pipeline must use the same samples, \
+                    got [{}] for {} and [{}] for {}",
+                    samples_names, first.name(), other_names, calculator.name()
+                )));
+            }
+
+            if calculator.gradients() != first.gradients() {
+                return Err(Error::InvalidParameter(
+                    "either all or none of the calculators in a pipeline must compute gradients".into()
+                ));
+            }
+        }
+
+        return Ok(Pipeline {
+            parameters: parameters,
+            calculators: calculators,
+        });
+    }
+}
+
+impl CalculatorBase for Pipeline {
+    fn name(&self) -> String {
+        let names = self.calculators.iter().map(Calculator::name).collect::<Vec<_>>();
+        return format!("pipeline of [{}]", names.join(", "));
+    }
+
+    fn get_parameters(&self) -> String {
+        serde_json::to_string(&self.parameters).expect("failed to serialize to JSON")
+    }
+
+    fn features_names(&self) -> Vec<&str> {
+        vec!["block", "feature"]
+    }
+
+    fn features(&self) -> Indexes {
+        let mut features = IndexesBuilder::new(self.features_names());
+        for (block, calculator) in self.calculators.iter().enumerate() {
+            for feature in 0..calculator.default_features().count() {
+                features.add(&[IndexValue::from(block), IndexValue::from(feature)]);
+            }
+        }
+        return features.finish();
+    }
+
+    fn samples_builder(&self) -> Box<dyn SamplesBuilder> {
+        Box::new(PipelineSamples {
+            builders: self.calculators.iter().map(Calculator::samples_builder).collect(),
+        })
+    }
+
+    fn compute_gradients(&self) -> bool {
+        self.calculators[0].gradients()
+    }
+
+    fn supports_gradients(&self) -> bool {
+        self.calculators.iter().all(Calculator::supports_gradients)
+    }
+
+    fn check_features(&self, indexes: &Indexes) -> Result<(), Error> {
+        assert_eq!(indexes.names(), self.features_names());
+        for value in indexes.iter() {
+            let block = value[0].usize();
+            if block >= self.calculators.len() {
+                return Err(Error::InvalidParameter(format!(
+                    "block index is too large for this pipeline: got {}, \
+                    expected value lower than {}", block, self.calculators.len()
+                )));
+            }
+
+            let count = self.calculators[block].default_features().count();
+            if value[1].usize() >= count {
+                return Err(Error::InvalidParameter(format!(
+                    "feature index is too large for block {} of this pipeline: \
+                    got {}, expected value lower than {}", block, value[1].usize(), count
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    #[time_graph::instrument(name = "Pipeline::compute")]
+    fn compute(&mut self, systems: &mut [Box<dyn System>], descriptor: &mut Descriptor) -> Result<(), Error> {
+        assert_eq!(descriptor.features.names(), self.features_names());
+
+        for (block, calculator) in self.calculators.iter_mut().enumerate() {
+            crate::calculator::check_cancelled()?;
+
+            // features requested for this block, and the corresponding columns
+            // in the full descriptor
+            let default_features = calculator.default_features();
+            let mut features = IndexesBuilder::new(default_features.names());
+            let mut columns = Vec::new();
+            for (feature_i, feature) in descriptor.features.iter().enumerate() {
+                if feature[0].usize() == block {
+                    features.add(&default_features[feature[1].usize()]);
+                    columns.push(feature_i);
+                }
+            }
+
+            // samples requested for this block, and the corresponding rows in
+            // the full descriptor
+            let all_samples = calculator.samples_builder().samples(systems)?;
+            let mut samples = IndexesBuilder::new(all_samples.names());
+            let mut rows = Vec::new();
+            for (sample_i, sample) in descriptor.samples.iter().enumerate() {
+                if all_samples.contains(sample) {
+                    samples.add(sample);
+                    rows.push(sample_i);
+                }
+            }
+
+            if columns.is_empty() || rows.is_empty() {
+                continue;
+            }
+
+            let options = CalculationOptions {
+                selected_samples: SelectedIndexes::Subset(samples.finish()),
+                selected_features: SelectedIndexes::Subset(features.finish()),
+                // use the same parallelization strategy as the pipeline
+                parallelism: crate::calculator::parallelism(),
+                deterministic: crate::calculator::deterministic(),
+                ..Default::default()
+            };
+
+            let mut block_descriptor = Descriptor::new();
+            calculator.compute(systems, &mut block_descriptor, options).with_context(|| format!(
+                "failed to compute block {} of the pipeline", block
+            ))?;
+
+            for (block_row, &row) in rows.iter().enumerate() {
+                for (block_column, &column) in columns.iter().enumerate() {
+                    descriptor.values[[row, column]] = block_descriptor.values[[block_row, block_column]];
+                }
+            }
+
+            if let Some(ref mut gradients) = descriptor.gradients {
+                let gradients_samples = descriptor.gradients_samples.as_ref().expect("missing gradients samples");
+                let block_gradients = block_descriptor.gradients.as_ref().expect("missing block gradients");
+                let block_gradients_samples = block_descriptor.gradients_samples.as_ref().expect("missing block gradients samples");
+
+                for (block_row, block_gradient_sample) in block_gradients_samples.iter().enumerate() {
+                    let mut gradient_sample = block_gradient_sample.to_vec();
+                    gradient_sample[0] = IndexValue::from(rows[block_gradient_sample[0].usize()]);
+                    let row = gradients_samples.position(&gradient_sample).expect("missing gradient sample in the pipeline");
+
+                    for (block_column, &column) in columns.iter().enumerate() {
+                        gradients[[row, column]] = block_gradients[[block_row, block_column]];
+                    }
+                }
+            }
+        }
+
+        return Ok(());
+    }
+}
+
+/// Samples for a pipeline: the union of the samples of all calculators in the
+/// pipeline, and the union of the corresponding gradients samples.
+struct PipelineSamples {
+    builders: Vec<Box<dyn SamplesBuilder>>,
+}
+
+impl SamplesBuilder for PipelineSamples {
+    fn names(&self) -> Vec<&str> {
+        self.builders[0].names()
+    }
+
+    fn samples(&self, systems: &mut [Box<dyn System>]) -> Result<Indexes, Error> {
+        let mut all_samples = BTreeSet::new();
+        for builder in &self.builders {
+            for sample in builder.samples(systems)?.iter() {
+                all_samples.insert(sample.to_vec());
+            }
+        }
+
+        let mut indexes = IndexesBuilder::new(self.names());
+        for sample in all_samples {
+            indexes.add(&sample);
+        }
+        return Ok(indexes.finish());
+    }
+
+    fn gradients_for(&self, systems: &mut [Box<dyn System>], samples: &Indexes) -> Result<Option<Indexes>, Error> {
+        assert_eq!(samples.names(), self.names());
+
+        let mut all_gradients = BTreeSet::new();
+        for builder in &self.builders {
+            // only use the samples which are part of this builder samples
+            let builder_samples = builder.samples(systems)?;
+            let mut selected = IndexesBuilder::new(self.names());
+            let mut rows = Vec::new();
+            for (sample_i, sample) in samples.iter().enumerate() {
+                if builder_samples.contains(sample) {
+                    selected.add(sample);
+                    rows.push(sample_i);
+                }
+            }
+
+            let gradients = match builder.gradients_for(systems, &selected.finish())? {
+                Some(gradients) => gradients,
+                None => return Ok(None),
+            };
+            assert_eq!(gradients.names(), ["sample", "atom", "spatial"]);
+
+            for gradient_sample in gradients.iter() {
+                let mut gradient_sample = gradient_sample.to_vec();
+                gradient_sample[0] = IndexValue::from(rows[gradient_sample[0].usize()]);
+                all_gradients.insert(gradient_sample);
+            }
+        }
+
+        let mut gradients = IndexesBuilder::new(vec!["sample", "atom", "spatial"]);
+        for gradient_sample in all_gradients {
+            gradients.add(&gradient_sample);
+        }
+        return Ok(Some(gradients.finish()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use crate::systems::test_utils::{test_system, test_systems};
+    use crate::{CalculationOptions, Calculator, Descriptor};
+    use crate::calculators::CalculatorBase;
+
+    use super::{Pipeline, PipelineParameters};
+
+    fn radial_distribution(cutoff: f64, gradients: bool) -> serde_json::Value {
+        serde_json::json!({
+            "name": "radial_distribution",
+            "parameters": {
+                "cutoff": cutoff,
+                "n_points": 6,
+                "smearing": 0.3,
+                "cutoff_function": {"ShiftedCosine": {"width": 0.3}},
+                "gradients": gradients,
+            }
+        })
+    }
+
+    fn pipeline(calculators: &[serde_json::Value]) -> Result<Pipeline, crate::Error> {
+        let parameters = serde_json::from_value::<PipelineParameters>(serde_json::json!({
+            "calculators": calculators
+        })).unwrap();
+        return Pipeline::new(parameters);
+    }
+
+    #[test]
+    fn values() {
+        let mut calculator = Calculator::from(Box::new(
+            pipeline(&[radial_distribution(1.2, false), radial_distribution(3.5, false)]).unwrap()
+        ) as Box<dyn CalculatorBase>);
+
+        let mut systems = test_systems(&["water"]);
+        let mut descriptor = Descriptor::new();
+        calculator.compute(&mut systems, &mut descriptor, Default::default()).unwrap();
+
+        assert_eq!(descriptor.features.names(), ["block", "feature"]);
+        assert_eq!(descriptor.features.count(), 12);
+        // the H-H distance is only inside the second cutoff
+        assert_eq!(descriptor.samples.count(), 5);
+
+        for (block, cutoff) in [1.2, 3.5].iter().enumerate() {
+            let mut reference = Calculator::new(
+                "radial_distribution",
+                radial_distribution(*cutoff, false)["parameters"].to_string(),
+            ).unwrap();
+            let mut expected = Descriptor::new();
+            reference.compute(&mut systems, &mut expected, Default::default()).unwrap();
+
+            for (sample_i, sample) in descriptor.samples.iter().enumerate() {
+                let expected_row = expected.samples.position(sample);
+                for feature in 0..6 {
+                    let value = descriptor.values[[sample_i, 6 * block + feature]];
+                    match expected_row {
+                        Some(row) => assert_relative_eq!(value, expected.values[[row, feature]]),
+                        None => assert_eq!(value, 0.0),
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn neighbors_cutoff() {
+        let mut calculator = Calculator::from(Box::new(
+            pipeline(&[radial_distribution(1.2, true), radial_distribution(3.5, true)]).unwrap()
+        ) as Box<dyn CalculatorBase>);
+
+        let mut systems = test_systems(&["water", "methane"]);
+        let options = CalculationOptions {
+            use_native_system: true,
+            ..Default::default()
+        };
+        let mut expected = Descriptor::new();
+        calculator.compute(&mut systems, &mut expected, options).unwrap();
+
+        let options = CalculationOptions {
+            use_native_system: true,
+            neighbors_cutoff: Some(3.5),
+            ..Default::default()
+        };
+        let mut descriptor = Descriptor::new();
+        calculator.compute(&mut systems, &mut descriptor, options).unwrap();
+
+        assert_eq!(descriptor.samples, expected.samples);
+        assert_eq!(descriptor.gradients_samples, expected.gradients_samples);
+        assert_eq!(descriptor.values, expected.values);
+        assert_eq!(descriptor.gradients, expected.gradients);
+
+        let options = CalculationOptions {
+            neighbors_cutoff: Some(3.5),
+            ..Default::default()
+        };
+        let error = calculator.compute(&mut systems, &mut descriptor, options).unwrap_err();
+        assert_eq!(error.to_string(), "invalid parameter: neighbors_cutoff can only be used with use_native_system");
+    }
+
+    #[test]
+    fn finite_differences() {
+        let calculator = Calculator::from(Box::new(
+            pipeline(&[radial_distribution(1.2, true), radial_distribution(3.5, true)]).unwrap()
+        ) as Box<dyn CalculatorBase>);
+
+        let system = test_system("water");
+        crate::calculators::tests_utils::finite_difference(calculator, system);
+    }
+
+    #[test]
+    fn invalid_parameters() {
+        let error = pipeline(&[]).err().unwrap();
+        assert_eq!(error.to_string(), "invalid parameter: a pipeline must contain at least one calculator");
+
+        let composition = serde_json::json!({
+            "name": "composition",
+            "parameters": {"per_structure": true}
+        });
+        let error = pipeline(&[radial_distribution(3.5, false), composition]).err().unwrap();
+        assert_eq!(error.to_string(),
+            "invalid parameter: all calculators in a pipeline must use the same samples, \
+            got [structure, center, species_center, species_neighbor] for radial distribution \
+            and [structure, species] for composition"
+        );
+
+        let error = pipeline(&[radial_distribution(3.5, false), radial_distribution(3.5, true)]).err().unwrap();
+        assert_eq!(error.to_string(), "invalid parameter: either all or none of the calculators in a pipeline must compute gradients");
+    }
+}
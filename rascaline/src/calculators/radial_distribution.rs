@@ -0,0 +1,325 @@
+use std::collections::BTreeMap;
+
+use super::CalculatorBase;
+use super::soap::CutoffFunction;
+
+use crate::descriptor::{Indexes, IndexesBuilder, IndexValue};
+use crate::descriptor::{SamplesBuilder, TwoBodiesSpeciesSamples};
+use crate::{Descriptor, Error, ErrorContext, System};
+
+/// Parameters for the smoothed radial distribution calculator
+#[derive(Debug, Clone)]
+#[derive(serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+pub struct RadialDistributionParameters {
+    /// Spherical cutoff to use for atomic environments
+    pub cutoff: f64,
+    /// Number of points in the radial grid. The points are at the center of
+    /// `n_points` bins of equal size covering `[0, cutoff]`.
+    pub n_points: usize,
+    /// Width of the gaussian used to broaden each neighbor distance
+    pub smearing: f64,
+    /// Cutoff function used to smooth the behavior around the cutoff radius
+    pub cutoff_function: CutoffFunction,
+    /// Should we also compute gradients of the feature?
+    pub gradients: bool,
+}
+
+/// Smoothed radial distribution of neighbors around each atom.
+///
+/// Each neighbor `j` of a center `i` within the spherical cutoff contributes a
+/// normalized gaussian of width `smearing` centered on the distance `r_ij`,
+/// multiplied by the cutoff function. The features are the values of the
+/// resulting function on a regular grid of `n_points` points, separately for
+/// each neighbor species.
+#[derive(Debug, Clone)]
+pub struct RadialDistribution {
+    parameters: RadialDistributionParameters,
+    /// Positions of the grid points
+    points: Vec<f64>,
+}
+
+impl RadialDistribution {
+    /// Create a new `RadialDistribution` calculator with the given parameters
+    pub fn new(parameters: RadialDistributionParameters) -> Result<RadialDistribution, Error> {
+        parameters.cutoff_function.validate()?;
+
+        if !(parameters.cutoff > 0.0 && parameters.cutoff.is_finite()) {
+            return Err(Error::InvalidParameter(format!(
+                "cutoff must be a positive number for the radial distribution, got {}",
+                parameters.cutoff
+            )));
+        }
+
+        if !(parameters.smearing > 0.0 && parameters.smearing.is_finite()) {
+            return Err(Error::InvalidParameter(format!(
+                "smearing must be a positive number for the radial distribution, got {}",
+                parameters.smearing
+            )));
+        }
+
+        if parameters.n_points == 0 {
+            return Err(Error::InvalidParameter(
+                "n_points must be at least 1 for the radial distribution".into()
+            ));
+        }
+
+        let delta = parameters.cutoff / parameters.n_points as f64;
+        let points = (0..parameters.n_points)
+            .map(|i| (i as f64 + 0.5) * delta)
+            .collect();
+
+        return Ok(RadialDistribution {
+            parameters: parameters,
+            points: points,
+        });
+    }
+
+    /// Compute the contribution of a neighbor at the given `distance` to all
+    /// requested grid `points`, and optionally the derivative of this
+    /// contribution w.r.t. the distance
+    fn compute_distance(&self, distance: f64, points: &[usize], values: &mut [f64], derivatives: Option<&mut [f64]>) {
+        let cutoff = self.parameters.cutoff;
+        let sigma = self.parameters.smearing;
+        let normalization = 1.0 / (f64::sqrt(2.0 * std::f64::consts::PI) * sigma);
+
+        let fc = self.parameters.cutoff_function.compute(distance, cutoff);
+        let dfc = self.parameters.cutoff_function.derivative(distance, cutoff);
+
+        for (value, &point) in values.iter_mut().zip(points) {
+            let delta = self.points[point] - distance;
+            let gaussian = normalization * f64::exp(-0.5 * delta * delta / (sigma * sigma));
+            *value = fc * gaussian;
+        }
+
+        if let Some(derivatives) = derivatives {
+            for (derivative, &point) in derivatives.iter_mut().zip(points) {
+                let delta = self.points[point] - distance;
+                let gaussian = normalization * f64::exp(-0.5 * delta * delta / (sigma * sigma));
+                *derivative = dfc * gaussian + fc * gaussian * delta / (sigma * sigma);
+            }
+        }
+    }
+}
+
+impl CalculatorBase for RadialDistribution {
+    fn name(&self) -> String {
+        "radial distribution".into()
+    }
+
+    fn get_parameters(&self) -> String {
+        serde_json::to_string(&self.parameters).expect("failed to serialize to JSON")
+    }
+
+    fn features_names(&self) -> Vec<&str> {
+        vec!["point"]
+    }
+
+    fn features(&self) -> Indexes {
+        let mut features = IndexesBuilder::new(self.features_names());
+        for point in 0..self.parameters.n_points {
+            features.add(&[IndexValue::from(point)]);
+        }
+        return features.finish();
+    }
+
+    fn samples_builder(&self) -> Box<dyn SamplesBuilder> {
+        Box::new(TwoBodiesSpeciesSamples::new(self.parameters.cutoff))
+    }
+
+    fn compute_gradients(&self) -> bool {
+        self.parameters.gradients
+    }
+
+    fn supports_gradients(&self) -> bool {
+        true
+    }
+
+    fn check_features(&self, indexes: &Indexes) -> Result<(), Error> {
+        assert_eq!(indexes.names(), self.features_names());
+        for value in indexes.iter() {
+            if value[0].usize() >= self.parameters.n_points {
+                return Err(Error::InvalidParameter(format!(
+                    "point index is too large for this radial distribution: \
+                    got {}, expected value lower than {}", value[0].usize(), self.parameters.n_points
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    #[time_graph::instrument(name = "RadialDistribution::compute")]
+    fn compute(&mut self, systems: &mut [Box<dyn System>], descriptor: &mut Descriptor) -> Result<(), Error> {
+        assert_eq!(descriptor.samples.names(), &["structure", "center", "species_center", "species_neighbor"]);
+        assert_eq!(descriptor.features.names(), &["point"]);
+
+        let points = descriptor.features.iter()
+            .map(|feature| feature[0].usize())
+            .collect::<Vec<_>>();
+
+        // sample index for each (structure, center, species_neighbor)
+        let mut samples = BTreeMap::new();
+        for (sample_i, sample) in descriptor.samples.iter().enumerate() {
+            samples.insert((sample[0].usize(), sample[1].usize(), sample[3].i32()), sample_i);
+        }
+
+        // first row of the gradients for each (sample, atom)
+        let mut gradient_rows = BTreeMap::new();
+        if let Some(ref gradients_samples) = descriptor.gradients_samples {
+            for (row, gradient_sample) in gradients_samples.iter().enumerate() {
+                if gradient_sample[2].usize() == 0 {
+                    gradient_rows.insert((gradient_sample[0].usize(), gradient_sample[1].usize()), row);
+                }
+            }
+        }
+
+        let workspace = crate::calculator::workspace();
+        let mut buffers = workspace.take::<Buffers>();
+        let Buffers { values, derivatives } = &mut *buffers;
+        values.clear();
+        values.resize(points.len(), 0.0);
+        derivatives.clear();
+        derivatives.resize(points.len(), 0.0);
+
+        for (i_system, system) in systems.iter_mut().enumerate() {
+            crate::calculator::check_cancelled()?;
+            system.compute_neighbors(self.parameters.cutoff).with_context(|| format!(
+                "failed to compute the neighbors list for structure {}", i_system
+            ))?;
+
+            let species = system.species()?;
+            for pair in system.pairs()? {
+                let compute_derivatives = descriptor.gradients.is_some();
+                self.compute_distance(
+                    pair.distance,
+                    &points,
+                    values,
+                    if compute_derivatives { Some(&mut **derivatives) } else { None },
+                );
+
+                let direction = pair.vector / pair.distance;
+                let environments = [
+                    (pair.first, pair.second, 1.0),
+                    (pair.second, pair.first, -1.0),
+                ];
+                for &(center, neighbor, sign) in &environments {
+                    let sample_i = match samples.get(&(i_system, center, species[neighbor])) {
+                        Some(&sample_i) => sample_i,
+                        None => continue,
+                    };
+
+                    let mut sample_values = descriptor.values.row_mut(sample_i);
+                    for (value, contribution) in sample_values.iter_mut().zip(values.iter()) {
+                        *value += contribution;
+                    }
+
+                    if let Some(ref mut gradients) = descriptor.gradients {
+                        // the distance is r_ij = |r_j - r_i|, so its gradient
+                        // w.r.t. r_j is the direction from i to j, and the
+                        // opposite w.r.t. r_i
+                        let direction = sign * direction;
+                        for &(atom, atom_sign) in &[(neighbor, 1.0), (center, -1.0)] {
+                            let first_row = match gradient_rows.get(&(sample_i, atom)) {
+                                Some(&row) => row,
+                                None => continue,
+                            };
+
+                            for spatial in 0..3 {
+                                let mut gradient = gradients.row_mut(first_row + spatial);
+                                let factor = atom_sign * direction[spatial];
+                                for (value, derivative) in gradient.iter_mut().zip(derivatives.iter()) {
+                                    *value += factor * derivative;
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        return Ok(());
+    }
+}
+
+/// Temporary buffers used by `RadialDistribution::compute`, kept in the
+/// calculator workspace between calls
+#[derive(Default)]
+struct Buffers {
+    values: Vec<f64>,
+    derivatives: Vec<f64>,
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use crate::systems::test_utils::{test_system, test_systems};
+    use crate::{Calculator, Descriptor};
+    use crate::calculators::CalculatorBase;
+    use crate::calculators::soap::CutoffFunction;
+
+    use super::{RadialDistribution, RadialDistributionParameters};
+
+    fn parameters(gradients: bool) -> RadialDistributionParameters {
+        RadialDistributionParameters {
+            cutoff: 3.5,
+            n_points: 20,
+            smearing: 0.3,
+            cutoff_function: CutoffFunction::ShiftedCosine { width: 0.5 },
+            gradients: gradients,
+        }
+    }
+
+    fn calculator(parameters: RadialDistributionParameters) -> Calculator {
+        Calculator::from(Box::new(
+            RadialDistribution::new(parameters).unwrap()
+        ) as Box<dyn CalculatorBase>)
+    }
+
+    #[test]
+    fn values() {
+        let mut systems = test_systems(&["water"]);
+        let mut descriptor = Descriptor::new();
+        calculator(parameters(false)).compute(&mut systems, &mut descriptor, Default::default()).unwrap();
+
+        assert_eq!(descriptor.samples.names(), ["structure", "center", "species_center", "species_neighbor"]);
+        assert_eq!(descriptor.samples.count(), 5);
+        assert_eq!(descriptor.features.count(), 20);
+
+        // the integral of the distribution is the number of neighbors (up to
+        // the cutoff function and the tails of the gaussian)
+        let delta = 3.5 / 20.0;
+        for (sample_i, sample) in descriptor.samples.iter().enumerate() {
+            let expected = match (sample[2].i32(), sample[3].i32()) {
+                // O center, H neighbors
+                (123456, 1) => 2.0,
+                // H center, O or H neighbor
+                (1, 123456 | 1) => 1.0,
+                _ => panic!("unexpected sample {:?}", sample),
+            };
+
+            let integral = descriptor.values.row(sample_i).sum() * delta;
+            assert_relative_eq!(integral, expected, max_relative = 2e-3);
+        }
+
+        // the maximal value is at the point closest to the O-H distance
+        let sample_i = descriptor.samples.iter().position(|sample| sample[2].i32() == 123456).unwrap();
+        let row = descriptor.values.row(sample_i);
+        let max_point = (0..20).max_by(|&a, &b| row[a].partial_cmp(&row[b]).unwrap()).unwrap();
+        assert_eq!(max_point, 5);
+    }
+
+    #[test]
+    fn finite_differences() {
+        let calculator = calculator(parameters(true));
+        let system = test_system("water");
+        crate::calculators::tests_utils::finite_difference(calculator, system);
+    }
+
+    #[test]
+    fn invalid_parameters() {
+        let mut parameters = parameters(false);
+        parameters.n_points = 0;
+        let error = RadialDistribution::new(parameters).unwrap_err();
+        assert_eq!(error.to_string(), "invalid parameter: n_points must be at least 1 for the radial distribution");
+    }
+}
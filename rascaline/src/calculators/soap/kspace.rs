@@ -0,0 +1,521 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::f64::consts::PI;
+
+use ndarray::Array2;
+
+use crate::{Error, System, Vector3D};
+use crate::descriptor::{Descriptor, Indexes, IndexesBuilder, IndexValue, SamplesBuilder};
+use crate::math::SphericalHarmonicsCache;
+use crate::math::quadrature::Quadrature;
+use crate::math::special::spherical_bessel;
+
+use super::{GtoRadialIntegral, GtoParameters};
+use super::{CutoffFunction, SphericalExpansionParameters};
+
+/// Parameters for the evaluation of the spherical expansion in reciprocal
+/// space.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+pub struct KSpaceParameters {
+    /// Cutoff in reciprocal space: only wave vectors with a norm smaller than
+    /// this value are included. The Fourier transform of the atomic density
+    /// decays as `exp(-σ^2 k^2 / 2)` where σ is the atomic gaussian width, so
+    /// a value around `6 / σ` is usually enough.
+    pub k_cutoff: f64,
+}
+
+/// Evaluation of the spherical expansion of the periodic atomic density in
+/// reciprocal space.
+///
+/// The density of all the atoms in a given channel `ρ(r) = Σ_j w_j exp(-|r -
+/// r_j|^2 / 2σ^2)` (summed over all periodic images) is written as a Fourier
+/// series, and the expansion coefficients around the center `i` are given by
+///
+/// ```text
+/// c_nlm(i) = 4π/V Σ_k ρ(k) J_nl(k) Y_lm(k/|k|) Re[i^l exp(i k·r_i) S(k)]
+/// ```
+///
+/// where `S(k) = Σ_j w_j exp(-i k·r_j)` is the structure factor, `ρ(k) = (2π
+/// σ^2)^{3/2} exp(-σ^2 k^2 / 2)` is the Fourier transform of a single atomic
+/// gaussian, and `J_nl(k) = ∫ R_n(x) f_c(x) s(x) j_l(k x) x^2 dx` is the
+/// integral of the radial basis multiplied by the cutoff function and radial
+/// scaling over the cutoff sphere, computed with a Gauss-Legendre quadrature.
+pub(super) struct KSpaceExpansion {
+    /// Number of angular channels
+    max_angular: usize,
+    /// Cutoff in reciprocal space
+    k_cutoff: f64,
+    /// Width of the atomic gaussian density
+    atomic_gaussian_width: f64,
+    /// Nodes of the radial quadrature
+    nodes: Vec<f64>,
+    /// `R_n(x) f_c(x) s(x) x^2 w` for all quadrature nodes `x` (with weight
+    /// `w`), in a `max_radial x n_nodes` array
+    radial: Array2<f64>,
+}
+
+impl KSpaceExpansion {
+    pub fn new(parameters: &SphericalExpansionParameters, kspace: KSpaceParameters) -> Result<KSpaceExpansion, Error> {
+        if !(kspace.k_cutoff > 0.0 && kspace.k_cutoff.is_finite()) {
+            return Err(Error::InvalidParameter(format!(
+                "k_cutoff must be a positive number for the k-space spherical expansion, got {}",
+                kspace.k_cutoff
+            )));
+        }
+
+        let gto = GtoRadialIntegral::new(GtoParameters {
+            max_radial: parameters.max_radial,
+            max_angular: parameters.max_angular,
+            atomic_gaussian_width: parameters.atomic_gaussian_width,
+            cutoff: parameters.cutoff,
+        })?;
+
+        // the integrand oscillates with a period of 2π / k, use enough nodes
+        // to resolve the oscillations for all k below the cutoff
+        let cutoff = parameters.cutoff;
+        let order = (kspace.k_cutoff * cutoff) as usize + 2 * parameters.max_radial + 30;
+        let quadrature = Quadrature::gauss_legendre(order);
+
+        // the shifted cosine cutoff function is only smooth by parts, so we
+        // integrate separately on both sides of the kink
+        let mut segments = Vec::new();
+        match parameters.cutoff_function {
+            CutoffFunction::ShiftedCosine { width } if width > 0.0 && width < cutoff => {
+                segments.push(quadrature.rescaled(0.0, cutoff - width));
+                segments.push(quadrature.rescaled(cutoff - width, cutoff));
+            }
+            _ => segments.push(quadrature.rescaled(0.0, cutoff)),
+        }
+
+        let n_nodes = segments.iter().map(Quadrature::order).sum();
+        let mut nodes = Vec::with_capacity(n_nodes);
+        let mut radial = Array2::zeros((parameters.max_radial, n_nodes));
+        let mut basis = vec![0.0; parameters.max_radial];
+        for segment in &segments {
+            for (&x, &weight) in segment.nodes.iter().zip(&segment.weights) {
+                gto.radial_basis(x, &mut basis);
+                let factor = weight * x * x
+                    * parameters.cutoff_function.compute(x, cutoff)
+                    * parameters.radial_scaling.compute(x);
+
+                let node = nodes.len();
+                for (n, value) in basis.iter().enumerate() {
+                    radial[[n, node]] = factor * value;
+                }
+                nodes.push(x);
+            }
+        }
+
+        return Ok(KSpaceExpansion {
+            max_angular: parameters.max_angular,
+            k_cutoff: kspace.k_cutoff,
+            atomic_gaussian_width: parameters.atomic_gaussian_width,
+            nodes: nodes,
+            radial: radial,
+        });
+    }
+
+    /// Compute the `J_nl(k)` radial integrals, and store them in `values`
+    fn radial_integral(&self, k: f64, bessel: &mut Array2<f64>, values: &mut Array2<f64>) {
+        for (node, &x) in self.nodes.iter().enumerate() {
+            let mut row = bessel.row_mut(node);
+            spherical_bessel(self.max_angular, k * x, row.as_slice_mut().expect("bessel rows are contiguous"));
+        }
+        values.assign(&self.radial.dot(&*bessel));
+    }
+
+    /// Compute the spherical expansion for all the samples in `descriptor`
+    /// associated with the structure `i_system`. `channels` contains the
+    /// neighbor channel of all atoms in the system, and `weights` their
+    /// weight (if any).
+    #[time_graph::instrument(name = "KSpaceExpansion::compute_system")]
+    pub fn compute_system(
+        &self,
+        i_system: usize,
+        system: &dyn System,
+        channels: &[i32],
+        weights: Option<&[f64]>,
+        descriptor: &mut Descriptor,
+    ) -> Result<(), Error> {
+        if system.cell()?.is_infinite() {
+            return Err(Error::InvalidParameter(format!(
+                "the k-space spherical expansion requires periodic systems, \
+                but structure {} does not have a unit cell", i_system
+            )));
+        }
+
+        let positions = system.positions()?;
+        let weight = |atom: usize| weights.map_or(1.0, |weights| weights[atom]);
+
+        // (sample index, center, channel) for all samples in this system
+        let samples = descriptor.samples.iter()
+            .enumerate()
+            .filter(|(_, sample)| sample[0].usize() == i_system)
+            .map(|(sample_i, sample)| (sample_i, sample[1].usize(), sample[3].i32()))
+            .collect::<Vec<_>>();
+
+        if samples.is_empty() {
+            return Ok(());
+        }
+
+        // rows of the gradients array for all (sample, atom) pairs
+        let mut gradient_rows = BTreeMap::new();
+        if let Some(ref gradients_samples) = descriptor.gradients_samples {
+            for (row, gradient_sample) in gradients_samples.iter().enumerate() {
+                let sample_i = gradient_sample[0].usize();
+                let atom = gradient_sample[1].usize();
+                let spatial = gradient_sample[2].usize();
+
+                if descriptor.samples[sample_i][0].usize() != i_system {
+                    continue;
+                }
+
+                gradient_rows.entry(sample_i)
+                    .or_insert_with(BTreeMap::new)
+                    .entry(atom)
+                    .or_insert([0; 3])[spatial] = row;
+            }
+        }
+
+        let features = descriptor.features.iter()
+            .map(|feature| (feature[0].usize(), feature[1].isize(), feature[2].usize()))
+            .collect::<Vec<_>>();
+
+        let volume = system.cell()?.volume();
+        let sigma2 = self.atomic_gaussian_width * self.atomic_gaussian_width;
+        let density_normalization = 4.0 * PI / volume * f64::powf(2.0 * PI * sigma2, 1.5);
+
+        let max_radial = self.radial.shape()[0];
+        let mut bessel = Array2::zeros((self.nodes.len(), self.max_angular + 1));
+        let mut radial_integral = Array2::zeros((max_radial, self.max_angular + 1));
+        let mut spherical_harmonics = SphericalHarmonicsCache::new(self.max_angular, false);
+        let mut prefactors = vec![0.0; features.len()];
+
+        let mut kvectors = system.cell()?.reciprocal_half_space(self.k_cutoff);
+        kvectors.push(Vector3D::zero());
+        for k in kvectors {
+            let k_norm = k.norm();
+            self.radial_integral(k_norm, &mut bessel, &mut radial_integral);
+
+            let mut prefactor = density_normalization * f64::exp(-0.5 * sigma2 * k_norm * k_norm);
+            let direction = if k_norm == 0.0 {
+                // only l=0 contributes for k=0 (since j_l(0) = 0 for l > 0),
+                // and the corresponding spherical harmonic does not depend
+                // on the direction
+                Vector3D::new(0.0, 0.0, 1.0)
+            } else {
+                // we only include one of k and -k
+                prefactor *= 2.0;
+                k / k_norm
+            };
+
+            spherical_harmonics.compute_no_gradients(direction);
+            for (feature_i, &(l, m, n)) in features.iter().enumerate() {
+                prefactors[feature_i] = prefactor
+                    * radial_integral[[n, l]]
+                    * spherical_harmonics.values[[l as isize, m]];
+            }
+
+            // exp(-i k·r_j) for all atoms
+            let phases = positions.iter()
+                .map(|&position| {
+                    let (sin, cos) = f64::sin_cos(k * position);
+                    Complex::new(cos, -sin)
+                })
+                .collect::<Vec<_>>();
+
+            let mut structure_factors = BTreeMap::new();
+            for (atom, phase) in phases.iter().enumerate() {
+                let factor = structure_factors.entry(channels[atom]).or_insert_with(Complex::zero);
+                *factor += phase.scale(weight(atom));
+            }
+
+            for &(sample_i, center, channel) in &samples {
+                let structure_factor = structure_factors.get(&channel).copied().unwrap_or_else(Complex::zero);
+                let center_phase = phases[center].conj();
+                let density = center_phase * structure_factor;
+
+                let mut values = descriptor.values.row_mut(sample_i);
+                for (feature_i, &(l, _, _)) in features.iter().enumerate() {
+                    values[feature_i] += prefactors[feature_i] * density.real_times_i_power(l);
+                }
+
+                let rows = match gradient_rows.get(&sample_i) {
+                    Some(rows) => rows,
+                    None => continue,
+                };
+
+                let gradients = descriptor.gradients.as_mut().expect("missing gradients");
+                for (&atom, rows) in rows {
+                    // derivative of the density with respect to the position
+                    // of `atom`, without the `i k` factor
+                    let (density, sign) = if atom == center {
+                        let mut others = density;
+                        if channels[center] == channel {
+                            // the center does not move relative to itself
+                            others -= Complex::new(weight(center), 0.0);
+                        }
+                        (others, 1.0)
+                    } else if channels[atom] == channel {
+                        (center_phase * phases[atom].scale(weight(atom)), -1.0)
+                    } else {
+                        continue;
+                    };
+
+                    for (spatial, &row) in rows.iter().enumerate() {
+                        let factor = sign * k[spatial];
+                        if factor == 0.0 {
+                            continue;
+                        }
+
+                        let mut gradient = gradients.row_mut(row);
+                        for (feature_i, &(l, _, _)) in features.iter().enumerate() {
+                            gradient[feature_i] += factor * prefactors[feature_i] * density.real_times_i_power(l + 1);
+                        }
+                    }
+                }
+            }
+        }
+
+        return Ok(());
+    }
+}
+
+/// Minimal complex number implementation for the structure factors
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Complex {
+    re: f64,
+    im: f64,
+}
+
+impl Complex {
+    fn new(re: f64, im: f64) -> Complex {
+        Complex { re, im }
+    }
+
+    fn zero() -> Complex {
+        Complex::new(0.0, 0.0)
+    }
+
+    fn conj(self) -> Complex {
+        Complex::new(self.re, -self.im)
+    }
+
+    fn scale(self, factor: f64) -> Complex {
+        Complex::new(factor * self.re, factor * self.im)
+    }
+
+    /// Get the real part of `i^power * self`
+    fn real_times_i_power(self, power: usize) -> f64 {
+        match power % 4 {
+            0 => self.re,
+            1 => -self.im,
+            2 => -self.re,
+            _ => self.im,
+        }
+    }
+}
+
+impl std::ops::Mul for Complex {
+    type Output = Complex;
+    fn mul(self, other: Complex) -> Complex {
+        Complex::new(
+            self.re * other.re - self.im * other.im,
+            self.re * other.im + self.im * other.re,
+        )
+    }
+}
+
+impl std::ops::AddAssign for Complex {
+    fn add_assign(&mut self, other: Complex) {
+        self.re += other.re;
+        self.im += other.im;
+    }
+}
+
+impl std::ops::SubAssign for Complex {
+    fn sub_assign(&mut self, other: Complex) {
+        self.re -= other.re;
+        self.im -= other.im;
+    }
+}
+
+/// Samples for the k-space spherical expansion. The density is not truncated
+/// to the neighbors of the center, so the samples contain all neighbor
+/// channels present in a structure around every center; and the gradients
+/// samples contain the center and all the atoms in the corresponding channel.
+pub(super) struct KSpaceSamples {
+    /// Channel to use for each neighbor species, species not in this map are
+    /// used as their own channel
+    pub channels: BTreeMap<i32, i32>,
+}
+
+impl KSpaceSamples {
+    fn channel(&self, species: i32) -> i32 {
+        return self.channels.get(&species).copied().unwrap_or(species);
+    }
+}
+
+impl SamplesBuilder for KSpaceSamples {
+    fn names(&self) -> Vec<&str> {
+        vec!["structure", "center", "species_center", "species_neighbor"]
+    }
+
+    fn samples(&self, systems: &mut [Box<dyn System>]) -> Result<Indexes, Error> {
+        let mut indexes = IndexesBuilder::new(self.names());
+        for (i_system, system) in systems.iter().enumerate() {
+            let species = system.species()?;
+            let channels = species.iter()
+                .map(|&species| self.channel(species))
+                .collect::<BTreeSet<_>>();
+
+            for (center, &species_center) in species.iter().enumerate() {
+                for &channel in &channels {
+                    indexes.add(&[
+                        IndexValue::from(i_system),
+                        IndexValue::from(center),
+                        IndexValue::from(species_center),
+                        IndexValue::from(channel),
+                    ]);
+                }
+            }
+        }
+
+        return Ok(indexes.finish());
+    }
+
+    fn gradients_for(&self, systems: &mut [Box<dyn System>], samples: &Indexes) -> Result<Option<Indexes>, Error> {
+        assert_eq!(samples.names(), self.names());
+
+        let mut gradients = IndexesBuilder::new(vec!["sample", "atom", "spatial"]);
+        for (i_sample, sample) in samples.iter().enumerate() {
+            let system = &systems[sample[0].usize()];
+            let center = sample[1].usize();
+            let channel = sample[3].i32();
+
+            for (atom, &species) in system.species()?.iter().enumerate() {
+                if atom == center || self.channel(species) == channel {
+                    for spatial in 0..3 {
+                        gradients.add(&[
+                            IndexValue::from(i_sample), IndexValue::from(atom), IndexValue::from(spatial)
+                        ]);
+                    }
+                }
+            }
+        }
+
+        return Ok(Some(gradients.finish()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use crate::systems::test_utils::test_system;
+    use crate::systems::{SimpleSystem, UnitCell};
+    use crate::{Calculator, Descriptor, Vector3D};
+    use crate::calculators::CalculatorBase;
+
+    use super::super::{SphericalExpansion, SphericalExpansionParameters};
+    use super::super::{CutoffFunction, RadialBasis, RadialScaling};
+    use super::KSpaceParameters;
+
+    fn parameters(gradients: bool, kspace: Option<KSpaceParameters>) -> SphericalExpansionParameters {
+        SphericalExpansionParameters {
+            atomic_gaussian_width: 0.3,
+            cutoff: 4.0,
+            cutoff_function: CutoffFunction::ShiftedCosine { width: 0.5 },
+            gradients: gradients,
+            max_radial: 4,
+            max_angular: 3,
+            max_radial_per_angular: None,
+            radial_basis: RadialBasis::Gto {},
+            radial_scaling: RadialScaling::None {},
+            center_atom_weight: 1.0,
+            neighbor_weights: None,
+            neighbor_channels: Default::default(),
+            exclude_self_pairs: false,
+            first_shell_tolerance: None,
+            spherical_harmonics: Default::default(),
+            kspace: kspace,
+        }
+    }
+
+    fn calculator(parameters: SphericalExpansionParameters) -> Calculator {
+        Calculator::from(Box::new(
+            SphericalExpansion::new(parameters).unwrap()
+        ) as Box<dyn CalculatorBase>)
+    }
+
+    #[test]
+    fn real_space_equivalence() {
+        // all atoms are far from the cutoff and from their periodic images,
+        // so the k-space and real space expansions should match
+        let mut systems = vec![Box::new(test_system("water")) as Box<dyn crate::System>];
+
+        let mut reference = Descriptor::new();
+        calculator(parameters(false, None))
+            .compute(&mut systems, &mut reference, Default::default())
+            .unwrap();
+
+        let mut descriptor = Descriptor::new();
+        calculator(parameters(false, Some(KSpaceParameters { k_cutoff: 20.0 })))
+            .compute(&mut systems, &mut descriptor, Default::default())
+            .unwrap();
+
+        assert_eq!(descriptor.samples, reference.samples);
+        assert_eq!(descriptor.features, reference.features);
+        assert_relative_eq!(descriptor.values, reference.values, epsilon = 1e-6, max_relative = 1e-6);
+    }
+
+    #[test]
+    fn finite_differences() {
+        let calculator = calculator(parameters(true, Some(KSpaceParameters { k_cutoff: 6.0 })));
+
+        let mut system = SimpleSystem::new(UnitCell::cubic(4.0));
+        system.add_atom(8, Vector3D::new(0.0, 0.0, 0.0));
+        system.add_atom(1, Vector3D::new(0.1, 0.75545, -0.58895));
+        system.add_atom(1, Vector3D::new(0.0, -0.75545, -0.48895));
+        system.add_atom(1, Vector3D::new(2.0, 1.5, 1.2));
+        crate::calculators::tests_utils::finite_difference(calculator, system);
+    }
+
+    #[test]
+    fn invalid_parameters() {
+        let error = SphericalExpansion::new(
+            parameters(false, Some(KSpaceParameters { k_cutoff: -1.0 }))
+        ).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "invalid parameter: k_cutoff must be a positive number for the k-space spherical expansion, got -1"
+        );
+
+        let mut parameters = parameters(false, Some(KSpaceParameters { k_cutoff: 6.0 }));
+        parameters.center_atom_weight = 0.5;
+        let error = SphericalExpansion::new(parameters).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "invalid parameter: center_atom_weight must be 1 when using the k-space spherical expansion, got 0.5"
+        );
+    }
+
+    #[test]
+    fn non_periodic_systems() {
+        let mut system = SimpleSystem::new(UnitCell::infinite());
+        system.add_atom(1, Vector3D::new(0.0, 0.0, 0.0));
+        system.add_atom(1, Vector3D::new(0.0, 0.0, 1.0));
+        let mut systems = vec![Box::new(system) as Box<dyn crate::System>];
+
+        let mut descriptor = Descriptor::new();
+        let error = calculator(parameters(false, Some(KSpaceParameters { k_cutoff: 6.0 })))
+            .compute(&mut systems, &mut descriptor, Default::default())
+            .unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "invalid parameter: the k-space spherical expansion requires periodic systems, \
+            but structure 0 does not have a unit cell"
+        );
+    }
+}
@@ -0,0 +1,622 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use ndarray::parallel::prelude::*;
+
+use crate::descriptor::{SamplesBuilder, IndexValue, Indexes, IndexesBuilder};
+use crate::descriptor::{TwoBodiesSpeciesSamples, ThreeBodiesSpeciesSamples};
+
+use crate::{CalculationOptions, Calculator, SelectedIndexes};
+use crate::{Descriptor, Error, ErrorContext, System};
+use crate::math::ClebschGordan;
+
+use super::{super::CalculatorBase, SphericalExpansionParameters};
+use super::{SphericalExpansion, RadialBasis, CutoffFunction, RadialScaling};
+use super::SphericalHarmonicsConvention;
+use super::spherical_expansion::{default_center_atom_weight, hyper_parameters};
+
+/// Parameters for the lambda-SOAP calculator.
+///
+/// lambda-SOAP contains covariant features transforming like the spherical
+/// harmonics `Y_{lambda mu}` under rotations, built from the spherical
+/// expansion coefficients of two neighbor species with angular channels `l1`
+/// and `l2`:
+///
+/// `< n1 l1 n2 l2; lambda mu | X_i > = \sum_{m1 m2} C^{lambda mu}_{l1 m1 l2 m2}
+/// < n1 l1 m1 | X_i > < n2 l2 m2 | X_i >`
+///
+/// where the `C` are the Clebsch-Gordan coefficients for real spherical
+/// harmonics. `lambda = 1` gives vectors (for example to learn dipole moments)
+/// and `lambda = 2` gives symmetric traceless tensors (for example to learn
+/// polarizabilities or quadrupoles).
+///
+/// See [this paper](https://doi.org/10.1103/PhysRevLett.120.036002) for more
+/// information on the lambda-SOAP representation.
+#[derive(Debug, Clone)]
+#[derive(serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+pub struct LambdaSoapParameters {
+    /// Spherical cutoff to use for atomic environments
+    pub cutoff: f64,
+    /// Number of radial basis function to use
+    pub max_radial: usize,
+    /// Number of spherical harmonics to use
+    pub max_angular: usize,
+    /// Width of the atom-centered gaussian creating the atomic density
+    pub atomic_gaussian_width: f64,
+    /// Should we also compute gradients of the feature?
+    pub gradients: bool,
+    /// radial basis to use for the radial integral
+    pub radial_basis: RadialBasis,
+    /// cutoff function used to smooth the behavior around the cutoff radius
+    pub cutoff_function: CutoffFunction,
+    /// radial scaling can be used to reduce the importance of neighbor atoms
+    /// further away from the center, usually improving the performance of the
+    /// model
+    #[serde(default)]
+    pub radial_scaling: RadialScaling,
+    /// Weight of the central atom own density in its environment. Setting
+    /// this to 0 removes the contribution of the central atom, and any other
+    /// value scales it.
+    #[serde(default = "default_center_atom_weight")]
+    pub center_atom_weight: f64,
+    /// Angular order of the features, i.e. how they transform under rotations
+    pub lambda: usize,
+    /// Behavior of the features under inversion: `1` for proper tensors, only
+    /// using `l1 + l2 + lambda` even; and `-1` for pseudo-tensors, only using
+    /// `l1 + l2 + lambda` odd. The default is `1`.
+    #[serde(default = "default_parity")]
+    pub parity: i32,
+}
+
+fn default_parity() -> i32 {
+    1
+}
+
+/// Calculator implementing the lambda-SOAP representation, i.e. per-atom
+/// features transforming like spherical harmonics of degree `lambda` under
+/// rotations. These can be used to learn vectorial or tensorial properties
+/// directly.
+///
+/// The features are indexed by `l1, l2, n1, n2, mu`, with `mu` going from
+/// `-lambda` to `lambda` and corresponding to the `m` index of real spherical
+/// harmonics. For `lambda = 1`, `mu = 1, -1, 0` correspond to the `x`, `y` and
+/// `z` components of a vector.
+pub struct LambdaSoap {
+    parameters: LambdaSoapParameters,
+    spherical_expansion_calculator: Calculator,
+    spherical_expansion: Descriptor,
+    clebsch_gordan: ClebschGordan,
+}
+
+impl LambdaSoap {
+    pub fn new(parameters: LambdaSoapParameters) -> Result<LambdaSoap, Error> {
+        if parameters.parity != 1 && parameters.parity != -1 {
+            return Err(Error::InvalidParameter(format!(
+                "parity must be 1 or -1 for lambda-SOAP, got {}", parameters.parity
+            )));
+        }
+
+        if angular_pairs(parameters.max_angular, parameters.lambda, parameters.parity).is_empty() {
+            return Err(Error::InvalidParameter(format!(
+                "there are no pairs of angular channels up to max_angular={} \
+                coupling to lambda={} with parity {}",
+                parameters.max_angular, parameters.lambda, parameters.parity
+            )));
+        }
+
+        let expansion_parameters = SphericalExpansionParameters {
+            cutoff: parameters.cutoff,
+            max_radial: parameters.max_radial,
+            max_angular: parameters.max_angular,
+            max_radial_per_angular: None,
+            atomic_gaussian_width: parameters.atomic_gaussian_width,
+            gradients: parameters.gradients,
+            radial_basis: parameters.radial_basis,
+            cutoff_function: parameters.cutoff_function,
+            radial_scaling: parameters.radial_scaling,
+            center_atom_weight: parameters.center_atom_weight,
+            neighbor_weights: None,
+            neighbor_channels: BTreeMap::new(),
+            exclude_self_pairs: false,
+            first_shell_tolerance: None,
+            spherical_harmonics: SphericalHarmonicsConvention::default(),
+            kspace: None,
+        };
+
+        let spherical_expansion = SphericalExpansion::new(expansion_parameters)?;
+
+        return Ok(LambdaSoap {
+            parameters: parameters,
+            spherical_expansion_calculator: Calculator::from(
+                Box::new(spherical_expansion) as Box<dyn CalculatorBase>
+            ),
+            spherical_expansion: Descriptor::new(),
+            clebsch_gordan: ClebschGordan::new(),
+        });
+    }
+
+    /// Create the list of spherical expansion samples required to compute the
+    /// lambda-SOAP `samples` requested by the user
+    fn get_expansion_samples(samples: &Indexes) -> Indexes {
+        let mut set = BTreeSet::new();
+        for sample in samples {
+            set.insert([sample[0], sample[1], sample[2], sample[3]]);
+            set.insert([sample[0], sample[1], sample[2], sample[4]]);
+        }
+
+        let mut spherical_expansion_samples = IndexesBuilder::new(
+            TwoBodiesSpeciesSamples::new(1.0).names()
+        );
+        for index in set {
+            spherical_expansion_samples.add(&index);
+        }
+
+        return spherical_expansion_samples.finish()
+    }
+
+    /// Create the list of spherical expansion features required to compute the
+    /// lambda-SOAP `features` requested by the user
+    fn get_expansion_features(features: &Indexes) -> Indexes {
+        let mut set = BTreeSet::new();
+        for feature in features {
+            for &(l, n) in &[(feature[0], feature[2]), (feature[1], feature[3])] {
+                let l = l.isize();
+                for m in -l..=l {
+                    set.insert([IndexValue::from(l), IndexValue::from(m), n]);
+                }
+            }
+        }
+
+        let mut spherical_expansion_features = IndexesBuilder::new(vec!["l", "m", "n"]);
+        for index in set {
+            spherical_expansion_features.add(&index);
+        }
+
+        return spherical_expansion_features.finish();
+    }
+
+    /// Get the non-zero terms in the sum defining each of the `features`, as
+    /// a list of `(first, second, coefficient)` where `first` and `second` are
+    /// the positions of the spherical expansion features to multiply together.
+    fn get_terms(&mut self, features: &Indexes) -> Vec<Vec<(usize, usize, f64)>> {
+        let expansion_features = &self.spherical_expansion.features;
+        let lambda = self.parameters.lambda;
+
+        let mut all_terms = Vec::with_capacity(features.count());
+        for feature in features {
+            let l1 = feature[0].usize();
+            let l2 = feature[1].usize();
+            let n1 = feature[2];
+            let n2 = feature[3];
+            let mu = feature[4].isize();
+
+            let coefficients = self.clebsch_gordan.real(l1, l2, lambda);
+
+            let mut terms = Vec::new();
+            for m1 in -(l1 as isize)..=(l1 as isize) {
+                for m2 in -(l2 as isize)..=(l2 as isize) {
+                    let coefficient = coefficients[[
+                        (m1 + l1 as isize) as usize,
+                        (m2 + l2 as isize) as usize,
+                        (mu + lambda as isize) as usize,
+                    ]];
+
+                    if coefficient == 0.0 {
+                        continue;
+                    }
+
+                    let first = expansion_features.position(
+                        &[IndexValue::from(l1), IndexValue::from(m1), n1]
+                    ).expect("missing feature `l1, m1, n1` in spherical expansion");
+                    let second = expansion_features.position(
+                        &[IndexValue::from(l2), IndexValue::from(m2), n2]
+                    ).expect("missing feature `l2, m2, n2` in spherical expansion");
+
+                    terms.push((first, second, coefficient));
+                }
+            }
+            all_terms.push(terms);
+        }
+
+        return all_terms;
+    }
+}
+
+/// Get all the pairs of angular channels `(l1, l2)` up to `max_angular` which
+/// couple to `lambda` with the given `parity`
+fn angular_pairs(max_angular: usize, lambda: usize, parity: i32) -> Vec<(usize, usize)> {
+    let mut pairs = Vec::new();
+    for l1 in 0..=max_angular {
+        for l2 in 0..=max_angular {
+            let triangle = l1 + l2 >= lambda && l1 + lambda >= l2 && l2 + lambda >= l1;
+            let pair_parity = 1 - 2 * ((l1 + l2 + lambda) % 2) as i32;
+            if triangle && pair_parity == parity {
+                pairs.push((l1, l2));
+            }
+        }
+    }
+    return pairs;
+}
+
+impl std::fmt::Debug for LambdaSoap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self.parameters)
+    }
+}
+
+impl CalculatorBase for LambdaSoap {
+    fn name(&self) -> String {
+        "lambda-SOAP".into()
+    }
+
+    fn get_parameters(&self) -> String {
+        serde_json::to_string(&self.parameters).expect("failed to serialize to JSON")
+    }
+
+    fn features_names(&self) -> Vec<&str> {
+        vec!["l1", "l2", "n1", "n2", "mu"]
+    }
+
+    fn features(&self) -> Indexes {
+        let lambda = self.parameters.lambda as isize;
+        let mut features = IndexesBuilder::new(self.features_names());
+        for (l1, l2) in angular_pairs(self.parameters.max_angular, self.parameters.lambda, self.parameters.parity) {
+            for n1 in 0..self.parameters.max_radial {
+                for n2 in 0..self.parameters.max_radial {
+                    for mu in -lambda..=lambda {
+                        features.add(&[
+                            IndexValue::from(l1), IndexValue::from(l2),
+                            IndexValue::from(n1), IndexValue::from(n2),
+                            IndexValue::from(mu),
+                        ]);
+                    }
+                }
+            }
+        }
+        return features.finish();
+    }
+
+    fn samples_builder(&self) -> Box<dyn SamplesBuilder> {
+        Box::new(ThreeBodiesSpeciesSamples::with_self_contribution(self.parameters.cutoff))
+    }
+
+    fn compute_gradients(&self) -> bool {
+        self.parameters.gradients
+    }
+
+    fn supports_gradients(&self) -> bool {
+        true
+    }
+
+    fn hyper_parameters(&self) -> Vec<String> {
+        hyper_parameters(&self.parameters.cutoff_function, &self.parameters.radial_scaling)
+    }
+
+    fn check_features(&self, indexes: &Indexes) -> Result<(), Error> {
+        assert_eq!(indexes.names(), self.features_names());
+        let lambda = self.parameters.lambda;
+        let pairs = angular_pairs(self.parameters.max_angular, lambda, self.parameters.parity);
+        for value in indexes {
+            let l1 = value[0].usize();
+            let l2 = value[1].usize();
+            let n1 = value[2].usize();
+            let n2 = value[3].usize();
+            let mu = value[4].isize();
+
+            if !pairs.contains(&(l1, l2)) {
+                return Err(Error::InvalidParameter(format!(
+                    "'l1'={} and 'l2'={} do not couple to lambda={} with parity {} \
+                    for this LambdaSoap", l1, l2, lambda, self.parameters.parity
+                )));
+            }
+
+            if n1 >= self.parameters.max_radial || n2 >= self.parameters.max_radial {
+                return Err(Error::InvalidParameter(format!(
+                    "'n1' and 'n2' must be below {} for this LambdaSoap, got {} and {}",
+                    self.parameters.max_radial, n1, n2
+                )));
+            }
+
+            if mu.abs() > lambda as isize {
+                return Err(Error::InvalidParameter(format!(
+                    "'mu' is not inside [-lambda, lambda]: got mu={} but lambda={}", mu, lambda
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn save_state(&self) -> Result<Option<serde_json::Value>, Error> {
+        self.spherical_expansion_calculator.save_state()
+    }
+
+    fn precompute(&mut self) -> Result<(), Error> {
+        self.spherical_expansion_calculator.precompute()
+    }
+
+    fn load_state(&mut self, state: serde_json::Value) -> Result<(), Error> {
+        self.spherical_expansion_calculator.load_state(state)
+    }
+
+    #[time_graph::instrument(name = "LambdaSoap::compute")]
+    fn compute(&mut self, systems: &mut [Box<dyn System>], descriptor: &mut Descriptor) -> Result<(), Error> {
+        assert_eq!(descriptor.samples.names(), self.samples_builder().names());
+        assert_eq!(descriptor.features.names(), self.features_names());
+
+        let options = CalculationOptions {
+            selected_samples: SelectedIndexes::Subset(LambdaSoap::get_expansion_samples(&descriptor.samples)),
+            selected_features: SelectedIndexes::Subset(LambdaSoap::get_expansion_features(&descriptor.features)),
+            // use the same parallelization strategy as lambda-SOAP
+            parallelism: crate::calculator::parallelism(),
+            deterministic: crate::calculator::deterministic(),
+            ..Default::default()
+        };
+
+        self.spherical_expansion_calculator.compute(
+            systems,
+            &mut self.spherical_expansion,
+            options,
+        ).context("failed to compute the spherical expansion")?;
+
+        let all_terms = self.get_terms(&descriptor.features);
+
+        let spherical_expansion_samples = &self.spherical_expansion.samples;
+        let spherical_expansion_values = &self.spherical_expansion.values;
+
+        let samples = &descriptor.samples;
+        let values = &mut descriptor.values;
+
+        // positions of the spherical expansion samples for both neighbor
+        // species of a given sample
+        let neighbors_samples = |sample_i: usize| {
+            let sample = &samples[sample_i];
+            let neighbor_1 = spherical_expansion_samples.position(&[
+                sample[0], sample[1], sample[2], sample[3]
+            ]).expect("missing data for one of the neighbor species");
+            let neighbor_2 = spherical_expansion_samples.position(&[
+                sample[0], sample[1], sample[2], sample[4]
+            ]).expect("missing data for one of the neighbor species");
+            return (neighbor_1, neighbor_2);
+        };
+
+        crate::calculator::install(|| {
+            values.axis_iter_mut(ndarray::Axis(0))
+                .into_par_iter()
+                .enumerate()
+                .for_each(|(sample_i, mut value)| {
+                    let (neighbor_1, neighbor_2) = neighbors_samples(sample_i);
+                    let values_1 = spherical_expansion_values.row(neighbor_1);
+                    let values_2 = spherical_expansion_values.row(neighbor_2);
+
+                    for (feature_i, terms) in all_terms.iter().enumerate() {
+                        let mut sum = 0.0;
+                        for &(first, second, coefficient) in terms {
+                            sum += coefficient * values_1[first] * values_2[second];
+                        }
+                        value[feature_i] = sum;
+                    }
+                });
+        });
+
+        if self.parameters.gradients {
+            let gradients = descriptor.gradients.as_mut().expect("missing lambda-SOAP gradients");
+            let gradient_samples = descriptor.gradients_samples.as_ref().expect("missing lambda-SOAP gradient samples");
+
+            let se_gradients_samples = self.spherical_expansion.gradients_samples.as_ref().expect("missing spherical expansion gradient samples");
+            let se_gradients = self.spherical_expansion.gradients.as_ref().expect("missing spherical expansion gradients");
+
+            crate::calculator::install(|| {
+                gradients.axis_iter_mut(ndarray::Axis(0))
+                    .into_par_iter()
+                    .enumerate()
+                    .for_each(|(gradient_sample_i, mut gradient)| {
+                        let gradient_sample = &gradient_samples[gradient_sample_i];
+                        let grad_atom = gradient_sample[1];
+                        let spatial = gradient_sample[2];
+
+                        let (neighbor_1, neighbor_2) = neighbors_samples(gradient_sample[0].usize());
+                        let values_1 = spherical_expansion_values.row(neighbor_1);
+                        let values_2 = spherical_expansion_values.row(neighbor_2);
+
+                        let gradients_1 = se_gradients_samples.position(&[
+                            IndexValue::from(neighbor_1), grad_atom, spatial
+                        ]).map(|i| se_gradients.row(i));
+                        let gradients_2 = se_gradients_samples.position(&[
+                            IndexValue::from(neighbor_2), grad_atom, spatial
+                        ]).map(|i| se_gradients.row(i));
+
+                        for (feature_i, terms) in all_terms.iter().enumerate() {
+                            let mut sum = 0.0;
+                            for &(first, second, coefficient) in terms {
+                                if let Some(ref gradients_1) = gradients_1 {
+                                    sum += coefficient * gradients_1[first] * values_2[second];
+                                }
+
+                                if let Some(ref gradients_2) = gradients_2 {
+                                    sum += coefficient * values_1[first] * gradients_2[second];
+                                }
+                            }
+                            gradient[feature_i] = sum;
+                        }
+                    });
+            });
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use crate::systems::test_utils::{test_systems, test_system};
+    use crate::descriptor::{IndexValue, IndexesBuilder};
+    use crate::{Descriptor, Calculator, Matrix3, System, Vector3D};
+    use crate::calculators::CalculatorBase;
+    use crate::math::WignerD;
+
+    use super::{LambdaSoap, LambdaSoapParameters};
+    use super::{CutoffFunction, RadialBasis, RadialScaling};
+
+    // small helper function to create IndexValue
+    fn v(i: i32) -> IndexValue { IndexValue::from(i) }
+
+    fn parameters(gradients: bool, lambda: usize, parity: i32) -> LambdaSoapParameters {
+        LambdaSoapParameters {
+            atomic_gaussian_width: 0.3,
+            cutoff: 3.5,
+            cutoff_function: CutoffFunction::ShiftedCosine { width: 0.5 },
+            gradients: gradients,
+            max_radial: 4,
+            max_angular: 3,
+            radial_basis: RadialBasis::Gto {},
+            radial_scaling: RadialScaling::None {},
+            center_atom_weight: 1.0,
+            lambda: lambda,
+            parity: parity,
+        }
+    }
+
+    fn calculator(gradients: bool, lambda: usize, parity: i32) -> Calculator {
+        Calculator::from(Box::new(LambdaSoap::new(
+            parameters(gradients, lambda, parity)
+        ).unwrap()) as Box<dyn CalculatorBase>)
+    }
+
+    /// Compute lambda-SOAP for water, transforming all positions with
+    /// `transformation`
+    fn compute(lambda: usize, parity: i32, transformation: &Matrix3) -> Descriptor {
+        let mut system = test_system("water");
+        for position in system.positions_mut() {
+            *position = *transformation * *position;
+        }
+
+        let mut descriptor = Descriptor::new();
+        let mut systems = vec![Box::new(system) as Box<dyn System>];
+        calculator(false, lambda, parity).compute(&mut systems, &mut descriptor, Default::default()).unwrap();
+        return descriptor;
+    }
+
+    #[test]
+    fn features() {
+        let calculator = LambdaSoap::new(parameters(false, 1, 1)).unwrap();
+        let features = calculator.features();
+        assert_eq!(features.names(), ["l1", "l2", "n1", "n2", "mu"]);
+        // (l1, l2) in (0, 1), (1, 0), (1, 2), (2, 1), (2, 3), (3, 2)
+        assert_eq!(features.count(), 6 * 4 * 4 * 3);
+
+        let calculator = LambdaSoap::new(parameters(false, 2, 1)).unwrap();
+        // (l1, l2) in (0, 2), (1, 1), (1, 3), (2, 0), (2, 2), (3, 1), (3, 3)
+        assert_eq!(calculator.features().count(), 7 * 4 * 4 * 5);
+
+        let calculator = LambdaSoap::new(parameters(false, 2, -1)).unwrap();
+        // (l1, l2) in (1, 2), (2, 1), (2, 3), (3, 2)
+        assert_eq!(calculator.features().count(), 4 * 4 * 4 * 5);
+    }
+
+    #[test]
+    fn rotations() {
+        let rotation = Matrix3::rotation(&Vector3D::new(0.3, -1.2, 0.7).normalized(), 1.1);
+        let wigner = WignerD::new(2, &rotation);
+
+        for &(lambda, parity) in &[(1, 1), (2, 1), (2, -1)] {
+            let reference = compute(lambda, parity, &Matrix3::one());
+            let rotated = compute(lambda, parity, &rotation);
+            assert_eq!(reference.samples, rotated.samples);
+
+            // the features transform like spherical harmonics of degree lambda
+            let block_size = 2 * lambda + 1;
+            let matrix = wigner.matrix(lambda);
+            let mut has_non_zero = false;
+            for sample_i in 0..reference.samples.count() {
+                for start in (0..reference.features.count()).step_by(block_size) {
+                    let block = reference.values.slice(ndarray::s![sample_i, start..(start + block_size)]);
+                    let rotated_block = rotated.values.slice(ndarray::s![sample_i, start..(start + block_size)]);
+
+                    has_non_zero |= block.iter().any(|v| v.abs() > 1e-6);
+                    let expected = matrix.dot(&block);
+                    for mu in 0..block_size {
+                        assert_relative_eq!(rotated_block[mu], expected[mu], epsilon = 1e-12);
+                    }
+                }
+            }
+            assert!(has_non_zero);
+        }
+
+        // for lambda = 1, mu = 1, -1, 0 transform like x, y and z
+        let reference = compute(1, 1, &Matrix3::one());
+        let rotated = compute(1, 1, &rotation);
+        for sample_i in 0..reference.samples.count() {
+            for start in (0..reference.features.count()).step_by(3) {
+                let vector = Vector3D::new(
+                    reference.values[[sample_i, start + 2]],
+                    reference.values[[sample_i, start]],
+                    reference.values[[sample_i, start + 1]],
+                );
+                let expected = rotation * vector;
+
+                assert_relative_eq!(rotated.values[[sample_i, start + 2]], expected[0], epsilon = 1e-12);
+                assert_relative_eq!(rotated.values[[sample_i, start]], expected[1], epsilon = 1e-12);
+                assert_relative_eq!(rotated.values[[sample_i, start + 1]], expected[2], epsilon = 1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn inversion() {
+        let inversion = -1.0 * Matrix3::one();
+        for &(lambda, parity) in &[(1, 1), (2, 1), (2, -1)] {
+            let reference = compute(lambda, parity, &Matrix3::one());
+            let inverted = compute(lambda, parity, &inversion);
+
+            // proper tensors of degree lambda get a factor (-1)^lambda under
+            // inversion, and pseudo-tensors get the opposite factor
+            let factor = parity as f64 * f64::powi(-1.0, lambda as i32);
+            for (value, inverted) in reference.values.iter().zip(inverted.values.iter()) {
+                assert_relative_eq!(factor * value, inverted, epsilon = 1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn finite_differences() {
+        for &(lambda, parity) in &[(1, 1), (2, -1)] {
+            let system = test_system("water");
+            crate::calculators::tests_utils::finite_difference(calculator(true, lambda, parity), system);
+        }
+    }
+
+    #[test]
+    fn compute_partial() {
+        let mut systems = test_systems(&["water", "methane"]);
+
+        let mut samples = IndexesBuilder::new(vec!["structure", "center", "species_center", "species_neighbor_1", "species_neighbor_2"]);
+        samples.add(&[v(0), v(1), v(1), v(1), v(123456)]);
+        samples.add(&[v(0), v(0), v(123456), v(1), v(1)]);
+        samples.add(&[v(1), v(0), v(6), v(1), v(6)]);
+        samples.add(&[v(1), v(2), v(1), v(1), v(1)]);
+
+        let mut features = IndexesBuilder::new(vec!["l1", "l2", "n1", "n2", "mu"]);
+        features.add(&[v(0), v(2), v(0), v(1), v(2)]);
+        features.add(&[v(1), v(1), v(3), v(3), v(0)]);
+        features.add(&[v(3), v(1), v(1), v(2), v(-1)]);
+        features.add(&[v(2), v(2), v(0), v(0), v(-2)]);
+
+        crate::calculators::tests_utils::compute_partial(
+            calculator(false, 2, 1), &mut systems, samples.finish(), features.finish()
+        );
+    }
+
+    #[test]
+    fn invalid_parameters() {
+        let error = LambdaSoap::new(parameters(false, 7, 1)).unwrap_err();
+        assert_eq!(error.to_string(), "invalid parameter: there are no pairs of angular channels up to max_angular=3 coupling to lambda=7 with parity 1");
+
+        let error = LambdaSoap::new(parameters(false, 0, -1)).unwrap_err();
+        assert_eq!(error.to_string(), "invalid parameter: there are no pairs of angular channels up to max_angular=3 coupling to lambda=0 with parity -1");
+
+        let error = LambdaSoap::new(parameters(false, 1, 2)).unwrap_err();
+        assert_eq!(error.to_string(), "invalid parameter: parity must be 1 or -1 for lambda-SOAP, got 2");
+    }
+}
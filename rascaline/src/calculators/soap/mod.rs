@@ -4,12 +4,19 @@ pub use self::radial_integral::{GtoRadialIntegral, GtoParameters};
 pub use self::radial_integral::{HyperGeometricSphericalExpansion, HyperGeometricParameters};
 pub use self::radial_integral::{SplinedRadialIntegral, SplinedRIParameters};
 
-mod spherical_harmonics;
-pub use self::spherical_harmonics::{SphericalHarmonics, SphericalHarmonicsArray};
+pub use crate::math::{SphericalHarmonics, SphericalHarmonicsArray, SphericalHarmonicsCache};
 
 mod spherical_expansion;
 pub use self::spherical_expansion::{SphericalExpansion, SphericalExpansionParameters};
 pub use self::spherical_expansion::{RadialBasis, CutoffFunction, RadialScaling};
+pub use self::spherical_expansion::SphericalHarmonicsConvention;
+pub use self::spherical_expansion::clear_spline_cache;
+
+mod kspace;
+pub use self::kspace::KSpaceParameters;
 
 mod power_spectrum;
-pub use self::power_spectrum::{SoapPowerSpectrum, PowerSpectrumParameters};
+pub use self::power_spectrum::{SoapPowerSpectrum, PowerSpectrumParameters, AlchemicalCompression};
+
+mod lambda_soap;
+pub use self::lambda_soap::{LambdaSoap, LambdaSoapParameters};
@@ -1,15 +1,19 @@
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet};
 
+use ndarray::{Array1, Array2};
 use ndarray::parallel::prelude::*;
 
 use crate::descriptor::{SamplesBuilder, IndexValue, Indexes, IndexesBuilder};
-use crate::descriptor::{TwoBodiesSpeciesSamples, ThreeBodiesSpeciesSamples};
+use crate::descriptor::{TwoBodiesSpeciesSamples, ThreeBodiesSpeciesSamples, MemoryLayout};
 
 use crate::{CalculationOptions, Calculator, SelectedIndexes};
-use crate::{Descriptor, Error, System};
+use crate::{Descriptor, Error, ErrorContext, System};
+use crate::math::strided_dot;
 
 use super::{super::CalculatorBase, SphericalExpansionParameters};
 use super::{SphericalExpansion, RadialBasis, CutoffFunction, RadialScaling};
+use super::SphericalHarmonicsConvention;
+use super::spherical_expansion::{default_center_atom_weight, hyper_parameters};
 
 
 /// Parameters for SOAP power spectrum calculator.
@@ -34,6 +38,13 @@ pub struct PowerSpectrumParameters {
     pub max_radial: usize,
     /// Number of spherical harmonics to use
     pub max_angular: usize,
+    /// Number of radial basis function to use for each angular channel, from
+    /// `l=0` to `l=max_angular`. The power spectrum only contains features
+    /// with `n1` and `n2` below the corresponding value. All values must be
+    /// between 1 and `max_radial`. By default, `max_radial` radial basis
+    /// functions are used for all angular channels.
+    #[serde(default)]
+    pub max_radial_per_angular: Option<Vec<usize>>,
     /// Width of the atom-centered gaussian creating the atomic density
     pub atomic_gaussian_width: f64,
     /// Should we also compute gradients of the feature?
@@ -47,6 +58,102 @@ pub struct PowerSpectrumParameters {
     /// model
     #[serde(default)]
     pub radial_scaling: RadialScaling,
+    /// Weight of the central atom own density in its environment. Setting
+    /// this to 0 removes the contribution of the central atom, and any other
+    /// value scales it.
+    #[serde(default = "default_center_atom_weight")]
+    pub center_atom_weight: f64,
+    /// Name of per-atom data (see `System::data`) containing a weight for
+    /// each atom, for example a partial charge or a site occupancy. When set,
+    /// the contribution of each atom to the density (including the central
+    /// atom own contribution) is multiplied by its weight.
+    #[serde(default)]
+    pub neighbor_weights: Option<String>,
+    /// Group multiple neighbor species in a single channel, for example to use
+    /// the same channel for all halogens. The keys of this map are neighbor
+    /// species, and the values the corresponding channel, which is used in
+    /// place of the species in the `species_neighbor_1` and
+    /// `species_neighbor_2` samples. Species not in this map are used as their
+    /// own channel.
+    #[serde(default)]
+    pub neighbor_channels: BTreeMap<i32, i32>,
+    /// Remove all contributions of the central atom to its own environment:
+    /// the central atom own density (regardless of `center_atom_weight`) and
+    /// the density of the periodic images of the central atom.
+    #[serde(default)]
+    pub exclude_self_pairs: bool,
+    /// Only include the neighbors in the first coordination shell of each
+    /// center, see the corresponding parameter of the spherical expansion.
+    #[serde(default)]
+    pub first_shell_tolerance: Option<f64>,
+    /// compress the neighbor species in a small number of channels before
+    /// computing the power spectrum, reducing the number of samples for
+    /// systems with a lot of different species
+    #[serde(default)]
+    pub alchemical_compression: Option<AlchemicalCompression>,
+}
+
+/// Alchemical compression of the neighbor species in the power spectrum.
+///
+/// The spherical expansion coefficients of all neighbor species around a given
+/// center are mixed in `mixing.len()` channels, with the coefficients of
+/// channel `q` being `\sum_s mixing[q][s] < n l m | X_i^s >`. The power
+/// spectrum is then computed for all pairs of channels instead of all pairs of
+/// neighbor species, and the `species_neighbor_1` and `species_neighbor_2`
+/// samples contain the index of the channels. The number of samples for each
+/// center is then quadratic in the number of channels instead of the number
+/// of species.
+#[derive(Debug, Clone)]
+#[derive(serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+pub struct AlchemicalCompression {
+    /// List of all the species that can appear as neighbors, in the same order
+    /// as the columns of `mixing`
+    pub species: Vec<i32>,
+    /// Mixing matrix, with one row for each channel and one column for each
+    /// species in `species`
+    pub mixing: Vec<Vec<f64>>,
+}
+
+impl AlchemicalCompression {
+    fn validate(&self) -> Result<(), Error> {
+        let mut species = self.species.clone();
+        species.sort_unstable();
+        species.dedup();
+        if species.len() != self.species.len() {
+            return Err(Error::InvalidParameter(
+                "the species in alchemical compression must be unique".into()
+            ));
+        }
+
+        if self.mixing.is_empty() {
+            return Err(Error::InvalidParameter(
+                "alchemical compression requires at least one channel".into()
+            ));
+        }
+
+        for (channel, row) in self.mixing.iter().enumerate() {
+            if row.len() != self.species.len() {
+                return Err(Error::InvalidParameter(format!(
+                    "expected {} mixing coefficients for channel {} in alchemical compression, got {}",
+                    self.species.len(), channel, row.len()
+                )));
+            }
+
+            if row.iter().any(|value| !value.is_finite()) {
+                return Err(Error::InvalidParameter(format!(
+                    "all mixing coefficients in alchemical compression must be finite, got {:?} for channel {}",
+                    row, channel
+                )));
+            }
+        }
+
+        return Ok(());
+    }
+
+    /// Get the column of `mixing` corresponding to the given `species`
+    fn species_column(&self, species: i32) -> Option<usize> {
+        self.species.iter().position(|&s| s == species)
+    }
 }
 
 /// Calculator implementing the Smooth Overlap of Atomic Position (SOAP) power
@@ -59,15 +166,33 @@ pub struct SoapPowerSpectrum {
 
 impl SoapPowerSpectrum {
     pub fn new(parameters: PowerSpectrumParameters) -> Result<SoapPowerSpectrum, Error> {
+        if let Some(ref compression) = parameters.alchemical_compression {
+            compression.validate()?;
+
+            if !parameters.neighbor_channels.is_empty() {
+                return Err(Error::InvalidParameter(
+                    "neighbor_channels can not be used together with alchemical_compression".into()
+                ));
+            }
+        }
+
         let expansion_parameters = SphericalExpansionParameters {
             cutoff: parameters.cutoff,
             max_radial: parameters.max_radial,
             max_angular: parameters.max_angular,
+            max_radial_per_angular: parameters.max_radial_per_angular.clone(),
             atomic_gaussian_width: parameters.atomic_gaussian_width,
             gradients: parameters.gradients,
             radial_basis: parameters.radial_basis,
             cutoff_function: parameters.cutoff_function,
             radial_scaling: parameters.radial_scaling,
+            center_atom_weight: parameters.center_atom_weight,
+            neighbor_weights: parameters.neighbor_weights.clone(),
+            neighbor_channels: parameters.neighbor_channels.clone(),
+            exclude_self_pairs: parameters.exclude_self_pairs,
+            first_shell_tolerance: parameters.first_shell_tolerance,
+            spherical_harmonics: SphericalHarmonicsConvention::default(),
+            kspace: None,
         };
 
         let spherical_expansion = SphericalExpansion::new(expansion_parameters)?;
@@ -86,6 +211,21 @@ impl SoapPowerSpectrum {
     fn get_expansion_samples(&self, samples: &Indexes) -> Indexes {
         assert_eq!(samples.names(), self.samples_builder().names());
 
+        if self.parameters.alchemical_compression.is_some() {
+            // all neighbor species are mixed together, so we need the
+            // spherical expansion for all neighbor species around the centers
+            let mut centers = BTreeSet::new();
+            for sample in samples {
+                centers.insert([sample[0], sample[1]]);
+            }
+
+            let mut spherical_expansion_samples = IndexesBuilder::new(vec!["structure", "center"]);
+            for center in centers {
+                spherical_expansion_samples.add(&center);
+            }
+            return spherical_expansion_samples.finish();
+        }
+
         let mut set = BTreeSet::new();
         for sample in samples {
             let structure = sample[0];
@@ -114,26 +254,25 @@ impl SoapPowerSpectrum {
 
     /// Create the list of spherical expansion features required to compute the
     /// power spectrum `features` requested by the user
-    fn get_expansion_features(&self, features: &Indexes) -> (Indexes, usize) {
+    ///
+    /// This also returns the number of different radial indexes for each
+    /// angular channel `l`, which is the size of a given `l, m` block in the
+    /// spherical expansion features.
+    fn get_expansion_features(&self, features: &Indexes) -> (Indexes, BTreeMap<isize, usize>) {
         assert_eq!(features.names(), self.features_names());
 
-        let radial_values = features
-            .iter()
-            .flat_map(|feature| [feature[0].usize(), feature[1].usize()])
-            .collect::<BTreeSet<_>>();
-
-        let n_radial_values = radial_values.len();
-
-        let angular_values = features
-            .iter()
-            .map(|feature| feature[2].isize())
-            .collect::<BTreeSet<_>>();
+        // the radial values can be different for different angular channels
+        let mut radial_values = BTreeMap::new();
+        for feature in features {
+            let radial = radial_values.entry(feature[2].isize()).or_insert_with(BTreeSet::new);
+            radial.insert(feature[0].usize());
+            radial.insert(feature[1].usize());
+        }
 
         let mut set = BTreeSet::new();
-
-        for l in angular_values {
+        for (&l, radial) in &radial_values {
             for m in -l..=l {
-                for &n in &radial_values {
+                for &n in radial {
                     set.insert([IndexValue::from(l), IndexValue::from(m), IndexValue::from(n)]);
                 }
             }
@@ -144,8 +283,97 @@ impl SoapPowerSpectrum {
             spherical_expansion_features.add(&index);
         }
 
+        let n_radial_values = radial_values.iter()
+            .map(|(&l, radial)| (l, radial.len()))
+            .collect();
+
         return (spherical_expansion_features.finish(), n_radial_values);
     }
+
+    /// Get the number of radial basis functions used for the angular channel
+    /// `l`
+    fn max_radial(&self, l: usize) -> usize {
+        return self.parameters.max_radial_per_angular.as_ref()
+            .map_or(self.parameters.max_radial, |max_radial| max_radial[l]);
+    }
+
+    /// Mix the spherical expansion coefficients for the different neighbor
+    /// species according to the alchemical `compression`, replacing
+    /// `self.spherical_expansion` with the coefficients of the compressed
+    /// channels. The `species_neighbor` samples of the new spherical expansion
+    /// contain the index of the channels.
+    #[time_graph::instrument(name = "SoapPowerSpectrum::compress_expansion")]
+    fn compress_expansion(&mut self, compression: &AlchemicalCompression) {
+        let expansion = &self.spherical_expansion;
+        let n_channels = compression.mixing.len();
+        let n_features = expansion.features.count();
+
+        // group the spherical expansion samples by center
+        let mut centers = BTreeMap::new();
+        for (sample_i, sample) in expansion.samples.iter().enumerate() {
+            let column = compression.species_column(sample[3].i32())
+                .expect("neighbor species should be part of the compression");
+            centers.entry([sample[0], sample[1], sample[2]])
+                .or_insert_with(Vec::new)
+                .push((sample_i, column));
+        }
+
+        // mixed sample index for each spherical expansion sample, together
+        // with the corresponding column of the mixing matrix
+        let mut mixed_samples = vec![(0, 0); expansion.samples.count()];
+        let mut samples = IndexesBuilder::new(expansion.samples.names());
+        let mut values = Array2::zeros((centers.len() * n_channels, n_features));
+        for (center_i, (center, contributions)) in centers.iter().enumerate() {
+            for (channel, mixing) in compression.mixing.iter().enumerate() {
+                let mixed_i = center_i * n_channels + channel;
+                samples.add(&[center[0], center[1], center[2], IndexValue::from(channel)]);
+
+                let mut row = values.row_mut(mixed_i);
+                for &(sample_i, column) in contributions {
+                    row.scaled_add(mixing[column], &expansion.values.row(sample_i));
+                }
+            }
+
+            for &(sample_i, column) in contributions {
+                mixed_samples[sample_i] = (center_i * n_channels, column);
+            }
+        }
+
+        let (gradients_samples, gradients) = match (&expansion.gradients_samples, &expansion.gradients) {
+            (Some(gradients_samples), Some(gradients)) => {
+                let mut mixed_gradients = BTreeMap::new();
+                for (gradient_i, gradient_sample) in gradients_samples.iter().enumerate() {
+                    let (first_mixed_i, column) = mixed_samples[gradient_sample[0].usize()];
+                    for (channel, mixing) in compression.mixing.iter().enumerate() {
+                        let key = [IndexValue::from(first_mixed_i + channel), gradient_sample[1], gradient_sample[2]];
+                        let row = mixed_gradients.entry(key).or_insert_with(|| Array1::zeros(n_features));
+                        row.scaled_add(mixing[column], &gradients.row(gradient_i));
+                    }
+                }
+
+                let mut new_gradients_samples = IndexesBuilder::new(gradients_samples.names());
+                let mut new_gradients = Array2::zeros((mixed_gradients.len(), n_features));
+                for (gradient_i, (key, row)) in mixed_gradients.into_iter().enumerate() {
+                    new_gradients_samples.add(&key);
+                    new_gradients.row_mut(gradient_i).assign(&row);
+                }
+                (Some(new_gradients_samples.finish()), Some(new_gradients))
+            }
+            _ => (None, None),
+        };
+
+        self.spherical_expansion = Descriptor {
+            values: values,
+            samples: samples.finish(),
+            gradients: gradients,
+            gradients_samples: gradients_samples,
+            features: expansion.features.clone(),
+            hyper_gradients: None,
+            hyper_parameters: Vec::new(),
+            layout: MemoryLayout::RowMajor,
+            pending_gradients: None,
+        };
+    }
 }
 
 impl std::fmt::Debug for SoapPowerSpectrum {
@@ -162,6 +390,9 @@ struct FeatureBlock {
     /// angular basis number for this feature block. The block size is 2l + 1,
     /// corresponding to all m values from -l to l
     l: isize,
+    /// number of radial basis functions for this angular channel, i.e. the
+    /// stride between two consecutive values of m in the spherical expansion
+    n_radial: usize,
     /// Index of the first feature in the spherical expansion (corresponding to
     /// `n1, l, m=-l`)
     start_n1_l: usize,
@@ -188,6 +419,10 @@ impl CalculatorBase for SoapPowerSpectrum {
         for n1 in 0..self.parameters.max_radial {
             for n2 in 0..self.parameters.max_radial {
                 for l in 0..(self.parameters.max_angular + 1) {
+                    if n1 >= self.max_radial(l) || n2 >= self.max_radial(l) {
+                        continue;
+                    }
+
                     features.add(&[
                         IndexValue::from(n1), IndexValue::from(n2), IndexValue::from(l)
                     ]);
@@ -198,13 +433,31 @@ impl CalculatorBase for SoapPowerSpectrum {
     }
 
     fn samples_builder(&self) -> Box<dyn SamplesBuilder> {
-        Box::new(ThreeBodiesSpeciesSamples::with_self_contribution(self.parameters.cutoff))
+        if let Some(ref compression) = self.parameters.alchemical_compression {
+            Box::new(CompressedSpeciesSamples {
+                cutoff: self.parameters.cutoff,
+                compression: compression.clone(),
+            })
+        } else {
+            Box::new(
+                ThreeBodiesSpeciesSamples::with_self_contribution(self.parameters.cutoff)
+                    .with_neighbor_channels(self.parameters.neighbor_channels.clone())
+            )
+        }
     }
 
     fn compute_gradients(&self) -> bool {
         self.parameters.gradients
     }
 
+    fn supports_gradients(&self) -> bool {
+        true
+    }
+
+    fn hyper_parameters(&self) -> Vec<String> {
+        hyper_parameters(&self.parameters.cutoff_function, &self.parameters.radial_scaling)
+    }
+
     fn check_features(&self, indexes: &Indexes) -> Result<(), Error> {
         assert_eq!(indexes.names(), self.features_names());
         for value in indexes {
@@ -212,24 +465,24 @@ impl CalculatorBase for SoapPowerSpectrum {
             let n2 = value[1].usize();
             let l = value[2].usize();
 
-            if n1 >= self.parameters.max_radial {
+            if l > self.parameters.max_angular {
                 return Err(Error::InvalidParameter(format!(
-                    "'n1' is too large for this SoapPowerSpectrum: \
-                    expected value below {}, got {}", self.parameters.max_radial, n1
+                    "'l' is too large for this SoapPowerSpectrum: \
+                    expected value below {}, got {}", self.parameters.max_angular + 1, l
                 )))
             }
 
-            if n2 >= self.parameters.max_radial {
+            if n1 >= self.max_radial(l) {
                 return Err(Error::InvalidParameter(format!(
-                    "'n2' is too large for this SoapPowerSpectrum: \
-                    expected value below {}, got {}", self.parameters.max_radial, n2
+                    "'n1' is too large for this SoapPowerSpectrum: \
+                    expected value below {}, got {}", self.max_radial(l), n1
                 )))
             }
 
-            if l > self.parameters.max_angular {
+            if n2 >= self.max_radial(l) {
                 return Err(Error::InvalidParameter(format!(
-                    "'l' is too large for this SoapPowerSpectrum: \
-                    expected value below {}, got {}", self.parameters.max_angular + 1, l
+                    "'n2' is too large for this SoapPowerSpectrum: \
+                    expected value below {}, got {}", self.max_radial(l), n2
                 )))
             }
         }
@@ -237,18 +490,34 @@ impl CalculatorBase for SoapPowerSpectrum {
         Ok(())
     }
 
+    fn save_state(&self) -> Result<Option<serde_json::Value>, Error> {
+        self.spherical_expansion_calculator.save_state()
+    }
+
+    fn precompute(&mut self) -> Result<(), Error> {
+        self.spherical_expansion_calculator.precompute()
+    }
+
+    fn load_state(&mut self, state: serde_json::Value) -> Result<(), Error> {
+        self.spherical_expansion_calculator.load_state(state)
+    }
+
     #[time_graph::instrument(name = "SoapPowerSpectrum::compute")]
     fn compute(&mut self, systems: &mut [Box<dyn System>], descriptor: &mut Descriptor) -> Result<(), Error> {
         assert_eq!(descriptor.samples.names(), self.samples_builder().names());
         assert_eq!(descriptor.features.names(), self.features_names());
 
-        // `n_different_radial` is the number of different radial indexes. This
-        // will be the size of a given lm block in spherical expansion
+        // `n_different_radial` is the number of different radial indexes for
+        // each angular channel. This will be the size of a given lm block in
+        // spherical expansion
         let (selected_features, n_different_radial) = self.get_expansion_features(&descriptor.features);
 
         let options = CalculationOptions {
             selected_samples: SelectedIndexes::Subset(self.get_expansion_samples(&descriptor.samples)),
             selected_features: SelectedIndexes::Subset(selected_features),
+            // use the same parallelization strategy as the power spectrum
+            parallelism: crate::calculator::parallelism(),
+            deterministic: crate::calculator::deterministic(),
             ..Default::default()
         };
 
@@ -256,7 +525,11 @@ impl CalculatorBase for SoapPowerSpectrum {
             systems,
             &mut self.spherical_expansion,
             options,
-        ).expect("failed to compute spherical expansion");
+        ).context("failed to compute the spherical expansion")?;
+
+        if let Some(compression) = self.parameters.alchemical_compression.clone() {
+            self.compress_expansion(&compression);
+        }
 
         // Find out where feature blocks of the spherical expansion are located
         let mut feature_blocks = Vec::with_capacity(descriptor.features.count());
@@ -272,7 +545,8 @@ impl CalculatorBase for SoapPowerSpectrum {
                 &[IndexValue::from(l), IndexValue::from(-l), n2]
             ).expect("missing feature `l, m, n2` in spherical expansion");
 
-            feature_blocks.push(FeatureBlock { l, start_n1_l, start_n2_l });
+            let n_radial = n_different_radial[&l];
+            feature_blocks.push(FeatureBlock { l, n_radial, start_n1_l, start_n2_l });
         }
 
         let spherical_expansion_samples = &self.spherical_expansion.samples;
@@ -280,76 +554,13 @@ impl CalculatorBase for SoapPowerSpectrum {
         let spherical_expansion_values = &self.spherical_expansion.values;
 
         let samples = &descriptor.samples;
+        let values = &mut descriptor.values;
 
-        descriptor.values.axis_iter_mut(ndarray::Axis(0))
-            .into_par_iter()
-            .enumerate()
-            .for_each(|(sample_i, mut value)| {
-                let sample = &samples[sample_i];
-                let structure = sample[0];
-                let center = sample[1];
-                let species_center = sample[2];
-                let species_neighbor_1 = sample[3];
-                let species_neighbor_2 = sample[4];
-
-                let neighbor_1 = spherical_expansion_samples.position(&[
-                    structure, center, species_center, species_neighbor_1
-                ]).expect("missing data for one of the neighbor species");
-                let neighbor_2 = spherical_expansion_samples.position(&[
-                    structure, center, species_center, species_neighbor_2
-                ]).expect("missing data for one of the neighbor species");
-
-                for (feature_i, block) in feature_blocks.iter().enumerate() {
-                    let &FeatureBlock { l, start_n1_l, start_n2_l } = block;
-
-                    let mut sum = 0.0;
-                    for (index_m, m) in (-l..=l).enumerate() {
-                        let feature_1 = start_n1_l + index_m * n_different_radial;
-                        let feature_2 = start_n2_l + index_m * n_different_radial;
-                        // check that we are accessing the right value of m
-                        debug_assert_eq!(spherical_expansion_features[feature_1][1].isize(), m);
-                        debug_assert_eq!(spherical_expansion_features[feature_2][1].isize(), m);
-
-                        // unsafe is required to remove the bound checking in
-                        // release mode (`uget` still checks bounds in debug
-                        // mode)
-                        unsafe {
-                            sum += spherical_expansion_values.uget([neighbor_1, feature_1])
-                                * spherical_expansion_values.uget([neighbor_2, feature_2]);
-                        }
-                    }
-
-                    if species_neighbor_1 != species_neighbor_2 {
-                        // We only store values for `species_neighbor_1 <
-                        // species_neighbor_2` because the values are the same for
-                        // pairs `species_neighbor_1 <-> species_neighbor_2` and
-                        // `species_neighbor_2 <-> species_neighbor_1`. To ensure
-                        // the final kernels are correct, we have to multiply the
-                        // corresponding values.
-                        sum *= std::f64::consts::SQRT_2;
-                    }
-
-                    let normalization = f64::sqrt(2.0 * l as f64 + 1.0);
-                    value[feature_i] = sum / normalization;
-                }
-            });
-
-        if self.parameters.gradients {
-            let gradients = descriptor.gradients.as_mut().expect("missing power spectrum gradients");
-            let gradient_samples = descriptor.gradients_samples.as_ref().expect("missing power spectrum gradient samples");
-
-            let se_gradients_samples = self.spherical_expansion.gradients_samples.as_ref().expect("missing spherical expansion gradient samples");
-            let se_gradients = self.spherical_expansion.gradients.as_ref().expect("missing spherical expansion gradients");
-
-            gradients.axis_iter_mut(ndarray::Axis(0))
+        crate::calculator::install(|| {
+            values.axis_iter_mut(ndarray::Axis(0))
                 .into_par_iter()
                 .enumerate()
-                .for_each(|(gradient_sample_i, mut gradient)| {
-                    let gradient_sample = &gradient_samples[gradient_sample_i];
-                    let sample_i = gradient_sample[0].usize();
-                    let grad_atom = gradient_sample[1];
-                    let spatial = gradient_sample[2];
-
+                .for_each(|(sample_i, mut value)| {
                     let sample = &samples[sample_i];
                     let structure = sample[0];
                     let center = sample[1];
@@ -357,64 +568,227 @@ impl CalculatorBase for SoapPowerSpectrum {
                     let species_neighbor_1 = sample[3];
                     let species_neighbor_2 = sample[4];
 
-                    let sample_neighbor_1 = spherical_expansion_samples.position(&[
+                    let neighbor_1 = spherical_expansion_samples.position(&[
                         structure, center, species_center, species_neighbor_1
-                    ]).expect("missing data for the first neighbor");
-                    let sample_neighbor_2 = spherical_expansion_samples.position(&[
+                    ]).expect("missing data for one of the neighbor species");
+                    let neighbor_2 = spherical_expansion_samples.position(&[
                         structure, center, species_center, species_neighbor_2
-                    ]).expect("missing data for the second neighbor");
+                    ]).expect("missing data for one of the neighbor species");
 
-                    let grad_neighbor_1 = se_gradients_samples.position(&[
-                        IndexValue::from(sample_neighbor_1), grad_atom, spatial
-                    ]);
-                    let grad_neighbor_2 = se_gradients_samples.position(&[
-                        IndexValue::from(sample_neighbor_2), grad_atom, spatial
-                    ]);
+                    let values_1 = row_slice(spherical_expansion_values, neighbor_1);
+                    let values_2 = row_slice(spherical_expansion_values, neighbor_2);
 
                     for (feature_i, block) in feature_blocks.iter().enumerate() {
-                        let &FeatureBlock { l, start_n1_l, start_n2_l } = block;
-
-                        let mut sum = 0.0;
-                        for (index_m, m) in (-l..=l).enumerate() {
-                            let feature_1 = start_n1_l + index_m * n_different_radial;
-                            let feature_2 = start_n2_l + index_m * n_different_radial;
-                            // check that we are accessing the right value of m
-                            debug_assert_eq!(spherical_expansion_features[feature_1][1].isize(), m);
-                            debug_assert_eq!(spherical_expansion_features[feature_2][1].isize(), m);
-
-                            if let Some(grad_neighbor_1) = grad_neighbor_1 {
-                                // unsafe is required to remove the bound
-                                // checking in release mode (`uget` still checks
-                                // bounds in debug mode)
-                                unsafe {
-                                    sum += se_gradients.uget([grad_neighbor_1, feature_1])
-                                         * spherical_expansion_values.uget([sample_neighbor_2, feature_2]);
-                                }
-                            }
-
-                            if let Some(grad_neighbor_2) = grad_neighbor_2 {
-                                unsafe {
-                                    sum += spherical_expansion_values.uget([sample_neighbor_1, feature_1])
-                                         * se_gradients.uget([grad_neighbor_2, feature_2]);
-                                }
+                        let &FeatureBlock { l, n_radial, start_n1_l, start_n2_l } = block;
+
+                        // check that we are accessing the right values of m
+                        if cfg!(debug_assertions) {
+                            for (index_m, m) in (-l..=l).enumerate() {
+                                let feature_1 = start_n1_l + index_m * n_radial;
+                                let feature_2 = start_n2_l + index_m * n_radial;
+                                debug_assert_eq!(spherical_expansion_features[feature_1][1].isize(), m);
+                                debug_assert_eq!(spherical_expansion_features[feature_2][1].isize(), m);
                             }
                         }
 
+                        let mut sum = strided_dot(
+                            values_1, start_n1_l,
+                            values_2, start_n2_l,
+                            n_radial,
+                            (2 * l + 1) as usize,
+                        );
+
                         if species_neighbor_1 != species_neighbor_2 {
-                            // see above
+                            // We only store values for `species_neighbor_1 <
+                            // species_neighbor_2` because the values are the same for
+                            // pairs `species_neighbor_1 <-> species_neighbor_2` and
+                            // `species_neighbor_2 <-> species_neighbor_1`. To ensure
+                            // the final kernels are correct, we have to multiply the
+                            // corresponding values.
                             sum *= std::f64::consts::SQRT_2;
                         }
 
                         let normalization = f64::sqrt(2.0 * l as f64 + 1.0);
-                        gradient[feature_i] = sum / normalization;
+                        value[feature_i] = sum / normalization;
                     }
                 });
+        });
+
+        if self.parameters.gradients {
+            let gradients = descriptor.gradients.as_mut().expect("missing power spectrum gradients");
+            let gradient_samples = descriptor.gradients_samples.as_ref().expect("missing power spectrum gradient samples");
+
+            let se_gradients_samples = self.spherical_expansion.gradients_samples.as_ref().expect("missing spherical expansion gradient samples");
+            let se_gradients = self.spherical_expansion.gradients.as_ref().expect("missing spherical expansion gradients");
+
+            crate::calculator::install(|| {
+                gradients.axis_iter_mut(ndarray::Axis(0))
+                    .into_par_iter()
+                    .enumerate()
+                    .for_each(|(gradient_sample_i, mut gradient)| {
+                        let gradient_sample = &gradient_samples[gradient_sample_i];
+                        let sample_i = gradient_sample[0].usize();
+                        let grad_atom = gradient_sample[1];
+                        let spatial = gradient_sample[2];
+
+                        let sample = &samples[sample_i];
+                        let structure = sample[0];
+                        let center = sample[1];
+                        let species_center = sample[2];
+                        let species_neighbor_1 = sample[3];
+                        let species_neighbor_2 = sample[4];
+
+                        let sample_neighbor_1 = spherical_expansion_samples.position(&[
+                            structure, center, species_center, species_neighbor_1
+                        ]).expect("missing data for the first neighbor");
+                        let sample_neighbor_2 = spherical_expansion_samples.position(&[
+                            structure, center, species_center, species_neighbor_2
+                        ]).expect("missing data for the second neighbor");
+
+                        let grad_neighbor_1 = se_gradients_samples.position(&[
+                            IndexValue::from(sample_neighbor_1), grad_atom, spatial
+                        ]);
+                        let grad_neighbor_2 = se_gradients_samples.position(&[
+                            IndexValue::from(sample_neighbor_2), grad_atom, spatial
+                        ]);
+
+                        let values_1 = row_slice(spherical_expansion_values, sample_neighbor_1);
+                        let values_2 = row_slice(spherical_expansion_values, sample_neighbor_2);
+                        let gradients_1 = grad_neighbor_1.map(|i| row_slice(se_gradients, i));
+                        let gradients_2 = grad_neighbor_2.map(|i| row_slice(se_gradients, i));
+
+                        for (feature_i, block) in feature_blocks.iter().enumerate() {
+                            let &FeatureBlock { l, n_radial, start_n1_l, start_n2_l } = block;
+
+                            // check that we are accessing the right values of m
+                            if cfg!(debug_assertions) {
+                                for (index_m, m) in (-l..=l).enumerate() {
+                                    let feature_1 = start_n1_l + index_m * n_radial;
+                                    let feature_2 = start_n2_l + index_m * n_radial;
+                                    debug_assert_eq!(spherical_expansion_features[feature_1][1].isize(), m);
+                                    debug_assert_eq!(spherical_expansion_features[feature_2][1].isize(), m);
+                                }
+                            }
+
+                            let count = (2 * l + 1) as usize;
+                            let mut sum = 0.0;
+                            if let Some(gradients_1) = gradients_1 {
+                                sum += strided_dot(
+                                    gradients_1, start_n1_l,
+                                    values_2, start_n2_l,
+                                    n_radial, count,
+                                );
+                            }
+
+                            if let Some(gradients_2) = gradients_2 {
+                                sum += strided_dot(
+                                    values_1, start_n1_l,
+                                    gradients_2, start_n2_l,
+                                    n_radial, count,
+                                );
+                            }
+
+                            if species_neighbor_1 != species_neighbor_2 {
+                                // see above
+                                sum *= std::f64::consts::SQRT_2;
+                            }
+
+                            let normalization = f64::sqrt(2.0 * l as f64 + 1.0);
+                            gradient[feature_i] = sum / normalization;
+                        }
+                    });
+            });
         }
 
         Ok(())
     }
 }
 
+/// Samples for the power spectrum with alchemical compression, containing all
+/// pairs of compressed channels for each center. The gradients samples contain
+/// the center and all its neighbors for every sample, since all neighbors
+/// contribute to all channels.
+struct CompressedSpeciesSamples {
+    cutoff: f64,
+    compression: AlchemicalCompression,
+}
+
+impl SamplesBuilder for CompressedSpeciesSamples {
+    fn names(&self) -> Vec<&str> {
+        vec!["structure", "center", "species_center", "species_neighbor_1", "species_neighbor_2"]
+    }
+
+    fn samples(&self, systems: &mut [Box<dyn System>]) -> Result<Indexes, Error> {
+        let n_channels = self.compression.mixing.len();
+
+        let mut indexes = IndexesBuilder::new(self.names());
+        for (i_system, system) in systems.iter().enumerate() {
+            for (center, &species) in system.species()?.iter().enumerate() {
+                if self.compression.species_column(species).is_none() {
+                    return Err(Error::InvalidParameter(format!(
+                        "species {} in structure {} is not part of the alchemical compression species",
+                        species, i_system
+                    )));
+                }
+
+                for channel_1 in 0..n_channels {
+                    for channel_2 in channel_1..n_channels {
+                        indexes.add(&[
+                            IndexValue::from(i_system),
+                            IndexValue::from(center),
+                            IndexValue::from(species),
+                            IndexValue::from(channel_1),
+                            IndexValue::from(channel_2),
+                        ]);
+                    }
+                }
+            }
+        }
+
+        return Ok(indexes.finish());
+    }
+
+    fn gradients_for(&self, systems: &mut [Box<dyn System>], samples: &Indexes) -> Result<Option<Indexes>, Error> {
+        assert_eq!(samples.names(), self.names());
+
+        let mut indexes = BTreeSet::new();
+        for (i_sample, sample) in samples.iter().enumerate() {
+            let i_system = sample[0].usize();
+            let center = sample[1].usize();
+
+            let system = &mut *systems[i_system];
+            system.compute_neighbors(self.cutoff).with_context(|| format!(
+                "failed to compute the neighbors list for structure {}", i_system
+            ))?;
+
+            indexes.insert((i_sample, center));
+            for pair in system.pairs_containing(center)? {
+                let neighbor = if pair.first == center {
+                    pair.second
+                } else {
+                    pair.first
+                };
+                indexes.insert((i_sample, neighbor));
+            }
+        }
+
+        let mut gradients = IndexesBuilder::new(vec!["sample", "atom", "spatial"]);
+        for (sample, atom) in indexes {
+            for spatial in 0..3_usize {
+                gradients.add(&[IndexValue::from(sample), IndexValue::from(atom), IndexValue::from(spatial)]);
+            }
+        }
+
+        return Ok(Some(gradients.finish()));
+    }
+}
+
+/// Get the row `i` of the given `array` as a slice
+fn row_slice(array: &Array2<f64>, i: usize) -> &[f64] {
+    let start = i * array.ncols();
+    let data = array.as_slice().expect("spherical expansion arrays should be contiguous");
+    return &data[start..(start + array.ncols())];
+}
 
 #[cfg(test)]
 mod tests {
@@ -436,8 +810,15 @@ mod tests {
             gradients: gradients,
             max_radial: 6,
             max_angular: 6,
+            max_radial_per_angular: None,
             radial_basis: RadialBasis::Gto {},
             radial_scaling: RadialScaling::None {},
+            center_atom_weight: 1.0,
+            neighbor_weights: None,
+            neighbor_channels: BTreeMap::new(),
+            exclude_self_pairs: false,
+            first_shell_tolerance: None,
+            alchemical_compression: None,
         }
     }
 
@@ -505,4 +886,240 @@ mod tests {
         let system = test_system("water");
         crate::calculators::tests_utils::finite_difference(calculator, system);
     }
+
+    fn compression(mixing: Vec<Vec<f64>>) -> AlchemicalCompression {
+        AlchemicalCompression {
+            species: vec![1, 123456],
+            mixing: mixing,
+        }
+    }
+
+    #[test]
+    fn alchemical_compression() {
+        let mut calculator = Calculator::from(Box::new(SoapPowerSpectrum::new(
+            parameters(false)
+        ).unwrap()) as Box<dyn CalculatorBase>);
+
+        let mut systems = test_systems(&["water"]);
+        let mut reference = Descriptor::new();
+        calculator.compute(&mut systems, &mut reference, Default::default()).unwrap();
+
+        // with an identity mixing matrix, the channels are the species
+        let mut compressed = parameters(false);
+        compressed.alchemical_compression = Some(compression(vec![vec![1.0, 0.0], vec![0.0, 1.0]]));
+        let mut calculator = Calculator::from(Box::new(
+            SoapPowerSpectrum::new(compressed).unwrap()
+        ) as Box<dyn CalculatorBase>);
+
+        let mut descriptor = Descriptor::new();
+        calculator.compute(&mut systems, &mut descriptor, Default::default()).unwrap();
+
+        // 3 atoms, with 3 pairs of channels each
+        assert_eq!(descriptor.samples.count(), 9);
+        let species = [1, 123456];
+        for (sample_i, sample) in descriptor.samples.iter().enumerate() {
+            let expected = [
+                sample[0], sample[1], sample[2],
+                v(species[sample[3].usize()]),
+                v(species[sample[4].usize()]),
+            ];
+
+            let values = descriptor.values.row(sample_i);
+            match reference.samples.position(&expected) {
+                Some(reference_i) => assert_eq!(values, reference.values.row(reference_i)),
+                None => assert!(values.iter().all(|&v| v == 0.0)),
+            }
+        }
+
+        // a single channel with the same weight for all species gives the
+        // power spectrum of the species-agnostic density
+        let mut compressed = parameters(false);
+        compressed.alchemical_compression = Some(compression(vec![vec![1.0, 1.0]]));
+        let mut calculator = Calculator::from(Box::new(
+            SoapPowerSpectrum::new(compressed).unwrap()
+        ) as Box<dyn CalculatorBase>);
+
+        let mut descriptor = Descriptor::new();
+        calculator.compute(&mut systems, &mut descriptor, Default::default()).unwrap();
+        assert_eq!(descriptor.samples.iter().collect::<Vec<_>>(), vec![
+            &[v(0), v(0), v(123456), v(0), v(0)],
+            &[v(0), v(1), v(1), v(0), v(0)],
+            &[v(0), v(2), v(1), v(0), v(0)],
+        ]);
+
+        let mut compressed = parameters(false);
+        compressed.alchemical_compression = Some(compression(vec![vec![0.3, -1.2], vec![0.8, 0.5]]));
+        let calculator = Calculator::from(Box::new(
+            SoapPowerSpectrum::new(compressed).unwrap()
+        ) as Box<dyn CalculatorBase>);
+
+        let mut samples = IndexesBuilder::new(vec!["structure", "center", "species_center", "species_neighbor_1", "species_neighbor_2"]);
+        samples.add(&[v(0), v(1), v(1), v(0), v(1)]);
+        samples.add(&[v(0), v(2), v(1), v(1), v(1)]);
+
+        let mut features = IndexesBuilder::new(vec!["n1", "n2", "l"]);
+        features.add(&[v(0), v(1), v(0)]);
+        features.add(&[v(3), v(3), v(3)]);
+        features.add(&[v(1), v(4), v(4)]);
+
+        crate::calculators::tests_utils::compute_partial(
+            calculator, &mut systems, samples.finish(), features.finish()
+        );
+    }
+
+    #[test]
+    fn alchemical_compression_finite_differences() {
+        let mut compressed = parameters(true);
+        compressed.alchemical_compression = Some(compression(vec![vec![0.3, -1.2], vec![0.8, 0.5], vec![1.0, 0.0]]));
+        let calculator = Calculator::from(Box::new(
+            SoapPowerSpectrum::new(compressed).unwrap()
+        ) as Box<dyn CalculatorBase>);
+
+        let system = test_system("water");
+        crate::calculators::tests_utils::finite_difference(calculator, system);
+    }
+
+    #[test]
+    fn alchemical_compression_errors() {
+        let mut compressed = parameters(false);
+        compressed.alchemical_compression = Some(compression(vec![vec![0.3, -1.2], vec![0.8]]));
+        let error = SoapPowerSpectrum::new(compressed).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "invalid parameter: expected 2 mixing coefficients for channel 1 in alchemical compression, got 1"
+        );
+
+        let mut compressed = parameters(false);
+        compressed.alchemical_compression = Some(AlchemicalCompression {
+            species: vec![1, 6],
+            mixing: vec![vec![1.0, 1.0]],
+        });
+        let mut calculator = Calculator::from(Box::new(
+            SoapPowerSpectrum::new(compressed).unwrap()
+        ) as Box<dyn CalculatorBase>);
+
+        let mut systems = test_systems(&["water"]);
+        let mut descriptor = Descriptor::new();
+        let error = calculator.compute(&mut systems, &mut descriptor, Default::default()).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "invalid parameter: species 123456 in structure 0 is not part of the alchemical compression species"
+        );
+    }
+
+    #[test]
+    fn neighbor_channels() {
+        // grouping all species in a single channel is the same as an
+        // alchemical compression with a single channel and unit weights
+        let mut compressed = parameters(true);
+        compressed.alchemical_compression = Some(compression(vec![vec![1.0, 1.0]]));
+        let mut calculator = Calculator::from(Box::new(
+            SoapPowerSpectrum::new(compressed).unwrap()
+        ) as Box<dyn CalculatorBase>);
+
+        let mut systems = test_systems(&["water"]);
+        let mut reference = Descriptor::new();
+        calculator.compute(&mut systems, &mut reference, Default::default()).unwrap();
+
+        let mut grouped = parameters(true);
+        grouped.neighbor_channels = [(1, 0), (123456, 0)].iter().copied().collect();
+        let mut calculator = Calculator::from(Box::new(
+            SoapPowerSpectrum::new(grouped.clone()).unwrap()
+        ) as Box<dyn CalculatorBase>);
+
+        let mut descriptor = Descriptor::new();
+        calculator.compute(&mut systems, &mut descriptor, Default::default()).unwrap();
+
+        assert_eq!(descriptor.samples, reference.samples);
+        approx::assert_relative_eq!(descriptor.values, reference.values, max_relative = 1e-12);
+        assert_eq!(descriptor.gradients_samples, reference.gradients_samples);
+        approx::assert_relative_eq!(
+            descriptor.gradients.as_ref().unwrap(),
+            reference.gradients.as_ref().unwrap(),
+            epsilon = 1e-14,
+            max_relative = 1e-12
+        );
+
+        grouped.alchemical_compression = Some(compression(vec![vec![1.0, 1.0]]));
+        let error = SoapPowerSpectrum::new(grouped).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "invalid parameter: neighbor_channels can not be used together with alchemical_compression"
+        );
+    }
+
+    #[test]
+    fn neighbor_channels_finite_differences() {
+        let mut grouped = parameters(true);
+        grouped.neighbor_channels = [(123456, 1)].iter().copied().collect();
+        let calculator = Calculator::from(Box::new(
+            SoapPowerSpectrum::new(grouped).unwrap()
+        ) as Box<dyn CalculatorBase>);
+
+        let system = test_system("water");
+        crate::calculators::tests_utils::finite_difference(calculator, system);
+    }
+
+    #[test]
+    fn max_radial_per_angular() {
+        let mut calculator = Calculator::from(Box::new(SoapPowerSpectrum::new(
+            parameters(true)
+        ).unwrap()) as Box<dyn CalculatorBase>);
+
+        let mut systems = test_systems(&["water", "methane"]);
+        let mut reference = Descriptor::new();
+        calculator.compute(&mut systems, &mut reference, Default::default()).unwrap();
+
+        let mut trimmed = parameters(true);
+        trimmed.max_radial_per_angular = Some(vec![6, 5, 4, 3, 2, 1, 1]);
+        let mut calculator = Calculator::from(Box::new(
+            SoapPowerSpectrum::new(trimmed).unwrap()
+        ) as Box<dyn CalculatorBase>);
+
+        let mut descriptor = Descriptor::new();
+        calculator.compute(&mut systems, &mut descriptor, Default::default()).unwrap();
+        assert_eq!(descriptor.samples, reference.samples);
+        assert_eq!(descriptor.features.count(), 36 + 25 + 16 + 9 + 4 + 1 + 1);
+
+        // the features are a subset of the full power spectrum
+        let gradients = descriptor.gradients.as_ref().unwrap();
+        let reference_gradients = reference.gradients.as_ref().unwrap();
+        for (feature_i, feature) in descriptor.features.iter().enumerate() {
+            let reference_i = reference.features.position(feature).unwrap();
+            approx::assert_relative_eq!(
+                descriptor.values.column(feature_i),
+                reference.values.column(reference_i),
+                max_relative = 1e-12
+            );
+            approx::assert_relative_eq!(
+                gradients.column(feature_i),
+                reference_gradients.column(reference_i),
+                max_relative = 1e-12
+            );
+        }
+
+        let mut trimmed = parameters(false);
+        trimmed.max_radial_per_angular = Some(vec![6, 5, 4, 3, 2, 1, 1]);
+        let calculator = SoapPowerSpectrum::new(trimmed).unwrap();
+
+        let mut features = IndexesBuilder::new(vec!["n1", "n2", "l"]);
+        features.add(&[v(2), v(0), v(4)]);
+        let error = calculator.check_features(&features.finish()).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "invalid parameter: 'n1' is too large for this SoapPowerSpectrum: expected value below 2, got 2"
+        );
+    }
+
+    #[test]
+    fn max_radial_per_angular_finite_differences() {
+        let mut trimmed = parameters(true);
+        trimmed.max_radial_per_angular = Some(vec![2, 6, 1, 3, 5, 4, 1]);
+        let calculator = Calculator::from(Box::new(
+            SoapPowerSpectrum::new(trimmed).unwrap()
+        ) as Box<dyn CalculatorBase>);
+
+        let system = test_system("water");
+        crate::calculators::tests_utils::finite_difference(calculator, system);
+    }
 }
@@ -5,7 +5,7 @@ use ndarray::{Array2, ArrayViewMut2};
 use nalgebra as na;
 use nalgebra::linalg::SymmetricEigen;
 
-use crate::math::gamma;
+use crate::math::special::gamma;
 use crate::Error;
 
 use super::RadialIntegral;
@@ -183,6 +183,32 @@ impl GtoRadialIntegral {
             gto_orthonormalization: gto_orthonormalization,
         })
     }
+
+    /// Evaluate the orthonormalized radial basis functions `R_n(r)` for all
+    /// `n` at the distance `r`, and store them in `values`.
+    ///
+    /// The basis functions are normalized consistently with the radial
+    /// integral, i.e. for a gaussian atomic density `exp(-c x^2)`, with `c = 1
+    /// / 2σ^2`, the radial integral is `4π ∫ R_n(x) x^2 exp(-c (x^2 + r^2))
+    /// i_l(2 c x r) dx`, where `i_l` is the modified spherical Bessel function
+    /// of the first kind.
+    pub fn radial_basis(&self, r: f64, values: &mut [f64]) {
+        assert_eq!(
+            values.len(), self.parameters.max_radial,
+            "wrong size for values, expected {} elements, got {}",
+            self.parameters.max_radial, values.len()
+        );
+
+        let gtos = self.gto_gaussian_constants.iter()
+            .enumerate()
+            .map(|(n, &constant)| r.powi(n as i32) * f64::exp(-constant * r * r))
+            .collect::<Vec<_>>();
+
+        for (n, value) in values.iter_mut().enumerate() {
+            let row = self.gto_orthonormalization.row(n);
+            *value = row.iter().zip(&gtos).map(|(t, g)| t * g).sum::<f64>() / f64::consts::PI.powf(1.5);
+        }
+    }
 }
 
 impl RadialIntegral for GtoRadialIntegral {
@@ -421,4 +447,35 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn radial_basis() {
+        let parameters = GtoParameters {
+            max_radial: 6,
+            max_angular: 0,
+            cutoff: 4.5,
+            atomic_gaussian_width: 0.5,
+        };
+        let gto = GtoRadialIntegral::new(parameters).unwrap();
+        let c = 1.0 / (2.0 * 0.5 * 0.5);
+
+        let quadrature = crate::math::quadrature::Quadrature::gauss_legendre(200).rescaled(0.0, 25.0);
+        let mut values = Array2::from_elem((6, 1), 0.0);
+        for &r in &[0.0, 0.8, 2.3] {
+            gto.compute(r, values.view_mut(), None);
+
+            for n in 0..6 {
+                // i_0(z) = sinh(z) / z
+                let integral = quadrature.integrate(|x| {
+                    let mut basis = [0.0; 6];
+                    gto.radial_basis(x, &mut basis);
+                    let z = 2.0 * c * x * r;
+                    let i_0 = if z == 0.0 { 1.0 } else { f64::sinh(z) / z };
+                    basis[n] * x * x * f64::exp(-c * (x * x + r * r)) * i_0
+                });
+                let expected = 4.0 * std::f64::consts::PI * integral;
+                assert_relative_eq!(values[[n, 0]], expected, max_relative=1e-9, epsilon=1e-12);
+            }
+        }
+    }
 }
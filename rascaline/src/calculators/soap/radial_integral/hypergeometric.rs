@@ -1,185 +1,31 @@
-//! This module implement a modified version of Kummer's confluent
-//! hypergeometric function 1F1: `G(rij) = Γ(a) / Γ(b) exp(-c rij^2) 1F1(a, b,
-//! c^2 rij^2 / (c + d))` where `a = (n + l + 3) / 2`, `b = l + 3 / 2`, `c = 1 /
-//! 2σ^2`, `d = 1 / 2 (r_cut \sqrt(n) / (n_max + 1))^2`; σ is the atomic density
-//! gaussian width, `r_cut` the cutoff radius, `n_max` the number of radial
-//! basis, n the current radial basis index and l the current angular index.
-//!
-//! This function is used to compute the radial integral in SOAP spherical
-//! expansion when using GTO radial basis.
+//! Implementation of the `G(rij) = Γ(a) / Γ(b) exp(-c rij^2) 1F1(a, b, c^2
+//! rij^2 / (c + d))` function used to compute the radial integral in SOAP
+//! spherical expansion when using GTO radial basis, where `a = (n + l + 3) /
+//! 2`, `b = l + 3 / 2`, `c = 1 / 2σ^2`, `d = 1 / 2 (r_cut \sqrt(n) / (n_max +
+//! 1))^2`; σ is the atomic density gaussian width, `r_cut` the cutoff radius,
+//! `n_max` the number of radial basis, n the current radial basis index and l
+//! the current angular index.
 //!
 //! This code was originally written by Félix Musil @ COSMO/EPFL
-use std::f64;
-
 use ndarray::{Array2, ArrayViewMut2, Axis, azip};
-use log::warn;
-
-use crate::math::gamma;
-
-// The precision up to which we converge our implementation of the G function
-const HYPERGEOMETRIC_PRECISION: f64 = 1e-13;
-
-/// Compute 2F0 as a the corresponding series truncated to the first n terms.
-#[derive(Debug, Clone)]
-struct Series2F0 {
-    coefficients: Vec<f64>,
-}
-
-impl Series2F0 {
-    /// Create a new `Series2F0` computing the function itself
-    pub fn new(a: f64, b: f64, n_terms: usize) -> Series2F0 {
-        assert!(a > 0.0 && b > 0.0, "a and b must be positive");
-
-        let mut coefficients = Vec::new();
-        coefficients.reserve(n_terms);
-
-        let mut coefficient = (b - a) * (1.0 - a);
-        coefficients.push(coefficient);
-        for i in (1..n_terms).map(|i| i as f64) {
-            coefficient *= (b - a + i) * (1.0 - a + i) / (i + 1.0);
-            coefficients.push(coefficient);
-        }
-
-        return Series2F0 {
-            coefficients: coefficients,
-        }
-    }
-
-    /// Create a new `Series2F0` computing the function derivative
-    pub fn derivative(a: f64, b: f64, n_terms: usize) -> Series2F0 {
-        assert!(a > 0.0 && b > 0.0, "a and b must be positive");
 
-        let mut coefficients = Vec::new();
-        coefficients.reserve(n_terms);
+use crate::math::special::{gamma, Hyp1F1};
 
-        let mut coefficient = (b - a) * -a;
-        coefficients.push(coefficient);
-        for i in (1..n_terms).map(|i| i as f64) {
-            coefficient *= (b - a + i) * (i - a) / (i + 1.0);
-            coefficients.push(coefficient);
-        }
-
-        return Series2F0 {
-            coefficients: coefficients,
-        }
-    }
-
-    /// Actually compute the function for input parameter `z`
-    pub fn compute(&self, z: f64) -> f64 {
-        let mut result = 1.0;
-        let iz = 1.0 / z;
-        let mut iz_pow = 1.0;
-        for coefficient in &self.coefficients {
-            iz_pow *= iz;
-            result += coefficient * iz_pow;
-        }
-        return result;
-    }
-}
-
-/// Compute 1F1 as a the corresponding series truncated to the first n terms.
+/// The `G(z) = Γ(a) / Γ(b) exp(z2) 1F1(a, b, z)` function for a given set of
+/// `a` and `b` parameters
 #[derive(Debug, Clone)]
-struct Series1F1 {
-    coefficients: Vec<f64>,
-}
-
-impl Series1F1 {
-    /// Create a new `Series1F1` computing the function itself
-    pub fn new(a: f64, b: f64, n_terms: usize) -> Series1F1 {
-        assert!(a > 0.0 && b > 0.0, "a and b must be positive");
-
-        let mut coefficients = Vec::new();
-        coefficients.reserve(n_terms);
-
-        let mut coefficient = a / b;
-        coefficients.push(coefficient);
-        for i in (1..n_terms).map(|i| i as f64) {
-            coefficient *= (a + i) / ((i + 1.0) * (b + i));
-            coefficients.push(coefficient);
-        }
-
-        return Series1F1 {
-            coefficients: coefficients,
-        }
-    }
-
-    /// Create a new `Series1F1` computing the function derivative
-    pub fn derivative(a: f64, b: f64, n_terms: usize) -> Series1F1 {
-        assert!(a > 0.0 && b > 0.0, "a and b must be positive");
-
-        let mut coefficients = Vec::new();
-        coefficients.reserve(n_terms);
-
-        let mut coefficient = (a + 1.0) / (b + 1.0);
-        coefficients.push(coefficient);
-        for i in (1..n_terms).map(|i| i as f64) {
-            coefficient *= (a + i + 1.0) / ((i + 1.0) * (b + i + 1.0));
-            coefficients.push(coefficient);
-        }
-
-        return Series1F1 {
-            coefficients: coefficients,
-        }
-    }
-
-    /// Actually compute the function for input parameter `z`
-    #[allow(clippy::identity_op)]
-    pub fn compute(&self, z: f64) -> f64 {
-        let mut result = 1.0;
-        let mut z_pow = z;
-        let z4 = z * z * z * z;
-        // Use an adaptive summation: computes several terms at a time and check
-        // if required accuracy have been reached (typical n. of terms needed is
-        // ~20)
-        for i in (0..self.coefficients.len()).step_by(4)  {
-            let mut term = self.coefficients[i + 3];
-            term = self.coefficients[i + 2] + z * term;
-            term = self.coefficients[i + 1] + z * term;
-            term = self.coefficients[i + 0] + z * term;
-            term *= z_pow;
-
-            if term < HYPERGEOMETRIC_PRECISION * result {
-                result += term;
-                break;
-            }
-
-            z_pow *= z4;
-            result += term;
-        }
-
-        return result;
-    }
-}
-
-/// Compute G using the direct sum for 1F1
-///
-/// `1F1(a, b, z) = \sum_{j=0}^{\infty} \frac{(a)_j}{(b)_jj!} z^{j}`
-#[derive(Debug, Clone)]
-struct HyperGeometricSeries {
-    /// First parameter to the 1F1 function
-    a: f64,
-    /// Second parameter to the 1F1 function
-    b: f64,
+struct HyperGeometric {
     /// Store precomputed `\frac{\Gamma{a}}{\Gamma{b}}`
     gamma_ratio: f64,
-    /// Actual implementation of 1F1, computing the function
-    series_1f1: Series1F1,
-    /// Actual implementation of 1F1, computing the function derivative
-    series_1f1_derivative: Series1F1,
+    hyp1f1: Hyp1F1,
 }
 
-impl HyperGeometricSeries {
-    /// Create a new `HyperGeometricSeries` for the given a and b parameters
-    pub fn new(a: f64, b: f64) -> HyperGeometricSeries {
-        assert!(a > 0.0 && b > 0.0, "a and b must be positive");
-
-        HyperGeometricSeries {
-            a,
-            b,
+impl HyperGeometric {
+    fn new(a: f64, b: f64) -> HyperGeometric {
+        return HyperGeometric {
             gamma_ratio: gamma(a) / gamma(b),
-            series_1f1: Series1F1::new(a, b, 200),
-            series_1f1_derivative: Series1F1::derivative(a, b, 200),
-        }
+            hyp1f1: Hyp1F1::new(a, b),
+        };
     }
 
     /// Computes G(z), with `z = c^2 rij^2 / (c + d)` and `z2 = -c rij^2`
@@ -190,206 +36,11 @@ impl HyperGeometricSeries {
     /// \frac{\Gamma(a)}{\Gamma(b)} * \exp{-\alpha r_{ij}^2}`. We do this to
     /// avoid computing both d1F1/dz and 1F1 when asking for gradients and
     /// perform this step in `HyperGeometricSphericalExpansion`.
-    pub fn compute(&self, z: f64, z2: f64, derivative: bool) -> f64 {
+    fn compute(&self, z: f64, z2: f64, derivative: bool) -> f64 {
         if derivative {
-            self.gamma_ratio * self.series_1f1_derivative.compute(z) * f64::exp(z2) * self.a / self.b
-        } else {
-            self.gamma_ratio * self.series_1f1.compute(z) * f64::exp(z2)
-        }
-    }
-
-    /// Compute 1F1 itself to check for the best validity domain of this
-    /// implementation
-    pub fn compute_1f1(&self, z: f64) -> f64 {
-        self.series_1f1.compute(z)
-    }
-}
-
-/// Compute G using the asymptotic expansion of 1F1 as the third argument goes
-/// to infinity:
-///
-/// `1F1(a,b,z) \sim \exp{z} z^{a-b} \frac{\Gamma{b}}{\Gamma{a}} 2F0(a, b, z)`
-///
-/// where `2F0(a, b, z) = \sum_{j=0}^{\infty} \frac{(b-a)_j(1-a)_j}{j!} z^{-j}`
-#[derive(Debug, Clone)]
-struct HyperGeometricAsymptotic {
-    /// First parameter to the 1F1 function
-    a: f64,
-    /// Second parameter to the 1F1 function
-    b: f64,
-    /// Store precomputed `\frac{\Gamma{a}}{\Gamma{b}}`
-    gamma_ratio: f64,
-    /// Actual implementation of 2F0, computing the function
-    series_2f0: Series2F0,
-    /// Actual implementation of 2F0, computing the function derivative
-    series_2f0_derivative: Series2F0,
-}
-
-impl HyperGeometricAsymptotic {
-    /// Create a new `HyperGeometricAsymptotic` for the given a and b parameters
-    pub fn new(a: f64, b: f64) -> HyperGeometricAsymptotic {
-        assert!(a > 0.0 && b > 0.0, "a and b must be positive");
-
-        HyperGeometricAsymptotic {
-            a,
-            b,
-            gamma_ratio: gamma(a) / gamma(b),
-            series_2f0: Series2F0::new(a, b, 20),
-            series_2f0_derivative: Series2F0::derivative(a, b, 20),
+            return self.gamma_ratio * self.hyp1f1.scaled_derivative(z, z2);
         }
-    }
-
-    /// Computes G(z), with `z = c^2 rij^2 / (c + d)` and `z2 = -c rij^2`
-    ///
-    /// # Warning
-    ///
-    /// The derivative this function computes is not dG/dz but `d1F1/dz *
-    /// \frac{\Gamma(a)}{\Gamma(b)} * \exp{-\alpha r_{ij}^2}`. We do this to
-    /// avoid computing both d1F1/dz and 1F1 when asking for gradients and
-    /// perform this step in `HyperGeometricSphericalExpansion`.
-    pub fn compute(&self, z: f64, z2: f64, derivative: bool) -> f64 {
-        let hyp2f0 = if derivative {
-            self.series_2f0_derivative.compute(z)
-        } else {
-            self.series_2f0.compute(z)
-        };
-        let factor = z.powf(self.a - self.b);
-        return hyp2f0 * f64::exp(z + z2) * factor;
-    }
-
-    /// Compute 1F1 itself to check for the best validity domain of this
-    /// implementation
-    pub fn compute_1f1(&self, z: f64) -> f64 {
-        let factor = z.powf(self.a - self.b);
-        return f64::exp(z) * factor * self.series_2f0.compute(z) / self.gamma_ratio;
-    }
-}
-
-/// Switch between the asymptotic expansion or the series implementations of our
-/// hypergeometric function G(z) depending on the arguments domains.
-#[derive(Debug, Clone)]
-pub struct HyperGeometric {
-    /// when a == b, 1F1 is an exponential, so we use a fast path in this case
-    is_exponential: bool,
-    /// for the exponential case, store Γ(a) / Γ(b)
-    gamma_ratio: f64,
-    /// Asymptotic implementation of the G function
-    asymptotic: HyperGeometricAsymptotic,
-    /// Series implementation of the G function
-    series: HyperGeometricSeries,
-    /// For z above this value, use the asymptotic implementation, for z below
-    /// this value use the series implementation
-    switching_point: f64,
-}
-
-impl HyperGeometric {
-    pub fn new(a: f64, b: f64) -> HyperGeometric {
-        // We try to determine what is the switching point between power series
-        // and asymptotic expansion. We choose the method that requires fewer
-        // terms for a given target accuracy. The asymptotic expansion tends to
-        // overflow at the switching point.
-
-        const MAX_ITERATIONS: usize = 100;
-
-        let series = HyperGeometricSeries::new(a, b);
-        let asymptotic = HyperGeometricAsymptotic::new(a, b);
-
-        if f64::abs(a - b) < 100.0 * f64::EPSILON {
-            return HyperGeometric {
-                is_exponential: true,
-                gamma_ratio: gamma(a) / gamma(b),
-                series: series,
-                asymptotic: asymptotic,
-                switching_point: 0.0,
-            }
-        }
-
-        // Find the largest z for which the 2 definitions agree within tolerance
-        // using bisection.
-
-        // brackets the switching point
-        let mut z_switch = 1.0;
-        let mut z_below  = 1.0;
-        let mut z_above = 1.0;
-
-        let precision = 1e2 * HYPERGEOMETRIC_PRECISION;
-        if f64::abs(1.0 - series.compute_1f1(z_switch) / asymptotic.compute_1f1(z_switch)) > HYPERGEOMETRIC_PRECISION {
-            for _ in 0..MAX_ITERATIONS {
-                z_above *= 1.5;
-                let s = series.compute_1f1(z_above);
-                let a = asymptotic.compute_1f1(z_above);
-                if f64::abs(1.0 - s / a) < precision {
-                    break;
-                }
-            }
-        } else {
-            for _ in 0..MAX_ITERATIONS {
-                z_below *= 0.5;
-                let s = series.compute_1f1(z_below);
-                let a = asymptotic.compute_1f1(z_below);
-
-                if f64::abs(1.0 - s / a) > precision {
-                    break;
-                }
-            }
-        }
-
-        // and now bisects until we are reasonably close to an accurate
-        // determination
-        let mut accuracy_reached = false;
-        z_switch = (z_above + z_below) * 0.5;
-        let mut series_result = series.compute_1f1(z_switch);
-        let mut asymptotic_result = asymptotic.compute_1f1(z_switch);
-        for _ in 0..MAX_ITERATIONS {
-            if f64::abs(1.0 - series_result / asymptotic_result) > HYPERGEOMETRIC_PRECISION {
-                z_below = z_switch;
-            } else {
-                z_above = z_switch;
-            }
-
-            z_switch = (z_above + z_below) * 0.5;
-            series_result = series.compute_1f1(z_switch);
-            asymptotic_result = asymptotic.compute_1f1(z_switch);
-
-            if z_above - z_below < HYPERGEOMETRIC_PRECISION {
-                accuracy_reached = true;
-                break;
-            }
-        }
-
-        if !accuracy_reached {
-            warn!(
-                "failed to reach sufficient accuracy for the \
-                hypergeometric function with parameters a={} and b={}",
-                a, b
-            );
-        }
-
-        HyperGeometric {
-            is_exponential: false,
-            gamma_ratio: 0.0,
-            series: series,
-            asymptotic: asymptotic,
-            switching_point: z_switch,
-        }
-    }
-
-    pub fn compute(&self, z: f64, z2: f64, derivative: bool) -> f64 {
-        let result;
-        if self.is_exponential {
-            result = self.gamma_ratio * f64::exp(z + z2);
-        } else if z > self.switching_point {
-            result = self.asymptotic.compute(z, z2, derivative);
-        } else {
-            result = self.series.compute(z, z2, derivative);
-        }
-
-        debug_assert!(
-            result.is_finite(),
-            "HyperGeometric overflowed with z={}", z
-        );
-
-        return result;
+        return self.gamma_ratio * self.hyp1f1.scaled(z, z2);
     }
 }
 
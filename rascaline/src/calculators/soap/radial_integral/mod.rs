@@ -6,9 +6,9 @@ use ndarray::ArrayViewMut2;
 /// mor information on the radial integral.
 ///
 /// `std::panic::RefUnwindSafe` is a required super-trait to enable passing
-/// radial integrals across the C API. `Send` is a required super-trait to
-/// enable passing radial integrals between threads.
-pub trait RadialIntegral: std::panic::RefUnwindSafe + Send {
+/// radial integrals across the C API. `Send` and `Sync` are required
+/// super-traits to enable sharing radial integrals between threads.
+pub trait RadialIntegral: std::panic::RefUnwindSafe + Send + Sync {
     /// Compute the radial integral for a single `distance` between two atoms
     /// and store the resulting data in the `max_radial x (max_angular + 1)`
     /// array `values`. If `gradients` is `Some`, also compute and store
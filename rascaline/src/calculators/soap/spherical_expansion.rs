@@ -1,21 +1,25 @@
 use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet};
+use std::f64::consts::FRAC_1_SQRT_2;
+use std::sync::{Arc, Mutex};
 
 use rayon::prelude::*;
-use ndarray::{Array1, Array2, Axis};
+use ndarray::{Array1, Array2, ArrayViewMut1, ArrayViewMut2, Axis};
 use thread_local::ThreadLocal;
 
-use crossbeam::channel::Sender;
+use crossbeam::channel::{Receiver, Sender};
 
 use crate::descriptor::{IndexesBuilder, IndexValue, Indexes, SamplesBuilder, TwoBodiesSpeciesSamples};
-use crate::{Descriptor, Error, System, Vector3D};
+use crate::{Descriptor, Error, ErrorContext, Parallelism, System, Vector3D};
 use crate::types::StackVec;
 
-use super::super::CalculatorBase;
+use super::super::{CalculatorBase, GradientsCache};
 use super::RadialIntegral;
 use super::{GtoRadialIntegral, GtoParameters};
 use super::{SplinedRadialIntegral, SplinedRIParameters};
+use super::kspace::{KSpaceExpansion, KSpaceParameters, KSpaceSamples};
 
-use super::{SphericalHarmonics, SphericalHarmonicsArray};
+use crate::math::{SphericalHarmonicsArray, SphericalHarmonicsCache};
 
 /// Specialized function to compute (-1)^l. Using this instead of
 /// `f64::powi(-1.0, l as i32)` shaves 10% of the computational time
@@ -51,37 +55,187 @@ pub enum RadialBasis {
 }
 
 impl RadialBasis {
-    fn construct(&self, parameters: &SphericalExpansionParameters) -> Result<Box<dyn RadialIntegral>, Error> {
-        match self {
-            RadialBasis::Gto {} => {
-                let parameters = GtoParameters {
-                    max_radial: parameters.max_radial,
-                    max_angular: parameters.max_angular,
-                    atomic_gaussian_width: parameters.atomic_gaussian_width,
-                    cutoff: parameters.cutoff,
-                };
-                return Ok(Box::new(GtoRadialIntegral::new(parameters)?));
+    fn gto_parameters(parameters: &SphericalExpansionParameters) -> GtoParameters {
+        GtoParameters {
+            max_radial: parameters.max_radial,
+            max_angular: parameters.max_angular,
+            atomic_gaussian_width: parameters.atomic_gaussian_width,
+            cutoff: parameters.cutoff,
+        }
+    }
+
+    fn spline_parameters(parameters: &SphericalExpansionParameters) -> SplinedRIParameters {
+        SplinedRIParameters {
+            max_radial: parameters.max_radial,
+            max_angular: parameters.max_angular,
+            cutoff: parameters.cutoff,
+        }
+    }
+
+    /// Check that this radial basis can be used with the given parameters,
+    /// without creating the (potentially expensive) spline. The spline is
+    /// created by `SphericalExpansion::precompute`, which also reports errors
+    /// when creating the calculator.
+    fn validate(&self, parameters: &SphericalExpansionParameters) -> Result<(), Error> {
+        GtoRadialIntegral::new(RadialBasis::gto_parameters(parameters))?;
+        if let RadialBasis::SplinedGto { accuracy } = self {
+            if *accuracy < 0.0 {
+                return Err(Error::InvalidParameter(format!(
+                    "got invalid accuracy in spline ({}), it must be positive", accuracy
+                )));
             }
+        }
+        return Ok(());
+    }
+
+    /// Get the splined radial integral for this radial basis, or `None` if
+    /// this radial basis does not use splines.
+    ///
+    /// Splines are shared through `SPLINE_CACHE`, and only created if no
+    /// spline with the same parameters is currently in the cache.
+    fn spline(&self, parameters: &SphericalExpansionParameters) -> Result<Option<Arc<SplinedRadialIntegral>>, Error> {
+        match self {
+            RadialBasis::Gto {} => return Ok(None),
             RadialBasis::SplinedGto { accuracy } => {
-                let parameters = GtoParameters {
-                    max_radial: parameters.max_radial,
-                    max_angular: parameters.max_angular,
-                    atomic_gaussian_width: parameters.atomic_gaussian_width,
-                    cutoff: parameters.cutoff,
-                };
-                let gto = GtoRadialIntegral::new(parameters)?;
+                let key = SplineCacheKey::new(parameters, *accuracy);
+                if let Some(spline) = SPLINE_CACHE.lock().expect("poisoned mutex").get(&key) {
+                    return Ok(Some(spline));
+                }
 
-                let parameters = SplinedRIParameters {
-                    max_radial: parameters.max_radial,
-                    max_angular: parameters.max_angular,
-                    cutoff: parameters.cutoff,
-                };
-                return Ok(Box::new(SplinedRadialIntegral::with_accuracy(parameters, *accuracy, gto)?));
+                // create the spline without holding the lock, since this can
+                // take a long time
+                let gto = GtoRadialIntegral::new(RadialBasis::gto_parameters(parameters))?;
+                let spline = Arc::new(SplinedRadialIntegral::with_accuracy(
+                    RadialBasis::spline_parameters(parameters), *accuracy, gto
+                )?);
+
+                // another thread might have created the same spline while we
+                // were not holding the lock, in which case `insert` gives us
+                // the existing one
+                let spline = SPLINE_CACHE.lock().expect("poisoned mutex").insert(key, spline);
+                return Ok(Some(spline));
+            }
+        }
+    }
+
+    fn construct(&self, parameters: &SphericalExpansionParameters) -> Result<Arc<dyn RadialIntegral>, Error> {
+        match self {
+            RadialBasis::Gto {} => {
+                let gto = GtoRadialIntegral::new(RadialBasis::gto_parameters(parameters))?;
+                return Ok(Arc::new(gto));
+            }
+            RadialBasis::SplinedGto { .. } => {
+                let spline = self.spline(parameters)?.expect("missing spline for SplinedGto");
+                return Ok(spline);
             }
         };
     }
 }
 
+/// Parameters identifying a splined GTO radial integral in `SPLINE_CACHE`.
+/// Floating point parameters are stored using their bit representation to be
+/// compared exactly.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct SplineCacheKey {
+    max_radial: usize,
+    max_angular: usize,
+    cutoff: u64,
+    atomic_gaussian_width: u64,
+    accuracy: u64,
+}
+
+impl SplineCacheKey {
+    fn new(parameters: &SphericalExpansionParameters, accuracy: f64) -> SplineCacheKey {
+        SplineCacheKey {
+            max_radial: parameters.max_radial,
+            max_angular: parameters.max_angular,
+            cutoff: parameters.cutoff.to_bits(),
+            atomic_gaussian_width: parameters.atomic_gaussian_width.to_bits(),
+            accuracy: accuracy.to_bits(),
+        }
+    }
+}
+
+/// Maximal number of splines kept in `SPLINE_CACHE`
+const SPLINE_CACHE_CAPACITY: usize = 8;
+
+/// Least recently used cache of splined radial integrals. The cache keeps the
+/// splines alive, so calculators created one after the other with the same
+/// parameters share the spline, even if the first calculator was dropped
+/// before the second one was created. When the cache is full, the least
+/// recently used spline is removed from the cache, and its memory is released
+/// once no calculator uses it anymore.
+struct SplineCache {
+    capacity: usize,
+    /// Cached splines, from the least to the most recently used
+    splines: Vec<(SplineCacheKey, Arc<SplinedRadialIntegral>)>,
+}
+
+impl SplineCache {
+    fn new(capacity: usize) -> SplineCache {
+        SplineCache {
+            capacity: capacity,
+            splines: Vec::new(),
+        }
+    }
+
+    /// Get the spline corresponding to `key`, marking it as the most recently
+    /// used one
+    fn get(&mut self, key: &SplineCacheKey) -> Option<Arc<SplinedRadialIntegral>> {
+        let position = self.splines.iter().position(|(cached, _)| cached == key)?;
+        let entry = self.splines.remove(position);
+        let spline = Arc::clone(&entry.1);
+        self.splines.push(entry);
+        return Some(spline);
+    }
+
+    /// Add a `spline` corresponding to `key` to the cache, removing the least
+    /// recently used splines if the cache is full. If the cache already
+    /// contains a spline for `key`, this one is returned instead.
+    fn insert(&mut self, key: SplineCacheKey, spline: Arc<SplinedRadialIntegral>) -> Arc<SplinedRadialIntegral> {
+        if let Some(existing) = self.get(&key) {
+            return existing;
+        }
+
+        self.splines.push((key, Arc::clone(&spline)));
+        if self.splines.len() > self.capacity {
+            let extra = self.splines.len() - self.capacity;
+            self.splines.drain(..extra);
+        }
+        return spline;
+    }
+
+    fn clear(&mut self) {
+        self.splines.clear();
+    }
+}
+
+lazy_static::lazy_static!{
+    /// Splined radial integrals shared between all calculators created with
+    /// the same parameters, to only pay the cost of creating the spline once.
+    static ref SPLINE_CACHE: Mutex<SplineCache> = Mutex::new(SplineCache::new(SPLINE_CACHE_CAPACITY));
+}
+
+/// Remove all the splined radial integrals from the cache shared by spherical
+/// expansion calculators, releasing the memory used by the splines which are
+/// not used by any calculator. Calculators already using these splines are
+/// not affected, but new calculators will create new splines instead of
+/// sharing them with existing calculators.
+pub fn clear_spline_cache() {
+    SPLINE_CACHE.lock().expect("poisoned mutex").clear();
+}
+
+/// Pre-computed state of a spherical expansion, as created by
+/// `SphericalExpansion::save_state`
+#[derive(serde::Deserialize)]
+struct SavedSpline {
+    /// Width of the atomic gaussian used to create the spline. This is not
+    /// part of `SplinedRIParameters`, but changes the splined values.
+    atomic_gaussian_width: f64,
+    /// The splined radial integral
+    spline: SplinedRadialIntegral,
+}
+
 /// Possible values for the smoothing cutoff function
 #[derive(Debug, Clone, Copy)]
 #[derive(serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
@@ -225,6 +379,40 @@ impl RadialScaling {
     }
 }
 
+/// Convention used for the spherical harmonics in the spherical expansion
+/// coefficients, to match the output of other codes.
+///
+/// The default real spherical harmonics follow the definition used on
+/// [Wikipedia](https://en.wikipedia.org/wiki/Spherical_harmonics#Real_form),
+/// which does not include the Condon-Shortley phase.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+pub enum SphericalHarmonicsConvention {
+    /// Real spherical harmonics `Y_l^m`. If `condon_shortley_phase` is `true`,
+    /// all harmonics with odd `m` are multiplied by -1, which corresponds to
+    /// real harmonics built from complex harmonics including the
+    /// Condon-Shortley phase.
+    Real {
+        condon_shortley_phase: bool,
+    },
+    /// Complex spherical harmonics. The coefficients are projections of the
+    /// density on the complex harmonics, i.e. `c_{nlm} = \sum_j R_{nl}(r_j)
+    /// conj(Y_l^m(r_j))`. The features contain an additional `component`
+    /// index, with 0 for the real part and 1 for the imaginary part of the
+    /// coefficients. If `condon_shortley_phase` is `true`, the harmonics with
+    /// positive odd `m` are multiplied by -1, such that `Y_l^{-m} = (-1)^m
+    /// conj(Y_l^m)`; otherwise `Y_l^{-m} = conj(Y_l^m)`.
+    Complex {
+        condon_shortley_phase: bool,
+    },
+}
+
+impl Default for SphericalHarmonicsConvention {
+    fn default() -> SphericalHarmonicsConvention {
+        SphericalHarmonicsConvention::Real { condon_shortley_phase: false }
+    }
+}
+
 /// Parameters for spherical expansion calculator.
 ///
 /// The spherical expansion is at the core of representations in the SOAP
@@ -241,6 +429,13 @@ pub struct SphericalExpansionParameters {
     pub max_radial: usize,
     /// Number of spherical harmonics to use in the expansion
     pub max_angular: usize,
+    /// Number of radial basis function to use for each angular channel, from
+    /// `l=0` to `l=max_angular`. High angular channels usually need fewer
+    /// radial functions, and this can be used to remove the corresponding
+    /// features. All values must be between 1 and `max_radial`. By default,
+    /// `max_radial` radial basis functions are used for all angular channels.
+    #[serde(default)]
+    pub max_radial_per_angular: Option<Vec<usize>>,
     /// Width of the atom-centered gaussian used to create the atomic density
     pub atomic_gaussian_width: f64,
     /// Should we also compute gradients of the feature?
@@ -254,11 +449,78 @@ pub struct SphericalExpansionParameters {
     /// model
     #[serde(default)]
     pub radial_scaling: RadialScaling,
+    /// Weight of the central atom own density in its environment. Setting
+    /// this to 0 removes the contribution of the central atom, and any other
+    /// value scales it.
+    #[serde(default = "default_center_atom_weight")]
+    pub center_atom_weight: f64,
+    /// Name of per-atom data (see `System::data`) containing a weight for
+    /// each atom, for example a partial charge or a site occupancy. When set,
+    /// the contribution of each atom to the density (including the central
+    /// atom own contribution) is multiplied by its weight.
+    #[serde(default)]
+    pub neighbor_weights: Option<String>,
+    /// Group multiple neighbor species in a single channel, for example to use
+    /// the same channel for all halogens. The keys of this map are neighbor
+    /// species, and the values the corresponding channel, which is used in
+    /// place of the species in the `species_neighbor` samples. Species not
+    /// in this map are used as their own channel.
+    #[serde(default)]
+    pub neighbor_channels: BTreeMap<i32, i32>,
+    /// Remove all contributions of the central atom to its own environment:
+    /// the central atom own density (regardless of `center_atom_weight`) and
+    /// the density of the periodic images of the central atom.
+    #[serde(default)]
+    pub exclude_self_pairs: bool,
+    /// Only include the neighbors in the first coordination shell of each
+    /// center. The first shell is detected separately for each center, and
+    /// contains all neighbors at a distance smaller than `(1 + tolerance)`
+    /// times the distance to the closest neighbor of this center. Neighbors
+    /// entering or leaving the first shell make the expansion discontinuous.
+    #[serde(default)]
+    pub first_shell_tolerance: Option<f64>,
+    /// Convention to use for the spherical harmonics in the output. By
+    /// default, real spherical harmonics without the Condon-Shortley phase
+    /// are used.
+    #[serde(default)]
+    pub spherical_harmonics: SphericalHarmonicsConvention,
+    /// Compute the expansion in reciprocal space instead of summing over
+    /// pairs of atoms. This scales better than the real space evaluation for
+    /// dense periodic systems with large cutoffs, and requires all systems to
+    /// be periodic. The reciprocal space evaluation expands the full periodic
+    /// density of all atoms inside the cutoff sphere (with the cutoff function
+    /// and radial scaling applied to the density instead of to each neighbor
+    /// contribution), so the values differ slightly from the real space
+    /// evaluation for atoms close to the cutoff.
+    #[serde(default)]
+    pub kspace: Option<KSpaceParameters>,
+}
+
+/// Default value for `center_atom_weight`, keeping the full contribution of
+/// the central atom
+pub(super) fn default_center_atom_weight() -> f64 {
+    1.0
+}
+
+/// Get the continuous hyper-parameters shared by all SOAP calculators, as JSON
+/// pointers into the parameters: the atomic gaussian width, the width of the
+/// cutoff function and the parameters of the radial scaling, when they are
+/// used.
+pub(super) fn hyper_parameters(cutoff_function: &CutoffFunction, radial_scaling: &RadialScaling) -> Vec<String> {
+    let mut hyper_parameters = vec!["/atomic_gaussian_width".to_string()];
+    if let CutoffFunction::ShiftedCosine { .. } = cutoff_function {
+        hyper_parameters.push("/cutoff_function/ShiftedCosine/width".into());
+    }
+    if let RadialScaling::Willatt2018 { .. } = radial_scaling {
+        hyper_parameters.push("/radial_scaling/Willatt2018/scale".into());
+        hyper_parameters.push("/radial_scaling/Willatt2018/rate".into());
+    }
+    return hyper_parameters;
 }
 
 struct RadialIntegralImpl {
     /// Implementation of the radial integral
-    code: Box<dyn RadialIntegral>,
+    code: Arc<dyn RadialIntegral>,
     /// Cache for the radial integral values
     values: Array2<f64>,
     /// Cache for the radial integral gradient
@@ -266,8 +528,13 @@ struct RadialIntegralImpl {
 }
 
 impl RadialIntegralImpl {
-    fn new(parameters: &SphericalExpansionParameters) -> Result<Self, Error> {
-        let code = parameters.radial_basis.construct(parameters)?;
+    /// Create a new `RadialIntegralImpl`, re-using the already created `spline`
+    /// if there is one.
+    fn new(parameters: &SphericalExpansionParameters, spline: Option<&Arc<SplinedRadialIntegral>>) -> Result<Self, Error> {
+        let code = match spline {
+            Some(spline) => Arc::clone(spline) as Arc<dyn RadialIntegral>,
+            None => parameters.radial_basis.construct(parameters)?,
+        };
         let shape = (parameters.max_radial, parameters.max_angular + 1);
         let values = Array2::from_elem(shape, 0.0);
         let gradients = if parameters.gradients {
@@ -292,45 +559,6 @@ impl RadialIntegralImpl {
     }
 }
 
-struct SphericalHarmonicsImpl {
-    /// Implementation of the spherical harmonics
-    code: SphericalHarmonics,
-    /// Cache for the spherical harmonics values
-    values: SphericalHarmonicsArray,
-    /// Cache for the spherical harmonics gradients (one value each for x/y/z)
-    gradients: Option<[SphericalHarmonicsArray; 3]>,
-}
-
-impl SphericalHarmonicsImpl {
-    fn new(parameters: &SphericalExpansionParameters) -> SphericalHarmonicsImpl {
-        let code = SphericalHarmonics::new(parameters.max_angular);
-        let values = SphericalHarmonicsArray::new(parameters.max_angular);
-        let gradients = if parameters.gradients {
-            Some([
-                SphericalHarmonicsArray::new(parameters.max_angular),
-                SphericalHarmonicsArray::new(parameters.max_angular),
-                SphericalHarmonicsArray::new(parameters.max_angular)
-            ])
-        } else {
-            None
-        };
-
-        return SphericalHarmonicsImpl { code, values, gradients };
-    }
-
-    fn compute(&mut self, direction: Vector3D) {
-        self.code.compute(
-            direction,
-            &mut self.values,
-            self.gradients.as_mut(),
-        );
-    }
-
-    fn compute_no_gradients(&mut self, direction: Vector3D) {
-        self.code.compute(direction, &mut self.values, self.gradients.as_mut());
-    }
-}
-
 /// Identify which atom in a pair we are referring to
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum AtomInPair {
@@ -352,28 +580,30 @@ struct PairContribution {
     ///
     /// For each sample, the first element in the tuple is the position in the
     /// spherical expansion array where we want to accumulate the values (i.e.
-    /// the index of the center); and the second element in the tuple describe
-    /// whether the center is the first or second atom in the pair.
+    /// the index of the center); the second element in the tuple describe
+    /// whether the center is the first or second atom in the pair; and the
+    /// third element is the weight of the neighbor atom.
     ///
     /// The use of `StackVec` instead of `Vec` or `SmallVec` is a performance
     /// optimization.
-    samples: StackVec<[(usize, AtomInPair); 2]>,
+    samples: StackVec<[(usize, AtomInPair, f64); 2]>,
     /// pair contribution to the spherical expansion
     values: Array1<f64>,
 }
 
 impl PairContribution {
-    /// Create a new pair contribution with the given feature `size`
-    fn new(size: usize) -> PairContribution {
+    /// Create a new pair contribution with the given feature `size`, taking
+    /// the array from the `pool`
+    fn new(size: usize, pool: &ArrayPool) -> PairContribution {
         PairContribution {
             samples: StackVec::new(),
-            values: Array1::from_elem(size, 0.0),
+            values: pool.get(size),
         }
     }
 
     /// Add a sample to which this pair contribution should be accumulated into
-    fn add_sample(&mut self, index: usize, center_position_in_pair: AtomInPair) {
-        self.samples.push((index, center_position_in_pair));
+    fn add_sample(&mut self, index: usize, center_position_in_pair: AtomInPair, neighbor_weight: f64) {
+        self.samples.push((index, center_position_in_pair, neighbor_weight));
     }
 }
 
@@ -386,40 +616,136 @@ struct GradientsPairContribution {
     /// For each sample, the first element in the tuple is the position in the
     /// gradient array of the x component of the gradient. The second and third
     /// elements in the tuple describe whether the center/neighbor atoms are the
-    /// the first or second atom in the pair.
+    /// the first or second atom in the pair. The fourth element is the weight
+    /// of the atom which is not the center of the environment.
     ///
     /// The use of `StackVec` instead of `Vec` or `SmallVec` is a performance
     /// optimization.
-    samples: StackVec<[(usize, AtomInPair, AtomInPair); 4]>,
+    samples: StackVec<[(usize, AtomInPair, AtomInPair, f64); 4]>,
     /// gradient w.r.t. each of the cartesian coordinate
     gradients: [Array1<f64>; 3],
 }
 
 impl GradientsPairContribution {
-    /// Create a new gradient pair contribution with the given feature `size`
-    fn new(size: usize) -> GradientsPairContribution {
+    /// Create a new gradient pair contribution with the given feature `size`,
+    /// taking the arrays from the `pool`
+    fn new(size: usize, pool: &ArrayPool) -> GradientsPairContribution {
         GradientsPairContribution {
             samples: StackVec::new(),
-            gradients: [
-                Array1::from_elem(size, 0.0),
-                Array1::from_elem(size, 0.0),
-                Array1::from_elem(size, 0.0),
-            ],
+            gradients: [pool.get(size), pool.get(size), pool.get(size)],
         }
     }
 
     /// Add a sample to which this pair contribution should be accumulated into
-    fn add_sample(&mut self, index: usize, center_position_in_pair: AtomInPair, neighbor_position_in_pair: AtomInPair) {
-        self.samples.push((index, center_position_in_pair, neighbor_position_in_pair));
+    fn add_sample(
+        &mut self,
+        index: usize,
+        center_position_in_pair: AtomInPair,
+        neighbor_position_in_pair: AtomInPair,
+        neighbor_weight: f64,
+    ) {
+        self.samples.push((index, center_position_in_pair, neighbor_position_in_pair, neighbor_weight));
+    }
+}
+
+/// Pool of arrays used to store the contributions of pairs, which are
+/// created in the "compute" threads and dropped in a "writer" thread. The
+/// writer threads send the arrays back to the pool once the contribution has
+/// been accumulated, and the compute threads take them from the pool instead
+/// of allocating new ones. The arrays are kept in the calculator workspace
+/// between calls to `compute`.
+struct ArrayPool {
+    sender: Sender<Array1<f64>>,
+    receiver: Receiver<Array1<f64>>,
+}
+
+/// Temporary buffers used by `SphericalExpansion`, kept in the calculator
+/// workspace between calls
+#[derive(Default)]
+struct Buffers {
+    /// arrays from `ArrayPool`
+    contributions: Vec<Array1<f64>>,
+}
+
+impl ArrayPool {
+    /// Create a new pool containing the given `arrays`
+    fn new(arrays: Vec<Array1<f64>>) -> ArrayPool {
+        let (sender, receiver) = crossbeam::channel::unbounded();
+        for array in arrays {
+            sender.send(array).expect("receiver hanged up");
+        }
+        ArrayPool { sender, receiver }
+    }
+
+    /// Get an array of the given `size` filled with zeros, re-using one of
+    /// the arrays in the pool if possible
+    fn get(&self, size: usize) -> Array1<f64> {
+        if size == 0 {
+            return Array1::zeros(0);
+        }
+
+        while let Ok(mut array) = self.receiver.try_recv() {
+            // arrays with the wrong size come from a previous calculation
+            // with different features, and are dropped
+            if array.len() == size {
+                array.fill(0.0);
+                return array;
+            }
+        }
+
+        return Array1::zeros(size);
+    }
+
+    /// Give back an `array` to the pool
+    fn recycle(&self, array: Array1<f64>) {
+        if !array.is_empty() {
+            self.sender.send(array).expect("receiver hanged up");
+        }
+    }
+
+    /// Give back the arrays of a gradients `contribution` to the pool
+    fn recycle_gradients(&self, contribution: GradientsPairContribution) {
+        let [gradient_x, gradient_y, gradient_z] = contribution.gradients;
+        self.recycle(gradient_x);
+        self.recycle(gradient_y);
+        self.recycle(gradient_z);
+    }
+
+    /// Get all the arrays currently in the pool
+    fn into_arrays(self) -> Vec<Array1<f64>> {
+        return self.receiver.try_iter().collect();
     }
 }
 
+/// Add `weight * m_1_pow_l * values` to `row`, without allocating a temporary
+/// array for the product
+fn scaled_add_reversed(mut row: ArrayViewMut1<f64>, weight: f64, m_1_pow_l: &Array1<f64>, values: &Array1<f64>) {
+    for ((output, &sign), &value) in row.iter_mut().zip(m_1_pow_l).zip(values) {
+        *output += weight * sign * value;
+    }
+}
+
+/// Data needed to compute the spherical expansion for a single system: the
+/// index of the system, the species and weights (if any) of the atoms, the
+/// list of pairs in the system and the radius of the first coordination shell
+/// of all atoms (if any)
+type SystemPairs<'a> = (usize, &'a [i32], &'a [crate::systems::Pair], Option<&'a [f64]>, Option<&'a [f64]>);
+
 /// The actual calculator used to compute SOAP spherical expansion coefficients
 pub struct SphericalExpansion {
     /// Parameters governing the spherical expansion
     parameters: SphericalExpansionParameters,
+    /// Splined radial integral shared by all threads. Creating the spline can
+    /// be expensive, so this is done in `precompute` (called by
+    /// `Calculator::new`), on the first call to `compute`, or restored from a
+    /// saved state with `load_state`. This is always `None` if the radial
+    /// basis does not use splines.
+    spline: Option<Arc<SplinedRadialIntegral>>,
     radial_integral: ThreadLocal<RefCell<RadialIntegralImpl>>,
-    spherical_harmonics: ThreadLocal<RefCell<SphericalHarmonicsImpl>>,
+    spherical_harmonics: ThreadLocal<RefCell<SphericalHarmonicsCache>>,
+    /// Reciprocal space implementation of the expansion, if requested in the
+    /// parameters
+    kspace: Option<KSpaceExpansion>,
 }
 
 impl std::fmt::Debug for SphericalExpansion {
@@ -430,19 +756,84 @@ impl std::fmt::Debug for SphericalExpansion {
 
 impl SphericalExpansion {
     /// Create a new `SphericalExpansion` calculator with the given parameters
+    #[allow(clippy::float_cmp)]
     pub fn new(parameters: SphericalExpansionParameters) -> Result<SphericalExpansion, Error> {
         // validate parameters once in the constructor
         parameters.cutoff_function.validate()?;
         parameters.radial_scaling.validate()?;
-        RadialIntegralImpl::new(&parameters)?;
+        parameters.radial_basis.validate(&parameters)?;
+
+        if !parameters.center_atom_weight.is_finite() {
+            return Err(Error::InvalidParameter(format!(
+                "expected a finite value for center_atom_weight, got {}",
+                parameters.center_atom_weight
+            )));
+        }
+
+        if let Some(ref max_radial_per_angular) = parameters.max_radial_per_angular {
+            if max_radial_per_angular.len() != parameters.max_angular + 1 {
+                return Err(Error::InvalidParameter(format!(
+                    "expected {} values in max_radial_per_angular, got {}",
+                    parameters.max_angular + 1, max_radial_per_angular.len()
+                )));
+            }
+
+            for (l, &max_radial) in max_radial_per_angular.iter().enumerate() {
+                if max_radial == 0 || max_radial > parameters.max_radial {
+                    return Err(Error::InvalidParameter(format!(
+                        "max_radial_per_angular for l={} must be between 1 and max_radial ({}), got {}",
+                        l, parameters.max_radial, max_radial
+                    )));
+                }
+            }
+        }
+
+        if let Some(tolerance) = parameters.first_shell_tolerance {
+            if !(tolerance >= 0.0 && tolerance.is_finite()) {
+                return Err(Error::InvalidParameter(format!(
+                    "first_shell_tolerance must be a positive number, got {}",
+                    tolerance
+                )));
+            }
+        }
+
+        let kspace = match parameters.kspace {
+            Some(kspace) => {
+                if parameters.center_atom_weight != 1.0 {
+                    return Err(Error::InvalidParameter(format!(
+                        "center_atom_weight must be 1 when using the k-space spherical expansion, got {}",
+                        parameters.center_atom_weight
+                    )));
+                }
+
+                if parameters.exclude_self_pairs || parameters.first_shell_tolerance.is_some() {
+                    return Err(Error::InvalidParameter(
+                        "exclude_self_pairs and first_shell_tolerance can not be used with the k-space spherical expansion".into()
+                    ));
+                }
+                Some(KSpaceExpansion::new(&parameters, kspace)?)
+            }
+            None => None,
+        };
 
         return Ok(SphericalExpansion {
             parameters,
+            spline: None,
             radial_integral: ThreadLocal::new(),
             spherical_harmonics: ThreadLocal::new(),
+            kspace: kspace,
         });
     }
 
+    /// Create the splined radial integral if this calculator uses one and it
+    /// was not created yet
+    fn ensure_spline(&mut self) -> Result<(), Error> {
+        if self.spline.is_none() {
+            self.spline = self.parameters.radial_basis.spline(&self.parameters)?;
+        }
+        return Ok(());
+    }
+
     /// Compute the product of radial scaling & cutoff smoothing functions
     fn scaling_functions(&self, r: f64) -> f64 {
         let cutoff = self.parameters.cutoff_function.compute(r, self.parameters.cutoff);
@@ -463,30 +854,44 @@ impl SphericalExpansion {
 
     /// Compute the self contribution to spherical expansion, i.e. the
     /// contribution of the central atom own density to the expansion around
-    /// itself.
+    /// itself, scaled by `center_atom_weight` and by the weight of the central
+    /// atom.
     ///
     /// The self contribution does not have contributions to the gradients
-    fn do_self_contributions(&mut self, descriptor: &mut Descriptor) {
+    fn do_self_contributions(&mut self, systems: &[Box<dyn System>], descriptor: &mut Descriptor) -> Result<(), Error> {
+        let center_atom_weight = self.parameters.center_atom_weight;
+        if center_atom_weight == 0.0 || self.parameters.exclude_self_pairs {
+            return Ok(());
+        }
+
+        let mut weights = Vec::with_capacity(systems.len());
+        for (i_system, system) in systems.iter().enumerate() {
+            weights.push(self.neighbor_weights(i_system, &**system)?);
+        }
+
         // we could cache the self contribution since they only depend on the
         // gaussian atomic width. For now, we recompute them all the time
 
         let mut radial_integral = self.radial_integral.get_or(|| {
-            let ri = RadialIntegralImpl::new(&self.parameters).expect("invalid parameters");
+            let ri = RadialIntegralImpl::new(&self.parameters, self.spline.as_ref()).expect("invalid parameters");
             RefCell::new(ri)
         }).borrow_mut();
 
         let mut spherical_harmonics = self.spherical_harmonics.get_or(|| {
-            RefCell::new(SphericalHarmonicsImpl::new(&self.parameters))
+            RefCell::new(SphericalHarmonicsCache::new(self.parameters.max_angular, self.parameters.gradients))
         }).borrow_mut();
 
         for (i_env, requested_env) in descriptor.samples.iter().enumerate() {
-            let species_center = requested_env[2];
-            let species_neighbor = requested_env[3];
+            let structure = requested_env[0].usize();
+            let center = requested_env[1].usize();
+            let species_center = requested_env[2].i32();
+            let species_neighbor = requested_env[3].i32();
 
-            if species_center == species_neighbor {
+            if self.channel(species_center) == species_neighbor {
                 radial_integral.compute_no_gradients(0.0);
                 spherical_harmonics.compute_no_gradients(Vector3D::new(0.0, 0.0, 1.0));
-                let f_scaling = self.scaling_functions(0.0);
+                let weight = weights[structure].map_or(1.0, |weights| weights[center]);
+                let f_scaling = weight * center_atom_weight * self.scaling_functions(0.0);
 
                 for (feature_i, feature) in descriptor.features.iter().enumerate() {
                     let l = feature[0].usize();
@@ -500,63 +905,114 @@ impl SphericalExpansion {
                 }
             }
         }
+
+        return Ok(());
+    }
+
+    /// Get the number of radial basis functions used for the angular channel
+    /// `l`
+    fn max_radial(&self, l: usize) -> usize {
+        return self.parameters.max_radial_per_angular.as_ref()
+            .map_or(self.parameters.max_radial, |max_radial| max_radial[l]);
+    }
+
+    /// Get the neighbor channel corresponding to the given `species`
+    fn channel(&self, species: i32) -> i32 {
+        return self.parameters.neighbor_channels.get(&species).copied().unwrap_or(species);
+    }
+
+    /// Get the radius of the first coordination shell of all atoms in
+    /// `system`, if this calculator uses `first_shell_tolerance`. The neighbor
+    /// list of the system must already be computed.
+    fn first_shell_radii(&self, system: &dyn System) -> Result<Option<Vec<f64>>, Error> {
+        let tolerance = match self.parameters.first_shell_tolerance {
+            Some(tolerance) => tolerance,
+            None => return Ok(None),
+        };
+
+        let mut closest = vec![f64::INFINITY; system.size()?];
+        for pair in system.pairs()? {
+            if self.parameters.exclude_self_pairs && pair.first == pair.second {
+                continue;
+            }
+
+            closest[pair.first] = f64::min(closest[pair.first], pair.distance);
+            closest[pair.second] = f64::min(closest[pair.second], pair.distance);
+        }
+
+        let radii = closest.into_iter()
+            .map(|distance| (1.0 + tolerance) * distance)
+            .collect();
+
+        return Ok(Some(radii));
+    }
+
+    /// Get the weights of all atoms in `system` (the structure at index
+    /// `i_system`), if this calculator uses `neighbor_weights`
+    fn neighbor_weights<'a>(&self, i_system: usize, system: &'a dyn System) -> Result<Option<&'a [f64]>, Error> {
+        let name = match self.parameters.neighbor_weights {
+            Some(ref name) => name,
+            None => return Ok(None),
+        };
+
+        let weights = system.data(name)?.ok_or_else(|| Error::InvalidParameter(format!(
+            "missing per-atom data '{}' for the neighbor weights in structure {}", name, i_system
+        )))?;
+
+        if weights.len() != system.size()? {
+            return Err(Error::InvalidParameter(format!(
+                "expected {} values in per-atom data '{}' for structure {}, got {}",
+                system.size()?, name, i_system, weights.len()
+            )));
+        }
+
+        return Ok(Some(weights));
     }
 
     /// Accumulate the spherical expansion coefficients for the given pair.
     ///
-    /// This function passes results back to calling code though `sender`
+    /// This function passes results back to calling code though `sender`,
+    /// together with `pair_index`, the position of this pair in the list of
+    /// all pairs. The arrays for the results are taken from `pool`.
+    #[allow(clippy::too_many_arguments)]
     fn accumulate_for_pair(
         &self,
-        sender: &Sender<PairContribution>,
+        sender: &Sender<(usize, PairContribution)>,
+        pool: &ArrayPool,
+        pair_index: usize,
         samples: &Indexes,
         features: &Indexes,
-        pair: &Pair
+        pair: &Pair,
+        cache: Option<&Mutex<Vec<CachedPair>>>,
     ) -> (Option<usize>, Option<usize>) {
-        let first_sample_i = samples.position(&[
-            IndexValue::from(pair.system),
-            IndexValue::from(pair.first),
-            IndexValue::from(pair.species_first),
-            IndexValue::from(pair.species_second),
-        ]);
-
-        let second_sample_i = if pair.first == pair.second {
-            // do not compute for the reversed pair if the pair is between an
-            // atom and its image
-            None
-        } else {
-            samples.position(&[
-                IndexValue::from(pair.system),
-                IndexValue::from(pair.second),
-                IndexValue::from(pair.species_second),
-                IndexValue::from(pair.species_first),
-            ])
-        };
-
+        let (first_sample_i, second_sample_i) = pair_samples(samples, pair);
         if first_sample_i.is_none() && second_sample_i.is_none() {
-            // nothing to do
+            // nothing to do, but the receiver still needs to know that this
+            // pair has been handled
+            sender.send((pair_index, PairContribution::new(0, pool))).expect("receiver hanged up");
             return (None, None);
         }
 
         let mut radial_integral = self.radial_integral.get_or(|| {
-            let ri = RadialIntegralImpl::new(&self.parameters).expect("invalid parameters");
+            let ri = RadialIntegralImpl::new(&self.parameters, self.spline.as_ref()).expect("invalid parameters");
             RefCell::new(ri)
         }).borrow_mut();
 
         let mut spherical_harmonics = self.spherical_harmonics.get_or(|| {
-            RefCell::new(SphericalHarmonicsImpl::new(&self.parameters))
+            RefCell::new(SphericalHarmonicsCache::new(self.parameters.max_angular, self.parameters.gradients))
         }).borrow_mut();
 
         radial_integral.compute(pair.distance);
         spherical_harmonics.compute(pair.direction);
         let f_scaling = self.scaling_functions(pair.distance);
 
-        let mut pair_contribution = PairContribution::new(features.count());
+        let mut pair_contribution = PairContribution::new(features.count(), pool);
         if let Some(index) = first_sample_i {
-            pair_contribution.add_sample(index, AtomInPair::First);
+            pair_contribution.add_sample(index, AtomInPair::First, pair.weight_second);
         }
 
         if let Some(index) = second_sample_i {
-            pair_contribution.add_sample(index, AtomInPair::Second);
+            pair_contribution.add_sample(index, AtomInPair::Second, pair.weight_first);
         }
 
         for (feature_i, feature) in features.iter().enumerate() {
@@ -571,18 +1027,36 @@ impl SphericalExpansion {
             pair_contribution.values[feature_i] = n_l_m_value;
         }
 
-        sender.send(pair_contribution).expect("receiver hanged up");
+        sender.send((pair_index, pair_contribution)).expect("receiver hanged up");
+
+        if let Some(cache) = cache {
+            let cached = CachedPair {
+                index: pair_index,
+                pair: pair.clone(),
+                f_scaling: f_scaling,
+                f_scaling_grad: self.scaling_functions_gradient(pair.distance),
+                ri_values: radial_integral.values.clone(),
+                ri_gradients: radial_integral.gradients.clone().expect("missing radial integral gradients"),
+                sph_values: spherical_harmonics.values.clone(),
+                sph_gradients: spherical_harmonics.gradients.clone().expect("missing spherical harmonics gradients"),
+            };
+            cache.lock().expect("poisoned mutex").push(cached);
+        }
+
         return (first_sample_i, second_sample_i);
     }
 
     /// Accumulate the spherical expansion gradients for the given pair.
     ///
     /// This function assumes that the radial integral and spherical harmonics
-    /// have just been computed for this pair in the current thread.
-    #[allow(clippy::needless_range_loop)]
+    /// have just been computed for this pair in the current thread. The arrays
+    /// for the results are taken from `pool`.
+    #[allow(clippy::too_many_arguments)]
     fn accumulate_gradient_for_pair(
         &self,
-        sender: &Sender<GradientsPairContribution>,
+        sender: &Sender<(usize, GradientsPairContribution)>,
+        pool: &ArrayPool,
+        pair_index: usize,
         gradients_samples: &Indexes,
         features: &Indexes,
         pair: &Pair,
@@ -593,105 +1067,644 @@ impl SphericalExpansion {
         // Check if any of the sample corresponding to the current pair
         // actually contributes to the selected samples, or exit early
         if samples.0.is_none() && samples.1.is_none() {
+            sender.send((pair_index, GradientsPairContribution::new(0, pool))).expect("receiver hanged up");
             return;
         }
 
-        // get the positions in the gradients array where to store the
-        // contributions to the gradient for this specific pair, if they
-        // exist in the set of requested samples
+        let radial_integral = self.radial_integral.get_or(|| {
+            let ri = RadialIntegralImpl::new(&self.parameters, self.spline.as_ref()).expect("invalid parameters");
+            RefCell::new(ri)
+        }).borrow();
 
-        // store derivative w.r.t. `second` of the environment around `first`
-        let first_grad_i = samples.0.map(|i_sample| {
-            gradients_samples.position(&[
-                IndexValue::from(i_sample), IndexValue::from(pair.second), IndexValue::from(0)
-            ]).expect("this pair should contribute to this gradient")
-        });
+        let spherical_harmonics = self.spherical_harmonics.get_or(|| {
+            RefCell::new(SphericalHarmonicsCache::new(self.parameters.max_angular, self.parameters.gradients))
+        }).borrow();
 
-        // store derivative w.r.t. `first` of the environment around `first`
-        let first_self_grad_i = samples.0.map(|i_sample| {
-            gradients_samples.position(&[
-                IndexValue::from(i_sample), IndexValue::from(pair.first), IndexValue::from(0)
-            ]).expect("this pair should contribute to this gradient")
-        });
+        let pair_contribution = gradients_pair_contribution(
+            pool,
+            gradients_samples,
+            features,
+            pair,
+            samples,
+            (&radial_integral.values, radial_integral.gradients.as_ref().expect("missing radial integral gradients")),
+            (&spherical_harmonics.values, spherical_harmonics.gradients.as_ref().expect("missing spherical harmonics gradients")),
+            (self.scaling_functions(pair.distance), self.scaling_functions_gradient(pair.distance)),
+        );
 
-        // store derivative w.r.t. `first` of the environment around `second`
-        let second_grad_i = samples.1.map(|i_sample| {
-            gradients_samples.position(&[
-                IndexValue::from(i_sample), IndexValue::from(pair.first), IndexValue::from(0)
-            ]).expect("this pair should contribute to this gradient")
-        });
-        // store derivative w.r.t. `second` of the environment around `second`
-        let second_self_grad_i = samples.1.map(|i_sample| {
-            gradients_samples.position(&[
-                IndexValue::from(i_sample), IndexValue::from(pair.second), IndexValue::from(0)
-            ]).expect("this pair should contribute to this gradient")
-        });
+        sender.send((pair_index, pair_contribution)).expect("receiver hanged up");
+    }
+
+    /// Accumulate the contributions of all pairs in the given `systems` to the
+    /// spherical expansion, storing them in the `descriptor`. Each entry in
+    /// `systems` contains the index of a system, the atomic species, the list
+    /// of pairs in this system and the weights of the atoms (if any).
+    ///
+    /// If `parallel_systems` is `true`, work is distributed over threads one
+    /// atomic environment at the time across all systems, using the number
+    /// of pairs around each center to balance the load (see
+    /// [`balanced_work_items`]); otherwise work is distributed one pair at the
+    /// time.
+    ///
+    /// If the current calculation is deterministic, the contributions of the
+    /// pairs are accumulated in the order of the pairs in `systems`, making
+    /// the results independent of the number of threads and of the way work
+    /// is distributed between them.
+    #[allow(clippy::too_many_lines)]
+    fn accumulate_all_pairs(
+        &self,
+        descriptor: &mut Descriptor,
+        systems: &[SystemPairs],
+        parallel_systems: bool,
+        cache: Option<&Mutex<Vec<CachedPair>>>,
+    ) {
+        // Setup parallel computation.
+        //
+        // This code distribute work for computing the spherical expansion
+        // over multiple threads, iterating over pairs (or systems) in
+        // parallel.
+        //
+        // To workaround the fact that each working thread would like to
+        // write data to potentially the same location as other threads
+        // (since two different pairs can produce data to be stored in the
+        // same environment), we use channels to send data two other threads
+        // (one for values, one for gradients) that only receive data and
+        // write it to the descriptor.
+        let samples = &descriptor.samples;
+        let gradient_samples = descriptor.gradients_samples.as_ref();
+        let features = &descriptor.features;
+        let values = &mut descriptor.values;
+        let gradients = descriptor.gradients.as_mut();
+
+        let deterministic = crate::calculator::deterministic();
+        // position of the first pair of each system in the list of all pairs
+        let mut first_pair_index = Vec::with_capacity(systems.len());
+        let mut n_pairs = 0;
+        for &(_, _, pairs, _, _) in systems {
+            first_pair_index.push(n_pairs);
+            n_pairs += pairs.len();
+        }
+        let first_pair_index = &first_pair_index;
+
+        // use crossbeam channels instead of std::sync::mpsc::SyncChannel
+        // since crossbeam is faster in our case.
+        let (sender_values, receiver_values) = crossbeam::channel::unbounded::<(usize, PairContribution)>();
+        let (sender_grad, receiver_grad) = crossbeam::channel::unbounded::<(usize, GradientsPairContribution)>();
+
+        // re-use the arrays for the pairs contributions between pairs, and
+        // between calls
+        let workspace = crate::calculator::workspace();
+        let mut buffers = workspace.take::<Buffers>();
+        let array_pool = ArrayPool::new(std::mem::take(&mut buffers.contributions));
+        let pool = &array_pool;
+
+        // use crossbeam scoped threads instead of rayon's, to ensure we
+        // make progress even with RAYON_NUM_THREADS=1
+        crossbeam::thread::scope(|s|{
+            // re-borrow self as an immutable reference to be passed to
+            // the closure producing values below
+            let this = &*self;
+
+            // Start a thread to receive and collect values
+            s.spawn(move |_| {
+                let m_1_pow_l = features.iter()
+                    .map(|feature| m_1_pow(feature[0].usize()))
+                    .collect::<Array1<f64>>();
+
+                receive_all(receiver_values, deterministic, |contribution| {
+                    for &(index, center, weight) in contribution.samples.iter() {
+                        let mut row = values.index_axis_mut(Axis(0), index);
+                        match center {
+                            AtomInPair::First => {
+                                row.scaled_add(weight, &contribution.values);
+                            }
+                            AtomInPair::Second => {
+                                // Use the fact that `se[n, l, m](-r) =
+                                // (-1)^l se[n, l, m](r)` where se is the
+                                // spherical expansion
+                                scaled_add_reversed(row, weight, &m_1_pow_l, &contribution.values);
+                            }
+                        }
+                    }
+                    pool.recycle(contribution.values);
+                });
+            });
+
+            // Start a thread to receive and collect gradients. There is no
+            // storage for gradients when computing lazy gradients, in which
+            // case the gradients are not computed here.
+            if let Some(gradients) = gradients {
+                s.spawn(move |_| {
+                    let mut gradients = gradients.view_mut();
+                    let m_1_pow_l = features.iter()
+                        .map(|feature| m_1_pow(feature[0].usize()))
+                        .collect::<Array1<f64>>();
+
+                    receive_all(receiver_grad, deterministic, |contribution| {
+                        accumulate_gradients_contribution(&mut gradients, &m_1_pow_l, &contribution);
+                        pool.recycle_gradients(contribution);
+                    });
+                });
+            }
 
-        debug_assert!(!(
-            first_grad_i.is_none() &&
-            first_self_grad_i.is_none() &&
-            second_grad_i.is_none() &&
-            second_self_grad_i.is_none()
-        ));
+            // Produce values in the current thread. This uses all the
+            // threads in the rayon thread pool of the current calculation
+            // to actually do the work, while the threads started above
+            // collect the results.
+            crate::calculator::install(move || {
+                let accumulate = |pair_index: usize, system: &SystemPairs, pair: &crate::systems::Pair| {
+                    let &(i_system, species, _, weights, first_shell) = system;
+
+                    let self_pair = this.parameters.exclude_self_pairs && pair.first == pair.second;
+                    let in_first_shell = |center: usize| match first_shell {
+                        Some(radii) => pair.distance <= radii[center],
+                        None => true,
+                    };
+
+                    let mut pair = Pair {
+                        system: i_system,
+                        first: pair.first,
+                        second: pair.second,
+                        species_first: species[pair.first],
+                        species_second: species[pair.second],
+                        channel_first: this.channel(species[pair.first]),
+                        channel_second: this.channel(species[pair.second]),
+                        weight_first: weights.map_or(1.0, |weights| weights[pair.first]),
+                        weight_second: weights.map_or(1.0, |weights| weights[pair.second]),
+                        distance: pair.distance,
+                        direction: pair.vector / pair.distance,
+                        in_first_environment: !self_pair && in_first_shell(pair.first),
+                        in_second_environment: !self_pair && in_first_shell(pair.second),
+                    };
+
+                    // Deal with the possibility that two atoms are at the
+                    // same position. While this is not usual, there is no
+                    // reason to prevent the calculation of spherical
+                    // expansion. The user will still get a warning about
+                    // atoms being very close together when calculating the
+                    // neighbor list.
+                    if pair.distance < 1e-6 {
+                        pair.direction = Vector3D::new(0.0, 0.0, 1.0);
+                    }
+
+                    let samples = this.accumulate_for_pair(
+                        &sender_values,
+                        pool,
+                        pair_index,
+                        samples,
+                        features,
+                        &pair,
+                        cache,
+                    );
+
+                    if let Some(gradient_samples) = gradient_samples {
+                        this.accumulate_gradient_for_pair(
+                            &sender_grad,
+                            pool,
+                            pair_index,
+                            gradient_samples,
+                            features,
+                            &pair,
+                            samples,
+                        );
+                    }
+                };
 
-        let mut pair_contribution = GradientsPairContribution::new(features.count());
-        if let Some(index) = first_grad_i {
-            pair_contribution.add_sample(index, AtomInPair::First, AtomInPair::Second);
+                if parallel_systems {
+                    let work = balanced_work_items(systems);
+                    work.par_iter().with_min_len(1).for_each(|item| {
+                        let system = &systems[item.system];
+                        let first = first_pair_index[item.system];
+                        for &i_pair in &item.pairs {
+                            accumulate(first + i_pair, system, &system.2[i_pair]);
+                        }
+                    });
+                } else {
+                    for (system, &first) in systems.iter().zip(first_pair_index) {
+                        system.2.par_iter().enumerate().for_each(|(i_pair, pair)| {
+                            accumulate(first + i_pair, system, pair);
+                        });
+                    }
+                }
+            });
+        }).expect("one of the thread panicked");
+
+        buffers.contributions = array_pool.into_arrays();
+    }
+
+    /// Compute the spherical expansion with real spherical harmonics (without
+    /// the Condon-Shortley phase), for the samples and features in
+    /// `descriptor`. If `cache` is given, the data needed to compute the
+    /// gradients later is added to it for all pairs.
+    fn compute_real(
+        &mut self,
+        systems: &mut [Box<dyn System>],
+        descriptor: &mut Descriptor,
+        cache: Option<&Mutex<Vec<CachedPair>>>,
+    ) -> Result<(), Error> {
+        if let Some(ref kspace) = self.kspace {
+            for (i_system, system) in systems.iter().enumerate() {
+                crate::calculator::check_cancelled()?;
+                let channels = system.species()?.iter()
+                    .map(|&species| self.channel(species))
+                    .collect::<Vec<_>>();
+                let weights = self.neighbor_weights(i_system, &**system)?;
+                kspace.compute_system(i_system, &**system, &channels, weights, descriptor)?;
+            }
+            return Ok(());
         }
 
-        if let Some(index) = first_self_grad_i {
-            pair_contribution.add_sample(index, AtomInPair::First, AtomInPair::First);
+        self.ensure_spline()?;
+        self.do_self_contributions(systems, descriptor)?;
+
+        match crate::calculator::parallelism() {
+            Parallelism::Samples => {
+                for (i_system, system) in systems.iter_mut().enumerate() {
+                    crate::calculator::check_cancelled()?;
+                    system.compute_neighbors(self.parameters.cutoff).with_context(|| format!(
+                        "failed to compute the neighbors list for structure {}", i_system
+                    ))?;
+                    let species = system.species()?;
+                    let pairs = system.pairs()?;
+                    let weights = self.neighbor_weights(i_system, &**system)?;
+                    let first_shell = self.first_shell_radii(&**system)?;
+
+                    self.accumulate_all_pairs(
+                        descriptor,
+                        &[(i_system, species, pairs, weights, first_shell.as_deref())],
+                        false,
+                        cache,
+                    );
+                }
+            }
+            Parallelism::Systems => {
+                for (i_system, system) in systems.iter_mut().enumerate() {
+                    crate::calculator::check_cancelled()?;
+                    system.compute_neighbors(self.parameters.cutoff).with_context(|| format!(
+                        "failed to compute the neighbors list for structure {}", i_system
+                    ))?;
+                }
+
+                let first_shells = systems.iter()
+                    .map(|system| self.first_shell_radii(&**system))
+                    .collect::<Result<Vec<_>, Error>>()?;
+
+                let mut all_pairs = Vec::with_capacity(systems.len());
+                for (i_system, system) in systems.iter().enumerate() {
+                    let weights = self.neighbor_weights(i_system, &**system)?;
+                    let first_shell = first_shells[i_system].as_deref();
+                    all_pairs.push((i_system, system.species()?, system.pairs()?, weights, first_shell));
+                }
+
+                self.accumulate_all_pairs(descriptor, &all_pairs, true, cache);
+            }
         }
 
-        if let Some(index) = second_grad_i {
-            pair_contribution.add_sample(index, AtomInPair::Second, AtomInPair::First);
+        Ok(())
+    }
+}
+
+/// A unit of work when computing the spherical expansion of multiple systems
+/// in parallel: all the pairs having the same first atom in a given system.
+#[derive(Debug)]
+struct WorkItem {
+    /// index of the system in the list of systems
+    system: usize,
+    /// indexes of the pairs in this work item, in the list of pairs of the
+    /// system
+    pairs: Vec<usize>,
+}
+
+/// Split the pairs in `systems` into one work item for each (system, center)
+/// combination, sorted by decreasing estimated cost.
+///
+/// The cost of a work item is estimated from the number of neighbors of the
+/// center, i.e. the number of pairs in the item. Handling the most expensive
+/// items first and letting rayon's work-stealing distribute the rest keeps
+/// all threads busy when the dataset mixes small molecules with large
+/// periodic systems, instead of waiting for the thread handling the largest
+/// system to finish.
+fn balanced_work_items(systems: &[SystemPairs]) -> Vec<WorkItem> {
+    let mut work = Vec::new();
+    for (i_system, &(_, species, pairs, _, _)) in systems.iter().enumerate() {
+        let mut per_center = vec![Vec::new(); species.len()];
+        for (i_pair, pair) in pairs.iter().enumerate() {
+            per_center[pair.first].push(i_pair);
         }
 
-        if let Some(index) = second_self_grad_i {
-            pair_contribution.add_sample(index, AtomInPair::Second, AtomInPair::Second);
+        work.extend(per_center.into_iter()
+            .filter(|pairs| !pairs.is_empty())
+            .map(|pairs| WorkItem { system: i_system, pairs })
+        );
+    }
+
+    // this is a stable sort, so items with the same cost stay in the order of
+    // the systems and centers
+    work.sort_by_key(|item| std::cmp::Reverse(item.pairs.len()));
+    return work;
+}
+
+/// Receive the contributions of all pairs from `receiver`, and pass them to
+/// `accumulate`. Each contribution comes with the index of the corresponding
+/// pair.
+///
+/// If `deterministic` is `true`, the contributions are accumulated in the
+/// order of the pairs instead of the order in which they are received,
+/// buffering the ones arriving early. This ensures the floating point
+/// operations always happen in the same order.
+fn receive_all<T>(receiver: Receiver<(usize, T)>, deterministic: bool, mut accumulate: impl FnMut(T)) {
+    if !deterministic {
+        for (_, contribution) in receiver {
+            accumulate(contribution);
         }
+        return;
+    }
 
-        let radial_integral = self.radial_integral.get_or(|| {
-            let ri = RadialIntegralImpl::new(&self.parameters).expect("invalid parameters");
-            RefCell::new(ri)
-        }).borrow();
+    let mut next = 0;
+    let mut pending = BTreeMap::new();
+    for (pair_index, contribution) in receiver {
+        if pair_index != next {
+            pending.insert(pair_index, contribution);
+            continue;
+        }
 
-        let spherical_harmonics = self.spherical_harmonics.get_or(|| {
-            RefCell::new(SphericalHarmonicsImpl::new(&self.parameters))
-        }).borrow();
+        accumulate(contribution);
+        next += 1;
+        while let Some(contribution) = pending.remove(&next) {
+            accumulate(contribution);
+            next += 1;
+        }
+    }
 
-        let ri_values = &radial_integral.values;
-        let ri_gradients = radial_integral.gradients.as_ref().expect("missing radial integral gradients");
+    debug_assert!(pending.is_empty(), "missing contributions from some pairs");
+}
 
-        let sph_values = &spherical_harmonics.values;
-        let sph_gradients = spherical_harmonics.gradients.as_ref().expect("missing spherical harmonics gradients");
+/// Get the positions in `samples` of the environments around the first and
+/// second atom of the `pair`, if these environments are part of the samples
+/// and the pair contributes to them.
+fn pair_samples(samples: &Indexes, pair: &Pair) -> (Option<usize>, Option<usize>) {
+    let first_sample_i = if pair.in_first_environment {
+        samples.position(&[
+            IndexValue::from(pair.system),
+            IndexValue::from(pair.first),
+            IndexValue::from(pair.species_first),
+            IndexValue::from(pair.channel_second),
+        ])
+    } else {
+        None
+    };
 
-        let f_scaling = self.scaling_functions(pair.distance);
-        let f_scaling_grad = self.scaling_functions_gradient(pair.distance);
+    let second_sample_i = if pair.first == pair.second || !pair.in_second_environment {
+        // do not compute for the reversed pair if the pair is between an
+        // atom and its image
+        None
+    } else {
+        samples.position(&[
+            IndexValue::from(pair.system),
+            IndexValue::from(pair.second),
+            IndexValue::from(pair.species_second),
+            IndexValue::from(pair.channel_first),
+        ])
+    };
 
-        for spatial in 0..3 {
-            let dr_d_spatial = pair.direction[spatial];
-            let sph_gradient = &sph_gradients[spatial];
-            let gradients = &mut pair_contribution.gradients[spatial];
+    return (first_sample_i, second_sample_i);
+}
+
+/// Compute the contribution of a single `pair` to the spherical expansion
+/// gradients, for the environments at the given positions in the `samples`.
+///
+/// The radial integral (`ri`), spherical harmonics (`sph`) and product of the
+/// scaling functions (`f_scaling`) are given as a tuple of their values and
+/// gradients for this pair. The arrays for the contribution are taken from
+/// `pool`.
+#[allow(clippy::needless_range_loop, clippy::too_many_arguments)]
+fn gradients_pair_contribution(
+    pool: &ArrayPool,
+    gradients_samples: &Indexes,
+    features: &Indexes,
+    pair: &Pair,
+    samples: (Option<usize>, Option<usize>),
+    ri: (&Array2<f64>, &Array2<f64>),
+    sph: (&SphericalHarmonicsArray, &[SphericalHarmonicsArray; 3]),
+    f_scaling: (f64, f64),
+) -> GradientsPairContribution {
+    // get the positions in the gradients array where to store the
+    // contributions to the gradient for this specific pair, if they
+    // exist in the set of requested samples
+
+    // store derivative w.r.t. `second` of the environment around `first`
+    let first_grad_i = samples.0.map(|i_sample| {
+        gradients_samples.position(&[
+            IndexValue::from(i_sample), IndexValue::from(pair.second), IndexValue::from(0)
+        ]).expect("this pair should contribute to this gradient")
+    });
+
+    // store derivative w.r.t. `first` of the environment around `first`
+    let first_self_grad_i = samples.0.map(|i_sample| {
+        gradients_samples.position(&[
+            IndexValue::from(i_sample), IndexValue::from(pair.first), IndexValue::from(0)
+        ]).expect("this pair should contribute to this gradient")
+    });
+
+    // store derivative w.r.t. `first` of the environment around `second`
+    let second_grad_i = samples.1.map(|i_sample| {
+        gradients_samples.position(&[
+            IndexValue::from(i_sample), IndexValue::from(pair.first), IndexValue::from(0)
+        ]).expect("this pair should contribute to this gradient")
+    });
+    // store derivative w.r.t. `second` of the environment around `second`
+    let second_self_grad_i = samples.1.map(|i_sample| {
+        gradients_samples.position(&[
+            IndexValue::from(i_sample), IndexValue::from(pair.second), IndexValue::from(0)
+        ]).expect("this pair should contribute to this gradient")
+    });
+
+    debug_assert!(!(
+        first_grad_i.is_none() &&
+        first_self_grad_i.is_none() &&
+        second_grad_i.is_none() &&
+        second_self_grad_i.is_none()
+    ));
+
+    let mut pair_contribution = GradientsPairContribution::new(features.count(), pool);
+    if let Some(index) = first_grad_i {
+        pair_contribution.add_sample(index, AtomInPair::First, AtomInPair::Second, pair.weight_second);
+    }
+
+    if let Some(index) = first_self_grad_i {
+        pair_contribution.add_sample(index, AtomInPair::First, AtomInPair::First, pair.weight_second);
+    }
+
+    if let Some(index) = second_grad_i {
+        pair_contribution.add_sample(index, AtomInPair::Second, AtomInPair::First, pair.weight_first);
+    }
+
+    if let Some(index) = second_self_grad_i {
+        pair_contribution.add_sample(index, AtomInPair::Second, AtomInPair::Second, pair.weight_first);
+    }
+
+    let (ri_values, ri_gradients) = ri;
+    let (sph_values, sph_gradients) = sph;
+    let (f_scaling, f_scaling_grad) = f_scaling;
+
+    for spatial in 0..3 {
+        let dr_d_spatial = pair.direction[spatial];
+        let sph_gradient = &sph_gradients[spatial];
+        let gradients = &mut pair_contribution.gradients[spatial];
+
+        for (feature_i, feature) in features.iter().enumerate() {
+            let l = feature[0].usize();
+            let m = feature[1].isize();
+            let n = feature[2].usize();
+
+            let sph_value = sph_values[[l as isize, m]];
+            let sph_grad = sph_gradient[[l as isize, m]];
 
-            for (feature_i, feature) in features.iter().enumerate() {
-                let l = feature[0].usize();
-                let m = feature[1].isize();
-                let n = feature[2].usize();
+            let ri_value = ri_values[[n, l]];
+            let ri_grad = ri_gradients[[n, l]];
+
+            gradients[feature_i] = f_scaling_grad * dr_d_spatial * ri_value * sph_value
+                                 + f_scaling * ri_grad * dr_d_spatial * sph_value
+                                 + f_scaling * ri_value * sph_grad / pair.distance;
+        }
+    }
 
-                let sph_value = sph_values[[l as isize, m]];
-                let sph_grad = sph_gradient[[l as isize, m]];
+    return pair_contribution;
+}
 
-                let ri_value = ri_values[[n, l]];
-                let ri_grad = ri_gradients[[n, l]];
+/// Accumulate the gradients `contribution` of a single pair in the
+/// `gradients` array. `m_1_pow_l` contains `(-1)^l` for all the features.
+fn accumulate_gradients_contribution(
+    gradients: &mut ArrayViewMut2<f64>,
+    m_1_pow_l: &Array1<f64>,
+    contribution: &GradientsPairContribution,
+) {
+    use self::AtomInPair::*;
 
-                gradients[feature_i] = f_scaling_grad * dr_d_spatial * ri_value * sph_value
-                                     + f_scaling * ri_grad * dr_d_spatial * sph_value
-                                     + f_scaling * ri_value * sph_grad / pair.distance;
+    for &(index, center, neighbor, weight) in contribution.samples.iter() {
+        for spatial in 0..3 {
+            let gradient = &contribution.gradients[spatial];
+            // we assume that the three spatial
+            // components are stored one after the other
+            let mut row = gradients.index_axis_mut(Axis(0), index + spatial);
+
+            match (center, neighbor) {
+                (First, Second) => {
+                    row.scaled_add(weight, gradient);
+                }
+                (First, First) => {
+                    row.scaled_add(-weight, gradient);
+                }
+                // when storing data for "reversed"
+                // gradients, use the fact that `grad_j
+                // se_i[n, l, m](r) = - (-1)^l grad_i
+                // se_j[n, l, m](r)` where se is the
+                // spherical expansion.
+                (Second, First) => {
+                    scaled_add_reversed(row, -weight, m_1_pow_l, gradient);
+                }
+                (Second, Second) => {
+                    scaled_add_reversed(row, weight, m_1_pow_l, gradient);
+                }
             }
         }
+    }
+}
+
+/// Multiply the columns of `array` corresponding to features with odd `m` by
+/// -1, to include the Condon-Shortley phase in real spherical harmonics.
+fn apply_condon_shortley_phase(features: &Indexes, mut array: ArrayViewMut2<f64>) {
+    for (feature_i, feature) in features.iter().enumerate() {
+        if feature[1].isize() % 2 != 0 {
+            array.column_mut(feature_i).mapv_inplace(|v| -v);
+        }
+    }
+}
+
+/// Data for a single pair kept by `SphericalExpansionGradientsCache`: the
+/// radial integral, spherical harmonics and product of the scaling functions,
+/// together with their gradients
+#[derive(Debug, Clone)]
+struct CachedPair {
+    /// index of the pair used to sort the cached pairs of each system
+    index: usize,
+    pair: Pair,
+    f_scaling: f64,
+    f_scaling_grad: f64,
+    ri_values: Array2<f64>,
+    ri_gradients: Array2<f64>,
+    sph_values: SphericalHarmonicsArray,
+    sph_gradients: [SphericalHarmonicsArray; 3],
+}
+
+/// Intermediate quantities for the gradients of the spherical expansion, kept
+/// for `CalculationOptions::lazy_gradients`. Only the pairs contributing to
+/// at least one sample of the initial calculation are cached.
+#[derive(Debug)]
+struct SphericalExpansionGradientsCache {
+    pairs: Vec<CachedPair>,
+    condon_shortley_phase: bool,
+}
+
+impl GradientsCache for SphericalExpansionGradientsCache {
+    #[time_graph::instrument(name = "SphericalExpansionGradientsCache::compute_gradients")]
+    fn compute_gradients(
+        &self,
+        samples: &Indexes,
+        gradients_samples: &Indexes,
+        features: &Indexes,
+        gradients: ArrayViewMut2<f64>,
+    ) -> Result<(), Error> {
+        let mut gradients = gradients;
+        let deterministic = crate::calculator::deterministic();
+        let (sender, receiver) = crossbeam::channel::unbounded::<(usize, GradientsPairContribution)>();
+
+        let workspace = crate::calculator::workspace();
+        let mut buffers = workspace.take::<Buffers>();
+        let array_pool = ArrayPool::new(std::mem::take(&mut buffers.contributions));
+        let pool = &array_pool;
+
+        crossbeam::thread::scope(|s| {
+            let gradients = &mut gradients;
+            s.spawn(move |_| {
+                let m_1_pow_l = features.iter()
+                    .map(|feature| m_1_pow(feature[0].usize()))
+                    .collect::<Array1<f64>>();
+
+                receive_all(receiver, deterministic, |contribution| {
+                    accumulate_gradients_contribution(gradients, &m_1_pow_l, &contribution);
+                    pool.recycle_gradients(contribution);
+                });
+            });
+
+            crate::calculator::install(move || {
+                self.pairs.par_iter().enumerate().for_each(|(cached_i, cached)| {
+                    let samples = pair_samples(samples, &cached.pair);
+                    let contribution = if samples.0.is_none() && samples.1.is_none() {
+                        GradientsPairContribution::new(0, pool)
+                    } else {
+                        gradients_pair_contribution(
+                            pool,
+                            gradients_samples,
+                            features,
+                            &cached.pair,
+                            samples,
+                            (&cached.ri_values, &cached.ri_gradients),
+                            (&cached.sph_values, &cached.sph_gradients),
+                            (cached.f_scaling, cached.f_scaling_grad),
+                        )
+                    };
+                    sender.send((cached_i, contribution)).expect("receiver hanged up");
+                });
+            });
+        }).expect("one of the thread panicked");
+
+        buffers.contributions = array_pool.into_arrays();
+
+        if self.condon_shortley_phase {
+            apply_condon_shortley_phase(features, gradients);
+        }
 
-        sender.send(pair_contribution).expect("receiver hanged up");
+        return Ok(());
     }
 }
 
@@ -709,11 +1722,23 @@ struct Pair {
     species_first: i32,
     /// species of the second atom of the pair
     species_second: i32,
+    /// neighbor channel of the first atom of the pair
+    channel_first: i32,
+    /// neighbor channel of the second atom of the pair
+    channel_second: i32,
+    /// weight of the first atom of the pair
+    weight_first: f64,
+    /// weight of the second atom of the pair
+    weight_second: f64,
     /// distance between the first and second atom in the pair
     distance: f64,
     /// direction vector (normalized) from the first to the second atom in the
     /// pair
     direction: Vector3D,
+    /// should this pair contribute to the environment of the first atom?
+    in_first_environment: bool,
+    /// should this pair contribute to the environment of the second atom?
+    in_second_environment: bool,
 }
 
 impl CalculatorBase for SphericalExpansion {
@@ -726,17 +1751,32 @@ impl CalculatorBase for SphericalExpansion {
     }
 
     fn features_names(&self) -> Vec<&str> {
-        vec!["l", "m", "n"]
+        match self.parameters.spherical_harmonics {
+            SphericalHarmonicsConvention::Real { .. } => vec!["l", "m", "n"],
+            SphericalHarmonicsConvention::Complex { .. } => vec!["l", "m", "n", "component"],
+        }
     }
 
     fn features(&self) -> Indexes {
         let mut features = IndexesBuilder::new(self.features_names());
         for l in 0..((self.parameters.max_angular + 1) as isize) {
             for m in -l..=l {
-                for n in 0..(self.parameters.max_radial as isize) {
-                    features.add(&[
-                        IndexValue::from(l), IndexValue::from(m), IndexValue::from(n)
-                    ]);
+                for n in 0..(self.max_radial(l as usize) as isize) {
+                    match self.parameters.spherical_harmonics {
+                        SphericalHarmonicsConvention::Real { .. } => {
+                            features.add(&[
+                                IndexValue::from(l), IndexValue::from(m), IndexValue::from(n)
+                            ]);
+                        }
+                        SphericalHarmonicsConvention::Complex { .. } => {
+                            for component in 0..2 {
+                                features.add(&[
+                                    IndexValue::from(l), IndexValue::from(m), IndexValue::from(n),
+                                    IndexValue::from(component),
+                                ]);
+                            }
+                        }
+                    }
                 }
             }
         }
@@ -744,13 +1784,30 @@ impl CalculatorBase for SphericalExpansion {
     }
 
     fn samples_builder(&self) -> Box<dyn SamplesBuilder> {
-        Box::new(TwoBodiesSpeciesSamples::with_self_contribution(self.parameters.cutoff))
+        if self.kspace.is_some() {
+            return Box::new(KSpaceSamples {
+                channels: self.parameters.neighbor_channels.clone(),
+            });
+        }
+
+        Box::new(
+            TwoBodiesSpeciesSamples::with_self_contribution(self.parameters.cutoff)
+                .with_neighbor_channels(self.parameters.neighbor_channels.clone())
+        )
     }
 
     fn compute_gradients(&self) -> bool {
         self.parameters.gradients
     }
 
+    fn supports_gradients(&self) -> bool {
+        true
+    }
+
+    fn hyper_parameters(&self) -> Vec<String> {
+        hyper_parameters(&self.parameters.cutoff_function, &self.parameters.radial_scaling)
+    }
+
     fn check_features(&self, indexes: &Indexes) -> Result<(), Error> {
         assert_eq!(indexes.names(), self.features_names());
         for value in indexes {
@@ -771,10 +1828,16 @@ impl CalculatorBase for SphericalExpansion {
                 )))
             }
 
-            if n >= self.parameters.max_radial {
+            if n >= self.max_radial(l as usize) {
                 return Err(Error::InvalidParameter(format!(
                     "'n' is too large for this SphericalExpansion: \
-                    expected value below {}, got {}", self.parameters.max_radial, n
+                    expected value below {}, got {}", self.max_radial(l as usize), n
+                )))
+            }
+
+            if value.len() == 4 && value[3].usize() > 1 {
+                return Err(Error::InvalidParameter(format!(
+                    "'component' must be 0 or 1, got {}", value[3].usize()
                 )))
             }
         }
@@ -782,177 +1845,193 @@ impl CalculatorBase for SphericalExpansion {
         Ok(())
     }
 
-    #[time_graph::instrument(name = "SphericalExpansion::compute")]
-    #[allow(clippy::enum_glob_use)]
-    fn compute(&mut self, systems: &mut [Box<dyn System>], descriptor: &mut Descriptor) -> Result<(), Error> {
-        assert_eq!(descriptor.samples.names(), &["structure", "center", "species_center", "species_neighbor"]);
+    fn precompute(&mut self) -> Result<(), Error> {
+        self.ensure_spline()
+    }
+
+    fn save_state(&self) -> Result<Option<serde_json::Value>, Error> {
+        let spline = match self.spline {
+            Some(ref spline) => Arc::clone(spline),
+            None => match self.parameters.radial_basis.spline(&self.parameters)? {
+                Some(spline) => spline,
+                None => return Ok(None),
+            }
+        };
+
+        return Ok(Some(serde_json::json!({
+            "atomic_gaussian_width": self.parameters.atomic_gaussian_width,
+            "spline": serde_json::to_value(&*spline)?,
+        })));
+    }
+
+    fn load_state(&mut self, state: serde_json::Value) -> Result<(), Error> {
+        if let RadialBasis::Gto {} = self.parameters.radial_basis {
+            return Err(Error::InvalidParameter(
+                "can not load a splined radial integral for a spherical expansion using the Gto radial basis".into()
+            ));
+        }
+
+        let saved = serde_json::from_value::<SavedSpline>(state)?;
+        if saved.atomic_gaussian_width.to_bits() != self.parameters.atomic_gaussian_width.to_bits() {
+            return Err(Error::InvalidParameter(format!(
+                "the saved spline was created with atomic_gaussian_width={}, but this spherical expansion uses {}",
+                saved.atomic_gaussian_width, self.parameters.atomic_gaussian_width
+            )));
+        }
+
+        let spline = saved.spline;
+        spline.check()?;
+        if spline.parameters() != RadialBasis::spline_parameters(&self.parameters) {
+            return Err(Error::InvalidParameter(format!(
+                "the saved spline parameters ({:?}) do not match the parameters of this spherical expansion",
+                spline.parameters()
+            )));
+        }
+
+        self.spline = Some(Arc::new(spline));
+        // make sure all threads use the new spline
+        self.radial_integral = ThreadLocal::new();
+
+        return Ok(());
+    }
+
+    fn compute_caching_gradients(
+        &mut self,
+        systems: &mut [Box<dyn System>],
+        descriptor: &mut Descriptor,
+    ) -> Result<Option<Box<dyn GradientsCache>>, Error> {
+        let condon_shortley_phase = match self.parameters.spherical_harmonics {
+            SphericalHarmonicsConvention::Real { condon_shortley_phase } => condon_shortley_phase,
+            // the complex harmonics are computed from a separate descriptor
+            // containing the real ones, and are not cached for now
+            SphericalHarmonicsConvention::Complex { .. } => return Ok(None),
+        };
+
+        if self.kspace.is_some() || !self.parameters.gradients {
+            return Ok(None);
+        }
+
         assert_eq!(descriptor.features.names(), &["l", "m", "n"]);
+        debug_assert!(descriptor.gradients.is_none());
 
-        self.do_self_contributions(descriptor);
-
-        for (i_system, system) in systems.iter_mut().enumerate() {
-            system.compute_neighbors(self.parameters.cutoff)?;
-            let species = system.species()?;
-
-            let pairs = system.pairs()?;
-
-            // Setup parallel computation.
-            //
-            // This code distribute work for computing the spherical expansion
-            // over multiple threads, iterating over pairs in parallel.
-            //
-            // To workaround the fact that each working thread would like to
-            // write data to potentially the same location as other threads
-            // (since two different pairs can produce data to be stored in the
-            // same environment), we use channels to send data two other threads
-            // (one for values, one for gradients) that only receive data and
-            // write it to the descriptor.
-            let samples = &descriptor.samples;
-            let gradient_samples = descriptor.gradients_samples.as_ref();
-            let features = &descriptor.features;
-            let values = &mut descriptor.values;
-            let gradients = descriptor.gradients.as_mut();
-
-            // use crossbeam channels instead of std::sync::mpsc::SyncChannel
-            // since crossbeam is faster in our case.
-            let (sender_values, receiver_values) = crossbeam::channel::unbounded::<PairContribution>();
-            let (sender_grad, receiver_grad) = crossbeam::channel::unbounded::<GradientsPairContribution>();
-
-            // use crossbeam scoped threads instead of rayon's, to ensure we
-            // make progress even with RAYON_NUM_THREADS=1
-            crossbeam::thread::scope(|s|{
-                // re-borrow self as an immutable reference to be passed to
-                // the first closure below
-                let this = &*self;
-
-                // Start a thread to produce values. This thread will start
-                // RAYON_NUM_THREADS more threads, actually doing the work.
-                s.spawn(move |_| {
-                    pairs.par_iter()
-                        .map(|pair| (pair, sender_values.clone(), sender_grad.clone()))
-                        .for_each(|(pair, sender_values, sender_grad)| {
-                            let mut pair = Pair {
-                                system: i_system,
-                                first: pair.first,
-                                second: pair.second,
-                                species_first: species[pair.first],
-                                species_second: species[pair.second],
-                                distance: pair.distance,
-                                direction: pair.vector / pair.distance,
-                            };
-
-                            // Deal with the possibility that two atoms are at
-                            // the same position. While this is not usual, there
-                            // is no reason to prevent the calculation of
-                            // spherical expansion. The user will still get a
-                            // warning about atoms being very close together
-                            // when calculating the neighbor list.
-                            if pair.distance < 1e-6 {
-                                pair.direction = Vector3D::new(0.0, 0.0, 1.0);
-                            }
+        let cache = Mutex::new(Vec::new());
+        self.compute_real(systems, descriptor, Some(&cache))?;
+        if condon_shortley_phase {
+            apply_condon_shortley_phase(&descriptor.features, descriptor.values.view_mut());
+        }
 
-                            let samples = this.accumulate_for_pair(
-                                &sender_values,
-                                samples,
-                                features,
-                                &pair
-                            );
-
-                            if this.parameters.gradients {
-                                this.accumulate_gradient_for_pair(
-                                    &sender_grad,
-                                    gradient_samples.expect("missing gradient samples"),
-                                    features,
-                                    &pair,
-                                    samples,
-                                );
-                            }
-                        });
-                });
+        let mut pairs = cache.into_inner().expect("poisoned mutex");
+        pairs.sort_by_key(|cached| (cached.pair.system, cached.index));
 
-                // Start a thread to receive and collect values
-                s.spawn(move |_| {
-                    let m_1_pow_l = features.iter()
-                        .map(|feature| m_1_pow(feature[0].usize()))
-                        .collect::<Array1<f64>>();
+        return Ok(Some(Box::new(SphericalExpansionGradientsCache {
+            pairs: pairs,
+            condon_shortley_phase: condon_shortley_phase,
+        })));
+    }
 
-                    for contribution in receiver_values {
-                        for &(index, center) in contribution.samples.iter() {
-                            let mut row = values.index_axis_mut(Axis(0), index);
-                            match center {
-                                AtomInPair::First => {
-                                    row += &contribution.values;
-                                }
-                                AtomInPair::Second => {
-                                    // Use the fact that `se[n, l, m](-r) =
-                                    // (-1)^l se[n, l, m](r)` where se is the
-                                    // spherical expansion
-                                    row += &(m_1_pow_l.clone() * &contribution.values);
-                                }
-                            }
-                        }
+    #[time_graph::instrument(name = "SphericalExpansion::compute")]
+    fn compute(&mut self, systems: &mut [Box<dyn System>], descriptor: &mut Descriptor) -> Result<(), Error> {
+        assert_eq!(descriptor.samples.names(), &["structure", "center", "species_center", "species_neighbor"]);
 
+        match self.parameters.spherical_harmonics {
+            SphericalHarmonicsConvention::Real { condon_shortley_phase } => {
+                assert_eq!(descriptor.features.names(), &["l", "m", "n"]);
+                self.compute_real(systems, descriptor, None)?;
+
+                if condon_shortley_phase {
+                    apply_condon_shortley_phase(&descriptor.features, descriptor.values.view_mut());
+                    if let Some(ref mut gradients) = descriptor.gradients {
+                        apply_condon_shortley_phase(&descriptor.features, gradients.view_mut());
                     }
-                });
+                }
+            }
+            SphericalHarmonicsConvention::Complex { condon_shortley_phase } => {
+                assert_eq!(descriptor.features.names(), &["l", "m", "n", "component"]);
+
+                // compute the coefficients for all the real harmonics with
+                // the same `|m|` as the requested complex ones, and combine
+                // them to get the complex coefficients
+                let mut real_features = BTreeSet::new();
+                for feature in descriptor.features.iter() {
+                    let l = feature[0].isize();
+                    let m = feature[1].isize().abs();
+                    let n = feature[2].isize();
+                    real_features.insert((l, m, n));
+                    real_features.insert((l, -m, n));
+                }
 
-                // Start a thread to receive and collect gradients
-                if self.parameters.gradients {
-                    let gradients = gradients.expect("missing storage for gradients");
-                    s.spawn(move |_| {
-                        use self::AtomInPair::*;
-
-                        let m_1_pow_l = features.iter()
-                            .map(|feature| m_1_pow(feature[0].usize()))
-                            .collect::<Array1<f64>>();
-
-                        for contribution in receiver_grad {
-                            for &(index, center, neighbor) in contribution.samples.iter() {
-                                for spatial in 0..3 {
-                                    let gradient = &contribution.gradients[spatial];
-                                    // we assume that the three spatial
-                                    // components are stored one after the other
-                                    let mut row = gradients.index_axis_mut(Axis(0), index + spatial);
-
-                                    match (center, neighbor) {
-                                        (First, Second) => {
-                                            row += gradient;
-                                        }
-                                        (First, First) => {
-                                            row -= gradient;
-                                        }
-                                        // when storing data for "reversed"
-                                        // gradients, use the fact that `grad_j
-                                        // se_i[n, l, m](r) = - (-1)^l grad_i
-                                        // se_j[n, l, m](r)` where se is the
-                                        // spherical expansion.
-                                        (Second, First) => {
-                                            row -= &(m_1_pow_l.clone() * gradient);
-                                        }
-                                        (Second, Second) => {
-                                            row += &(m_1_pow_l.clone() * gradient);
-                                        }
-                                    }
-                                }
-                            }
+                let mut builder = IndexesBuilder::new(vec!["l", "m", "n"]);
+                for &(l, m, n) in &real_features {
+                    builder.add(&[IndexValue::from(l), IndexValue::from(m), IndexValue::from(n)]);
+                }
+                let real_features = builder.finish();
+
+                let mut real = Descriptor::new();
+                if let Some(ref gradients_samples) = descriptor.gradients_samples {
+                    real.prepare_gradients(descriptor.samples.clone(), gradients_samples.clone(), real_features);
+                } else {
+                    real.prepare(descriptor.samples.clone(), real_features);
+                }
+                self.compute_real(systems, &mut real, None)?;
+
+                for (feature_i, feature) in descriptor.features.iter().enumerate() {
+                    let l = feature[0];
+                    let m = feature[1].isize();
+                    let n = feature[2];
+
+                    let position = |m: isize| {
+                        real.features.position(&[l, IndexValue::from(m), n]).expect("missing real feature")
+                    };
+                    let positive = position(m.abs());
+                    let negative = position(-m.abs());
+
+                    // coefficients of the real harmonics with positive and
+                    // negative `m` in this component of the complex harmonics
+                    let (positive_factor, negative_factor) = if m == 0 {
+                        if feature[3].usize() == 0 { (1.0, 0.0) } else { (0.0, 0.0) }
+                    } else {
+                        let phase = if m > 0 && condon_shortley_phase && m % 2 != 0 { -1.0 } else { 1.0 };
+                        let sign = if m > 0 { -1.0 } else { 1.0 };
+                        if feature[3].usize() == 0 {
+                            (phase * FRAC_1_SQRT_2, 0.0)
+                        } else {
+                            (0.0, sign * phase * FRAC_1_SQRT_2)
                         }
-                    });
+                    };
+
+                    let mut column = descriptor.values.column_mut(feature_i);
+                    column.assign(&real.values.column(positive));
+                    column *= positive_factor;
+                    column.scaled_add(negative_factor, &real.values.column(negative));
+
+                    if let Some(ref mut gradients) = descriptor.gradients {
+                        let real_gradients = real.gradients.as_ref().expect("missing real gradients");
+                        let mut column = gradients.column_mut(feature_i);
+                        column.assign(&real_gradients.column(positive));
+                        column *= positive_factor;
+                        column.scaled_add(negative_factor, &real_gradients.column(negative));
+                    }
                 }
-            }).expect("one of the thread panicked");
+            }
         }
 
-        Ok(())
+        return Ok(());
     }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::systems::test_utils::{test_systems, test_system};
+    use crate::systems::{SimpleSystem, UnitCell};
     use crate::descriptor::{IndexValue, IndexesBuilder};
-    use crate::{Descriptor, Calculator};
+    use crate::{Descriptor, Calculator, System, Vector3D};
 
     use super::{SphericalExpansion, SphericalExpansionParameters};
-    use super::{CutoffFunction, RadialBasis, RadialScaling};
+    use super::{CutoffFunction, RadialBasis, RadialScaling, SphericalHarmonicsConvention};
+    use super::{SplineCache, SplineCacheKey, GtoRadialIntegral, SplinedRadialIntegral};
     use crate::calculators::CalculatorBase;
+    use std::collections::BTreeMap;
+    use std::sync::Arc;
 
     // small helper function to create IndexValue
     fn v(i: i32) -> IndexValue { IndexValue::from(i) }
@@ -965,8 +2044,16 @@ mod tests {
             gradients: gradients,
             max_radial: 6,
             max_angular: 6,
+            max_radial_per_angular: None,
             radial_basis: RadialBasis::Gto {},
             radial_scaling: RadialScaling::Willatt2018 { scale: 1.5, rate: 0.8, exponent: 2},
+            center_atom_weight: 1.0,
+            neighbor_weights: None,
+            neighbor_channels: BTreeMap::new(),
+            exclude_self_pairs: false,
+            first_shell_tolerance: None,
+            spherical_harmonics: SphericalHarmonicsConvention::default(),
+            kspace: None,
         }
     }
 
@@ -998,6 +2085,43 @@ mod tests {
         // `rascaline/tests/spherical-expansion.rs`
     }
 
+    #[test]
+    fn balanced_work_items() {
+        let mut systems = test_systems(&["water", "methane"]);
+        for system in &mut systems {
+            system.compute_neighbors(3.5).unwrap();
+        }
+
+        let all_pairs = systems.iter().enumerate().map(|(i_system, system)| {
+            (i_system, system.species().unwrap(), system.pairs().unwrap(), None, None)
+        }).collect::<Vec<_>>();
+
+        let work = super::balanced_work_items(&all_pairs);
+
+        // all pairs are in exactly one work item
+        let mut n_items = 0;
+        for (i_system, system) in all_pairs.iter().enumerate() {
+            let mut pairs = work.iter()
+                .filter(|item| item.system == i_system)
+                .flat_map(|item| item.pairs.iter().copied())
+                .collect::<Vec<_>>();
+            pairs.sort_unstable();
+            assert_eq!(pairs, (0..system.2.len()).collect::<Vec<_>>());
+
+            for item in work.iter().filter(|item| item.system == i_system) {
+                let first = system.2[item.pairs[0]].first;
+                assert!(item.pairs.iter().all(|&i| system.2[i].first == first));
+                n_items += 1;
+            }
+        }
+        assert_eq!(n_items, work.len());
+
+        // the most expensive work items come first
+        for window in work.windows(2) {
+            assert!(window[0].pairs.len() >= window[1].pairs.len());
+        }
+    }
+
     #[test]
     fn finite_differences() {
         let calculator = Calculator::from(Box::new(SphericalExpansion::new(
@@ -1035,6 +2159,424 @@ mod tests {
         );
     }
 
+    #[test]
+    fn spline_cache() {
+        let mut parameters = parameters(false);
+        parameters.radial_basis = RadialBasis::SplinedGto { accuracy: 3.1e-7 };
+
+        let mut first = SphericalExpansion::new(parameters.clone()).unwrap();
+        let mut second = SphericalExpansion::new(parameters.clone()).unwrap();
+        first.ensure_spline().unwrap();
+        second.ensure_spline().unwrap();
+        assert!(Arc::ptr_eq(first.spline.as_ref().unwrap(), second.spline.as_ref().unwrap()));
+
+        // different parameters use a different spline
+        parameters.cutoff = 4.0;
+        let mut third = SphericalExpansion::new(parameters).unwrap();
+        third.ensure_spline().unwrap();
+        assert!(!Arc::ptr_eq(first.spline.as_ref().unwrap(), third.spline.as_ref().unwrap()));
+    }
+
+    #[test]
+    fn spline_cache_eviction() {
+        let mut parameters = parameters(false);
+        let accuracy = 1e-5;
+        let create_spline = |parameters: &SphericalExpansionParameters| {
+            let gto = GtoRadialIntegral::new(RadialBasis::gto_parameters(parameters)).unwrap();
+            Arc::new(SplinedRadialIntegral::with_accuracy(
+                RadialBasis::spline_parameters(parameters), accuracy, gto
+            ).unwrap())
+        };
+
+        let mut cache = SplineCache::new(2);
+        let first_key = SplineCacheKey::new(&parameters, accuracy);
+        let first = cache.insert(first_key.clone(), create_spline(&parameters));
+
+        // splines stay in the cache after all their users are dropped
+        let weak = Arc::downgrade(&first);
+        std::mem::drop(first);
+        assert!(weak.upgrade().is_some());
+        let first = cache.get(&first_key).unwrap();
+
+        // inserting an existing key gives the cached spline
+        assert!(Arc::ptr_eq(&cache.insert(first_key.clone(), create_spline(&parameters)), &first));
+
+        parameters.cutoff = 4.0;
+        let second_key = SplineCacheKey::new(&parameters, accuracy);
+        cache.insert(second_key.clone(), create_spline(&parameters));
+
+        // using the first spline makes the second one the least recently used
+        assert!(cache.get(&first_key).is_some());
+        parameters.cutoff = 4.5;
+        let third_key = SplineCacheKey::new(&parameters, accuracy);
+        cache.insert(third_key.clone(), create_spline(&parameters));
+        assert!(cache.get(&first_key).is_some());
+        assert!(cache.get(&second_key).is_none());
+        assert!(cache.get(&third_key).is_some());
+
+        cache.clear();
+        assert!(cache.get(&first_key).is_none());
+        std::mem::drop(first);
+        assert!(weak.upgrade().is_none());
+    }
+
+    #[test]
+    fn center_atom_weight() {
+        let compute = |center_atom_weight| {
+            let mut parameters = parameters(true);
+            parameters.center_atom_weight = center_atom_weight;
+            let mut calculator = Calculator::from(Box::new(
+                SphericalExpansion::new(parameters).unwrap()
+            ) as Box<dyn CalculatorBase>);
+
+            let mut systems = test_systems(&["water"]);
+            let mut descriptor = Descriptor::new();
+            calculator.compute(&mut systems, &mut descriptor, Default::default()).unwrap();
+            return descriptor;
+        };
+
+        let full = compute(1.0);
+        let without = compute(0.0);
+        let scaled = compute(-0.5);
+        assert_eq!(full.samples, without.samples);
+        assert_eq!(full.samples, scaled.samples);
+
+        // only the samples with the center species as neighbor change, and
+        // the values are linear in the weight
+        for (sample_i, sample) in full.samples.iter().enumerate() {
+            let self_contribution = &full.values.row(sample_i) - &without.values.row(sample_i);
+            if sample[2] == sample[3] {
+                assert!(self_contribution.iter().any(|&v| v != 0.0));
+            } else {
+                assert!(self_contribution.iter().all(|&v| v == 0.0));
+            }
+
+            let expected = &without.values.row(sample_i) - &(0.5 * &self_contribution);
+            approx::assert_relative_eq!(scaled.values.row(sample_i), expected, epsilon = 1e-12);
+        }
+
+        // the central atom does not contribute to the gradients
+        assert_eq!(full.gradients, without.gradients);
+        assert_eq!(full.gradients, scaled.gradients);
+
+        let mut parameters = parameters(false);
+        parameters.center_atom_weight = f64::NAN;
+        let error = SphericalExpansion::new(parameters).unwrap_err();
+        assert_eq!(error.to_string(), "invalid parameter: expected a finite value for center_atom_weight, got NaN");
+    }
+
+    #[test]
+    fn neighbor_weights() {
+        let mut calculator = Calculator::from(Box::new(SphericalExpansion::new(
+            parameters(true)
+        ).unwrap()) as Box<dyn CalculatorBase>);
+
+        let mut systems = test_systems(&["water"]);
+        let mut reference = Descriptor::new();
+        calculator.compute(&mut systems, &mut reference, Default::default()).unwrap();
+
+        let mut weighted = parameters(true);
+        weighted.neighbor_weights = Some("occupancy".into());
+        let mut calculator = Calculator::from(Box::new(
+            SphericalExpansion::new(weighted).unwrap()
+        ) as Box<dyn CalculatorBase>);
+
+        // the water system contains O, H, H
+        let mut system = test_system("water");
+        system.set_data("occupancy", vec![2.0, 0.5, 0.5]).unwrap();
+
+        let mut descriptor = Descriptor::new();
+        calculator.compute(&mut [Box::new(system)], &mut descriptor, Default::default()).unwrap();
+        assert_eq!(descriptor.samples, reference.samples);
+        assert_eq!(descriptor.gradients_samples, reference.gradients_samples);
+
+        // all atoms of the same species have the same weight, so the
+        // contributions for each neighbor species are scaled by this weight
+        let weight = |species: IndexValue| if species.i32() == 1 { 0.5 } else { 2.0 };
+        for (sample_i, sample) in descriptor.samples.iter().enumerate() {
+            let expected = weight(sample[3]) * &reference.values.row(sample_i);
+            approx::assert_relative_eq!(descriptor.values.row(sample_i), expected, max_relative = 1e-12);
+        }
+
+        let gradients = descriptor.gradients.as_ref().unwrap();
+        let reference_gradients = reference.gradients.as_ref().unwrap();
+        for (gradient_i, gradient_sample) in descriptor.gradients_samples.as_ref().unwrap().iter().enumerate() {
+            let sample = &descriptor.samples[gradient_sample[0].usize()];
+            let expected = weight(sample[3]) * &reference_gradients.row(gradient_i);
+            approx::assert_relative_eq!(gradients.row(gradient_i), expected, max_relative = 1e-12);
+        }
+
+        // missing data
+        let mut systems = test_systems(&["water"]);
+        let mut descriptor = Descriptor::new();
+        let error = calculator.compute(&mut systems, &mut descriptor, Default::default()).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "invalid parameter: missing per-atom data 'occupancy' for the neighbor weights in structure 0"
+        );
+    }
+
+    #[test]
+    fn neighbor_weights_finite_differences() {
+        let mut weighted = parameters(true);
+        weighted.neighbor_weights = Some("charge".into());
+        let calculator = Calculator::from(Box::new(
+            SphericalExpansion::new(weighted).unwrap()
+        ) as Box<dyn CalculatorBase>);
+
+        let mut system = test_system("water");
+        system.set_data("charge", vec![-0.8, 0.3, 0.5]).unwrap();
+        crate::calculators::tests_utils::finite_difference(calculator, system);
+    }
+
+    #[test]
+    fn neighbor_channels() {
+        let mut calculator = Calculator::from(Box::new(SphericalExpansion::new(
+            parameters(false)
+        ).unwrap()) as Box<dyn CalculatorBase>);
+
+        let mut systems = test_systems(&["water", "methane"]);
+        let mut reference = Descriptor::new();
+        calculator.compute(&mut systems, &mut reference, Default::default()).unwrap();
+
+        let mut grouped = parameters(false);
+        grouped.neighbor_channels = [(1, -1), (6, -1), (123456, -1)].iter().copied().collect();
+        let mut calculator = Calculator::from(Box::new(
+            SphericalExpansion::new(grouped).unwrap()
+        ) as Box<dyn CalculatorBase>);
+
+        let mut descriptor = Descriptor::new();
+        calculator.compute(&mut systems, &mut descriptor, Default::default()).unwrap();
+        assert_eq!(descriptor.samples.count(), 8);
+
+        // all species are in the same channel, so the expansion is the sum of
+        // the expansions for all neighbor species
+        for (sample_i, sample) in descriptor.samples.iter().enumerate() {
+            assert_eq!(sample[3], v(-1));
+
+            let mut expected = ndarray::Array1::zeros(descriptor.features.count());
+            for (reference_i, reference_sample) in reference.samples.iter().enumerate() {
+                if reference_sample[..3] == sample[..3] {
+                    expected += &reference.values.row(reference_i);
+                }
+            }
+            approx::assert_relative_eq!(descriptor.values.row(sample_i), expected, max_relative = 1e-12);
+        }
+    }
+
+    #[test]
+    fn neighbor_channels_finite_differences() {
+        let mut grouped = parameters(true);
+        grouped.neighbor_channels = [(123456, 1)].iter().copied().collect();
+        let calculator = Calculator::from(Box::new(
+            SphericalExpansion::new(grouped).unwrap()
+        ) as Box<dyn CalculatorBase>);
+
+        let system = test_system("water");
+        crate::calculators::tests_utils::finite_difference(calculator, system);
+    }
+
+    #[test]
+    fn exclude_self_pairs() {
+        let compute = |parameters: SphericalExpansionParameters, system: SimpleSystem| {
+            let mut calculator = Calculator::from(Box::new(
+                SphericalExpansion::new(parameters).unwrap()
+            ) as Box<dyn CalculatorBase>);
+
+            let mut systems = vec![Box::new(system) as Box<dyn System>];
+            let mut descriptor = Descriptor::new();
+            calculator.compute(&mut systems, &mut descriptor, Default::default()).unwrap();
+            return descriptor;
+        };
+
+        let mut without_center = parameters(true);
+        without_center.center_atom_weight = 0.0;
+        let mut excluded = parameters(true);
+        excluded.exclude_self_pairs = true;
+
+        // without periodic images of the atoms inside the cutoff, this is the
+        // same as removing the central atom contribution
+        let reference = compute(without_center.clone(), test_system("water"));
+        let descriptor = compute(excluded.clone(), test_system("water"));
+        assert_eq!(descriptor.samples, reference.samples);
+        assert_eq!(descriptor.values, reference.values);
+        assert_eq!(descriptor.gradients, reference.gradients);
+
+        // the periodic images of a single atom are removed from its
+        // environment
+        let mut system = SimpleSystem::new(UnitCell::cubic(3.0));
+        system.add_atom(1, Vector3D::new(0.0, 0.0, 0.0));
+
+        let reference = compute(without_center, system.clone());
+        assert!(reference.values.iter().any(|&v| v != 0.0));
+
+        let descriptor = compute(excluded, system);
+        assert!(descriptor.values.iter().all(|&v| v == 0.0));
+    }
+
+    #[test]
+    fn first_shell() {
+        let compute = |first_shell_tolerance| {
+            let mut parameters = parameters(true);
+            parameters.center_atom_weight = 0.0;
+            parameters.first_shell_tolerance = first_shell_tolerance;
+            let mut calculator = Calculator::from(Box::new(
+                SphericalExpansion::new(parameters).unwrap()
+            ) as Box<dyn CalculatorBase>);
+
+            let mut systems = test_systems(&["water"]);
+            let mut descriptor = Descriptor::new();
+            calculator.compute(&mut systems, &mut descriptor, Default::default()).unwrap();
+            return descriptor;
+        };
+
+        let full = compute(None);
+        let first_shell = compute(Some(0.1));
+        assert_eq!(full.samples, first_shell.samples);
+
+        // the first shell of the oxygen contains both hydrogen, and the first
+        // shell of the hydrogen atoms only contains the oxygen
+        for (sample_i, sample) in full.samples.iter().enumerate() {
+            if sample[2] == v(1) && sample[3] == v(1) {
+                assert!(full.values.row(sample_i).iter().any(|&v| v != 0.0));
+                assert!(first_shell.values.row(sample_i).iter().all(|&v| v == 0.0));
+            } else {
+                assert_eq!(full.values.row(sample_i), first_shell.values.row(sample_i));
+            }
+        }
+
+        let mut parameters = parameters(true);
+        parameters.first_shell_tolerance = Some(0.1);
+        let calculator = Calculator::from(Box::new(
+            SphericalExpansion::new(parameters.clone()).unwrap()
+        ) as Box<dyn CalculatorBase>);
+        crate::calculators::tests_utils::finite_difference(calculator, test_system("water"));
+
+        parameters.first_shell_tolerance = Some(-0.1);
+        let error = SphericalExpansion::new(parameters).unwrap_err();
+        assert_eq!(error.to_string(), "invalid parameter: first_shell_tolerance must be a positive number, got -0.1");
+    }
+
+    #[test]
+    fn spherical_harmonics_conventions() {
+        let compute = |convention| {
+            let mut parameters = parameters(true);
+            parameters.spherical_harmonics = convention;
+            let mut calculator = Calculator::from(Box::new(
+                SphericalExpansion::new(parameters).unwrap()
+            ) as Box<dyn CalculatorBase>);
+
+            let mut systems = test_systems(&["water"]);
+            let mut descriptor = Descriptor::new();
+            calculator.compute(&mut systems, &mut descriptor, Default::default()).unwrap();
+            return descriptor;
+        };
+
+        let real = compute(SphericalHarmonicsConvention::Real { condon_shortley_phase: false });
+        let real_cs = compute(SphericalHarmonicsConvention::Real { condon_shortley_phase: true });
+        assert_eq!(real.features, real_cs.features);
+
+        // the Condon-Shortley phase changes the sign of odd m
+        let real_gradients = real.gradients.as_ref().unwrap();
+        let real_cs_gradients = real_cs.gradients.as_ref().unwrap();
+        for (feature_i, feature) in real.features.iter().enumerate() {
+            let sign = if feature[1].isize() % 2 == 0 { 1.0 } else { -1.0 };
+            assert_eq!(real_cs.values.column(feature_i), sign * &real.values.column(feature_i));
+            assert_eq!(real_cs_gradients.column(feature_i), sign * &real_gradients.column(feature_i));
+        }
+
+        for &condon_shortley_phase in &[false, true] {
+            let complex = compute(SphericalHarmonicsConvention::Complex { condon_shortley_phase });
+            assert_eq!(complex.features.names(), &["l", "m", "n", "component"]);
+            assert_eq!(complex.features.count(), 2 * real.features.count());
+
+            let value = |descriptor: &Descriptor, sample_i: usize, feature: &[IndexValue]| {
+                let feature_i = descriptor.features.position(feature).unwrap();
+                return descriptor.values[[sample_i, feature_i]];
+            };
+
+            for sample_i in 0..real.samples.count() {
+                for l in 0..=6 {
+                    for n in 0..6 {
+                        let mut real_norm = 0.0;
+                        let mut complex_norm = 0.0;
+                        for m in -l..=l {
+                            let real_value = value(&real, sample_i, &[v(l), v(m), v(n)]);
+                            real_norm += real_value * real_value;
+
+                            let re = value(&complex, sample_i, &[v(l), v(m), v(n), v(0)]);
+                            let im = value(&complex, sample_i, &[v(l), v(m), v(n), v(1)]);
+                            complex_norm += re * re + im * im;
+
+                            // relation between positive and negative m
+                            let re_minus = value(&complex, sample_i, &[v(l), v(-m), v(n), v(0)]);
+                            let im_minus = value(&complex, sample_i, &[v(l), v(-m), v(n), v(1)]);
+                            let phase = if condon_shortley_phase && m % 2 != 0 { -1.0 } else { 1.0 };
+                            approx::assert_relative_eq!(re_minus, phase * re, epsilon = 1e-15);
+                            approx::assert_relative_eq!(im_minus, -phase * im, epsilon = 1e-15);
+                        }
+
+                        approx::assert_relative_eq!(real_norm, complex_norm, epsilon = 1e-15, max_relative = 1e-12);
+                    }
+                }
+            }
+        }
+
+        let mut parameters = parameters(true);
+        parameters.spherical_harmonics = SphericalHarmonicsConvention::Complex { condon_shortley_phase: true };
+        let calculator = Calculator::from(Box::new(
+            SphericalExpansion::new(parameters).unwrap()
+        ) as Box<dyn CalculatorBase>);
+        crate::calculators::tests_utils::finite_difference(calculator, test_system("water"));
+    }
+
+    #[test]
+    fn max_radial_per_angular() {
+        let mut calculator = Calculator::from(Box::new(SphericalExpansion::new(
+            parameters(true)
+        ).unwrap()) as Box<dyn CalculatorBase>);
+
+        let mut systems = test_systems(&["water", "methane"]);
+        let mut reference = Descriptor::new();
+        calculator.compute(&mut systems, &mut reference, Default::default()).unwrap();
+
+        let mut trimmed = parameters(true);
+        trimmed.max_radial_per_angular = Some(vec![6, 5, 4, 3, 2, 1, 1]);
+        let mut calculator = Calculator::from(Box::new(
+            SphericalExpansion::new(trimmed).unwrap()
+        ) as Box<dyn CalculatorBase>);
+
+        let mut descriptor = Descriptor::new();
+        calculator.compute(&mut systems, &mut descriptor, Default::default()).unwrap();
+        assert_eq!(descriptor.samples, reference.samples);
+        assert_eq!(descriptor.features.count(), 6 + 3 * 5 + 5 * 4 + 7 * 3 + 9 * 2 + 11 + 13);
+
+        // the features are a subset of the full expansion
+        let gradients = descriptor.gradients.as_ref().unwrap();
+        let reference_gradients = reference.gradients.as_ref().unwrap();
+        for (feature_i, feature) in descriptor.features.iter().enumerate() {
+            assert!(feature[2].usize() < [6, 5, 4, 3, 2, 1, 1][feature[0].usize()]);
+
+            let reference_i = reference.features.position(feature).unwrap();
+            assert_eq!(descriptor.values.column(feature_i), reference.values.column(reference_i));
+            assert_eq!(gradients.column(feature_i), reference_gradients.column(reference_i));
+        }
+
+        // invalid parameters
+        let mut trimmed = parameters(false);
+        trimmed.max_radial_per_angular = Some(vec![6, 5, 4]);
+        let error = SphericalExpansion::new(trimmed).unwrap_err();
+        assert_eq!(error.to_string(), "invalid parameter: expected 7 values in max_radial_per_angular, got 3");
+
+        let mut trimmed = parameters(false);
+        trimmed.max_radial_per_angular = Some(vec![6, 7, 4, 3, 2, 1, 0]);
+        let error = SphericalExpansion::new(trimmed).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "invalid parameter: max_radial_per_angular for l=1 must be between 1 and max_radial (6), got 7"
+        );
+    }
+
     mod cutoff_function {
         use super::super::CutoffFunction;
 
@@ -6,7 +6,7 @@ use super::CalculatorBase;
 
 use crate::descriptor::{Indexes, IndexesBuilder, IndexValue};
 use crate::descriptor::{SamplesBuilder, TwoBodiesSpeciesSamples};
-use crate::{Descriptor, Error, System};
+use crate::{Descriptor, Error, ErrorContext, System};
 
 #[derive(Debug, Clone)]
 #[derive(serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
@@ -84,6 +84,7 @@ impl CalculatorBase for SortedDistances {
         // the current system
         let mut current = 0;
         for (i_system, system) in systems.iter_mut().enumerate() {
+            crate::calculator::check_cancelled()?;
             let system_size = system.size()?;
 
             // `distances` contains a vector of distances vector (one distance
@@ -98,7 +99,9 @@ impl CalculatorBase for SortedDistances {
             }
 
             // Collect all distances around each center in `distances`
-            system.compute_neighbors(self.cutoff)?;
+            system.compute_neighbors(self.cutoff).with_context(|| format!(
+                "failed to compute the neighbors list for structure {}", i_system
+            ))?;
             let species = system.species()?;
             for pair in system.pairs()? {
                 let i = pair.first;
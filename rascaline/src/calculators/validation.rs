@@ -0,0 +1,345 @@
+use schemars::JsonSchema;
+use schemars::schema::{InstanceType, RootSchema, Schema, SchemaObject, SingleOrVec};
+use serde_json::Value;
+
+use crate::Error;
+
+/// Validate the JSON hyper-parameters in `json` against the JSON schema of
+/// `T`, before deserializing them with serde.
+///
+/// The returned error lists all the problems found in the parameters: missing
+/// or unknown keys (with suggestions for likely typos), values with the wrong
+/// type and values outside of the allowed range, together with the path to
+/// the corresponding field.
+pub(crate) fn validate_parameters<T: JsonSchema>(json: &str) -> Result<(), Error> {
+    let value = serde_json::from_str::<Value>(json)?;
+
+    let root = schemars::schema_for!(T);
+    let mut validator = Validator {
+        root: &root,
+        errors: Vec::new(),
+    };
+    validator.validate(&root.schema, &value, "");
+
+    if validator.errors.is_empty() {
+        return Ok(());
+    }
+
+    return Err(Error::InvalidParameter(validator.errors.join("; ")));
+}
+
+struct Validator<'a> {
+    root: &'a RootSchema,
+    errors: Vec<String>,
+}
+
+impl<'a> Validator<'a> {
+    fn validate_schema(&mut self, schema: &'a Schema, value: &Value, path: &str) {
+        match schema {
+            Schema::Bool(true) => {}
+            Schema::Bool(false) => {
+                self.errors.push(format!("unexpected value for {}", location(path)));
+            }
+            Schema::Object(object) => self.validate(object, value, path),
+        }
+    }
+
+    fn validate(&mut self, schema: &'a SchemaObject, value: &Value, path: &str) {
+        if let Some(ref reference) = schema.reference {
+            let name = reference.trim_start_matches("#/definitions/");
+            match self.root.definitions.get(name) {
+                Some(definition) => self.validate_schema(definition, value, path),
+                None => self.errors.push(format!("unknown reference '{}' in JSON schema", reference)),
+            }
+        }
+
+        if let Some(ref subschemas) = schema.subschemas {
+            if let Some(ref all_of) = subschemas.all_of {
+                for schema in all_of {
+                    self.validate_schema(schema, value, path);
+                }
+            }
+
+            if let Some(ref one_of) = subschemas.one_of {
+                self.validate_alternatives(one_of, value, path);
+            }
+
+            if let Some(ref any_of) = subschemas.any_of {
+                self.validate_alternatives(any_of, value, path);
+            }
+        }
+
+        if let Some(ref instance_type) = schema.instance_type {
+            let matches = match instance_type {
+                SingleOrVec::Single(instance_type) => has_type(value, **instance_type),
+                SingleOrVec::Vec(types) => types.iter().any(|&t| has_type(value, t)),
+            };
+
+            if !matches {
+                self.errors.push(format!(
+                    "expected {} for {}, got {}",
+                    type_name(instance_type), location(path), value
+                ));
+                return;
+            }
+        }
+
+        if let Some(ref enum_values) = schema.enum_values {
+            if !enum_values.contains(value) {
+                let expected = enum_values.iter().map(ToString::to_string).collect::<Vec<_>>();
+                self.errors.push(format!(
+                    "expected one of {} for {}, got {}",
+                    expected.join(", "), location(path), value
+                ));
+            }
+        }
+
+        if let (Some(ref number), Some(actual)) = (&schema.number, value.as_f64()) {
+            if let Some(minimum) = number.minimum {
+                if actual < minimum {
+                    self.errors.push(format!(
+                        "expected {} to be at least {}, got {}", location(path), minimum, value
+                    ));
+                }
+            }
+
+            if let Some(maximum) = number.maximum {
+                if actual > maximum {
+                    self.errors.push(format!(
+                        "expected {} to be at most {}, got {}", location(path), maximum, value
+                    ));
+                }
+            }
+        }
+
+        if let (Some(ref object), Some(map)) = (&schema.object, value.as_object()) {
+            for key in &object.required {
+                if !map.contains_key(key) {
+                    self.errors.push(format!("missing '{}' in {}", key, location(path)));
+                }
+            }
+
+            for (key, value) in map {
+                let path = if path.is_empty() { key.clone() } else { format!("{}.{}", path, key) };
+                if let Some(property) = object.properties.get(key) {
+                    self.validate_schema(property, value, &path);
+                } else if let Some(ref additional) = object.additional_properties {
+                    self.validate_schema(additional, value, &path);
+                } else if !object.properties.is_empty() {
+                    let message = match suggest(key, object.properties.keys()) {
+                        Some(suggestion) => format!("unknown key '{}', did you mean '{}'?", path, suggestion),
+                        None => format!("unknown key '{}'", path),
+                    };
+                    self.errors.push(message);
+                }
+            }
+        }
+    }
+
+    /// Validate `value` against a set of alternatives schemas, as generated
+    /// for enums. If the value does not match any of the alternatives, the
+    /// errors for the most likely alternative are reported.
+    fn validate_alternatives(&mut self, alternatives: &'a [Schema], value: &Value, path: &str) {
+        let mut all_errors = Vec::new();
+        for alternative in alternatives {
+            let mut validator = Validator {
+                root: self.root,
+                errors: Vec::new(),
+            };
+            validator.validate_schema(alternative, value, path);
+            if validator.errors.is_empty() {
+                return;
+            }
+            all_errors.push(validator.errors);
+        }
+
+        // serde externally tagged enums are represented as objects with a
+        // single key giving the name of the variant
+        let variants = alternatives.iter().filter_map(variant_name).collect::<Vec<_>>();
+        if variants.len() == alternatives.len() {
+            if let Some(map) = value.as_object() {
+                if map.len() == 1 {
+                    let key = map.keys().next().expect("map has one element");
+                    if let Some(i) = variants.iter().position(|variant| variant == key) {
+                        self.errors.append(&mut all_errors[i]);
+                        return;
+                    }
+
+                    let mut message = format!(
+                        "unknown variant '{}' for {}, expected one of '{}'",
+                        key, location(path), variants.join("', '")
+                    );
+                    if let Some(suggestion) = suggest(key, variants.iter()) {
+                        message.push_str(", did you mean '");
+                        message.push_str(suggestion);
+                        message.push_str("'?");
+                    }
+                    self.errors.push(message);
+                    return;
+                }
+            }
+
+            self.errors.push(format!(
+                "expected an object with one of '{}' for {}, got {}",
+                variants.join("', '"), location(path), value
+            ));
+            return;
+        }
+
+        // otherwise, report the errors for the alternative with the fewest
+        // errors
+        let errors = all_errors.into_iter()
+            .min_by_key(Vec::len)
+            .expect("there should be at least one alternative");
+        self.errors.extend(errors);
+    }
+}
+
+/// Get the name of the variant for serde externally tagged enums
+fn variant_name(schema: &Schema) -> Option<String> {
+    if let Schema::Object(object) = schema {
+        if let Some(ref validation) = object.object {
+            if validation.required.len() == 1 && validation.properties.len() == 1 {
+                return validation.required.iter().next().cloned();
+            }
+        }
+    }
+    return None;
+}
+
+/// Check if the JSON `value` has the given JSON schema `instance_type`
+fn has_type(value: &Value, instance_type: InstanceType) -> bool {
+    match instance_type {
+        InstanceType::Null => value.is_null(),
+        InstanceType::Boolean => value.is_boolean(),
+        InstanceType::Object => value.is_object(),
+        InstanceType::Array => value.is_array(),
+        InstanceType::Number => value.is_number(),
+        InstanceType::String => value.is_string(),
+        InstanceType::Integer => value.is_i64() || value.is_u64(),
+    }
+}
+
+/// Get a human readable name for the JSON schema `instance_type`
+fn type_name(instance_type: &SingleOrVec<InstanceType>) -> String {
+    let name = |instance_type: &InstanceType| match instance_type {
+        InstanceType::Null => "null",
+        InstanceType::Boolean => "a boolean",
+        InstanceType::Object => "an object",
+        InstanceType::Array => "an array",
+        InstanceType::Number => "a number",
+        InstanceType::String => "a string",
+        InstanceType::Integer => "an integer",
+    };
+
+    match instance_type {
+        SingleOrVec::Single(instance_type) => name(instance_type).into(),
+        SingleOrVec::Vec(types) => types.iter().map(name).collect::<Vec<_>>().join(" or "),
+    }
+}
+
+/// Get a description of the location of `path` for error messages
+fn location(path: &str) -> String {
+    if path.is_empty() {
+        "parameters".into()
+    } else {
+        format!("'{}'", path)
+    }
+}
+
+/// Find the closest string to `name` in `candidates`, if it is close enough
+/// to be a typo.
+fn suggest<'b>(name: &str, candidates: impl Iterator<Item=&'b String>) -> Option<&'b str> {
+    let max_distance = std::cmp::max(2, name.chars().count() / 3);
+    return candidates
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|&(distance, _)| distance <= max_distance)
+        .min_by_key(|&(distance, _)| distance)
+        .map(|(_, candidate)| candidate.as_str());
+}
+
+/// Compute the Levenshtein distance between `a` and `b`
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut previous = (0..=b.len()).collect::<Vec<_>>();
+    let mut current = vec![0; b.len() + 1];
+
+    for (i, char_a) in a.chars().enumerate() {
+        current[0] = i + 1;
+        for (j, &char_b) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(char_a != char_b);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+
+    return previous[b.len()];
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{edit_distance, validate_parameters};
+    use crate::calculators::{SortedDistances, SphericalExpansionParameters};
+
+    #[test]
+    fn distance() {
+        assert_eq!(edit_distance("max_angular", "max_angular"), 0);
+        assert_eq!(edit_distance("max_angula", "max_angular"), 1);
+        assert_eq!(edit_distance("max_angualr", "max_angular"), 2);
+        assert_eq!(edit_distance("", "abc"), 3);
+    }
+
+    #[test]
+    fn valid() {
+        validate_parameters::<SortedDistances>(r#"{"cutoff": 3.5, "max_neighbors": 4}"#).unwrap();
+        validate_parameters::<SphericalExpansionParameters>(r#"{
+            "cutoff": 3.5,
+            "max_radial": 4,
+            "max_angular": 4,
+            "atomic_gaussian_width": 0.3,
+            "gradients": false,
+            "radial_basis": {"SplinedGto": {"accuracy": 1e-8}},
+            "cutoff_function": {"ShiftedCosine": {"width": 0.5}}
+        }"#).unwrap();
+    }
+
+    #[test]
+    fn errors() {
+        let error = validate_parameters::<SortedDistances>(r#"{"cutoff": "3.5", "max_neighbors": -4}"#).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "invalid parameter: expected a number for 'cutoff', got \"3.5\"; \
+            expected 'max_neighbors' to be at least 0, got -4"
+        );
+
+        let error = validate_parameters::<SortedDistances>(r#"{"cutof": 3.5, "max_neighbors": 4}"#).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "invalid parameter: missing 'cutoff' in parameters; unknown key 'cutof', did you mean 'cutoff'?"
+        );
+
+        let error = validate_parameters::<SphericalExpansionParameters>(r#"{
+            "cutoff": 3.5,
+            "max_radial": 4,
+            "max_angula": 4,
+            "atomic_gaussian_width": 0.3,
+            "gradients": false,
+            "radial_basis": {"Gtoo": {}},
+            "cutoff_function": {"ShiftedCosine": {"widht": 0.5}}
+        }"#).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "invalid parameter: missing 'max_angular' in parameters; \
+            missing 'width' in 'cutoff_function.ShiftedCosine'; \
+            unknown key 'cutoff_function.ShiftedCosine.widht', did you mean 'width'?; \
+            unknown key 'max_angula', did you mean 'max_angular'?; \
+            unknown variant 'Gtoo' for 'radial_basis', expected one of 'Gto', 'SplinedGto', did you mean 'Gto'?"
+        );
+
+        let error = validate_parameters::<SortedDistances>("[1, 2]").unwrap_err();
+        assert_eq!(error.to_string(), "invalid parameter: expected an object for parameters, got [1,2]");
+
+        let error = validate_parameters::<SortedDistances>("{").unwrap_err();
+        assert!(error.to_string().starts_with("json error: "));
+    }
+}
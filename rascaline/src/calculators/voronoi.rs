@@ -0,0 +1,324 @@
+use std::collections::BTreeMap;
+
+use super::CalculatorBase;
+
+use crate::descriptor::{Indexes, IndexesBuilder, IndexValue};
+use crate::descriptor::{SamplesBuilder, TwoBodiesSpeciesSamples};
+use crate::math::voronoi::VoronoiCell;
+use crate::systems::UnitCell;
+use crate::{Descriptor, Error, ErrorContext, System, Vector3D};
+
+/// Parameters for the Voronoi environment calculator
+#[derive(Debug, Clone)]
+#[derive(serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+pub struct VoronoiParameters {
+    /// Spherical cutoff used to find the neighbors of each atom. This must be
+    /// large enough to contain all the neighbors sharing a face with the
+    /// central atom, i.e. larger than twice the distance between the central
+    /// atom and the furthest vertex of its Voronoi cell.
+    pub cutoff: f64,
+}
+
+/// Features computed from the Voronoi tessellation of the structures.
+///
+/// The Voronoi cell of an atom is the region of space closer to this atom than
+/// to any other atom (including periodic images). For each center and each
+/// neighbor species, the features contain the number of faces of the Voronoi
+/// cell shared with neighbors of this species (`property = 0`), the total area
+/// of these faces (`property = 1`), and the volume of the Voronoi cell of the
+/// center (`property = 2`), which is the same for all neighbor species.
+///
+/// This calculator does not support gradients, since the topology of the
+/// Voronoi cells changes discontinuously with the positions of the atoms.
+#[derive(Debug, Clone)]
+pub struct VoronoiEnvironment {
+    parameters: VoronoiParameters,
+}
+
+impl VoronoiEnvironment {
+    /// Create a new `VoronoiEnvironment` calculator with the given parameters
+    pub fn new(parameters: VoronoiParameters) -> Result<VoronoiEnvironment, Error> {
+        if !(parameters.cutoff > 0.0 && parameters.cutoff.is_finite()) {
+            return Err(Error::InvalidParameter(format!(
+                "cutoff must be a positive number for the Voronoi environment, got {}",
+                parameters.cutoff
+            )));
+        }
+
+        return Ok(VoronoiEnvironment {
+            parameters: parameters,
+        });
+    }
+
+    /// Get the positions (relative to the center) of all periodic images of
+    /// the center within the cutoff
+    fn self_images(&self, cell: &UnitCell) -> Vec<Vector3D> {
+        let mut images = Vec::new();
+        if cell.is_infinite() {
+            return images;
+        }
+
+        let cutoff = self.parameters.cutoff;
+        let matrix = cell.matrix();
+        let distances = cell.distances_between_faces();
+        let max_n = [
+            (cutoff / distances[0]) as i32,
+            (cutoff / distances[1]) as i32,
+            (cutoff / distances[2]) as i32,
+        ];
+
+        for n1 in -max_n[0]..=max_n[0] {
+            for n2 in -max_n[1]..=max_n[1] {
+                for n3 in -max_n[2]..=max_n[2] {
+                    if n1 == 0 && n2 == 0 && n3 == 0 {
+                        continue;
+                    }
+
+                    let vector = n1 as f64 * Vector3D::from(matrix[0])
+                        + n2 as f64 * Vector3D::from(matrix[1])
+                        + n3 as f64 * Vector3D::from(matrix[2]);
+                    if vector.norm() < cutoff {
+                        images.push(vector);
+                    }
+                }
+            }
+        }
+
+        return images;
+    }
+}
+
+impl CalculatorBase for VoronoiEnvironment {
+    fn name(&self) -> String {
+        "Voronoi environment".into()
+    }
+
+    fn get_parameters(&self) -> String {
+        serde_json::to_string(&self.parameters).expect("failed to serialize to JSON")
+    }
+
+    fn features_names(&self) -> Vec<&str> {
+        vec!["property"]
+    }
+
+    fn features(&self) -> Indexes {
+        let mut features = IndexesBuilder::new(self.features_names());
+        for property in 0..3 {
+            features.add(&[IndexValue::from(property)]);
+        }
+        return features.finish();
+    }
+
+    fn samples_builder(&self) -> Box<dyn SamplesBuilder> {
+        Box::new(TwoBodiesSpeciesSamples::new(self.parameters.cutoff))
+    }
+
+    fn compute_gradients(&self) -> bool {
+        false
+    }
+
+    fn check_features(&self, indexes: &Indexes) -> Result<(), Error> {
+        assert_eq!(indexes.names(), self.features_names());
+        for value in indexes.iter() {
+            if value[0].usize() >= 3 {
+                return Err(Error::InvalidParameter(format!(
+                    "invalid feature for Voronoi environment: got {}, expected 0, 1 or 2",
+                    value[0].usize()
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    #[time_graph::instrument(name = "VoronoiEnvironment::compute")]
+    fn compute(&mut self, systems: &mut [Box<dyn System>], descriptor: &mut Descriptor) -> Result<(), Error> {
+        assert_eq!(descriptor.samples.names(), &["structure", "center", "species_center", "species_neighbor"]);
+        assert_eq!(descriptor.features.names(), &["property"]);
+
+        let cutoff = self.parameters.cutoff;
+        let properties = descriptor.features.iter()
+            .map(|feature| feature[0].usize())
+            .collect::<Vec<_>>();
+
+        // all requested samples, grouped by (structure, center)
+        let mut samples = BTreeMap::new();
+        for (sample_i, sample) in descriptor.samples.iter().enumerate() {
+            samples.entry((sample[0].usize(), sample[1].usize()))
+                .or_insert_with(BTreeMap::new)
+                .insert(sample[3].i32(), sample_i);
+        }
+
+        for (i_system, system) in systems.iter_mut().enumerate() {
+            crate::calculator::check_cancelled()?;
+            system.compute_neighbors(cutoff).with_context(|| format!(
+                "failed to compute the neighbors list for structure {}", i_system
+            ))?;
+
+            let species = system.species()?;
+            let self_images = self.self_images(&system.cell()?);
+            for center in 0..system.size()? {
+                let center_samples = match samples.get(&(i_system, center)) {
+                    Some(center_samples) => center_samples,
+                    None => continue,
+                };
+
+                // all neighbors as (atom, vector from the center to the neighbor).
+                // Periodic images of the center are taken from the unit cell
+                // instead of the neighbors list.
+                let mut neighbors = system.pairs_containing(center)?.iter()
+                    .filter(|pair| pair.first != pair.second)
+                    .map(|pair| {
+                        if pair.first == center {
+                            (pair.second, pair.vector)
+                        } else {
+                            (pair.first, -pair.vector)
+                        }
+                    })
+                    .chain(self_images.iter().map(|&vector| (center, vector)))
+                    .collect::<Vec<_>>();
+                neighbors.sort_by(|a, b| a.1.norm2().partial_cmp(&b.1.norm2()).expect("got NaN distance"));
+
+                let mut cell = VoronoiCell::cube(cutoff);
+                for (i_neighbor, &(_, vector)) in neighbors.iter().enumerate() {
+                    if vector.norm() > 2.0 * cell.max_radius() {
+                        // neighbors are sorted by distance, none of the
+                        // remaining ones can cut the cell
+                        break;
+                    }
+                    cell.cut(vector, i_neighbor);
+                }
+
+                let radius = cell.max_radius();
+                if 2.0 * radius >= cutoff {
+                    return Err(Error::InvalidParameter(format!(
+                        "the Voronoi cell of atom {} in structure {} is not fully \
+                        determined by the neighbors inside the cutoff, the cutoff \
+                        should be larger than {}", center, i_system, 2.0 * radius
+                    )));
+                }
+
+                let volume = cell.volume();
+                let mut faces_by_species = BTreeMap::new();
+                for face in cell.faces() {
+                    let i_neighbor = face.neighbor.expect("all faces should come from neighbors");
+                    let neighbor_species = species[neighbors[i_neighbor].0];
+                    let entry = faces_by_species.entry(neighbor_species).or_insert((0.0, 0.0));
+                    entry.0 += 1.0;
+                    entry.1 += face.area();
+                }
+
+                for (&neighbor_species, &sample_i) in center_samples {
+                    let (count, area) = faces_by_species.get(&neighbor_species)
+                        .copied()
+                        .unwrap_or((0.0, 0.0));
+
+                    for (feature_i, &property) in properties.iter().enumerate() {
+                        descriptor.values[[sample_i, feature_i]] = match property {
+                            0 => count,
+                            1 => area,
+                            2 => volume,
+                            _ => unreachable!(),
+                        };
+                    }
+                }
+            }
+        }
+
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use crate::systems::{SimpleSystem, UnitCell};
+    use crate::systems::test_utils::test_systems;
+    use crate::{Calculator, Descriptor, System, Vector3D};
+    use crate::calculators::CalculatorBase;
+
+    use super::{VoronoiEnvironment, VoronoiParameters};
+
+    fn calculator(cutoff: f64) -> Calculator {
+        Calculator::from(Box::new(
+            VoronoiEnvironment::new(VoronoiParameters { cutoff: cutoff }).unwrap()
+        ) as Box<dyn CalculatorBase>)
+    }
+
+    #[test]
+    fn simple_cubic() {
+        let mut system = SimpleSystem::new(UnitCell::cubic(2.0));
+        system.add_atom(1, Vector3D::new(0.3, 0.2, 0.1));
+        let mut systems = vec![Box::new(system) as Box<dyn System>];
+
+        let mut descriptor = Descriptor::new();
+        calculator(4.0).compute(&mut systems, &mut descriptor, Default::default()).unwrap();
+
+        assert_eq!(descriptor.samples.count(), 1);
+        let values = descriptor.values.row(0);
+        assert_relative_eq!(values[0], 6.0);
+        assert_relative_eq!(values[1], 24.0, max_relative = 1e-12);
+        assert_relative_eq!(values[2], 8.0, max_relative = 1e-12);
+    }
+
+    #[test]
+    fn body_centered_cubic() {
+        let mut system = SimpleSystem::new(UnitCell::cubic(2.0));
+        system.add_atom(1, Vector3D::new(0.0, 0.0, 0.0));
+        system.add_atom(2, Vector3D::new(1.0, 1.0, 1.0));
+        let mut systems = vec![Box::new(system) as Box<dyn System>];
+
+        let mut descriptor = Descriptor::new();
+        calculator(3.0).compute(&mut systems, &mut descriptor, Default::default()).unwrap();
+
+        // the Voronoi cells are truncated octahedra, with 6 square faces
+        // shared with atoms of the same species and 8 hexagonal faces shared
+        // with atoms of the other species
+        let edge = 1.0 / f64::sqrt(2.0);
+        let square = edge * edge;
+        let hexagon = 1.5 * f64::sqrt(3.0) * edge * edge;
+
+        assert_eq!(descriptor.samples.count(), 4);
+        for (sample_i, sample) in descriptor.samples.iter().enumerate() {
+            let values = descriptor.values.row(sample_i);
+            if sample[2] == sample[3] {
+                assert_relative_eq!(values[0], 6.0);
+                assert_relative_eq!(values[1], 6.0 * square, max_relative = 1e-12);
+            } else {
+                assert_relative_eq!(values[0], 8.0);
+                assert_relative_eq!(values[1], 8.0 * hexagon, max_relative = 1e-12);
+            }
+            assert_relative_eq!(values[2], 4.0, max_relative = 1e-12);
+        }
+    }
+
+    #[test]
+    fn total_volume() {
+        let mut systems = test_systems(&["methane"]);
+        let mut descriptor = Descriptor::new();
+        calculator(9.0).compute(&mut systems, &mut descriptor, Default::default()).unwrap();
+
+        // the Voronoi cells fill the whole unit cell
+        let mut volumes = [0.0; 5];
+        for (sample_i, sample) in descriptor.samples.iter().enumerate() {
+            volumes[sample[1].usize()] = descriptor.values[[sample_i, 2]];
+        }
+        assert_relative_eq!(volumes.iter().sum::<f64>(), 125.0, max_relative = 1e-10);
+    }
+
+    #[test]
+    fn cutoff_too_small() {
+        let mut systems = test_systems(&["water"]);
+        let mut descriptor = Descriptor::new();
+        let error = calculator(3.0).compute(&mut systems, &mut descriptor, Default::default()).unwrap_err();
+        assert!(error.to_string().starts_with(
+            "invalid parameter: the Voronoi cell of atom 0 in structure 0 is not fully determined"
+        ));
+    }
+
+    #[test]
+    fn invalid_parameters() {
+        let error = VoronoiEnvironment::new(VoronoiParameters { cutoff: -1.0 }).unwrap_err();
+        assert_eq!(error.to_string(), "invalid parameter: cutoff must be a positive number for the Voronoi environment, got -1");
+    }
+}
@@ -0,0 +1,322 @@
+use std::path::{Path, PathBuf};
+
+use ndarray::Axis;
+
+use crate::descriptor::{IndexesBuilder, IndexValue};
+use crate::{Calculator, CalculationOptions, Descriptor, Error, SelectedIndexes, System};
+
+/// Metadata stored alongside the checkpoint files, used to check that we are
+/// resuming the same calculation
+#[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+struct CheckpointMetadata {
+    calculator: String,
+    /// parameters of the calculator, parsed as JSON to ignore differences in
+    /// formatting
+    parameters: serde_json::Value,
+    samples_names: Vec<String>,
+    features_names: Vec<String>,
+    batch_size: usize,
+    systems: usize,
+}
+
+impl Calculator {
+    /// Compute the descriptor for all the given `systems`, in batches of
+    /// `batch_size` systems, storing the descriptor for each batch in
+    /// `directory` as soon as it is computed.
+    ///
+    /// If the calculation is interrupted (crash, job time limit, *etc.*),
+    /// calling this function again with the same calculator, systems,
+    /// `directory` and `batch_size` will re-use the batches already computed
+    /// and only compute the missing ones. Once all the batches are available,
+    /// they are merged into `descriptor`.
+    ///
+    /// The checkpoint files are not removed at the end of the calculation.
+    ///
+    /// # Errors
+    ///
+    /// In addition to the errors from `Calculator::compute`, this function
+    /// returns an error if `directory` contains a checkpoint for a different
+    /// calculation, if writing the checkpoint files failed, if
+    /// `options.selected_samples` is not `SelectedIndexes::All`, or if
+    /// `options.lazy_gradients` is set.
+    pub fn compute_checkpointed(
+        &mut self,
+        systems: &mut [Box<dyn System>],
+        descriptor: &mut Descriptor,
+        options: CalculationOptions,
+        directory: &Path,
+        batch_size: usize,
+    ) -> Result<(), Error> {
+        if batch_size == 0 {
+            return Err(Error::InvalidParameter(
+                "batch_size must be at least 1 in compute_checkpointed".into()
+            ));
+        }
+
+        let CalculationOptions {
+            use_native_system, selected_samples, selected_features, threads,
+            parallelism, check_finite, deterministic, neighbors_cutoff,
+            hyper_gradients, lazy_gradients,
+        } = options;
+
+        if let SelectedIndexes::Subset(_) = selected_samples {
+            return Err(Error::InvalidParameter(
+                "selected samples are not supported in compute_checkpointed".into()
+            ));
+        }
+
+        if lazy_gradients {
+            // the gradients would be computed when saving each batch anyway
+            return Err(Error::InvalidParameter(
+                "lazy gradients are not supported in compute_checkpointed".into()
+            ));
+        }
+
+        std::fs::create_dir_all(directory)?;
+
+        let samples_names = self.samples_builder().names().iter().map(|&name| name.to_owned()).collect::<Vec<_>>();
+        let features_names = self.default_features().names().iter().map(|&name| name.to_owned()).collect::<Vec<_>>();
+        let metadata = CheckpointMetadata {
+            calculator: self.name(),
+            parameters: serde_json::from_str(self.parameters())?,
+            samples_names: samples_names,
+            features_names: features_names,
+            batch_size: batch_size,
+            systems: systems.len(),
+        };
+
+        let metadata_path = directory.join("checkpoint.json");
+        if metadata_path.exists() {
+            let file = std::fs::File::open(&metadata_path)?;
+            let existing = serde_json::from_reader::<_, CheckpointMetadata>(std::io::BufReader::new(file))?;
+            if existing != metadata {
+                return Err(Error::InvalidParameter(format!(
+                    "the checkpoint in '{}' was created for a different calculation",
+                    directory.display()
+                )));
+            }
+        } else {
+            write_atomically(&metadata_path, |writer| {
+                serde_json::to_writer(writer, &metadata)?;
+                return Ok(());
+            })?;
+        }
+
+        let mut batches = Vec::new();
+        for (i_batch, batch) in systems.chunks_mut(batch_size).enumerate() {
+            let path = directory.join(format!("batch-{}.json", i_batch));
+            let batch_descriptor = if path.exists() {
+                let file = std::fs::File::open(&path)?;
+                let batch_descriptor = Descriptor::load(std::io::BufReader::new(file))?;
+                if batch_descriptor.samples.names() != metadata.samples_names ||
+                   batch_descriptor.features.names() != metadata.features_names {
+                    return Err(Error::InvalidParameter(format!(
+                        "the checkpoint in '{}' contains a batch with different samples or features names",
+                        directory.display()
+                    )));
+                }
+                batch_descriptor
+            } else {
+                let mut batch_descriptor = Descriptor::new();
+                let batch_options = CalculationOptions {
+                    use_native_system: use_native_system,
+                    selected_samples: SelectedIndexes::All,
+                    selected_features: selected_features.clone(),
+                    threads: threads,
+                    parallelism: parallelism,
+                    check_finite: check_finite,
+                    deterministic: deterministic,
+                    neighbors_cutoff: neighbors_cutoff,
+                    hyper_gradients: hyper_gradients,
+                    lazy_gradients: false,
+                };
+                self.compute(batch, &mut batch_descriptor, batch_options)?;
+
+                write_atomically(&path, |writer| batch_descriptor.save(writer))?;
+                batch_descriptor
+            };
+
+            batches.push((i_batch * batch_size, batch_descriptor));
+        }
+
+        *descriptor = merge_batches(&batches)?;
+        return Ok(());
+    }
+}
+
+/// Write data to `path` using the `write` function, going through a
+/// temporary file to never leave partially written files around if the
+/// process is interrupted.
+fn write_atomically<F>(path: &Path, write: F) -> Result<(), Error>
+    where F: FnOnce(&mut std::io::BufWriter<std::fs::File>) -> Result<(), Error>
+{
+    let mut tmp_path = PathBuf::from(path);
+    tmp_path.set_extension("tmp");
+
+    let file = std::fs::File::create(&tmp_path)?;
+    let mut writer = std::io::BufWriter::new(file);
+    write(&mut writer)?;
+
+    let file = writer.into_inner().map_err(|e| e.into_error())?;
+    file.sync_all()?;
+
+    std::fs::rename(&tmp_path, path)?;
+    return Ok(());
+}
+
+/// Merge descriptors computed on consecutive batches of systems into a single
+/// descriptor. Each entry in `batches` contains the index of the first system
+/// in the batch and the corresponding descriptor.
+fn merge_batches(batches: &[(usize, Descriptor)]) -> Result<Descriptor, Error> {
+    let first = match batches.first() {
+        Some((_, first)) => first,
+        None => return Ok(Descriptor::new()),
+    };
+
+    let samples_names = first.samples.names();
+    if samples_names.first() != Some(&"structure") {
+        return Err(Error::Internal(format!(
+            "expected the first sample variable to be 'structure', got {:?}", samples_names
+        )));
+    }
+
+    let mut samples = IndexesBuilder::new(samples_names);
+    let mut gradients_samples = first.gradients_samples.as_ref().map(|g| IndexesBuilder::new(g.names()));
+
+    let mut n_samples = 0;
+    for (first_system, batch) in batches {
+        if batch.features != first.features {
+            return Err(Error::InvalidParameter(
+                "all batches in the checkpoint must have the same features".into()
+            ));
+        }
+
+        for sample in batch.samples.iter() {
+            let mut sample = sample.to_vec();
+            sample[0] = IndexValue::from(sample[0].usize() + first_system);
+            samples.add(&sample);
+        }
+
+        match (&mut gradients_samples, &batch.gradients_samples) {
+            (Some(builder), Some(batch_gradients_samples)) => {
+                for gradient_sample in batch_gradients_samples.iter() {
+                    let mut gradient_sample = gradient_sample.to_vec();
+                    gradient_sample[0] = IndexValue::from(gradient_sample[0].usize() + n_samples);
+                    builder.add(&gradient_sample);
+                }
+            }
+            (None, None) => {}
+            _ => {
+                return Err(Error::InvalidParameter(
+                    "some batches in the checkpoint have gradients and some do not".into()
+                ));
+            }
+        }
+
+        n_samples += batch.samples.count();
+    }
+
+    let values = batches.iter().map(|(_, batch)| batch.values.view()).collect::<Vec<_>>();
+    let values = ndarray::concatenate(Axis(0), &values).expect("invalid values shape");
+
+    let gradients = if first.gradients.is_some() {
+        let gradients = batches.iter()
+            .map(|(_, batch)| batch.gradients.as_ref().expect("missing gradients").view())
+            .collect::<Vec<_>>();
+        Some(ndarray::concatenate(Axis(0), &gradients).expect("invalid gradients shape"))
+    } else {
+        None
+    };
+
+    let hyper_gradients = if first.hyper_gradients.is_some() {
+        let mut hyper_gradients = Vec::new();
+        for (_, batch) in batches {
+            if batch.hyper_parameters != first.hyper_parameters {
+                return Err(Error::InvalidParameter(
+                    "all batches in the checkpoint must have the same hyper-parameters gradients".into()
+                ));
+            }
+            hyper_gradients.push(batch.hyper_gradients.as_ref().expect("missing hyper-parameters gradients").view());
+        }
+        Some(ndarray::concatenate(Axis(1), &hyper_gradients).expect("invalid hyper-parameters gradients shape"))
+    } else {
+        None
+    };
+
+    let mut descriptor = Descriptor::new();
+    descriptor.values = values;
+    descriptor.samples = samples.finish();
+    descriptor.gradients = gradients;
+    descriptor.gradients_samples = gradients_samples.map(IndexesBuilder::finish);
+    descriptor.features = first.features.clone();
+    descriptor.hyper_gradients = hyper_gradients;
+    descriptor.hyper_parameters.clone_from(&first.hyper_parameters);
+
+    return Ok(descriptor);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Calculator, CalculationOptions, Descriptor};
+    use crate::systems::test_utils::test_systems;
+
+    fn calculator() -> Calculator {
+        Calculator::new("dummy_calculator", r#"{
+            "cutoff": 1.0,
+            "delta": 9,
+            "name": "",
+            "gradients": true
+        }"#.to_owned()).unwrap()
+    }
+
+    #[test]
+    fn checkpoint() {
+        let directory = std::env::temp_dir().join(format!("rascaline-checkpoint-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&directory);
+
+        let mut calculator = calculator();
+        let mut systems = test_systems(&["water", "methane", "CH", "water"]);
+
+        let mut expected = Descriptor::new();
+        calculator.compute(&mut systems, &mut expected, CalculationOptions::default()).unwrap();
+
+        let mut descriptor = Descriptor::new();
+        calculator.compute_checkpointed(&mut systems, &mut descriptor, Default::default(), &directory, 3).unwrap();
+        assert_eq!(descriptor.values, expected.values);
+        assert_eq!(descriptor.samples, expected.samples);
+        assert_eq!(descriptor.gradients, expected.gradients);
+        assert_eq!(descriptor.gradients_samples, expected.gradients_samples);
+        assert_eq!(descriptor.features, expected.features);
+
+        assert!(directory.join("batch-0.json").exists());
+        assert!(directory.join("batch-1.json").exists());
+
+        // resume after losing the last batch
+        std::fs::remove_file(directory.join("batch-1.json")).unwrap();
+        let mut descriptor = Descriptor::new();
+        calculator.compute_checkpointed(&mut systems, &mut descriptor, Default::default(), &directory, 3).unwrap();
+        assert_eq!(descriptor.values, expected.values);
+        assert_eq!(descriptor.gradients, expected.gradients);
+
+        // using a different batch size is an error
+        let error = calculator.compute_checkpointed(&mut systems, &mut descriptor, Default::default(), &directory, 2).unwrap_err();
+        assert!(error.to_string().contains("was created for a different calculation"));
+
+        // and so is using different parameters
+        let mut other = Calculator::new("dummy_calculator", r#"{
+            "cutoff": 1.0,
+            "delta": 7,
+            "name": "",
+            "gradients": true
+        }"#.to_owned()).unwrap();
+        let error = other.compute_checkpointed(&mut systems, &mut descriptor, Default::default(), &directory, 3).unwrap_err();
+        assert!(error.to_string().contains("was created for a different calculation"));
+
+        // but differences in the formatting of the parameters are ignored
+        let mut same = Calculator::new("dummy_calculator", r#"{"cutoff": 1.0, "delta": 9, "name": "", "gradients": true}"#.to_owned()).unwrap();
+        same.compute_checkpointed(&mut systems, &mut descriptor, Default::default(), &directory, 3).unwrap();
+        assert_eq!(descriptor.values, expected.values);
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
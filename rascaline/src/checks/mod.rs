@@ -0,0 +1,160 @@
+//! Consistency checks for calculators, verifying that the computed
+//! representations have the symmetries they should have, and that they do not
+//! change compared to some reference data (see [`Reference`]).
+//!
+//! These checks are used when testing the calculators implemented in
+//! rascaline, and can also be used to check new calculators implemented
+//! outside of rascaline. All checks return `Ok(())` if the calculator passed
+//! the check, and `Err(Error::Internal)` with a description of the failure
+//! otherwise.
+//!
+//! This module also provides [`strain_gradients`], computing the gradients of
+//! a representation with respect to the cell strain with finite differences.
+
+use ndarray::{Array2, ArrayView1};
+
+use crate::{Descriptor, Error};
+use crate::descriptor::IndexValue;
+
+mod rotational;
+pub use self::rotational::rotational;
+
+mod permutation;
+pub use self::permutation::permutation;
+
+mod translation;
+pub use self::translation::translation;
+
+mod regression;
+pub use self::regression::{regression, Reference};
+
+mod strain;
+pub use self::strain::strain_gradients;
+
+use crate::math::Random;
+
+/// Check that `actual` and `expected` are equal, up to `tolerance`. Two values
+/// are considered equal if `|actual - expected| <= tolerance * max(|expected|,
+/// 1)`. The `check` and `array` strings are used in the error message.
+fn check_arrays(
+    check: &str,
+    array: &str,
+    actual: &Array2<f64>,
+    expected: &Array2<f64>,
+    tolerance: f64,
+) -> Result<(), Error> {
+    if actual.shape() != expected.shape() {
+        return Err(Error::Internal(format!(
+            "{} check failed: {} have a different shape, expected {:?}, got {:?}",
+            check, array, expected.shape(), actual.shape()
+        )));
+    }
+
+    for (row, (actual, expected)) in actual.outer_iter().zip(expected.outer_iter()).enumerate() {
+        check_rows(check, array, row, actual, expected, tolerance)?;
+    }
+
+    return Ok(());
+}
+
+/// Check that the `row` of the `actual` and `expected` arrays are equal, up
+/// to `tolerance` (see `check_arrays`).
+fn check_rows(
+    check: &str,
+    array: &str,
+    row: usize,
+    actual: ArrayView1<f64>,
+    expected: ArrayView1<f64>,
+    tolerance: f64,
+) -> Result<(), Error> {
+    for (column, (&actual, &expected)) in actual.iter().zip(expected).enumerate() {
+        if (actual - expected).abs() > tolerance * expected.abs().max(1.0) {
+            return Err(Error::Internal(format!(
+                "{} check failed: {}[{}, {}] should be {}, got {}",
+                check, array, row, column, expected, actual
+            )));
+        }
+    }
+
+    return Ok(());
+}
+
+/// Check that the `actual` descriptor contains the same data as `expected`,
+/// up to a reordering of the samples. The descriptors are computed on systems
+/// with the same atoms in a different order: atom `i` in the system used for
+/// `actual` corresponds to atom `atoms[i]` in the system used for `expected`.
+///
+/// The atoms are identified by the `center` variable in the samples, and the
+/// `atom` variable in the gradients samples.
+fn check_reordered(
+    check: &str,
+    actual: &Descriptor,
+    expected: &Descriptor,
+    atoms: &[usize],
+    tolerance: f64,
+) -> Result<(), Error> {
+    if actual.features != expected.features {
+        return Err(Error::Internal(format!(
+            "{} check failed: the features are not the same", check
+        )));
+    }
+
+    let names = actual.samples.names();
+    if names != expected.samples.names() || actual.samples.count() != expected.samples.count() {
+        return Err(Error::Internal(format!(
+            "{} check failed: the samples are not the same", check
+        )));
+    }
+    let center = names.iter().position(|&name| name == "center");
+
+    // position of each sample of `actual` in `expected`
+    let mut samples = Vec::with_capacity(actual.samples.count());
+    for (row, sample) in actual.samples.iter().enumerate() {
+        let mut sample = sample.to_vec();
+        if let Some(center) = center {
+            sample[center] = IndexValue::from(atoms[sample[center].usize()]);
+        }
+
+        let expected_row = expected.samples.position(&sample).ok_or_else(|| Error::Internal(format!(
+            "{} check failed: missing sample {:?}", check, sample
+        )))?;
+        samples.push(expected_row);
+
+        check_rows(
+            check, "values", row,
+            actual.values.row(row),
+            expected.values.row(expected_row),
+            tolerance
+        )?;
+    }
+
+    let gradients = (&actual.gradients_samples, &actual.gradients, &expected.gradients_samples, &expected.gradients);
+    if let (Some(actual_samples), Some(actual), Some(expected_samples), Some(expected)) = gradients {
+        if actual_samples.count() != expected_samples.count() {
+            return Err(Error::Internal(format!(
+                "{} check failed: the gradients samples are not the same", check
+            )));
+        }
+
+        for (row, gradient_sample) in actual_samples.iter().enumerate() {
+            let gradient_sample = [
+                IndexValue::from(samples[gradient_sample[0].usize()]),
+                IndexValue::from(atoms[gradient_sample[1].usize()]),
+                gradient_sample[2],
+            ];
+
+            let expected_row = expected_samples.position(&gradient_sample).ok_or_else(|| Error::Internal(format!(
+                "{} check failed: missing gradient sample {:?}", check, gradient_sample
+            )))?;
+
+            check_rows(
+                check, "gradients", row,
+                actual.row(row),
+                expected.row(expected_row),
+                tolerance
+            )?;
+        }
+    }
+
+    return Ok(());
+}
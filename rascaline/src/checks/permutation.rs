@@ -0,0 +1,114 @@
+use std::convert::TryFrom;
+
+use crate::{Calculator, Error, SimpleSystem, System};
+
+use super::{Random, check_reordered};
+use super::rotational::compute;
+
+/// Number of random permutations used in the check
+const N_PERMUTATIONS: usize = 10;
+
+/// Check that the representation computed by `calculator` for `system` does
+/// not depend on the order of the atoms in the system.
+///
+/// This applies random permutations to the atoms in the system, and checks
+/// that the values and gradients are the same as for the initial system, up
+/// to a reordering of the samples. The atoms are identified by the `center`
+/// variable in the samples and the `atom` variable in the gradients samples.
+///
+/// Two values are considered equal if `|actual - expected| <= tolerance *
+/// max(|expected|, 1)`. The permutations are pseudo-random, but the same
+/// permutations are used every time this function is called.
+pub fn permutation(calculator: &mut Calculator, system: &dyn System, tolerance: f64) -> Result<(), Error> {
+    let system = SimpleSystem::try_from(system)?;
+    let reference = compute(calculator, system.clone())?;
+
+    let species = system.species()?;
+    let positions = system.positions()?;
+
+    let mut random = Random::new(0x5EED_3F00);
+    for _ in 0..N_PERMUTATIONS {
+        let atoms = random_permutation(&mut random, species.len());
+
+        let mut permuted = SimpleSystem::new(system.cell()?);
+        for &atom in &atoms {
+            permuted.add_atom(species[atom], positions[atom]);
+        }
+
+        let permuted = compute(calculator, permuted)?;
+        check_reordered("permutation", &permuted, &reference, &atoms, tolerance)?;
+    }
+
+    return Ok(());
+}
+
+/// Get a random permutation of `0..n`
+fn random_permutation(random: &mut Random, n: usize) -> Vec<usize> {
+    let mut permutation = (0..n).collect::<Vec<_>>();
+    random.shuffle(&mut permutation);
+    return permutation;
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Calculator;
+    use crate::systems::test_utils::test_system;
+
+    use crate::math::Random;
+    use super::random_permutation;
+
+    #[test]
+    fn permutations() {
+        let mut random = Random::new(7);
+        for _ in 0..10 {
+            let mut permutation = random_permutation(&mut random, 12);
+            permutation.sort_unstable();
+            assert_eq!(permutation, (0..12).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn soap_power_spectrum() {
+        let mut calculator = Calculator::new("soap_power_spectrum", r#"{
+            "cutoff": 3.5,
+            "max_radial": 4,
+            "max_angular": 4,
+            "atomic_gaussian_width": 0.3,
+            "radial_basis": {"Gto": {}},
+            "cutoff_function": {"ShiftedCosine": {"width": 0.5}},
+            "gradients": true
+        }"#.to_owned()).unwrap();
+
+        super::permutation(&mut calculator, &test_system("methane"), 1e-12).unwrap();
+    }
+
+    #[test]
+    fn spherical_expansion() {
+        let mut calculator = Calculator::new("spherical_expansion", r#"{
+            "cutoff": 3.5,
+            "max_radial": 4,
+            "max_angular": 4,
+            "atomic_gaussian_width": 0.3,
+            "radial_basis": {"Gto": {}},
+            "cutoff_function": {"ShiftedCosine": {"width": 0.5}},
+            "gradients": true
+        }"#.to_owned()).unwrap();
+
+        super::permutation(&mut calculator, &test_system("water"), 1e-12).unwrap();
+    }
+
+    #[test]
+    fn not_invariant() {
+        // the dummy calculator uses the index of the center atom in the
+        // values, which changes when the atoms are permuted
+        let mut calculator = Calculator::new("dummy_calculator", r#"{
+            "cutoff": 3.0,
+            "delta": 0,
+            "name": "",
+            "gradients": false
+        }"#.to_owned()).unwrap();
+
+        let error = super::permutation(&mut calculator, &test_system("methane"), 1e-12).unwrap_err();
+        assert!(error.to_string().starts_with("internal error: permutation check failed: values["));
+    }
+}
@@ -0,0 +1,214 @@
+use std::convert::TryFrom;
+
+use crate::{CalculationOptions, Calculator, Descriptor, Error, ErrorContext, Matrix3, SimpleSystem, System, Vector3D};
+use crate::systems::UnitCell;
+
+use super::check_arrays;
+
+/// Reference output of a calculator on a small set of systems, used for
+/// regression tests.
+///
+/// A reference is created with [`Reference::record`], saved to a file with
+/// [`Reference::save`], and later loaded with [`Reference::load`] to check
+/// that a calculator still produces the same output with [`regression`]. This
+/// works with any calculator, including calculators implemented outside of
+/// rascaline.
+#[derive(Clone, Debug)]
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct Reference {
+    /// Name of the calculator, as given by `Calculator::name`
+    pub name: String,
+    /// Hyper-parameters of the calculator, as given by `Calculator::parameters`
+    pub parameters: String,
+    /// Systems used to compute the reference
+    systems: Vec<ReferenceSystem>,
+    /// Expected output of the calculator on the systems
+    pub descriptor: Descriptor,
+}
+
+/// Serializable version of a system
+#[derive(Clone, Debug)]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct ReferenceSystem {
+    species: Vec<i32>,
+    positions: Vec<[f64; 3]>,
+    /// unit cell matrix, full of zeros for infinite cells
+    cell: [[f64; 3]; 3],
+}
+
+impl Reference {
+    /// Record the output of `calculator` on the given `systems`. The
+    /// calculation is always run in deterministic mode, see
+    /// `CalculationOptions::deterministic`.
+    pub fn record(calculator: &mut Calculator, systems: &mut [Box<dyn System>]) -> Result<Reference, Error> {
+        let mut reference_systems = Vec::with_capacity(systems.len());
+        for system in systems.iter() {
+            let system = &**system;
+            reference_systems.push(ReferenceSystem {
+                species: system.species()?.to_vec(),
+                positions: system.positions()?.iter().map(|&position| *position).collect(),
+                cell: *system.cell()?.matrix(),
+            });
+        }
+
+        let mut reference = Reference {
+            name: calculator.name(),
+            parameters: calculator.parameters().to_owned(),
+            systems: reference_systems,
+            descriptor: Descriptor::new(),
+        };
+
+        reference.descriptor = compute(calculator, &reference)?;
+        return Ok(reference);
+    }
+
+    /// Get the systems used to compute this reference
+    pub fn systems(&self) -> Result<Vec<Box<dyn System>>, Error> {
+        let mut systems = Vec::with_capacity(self.systems.len());
+        for (i_system, system) in self.systems.iter().enumerate() {
+            if system.species.len() != system.positions.len() {
+                return Err(Error::InvalidParameter(format!(
+                    "system {} in the reference has {} species but {} positions",
+                    i_system, system.species.len(), system.positions.len()
+                )));
+            }
+
+            let cell = UnitCell::try_from(Matrix3::new(system.cell)).with_context(|| format!(
+                "system {} in the reference has an invalid unit cell", i_system
+            ))?;
+
+            let mut simple_system = SimpleSystem::new(cell);
+            for (&species, &position) in system.species.iter().zip(&system.positions) {
+                simple_system.add_atom(species, Vector3D::from(position));
+            }
+            systems.push(Box::new(simple_system) as Box<dyn System>);
+        }
+
+        return Ok(systems);
+    }
+
+    /// Save this reference to the given `writer`, in JSON format
+    pub fn save<W: std::io::Write>(&self, writer: W) -> Result<(), Error> {
+        serde_json::to_writer(writer, self)?;
+        return Ok(());
+    }
+
+    /// Load a reference previously saved with `Reference::save` from the
+    /// given `reader`
+    pub fn load<R: std::io::Read>(reader: R) -> Result<Reference, Error> {
+        let reference = serde_json::from_reader(reader)?;
+        return Ok(reference);
+    }
+}
+
+/// Check that `calculator` produces the same output as the one recorded in
+/// `reference`, on the same systems.
+///
+/// The calculator must use the same hyper-parameters as the one used to
+/// record the reference. The samples and features must be exactly the same,
+/// and two values (or gradients) are considered equal if `|actual - expected|
+/// <= tolerance * max(|expected|, 1)`.
+pub fn regression(calculator: &mut Calculator, reference: &Reference, tolerance: f64) -> Result<(), Error> {
+    let parameters = serde_json::from_str::<serde_json::Value>(calculator.parameters())?;
+    let expected_parameters = serde_json::from_str::<serde_json::Value>(&reference.parameters)?;
+    if parameters != expected_parameters {
+        return Err(Error::InvalidParameter(format!(
+            "the calculator parameters ({}) do not match the parameters used for the reference ({})",
+            calculator.parameters(), reference.parameters
+        )));
+    }
+
+    let actual = compute(calculator, reference)?;
+    let expected = &reference.descriptor;
+
+    if actual.samples != expected.samples {
+        return Err(Error::Internal(
+            "regression check failed: the samples are not the same".into()
+        ));
+    }
+
+    if actual.features != expected.features {
+        return Err(Error::Internal(
+            "regression check failed: the features are not the same".into()
+        ));
+    }
+
+    if actual.gradients_samples != expected.gradients_samples {
+        return Err(Error::Internal(
+            "regression check failed: the gradients samples are not the same".into()
+        ));
+    }
+
+    check_arrays("regression", "values", &actual.values, &expected.values, tolerance)?;
+    if let (Some(actual), Some(expected)) = (&actual.gradients, &expected.gradients) {
+        check_arrays("regression", "gradients", actual, expected, tolerance)?;
+    }
+
+    return Ok(());
+}
+
+/// Run the calculation on the systems of the `reference`
+fn compute(calculator: &mut Calculator, reference: &Reference) -> Result<Descriptor, Error> {
+    let mut systems = reference.systems()?;
+    for system in &mut systems {
+        // make sure the reference data does not depend on the system
+        // implementation (neighbors list, ...)
+        *system = Box::new(SimpleSystem::try_from(&**system)?);
+    }
+
+    let options = CalculationOptions {
+        deterministic: true,
+        ..Default::default()
+    };
+
+    let mut descriptor = Descriptor::new();
+    calculator.compute(&mut systems, &mut descriptor, options)?;
+    return Ok(descriptor);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Calculator;
+    use crate::systems::test_utils::test_systems;
+
+    use super::Reference;
+
+    fn calculator(delta: isize) -> Calculator {
+        return Calculator::new("dummy_calculator", format!(r#"{{
+            "cutoff": 3.0,
+            "delta": {},
+            "name": "",
+            "gradients": true
+        }}"#, delta)).unwrap();
+    }
+
+    #[test]
+    fn record_and_check() {
+        let mut calculator = calculator(3);
+        let mut systems = test_systems(&["water", "methane"]);
+        let reference = Reference::record(&mut calculator, &mut systems).unwrap();
+
+        let mut buffer = Vec::new();
+        reference.save(&mut buffer).unwrap();
+        let reference = Reference::load(buffer.as_slice()).unwrap();
+
+        assert_eq!(reference.systems().unwrap().len(), 2);
+        super::super::regression(&mut calculator, &reference, 1e-12).unwrap();
+    }
+
+    #[test]
+    fn errors() {
+        let mut systems = test_systems(&["water"]);
+        let mut reference = Reference::record(&mut calculator(3), &mut systems).unwrap();
+
+        let error = super::super::regression(&mut calculator(4), &reference, 1e-12).unwrap_err();
+        assert!(error.to_string().starts_with("invalid parameter: the calculator parameters"));
+
+        reference.descriptor.values[[1, 0]] += 1e-3;
+        let error = super::super::regression(&mut calculator(3), &reference, 1e-12).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "internal error: regression check failed: values[1, 0] should be 4.001, got 4"
+        );
+    }
+}
@@ -0,0 +1,178 @@
+use std::convert::TryFrom;
+
+use crate::{Calculator, Descriptor, Error, Matrix3, SimpleSystem, System};
+use crate::systems::UnitCell;
+
+use super::{Random, check_arrays};
+
+/// Number of random rotations used in the check
+const N_ROTATIONS: usize = 10;
+
+/// Check that the representation computed by `calculator` for `system`
+/// behaves as expected under rotations, by applying random rotations to the
+/// system (atomic positions and unit cell) and comparing the descriptors
+/// computed on the rotated systems with the expected ones.
+///
+/// Representations with `l` and `m` in the features (such as the spherical
+/// expansion) are treated as equivariant, and their values should transform
+/// according to the Wigner-D matrices (see [`Descriptor::rotate`]). All other
+/// representations should be invariant. In both cases, the gradients (if any)
+/// should additionally rotate along the spatial dimension.
+///
+/// Two values are considered equal if `|actual - expected| <= tolerance *
+/// max(|expected|, 1)`. The rotations are pseudo-random, but the same
+/// rotations are used every time this function is called.
+pub fn rotational(calculator: &mut Calculator, system: &dyn System, tolerance: f64) -> Result<(), Error> {
+    let system = SimpleSystem::try_from(system)?;
+    let reference = compute(calculator, system.clone())?;
+
+    let names = reference.features.names();
+    let equivariant = names.contains(&"l") && names.contains(&"m");
+
+    let mut random = Random::new(0x5EED_2F00);
+    for _ in 0..N_ROTATIONS {
+        let rotation = random_rotation(&mut random);
+
+        let mut expected = reference.clone();
+        if equivariant {
+            expected.rotate(&rotation)?;
+        } else {
+            expected.rotate_gradients_directions(&rotation)?;
+        }
+
+        let rotated = compute(calculator, rotate_system(&system, &rotation)?)?;
+
+        if rotated.samples != expected.samples || rotated.gradients_samples != expected.gradients_samples {
+            return Err(Error::Internal(
+                "rotational check failed: the samples changed after rotating the system".into()
+            ));
+        }
+
+        check_arrays("rotational", "values", &rotated.values, &expected.values, tolerance)?;
+        if let (Some(actual), Some(expected)) = (&rotated.gradients, &expected.gradients) {
+            check_arrays("rotational", "gradients", actual, expected, tolerance)?;
+        }
+    }
+
+    return Ok(());
+}
+
+/// Run the calculation on a single system
+pub(super) fn compute(calculator: &mut Calculator, system: SimpleSystem) -> Result<Descriptor, Error> {
+    let mut systems = vec![Box::new(system) as Box<dyn System>];
+    let mut descriptor = Descriptor::new();
+    calculator.compute(&mut systems, &mut descriptor, Default::default())?;
+    return Ok(descriptor);
+}
+
+/// Get a rotation matrix uniformly distributed over all rotations, using a
+/// random unit quaternion (from K. Shoemake, Graphics Gems III, 1992).
+fn random_rotation(random: &mut Random) -> Matrix3 {
+    let u1 = random.next_f64();
+    let u2 = 2.0 * std::f64::consts::PI * random.next_f64();
+    let u3 = 2.0 * std::f64::consts::PI * random.next_f64();
+
+    let w = f64::sqrt(u1) * f64::cos(u3);
+    let x = f64::sqrt(1.0 - u1) * f64::sin(u2);
+    let y = f64::sqrt(1.0 - u1) * f64::cos(u2);
+    let z = f64::sqrt(u1) * f64::sin(u3);
+
+    return Matrix3::new([
+        [1.0 - 2.0 * (y * y + z * z), 2.0 * (x * y - z * w), 2.0 * (x * z + y * w)],
+        [2.0 * (x * y + z * w), 1.0 - 2.0 * (x * x + z * z), 2.0 * (y * z - x * w)],
+        [2.0 * (x * z - y * w), 2.0 * (y * z + x * w), 1.0 - 2.0 * (x * x + y * y)],
+    ]);
+}
+
+/// Apply `rotation` to all the atoms and the unit cell of `system`
+fn rotate_system(system: &SimpleSystem, rotation: &Matrix3) -> Result<SimpleSystem, Error> {
+    let cell = system.cell()?;
+    let cell = if cell.is_infinite() {
+        cell
+    } else {
+        // the rows of the cell matrix are the cell vectors
+        UnitCell::try_from(cell.matrix() * rotation.transposed())?
+    };
+
+    let mut rotated = SimpleSystem::new(cell);
+    for (&species, &position) in system.species()?.iter().zip(system.positions()?) {
+        rotated.add_atom(species, *rotation * position);
+    }
+    return Ok(rotated);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Calculator, Matrix3};
+    use crate::systems::test_utils::test_system;
+
+    use crate::math::Random;
+    use super::random_rotation;
+
+    #[test]
+    fn rotations() {
+        let mut random = Random::new(123);
+        for _ in 0..20 {
+            let rotation = random_rotation(&mut random);
+            let identity = rotation * rotation.transposed() - Matrix3::one();
+            assert!(identity.norm() < 1e-12);
+            assert!((rotation.determinant() - 1.0).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn spherical_expansion() {
+        let mut calculator = Calculator::new("spherical_expansion", r#"{
+            "cutoff": 3.5,
+            "max_radial": 4,
+            "max_angular": 4,
+            "atomic_gaussian_width": 0.3,
+            "radial_basis": {"Gto": {}},
+            "cutoff_function": {"ShiftedCosine": {"width": 0.5}},
+            "gradients": true
+        }"#.to_owned()).unwrap();
+
+        // the radial integral is splined, with an accuracy around 1e-8
+        super::rotational(&mut calculator, &test_system("water"), 1e-9).unwrap();
+    }
+
+    #[test]
+    fn soap_power_spectrum() {
+        let mut calculator = Calculator::new("soap_power_spectrum", r#"{
+            "cutoff": 3.5,
+            "max_radial": 4,
+            "max_angular": 4,
+            "atomic_gaussian_width": 0.3,
+            "radial_basis": {"Gto": {}},
+            "cutoff_function": {"ShiftedCosine": {"width": 0.5}},
+            "gradients": true
+        }"#.to_owned()).unwrap();
+
+        super::rotational(&mut calculator, &test_system("water"), 1e-9).unwrap();
+    }
+
+    #[test]
+    fn sorted_distances() {
+        let mut calculator = Calculator::new("sorted_distances", r#"{
+            "cutoff": 3.0,
+            "max_neighbors": 6
+        }"#.to_owned()).unwrap();
+
+        super::rotational(&mut calculator, &test_system("methane"), 1e-12).unwrap();
+    }
+
+    #[test]
+    fn not_invariant() {
+        // the dummy calculator uses the sum of positions, which is not
+        // rotationally invariant
+        let mut calculator = Calculator::new("dummy_calculator", r#"{
+            "cutoff": 3.0,
+            "delta": 0,
+            "name": "",
+            "gradients": false
+        }"#.to_owned()).unwrap();
+
+        let error = super::rotational(&mut calculator, &test_system("water"), 1e-9).unwrap_err();
+        assert!(error.to_string().starts_with("internal error: rotational check failed: values["));
+    }
+}
@@ -0,0 +1,147 @@
+use std::convert::TryFrom;
+
+use ndarray::Array4;
+
+use crate::{Calculator, Error, Matrix3, SimpleSystem, System};
+use crate::systems::UnitCell;
+
+use super::rotational::compute;
+
+/// Compute the gradients of the representation computed by `calculator` for
+/// `system` with respect to a strain of the system, using central finite
+/// differences with a displacement of `delta`.
+///
+/// A strain `ε` deforms both the unit cell and the atomic positions, with
+/// `r -> (1 + ε) r`. The gradients are returned as an array of shape
+/// `(n_samples, 3, 3, n_features)`, where the entry `[s, α, β, f]` contains
+/// the derivative of `values[s, f]` with respect to `ε_αβ`. Multiplying these
+/// gradients by the derivatives of a model with respect to the values gives
+/// the virial of the model.
+///
+/// This can be used to validate analytic gradients with respect to the cell,
+/// or in place of them for calculators which do not implement these. The
+/// samples of the descriptor must not change when straining the system, which
+/// can happen if some pair of atoms moves across the cutoff: in this case, a
+/// smaller `delta` should be used.
+pub fn strain_gradients(calculator: &mut Calculator, system: &dyn System, delta: f64) -> Result<Array4<f64>, Error> {
+    if !(delta > 0.0 && delta.is_finite()) {
+        return Err(Error::InvalidParameter(format!(
+            "the strain displacement must be a positive number, got {}", delta
+        )));
+    }
+
+    let system = SimpleSystem::try_from(system)?;
+    let reference = compute(calculator, system.clone())?;
+
+    let shape = (reference.samples.count(), 3, 3, reference.features.count());
+    let mut gradients = Array4::zeros(shape);
+    for alpha in 0..3 {
+        for beta in 0..3 {
+            let mut strain = Matrix3::zero();
+
+            strain[alpha][beta] = delta;
+            let plus = compute(calculator, strain_system(&system, &strain)?)?;
+
+            strain[alpha][beta] = -delta;
+            let minus = compute(calculator, strain_system(&system, &strain)?)?;
+
+            if plus.samples != reference.samples || minus.samples != reference.samples {
+                return Err(Error::InvalidParameter(format!(
+                    "the samples changed when straining the system by {}, \
+                    try to use a smaller displacement", delta
+                )));
+            }
+
+            let finite_difference = (plus.values - minus.values) / (2.0 * delta);
+            gradients.slice_mut(ndarray::s![.., alpha, beta, ..]).assign(&finite_difference);
+        }
+    }
+
+    return Ok(gradients);
+}
+
+/// Apply the deformation `1 + strain` to all the atoms and the unit cell of
+/// `system`
+fn strain_system(system: &SimpleSystem, strain: &Matrix3) -> Result<SimpleSystem, Error> {
+    let deformation = Matrix3::one() + *strain;
+
+    let cell = system.cell()?;
+    let cell = if cell.is_infinite() {
+        cell
+    } else {
+        // the rows of the cell matrix are the cell vectors
+        UnitCell::try_from(cell.matrix() * deformation.transposed())?
+    };
+
+    let mut strained = SimpleSystem::new(cell);
+    for (&species, &position) in system.species()?.iter().zip(system.positions()?) {
+        strained.add_atom(species, deformation * position);
+    }
+    return Ok(strained);
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use crate::{Calculator, SimpleSystem, System};
+    use crate::systems::UnitCell;
+    use crate::systems::test_utils::test_system;
+
+    use super::super::rotational::compute;
+
+    #[test]
+    fn soap_power_spectrum() {
+        let mut calculator = Calculator::new("soap_power_spectrum", r#"{
+            "cutoff": 3.5,
+            "max_radial": 4,
+            "max_angular": 4,
+            "atomic_gaussian_width": 0.3,
+            "radial_basis": {"Gto": {}},
+            "cutoff_function": {"ShiftedCosine": {"width": 0.5}},
+            "gradients": true
+        }"#.to_owned()).unwrap();
+
+        // without periodic boundary conditions, the strain gradients can be
+        // computed from the gradients with respect to the positions
+        let methane = test_system("methane");
+        let mut system = SimpleSystem::new(UnitCell::infinite());
+        for (&species, &position) in methane.species().unwrap().iter().zip(methane.positions().unwrap()) {
+            system.add_atom(species, position);
+        }
+        let positions = system.positions().unwrap().to_vec();
+
+        let strain_gradients = super::strain_gradients(&mut calculator, &system, 1e-6).unwrap();
+
+        let descriptor = compute(&mut calculator, system).unwrap();
+        let gradients = descriptor.gradients.as_ref().unwrap();
+        let gradients_samples = descriptor.gradients_samples.as_ref().unwrap();
+
+        let mut expected = ndarray::Array4::<f64>::zeros(strain_gradients.dim());
+        for (row, gradient_sample) in gradients_samples.iter().enumerate() {
+            let sample = gradient_sample[0].usize();
+            let atom = gradient_sample[1].usize();
+            let alpha = gradient_sample[2].usize();
+            for (beta, &position) in positions[atom].iter().enumerate() {
+                let mut expected = expected.slice_mut(ndarray::s![sample, alpha, beta, ..]);
+                expected.scaled_add(position, &gradients.row(row));
+            }
+        }
+
+        assert_relative_eq!(strain_gradients, expected, epsilon = 1e-6, max_relative = 1e-6);
+    }
+
+    #[test]
+    fn invalid_delta() {
+        let mut calculator = Calculator::new("sorted_distances", r#"{
+            "cutoff": 3.0,
+            "max_neighbors": 6
+        }"#.to_owned()).unwrap();
+
+        let error = super::strain_gradients(&mut calculator, &test_system("water"), -1e-3).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "invalid parameter: the strain displacement must be a positive number, got -0.001"
+        );
+    }
+}
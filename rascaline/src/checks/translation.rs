@@ -0,0 +1,106 @@
+use std::convert::TryFrom;
+
+use crate::{Calculator, Error, SimpleSystem, System, Vector3D};
+
+use super::{Random, check_reordered};
+use super::rotational::compute;
+
+/// Number of random translations used in the check
+const N_TRANSLATIONS: usize = 10;
+
+/// Check that the representation computed by `calculator` for `system` does
+/// not change when translating all the atoms in the system.
+///
+/// This applies random translations to the atoms in the system, and checks
+/// that the values and gradients are the same as for the initial system, up
+/// to a reordering of the samples. For periodic systems, the atoms are also
+/// wrapped inside the unit cell after the translation.
+///
+/// Two values are considered equal if `|actual - expected| <= tolerance *
+/// max(|expected|, 1)`. The translations are pseudo-random, but the same
+/// translations are used every time this function is called.
+pub fn translation(calculator: &mut Calculator, system: &dyn System, tolerance: f64) -> Result<(), Error> {
+    let system = SimpleSystem::try_from(system)?;
+    let reference = compute(calculator, system.clone())?;
+
+    let cell = system.cell()?;
+    let species = system.species()?;
+    let positions = system.positions()?;
+    let atoms = (0..species.len()).collect::<Vec<_>>();
+
+    let mut random = Random::new(0x5EED_7F00);
+    for _ in 0..N_TRANSLATIONS {
+        let translation = Vector3D::new(
+            10.0 * (random.next_f64() - 0.5),
+            10.0 * (random.next_f64() - 0.5),
+            10.0 * (random.next_f64() - 0.5),
+        );
+
+        let mut translated = SimpleSystem::new(cell);
+        for (&species, &position) in species.iter().zip(positions) {
+            let mut position = position + translation;
+            if !cell.is_infinite() {
+                // wrap the atom inside the unit cell
+                let fractional = cell.fractional(position);
+                let wrapped = Vector3D::new(
+                    fractional[0] - f64::floor(fractional[0]),
+                    fractional[1] - f64::floor(fractional[1]),
+                    fractional[2] - f64::floor(fractional[2]),
+                );
+                position = cell.cartesian(wrapped);
+            }
+            translated.add_atom(species, position);
+        }
+
+        let translated = compute(calculator, translated)?;
+        check_reordered("translation", &translated, &reference, &atoms, tolerance)?;
+    }
+
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Calculator;
+    use crate::systems::test_utils::test_system;
+
+    #[test]
+    fn soap_power_spectrum() {
+        let mut calculator = Calculator::new("soap_power_spectrum", r#"{
+            "cutoff": 3.5,
+            "max_radial": 4,
+            "max_angular": 4,
+            "atomic_gaussian_width": 0.3,
+            "radial_basis": {"Gto": {}},
+            "cutoff_function": {"ShiftedCosine": {"width": 0.5}},
+            "gradients": true
+        }"#.to_owned()).unwrap();
+
+        super::translation(&mut calculator, &test_system("methane"), 1e-9).unwrap();
+    }
+
+    #[test]
+    fn sorted_distances() {
+        let mut calculator = Calculator::new("sorted_distances", r#"{
+            "cutoff": 3.0,
+            "max_neighbors": 6
+        }"#.to_owned()).unwrap();
+
+        super::translation(&mut calculator, &test_system("water"), 1e-12).unwrap();
+    }
+
+    #[test]
+    fn not_invariant() {
+        // the dummy calculator uses the sum of positions, which is not
+        // translationally invariant
+        let mut calculator = Calculator::new("dummy_calculator", r#"{
+            "cutoff": 3.0,
+            "delta": 0,
+            "name": "",
+            "gradients": false
+        }"#.to_owned()).unwrap();
+
+        let error = super::translation(&mut calculator, &test_system("water"), 1e-9).unwrap_err();
+        assert!(error.to_string().starts_with("internal error: translation check failed: values["));
+    }
+}
@@ -0,0 +1,453 @@
+use std::collections::BTreeMap;
+
+use indexmap::set::IndexSet;
+use ndarray::{Array2, Array3, Axis, s};
+
+use crate::Error;
+use super::{Descriptor, Indexes, IndexesBuilder, IndexValue};
+
+/// A single block in a [`BlockSparseDescriptor`], containing all the samples
+/// associated with one set of values for the block variables.
+#[derive(Clone, Debug)]
+pub struct DescriptorBlock {
+    /// Values taken by the block variables for all samples in this block
+    pub key: Vec<IndexValue>,
+    /// Metadata describing the samples (i.e. rows) in the `values` array. The
+    /// block variables are not part of these samples.
+    pub samples: Indexes,
+    /// An array of size `samples.count()` by `features.count()`, containing
+    /// the values for this block
+    pub values: Array2<f64>,
+    /// Gradients of the values with respect to atomic positions, if the
+    /// initial descriptor contained gradients
+    pub gradients: Option<Array2<f64>>,
+    /// Metadata describing the rows in the `gradients` array. The `sample`
+    /// variable refers to the position of the sample in this block.
+    pub gradients_samples: Option<Indexes>,
+    /// Gradients of the values with respect to hyper-parameters, if the
+    /// initial descriptor contained them. This array has the same layout as
+    /// `Descriptor::hyper_gradients`.
+    pub hyper_gradients: Option<Array3<f64>>,
+}
+
+/// A `BlockSparseDescriptor` stores the values of a [`Descriptor`] as
+/// separate blocks, one for each set of values taken by some sample variables
+/// (typically `species_center` and `species_neighbor`).
+///
+/// Contrary to [`Descriptor::densify`], this does not need to store zeros for
+/// the missing combinations of samples and block variables, which can save a
+/// lot of memory for datasets with many different species. Block-sparse
+/// descriptors can be computed directly with `Calculator::compute_blocks`, or
+/// created from an existing descriptor with [`Descriptor::into_blocks`].
+///
+/// A dense descriptor can be re-created with
+/// [`BlockSparseDescriptor::densify`], but this allocates the full
+/// zero-padded array and should only be used when the consumer of the data
+/// requires it.
+#[derive(Clone, Debug)]
+pub struct BlockSparseDescriptor {
+    /// Name of the variables used to split the samples in blocks
+    variables: Vec<String>,
+    /// Metadata describing the features (i.e. columns) in all blocks
+    features: Indexes,
+    /// JSON pointers to the hyper-parameters corresponding to the first axis
+    /// of the blocks `hyper_gradients`
+    hyper_parameters: Vec<String>,
+    /// All blocks, sorted by key
+    blocks: Vec<DescriptorBlock>,
+}
+
+impl Descriptor {
+    /// Split this descriptor in blocks according to the values taken by the
+    /// given sample `variables`, creating a [`BlockSparseDescriptor`].
+    ///
+    /// Calling `descriptor.into_blocks(variables)?.densify()` gives the same
+    /// values and gradients as `descriptor.densify(variables, None)`, up to
+    /// the order of the samples and gradient samples. Lazy gradients are
+    /// computed before splitting the descriptor.
+    #[time_graph::instrument(name = "Descriptor::into_blocks")]
+    pub fn into_blocks(mut self, variables: &[&str]) -> Result<BlockSparseDescriptor, Error> {
+        self.compute_pending_gradients()?;
+
+        let names = self.samples.names();
+        let mut variables_positions = Vec::new();
+        for variable in variables {
+            if let Some(position) = names.iter().position(|name| name == variable) {
+                variables_positions.push(position);
+            } else {
+                return Err(Error::InvalidParameter(format!(
+                    "can not split along '{}' which is not present in the samples: [{}]",
+                    variable, names.join(", ")
+                )));
+            }
+        }
+
+        let remaining_names = names.iter()
+            .filter(|&name| !variables.contains(name))
+            .copied()
+            .collect::<Vec<_>>();
+
+        // map from block key => (block samples, rows in the initial values)
+        let mut blocks_data = BTreeMap::new();
+        // block key and position in the block of all samples
+        let mut sample_blocks = Vec::with_capacity(self.samples.count());
+        for (sample_i, sample) in self.samples.iter().enumerate() {
+            let key = variables_positions.iter().map(|&i| sample[i]).collect::<Vec<_>>();
+            let remaining = sample.iter()
+                .enumerate()
+                .filter(|(i, _)| !variables_positions.contains(i))
+                .map(|(_, &value)| value)
+                .collect::<Vec<_>>();
+
+            let (builder, rows) = blocks_data.entry(key.clone()).or_insert_with(|| {
+                (IndexesBuilder::new(remaining_names.clone()), Vec::new())
+            });
+            builder.add(&remaining);
+            sample_blocks.push((key, rows.len()));
+            rows.push(sample_i);
+        }
+
+        // map from block key => (block gradients samples, rows in the initial
+        // gradients)
+        let mut gradients_data = BTreeMap::new();
+        if let Some(ref gradients_samples) = self.gradients_samples {
+            for (gradient_sample_i, gradient_sample) in gradients_samples.iter().enumerate() {
+                let (key, position) = &sample_blocks[gradient_sample[0].usize()];
+                let (builder, rows) = gradients_data.entry(key).or_insert_with(|| {
+                    (IndexesBuilder::new(vec!["sample", "atom", "spatial"]), Vec::new())
+                });
+                builder.add(&[IndexValue::from(*position), gradient_sample[1], gradient_sample[2]]);
+                rows.push(gradient_sample_i);
+            }
+        }
+
+        let blocks = blocks_data.into_iter().map(|(key, (builder, rows))| {
+            let (gradients_samples, gradients) = match self.gradients {
+                Some(ref gradients) => {
+                    let (gradients_samples, gradients_rows) = gradients_data.remove(&key).unwrap_or_else(|| {
+                        (IndexesBuilder::new(vec!["sample", "atom", "spatial"]), Vec::new())
+                    });
+                    (Some(gradients_samples.finish()), Some(gradients.select(Axis(0), &gradients_rows)))
+                }
+                None => (None, None),
+            };
+
+            DescriptorBlock {
+                samples: builder.finish(),
+                values: self.values.select(Axis(0), &rows),
+                gradients: gradients,
+                gradients_samples: gradients_samples,
+                hyper_gradients: self.hyper_gradients.as_ref().map(|hyper| hyper.select(Axis(1), &rows)),
+                key: key,
+            }
+        }).collect();
+
+        return Ok(BlockSparseDescriptor {
+            variables: variables.iter().map(|&v| v.to_owned()).collect(),
+            features: self.features,
+            hyper_parameters: self.hyper_parameters,
+            blocks: blocks,
+        });
+    }
+}
+
+impl BlockSparseDescriptor {
+    /// Create an empty block-sparse descriptor, split along the given sample
+    /// `variables` and containing the given `features`
+    pub(crate) fn new(variables: &[&str], features: Indexes) -> BlockSparseDescriptor {
+        BlockSparseDescriptor {
+            variables: variables.iter().map(|&v| v.to_owned()).collect(),
+            features: features,
+            hyper_parameters: Vec::new(),
+            blocks: Vec::new(),
+        }
+    }
+
+    /// Add all the blocks from `other` to this descriptor. Both descriptors
+    /// must be split along the same variables, contain the same features and
+    /// should not share any block key.
+    pub(crate) fn extend(&mut self, other: BlockSparseDescriptor) {
+        assert_eq!(self.variables, other.variables, "mismatched block variables");
+        assert_eq!(self.features, other.features, "mismatched features");
+        debug_assert!(other.blocks.iter().all(|block| self.block(&block.key).is_none()));
+
+        self.hyper_parameters = other.hyper_parameters;
+        self.blocks.extend(other.blocks);
+        self.blocks.sort_by(|a, b| a.key.cmp(&b.key));
+    }
+
+    /// Get the name of the variables used to split the samples in blocks
+    pub fn variables(&self) -> Vec<&str> {
+        self.variables.iter().map(String::as_str).collect()
+    }
+
+    /// Get the metadata describing the features in all blocks
+    pub fn features(&self) -> &Indexes {
+        &self.features
+    }
+
+    /// Get the JSON pointers to the hyper-parameters corresponding to the
+    /// first axis of the blocks `hyper_gradients`
+    pub fn hyper_parameters(&self) -> &[String] {
+        &self.hyper_parameters
+    }
+
+    /// Get all the blocks in this descriptor, sorted by key
+    pub fn blocks(&self) -> &[DescriptorBlock] {
+        &self.blocks
+    }
+
+    /// Get the block corresponding to the given `key`, if any
+    pub fn block(&self, key: &[IndexValue]) -> Option<&DescriptorBlock> {
+        self.blocks.binary_search_by(|block| (&*block.key).cmp(key))
+            .ok()
+            .map(|i| &self.blocks[i])
+    }
+
+    /// Create a dense [`Descriptor`] from this set of blocks, moving the block
+    /// variables to the features in the same way as [`Descriptor::densify`].
+    ///
+    /// The samples (and gradient samples) of the new descriptor are ordered by
+    /// first appearance when going through the blocks in order.
+    #[time_graph::instrument(name = "BlockSparseDescriptor::densify")]
+    pub fn densify(&self) -> Descriptor {
+        let mut feature_names = self.variables();
+        feature_names.extend(self.features.names());
+        let mut features = IndexesBuilder::new(feature_names);
+        for block in &self.blocks {
+            for feature in self.features.iter() {
+                let mut new = block.key.clone();
+                new.extend(feature);
+                features.add(&new);
+            }
+        }
+
+        // we need to use indexmap::IndexSet here to get the new positions of
+        // the samples as we go over the blocks
+        let mut samples = IndexSet::new();
+        let mut positions = Vec::with_capacity(self.blocks.len());
+        for block in &self.blocks {
+            let block_positions = block.samples.iter()
+                .map(|sample| samples.insert_full(sample.to_vec()).0)
+                .collect::<Vec<_>>();
+            positions.push(block_positions);
+        }
+
+        let names = if let Some(block) = self.blocks.first() {
+            block.samples.names()
+        } else {
+            Vec::new()
+        };
+        let mut builder = IndexesBuilder::new(names);
+        for sample in samples {
+            builder.add(&sample);
+        }
+
+        let has_gradients = self.blocks.iter().any(|block| block.gradients.is_some());
+        // same as `positions` for the gradients samples
+        let mut gradients_positions = Vec::with_capacity(self.blocks.len());
+        let mut descriptor = Descriptor::new();
+        if has_gradients {
+            let mut gradients_samples = IndexSet::new();
+            for (block, block_positions) in self.blocks.iter().zip(&positions) {
+                let block_gradients_samples = block.gradients_samples.as_ref().expect("missing gradients samples");
+                let block_gradients_positions = block_gradients_samples.iter()
+                    .map(|gradient_sample| {
+                        let sample = block_positions[gradient_sample[0].usize()];
+                        let (position, _) = gradients_samples.insert_full((sample, gradient_sample[1]));
+                        3 * position + gradient_sample[2].usize()
+                    })
+                    .collect::<Vec<_>>();
+                gradients_positions.push(block_gradients_positions);
+            }
+
+            let mut builder_gradients = IndexesBuilder::new(vec!["sample", "atom", "spatial"]);
+            for (sample, atom) in gradients_samples {
+                builder_gradients.add(&[IndexValue::from(sample), atom, IndexValue::from(0)]);
+                builder_gradients.add(&[IndexValue::from(sample), atom, IndexValue::from(1)]);
+                builder_gradients.add(&[IndexValue::from(sample), atom, IndexValue::from(2)]);
+            }
+            descriptor.prepare_gradients(builder.finish(), builder_gradients.finish(), features.finish());
+        } else {
+            descriptor.prepare(builder.finish(), features.finish());
+        }
+
+        let mut hyper_gradients = self.blocks.first()
+            .and_then(|block| block.hyper_gradients.as_ref())
+            .map(|hyper| Array3::zeros((hyper.shape()[0], descriptor.samples.count(), descriptor.features.count())));
+
+        let block_size = self.features.count();
+        for (block_i, (block, block_positions)) in self.blocks.iter().zip(&positions).enumerate() {
+            let start = block_size * block_i;
+            let stop = block_size * (block_i + 1);
+            for (value, &sample) in block.values.outer_iter().zip(block_positions) {
+                descriptor.values.slice_mut(s![sample, start..stop]).assign(&value);
+            }
+
+            if let Some(ref mut all_gradients) = descriptor.gradients {
+                let gradients = block.gradients.as_ref().expect("missing gradients");
+                for (gradient, &row) in gradients.outer_iter().zip(&gradients_positions[block_i]) {
+                    all_gradients.slice_mut(s![row, start..stop]).assign(&gradient);
+                }
+            }
+
+            if let Some(ref mut all_hyper_gradients) = hyper_gradients {
+                let block_hyper_gradients = block.hyper_gradients.as_ref().expect("missing hyper-parameters gradients");
+                for (i, &sample) in block_positions.iter().enumerate() {
+                    all_hyper_gradients.slice_mut(s![.., sample, start..stop])
+                        .assign(&block_hyper_gradients.slice(s![.., i, ..]));
+                }
+            }
+        }
+
+        if hyper_gradients.is_some() {
+            descriptor.hyper_gradients = hyper_gradients;
+            descriptor.hyper_parameters.clone_from(&self.hyper_parameters);
+        }
+
+        return descriptor;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::{Array3, array};
+
+    use crate::systems::test_utils::test_systems;
+    use crate::descriptor::{Descriptor, IndexesBuilder, IndexValue};
+    use crate::descriptor::{TwoBodiesSpeciesSamples, SamplesBuilder};
+
+    // small helper function to create IndexValue
+    fn v(i: i32) -> IndexValue { IndexValue::from(i) }
+
+    fn water_descriptor() -> Descriptor {
+        let mut systems = test_systems(&["water"]);
+        let samples = TwoBodiesSpeciesSamples::new(3.0).samples(&mut systems).unwrap();
+
+        let mut features = IndexesBuilder::new(vec!["foo", "bar"]);
+        features.add(&[v(0), v(-1)]);
+        features.add(&[v(4), v(-2)]);
+        features.add(&[v(1), v(-5)]);
+
+        let mut descriptor = Descriptor::new();
+        descriptor.prepare(samples, features.finish());
+        descriptor.values.assign(&array![
+            // H channel around O
+            [1.0, 2.0, 3.0],
+            // H channel around H1
+            [4.0, 5.0, 6.0],
+            // O channel around H1
+            [7.0, 8.0, 9.0],
+            // H channel around H2
+            [10.0, 11.0, 12.0],
+            // O channel around H2
+            [13.0, 14.0, 15.0],
+        ]);
+
+        return descriptor;
+    }
+
+    #[test]
+    fn into_blocks() {
+        let blocks = water_descriptor().into_blocks(&["species_neighbor"]).unwrap();
+        assert_eq!(blocks.variables(), ["species_neighbor"]);
+        assert_eq!(blocks.features().names(), ["foo", "bar"]);
+        assert_eq!(blocks.blocks().len(), 2);
+
+        let block = blocks.block(&[v(1)]).unwrap();
+        assert_eq!(block.samples.names(), ["structure", "center", "species_center"]);
+        assert_eq!(block.samples.count(), 3);
+        assert_eq!(block.values, array![
+            [1.0, 2.0, 3.0],
+            [4.0, 5.0, 6.0],
+            [10.0, 11.0, 12.0],
+        ]);
+
+        let block = blocks.block(&[v(123456)]).unwrap();
+        assert_eq!(block.samples.count(), 2);
+        assert_eq!(block.samples[0], [v(0), v(1), v(1)]);
+        assert_eq!(block.values, array![
+            [7.0, 8.0, 9.0],
+            [13.0, 14.0, 15.0],
+        ]);
+
+        assert!(blocks.block(&[v(6)]).is_none());
+
+        let error = water_descriptor().into_blocks(&["not_there"]).unwrap_err();
+        assert_eq!(error.to_string(),
+            "invalid parameter: can not split along 'not_there' which is not \
+            present in the samples: [structure, center, species_center, species_neighbor]"
+        );
+    }
+
+    #[test]
+    fn densify() {
+        let mut expected = water_descriptor();
+        expected.densify(&["species_neighbor"], None).unwrap();
+
+        let densified = water_descriptor()
+            .into_blocks(&["species_neighbor"])
+            .unwrap()
+            .densify();
+
+        assert_eq!(densified.samples, expected.samples);
+        assert_eq!(densified.features, expected.features);
+        assert_eq!(densified.values, expected.values);
+        assert!(densified.gradients.is_none());
+    }
+
+    #[test]
+    fn gradients() {
+        let mut systems = test_systems(&["water"]);
+        let mut descriptor = water_descriptor();
+        let samples = descriptor.samples.clone();
+        let features = descriptor.features.clone();
+        let gradients_samples = TwoBodiesSpeciesSamples::new(3.0)
+            .gradients_for(&mut systems, &samples)
+            .unwrap()
+            .unwrap();
+
+        let values = descriptor.values.clone();
+        descriptor.prepare_gradients(samples, gradients_samples, features);
+        descriptor.values.assign(&values);
+        let gradients = descriptor.gradients.as_mut().unwrap();
+        for (i, gradient) in gradients.iter_mut().enumerate() {
+            *gradient = i as f64;
+        }
+        let mut hyper_gradients = Array3::zeros((2, 5, 3));
+        for (i, hyper_gradient) in hyper_gradients.iter_mut().enumerate() {
+            *hyper_gradient = -(i as f64);
+        }
+        descriptor.hyper_gradients = Some(hyper_gradients);
+        descriptor.hyper_parameters = vec!["/a".into(), "/b".into()];
+
+        let mut expected = descriptor.clone();
+        expected.densify(&["species_neighbor"], None).unwrap();
+
+        let blocks = descriptor.into_blocks(&["species_neighbor"]).unwrap();
+        assert_eq!(blocks.hyper_parameters(), ["/a", "/b"]);
+        let block = blocks.block(&[v(123456)]).unwrap();
+        let gradients_samples = block.gradients_samples.as_ref().unwrap();
+        assert_eq!(gradients_samples.names(), ["sample", "atom", "spatial"]);
+        assert_eq!(block.gradients.as_ref().unwrap().nrows(), gradients_samples.count());
+        assert_eq!(block.hyper_gradients.as_ref().unwrap().shape(), [2, 2, 3]);
+
+        let densified = blocks.densify();
+        assert_eq!(densified.samples, expected.samples);
+        assert_eq!(densified.values, expected.values);
+        assert_eq!(densified.hyper_gradients, expected.hyper_gradients);
+        assert_eq!(densified.hyper_parameters, expected.hyper_parameters);
+
+        // gradients samples can be in a different order
+        let gradients_samples = densified.gradients_samples.as_ref().unwrap();
+        let expected_gradients_samples = expected.gradients_samples.as_ref().unwrap();
+        assert_eq!(gradients_samples.count(), expected_gradients_samples.count());
+
+        let gradients = densified.gradients.as_ref().unwrap();
+        let expected_gradients = expected.gradients.as_ref().unwrap();
+        for (i, gradient_sample) in gradients_samples.iter().enumerate() {
+            let expected_i = expected_gradients_samples.position(gradient_sample).unwrap();
+            assert_eq!(gradients.row(i), expected_gradients.row(expected_i));
+        }
+    }
+}
@@ -0,0 +1,177 @@
+use ndarray::{Array2, Axis};
+use ndarray::parallel::prelude::*;
+
+use crate::Error;
+use super::{Descriptor, Indexes, IndexesBuilder};
+
+/// Number of samples handled together when computing the covariance
+const BLOCK_SIZE: usize = 512;
+
+impl Descriptor {
+    /// Compute the `features.count() x features.count()` Gram matrix `X^T X`
+    /// of the values `X` of this descriptor, summing over all samples.
+    ///
+    /// If `centered` is `true`, the mean of each feature over the samples is
+    /// subtracted from the values first, and the result is then `n_samples`
+    /// times the covariance matrix of the features. This is the first step of
+    /// a principal component analysis of the features.
+    ///
+    /// The samples are split in blocks, and the contributions of the different
+    /// blocks are computed in parallel.
+    #[time_graph::instrument(name = "Descriptor::feature_covariance")]
+    pub fn feature_covariance(&self, centered: bool) -> Array2<f64> {
+        let n_features = self.features.count();
+        let mean = if centered {
+            self.values.mean_axis(Axis(0))
+        } else {
+            None
+        };
+
+        return self.values.axis_chunks_iter(Axis(0), BLOCK_SIZE)
+            .into_par_iter()
+            .map(|block| {
+                if let Some(ref mean) = mean {
+                    let block = &block - mean;
+                    block.t().dot(&block)
+                } else {
+                    block.t().dot(&block)
+                }
+            })
+            .reduce(|| Array2::zeros((n_features, n_features)), |a, b| a + b);
+    }
+
+    /// Select a subset of the features of this descriptor such that the
+    /// absolute value of the Pearson correlation between any pair of selected
+    /// features (over all samples) is at most `threshold`. This can be used to
+    /// remove redundant features, for example to shrink SOAP vectors before
+    /// using them in kernel methods.
+    ///
+    /// The features are considered in order, and a feature is kept if it is
+    /// not correlated above `threshold` with any of the previously kept
+    /// features. Features with the same value for all samples can not be
+    /// correlated with anything, and are always removed. The correlations are
+    /// computed from [`Descriptor::feature_covariance`].
+    ///
+    /// The selected features are returned in the same order as in this
+    /// descriptor, and can be used as `CalculationOptions::selected_features`.
+    #[allow(clippy::float_cmp)]
+    #[time_graph::instrument(name = "Descriptor::uncorrelated_features")]
+    pub fn uncorrelated_features(&self, threshold: f64) -> Result<Indexes, Error> {
+        if !(0.0..=1.0).contains(&threshold) {
+            return Err(Error::InvalidParameter(format!(
+                "the correlation threshold must be between 0 and 1, got {}", threshold
+            )));
+        }
+
+        let covariance = self.feature_covariance(true);
+        let deviations = covariance.diag().mapv(f64::sqrt);
+
+        let mut kept: Vec<usize> = Vec::new();
+        for (feature, column) in self.values.axis_iter(Axis(1)).enumerate() {
+            let constant = match column.iter().next() {
+                Some(first) => column.iter().all(|value| value == first),
+                None => true,
+            };
+            if constant {
+                continue;
+            }
+
+            let correlated = kept.iter().any(|&other| {
+                let correlation = covariance[[feature, other]] / (deviations[feature] * deviations[other]);
+                correlation.abs() > threshold
+            });
+
+            if !correlated {
+                kept.push(feature);
+            }
+        }
+
+        let mut features = IndexesBuilder::new(self.features.names());
+        for feature in kept {
+            features.add(&self.features[feature]);
+        }
+
+        return Ok(features.finish());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+    use ndarray::{Array2, Axis};
+
+    use crate::descriptor::{IndexesBuilder, IndexValue};
+    use crate::Descriptor;
+
+    fn test_descriptor(n_samples: usize) -> Descriptor {
+        let mut samples = IndexesBuilder::new(vec!["sample"]);
+        for i in 0..n_samples {
+            samples.add(&[IndexValue::from(i)]);
+        }
+
+        let mut features = IndexesBuilder::new(vec!["feature"]);
+        for i in 0..3 {
+            features.add(&[IndexValue::from(i)]);
+        }
+
+        let mut descriptor = Descriptor::new();
+        descriptor.prepare(samples.finish(), features.finish());
+        for ((i, j), value) in descriptor.values.indexed_iter_mut() {
+            *value = f64::sin((3 * i + j) as f64) + j as f64;
+        }
+        return descriptor;
+    }
+
+    #[test]
+    fn feature_covariance() {
+        // use more samples than BLOCK_SIZE to check the reduction
+        let descriptor = test_descriptor(1200);
+        let values = &descriptor.values;
+
+        let covariance = descriptor.feature_covariance(false);
+        let expected = values.t().dot(values);
+        for (actual, expected) in covariance.iter().zip(expected.iter()) {
+            assert_relative_eq!(actual, expected, max_relative=1e-12);
+        }
+
+        let covariance = descriptor.feature_covariance(true);
+        let centered = values - &values.mean_axis(Axis(0)).unwrap();
+        let expected = centered.t().dot(&centered);
+        for (actual, expected) in covariance.iter().zip(expected.iter()) {
+            assert_relative_eq!(actual, expected, max_relative=1e-10, epsilon=1e-10);
+        }
+
+        let empty = test_descriptor(0);
+        assert_eq!(empty.feature_covariance(true), Array2::<f64>::zeros((3, 3)));
+    }
+
+    #[test]
+    fn uncorrelated_features() {
+        let mut descriptor = test_descriptor(10);
+        for (i, mut row) in descriptor.values.outer_iter_mut().enumerate() {
+            // feature 1 is anti-correlated with feature 0, and feature 2 is
+            // constant
+            row[0] = f64::sin(i as f64);
+            row[1] = 3.0 - 2.0 * row[0];
+            row[2] = 4.0;
+        }
+
+        let features = descriptor.uncorrelated_features(0.99).unwrap();
+        assert_eq!(features.names(), ["feature"]);
+        assert_eq!(features.iter().collect::<Vec<_>>(), [&[IndexValue::from(0)]]);
+
+        let mut descriptor = test_descriptor(10);
+        let features = descriptor.uncorrelated_features(1.0).unwrap();
+        assert_eq!(features.count(), 3);
+
+        descriptor.values.column_mut(1).fill(-1.0);
+        let features = descriptor.uncorrelated_features(1.0).unwrap();
+        assert_eq!(features.iter().collect::<Vec<_>>(), [&[IndexValue::from(0)], &[IndexValue::from(2)]]);
+
+        let error = descriptor.uncorrelated_features(1.5).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "invalid parameter: the correlation threshold must be between 0 and 1, got 1.5"
+        );
+    }
+}
@@ -1,17 +1,26 @@
-use std::collections::{BTreeSet, BTreeMap};
+use std::borrow::Cow;
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Arc;
 use indexmap::set::IndexSet;
 
 use itertools::Itertools;
-use ndarray::{Array2, ArrayView2, s};
+use ndarray::{Array2, Array3, ArrayView2, Axis, ShapeBuilder, s};
+use ndarray::parallel::prelude::*;
 
 use log::warn;
 
-use crate::{Error};
+use crate::{Error, ErrorContext};
+use crate::calculator::PendingGradients;
 use super::{Indexes, IndexesBuilder, IndexValue};
 
 /// A Descriptor contains the representation of atomistic systems, as computed
 /// by a [`crate::Calculator`].
 #[derive(Clone, Debug)]
+#[derive(serde::Deserialize)]
+#[serde(try_from = "UncheckedDescriptor")]
+// deserialization goes through `UncheckedDescriptor`, which checks the arrays
+// shapes against the indexes
+#[allow(clippy::unsafe_derive_deserialize)]
 pub struct Descriptor {
     /// An array of size `samples.count()` by `features.count()`, containing the
     /// representation of the atomistic systems.
@@ -21,7 +30,8 @@ pub struct Descriptor {
 
     /// An array of size `gradients_samples.count()` by `features.count()`,
     /// containing the gradients of the representation with respect to the
-    /// atomic positions.
+    /// atomic positions. When using `CalculationOptions::lazy_gradients`, this
+    /// is only set after a call to [`Descriptor::gradients()`].
     pub gradients: Option<Array2<f64>>,
     /// Metadata describing the samples (i.e. rows) in the `gradients` array
     pub gradients_samples: Option<Indexes>,
@@ -29,6 +39,201 @@ pub struct Descriptor {
     /// Metadata describing the features (i.e. columns) in both the `values` and
     /// `gradients` array
     pub features: Indexes,
+
+    /// An array of size `hyper_parameters.len()` by `samples.count()` by
+    /// `features.count()`, containing the gradients of the representation with
+    /// respect to the continuous hyper-parameters of the calculator. This is
+    /// only set when using `CalculationOptions::hyper_gradients`.
+    pub hyper_gradients: Option<Array3<f64>>,
+    /// JSON pointers to the hyper-parameters in the calculator parameters
+    /// corresponding to the first axis of the `hyper_gradients` array
+    pub hyper_parameters: Vec<String>,
+
+    /// Memory layout used for the `values` and `gradients` arrays
+    pub(crate) layout: MemoryLayout,
+    /// Data used to compute the gradients on first access when using
+    /// `CalculationOptions::lazy_gradients`
+    pub(crate) pending_gradients: Option<Arc<PendingGradients>>,
+}
+
+/// Serialization of a `Descriptor`. Lazy gradients are computed (on a copy of
+/// the descriptor) before serializing it, in the same way as in
+/// `Descriptor::save` and `Descriptor::save_cbor`.
+impl serde::Serialize for Descriptor {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let descriptor = self.with_computed_gradients().map_err(serde::ser::Error::custom)?;
+        let data = SerializedDescriptor {
+            values: &descriptor.values,
+            samples: &descriptor.samples,
+            gradients: descriptor.gradients.as_ref(),
+            gradients_samples: descriptor.gradients_samples.as_ref(),
+            features: &descriptor.features,
+            hyper_gradients: descriptor.hyper_gradients.as_ref(),
+            hyper_parameters: &descriptor.hyper_parameters,
+        };
+        return data.serialize(serializer);
+    }
+}
+
+/// Data serialized for a `Descriptor`, mirroring `UncheckedDescriptor`
+#[derive(serde::Serialize)]
+struct SerializedDescriptor<'a> {
+    values: &'a Array2<f64>,
+    samples: &'a Indexes,
+    gradients: Option<&'a Array2<f64>>,
+    gradients_samples: Option<&'a Indexes>,
+    features: &'a Indexes,
+    hyper_gradients: Option<&'a Array3<f64>>,
+    hyper_parameters: &'a [String],
+}
+
+/// Memory layout of the `values` and `gradients` arrays in a [`Descriptor`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryLayout {
+    /// Row-major (C) layout, where all the features of a given sample are
+    /// contiguous in memory. This is the default layout.
+    RowMajor,
+    /// Column-major (Fortran) layout, where all the samples of a given feature
+    /// are contiguous in memory. This is better suited to operations working
+    /// on one feature at the time, such as per-feature statistics.
+    FeatureMajor,
+}
+
+/// Deserialized data for a `Descriptor`, before checking the consistency of
+/// the arrays shapes with the indexes
+#[derive(serde::Deserialize)]
+struct UncheckedDescriptor {
+    values: Array2<f64>,
+    samples: Indexes,
+    gradients: Option<Array2<f64>>,
+    gradients_samples: Option<Indexes>,
+    features: Indexes,
+    #[serde(default)]
+    hyper_gradients: Option<Array3<f64>>,
+    #[serde(default)]
+    hyper_parameters: Vec<String>,
+}
+
+impl std::convert::TryFrom<UncheckedDescriptor> for Descriptor {
+    type Error = Error;
+
+    fn try_from(unchecked: UncheckedDescriptor) -> Result<Descriptor, Error> {
+        let shape = [unchecked.samples.count(), unchecked.features.count()];
+        if unchecked.values.shape() != shape {
+            return Err(Error::InvalidParameter(format!(
+                "invalid descriptor: values have shape {:?}, but samples and features require {:?}",
+                unchecked.values.shape(), shape
+            )));
+        }
+
+        match (&unchecked.gradients, &unchecked.gradients_samples) {
+            (Some(gradients), Some(gradients_samples)) => {
+                let shape = [gradients_samples.count(), unchecked.features.count()];
+                if gradients.shape() != shape {
+                    return Err(Error::InvalidParameter(format!(
+                        "invalid descriptor: gradients have shape {:?}, but gradients samples and features require {:?}",
+                        gradients.shape(), shape
+                    )));
+                }
+
+                check_gradients_samples(gradients_samples, unchecked.samples.count())?;
+            }
+            (None, None) => {}
+            _ => {
+                return Err(Error::InvalidParameter(
+                    "invalid descriptor: gradients and gradients samples must be both present or both missing".into()
+                ));
+            }
+        }
+
+        match &unchecked.hyper_gradients {
+            Some(hyper_gradients) => {
+                let shape = [unchecked.hyper_parameters.len(), unchecked.samples.count(), unchecked.features.count()];
+                if hyper_gradients.shape() != shape {
+                    return Err(Error::InvalidParameter(format!(
+                        "invalid descriptor: hyper-parameters gradients have shape {:?}, but hyper-parameters, samples and features require {:?}",
+                        hyper_gradients.shape(), shape
+                    )));
+                }
+            }
+            None => {
+                if !unchecked.hyper_parameters.is_empty() {
+                    return Err(Error::InvalidParameter(
+                        "invalid descriptor: hyper-parameters are present without the corresponding gradients".into()
+                    ));
+                }
+            }
+        }
+
+        return Ok(Descriptor {
+            values: unchecked.values,
+            samples: unchecked.samples,
+            gradients: unchecked.gradients,
+            gradients_samples: unchecked.gradients_samples,
+            features: unchecked.features,
+            hyper_gradients: unchecked.hyper_gradients,
+            hyper_parameters: unchecked.hyper_parameters,
+            layout: MemoryLayout::RowMajor,
+            pending_gradients: None,
+        });
+    }
+}
+
+/// Move the hyper-parameters gradients to the `new_positions` of the
+/// corresponding values after a call to `Descriptor::densify`, filling missing
+/// entries with zeros.
+fn densify_hyper_gradients(
+    hyper_gradients: &Array3<f64>,
+    new_positions: &[Option<DensifiedPosition>],
+    shape: (usize, usize),
+    feature_block_size: usize,
+) -> Array3<f64> {
+    let mut new_hyper_gradients = Array3::zeros((hyper_gradients.shape()[0], shape.0, shape.1));
+    for (old_sample, new_position) in new_positions.iter().enumerate() {
+        if let Some(new_position) = new_position {
+            let start = feature_block_size * new_position.features_block;
+            let stop = feature_block_size * (new_position.features_block + 1);
+
+            let value = hyper_gradients.slice(s![.., old_sample, ..]);
+            new_hyper_gradients.slice_mut(s![.., new_position.sample, start..stop]).assign(&value);
+        }
+    }
+
+    return new_hyper_gradients;
+}
+
+/// Check that the `gradients_samples` refer to existing samples and spatial
+/// dimensions. This is used to validate descriptors coming from outside of
+/// rascaline before using the values in the gradients samples as indexes.
+fn check_gradients_samples(gradients_samples: &Indexes, samples_count: usize) -> Result<(), Error> {
+    if gradients_samples.names() != ["sample", "atom", "spatial"] {
+        return Err(Error::InvalidParameter(format!(
+            "invalid descriptor: gradients samples names should be [sample, atom, spatial], got [{}]",
+            gradients_samples.names().join(", ")
+        )));
+    }
+
+    for gradient_sample in gradients_samples {
+        let sample = gradient_sample[0].try_usize().context("invalid descriptor: invalid sample in gradients samples")?;
+        if sample >= samples_count {
+            return Err(Error::InvalidParameter(format!(
+                "invalid descriptor: gradients samples refer to sample {}, but there are only {} samples",
+                sample, samples_count
+            )));
+        }
+
+        gradient_sample[1].try_usize().context("invalid descriptor: invalid atom in gradients samples")?;
+
+        let spatial = gradient_sample[2].try_usize().context("invalid descriptor: invalid spatial in gradients samples")?;
+        if spatial >= 3 {
+            return Err(Error::InvalidParameter(format!(
+                "invalid descriptor: spatial index in gradients samples must be 0, 1 or 2, got {}",
+                spatial
+            )));
+        }
+    }
+
+    return Ok(());
 }
 
 impl Default for Descriptor {
@@ -45,7 +250,122 @@ impl Descriptor {
             features: indexes,
             gradients: None,
             gradients_samples: None,
+            hyper_gradients: None,
+            hyper_parameters: Vec::new(),
+            layout: MemoryLayout::RowMajor,
+            pending_gradients: None,
+        }
+    }
+
+    /// Get the memory layout of the `values` and `gradients` arrays in this
+    /// descriptor.
+    pub fn layout(&self) -> MemoryLayout {
+        self.layout
+    }
+
+    /// Set the memory layout of the `values` and `gradients` arrays in this
+    /// descriptor, converting the existing arrays if needed.
+    ///
+    /// The layout is kept by all subsequent calls to [`Descriptor::prepare`]
+    /// and [`Descriptor::prepare_gradients`], and thus by all calculations
+    /// using this descriptor. The hyper-parameters gradients always use a
+    /// row-major layout.
+    pub fn set_layout(&mut self, layout: MemoryLayout) {
+        self.layout = layout;
+
+        let values = std::mem::replace(&mut self.values, Array2::zeros((0, 0)));
+        self.values = with_layout(values, layout);
+
+        if let Some(gradients) = self.gradients.take() {
+            self.gradients = Some(with_layout(gradients, layout));
+        }
+    }
+
+    /// Get the gradients of the representation with respect to the atomic
+    /// positions, if any.
+    ///
+    /// If this descriptor was computed with
+    /// `CalculationOptions::lazy_gradients`, the gradients are computed on the
+    /// first call to this function, for the samples and features currently in
+    /// the descriptor, and stored in `gradients` and `gradients_samples`.
+    /// Otherwise, this gives the same result as `self.gradients.as_ref()`.
+    pub fn gradients(&mut self) -> Result<Option<&Array2<f64>>, Error> {
+        self.compute_pending_gradients()?;
+        return Ok(self.gradients.as_ref());
+    }
+
+    /// Compute the gradients of this descriptor if they were not computed yet
+    /// because of `CalculationOptions::lazy_gradients`. All the functions
+    /// modifying the samples, features or values of a descriptor must call
+    /// this first, since the lazy gradients are computed for the samples and
+    /// features in the descriptor.
+    pub(crate) fn compute_pending_gradients(&mut self) -> Result<(), Error> {
+        if let Some(pending) = self.pending_gradients.clone() {
+            let (gradients_samples, gradients) = pending.compute(self)?;
+            self.gradients_samples = Some(gradients_samples);
+            self.gradients = Some(gradients);
+            self.pending_gradients = None;
         }
+        return Ok(());
+    }
+
+    /// Get a version of this descriptor with the lazy gradients computed,
+    /// only cloning the descriptor if there are lazy gradients.
+    pub(crate) fn with_computed_gradients(&self) -> Result<Cow<'_, Descriptor>, Error> {
+        if self.pending_gradients.is_none() {
+            return Ok(Cow::Borrowed(self));
+        }
+
+        let mut descriptor = self.clone();
+        descriptor.compute_pending_gradients()?;
+        return Ok(Cow::Owned(descriptor));
+    }
+
+    /// Return an error if this descriptor has gradients which were not
+    /// computed yet because of `CalculationOptions::lazy_gradients`, for
+    /// functions reading the gradients without being able to compute them.
+    pub(crate) fn check_no_pending_gradients(&self, function: &str) -> Result<(), Error> {
+        if self.pending_gradients.is_some() {
+            return Err(Error::InvalidParameter(format!(
+                "the gradients of this descriptor have not been computed yet, call Descriptor::gradients() before {}",
+                function
+            )));
+        }
+        return Ok(());
+    }
+
+    /// Save this descriptor (values, gradients and all the indexes) to the
+    /// given `writer`. The descriptor can then be loaded back with
+    /// `Descriptor::load`.
+    ///
+    /// Lazy gradients are computed before saving the descriptor.
+    pub fn save<W: std::io::Write>(&self, writer: W) -> Result<(), Error> {
+        serde_json::to_writer(writer, &*self.with_computed_gradients()?)?;
+        return Ok(());
+    }
+
+    /// Load a descriptor previously saved with `Descriptor::save` from the
+    /// given `reader`.
+    pub fn load<R: std::io::Read>(reader: R) -> Result<Descriptor, Error> {
+        let descriptor = serde_json::from_reader(reader)?;
+        return Ok(descriptor);
+    }
+
+    /// Save this descriptor to the given `writer`, using the compact binary
+    /// [CBOR](https://cbor.io/) format instead of JSON. The descriptor can
+    /// then be loaded back with `Descriptor::load_cbor`.
+    ///
+    /// Lazy gradients are computed before saving the descriptor.
+    pub fn save_cbor<W: std::io::Write>(&self, writer: W) -> Result<(), Error> {
+        serde_cbor::to_writer(writer, &*self.with_computed_gradients()?)?;
+        return Ok(());
+    }
+
+    /// Load a descriptor previously saved with `Descriptor::save_cbor` from
+    /// the given `reader`.
+    pub fn load_cbor<R: std::io::Read>(reader: R) -> Result<Descriptor, Error> {
+        let descriptor = serde_cbor::from_reader(reader)?;
+        return Ok(descriptor);
     }
 
     /// Make this descriptor dense along the given `variables`.
@@ -110,6 +430,7 @@ impl Descriptor {
         variables: &[&str],
         requested: impl Into<Option<ArrayView2<'a, IndexValue>>>,
     ) -> Result<(), Error> {
+        self.compute_pending_gradients()?;
         self.densify_impl(variables, requested, /*do_gradient*/ true)?;
         return Ok(());
     }
@@ -132,9 +453,42 @@ impl Descriptor {
         variables: &[&str],
         requested: impl Into<Option<ArrayView2<'a, IndexValue>>>,
     ) -> Result<DensifiedPositions, Error> {
+        // the returned positions refer to the samples before densification,
+        // which are the ones needed to compute lazy gradients
+        self.compute_pending_gradients()?;
         self.densify_impl(variables, requested, /*do_gradient*/ false)
     }
 
+    /// Get the norm of each row of the values after densifying this
+    /// descriptor along the given `variables`, without creating the dense
+    /// values array.
+    ///
+    /// The features blocks coming from different samples do not overlap, so
+    /// the squared norm of a new sample is the sum of the squared norms of the
+    /// old samples mapped to it. Grouping the old samples by new sample makes
+    /// this linear in the number of samples.
+    #[time_graph::instrument(name="Descriptor::densified_norms")]
+    pub fn densified_norms(&self, variables: &[&str]) -> Result<Vec<f64>, Error> {
+        let squared_norms = self.values.axis_iter(Axis(0))
+            .into_par_iter()
+            .map(|row| row.dot(&row))
+            .collect::<Vec<_>>();
+
+        if variables.is_empty() || self.features.size() == 0 {
+            return Ok(squared_norms.iter().map(|norm| norm.sqrt()).collect());
+        }
+
+        let updated_samples = remove_from_samples(&self.samples, variables, None)?;
+        let mut new_squared_norms = vec![0.0; updated_samples.samples.count()];
+        for (old_sample, new_position) in updated_samples.new_positions.iter().enumerate() {
+            if let Some(new_position) = new_position {
+                new_squared_norms[new_position.sample] += squared_norms[old_sample];
+            }
+        }
+
+        return Ok(new_squared_norms.iter().map(|norm| norm.sqrt()).collect());
+    }
+
     /// Common implementation of `densify` & `densify_value`. This function
     /// returns the vector of new positions for values if `do_gradient` is
     /// false.
@@ -189,17 +543,25 @@ impl Descriptor {
 
         let feature_block_size = self.features.count();
         // copy values themselves as needed
-        let mut new_values = Array2::zeros((updated_samples.samples.count(), new_features_count));
+        let mut rows_mapping = vec![Vec::new(); updated_samples.samples.count()];
         for (old_sample, new_position) in updated_samples.new_positions.iter().enumerate() {
             if let Some(new_position) = new_position {
-                let start = feature_block_size * new_position.features_block;
-                let stop = feature_block_size * (new_position.features_block + 1);
-
-                let value = self.values.slice(s![old_sample, ..]);
-                new_values.slice_mut(s![new_position.sample, start..stop]).assign(&value);
+                rows_mapping[new_position.sample].push((old_sample, new_position.features_block));
             }
         }
 
+        let mut new_values = zeros((updated_samples.samples.count(), new_features_count), self.layout);
+        copy_densified_rows(&self.values, &mut new_values, &rows_mapping, feature_block_size);
+
+        if let Some(ref mut hyper_gradients) = self.hyper_gradients {
+            *hyper_gradients = densify_hyper_gradients(
+                hyper_gradients,
+                &updated_samples.new_positions,
+                (updated_samples.samples.count(), new_features_count),
+                feature_block_size,
+            );
+        }
+
         self.features = new_features;
         self.samples = updated_samples.samples;
         self.values = new_values;
@@ -208,7 +570,7 @@ impl Descriptor {
             return Ok(updated_samples.new_positions);
         }
 
-        if let Some(ref mut gradients) = self.gradients {
+        if let Some(ref gradients) = self.gradients {
             let gradients_samples = self.gradients_samples.as_ref().expect("missing gradients samples");
 
             // we need to use indexmap::IndexSet here to get the new positions
@@ -225,10 +587,7 @@ impl Descriptor {
                 }
             }
 
-            let mut new_gradients = Array2::zeros(
-                (3 * new_gradient_samples.len(), new_features_count)
-            );
-
+            let mut rows_mapping = vec![Vec::new(); 3 * new_gradient_samples.len()];
             for (old_grad_sample_i, gradient_sample) in gradients_samples.iter().enumerate() {
                 let sample = gradient_sample[0].usize();
                 let atom = gradient_sample[1];
@@ -239,15 +598,16 @@ impl Descriptor {
                         &(IndexValue::from(position.sample), atom)
                     ).expect("missing entry in new gradient samples");
                     let new_grad_position = 3 * new_grad_sample_i + spatial;
-
-                    let start = feature_block_size * position.features_block;
-                    let stop = feature_block_size * (position.features_block + 1);
-
-                    let value = gradients.slice(s![old_grad_sample_i, ..]);
-                    new_gradients.slice_mut(s![new_grad_position, start..stop]).assign(&value);
+                    rows_mapping[new_grad_position].push((old_grad_sample_i, position.features_block));
                 }
             }
 
+            let mut new_gradients = zeros(
+                (3 * new_gradient_samples.len(), new_features_count),
+                self.layout,
+            );
+            copy_densified_rows(gradients, &mut new_gradients, &rows_mapping, feature_block_size);
+
             let mut builder = IndexesBuilder::new(vec!["sample", "atom", "spatial"]);
             for (sample, atom) in new_gradient_samples {
                 builder.add(&[sample, atom, IndexValue::from(0)]);
@@ -272,10 +632,13 @@ impl Descriptor {
 
         // resize the 'values' array if needed, and set the requested initial value
         let shape = (self.samples.count(), self.features.count());
-        resize_and_reset(&mut self.values, shape);
+        resize_and_reset(&mut self.values, shape, self.layout);
 
         self.gradients = None;
         self.gradients_samples = None;
+        self.hyper_gradients = None;
+        self.hyper_parameters.clear();
+        self.pending_gradients = None;
     }
 
     /// Initialize this descriptor with the given `samples`, `gradients_samples`
@@ -297,23 +660,74 @@ impl Descriptor {
 
         // resize the 'values' array if needed, and set the requested initial value
         let shape = (self.samples.count(), self.features.count());
-        resize_and_reset(&mut self.values, shape);
+        resize_and_reset(&mut self.values, shape, self.layout);
+
+        self.hyper_gradients = None;
+        self.hyper_parameters.clear();
+        self.pending_gradients = None;
 
         let gradient_shape = (gradients_samples.count(), self.features.count());
         self.gradients_samples = Some(gradients_samples);
 
         if let Some(array) = &mut self.gradients {
             // resize the 'gradient' array if needed, and set the requested initial value
-            resize_and_reset(array, gradient_shape);
+            resize_and_reset(array, gradient_shape, self.layout);
         } else {
             // create a new gradient array
-            let array = Array2::from_elem(gradient_shape, 0.0);
+            let array = zeros(gradient_shape, self.layout);
             self.gradients = Some(array);
         }
     }
 }
 
-fn resize_and_reset(array: &mut Array2<f64>, shape: (usize, usize)) {
+/// Copy the rows of `old` into `new` when densifying a descriptor.
+/// `rows_mapping[i]` contains the list of `(old_row, features_block)` which
+/// should be copied in row `i` of `new`, inside the corresponding block of
+/// `feature_block_size` features. The rows of `new` are filled in parallel.
+fn copy_densified_rows(
+    old: &Array2<f64>,
+    new: &mut Array2<f64>,
+    rows_mapping: &[Vec<(usize, usize)>],
+    feature_block_size: usize,
+) {
+    new.axis_iter_mut(Axis(0))
+        .into_par_iter()
+        .zip(rows_mapping)
+        .for_each(|(mut row, mapping)| {
+            for &(old_row, features_block) in mapping {
+                let start = feature_block_size * features_block;
+                let stop = feature_block_size * (features_block + 1);
+                row.slice_mut(s![start..stop]).assign(&old.slice(s![old_row, ..]));
+            }
+        });
+}
+
+/// Create a new array full of zeros with the given `shape` and `layout`
+fn zeros(shape: (usize, usize), layout: MemoryLayout) -> Array2<f64> {
+    match layout {
+        MemoryLayout::RowMajor => Array2::zeros(shape),
+        MemoryLayout::FeatureMajor => Array2::zeros(shape.f()),
+    }
+}
+
+/// Get an array with the same content as `array`, and the given `layout`. This
+/// does not copy the data if `array` already uses the right layout.
+pub(crate) fn with_layout(array: Array2<f64>, layout: MemoryLayout) -> Array2<f64> {
+    let is_right_layout = match layout {
+        MemoryLayout::RowMajor => array.is_standard_layout(),
+        MemoryLayout::FeatureMajor => array.t().is_standard_layout(),
+    };
+
+    if is_right_layout {
+        return array;
+    }
+
+    let mut converted = zeros(array.dim(), layout);
+    converted.assign(&array);
+    return converted;
+}
+
+fn resize_and_reset(array: &mut Array2<f64>, shape: (usize, usize), layout: MemoryLayout) {
     // extract data by replacing array with a temporary value
     let mut tmp = Array2::zeros((0, 0));
     std::mem::swap(array, &mut tmp);
@@ -321,7 +735,10 @@ fn resize_and_reset(array: &mut Array2<f64>, shape: (usize, usize)) {
     let mut data = tmp.into_raw_vec();
     data.resize(shape.0 * shape.1, 0.0);
 
-    let mut values = Array2::from_shape_vec(shape, data).expect("wrong array shape");
+    let mut values = match layout {
+        MemoryLayout::RowMajor => Array2::from_shape_vec(shape, data),
+        MemoryLayout::FeatureMajor => Array2::from_shape_vec(shape.f(), data),
+    }.expect("wrong array shape");
     values.fill(0.0);
     let _replaced = std::mem::replace(array, values);
 }
@@ -434,11 +851,12 @@ fn remove_from_samples(
         new_features
     };
 
-    // map from new feature => feature block index
+    // map from new feature => feature block index, using a hash map to make
+    // the lookup for each sample below O(1)
     let features_blocks = features.iter()
         .enumerate()
         .map(|(i, v)| (v, i))
-        .collect::<BTreeMap<_, _>>();
+        .collect::<HashMap<_, _>>();
 
     // build the new samples & the mapping from old to new samples
     let mut new_positions = DensifiedPositions::new(samples.count());
@@ -498,6 +916,7 @@ mod tests {
     use crate::systems::test_utils::test_systems;
     use crate::descriptor::{TwoBodiesSpeciesSamples, StructureSpeciesSamples, SamplesBuilder};
     use ndarray::array;
+    use approx::assert_relative_eq;
 
     fn dummy_features() -> Indexes {
         let mut features = IndexesBuilder::new(vec!["foo", "bar"]);
@@ -545,6 +964,107 @@ mod tests {
         assert_eq!(gradients.shape(), [gradients_samples.count(), descriptor.features.count()]);
     }
 
+    #[test]
+    fn save_and_load() {
+        let mut descriptor = Descriptor::new();
+
+        let mut systems = test_systems(&["water", "CH"]);
+        let features = dummy_features();
+        let (samples, gradients) = StructureSpeciesSamples.with_gradients(&mut systems).unwrap();
+        descriptor.prepare_gradients(samples, gradients.unwrap(), features);
+        descriptor.values.mapv_inplace(|_| 1.0 / 3.0);
+        descriptor.gradients.as_mut().unwrap().mapv_inplace(|_| -1.0 / 7.0);
+
+        let mut buffer = Vec::new();
+        descriptor.save(&mut buffer).unwrap();
+        let loaded = Descriptor::load(buffer.as_slice()).unwrap();
+
+        assert_eq!(loaded.values, descriptor.values);
+        assert_eq!(loaded.samples, descriptor.samples);
+        assert_eq!(loaded.gradients, descriptor.gradients);
+        assert_eq!(loaded.gradients_samples, descriptor.gradients_samples);
+        assert_eq!(loaded.features, descriptor.features);
+
+        // inconsistent shapes are rejected
+        let mut json = serde_json::to_value(&descriptor).unwrap();
+        json["features"]["values"] = serde_json::json!([0, 0]);
+        let error = serde_json::from_value::<Descriptor>(json).unwrap_err();
+        assert!(error.to_string().starts_with("invalid parameter: invalid descriptor: values have shape [4, 3]"));
+
+        // gradients samples must refer to existing samples
+        let mut json = serde_json::to_value(&descriptor).unwrap();
+        json["gradients_samples"]["values"][0] = serde_json::json!(-1);
+        let error = serde_json::from_value::<Descriptor>(json).unwrap_err();
+        assert_eq!(error.to_string(),
+            "invalid descriptor: invalid sample in gradients samples: \
+            invalid parameter: expected a non-negative index value, got -1"
+        );
+
+        let mut json = serde_json::to_value(&descriptor).unwrap();
+        json["gradients_samples"]["values"][0] = serde_json::json!(4);
+        let error = serde_json::from_value::<Descriptor>(json).unwrap_err();
+        assert_eq!(error.to_string(),
+            "invalid parameter: invalid descriptor: gradients samples refer to \
+            sample 4, but there are only 4 samples"
+        );
+
+        let mut json = serde_json::to_value(&descriptor).unwrap();
+        json["gradients_samples"]["values"][2] = serde_json::json!(3);
+        let error = serde_json::from_value::<Descriptor>(json).unwrap_err();
+        assert_eq!(error.to_string(),
+            "invalid parameter: invalid descriptor: spatial index in gradients \
+            samples must be 0, 1 or 2, got 3"
+        );
+
+        // hyper-parameters gradients
+        descriptor.hyper_gradients = Some(ndarray::Array3::from_elem((1, 4, 3), 2.5));
+        descriptor.hyper_parameters = vec!["/width".into()];
+
+        let mut buffer = Vec::new();
+        descriptor.save(&mut buffer).unwrap();
+        let loaded = Descriptor::load(buffer.as_slice()).unwrap();
+        assert_eq!(loaded.hyper_gradients, descriptor.hyper_gradients);
+        assert_eq!(loaded.hyper_parameters, descriptor.hyper_parameters);
+
+        let mut json = serde_json::to_value(&descriptor).unwrap();
+        json["hyper_parameters"] = serde_json::json!(["/width", "/other"]);
+        let error = serde_json::from_value::<Descriptor>(json).unwrap_err();
+        assert_eq!(error.to_string(),
+            "invalid parameter: invalid descriptor: hyper-parameters gradients \
+            have shape [1, 4, 3], but hyper-parameters, samples and features \
+            require [2, 4, 3]"
+        );
+    }
+
+    #[test]
+    fn save_and_load_cbor() {
+        let mut descriptor = Descriptor::new();
+
+        let mut systems = test_systems(&["water", "CH"]);
+        let features = dummy_features();
+        let (samples, gradients) = StructureSpeciesSamples.with_gradients(&mut systems).unwrap();
+        descriptor.prepare_gradients(samples, gradients.unwrap(), features);
+        descriptor.values.mapv_inplace(|_| 1.0 / 3.0);
+        descriptor.gradients.as_mut().unwrap().mapv_inplace(|_| -1.0 / 7.0);
+
+        let mut buffer = Vec::new();
+        descriptor.save_cbor(&mut buffer).unwrap();
+        let loaded = Descriptor::load_cbor(buffer.as_slice()).unwrap();
+
+        assert_eq!(loaded.values, descriptor.values);
+        assert_eq!(loaded.samples, descriptor.samples);
+        assert_eq!(loaded.gradients, descriptor.gradients);
+        assert_eq!(loaded.gradients_samples, descriptor.gradients_samples);
+        assert_eq!(loaded.features, descriptor.features);
+
+        let mut json = Vec::new();
+        descriptor.save(&mut json).unwrap();
+        assert!(buffer.len() < json.len());
+
+        let error = Descriptor::load_cbor(&buffer[..10]).unwrap_err();
+        assert!(error.to_string().starts_with("cbor error: "));
+    }
+
     #[test]
     fn densify() {
         let mut descriptor = Descriptor::new();
@@ -801,6 +1321,31 @@ mod tests {
         ]);
     }
 
+    #[test]
+    fn densified_norms() {
+        let mut descriptor = Descriptor::new();
+
+        let mut systems = test_systems(&["water", "CH"]);
+        let samples = TwoBodiesSpeciesSamples::new(3.0).samples(&mut systems).unwrap();
+        descriptor.prepare(samples, dummy_features());
+        for ((i, j), value) in descriptor.values.indexed_iter_mut() {
+            *value = f64::sin((3 * i + j) as f64);
+        }
+
+        let norms = descriptor.densified_norms(&["species_neighbor"]).unwrap();
+
+        descriptor.densify(&["species_neighbor"], None).unwrap();
+        assert_eq!(norms.len(), descriptor.samples.count());
+        for (norm, row) in norms.iter().zip(descriptor.values.axis_iter(Axis(0))) {
+            assert_relative_eq!(*norm, row.dot(&row).sqrt(), max_relative=1e-12);
+        }
+
+        let norms = descriptor.densified_norms(&[]).unwrap();
+        for (norm, row) in norms.iter().zip(descriptor.values.axis_iter(Axis(0))) {
+            assert_relative_eq!(*norm, row.dot(&row).sqrt(), max_relative=1e-12);
+        }
+    }
+
     #[test]
     fn densify_values() {
         let mut descriptor = Descriptor::new();
@@ -891,4 +1436,41 @@ mod tests {
 
         assert_eq!(dense_gradients, reference_gradients);
     }
+
+    #[test]
+    fn memory_layout() {
+        let mut systems = test_systems(&["water"]);
+        let (samples, gradients) = TwoBodiesSpeciesSamples::new(3.0).with_gradients(&mut systems).unwrap();
+        let gradients = gradients.unwrap();
+
+        let mut reference = Descriptor::new();
+        reference.prepare_gradients(samples.clone(), gradients.clone(), dummy_features());
+        assert_eq!(reference.layout(), MemoryLayout::RowMajor);
+        reference.values.indexed_iter_mut().for_each(|((i, j), v)| *v = (3 * i + j) as f64);
+        reference.gradients.as_mut().unwrap().indexed_iter_mut().for_each(|((i, j), v)| *v = -((3 * i + j) as f64));
+
+        let mut descriptor = Descriptor::new();
+        descriptor.set_layout(MemoryLayout::FeatureMajor);
+        descriptor.prepare_gradients(samples, gradients, dummy_features());
+        assert_eq!(descriptor.layout(), MemoryLayout::FeatureMajor);
+        assert_eq!(descriptor.values.strides(), [1, 5]);
+        assert_eq!(descriptor.gradients.as_ref().unwrap().strides(), [1, 33]);
+
+        descriptor.values.assign(&reference.values);
+        descriptor.gradients.as_mut().unwrap().assign(reference.gradients.as_ref().unwrap());
+
+        // densify keeps the layout
+        reference.densify(&["species_neighbor"], None).unwrap();
+        descriptor.densify(&["species_neighbor"], None).unwrap();
+        assert_eq!(descriptor.values, reference.values);
+        assert_eq!(descriptor.gradients, reference.gradients);
+        assert_eq!(descriptor.values.strides(), [1, 3]);
+
+        // converting back to row-major
+        descriptor.set_layout(MemoryLayout::RowMajor);
+        assert!(descriptor.values.is_standard_layout());
+        assert!(descriptor.gradients.as_ref().unwrap().is_standard_layout());
+        assert_eq!(descriptor.values, reference.values);
+        assert_eq!(descriptor.gradients, reference.gradients);
+    }
 }
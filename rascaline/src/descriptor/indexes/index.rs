@@ -1,3 +1,4 @@
+use std::convert::TryFrom;
 use std::ffi::{CString, CStr};
 use std::collections::{BTreeSet, HashMap};
 use std::hash::BuildHasherDefault;
@@ -57,6 +58,10 @@ impl Clone for ConstCString {
 unsafe impl Sync for ConstCString {}
 
 
+/// A single value inside an index, stored as an `i32`. This type has the same
+/// memory layout as `i32`, so buffers of `i32` (e.g. coming from the C API) can
+/// be reinterpreted as buffers of `IndexValue`.
+#[repr(transparent)]
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct IndexValue(i32);
 
@@ -100,12 +105,21 @@ impl From<isize> for IndexValue {
 }
 
 impl IndexValue {
+    /// Get this value as a `usize`. The value must not be negative, use
+    /// `IndexValue::try_usize` for values coming from outside of rascaline.
     #[allow(clippy::cast_sign_loss)]
     pub fn usize(self) -> usize {
         debug_assert!(self.0 >= 0);
         self.0 as usize
     }
 
+    /// Get this value as a `usize`, returning an error if the value is negative
+    pub fn try_usize(self) -> Result<usize, Error> {
+        return usize::try_from(self.0).map_err(|_| Error::InvalidParameter(format!(
+            "expected a non-negative index value, got {}", self.0
+        )));
+    }
+
     pub fn isize(self) -> isize {
         self.0 as isize
     }
@@ -333,6 +347,67 @@ impl std::ops::Index<usize> for Indexes {
     }
 }
 
+/// Serialized representation of `Indexes`, with the values stored as a
+/// linearized 2D array in row-major order
+#[derive(serde::Serialize, serde::Deserialize)]
+struct SerializedIndexes {
+    names: Vec<String>,
+    values: Vec<i32>,
+}
+
+impl serde::Serialize for Indexes {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let data = SerializedIndexes {
+            names: self.names().into_iter().map(|name| name.to_owned()).collect(),
+            values: self.values.iter().map(|v| v.i32()).collect(),
+        };
+        return data.serialize(serializer);
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Indexes {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Indexes, D::Error> {
+        use serde::de::Error as _;
+
+        let serialized = SerializedIndexes::deserialize(deserializer)?;
+        for name in &serialized.names {
+            if !is_valid_index_name(name) {
+                return Err(D::Error::custom(format!(
+                    "all indexes names must be valid identifiers, '{}' is not", name
+                )));
+            }
+        }
+
+        if serialized.names.iter().collect::<BTreeSet<_>>().len() != serialized.names.len() {
+            return Err(D::Error::custom("invalid indexes: the same name is used multiple times"));
+        }
+
+        let size = serialized.names.len();
+        if (size == 0 && !serialized.values.is_empty()) || (size != 0 && serialized.values.len() % size != 0) {
+            return Err(D::Error::custom(format!(
+                "invalid indexes: {} values can not be split in entries of size {}",
+                serialized.values.len(), size
+            )));
+        }
+
+        let values = serialized.values.into_iter().map(IndexValue::from).collect::<Vec<_>>();
+        if size != 0 {
+            let mut unique = BTreeSet::new();
+            for chunk in values.chunks_exact(size) {
+                if !unique.insert(chunk) {
+                    return Err(D::Error::custom(format!(
+                        "invalid indexes: {:?} is present multiple times", chunk
+                    )));
+                }
+            }
+        }
+
+        let mut builder = IndexesBuilder::new(serialized.names.iter().map(|name| &**name).collect());
+        builder.values = values;
+        return Ok(builder.finish());
+    }
+}
+
 /// The `SampleBuilder` trait is used to abstract over the different kinds of
 /// samples used in Rascaline.
 ///
@@ -396,6 +471,15 @@ mod tests {
         assert_eq!(idx[2], [IndexValue::from(-4), IndexValue::from(-2413)]);
     }
 
+    #[test]
+    fn checked_conversions() {
+        assert_eq!(IndexValue::from(42).try_usize().unwrap(), 42);
+        assert_eq!(IndexValue::from(0).try_usize().unwrap(), 0);
+
+        let error = IndexValue::from(-3).try_usize().unwrap_err();
+        assert_eq!(error.to_string(), "invalid parameter: expected a non-negative index value, got -3");
+    }
+
     #[test]
     fn indexes_iter() {
         let mut builder = IndexesBuilder::new(vec!["foo", "bar"]);
@@ -433,4 +517,29 @@ mod tests {
         builder.add(&[IndexValue::from(0_usize), IndexValue::from(1)]);
         builder.finish();
     }
+
+    #[test]
+    fn serialization() {
+        let mut builder = IndexesBuilder::new(vec!["foo", "bar"]);
+        builder.add(&[IndexValue::from(2), IndexValue::from(3)]);
+        builder.add(&[IndexValue::from(-4), IndexValue::from(-2413)]);
+        let indexes = builder.finish();
+
+        let json = serde_json::to_string(&indexes).unwrap();
+        assert_eq!(json, r#"{"names":["foo","bar"],"values":[2,3,-4,-2413]}"#);
+        assert_eq!(serde_json::from_str::<Indexes>(&json).unwrap(), indexes);
+
+        let empty = IndexesBuilder::new(vec![]).finish();
+        let json = serde_json::to_string(&empty).unwrap();
+        assert_eq!(serde_json::from_str::<Indexes>(&json).unwrap(), empty);
+
+        let error = serde_json::from_str::<Indexes>(r#"{"names":["foo","bar"],"values":[2,3,-4]}"#).unwrap_err();
+        assert!(error.to_string().starts_with("invalid indexes: 3 values can not be split in entries of size 2"));
+
+        let error = serde_json::from_str::<Indexes>(r#"{"names":["foo"],"values":[2,2]}"#).unwrap_err();
+        assert!(error.to_string().starts_with("invalid indexes: [2] is present multiple times"));
+
+        let error = serde_json::from_str::<Indexes>(r#"{"names":["33 foo"],"values":[]}"#).unwrap_err();
+        assert!(error.to_string().starts_with("all indexes names must be valid identifiers, '33 foo' is not"));
+    }
 }
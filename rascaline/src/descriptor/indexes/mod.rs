@@ -3,9 +3,10 @@ pub use self::index::{IndexValue, Indexes, IndexesBuilder, SamplesBuilder};
 pub use self::index::is_valid_index_name;
 
 mod samples;
-pub use self::samples::{StructureSamples, AtomSamples};
+pub use self::samples::{StructureSamples, AtomSamples, PairSamples};
 
 mod species;
 pub use self::species::StructureSpeciesSamples;
+pub use self::species::AtomSpeciesSamples;
 pub use self::species::TwoBodiesSpeciesSamples;
 pub use self::species::ThreeBodiesSpeciesSamples;
@@ -1,6 +1,6 @@
 use std::collections::BTreeSet;
 
-use crate::{Error, System};
+use crate::{Error, ErrorContext, System};
 use super::{Indexes, IndexesBuilder, SamplesBuilder, IndexValue};
 
 /// `StructureSamples` is used to represents samples corresponding to full
@@ -92,7 +92,9 @@ impl SamplesBuilder for AtomSamples {
             let center = sample[1].usize();
 
             let system = &mut *systems[system_i];
-            system.compute_neighbors(self.cutoff)?;
+            system.compute_neighbors(self.cutoff).with_context(|| format!(
+                "failed to compute the neighbors list for structure {}", system_i
+            ))?;
 
             for pair in system.pairs_containing(center)? {
                 if pair.first == center {
@@ -114,6 +116,102 @@ impl SamplesBuilder for AtomSamples {
     }
 }
 
+/// `PairSamples` is used to represents pair-centered environments, where each
+/// pair of atoms closer than a given cutoff (i.e. a bond) is described with a
+/// feature vector based on the atoms around the pair.
+///
+/// The base set of indexes contains `structure`, `atom_1` and `atom_2`, with
+/// `atom_1 < atom_2`. Pairs between an atom and its own periodic images are
+/// not included. The gradient indexes also contains the `atom` with respect to
+/// which the gradient is taken, i.e. the two atoms in the pair and all their
+/// neighbors within the gradient cutoff; and the `spatial` (i.e x/y/z) index.
+pub struct PairSamples {
+    /// cutoff radius used to determine which pairs are included
+    bond_cutoff: f64,
+    /// spherical cutoff radius around the two atoms in the pair used to
+    /// determine which atoms are included in the gradients
+    gradients_cutoff: f64,
+}
+
+impl PairSamples {
+    /// Create a new `PairSamples`, containing all the pairs closer than
+    /// `bond_cutoff`; with gradients with respect to all the atoms within
+    /// `gradients_cutoff` of either atom in the pair.
+    pub fn new(bond_cutoff: f64, gradients_cutoff: f64) -> PairSamples {
+        assert!(bond_cutoff > 0.0 && bond_cutoff.is_finite(), "bond cutoff must be positive for PairSamples");
+        assert!(gradients_cutoff > 0.0 && gradients_cutoff.is_finite(), "gradients cutoff must be positive for PairSamples");
+        PairSamples { bond_cutoff, gradients_cutoff }
+    }
+}
+
+impl SamplesBuilder for PairSamples {
+    fn names(&self) -> Vec<&str> {
+        vec!["structure", "atom_1", "atom_2"]
+    }
+
+    fn samples(&self, systems: &mut [Box<dyn System>]) -> Result<Indexes, Error> {
+        let mut indexes = BTreeSet::new();
+        for (i_system, system) in systems.iter_mut().enumerate() {
+            system.compute_neighbors(self.bond_cutoff).with_context(|| format!(
+                "failed to compute the neighbors list for structure {}", i_system
+            ))?;
+
+            for pair in system.pairs()? {
+                if pair.first == pair.second {
+                    continue;
+                }
+
+                let (atom_1, atom_2) = if pair.first < pair.second {
+                    (pair.first, pair.second)
+                } else {
+                    (pair.second, pair.first)
+                };
+                indexes.insert((i_system, atom_1, atom_2));
+            }
+        }
+
+        let mut samples = IndexesBuilder::new(self.names());
+        for (i_system, atom_1, atom_2) in indexes {
+            samples.add(&[IndexValue::from(i_system), IndexValue::from(atom_1), IndexValue::from(atom_2)]);
+        }
+        return Ok(samples.finish());
+    }
+
+    fn gradients_for(&self, systems: &mut [Box<dyn System>], samples: &Indexes) -> Result<Option<Indexes>, Error> {
+        assert_eq!(samples.names(), self.names());
+
+        let mut indexes = BTreeSet::new();
+        for (i_sample, sample) in samples.iter().enumerate() {
+            let system_i = sample[0].usize();
+            let atom_1 = sample[1].usize();
+            let atom_2 = sample[2].usize();
+
+            let system = &mut *systems[system_i];
+            system.compute_neighbors(self.gradients_cutoff).with_context(|| format!(
+                "failed to compute the neighbors list for structure {}", system_i
+            ))?;
+
+            indexes.insert((i_sample, atom_1));
+            indexes.insert((i_sample, atom_2));
+            for &center in &[atom_1, atom_2] {
+                for pair in system.pairs_containing(center)? {
+                    indexes.insert((i_sample, pair.first));
+                    indexes.insert((i_sample, pair.second));
+                }
+            }
+        }
+
+        let mut gradients = IndexesBuilder::new(vec!["sample", "atom", "spatial"]);
+        for (i_sample, atom) in indexes {
+            gradients.add(&[IndexValue::from(i_sample), IndexValue::from(atom), IndexValue::from(0)]);
+            gradients.add(&[IndexValue::from(i_sample), IndexValue::from(atom), IndexValue::from(1)]);
+            gradients.add(&[IndexValue::from(i_sample), IndexValue::from(atom), IndexValue::from(2)]);
+        }
+
+        return Ok(Some(gradients.finish()));
+    }
+}
+
 
 #[cfg(test)]
 mod tests {
@@ -249,4 +347,41 @@ mod tests {
             &[v(1), v(4), v(0)], &[v(1), v(4), v(1)], &[v(1), v(4), v(2)],
         ]);
     }
+
+    #[test]
+    fn pairs() {
+        let mut systems = test_systems(&["methane", "water"]);
+        let builder = PairSamples::new(1.2, 1.2);
+        assert_eq!(builder.names(), &["structure", "atom_1", "atom_2"]);
+
+        let (samples, gradients) = builder.with_gradients(&mut systems).unwrap();
+        assert_eq!(builder.names(), samples.names());
+        assert_eq!(samples.iter().collect::<Vec<_>>(), vec![
+            // C-H bonds in methane
+            &[v(0), v(0), v(1)],
+            &[v(0), v(0), v(2)],
+            &[v(0), v(0), v(3)],
+            &[v(0), v(0), v(4)],
+            // O-H bonds in water
+            &[v(1), v(0), v(1)],
+            &[v(1), v(0), v(2)],
+        ]);
+
+        let gradients = gradients.unwrap();
+        assert_eq!(gradients.names(), &["sample", "atom", "spatial"]);
+
+        // in methane, all atoms are neighbors of the carbon
+        let atoms = gradients.iter()
+            .filter(|gradient| gradient[0].usize() == 0 && gradient[2].usize() == 0)
+            .map(|gradient| gradient[1].usize())
+            .collect::<Vec<_>>();
+        assert_eq!(atoms, [0, 1, 2, 3, 4]);
+
+        // in water, all atoms are neighbors of the oxygen
+        let atoms = gradients.iter()
+            .filter(|gradient| gradient[0].usize() == 5 && gradient[2].usize() == 0)
+            .map(|gradient| gradient[1].usize())
+            .collect::<Vec<_>>();
+        assert_eq!(atoms, [0, 1, 2]);
+    }
 }
@@ -0,0 +1,54 @@
+use crate::{Error, System};
+use super::super::{SamplesBuilder, Indexes, IndexesBuilder, IndexValue};
+
+/// `AtomSpeciesSamples` is used to represents samples corresponding to single
+/// atoms, without any information about their neighbors, where atoms with
+/// different chemical species are represented separately.
+///
+/// The base set of indexes contains `structure`, `center` (i.e. atom index
+/// inside the structure) and `species_center`. Since the samples do not
+/// contain any information about the neighbors, this builder does not support
+/// gradients.
+pub struct AtomSpeciesSamples;
+
+impl SamplesBuilder for AtomSpeciesSamples {
+    fn names(&self) -> Vec<&str> {
+        vec!["structure", "center", "species_center"]
+    }
+
+    fn samples(&self, systems: &mut [Box<dyn System>]) -> Result<Indexes, Error> {
+        let mut indexes = IndexesBuilder::new(self.names());
+        for (i_system, system) in systems.iter().enumerate() {
+            for (center, &species) in system.species()?.iter().enumerate() {
+                indexes.add(&[
+                    IndexValue::from(i_system), IndexValue::from(center), IndexValue::from(species)
+                ]);
+            }
+        }
+        return Ok(indexes.finish());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::systems::test_utils::test_systems;
+
+    // small helper function to create IndexValue
+    fn v(i: i32) -> IndexValue { IndexValue::from(i) }
+
+    #[test]
+    fn samples() {
+        let mut systems = test_systems(&["CH", "water"]);
+        let builder = AtomSpeciesSamples;
+        assert_eq!(builder.names(), &["structure", "center", "species_center"]);
+
+        let (samples, gradients) = builder.with_gradients(&mut systems).unwrap();
+        assert_eq!(samples.names(), builder.names());
+        assert_eq!(samples.iter().collect::<Vec<_>>(), vec![
+            &[v(0), v(0), v(1)], &[v(0), v(1), v(6)],
+            &[v(1), v(0), v(123456)], &[v(1), v(1), v(1)], &[v(1), v(2), v(1)],
+        ]);
+        assert!(gradients.is_none());
+    }
+}
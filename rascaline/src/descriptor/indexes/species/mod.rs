@@ -1,6 +1,9 @@
 mod structure;
 pub use self::structure::StructureSpeciesSamples;
 
+mod atom;
+pub use self::atom::AtomSpeciesSamples;
+
 mod two_bodies;
 pub use self::two_bodies::TwoBodiesSpeciesSamples;
 
@@ -1,8 +1,8 @@
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet};
 
 use itertools::Itertools;
 
-use crate::{Error, System};
+use crate::{Error, ErrorContext, System};
 use crate::systems::Pair;
 use super::super::{SamplesBuilder, Indexes, IndexesBuilder, IndexValue};
 
@@ -21,6 +21,9 @@ pub struct ThreeBodiesSpeciesSamples {
     cutoff: f64,
     /// Is the central atom considered to be its own neighbor?
     self_contribution: bool,
+    /// Channel to use for each neighbor species, species not in this map are
+    /// used as their own channel
+    channels: BTreeMap<i32, i32>,
 }
 
 impl ThreeBodiesSpeciesSamples {
@@ -31,6 +34,7 @@ impl ThreeBodiesSpeciesSamples {
         ThreeBodiesSpeciesSamples {
             cutoff: cutoff,
             self_contribution: false,
+            channels: BTreeMap::new(),
         }
     }
 
@@ -41,8 +45,24 @@ impl ThreeBodiesSpeciesSamples {
         ThreeBodiesSpeciesSamples {
             cutoff: cutoff,
             self_contribution: true,
+            channels: BTreeMap::new(),
         }
     }
+
+    /// Group the neighbor species in channels, using the given map from
+    /// species to channel. The neighbor species in the samples are replaced
+    /// by the corresponding channel, and all neighbors with species in the
+    /// same channel contribute to the same samples. Species not in
+    /// `channels` are used as their own channel.
+    pub fn with_neighbor_channels(mut self, channels: BTreeMap<i32, i32>) -> ThreeBodiesSpeciesSamples {
+        self.channels = channels;
+        return self;
+    }
+
+    /// Get the channel corresponding to the given neighbor `species`
+    fn channel(&self, species: i32) -> i32 {
+        return self.channels.get(&species).copied().unwrap_or(species);
+    }
 }
 
 /// A Set built as a sorted vector
@@ -88,20 +108,22 @@ impl SamplesBuilder for ThreeBodiesSpeciesSamples {
             if i < j { (i, j) } else { (j, i) }
         };
         for (i_system, system) in systems.iter_mut().enumerate() {
-            system.compute_neighbors(self.cutoff)?;
+            system.compute_neighbors(self.cutoff).with_context(|| format!(
+                "failed to compute the neighbors list for structure {}", i_system
+            ))?;
             let species = system.species()?;
 
             for center in 0..system.size()? {
-                let pairs = system.pairs_containing(center)?;
-                for (i, j) in triplets_from_pairs(pairs, center) {
-                    let (species_1, species_2) = sort_pair(species[i], species[j]);
+                for (i, j) in triplets_containing(&**system, center)? {
+                    let (species_1, species_2) = sort_pair(self.channel(species[i]), self.channel(species[j]));
                     set.insert((i_system, center, species[center], species_1, species_2));
                 }
             }
 
             if self.self_contribution {
                 for (center, &species_center) in species.iter().enumerate() {
-                    set.insert((i_system, center, species_center, species_center, species_center));
+                    let channel_center = self.channel(species_center);
+                    set.insert((i_system, center, species_center, channel_center, channel_center));
 
                     for pair in system.pairs_containing(center)? {
                         let neighbor = if pair.first == center {
@@ -110,7 +132,7 @@ impl SamplesBuilder for ThreeBodiesSpeciesSamples {
                             pair.first
                         };
 
-                        let (species_1, species_2) = sort_pair(species_center, species[neighbor]);
+                        let (species_1, species_2) = sort_pair(channel_center, self.channel(species[neighbor]));
                         set.insert((i_system, center, species_center, species_1, species_2));
                     }
                 }
@@ -147,14 +169,17 @@ impl SamplesBuilder for ThreeBodiesSpeciesSamples {
             let species_neighbor_2 = sample[4].i32();
 
             let system = &mut *systems[i_system.usize()];
-            system.compute_neighbors(self.cutoff)?;
+            system.compute_neighbors(self.cutoff).with_context(|| format!(
+                "failed to compute the neighbors list for structure {}", i_system.usize()
+            ))?;
 
             let species = system.species()?;
 
             if self.self_contribution {
-                let species_neighbor = if species_neighbor_1 == species_center {
+                let channel_center = self.channel(species_center);
+                let species_neighbor = if species_neighbor_1 == channel_center {
                     Some(species_neighbor_2)
-                } else if species_neighbor_2 == species_center {
+                } else if species_neighbor_2 == channel_center {
                     Some(species_neighbor_1)
                 } else {
                     None
@@ -173,7 +198,7 @@ impl SamplesBuilder for ThreeBodiesSpeciesSamples {
                             pair.first
                         };
 
-                        if species[neighbor] != species_neighbor {
+                        if self.channel(species[neighbor]) != species_neighbor {
                             continue;
                         }
 
@@ -182,9 +207,8 @@ impl SamplesBuilder for ThreeBodiesSpeciesSamples {
                 }
             }
 
-            let pairs = system.pairs_containing(center)?;
-            for (i, j) in triplets_from_pairs(pairs, center) {
-                let (species_1, species_2) = sort_pair(species[i], species[j]);
+            for (i, j) in triplets_containing(system, center)? {
+                let (species_1, species_2) = sort_pair(self.channel(species[i]), self.channel(species[j]));
 
                 if species_1 == species_neighbor_1 && species_2 == species_neighbor_2 {
                     indexes.insert((i_sample, center));
@@ -205,6 +229,18 @@ impl SamplesBuilder for ThreeBodiesSpeciesSamples {
     }
 }
 
+/// Get the list of triplet i-center-j in the given `system`, using the
+/// triplets provided by the system if any, and building them from the pairs
+/// containing `center` otherwise.
+fn triplets_containing(system: &dyn System, center: usize) -> Result<Vec<(usize, usize)>, Error> {
+    if let Some(triplets) = system.triplets_containing(center)? {
+        return Ok(triplets.iter().map(|triplet| (triplet.first, triplet.second)).collect());
+    }
+
+    let pairs = system.pairs_containing(center)?;
+    return Ok(triplets_from_pairs(pairs, center).collect());
+}
+
 /// Build the list of triplet i-center-j from the given list of pairs
 fn triplets_from_pairs(pairs: &[Pair], center: usize) -> impl Iterator<Item=(usize, usize)> + '_ {
     pairs.iter().cartesian_product(pairs).map(move |(first_pair, second_pair)| {
@@ -424,4 +460,65 @@ mod tests {
             [v(2), v(1), v(0)], [v(2), v(1), v(1)], [v(2), v(1), v(2)],
         ]);
     }
+
+    #[test]
+    fn neighbor_channels() {
+        let mut systems = test_systems(&["water"]);
+        let channels = [(123456, 1)].iter().copied().collect();
+        let builder = ThreeBodiesSpeciesSamples::with_self_contribution(2.0).with_neighbor_channels(channels);
+
+        let samples = builder.samples(&mut systems).unwrap();
+        assert_eq!(samples.iter().collect::<Vec<_>>(), vec![
+            // O neighbors are part of the H channel
+            &[v(0), v(0), v(123456), v(1), v(1)],
+            &[v(0), v(1), v(1), v(1), v(1)],
+            &[v(0), v(2), v(1), v(1), v(1)],
+        ]);
+
+        let gradients = builder.gradients_for(&mut systems, &samples).unwrap().unwrap();
+        assert_eq!(gradients.count(), 27);
+    }
+
+    /// Water system providing a custom list of triplets
+    struct WaterWithTriplets {
+        system: crate::SimpleSystem,
+        triplets: Vec<crate::systems::Triplet>,
+    }
+
+    impl System for WaterWithTriplets {
+        fn cell(&self) -> Result<crate::systems::UnitCell, Error> { self.system.cell() }
+        fn size(&self) -> Result<usize, Error> { self.system.size() }
+        fn species(&self) -> Result<&[i32], Error> { self.system.species() }
+        fn positions(&self) -> Result<&[crate::Vector3D], Error> { self.system.positions() }
+        fn compute_neighbors(&mut self, cutoff: f64) -> Result<(), Error> { self.system.compute_neighbors(cutoff) }
+        fn pairs(&self) -> Result<&[Pair], Error> { self.system.pairs() }
+        fn pairs_containing(&self, center: usize) -> Result<&[Pair], Error> { self.system.pairs_containing(center) }
+
+        fn triplets_containing(&self, center: usize) -> Result<Option<&[crate::systems::Triplet]>, Error> {
+            if center == 0 {
+                Ok(Some(&self.triplets))
+            } else {
+                Ok(Some(&[]))
+            }
+        }
+    }
+
+    #[test]
+    fn system_triplets() {
+        let system = WaterWithTriplets {
+            system: crate::systems::test_utils::test_system("water"),
+            triplets: vec![crate::systems::Triplet { first: 1, second: 2 }],
+        };
+        let mut systems = vec![Box::new(system) as Box<dyn System>];
+        let builder = ThreeBodiesSpeciesSamples::new(2.0);
+
+        let samples = builder.samples(&mut systems).unwrap();
+        assert_eq!(samples.iter().collect::<Vec<_>>(), vec![
+            // only the triplets given by the system are used
+            &[v(0), v(0), v(123456), v(1), v(1)],
+        ]);
+
+        let gradients = builder.gradients_for(&mut systems, &samples).unwrap().unwrap();
+        assert_eq!(gradients.count(), 9);
+    }
 }
@@ -1,6 +1,6 @@
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet};
 
-use crate::{Error, System};
+use crate::{Error, ErrorContext, System};
 use super::super::{SamplesBuilder, Indexes, IndexesBuilder, IndexValue};
 
 /// `TwoBodiesSpeciesSamples` is used to represents atom-centered environments,
@@ -18,6 +18,9 @@ pub struct TwoBodiesSpeciesSamples {
     cutoff: f64,
     /// Is the central atom considered to be its own neighbor?
     self_contribution: bool,
+    /// Channel to use for each neighbor species, species not in this map are
+    /// used as their own channel
+    channels: BTreeMap<i32, i32>,
 }
 
 impl TwoBodiesSpeciesSamples {
@@ -28,6 +31,7 @@ impl TwoBodiesSpeciesSamples {
         TwoBodiesSpeciesSamples {
             cutoff: cutoff,
             self_contribution: false,
+            channels: BTreeMap::new(),
         }
     }
 
@@ -38,8 +42,24 @@ impl TwoBodiesSpeciesSamples {
         TwoBodiesSpeciesSamples {
             cutoff: cutoff,
             self_contribution: true,
+            channels: BTreeMap::new(),
         }
     }
+
+    /// Group the neighbor species in channels, using the given map from
+    /// species to channel. The neighbor species in the samples are replaced
+    /// by the corresponding channel, and all neighbors with species in the
+    /// same channel contribute to the same samples. Species not in
+    /// `channels` are used as their own channel.
+    pub fn with_neighbor_channels(mut self, channels: BTreeMap<i32, i32>) -> TwoBodiesSpeciesSamples {
+        self.channels = channels;
+        return self;
+    }
+
+    /// Get the channel corresponding to the given neighbor `species`
+    fn channel(&self, species: i32) -> i32 {
+        return self.channels.get(&species).copied().unwrap_or(species);
+    }
 }
 
 impl SamplesBuilder for TwoBodiesSpeciesSamples {
@@ -53,12 +73,14 @@ impl SamplesBuilder for TwoBodiesSpeciesSamples {
         // given center
         let mut set = BTreeSet::new();
         for (i_system, system) in systems.iter_mut().enumerate() {
-            system.compute_neighbors(self.cutoff)?;
+            system.compute_neighbors(self.cutoff).with_context(|| format!(
+                "failed to compute the neighbors list for structure {}", i_system
+            ))?;
             let species = system.species()?;
 
             if self.self_contribution {
                 for (center, &species) in species.iter().enumerate() {
-                    set.insert((i_system, center, species, species));
+                    set.insert((i_system, center, species, self.channel(species)));
                 }
             }
 
@@ -66,8 +88,8 @@ impl SamplesBuilder for TwoBodiesSpeciesSamples {
                 let species_first = species[pair.first];
                 let species_second = species[pair.second];
 
-                set.insert((i_system, pair.first, species_first, species_second));
-                set.insert((i_system, pair.second, species_second, species_first));
+                set.insert((i_system, pair.first, species_first, self.channel(species_second)));
+                set.insert((i_system, pair.second, species_second, self.channel(species_first)));
             };
         }
 
@@ -92,9 +114,11 @@ impl SamplesBuilder for TwoBodiesSpeciesSamples {
             let species_neighbor = sample[3].i32();
 
             let system = &mut *systems[i_system.usize()];
-            system.compute_neighbors(self.cutoff)?;
+            system.compute_neighbors(self.cutoff).with_context(|| format!(
+                "failed to compute the neighbors list for structure {}", i_system.usize()
+            ))?;
 
-            if species_neighbor == species_center && self.self_contribution {
+            if species_neighbor == self.channel(species_center) && self.self_contribution {
                 indexes.insert((i_sample, center));
             }
 
@@ -102,7 +126,7 @@ impl SamplesBuilder for TwoBodiesSpeciesSamples {
             for pair in system.pairs_containing(center)? {
                 let neighbor = if pair.first == center { pair.second } else { pair.first };
 
-                if species[neighbor] != species_neighbor {
+                if self.channel(species[neighbor]) != species_neighbor {
                     continue;
                 }
 
@@ -286,4 +310,31 @@ mod tests {
             &[v(2), v(2), v(0)], &[v(2), v(2), v(1)], &[v(2), v(2), v(2)],
         ]);
     }
+
+    #[test]
+    fn neighbor_channels() {
+        let mut systems = test_systems(&["water"]);
+        let channels = [(1, 0), (123456, 0)].iter().copied().collect();
+        let builder = TwoBodiesSpeciesSamples::with_self_contribution(2.0).with_neighbor_channels(channels);
+
+        let (samples, gradients) = builder.with_gradients(&mut systems).unwrap();
+        assert_eq!(samples.iter().collect::<Vec<_>>(), vec![
+            // all neighbors are in the same channel
+            &[v(0), v(0), v(123456), v(0)],
+            &[v(0), v(1), v(1), v(0)],
+            &[v(0), v(2), v(1), v(0)],
+        ]);
+
+        // all atoms are neighbors of all the others
+        let gradients = gradients.unwrap();
+        assert_eq!(gradients.count(), 27);
+        for (i_sample, _) in samples.iter().enumerate() {
+            for atom in 0..3 {
+                for spatial in 0..3 {
+                    let gradient = [IndexValue::from(i_sample), IndexValue::from(atom), IndexValue::from(spatial)];
+                    assert!(gradients.contains(&gradient));
+                }
+            }
+        }
+    }
 }
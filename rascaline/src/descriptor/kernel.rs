@@ -0,0 +1,655 @@
+use std::io::Write;
+use std::ops::Range;
+
+use ndarray::{Array2, ArrayView2, ArrayViewMut2, Axis, s};
+use ndarray::parallel::prelude::*;
+
+use crate::Error;
+use super::{Descriptor, Indexes, IndexesBuilder, IndexValue};
+use super::npy::{write_array, write_indexes};
+
+/// Kernel matrices used by sparse kernel methods (also known as the Nyström
+/// approximation), as created by [`Descriptor::sparse_kernels`].
+#[derive(Clone, Debug)]
+pub struct SparseKernels {
+    /// Kernel between the samples of the training descriptor (rows) and the
+    /// sparse points (columns)
+    pub knm: Array2<f64>,
+    /// Gradients of `knm` with respect to atomic positions, with one row for
+    /// each gradient sample of the training descriptor, if the training
+    /// descriptor contains gradients
+    pub knm_gradients: Option<Array2<f64>>,
+    /// Metadata describing the rows of `knm_gradients`, using the same
+    /// variables as `Descriptor::gradients_samples`. The `sample` variable
+    /// refers to the rows of `knm`.
+    pub knm_gradients_samples: Option<Indexes>,
+    /// Kernel between the sparse points
+    pub kmm: Array2<f64>,
+    /// Exponent of the polynomial kernel
+    pub zeta: i32,
+}
+
+impl Descriptor {
+    /// Compute the dot product between the values of all the samples in this
+    /// descriptor and all the samples in `other`, i.e. the linear kernel
+    /// `X_self X_other^T`. The result has `self.samples.count()` rows and
+    /// `other.samples.count()` columns.
+    ///
+    /// If `normalize` is `true`, the dot product of each pair of samples is
+    /// divided by the norms of both samples, giving the cosine similarity
+    /// between the samples. Samples with all values equal to zero have a
+    /// normalized dot product of zero with all other samples.
+    ///
+    /// Both descriptors must have the same features.
+    #[time_graph::instrument(name = "Descriptor::dot")]
+    pub fn dot(&self, other: &Descriptor, normalize: bool) -> Result<Array2<f64>, Error> {
+        let mut output = Array2::zeros((self.samples.count(), other.samples.count()));
+        self.dot_into(
+            other,
+            0..self.samples.count(),
+            0..other.samples.count(),
+            normalize,
+            output.view_mut(),
+        )?;
+        return Ok(output);
+    }
+
+    /// Compute a block of the dot product between the samples of this
+    /// descriptor and the samples of `other` (see [`Descriptor::dot`]),
+    /// containing the given `rows` (i.e. samples of this descriptor) and
+    /// `columns` (i.e. samples of `other`), and write it to `output`.
+    ///
+    /// `output` must have a shape of `rows.len() x columns.len()`, but does
+    /// not need to be contiguous in memory. This can be used to assemble kernel
+    /// matrices too large to fit in memory one block at the time.
+    #[time_graph::instrument(name = "Descriptor::dot_into")]
+    pub fn dot_into(
+        &self,
+        other: &Descriptor,
+        rows: Range<usize>,
+        columns: Range<usize>,
+        normalize: bool,
+        mut output: ArrayViewMut2<f64>,
+    ) -> Result<(), Error> {
+        if self.features != other.features {
+            return Err(Error::InvalidParameter(
+                "can not compute the dot product of descriptors with different features".into()
+            ));
+        }
+
+        check_range(&rows, self.samples.count(), "rows")?;
+        check_range(&columns, other.samples.count(), "columns")?;
+
+        if output.shape() != [rows.len(), columns.len()] {
+            return Err(Error::InvalidParameter(format!(
+                "expected an output array of shape ({}, {}), got ({}, {})",
+                rows.len(), columns.len(), output.shape()[0], output.shape()[1]
+            )));
+        }
+
+        let values = self.values.slice(s![rows, ..]);
+        let other_values = other.values.slice(s![columns, ..]);
+        ndarray::linalg::general_mat_mul(1.0, &values, &other_values.t(), 0.0, &mut output);
+
+        if normalize {
+            let norms = row_norms(values);
+            let other_norms = row_norms(other_values);
+
+            for (mut row, &norm) in output.outer_iter_mut().zip(&norms) {
+                for (value, &other_norm) in row.iter_mut().zip(&other_norms) {
+                    let norm = norm * other_norm;
+                    if norm == 0.0 {
+                        *value = 0.0;
+                    } else {
+                        *value /= norm;
+                    }
+                }
+            }
+        }
+
+        return Ok(());
+    }
+
+    /// Compute the dot product between the samples of this descriptor and the
+    /// samples of `other` (see [`Descriptor::dot`]) `block_size` rows at the
+    /// time, writing each block of rows to `writer` as soon as it is computed.
+    /// Only `block_size` rows of the kernel are stored in memory at any time,
+    /// which allows to compute kernels too large to fit in memory directly to
+    /// a file or a socket.
+    ///
+    /// For each block, the samples corresponding to the rows are written
+    /// first, as a structured array in numpy's `.npy` format (see
+    /// [`Indexes::write_npy`](super::Indexes::write_npy)), followed by the rows of the kernel as a `.npy`
+    /// array of 64-bit floating point numbers. The blocks can be read back in
+    /// Python by calling `numpy.load` repeatedly on the same file.
+    #[time_graph::instrument(name = "Descriptor::dot_to_writer")]
+    pub fn dot_to_writer<W: Write>(
+        &self,
+        other: &Descriptor,
+        normalize: bool,
+        block_size: usize,
+        mut writer: W,
+    ) -> Result<(), Error> {
+        if block_size == 0 {
+            return Err(Error::InvalidParameter(
+                "block size must be at least 1".into()
+            ));
+        }
+
+        let n_samples = self.samples.count();
+        let n_other = other.samples.count();
+        let mut output = Array2::zeros((usize::min(block_size, n_samples), n_other));
+
+        let mut start = 0;
+        while start < n_samples {
+            let stop = usize::min(start + block_size, n_samples);
+            let mut block = output.slice_mut(s![..stop - start, ..]);
+            self.dot_into(other, start..stop, 0..n_other, normalize, block.view_mut())?;
+
+            write_indexes(&mut writer, &self.samples, start..stop)?;
+            write_array(&mut writer, block.view())?;
+            start = stop;
+        }
+
+        writer.flush()?;
+        return Ok(());
+    }
+
+    /// Append the rows corresponding to the samples of this descriptor to a
+    /// `kernel` previously computed with `training.dot(other, normalize)`,
+    /// giving the same kernel as computing the dot product of `training` and
+    /// this descriptor together with `other`. This is intended for on-the-fly
+    /// learning, where new structures are regularly added to the training
+    /// set, and avoids re-computing the existing rows of the kernel.
+    #[time_graph::instrument(name = "Descriptor::dot_append")]
+    pub fn dot_append(&self, other: &Descriptor, normalize: bool, kernel: &mut Array2<f64>) -> Result<(), Error> {
+        if kernel.ncols() != other.samples.count() {
+            return Err(Error::InvalidParameter(format!(
+                "the kernel has {} columns, but the other descriptor contains {} samples",
+                kernel.ncols(), other.samples.count()
+            )));
+        }
+
+        let rows = self.dot(other, normalize)?;
+        kernel.append(Axis(0), rows.view()).expect("invalid kernel shape");
+        return Ok(());
+    }
+}
+
+impl Descriptor {
+    /// Compute the kernel matrices used by sparse kernel methods between the
+    /// samples of this (training) descriptor and the `sparse` points, using
+    /// the polynomial kernel `(x_i · x_m)^zeta` of
+    /// [`KernelModel`](crate::models::KernelModel). The sparse points are
+    /// usually computed for a subset of the training samples, selected with
+    /// [`Descriptor::farthest_point_sampling`] and
+    /// `CalculationOptions::selected_samples`.
+    ///
+    /// The samples are matched on all the species variables (i.e. the
+    /// variables starting with `species_`, such as `species_center` or
+    /// `species_neighbor` when they were not moved to the features by
+    /// [`Descriptor::densify`]) remaining in the samples of the `sparse`
+    /// descriptor: the kernel between samples with different values for any of
+    /// these variables is set to zero. For example, atomic environments are
+    /// only compared to sparse points with the same central species. All the
+    /// species variables of the sparse points must also be present in the
+    /// samples of this descriptor.
+    #[time_graph::instrument(name = "Descriptor::sparse_kernels")]
+    pub fn sparse_kernels(&self, sparse: &Descriptor, zeta: i32) -> Result<SparseKernels, Error> {
+        if zeta < 1 {
+            return Err(Error::InvalidParameter(format!(
+                "zeta must be at least 1 for sparse kernels, got {}", zeta
+            )));
+        }
+        let (knm, knm_gradients) = self.sparse_knm(sparse, zeta)?;
+
+        let mut kmm = sparse.dot(sparse, false)?.mapv(|k| k.powi(zeta));
+        let variables = species_variables(sparse);
+        if !variables.is_empty() {
+            let sparse_species = species_keys(sparse, &variables)?;
+            mask_different_species(&mut kmm, &sparse_species, &sparse_species);
+        }
+
+        return Ok(SparseKernels {
+            knm: knm,
+            knm_gradients: knm_gradients,
+            knm_gradients_samples: self.gradients_samples.clone(),
+            kmm: kmm,
+            zeta: zeta,
+        });
+    }
+
+    /// Compute the `knm` and `knm_gradients` parts of
+    /// [`Descriptor::sparse_kernels`]
+    fn sparse_knm(&self, sparse: &Descriptor, zeta: i32) -> Result<(Array2<f64>, Option<Array2<f64>>), Error> {
+        self.check_no_pending_gradients("Descriptor::sparse_kernels")?;
+
+        let dot_nm = self.dot(sparse, false)?;
+        let mut knm = dot_nm.mapv(|k| k.powi(zeta));
+
+        // d/dr (x_i · x_m)^zeta = zeta (x_i · x_m)^(zeta - 1) (dx_i/dr · x_m)
+        let mut knm_gradients = None;
+        if let Some(ref gradients) = self.gradients {
+            let gradients_samples = self.gradients_samples.as_ref().expect("missing gradients samples");
+            let mut kernel_gradients = gradients.dot(&sparse.values.t());
+            for (mut row, gradient_sample) in kernel_gradients.outer_iter_mut().zip(gradients_samples) {
+                let dot = dot_nm.row(gradient_sample[0].usize());
+                for (value, &dot) in row.iter_mut().zip(&dot) {
+                    *value *= zeta as f64 * dot.powi(zeta - 1);
+                }
+            }
+            knm_gradients = Some(kernel_gradients);
+        }
+
+        let variables = species_variables(sparse);
+        if !variables.is_empty() {
+            let species = species_keys(self, &variables)?;
+            let sparse_species = species_keys(sparse, &variables)?;
+            mask_different_species(&mut knm, &species, &sparse_species);
+
+            if let Some(ref mut kernel_gradients) = knm_gradients {
+                let gradients_samples = self.gradients_samples.as_ref().expect("missing gradients samples");
+                let gradients_species = gradients_samples.iter()
+                    .map(|gradient_sample| species[gradient_sample[0].usize()].clone())
+                    .collect::<Vec<_>>();
+                mask_different_species(kernel_gradients, &gradients_species, &sparse_species);
+            }
+        }
+
+        return Ok((knm, knm_gradients));
+    }
+}
+
+impl SparseKernels {
+    /// Add the rows corresponding to the samples in the `new` descriptor to
+    /// `knm` (and `knm_gradients`), for the same `sparse` points as the ones
+    /// used to create these kernels. This is intended for on-the-fly
+    /// learning, where new structures are regularly added to the training
+    /// set, and avoids re-computing the existing rows of the kernels.
+    ///
+    /// The gradients of `new` are added to `knm_gradients`, and are required
+    /// if these kernels already contain gradients. The gradient samples of
+    /// `new` are added to `knm_gradients_samples`, with the `sample` variable
+    /// shifted by the number of existing rows in `knm` to refer to the rows
+    /// of the updated kernel.
+    #[time_graph::instrument(name = "SparseKernels::append")]
+    pub fn append(&mut self, new: &Descriptor, sparse: &Descriptor) -> Result<(), Error> {
+        if self.knm.ncols() != sparse.samples.count() {
+            return Err(Error::InvalidParameter(format!(
+                "these kernels were computed for {} sparse points, but the sparse descriptor contains {} samples",
+                self.knm.ncols(), sparse.samples.count()
+            )));
+        }
+
+        let (knm, knm_gradients) = new.sparse_knm(sparse, self.zeta)?;
+        match (&mut self.knm_gradients, knm_gradients) {
+            (Some(gradients), Some(new_gradients)) => {
+                gradients.append(Axis(0), new_gradients.view()).expect("invalid kernel gradients shape");
+
+                let gradients_samples = self.knm_gradients_samples.as_ref().expect("missing gradients samples");
+                let new_gradients_samples = new.gradients_samples.as_ref().expect("missing gradients samples");
+                let mut builder = IndexesBuilder::new(gradients_samples.names());
+                for gradient_sample in gradients_samples {
+                    builder.add(gradient_sample);
+                }
+                let shift = self.knm.nrows();
+                for gradient_sample in new_gradients_samples {
+                    let mut shifted = gradient_sample.to_vec();
+                    shifted[0] = IndexValue::from(gradient_sample[0].usize() + shift);
+                    builder.add(&shifted);
+                }
+                self.knm_gradients_samples = Some(builder.finish());
+            }
+            (Some(_), None) => {
+                return Err(Error::InvalidParameter(
+                    "the new descriptor must contain gradients to update kernels with gradients".into()
+                ));
+            }
+            (None, _) => {}
+        }
+
+        self.knm.append(Axis(0), knm.view()).expect("invalid kernel shape");
+        return Ok(());
+    }
+}
+
+/// Get the names of all the species variables (starting with `species_`) in
+/// the samples of `descriptor`
+fn species_variables(descriptor: &Descriptor) -> Vec<&str> {
+    descriptor.samples.names().into_iter()
+        .filter(|name| name.starts_with("species_"))
+        .collect()
+}
+
+/// Get the values taken by the species `variables` for all samples in
+/// `descriptor`
+fn species_keys(descriptor: &Descriptor, variables: &[&str]) -> Result<Vec<Vec<IndexValue>>, Error> {
+    let names = descriptor.samples.names();
+    let mut positions = Vec::new();
+    for variable in variables {
+        if let Some(position) = names.iter().position(|name| name == variable) {
+            positions.push(position);
+        } else {
+            return Err(Error::InvalidParameter(format!(
+                "the sparse points are matched on '{}', which is not present in the samples of the training descriptor: [{}]",
+                variable, names.join(", ")
+            )));
+        }
+    }
+
+    let keys = descriptor.samples.iter()
+        .map(|sample| positions.iter().map(|&i| sample[i]).collect())
+        .collect();
+    return Ok(keys);
+}
+
+/// Set the entries of `kernel` where the species of the row (from
+/// `rows_species`) and of the column (from `columns_species`) differ to zero.
+fn mask_different_species(kernel: &mut Array2<f64>, rows_species: &[Vec<IndexValue>], columns_species: &[Vec<IndexValue>]) {
+    for (mut row, row_species) in kernel.outer_iter_mut().zip(rows_species) {
+        for (value, column_species) in row.iter_mut().zip(columns_species) {
+            if row_species != column_species {
+                *value = 0.0;
+            }
+        }
+    }
+}
+
+/// Check that `range` is a valid range of indexes in an array of size `size`
+fn check_range(range: &Range<usize>, size: usize, name: &str) -> Result<(), Error> {
+    if range.start > range.end || range.end > size {
+        return Err(Error::InvalidParameter(format!(
+            "invalid range of {}: {}..{} for {} samples", name, range.start, range.end, size
+        )));
+    }
+    Ok(())
+}
+
+/// Compute the euclidean norm of all rows in `values`. Each row is only
+/// visited once, and the rows are handled in parallel.
+fn row_norms(values: ArrayView2<f64>) -> Vec<f64> {
+    values.axis_iter(Axis(0))
+        .into_par_iter()
+        .map(|row| row.dot(&row).sqrt())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+    use ndarray::{Array2, s};
+
+    use crate::descriptor::{IndexesBuilder, IndexValue};
+    use crate::Descriptor;
+
+    fn test_descriptor(n_samples: usize, shift: usize) -> Descriptor {
+        let mut samples = IndexesBuilder::new(vec!["sample"]);
+        for i in 0..n_samples {
+            samples.add(&[IndexValue::from(i)]);
+        }
+
+        let mut features = IndexesBuilder::new(vec!["feature"]);
+        for i in 0..4 {
+            features.add(&[IndexValue::from(i)]);
+        }
+
+        let mut descriptor = Descriptor::new();
+        descriptor.prepare(samples.finish(), features.finish());
+        for ((i, j), value) in descriptor.values.indexed_iter_mut() {
+            *value = f64::sin((4 * (i + shift) + j) as f64);
+        }
+        return descriptor;
+    }
+
+    #[test]
+    fn dot() {
+        let first = test_descriptor(5, 0);
+        let second = test_descriptor(3, 7);
+
+        let kernel = first.dot(&second, false).unwrap();
+        let expected = first.values.dot(&second.values.t());
+        assert_eq!(kernel.shape(), [5, 3]);
+        for (actual, expected) in kernel.iter().zip(expected.iter()) {
+            assert_relative_eq!(actual, expected, max_relative=1e-12);
+        }
+
+        let kernel = first.dot(&first, true).unwrap();
+        for i in 0..5 {
+            assert_relative_eq!(kernel[[i, i]], 1.0, max_relative=1e-12);
+        }
+
+        let mut zeros = test_descriptor(2, 0);
+        zeros.values.fill(0.0);
+        let kernel = first.dot(&zeros, true).unwrap();
+        assert_eq!(kernel, Array2::<f64>::zeros((5, 2)));
+    }
+
+    #[test]
+    fn dot_into() {
+        let first = test_descriptor(6, 0);
+        let second = test_descriptor(5, 3);
+        let full = first.dot(&second, true).unwrap();
+
+        // assemble the kernel block by block, inside a larger array
+        let mut output = Array2::from_elem((6, 8), -1.0);
+        for rows in &[0..4, 4..6] {
+            for columns in &[0..2, 2..5] {
+                let block = output.slice_mut(s![rows.clone(), columns.clone()]);
+                first.dot_into(&second, rows.clone(), columns.clone(), true, block).unwrap();
+            }
+        }
+
+        for (actual, expected) in output.slice(s![.., ..5]).iter().zip(full.iter()) {
+            assert_relative_eq!(actual, expected, max_relative=1e-12);
+        }
+        assert!(output.slice(s![.., 5..]).iter().all(|&value| value == -1.0));
+
+        let mut block = Array2::zeros((2, 2));
+        let error = first.dot_into(&second, 0..2, 4..6, false, block.view_mut()).unwrap_err();
+        assert_eq!(error.to_string(), "invalid parameter: invalid range of columns: 4..6 for 5 samples");
+
+        let error = first.dot_into(&second, 0..3, 0..2, false, block.view_mut()).unwrap_err();
+        assert_eq!(error.to_string(), "invalid parameter: expected an output array of shape (3, 2), got (2, 2)");
+
+        let mut other = Descriptor::new();
+        let mut features = IndexesBuilder::new(vec!["other"]);
+        features.add(&[IndexValue::from(0)]);
+        other.prepare(IndexesBuilder::new(vec!["sample"]).finish(), features.finish());
+        let error = first.dot(&other, false).unwrap_err();
+        assert_eq!(error.to_string(), "invalid parameter: can not compute the dot product of descriptors with different features");
+    }
+
+    #[test]
+    fn dot_to_writer() {
+        // skip the header of a .npy array at the start of `data`
+        fn skip_header(data: &[u8]) -> &[u8] {
+            assert_eq!(&data[..8], b"\x93NUMPY\x01\x00");
+            let len = u16::from_le_bytes([data[8], data[9]]) as usize;
+            return &data[10 + len..];
+        }
+
+        let first = test_descriptor(5, 0);
+        let second = test_descriptor(3, 7);
+        let full = first.dot(&second, true).unwrap();
+
+        let mut buffer = Vec::new();
+        first.dot_to_writer(&second, true, 2, &mut buffer).unwrap();
+
+        let mut data = &buffer[..];
+        for rows in &[0..2, 2..4, 4..5] {
+            data = skip_header(data);
+            for sample in rows.clone() {
+                let index = i32::from_le_bytes([data[0], data[1], data[2], data[3]]);
+                assert_eq!(index as usize, sample);
+                data = &data[4..];
+            }
+
+            data = skip_header(data);
+            for &expected in full.slice(s![rows.clone(), ..]).iter() {
+                let mut bytes = [0; 8];
+                bytes.copy_from_slice(&data[..8]);
+                assert_relative_eq!(f64::from_le_bytes(bytes), expected, max_relative=1e-12);
+                data = &data[8..];
+            }
+        }
+        assert!(data.is_empty());
+
+        let error = first.dot_to_writer(&second, true, 0, &mut buffer).unwrap_err();
+        assert_eq!(error.to_string(), "invalid parameter: block size must be at least 1");
+    }
+
+    /// Descriptor with a `species_center` sample variable and gradients,
+    /// containing the `samples` from `test_descriptor(4, 0)`
+    fn species_descriptor(samples: &[usize]) -> Descriptor {
+        let species = [1, 1, 6, 6];
+        let full = test_descriptor(4, 0);
+
+        let mut builder = IndexesBuilder::new(vec!["structure", "center", "species_center"]);
+        let mut gradients_samples = IndexesBuilder::new(vec!["sample", "atom", "spatial"]);
+        for (i, &sample) in samples.iter().enumerate() {
+            builder.add(&[IndexValue::from(0), IndexValue::from(sample), IndexValue::from(species[sample])]);
+            for spatial in 0..3 {
+                gradients_samples.add(&[IndexValue::from(i), IndexValue::from(sample), IndexValue::from(spatial)]);
+            }
+        }
+
+        let mut descriptor = Descriptor::new();
+        descriptor.prepare_gradients(builder.finish(), gradients_samples.finish(), full.features.clone());
+        for (i, &sample) in samples.iter().enumerate() {
+            descriptor.values.row_mut(i).assign(&full.values.row(sample));
+        }
+        for ((i, j), value) in descriptor.gradients.as_mut().unwrap().indexed_iter_mut() {
+            *value = f64::cos((4 * i + j) as f64);
+        }
+
+        return descriptor;
+    }
+
+    #[test]
+    fn sparse_kernels() {
+        let training = species_descriptor(&[0, 1, 2, 3]);
+        let sparse = species_descriptor(&[1, 2]);
+
+        let kernels = training.sparse_kernels(&sparse, 2).unwrap();
+        assert_eq!(kernels.knm.shape(), [4, 2]);
+        assert_eq!(kernels.kmm.shape(), [2, 2]);
+
+        let dot = training.values.dot(&sparse.values.t());
+        for i in 0..4 {
+            for m in 0..2 {
+                let same_species = training.samples[i][2] == sparse.samples[m][2];
+                let expected = if same_species { dot[[i, m]].powi(2) } else { 0.0 };
+                assert_relative_eq!(kernels.knm[[i, m]], expected, max_relative=1e-12);
+            }
+        }
+
+        assert_relative_eq!(kernels.kmm[[0, 0]], kernels.knm[[1, 0]], max_relative=1e-12);
+        assert_relative_eq!(kernels.kmm[[1, 1]], kernels.knm[[2, 1]], max_relative=1e-12);
+        assert_eq!(kernels.kmm[[0, 1]], 0.0);
+        assert_eq!(kernels.kmm[[1, 0]], 0.0);
+
+        let knm_gradients = kernels.knm_gradients.unwrap();
+        let gradients = training.gradients.as_ref().unwrap();
+        let gradients_samples = training.gradients_samples.as_ref().unwrap();
+        assert_eq!(knm_gradients.shape(), [12, 2]);
+        for (row, gradient_sample) in gradients_samples.iter().enumerate() {
+            let i = gradient_sample[0].usize();
+            for m in 0..2 {
+                let same_species = training.samples[i][2] == sparse.samples[m][2];
+                let expected = if same_species {
+                    2.0 * dot[[i, m]] * gradients.row(row).dot(&sparse.values.row(m))
+                } else {
+                    0.0
+                };
+                assert_relative_eq!(knm_gradients[[row, m]], expected, max_relative=1e-12);
+            }
+        }
+
+        // without species, all samples are compared
+        let first = test_descriptor(5, 0);
+        let second = test_descriptor(3, 7);
+        let kernels = first.sparse_kernels(&second, 1).unwrap();
+        assert_eq!(kernels.knm, first.dot(&second, false).unwrap());
+        assert!(kernels.knm_gradients.is_none());
+
+        let error = first.sparse_kernels(&second, 0).unwrap_err();
+        assert_eq!(error.to_string(), "invalid parameter: zeta must be at least 1 for sparse kernels, got 0");
+
+        // sparse points with species can not be matched to samples without
+        let error = test_descriptor(4, 0).sparse_kernels(&sparse, 1).unwrap_err();
+        assert_eq!(error.to_string(),
+            "invalid parameter: the sparse points are matched on 'species_center', \
+            which is not present in the samples of the training descriptor: [sample]"
+        );
+    }
+
+    #[test]
+    fn sparse_kernels_multiple_species() {
+        // samples are matched on all the species variables
+        let full = test_descriptor(4, 0);
+        let mut builder = IndexesBuilder::new(vec!["structure", "center", "species_center", "species_neighbor"]);
+        for (center, (species_center, species_neighbor)) in [(1, 1), (1, 6), (6, 1), (6, 6)].iter().enumerate() {
+            builder.add(&[
+                IndexValue::from(0), IndexValue::from(center),
+                IndexValue::from(*species_center), IndexValue::from(*species_neighbor)
+            ]);
+        }
+        let mut descriptor = Descriptor::new();
+        descriptor.prepare(builder.finish(), full.features.clone());
+        descriptor.values.assign(&full.values);
+
+        let kernels = descriptor.sparse_kernels(&descriptor, 1).unwrap();
+        let dot = descriptor.values.dot(&descriptor.values.t());
+        for i in 0..4 {
+            for j in 0..4 {
+                let expected = if i == j { dot[[i, j]] } else { 0.0 };
+                assert_relative_eq!(kernels.knm[[i, j]], expected, max_relative=1e-12);
+                assert_relative_eq!(kernels.kmm[[i, j]], expected, max_relative=1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn dot_append() {
+        let first = test_descriptor(6, 0);
+        let second = test_descriptor(4, 3);
+        let full = first.dot(&second, true).unwrap();
+
+        let mut kernel = test_descriptor(2, 0).dot(&second, true).unwrap();
+        test_descriptor(4, 2).dot_append(&second, true, &mut kernel).unwrap();
+        assert_eq!(kernel.shape(), [6, 4]);
+        for (actual, expected) in kernel.iter().zip(full.iter()) {
+            assert_relative_eq!(actual, expected, max_relative=1e-12);
+        }
+
+        let mut kernel = Array2::zeros((2, 3));
+        let error = first.dot_append(&second, true, &mut kernel).unwrap_err();
+        assert_eq!(error.to_string(), "invalid parameter: the kernel has 3 columns, but the other descriptor contains 4 samples");
+    }
+
+    #[test]
+    fn sparse_kernels_append() {
+        let sparse = species_descriptor(&[1, 2]);
+        let expected = species_descriptor(&[0, 1, 2, 3]).sparse_kernels(&sparse, 2).unwrap();
+
+        let mut kernels = species_descriptor(&[0, 1]).sparse_kernels(&sparse, 2).unwrap();
+        let mut new = species_descriptor(&[2, 3]);
+        // the gradients in `species_descriptor` depend on the row, use the
+        // same as the full descriptor
+        new.gradients.as_mut().unwrap().assign(&species_descriptor(&[0, 1, 2, 3]).gradients.unwrap().slice(s![6.., ..]));
+        kernels.append(&new, &sparse).unwrap();
+
+        assert_eq!(kernels.knm, expected.knm);
+        assert_eq!(kernels.knm_gradients, expected.knm_gradients);
+        assert_eq!(kernels.knm_gradients_samples, expected.knm_gradients_samples);
+        assert_eq!(kernels.kmm, expected.kmm);
+
+        let mut no_gradients = Descriptor::new();
+        no_gradients.prepare(new.samples.clone(), new.features.clone());
+        let error = kernels.append(&no_gradients, &sparse).unwrap_err();
+        assert_eq!(error.to_string(), "invalid parameter: the new descriptor must contain gradients to update kernels with gradients");
+
+        let error = kernels.append(&new, &species_descriptor(&[0, 1, 2])).unwrap_err();
+        assert_eq!(error.to_string(), "invalid parameter: these kernels were computed for 2 sparse points, but the sparse descriptor contains 3 samples");
+    }
+}
@@ -3,10 +3,31 @@ pub use self::indexes::{Indexes, IndexesBuilder, IndexValue};
 pub use self::indexes::is_valid_index_name;
 
 pub use self::indexes::SamplesBuilder;
-pub use self::indexes::{StructureSamples, AtomSamples};
-pub use self::indexes::{StructureSpeciesSamples, TwoBodiesSpeciesSamples};
+pub use self::indexes::{StructureSamples, AtomSamples, PairSamples};
+pub use self::indexes::{StructureSpeciesSamples, AtomSpeciesSamples, TwoBodiesSpeciesSamples};
 pub use self::indexes::{ThreeBodiesSpeciesSamples};
 
 #[allow(clippy::module_inception)]
 mod descriptor;
-pub use self::descriptor::Descriptor;
+pub use self::descriptor::{Descriptor, MemoryLayout};
+
+mod per_atom;
+mod rotation;
+mod covariance;
+mod kernel;
+pub use self::kernel::SparseKernels;
+
+mod blocks;
+pub use self::blocks::{BlockSparseDescriptor, DescriptorBlock};
+
+mod single_precision;
+pub use self::single_precision::SinglePrecisionDescriptor;
+
+mod selection;
+pub use self::selection::{SampleDistance, StructureSplit};
+
+mod projection;
+pub use self::projection::RandomProjection;
+
+mod npy;
+pub use self::npy::write_npy;
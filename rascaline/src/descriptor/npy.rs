@@ -0,0 +1,181 @@
+use std::convert::TryFrom;
+use std::io::Write;
+use std::ops::Range;
+use std::path::Path;
+
+use ndarray::ArrayView2;
+
+use crate::{Error, ErrorContext};
+use super::{Descriptor, Indexes};
+
+/// Write the header of a `.npy` file (version 1.0) for an array with the
+/// given numpy type description and shape, in C order.
+fn write_header<W: Write>(writer: &mut W, descr: &str, shape: &str) -> Result<(), Error> {
+    let mut header = format!(
+        "{{'descr': {}, 'fortran_order': False, 'shape': {}, }}",
+        descr, shape
+    );
+
+    // the total size of the preamble (magic string, version, header length
+    // and header) must be a multiple of 64 bytes, and the header must end
+    // with a newline
+    let preamble_size = 6 + 2 + 2;
+    let padding = 64 - (preamble_size + header.len() + 1) % 64;
+    header.push_str(&" ".repeat(padding % 64));
+    header.push('\n');
+
+    let header_len = u16::try_from(header.len()).map_err(|_| Error::InvalidParameter(
+        "header is too large for a .npy file".into()
+    ))?;
+
+    writer.write_all(b"\x93NUMPY")?;
+    writer.write_all(&[1, 0])?;
+    writer.write_all(&header_len.to_le_bytes())?;
+    writer.write_all(header.as_bytes())?;
+    return Ok(());
+}
+
+/// Write the given `array` to `path`, using numpy's `.npy` format. The file
+/// can then be loaded with `numpy.load`.
+pub fn write_npy(path: impl AsRef<Path>, array: ArrayView2<f64>) -> Result<(), Error> {
+    let path = path.as_ref();
+    let file = std::fs::File::create(path).with_context(|| format!(
+        "failed to create '{}'", path.display()
+    ))?;
+    let mut writer = std::io::BufWriter::new(file);
+    write_array(&mut writer, array)?;
+    writer.flush()?;
+    return Ok(());
+}
+
+/// Write the given `array` to `writer`, using numpy's `.npy` format
+pub(crate) fn write_array<W: Write>(writer: &mut W, array: ArrayView2<f64>) -> Result<(), Error> {
+    let shape = format!("({}, {})", array.nrows(), array.ncols());
+    write_header(writer, "'<f8'", &shape)?;
+    for value in array.iter() {
+        writer.write_all(&value.to_le_bytes())?;
+    }
+    return Ok(());
+}
+
+/// Write the entries in `range` of the given `indexes` to `writer`, using
+/// numpy's `.npy` format, as a one-dimensional structured array with one
+/// field of 32-bit integers for each of the variables in the indexes.
+pub(crate) fn write_indexes<W: Write>(writer: &mut W, indexes: &Indexes, range: Range<usize>) -> Result<(), Error> {
+    let fields = indexes.names().iter()
+        .map(|name| format!("('{}', '<i4')", name))
+        .collect::<Vec<_>>();
+    let descr = format!("[{}]", fields.join(", "));
+    let shape = format!("({},)", range.len());
+    write_header(writer, &descr, &shape)?;
+
+    for i in range {
+        for index in &indexes[i] {
+            writer.write_all(&index.i32().to_le_bytes())?;
+        }
+    }
+    return Ok(());
+}
+
+impl Indexes {
+    /// Write these indexes to `path`, using numpy's `.npy` format. The indexes
+    /// are stored as a one-dimensional structured array, with one field of
+    /// 32-bit integers for each of the variables in the indexes.
+    pub fn write_npy(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let path = path.as_ref();
+        let file = std::fs::File::create(path).with_context(|| format!(
+            "failed to create '{}'", path.display()
+        ))?;
+        let mut writer = std::io::BufWriter::new(file);
+        write_indexes(&mut writer, self, 0..self.count())?;
+        writer.flush()?;
+        return Ok(());
+    }
+}
+
+impl Descriptor {
+    /// Write all the arrays in this descriptor to separate `.npy` files in
+    /// `directory`, which is created if needed. The files are named
+    /// `values.npy`, `samples.npy` and `features.npy`, as well as
+    /// `gradients.npy` and `gradients_samples.npy` if this descriptor contains
+    /// gradients. Lazy gradients are computed before writing the files.
+    pub fn write_npy(&self, directory: impl AsRef<Path>) -> Result<(), Error> {
+        let directory = directory.as_ref();
+        std::fs::create_dir_all(directory)?;
+
+        let descriptor = self.with_computed_gradients()?;
+        let descriptor = &*descriptor;
+
+        write_npy(directory.join("values.npy"), descriptor.values.view())?;
+        descriptor.samples.write_npy(directory.join("samples.npy"))?;
+        descriptor.features.write_npy(directory.join("features.npy"))?;
+
+        if let Some(ref gradients) = descriptor.gradients {
+            write_npy(directory.join("gradients.npy"), gradients.view())?;
+        }
+
+        if let Some(ref gradients_samples) = descriptor.gradients_samples {
+            gradients_samples.write_npy(directory.join("gradients_samples.npy"))?;
+        }
+
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::array;
+
+    use crate::descriptor::{IndexesBuilder, IndexValue};
+
+    fn header(data: &[u8]) -> &str {
+        assert_eq!(&data[..8], b"\x93NUMPY\x01\x00");
+        let len = u16::from_le_bytes([data[8], data[9]]) as usize;
+        assert_eq!((10 + len) % 64, 0);
+        return std::str::from_utf8(&data[10..10 + len]).unwrap();
+    }
+
+    #[test]
+    fn values() {
+        let path = std::env::temp_dir().join(format!("rascaline-npy-values-{}.npy", std::process::id()));
+        let array = array![[1.0, 2.0, 3.0], [4.0, 5.0, 6.0]];
+        super::write_npy(&path, array.t()).unwrap();
+
+        let data = std::fs::read(&path).unwrap();
+        let header = header(&data);
+        assert_eq!(header.trim_end(), "{'descr': '<f8', 'fortran_order': False, 'shape': (3, 2), }");
+        assert!(header.ends_with('\n'));
+
+        let start = 10 + header.len();
+        let values = data[start..].chunks_exact(8)
+            .map(|chunk| f64::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3], chunk[4], chunk[5], chunk[6], chunk[7]]))
+            .collect::<Vec<_>>();
+        assert_eq!(values, [1.0, 4.0, 2.0, 5.0, 3.0, 6.0]);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn indexes() {
+        let path = std::env::temp_dir().join(format!("rascaline-npy-indexes-{}.npy", std::process::id()));
+        let mut indexes = IndexesBuilder::new(vec!["structure", "center"]);
+        indexes.add(&[IndexValue::from(0), IndexValue::from(1)]);
+        indexes.add(&[IndexValue::from(2), IndexValue::from(-3)]);
+        indexes.finish().write_npy(&path).unwrap();
+
+        let data = std::fs::read(&path).unwrap();
+        let header = header(&data);
+        assert_eq!(
+            header.trim_end(),
+            "{'descr': [('structure', '<i4'), ('center', '<i4')], 'fortran_order': False, 'shape': (2,), }"
+        );
+
+        let start = 10 + header.len();
+        let values = data[start..].chunks_exact(4)
+            .map(|chunk| i32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+            .collect::<Vec<_>>();
+        assert_eq!(values, [0, 1, 2, -3]);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,317 @@
+use ndarray::{Array2, ArrayView2, s};
+
+use crate::Error;
+use super::{Descriptor, IndexesBuilder, IndexValue};
+use super::descriptor::with_layout;
+
+/// Functions working with descriptors containing one sample per atom, in the
+/// layout used by molecular dynamics engines such as LAMMPS.
+impl Descriptor {
+    /// Check that the samples of this descriptor are `[structure, center]`,
+    /// i.e. that this descriptor contains one sample per atom.
+    fn check_per_atom_samples(&self, function: &str) -> Result<(), Error> {
+        if self.samples.names() != ["structure", "center"] {
+            return Err(Error::InvalidParameter(format!(
+                "{} requires samples to be [structure, center], got [{}]. \
+                You might need to call `densify` first.",
+                function, self.samples.names().join(", ")
+            )));
+        }
+        return Ok(());
+    }
+
+    /// Get the values of this descriptor for all the atoms in the given
+    /// `structure`, as an array of size `n_atoms x features.count()` where the
+    /// row `i` contains the values for the atom `i`.
+    ///
+    /// Atoms without a corresponding sample (for example when only computing
+    /// the descriptor for some of the atoms) are filled with zeros.
+    ///
+    /// This requires the samples to be `[structure, center]`, which can be
+    /// achieved by calling [`Descriptor::densify`] on any additional variable
+    /// (such as `species_center` or `species_neighbor`).
+    pub fn per_atom_values(&self, structure: usize, n_atoms: usize) -> Result<Array2<f64>, Error> {
+        self.check_per_atom_samples("Descriptor::per_atom_values")?;
+
+        let mut values = Array2::zeros((n_atoms, self.features.count()));
+        for (sample_i, sample) in self.samples.iter().enumerate() {
+            if sample[0].usize() != structure {
+                continue;
+            }
+
+            let center = sample[1].usize();
+            if center >= n_atoms {
+                return Err(Error::InvalidParameter(format!(
+                    "got a sample for atom {}, but n_atoms is {} in Descriptor::per_atom_values",
+                    center, n_atoms
+                )));
+            }
+
+            values.slice_mut(s![center, ..]).assign(&self.values.slice(s![sample_i, ..]));
+        }
+
+        return Ok(values);
+    }
+
+    /// Scatter the gradients of this descriptor for the given `structure` onto
+    /// the atoms, weighting each feature of each atom with `weights`.
+    ///
+    /// `weights` should have the shape `n_atoms x features.count()`, and
+    /// typically contains the derivative of the energy with respect to the
+    /// descriptor values for each atom. This function then returns an array of
+    /// shape `n_atoms x 3` containing
+    ///
+    /// ```text
+    /// output[j, α] = Σ_i Σ_f weights[i, f] * ∂values[i, f] / ∂r_j[α]
+    /// ```
+    ///
+    /// which is the gradient of the energy with respect to the positions, i.e.
+    /// the opposite of the forces acting on the atoms.
+    ///
+    /// This requires the samples to be `[structure, center]`, see
+    /// [`Descriptor::per_atom_values`].
+    pub fn scatter_gradients(&self, structure: usize, weights: ArrayView2<f64>) -> Result<Array2<f64>, Error> {
+        self.check_per_atom_samples("Descriptor::scatter_gradients")?;
+        self.check_no_pending_gradients("Descriptor::scatter_gradients")?;
+
+        let (gradients, gradients_samples) = match (&self.gradients, &self.gradients_samples) {
+            (Some(gradients), Some(gradients_samples)) => (gradients, gradients_samples),
+            _ => {
+                return Err(Error::InvalidParameter(
+                    "this descriptor does not contain gradients in Descriptor::scatter_gradients".into()
+                ));
+            }
+        };
+
+        let n_atoms = weights.nrows();
+        if weights.ncols() != self.features.count() {
+            return Err(Error::InvalidParameter(format!(
+                "expected weights to have {} columns in Descriptor::scatter_gradients, got {}",
+                self.features.count(), weights.ncols()
+            )));
+        }
+
+        let mut output = Array2::zeros((n_atoms, 3));
+        for (gradient_i, gradient_sample) in gradients_samples.iter().enumerate() {
+            let sample = &self.samples[gradient_sample[0].usize()];
+            if sample[0].usize() != structure {
+                continue;
+            }
+
+            let center = sample[1].usize();
+            let atom = gradient_sample[1].usize();
+            let spatial = gradient_sample[2].usize();
+            if center >= n_atoms || atom >= n_atoms {
+                return Err(Error::InvalidParameter(format!(
+                    "got a gradient for atoms {} and {}, but weights are only \
+                    given for {} atoms in Descriptor::scatter_gradients",
+                    center, atom, n_atoms
+                )));
+            }
+
+            let gradient = gradients.slice(s![gradient_i, ..]);
+            output[[atom, spatial]] += gradient.dot(&weights.slice(s![center, ..]));
+        }
+
+        return Ok(output);
+    }
+
+    /// Update this descriptor with the values and gradients from `partial`,
+    /// which contains a subset of the samples of this descriptor.
+    ///
+    /// This can be used when only some of the atoms moved since the last
+    /// calculation: the descriptor only needs to be re-computed for the
+    /// samples affected by the move (see
+    /// [`crate::systems::affected_centers`]), using
+    /// `CalculationOptions::selected_samples`, and then merged into the full
+    /// descriptor with this function.
+    pub fn update(&mut self, partial: &Descriptor) -> Result<(), Error> {
+        partial.check_no_pending_gradients("Descriptor::update")?;
+        self.compute_pending_gradients()?;
+
+        if partial.samples.names() != self.samples.names() {
+            return Err(Error::InvalidParameter(format!(
+                "can not update a descriptor with samples [{}] using a descriptor with samples [{}]",
+                self.samples.names().join(", "), partial.samples.names().join(", ")
+            )));
+        }
+
+        if partial.features != self.features {
+            return Err(Error::InvalidParameter(
+                "can not update a descriptor using a descriptor with different features".into()
+            ));
+        }
+
+        if self.hyper_gradients.is_some() && partial.hyper_parameters != self.hyper_parameters {
+            return Err(Error::InvalidParameter(
+                "can not update a descriptor with hyper-parameters gradients using a descriptor with different hyper-parameters gradients".into()
+            ));
+        }
+
+        if self.gradients.is_some() && partial.gradients.is_none() {
+            return Err(Error::InvalidParameter(
+                "can not update a descriptor with gradients using a descriptor without gradients".into()
+            ));
+        }
+
+        // position of each partial sample in this descriptor
+        let mut updated = vec![None; self.samples.count()];
+        for (partial_i, sample) in partial.samples.iter().enumerate() {
+            let sample_i = self.samples.position(sample).ok_or_else(|| Error::InvalidParameter(format!(
+                "sample {:?} is not part of the descriptor being updated", sample
+            )))?;
+            updated[sample_i] = Some(partial_i);
+        }
+
+        for (sample_i, partial_i) in updated.iter().enumerate() {
+            if let Some(partial_i) = partial_i {
+                self.values.slice_mut(s![sample_i, ..]).assign(&partial.values.slice(s![*partial_i, ..]));
+
+                if let Some(ref mut hyper_gradients) = self.hyper_gradients {
+                    let partial_hyper_gradients = partial.hyper_gradients.as_ref().expect("missing hyper-parameters gradients");
+                    hyper_gradients.slice_mut(s![.., sample_i, ..]).assign(&partial_hyper_gradients.slice(s![.., *partial_i, ..]));
+                }
+            }
+        }
+
+        let (gradients, gradients_samples) = match (&self.gradients, &self.gradients_samples) {
+            (Some(gradients), Some(gradients_samples)) => (gradients, gradients_samples),
+            _ => return Ok(()),
+        };
+
+        let partial_gradients = partial.gradients.as_ref().expect("missing gradients");
+        let partial_gradients_samples = partial.gradients_samples.as_ref().expect("missing gradients samples");
+
+        // the number of gradient rows for an updated sample can change (e.g.
+        // when an atom enters or leaves the cutoff), so we rebuild the full
+        // gradients array, going through samples in order
+        let mut rows_by_sample = vec![Vec::new(); self.samples.count()];
+        for (gradient_i, gradient_sample) in gradients_samples.iter().enumerate() {
+            rows_by_sample[gradient_sample[0].usize()].push(gradient_i);
+        }
+
+        let mut partial_rows_by_sample = vec![Vec::new(); partial.samples.count()];
+        for (gradient_i, gradient_sample) in partial_gradients_samples.iter().enumerate() {
+            partial_rows_by_sample[gradient_sample[0].usize()].push(gradient_i);
+        }
+
+        let mut new_gradients_samples = IndexesBuilder::new(vec!["sample", "atom", "spatial"]);
+        let mut new_gradients = Vec::new();
+        for (sample_i, partial_i) in updated.iter().enumerate() {
+            let (rows, samples, array) = match partial_i {
+                Some(partial_i) => (&partial_rows_by_sample[*partial_i], partial_gradients_samples, partial_gradients),
+                None => (&rows_by_sample[sample_i], gradients_samples, gradients),
+            };
+
+            for &row in rows {
+                let gradient_sample = &samples[row];
+                new_gradients_samples.add(&[IndexValue::from(sample_i), gradient_sample[1], gradient_sample[2]]);
+                new_gradients.extend(array.slice(s![row, ..]).iter());
+            }
+        }
+
+        let new_gradients_samples = new_gradients_samples.finish();
+        let shape = (new_gradients_samples.count(), self.features.count());
+        let new_gradients = Array2::from_shape_vec(shape, new_gradients).expect("invalid gradients shape");
+        self.gradients = Some(with_layout(new_gradients, self.layout));
+        self.gradients_samples = Some(new_gradients_samples);
+
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::Array2;
+
+    use crate::{Calculator, CalculationOptions, Descriptor, SelectedIndexes, System};
+    use crate::descriptor::{IndexesBuilder, IndexValue};
+    use crate::systems::test_utils::test_system;
+
+    fn calculator() -> Calculator {
+        Calculator::new("dummy_calculator", r#"{
+            "cutoff": 1.5,
+            "delta": 3,
+            "name": "",
+            "gradients": true
+        }"#.to_owned()).unwrap()
+    }
+
+    #[test]
+    fn per_atom_values() {
+        let mut calculator = calculator();
+        let mut systems = vec![Box::new(test_system("water")) as Box<dyn System>, Box::new(test_system("CH"))];
+
+        let mut descriptor = Descriptor::new();
+        calculator.compute(&mut systems, &mut descriptor, Default::default()).unwrap();
+
+        let values = descriptor.per_atom_values(1, 2).unwrap();
+        assert_eq!(values.shape(), [2, 2]);
+        assert_eq!(values.row(0), descriptor.values.row(3));
+        assert_eq!(values.row(1), descriptor.values.row(4));
+
+        let error = descriptor.per_atom_values(0, 2).unwrap_err();
+        assert_eq!(error.to_string(), "invalid parameter: got a sample for atom 2, but n_atoms is 2 in Descriptor::per_atom_values");
+    }
+
+    #[test]
+    fn scatter_gradients() {
+        let mut calculator = calculator();
+        let mut systems = vec![Box::new(test_system("water")) as Box<dyn System>];
+
+        let mut descriptor = Descriptor::new();
+        calculator.compute(&mut systems, &mut descriptor, Default::default()).unwrap();
+
+        let weights = ndarray::array![[1.0, 2.0], [-1.0, 0.5], [0.3, 0.2]];
+        let scattered = descriptor.scatter_gradients(0, weights.view()).unwrap();
+
+        let gradients = descriptor.gradients.as_ref().unwrap();
+        let mut expected = Array2::<f64>::zeros((3, 3));
+        for (row, gradient_sample) in descriptor.gradients_samples.as_ref().unwrap().iter().enumerate() {
+            let center = descriptor.samples[gradient_sample[0].usize()][1].usize();
+            let atom = gradient_sample[1].usize();
+            let spatial = gradient_sample[2].usize();
+            for feature in 0..2 {
+                expected[[atom, spatial]] += weights[[center, feature]] * gradients[[row, feature]];
+            }
+        }
+        assert_eq!(scattered, expected);
+
+        let error = descriptor.scatter_gradients(0, ndarray::array![[1.0]].view()).unwrap_err();
+        assert_eq!(error.to_string(), "invalid parameter: expected weights to have 2 columns in Descriptor::scatter_gradients, got 1");
+    }
+
+    #[test]
+    fn update() {
+        let mut calculator = calculator();
+        let mut system = test_system("water");
+        let mut systems = vec![Box::new(system.clone()) as Box<dyn System>];
+
+        let mut descriptor = Descriptor::new();
+        calculator.compute(&mut systems, &mut descriptor, Default::default()).unwrap();
+
+        // move the first hydrogen away from the oxygen
+        system.positions_mut()[1][2] -= 1.0;
+        let mut systems = vec![Box::new(system) as Box<dyn System>];
+
+        let mut expected = Descriptor::new();
+        calculator.compute(&mut systems, &mut expected, Default::default()).unwrap();
+
+        let mut selected = IndexesBuilder::new(vec!["structure", "center"]);
+        selected.add(&[IndexValue::from(0), IndexValue::from(0)]);
+        selected.add(&[IndexValue::from(0), IndexValue::from(1)]);
+        let options = CalculationOptions {
+            selected_samples: SelectedIndexes::Subset(selected.finish()),
+            ..Default::default()
+        };
+
+        let mut partial = Descriptor::new();
+        calculator.compute(&mut systems, &mut partial, options).unwrap();
+
+        descriptor.update(&partial).unwrap();
+        assert_eq!(descriptor.values, expected.values);
+        assert_eq!(descriptor.samples, expected.samples);
+        assert_eq!(descriptor.gradients_samples, expected.gradients_samples);
+        assert_eq!(descriptor.gradients, expected.gradients);
+    }
+}
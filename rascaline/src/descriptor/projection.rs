@@ -0,0 +1,185 @@
+use ndarray::{Array2, Array3};
+
+use crate::Error;
+use crate::math::Random;
+use super::{Descriptor, IndexesBuilder, IndexValue};
+use super::descriptor::with_layout;
+
+/// Kind of random matrix used by [`Descriptor::random_projection`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RandomProjection {
+    /// Dense projection, with all entries of the projection matrix drawn from
+    /// a normal distribution with variance `1 / width`
+    Gaussian,
+    /// Sparse projection (from D. Achlioptas, J. Comput. Syst. Sci. 66, 2003),
+    /// with entries of the projection matrix equal to `±sqrt(3 / width)` with
+    /// probability 1/6 each, and 0 with probability 2/3
+    Sparse,
+}
+
+impl RandomProjection {
+    /// Generate the `n_features x width` projection matrix, using a random
+    /// number generator initialized with `seed`
+    fn matrix(self, n_features: usize, width: usize, seed: u64) -> Array2<f64> {
+        let mut random = Random::new(seed);
+        let scale = 1.0 / f64::sqrt(width as f64);
+
+        let mut matrix = Array2::zeros((n_features, width));
+        match self {
+            RandomProjection::Gaussian => {
+                matrix.mapv_inplace(|_| scale * normal(&mut random));
+            }
+            RandomProjection::Sparse => {
+                let value = f64::sqrt(3.0) * scale;
+                matrix.mapv_inplace(|_| {
+                    let uniform = random.next_f64();
+                    if uniform < 1.0 / 6.0 {
+                        -value
+                    } else if uniform < 2.0 / 6.0 {
+                        value
+                    } else {
+                        0.0
+                    }
+                });
+            }
+        }
+
+        return matrix;
+    }
+}
+
+/// Draw a number from the standard normal distribution, using the Box-Muller
+/// transform
+fn normal(random: &mut Random) -> f64 {
+    // use 1 - x to get a value in (0, 1] and avoid taking the log of 0
+    let u1 = 1.0 - random.next_f64();
+    let u2 = random.next_f64();
+    return f64::sqrt(-2.0 * f64::ln(u1)) * f64::cos(2.0 * std::f64::consts::PI * u2);
+}
+
+impl Descriptor {
+    /// Compress the features of this descriptor to `width` new features, by
+    /// multiplying the values and gradients with a fixed random `projection`
+    /// matrix. The projection approximately preserves the distances and dot
+    /// products between samples (Johnson-Lindenstrauss lemma), and can be used
+    /// to get a cheap compressed representation when computing descriptors
+    /// with a very large number of features.
+    ///
+    /// The projection matrix is generated from `seed`, and is the same for all
+    /// descriptors with the same number of features: descriptors projected
+    /// with the same `width`, `projection` and `seed` can be compared with one
+    /// another. After the projection, the features contain a single
+    /// `projection` variable, going from 0 to `width - 1`.
+    #[time_graph::instrument(name = "Descriptor::random_projection")]
+    pub fn random_projection(&mut self, width: usize, projection: RandomProjection, seed: u64) -> Result<(), Error> {
+        if width == 0 {
+            return Err(Error::InvalidParameter(
+                "the width of a random projection must be at least 1".into()
+            ));
+        }
+
+        self.compute_pending_gradients()?;
+
+        let matrix = projection.matrix(self.features.count(), width, seed);
+
+        self.values = with_layout(self.values.dot(&matrix), self.layout);
+        if let Some(ref mut gradients) = self.gradients {
+            *gradients = with_layout(gradients.dot(&matrix), self.layout);
+        }
+
+        if let Some(ref mut hyper_gradients) = self.hyper_gradients {
+            let mut projected = Array3::zeros((hyper_gradients.shape()[0], hyper_gradients.shape()[1], width));
+            for (mut projected, hyper_gradient) in projected.outer_iter_mut().zip(hyper_gradients.outer_iter()) {
+                projected.assign(&hyper_gradient.dot(&matrix));
+            }
+            *hyper_gradients = projected;
+        }
+
+        let mut features = IndexesBuilder::new(vec!["projection"]);
+        for i in 0..width {
+            features.add(&[IndexValue::from(i)]);
+        }
+        self.features = features.finish();
+
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use crate::{Calculator, Descriptor};
+    use crate::systems::test_utils::test_systems;
+
+    use super::RandomProjection;
+
+    fn compute() -> Descriptor {
+        let mut calculator = Calculator::new("soap_power_spectrum", r#"{
+            "cutoff": 3.5,
+            "max_radial": 4,
+            "max_angular": 4,
+            "atomic_gaussian_width": 0.3,
+            "radial_basis": {"Gto": {}},
+            "cutoff_function": {"ShiftedCosine": {"width": 0.5}},
+            "gradients": true
+        }"#.to_owned()).unwrap();
+
+        let mut systems = test_systems(&["water", "methane"]);
+        let mut descriptor = Descriptor::new();
+        calculator.compute(&mut systems, &mut descriptor, Default::default()).unwrap();
+        descriptor.densify(&["species_neighbor_1", "species_neighbor_2"], None).unwrap();
+        return descriptor;
+    }
+
+    #[test]
+    fn projection() {
+        for &projection in &[RandomProjection::Gaussian, RandomProjection::Sparse] {
+            let reference = compute();
+            let n_features = reference.features.count();
+
+            let mut descriptor = reference.clone();
+            descriptor.random_projection(40, projection, 12).unwrap();
+            assert_eq!(descriptor.features.names(), ["projection"]);
+            assert_eq!(descriptor.features.count(), 40);
+            assert_eq!(descriptor.samples, reference.samples);
+            assert_eq!(descriptor.gradients_samples, reference.gradients_samples);
+
+            // the projection is linear, and applied consistently to values
+            // and gradients
+            let matrix = projection.matrix(n_features, 40, 12);
+            assert_relative_eq!(descriptor.values, reference.values.dot(&matrix), max_relative = 1e-12);
+            assert_relative_eq!(
+                descriptor.gradients.as_ref().unwrap(),
+                &reference.gradients.as_ref().unwrap().dot(&matrix),
+                max_relative = 1e-12
+            );
+
+            // the same seed gives the same projection
+            let mut other = reference.clone();
+            other.random_projection(40, projection, 12).unwrap();
+            assert_eq!(other.values, descriptor.values);
+        }
+    }
+
+    #[test]
+    fn preserve_norm() {
+        // with a large width, the norm is preserved on average
+        let matrix = RandomProjection::Gaussian.matrix(50, 2000, 7);
+        let norms = matrix.outer_iter().map(|row| row.dot(&row)).collect::<Vec<_>>();
+        let mean = norms.iter().sum::<f64>() / norms.len() as f64;
+        assert!((mean - 1.0).abs() < 0.05);
+
+        let matrix = RandomProjection::Sparse.matrix(50, 2000, 7);
+        let norms = matrix.outer_iter().map(|row| row.dot(&row)).collect::<Vec<_>>();
+        let mean = norms.iter().sum::<f64>() / norms.len() as f64;
+        assert!((mean - 1.0).abs() < 0.05);
+    }
+
+    #[test]
+    fn zero_width() {
+        let mut descriptor = compute();
+        let error = descriptor.random_projection(0, RandomProjection::Gaussian, 0).unwrap_err();
+        assert_eq!(error.to_string(), "invalid parameter: the width of a random projection must be at least 1");
+    }
+}
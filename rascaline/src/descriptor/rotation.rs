@@ -0,0 +1,242 @@
+use std::collections::BTreeMap;
+
+use ndarray::{Array2, s};
+
+use crate::{Error, Matrix3};
+use crate::math::WignerD;
+use super::{Descriptor, IndexValue};
+
+impl Descriptor {
+    /// Apply the rigid `rotation` to this equivariant descriptor, updating
+    /// the values and gradients to the ones that would be obtained by
+    /// computing the descriptor on the rotated structures.
+    ///
+    /// The features of this descriptor must contain the `l` and `m`
+    /// variables (for example the features of the spherical expansion), and
+    /// all the values of `m` from `-l` to `l` must be present for each
+    /// combination of the other feature variables. Each set of `2l + 1`
+    /// features is then multiplied by the corresponding Wigner-D matrix
+    /// (see [`WignerD`]).
+    ///
+    /// This can be used to check the equivariance of a representation, or to
+    /// augment a dataset with rotated structures without re-computing the
+    /// descriptor.
+    pub fn rotate(&mut self, rotation: &Matrix3) -> Result<(), Error> {
+        self.compute_pending_gradients()?;
+
+        let identity = *rotation * rotation.transposed() - Matrix3::one();
+        if identity.norm() > 1e-9 || (rotation.determinant() - 1.0).abs() > 1e-9 {
+            return Err(Error::InvalidParameter(
+                "Descriptor::rotate requires a proper rotation matrix".into()
+            ));
+        }
+
+        let names = self.features.names();
+        let l_variable = names.iter().position(|&name| name == "l");
+        let m_variable = names.iter().position(|&name| name == "m");
+        let (l_variable, m_variable) = match (l_variable, m_variable) {
+            (Some(l), Some(m)) => (l, m),
+            _ => {
+                return Err(Error::InvalidParameter(format!(
+                    "Descriptor::rotate requires 'l' and 'm' in the features, got [{}]",
+                    names.join(", ")
+                )));
+            }
+        };
+
+        // group features by all variables except m
+        let mut groups = BTreeMap::<Vec<IndexValue>, Vec<Option<usize>>>::new();
+        let mut max_angular = 0;
+        for (feature_i, feature) in self.features.iter().enumerate() {
+            let l = feature[l_variable].usize();
+            let m = feature[m_variable].isize();
+            if m.abs() > l as isize {
+                return Err(Error::InvalidParameter(format!(
+                    "invalid feature with l={} and m={} in Descriptor::rotate", l, m
+                )));
+            }
+            max_angular = max_angular.max(l);
+
+            let mut key = feature.to_vec();
+            key.remove(m_variable);
+            let group = groups.entry(key).or_insert_with(|| vec![None; 2 * l + 1]);
+            group[(m + l as isize) as usize] = Some(feature_i);
+        }
+
+        let mut groups_features = Vec::with_capacity(groups.len());
+        for (key, group) in groups {
+            let l = key[if l_variable < m_variable { l_variable } else { l_variable - 1 }].usize();
+            let features = group.into_iter().collect::<Option<Vec<_>>>().ok_or_else(|| Error::InvalidParameter(format!(
+                "Descriptor::rotate requires all values of m for l={}, some are missing in the features", l
+            )))?;
+            groups_features.push((l, features));
+        }
+
+        let gradients_rows = self.gradients_spatial_rows()?;
+
+        let wigner = WignerD::new(max_angular, rotation);
+        let rotate_features = |array: &mut Array2<f64>| {
+            for (l, features) in &groups_features {
+                let matrix = wigner.matrix(*l);
+                for row in 0..array.nrows() {
+                    let values = features.iter().map(|&f| array[[row, f]]).collect::<Vec<_>>();
+                    for (mu, &feature) in features.iter().enumerate() {
+                        array[[row, feature]] = matrix.slice(s![mu, ..]).iter()
+                            .zip(&values)
+                            .map(|(d, v)| d * v)
+                            .sum();
+                    }
+                }
+            }
+        };
+
+        rotate_features(&mut self.values);
+
+        if let Some(ref mut hyper_gradients) = self.hyper_gradients {
+            for mut hyper_gradient in hyper_gradients.outer_iter_mut() {
+                let mut rotated = hyper_gradient.to_owned();
+                rotate_features(&mut rotated);
+                hyper_gradient.assign(&rotated);
+            }
+        }
+
+        if let Some(ref mut gradients) = self.gradients {
+            rotate_features(gradients);
+
+            // the gradients with respect to the rotated positions are also
+            // rotated along the spatial dimension
+            rotate_spatial_rows(gradients, &gradients_rows, rotation);
+        }
+
+        return Ok(());
+    }
+
+    /// Rotate the gradients of this descriptor along the spatial dimension
+    /// only, leaving the values untouched. This is the transformation of the
+    /// gradients of an invariant descriptor when applying `rotation` to the
+    /// structures.
+    pub(crate) fn rotate_gradients_directions(&mut self, rotation: &Matrix3) -> Result<(), Error> {
+        let gradients_rows = self.gradients_spatial_rows()?;
+        if let Some(ref mut gradients) = self.gradients {
+            rotate_spatial_rows(gradients, &gradients_rows, rotation);
+        }
+        return Ok(());
+    }
+
+    /// Get the rows containing the x/y/z components of the gradients for each
+    /// (sample, atom) pair
+    fn gradients_spatial_rows(&self) -> Result<Vec<[usize; 3]>, Error> {
+        let mut gradients_rows = Vec::new();
+        if let Some(ref gradients_samples) = self.gradients_samples {
+            let mut rows = BTreeMap::<(usize, usize), [Option<usize>; 3]>::new();
+            for (row, gradient_sample) in gradients_samples.iter().enumerate() {
+                let key = (gradient_sample[0].usize(), gradient_sample[1].usize());
+                rows.entry(key).or_insert([None; 3])[gradient_sample[2].usize()] = Some(row);
+            }
+
+            for spatial_rows in rows.values() {
+                match spatial_rows {
+                    [Some(x), Some(y), Some(z)] => gradients_rows.push([*x, *y, *z]),
+                    _ => {
+                        return Err(Error::InvalidParameter(
+                            "Descriptor::rotate requires gradients along x, y and z for all atoms".into()
+                        ));
+                    }
+                }
+            }
+        }
+        return Ok(gradients_rows);
+    }
+}
+
+/// Mix the x/y/z rows of `gradients` given in `spatial_rows` with `rotation`
+fn rotate_spatial_rows(gradients: &mut Array2<f64>, spatial_rows: &[[usize; 3]], rotation: &Matrix3) {
+    let original = gradients.clone();
+    for rows in spatial_rows {
+        for (alpha, &row) in rows.iter().enumerate() {
+            let mut new = gradients.slice_mut(s![row, ..]);
+            new.fill(0.0);
+            for (beta, &other) in rows.iter().enumerate() {
+                new.scaled_add(rotation[alpha][beta], &original.slice(s![other, ..]));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use crate::{Calculator, Descriptor, Matrix3, SimpleSystem, System, Vector3D};
+    use crate::systems::UnitCell;
+    use crate::systems::test_utils::test_system;
+
+    fn water(rotation: &Matrix3) -> SimpleSystem {
+        let mut system = SimpleSystem::new(UnitCell::infinite());
+        system.add_atom(8, *rotation * Vector3D::new(0.0, 0.0, 0.0));
+        system.add_atom(1, *rotation * Vector3D::new(0.0, 0.75545, -0.58895));
+        system.add_atom(1, *rotation * Vector3D::new(0.0, -0.75545, -0.58895));
+        return system;
+    }
+
+    #[test]
+    fn equivariance() {
+        let mut calculator = Calculator::new("spherical_expansion", r#"{
+            "cutoff": 3.5,
+            "max_radial": 3,
+            "max_angular": 4,
+            "atomic_gaussian_width": 0.4,
+            "radial_basis": {"Gto": {}},
+            "cutoff_function": {"ShiftedCosine": {"width": 0.5}},
+            "radial_scaling": {"None": {}},
+            "gradients": true
+        }"#.to_owned()).unwrap();
+
+        let rotation = Matrix3::rotation(&Vector3D::new(1.0, -2.0, 0.5), 1.2);
+
+        let mut systems = vec![Box::new(water(&Matrix3::one())) as Box<dyn System>];
+        let mut descriptor = Descriptor::new();
+        calculator.compute(&mut systems, &mut descriptor, Default::default()).unwrap();
+
+        let mut systems = vec![Box::new(water(&rotation)) as Box<dyn System>];
+        let mut expected = Descriptor::new();
+        calculator.compute(&mut systems, &mut expected, Default::default()).unwrap();
+
+        descriptor.rotate(&rotation).unwrap();
+
+        assert_eq!(descriptor.samples, expected.samples);
+        for (value, expected) in descriptor.values.iter().zip(expected.values.iter()) {
+            assert_relative_eq!(value, expected, epsilon=1e-12, max_relative=1e-9);
+        }
+
+        assert_eq!(descriptor.gradients_samples, expected.gradients_samples);
+        let gradients = descriptor.gradients.as_ref().unwrap();
+        for (value, expected) in gradients.iter().zip(expected.gradients.as_ref().unwrap().iter()) {
+            assert_relative_eq!(value, expected, epsilon=1e-12, max_relative=1e-9);
+        }
+    }
+
+    #[test]
+    fn errors() {
+        let mut calculator = Calculator::new("dummy_calculator", r#"{
+            "cutoff": 1.5,
+            "delta": 3,
+            "name": "",
+            "gradients": false
+        }"#.to_owned()).unwrap();
+
+        let mut systems = vec![Box::new(test_system("water")) as Box<dyn System>];
+        let mut descriptor = Descriptor::new();
+        calculator.compute(&mut systems, &mut descriptor, Default::default()).unwrap();
+
+        let inversion = Matrix3::new([[-1.0, 0.0, 0.0], [0.0, -1.0, 0.0], [0.0, 0.0, -1.0]]);
+        let error = descriptor.rotate(&inversion).unwrap_err();
+        assert_eq!(error.to_string(), "invalid parameter: Descriptor::rotate requires a proper rotation matrix");
+
+        let error = descriptor.rotate(&Matrix3::one()).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "invalid parameter: Descriptor::rotate requires 'l' and 'm' in the features, got [index_delta, x_y_z]"
+        );
+    }
+}
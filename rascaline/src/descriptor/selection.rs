@@ -0,0 +1,341 @@
+use ndarray::{Array1, ArrayView1, Axis};
+use ndarray::parallel::prelude::*;
+
+use crate::Error;
+use crate::math::Random;
+use super::{Descriptor, Indexes, IndexesBuilder};
+
+/// Distance between samples used by [`Descriptor::farthest_point_sampling`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SampleDistance {
+    /// Euclidean distance between the values of the samples
+    Euclidean,
+    /// Cosine distance `1 - x·y / (|x| |y|)` between the values of the
+    /// samples. Samples with all values equal to zero are at a distance of 1
+    /// of all other samples.
+    Cosine,
+}
+
+impl SampleDistance {
+    /// Compute the distance between `a` and `b`, given their norms. For the
+    /// euclidean distance, this returns the squared distance, which is enough
+    /// to compare distances.
+    fn compute(self, a: ArrayView1<f64>, norm_a: f64, b: ArrayView1<f64>, norm_b: f64) -> f64 {
+        match self {
+            SampleDistance::Euclidean => {
+                let dot = a.dot(&b);
+                return f64::max(norm_a * norm_a + norm_b * norm_b - 2.0 * dot, 0.0);
+            }
+            SampleDistance::Cosine => {
+                if norm_a == 0.0 || norm_b == 0.0 {
+                    return 1.0;
+                }
+                return 1.0 - a.dot(&b) / (norm_a * norm_b);
+            }
+        }
+    }
+}
+
+/// How to split the structures of a descriptor between a training and a test
+/// set, see [`Descriptor::split_structures`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StructureSplit<'a> {
+    /// Put a `fraction` of the structures (rounded to the nearest integer) in
+    /// the training set, and the remaining structures in the test set. The
+    /// structures are shuffled with a pseudo-random number generator
+    /// initialized with `seed` before being split, so the same `seed` always
+    /// gives the same split.
+    Fraction {
+        fraction: f64,
+        seed: u64,
+    },
+    /// Put the given structures in the training set, and all the other
+    /// structures in the test set
+    Structures(&'a [usize]),
+}
+
+impl Descriptor {
+    /// Split the samples of this descriptor between a training and a test
+    /// set, keeping all the samples associated with the same structure in the
+    /// same set. The samples must contain a `structure` variable.
+    ///
+    /// This returns the training and test samples, in the same order as in
+    /// this descriptor. They can be used as `CalculationOptions::selected_samples`
+    /// to compute the corresponding subsets of the descriptor.
+    pub fn split_structures(&self, split: StructureSplit) -> Result<(Indexes, Indexes), Error> {
+        let names = self.samples.names();
+        let structure = names.iter().position(|&name| name == "structure").ok_or_else(|| Error::InvalidParameter(format!(
+            "can not split samples by structure, there is no 'structure' in the samples: [{}]",
+            names.join(", ")
+        )))?;
+
+        let mut structures = self.samples.iter()
+            .map(|sample| sample[structure].usize())
+            .collect::<Vec<_>>();
+        structures.sort_unstable();
+        structures.dedup();
+
+        let mut training = match split {
+            StructureSplit::Fraction { fraction, seed } => {
+                if !(0.0..=1.0).contains(&fraction) {
+                    return Err(Error::InvalidParameter(format!(
+                        "the fraction of structures in the training set must be between 0 and 1, got {}",
+                        fraction
+                    )));
+                }
+
+                let n_training = f64::round(fraction * structures.len() as f64) as usize;
+                let mut random = Random::new(seed);
+                random.shuffle(&mut structures);
+                structures[..n_training].to_vec()
+            }
+            StructureSplit::Structures(training) => {
+                for &value in training {
+                    if structures.binary_search(&value).is_err() {
+                        return Err(Error::InvalidParameter(format!(
+                            "structure {} is not part of the samples of this descriptor", value
+                        )));
+                    }
+                }
+                training.to_vec()
+            }
+        };
+
+        training.sort_unstable();
+
+        let mut training_samples = IndexesBuilder::new(names.clone());
+        let mut test_samples = IndexesBuilder::new(names);
+        for sample in self.samples.iter() {
+            if training.binary_search(&sample[structure].usize()).is_ok() {
+                training_samples.add(sample);
+            } else {
+                test_samples.add(sample);
+            }
+        }
+
+        return Ok((training_samples.finish(), test_samples.finish()));
+    }
+
+    /// Select `count` samples of this descriptor using farthest point sampling
+    /// (FPS) over the rows of the values, with the given `distance`. The
+    /// selected samples are returned in the order they were selected, and can
+    /// be used as `CalculationOptions::selected_samples`, for example to pick
+    /// the sparse points of a sparse kernel model.
+    ///
+    /// FPS starts from the `initial` sample (or, if `initial` is `None`, from
+    /// the sample farthest from the mean of all samples), and then repeatedly
+    /// selects the sample with the largest distance to all already selected
+    /// samples. Ties are resolved by picking the first sample, so the selection
+    /// is fully deterministic.
+    #[time_graph::instrument(name = "Descriptor::farthest_point_sampling")]
+    pub fn farthest_point_sampling(
+        &self,
+        count: usize,
+        distance: SampleDistance,
+        initial: Option<usize>,
+    ) -> Result<Indexes, Error> {
+        let n_samples = self.samples.count();
+        if count > n_samples {
+            return Err(Error::InvalidParameter(format!(
+                "can not select {} samples with farthest point sampling, the descriptor only contains {} samples",
+                count, n_samples
+            )));
+        }
+
+        let mut selected = IndexesBuilder::new(self.samples.names());
+        if count == 0 {
+            return Ok(selected.finish());
+        }
+
+        let norms = self.values.outer_iter().map(|row| row.dot(&row).sqrt()).collect::<Array1<f64>>();
+
+        let initial = if let Some(initial) = initial {
+            if initial >= n_samples {
+                return Err(Error::InvalidParameter(format!(
+                    "invalid initial sample {} for farthest point sampling, the descriptor only contains {} samples",
+                    initial, n_samples
+                )));
+            }
+            initial
+        } else {
+            let mean = self.values.mean_axis(Axis(0)).expect("descriptor should not be empty");
+            let mean_norm = mean.dot(&mean).sqrt();
+            let distances = self.values.outer_iter().zip(&norms).map(|(row, &norm)| {
+                distance.compute(row, norm, mean.view(), mean_norm)
+            }).collect::<Vec<_>>();
+            argmax(&distances)
+        };
+
+        let mut min_distances = vec![f64::INFINITY; n_samples];
+        let mut current = initial;
+        for _ in 0..count {
+            selected.add(&self.samples[current]);
+
+            let current_values = self.values.row(current);
+            let current_norm = norms[current];
+            self.values.axis_iter(Axis(0)).into_par_iter()
+                .zip(norms.as_slice().expect("norms should be contiguous").par_iter())
+                .zip(min_distances.par_iter_mut())
+                .for_each(|((row, &norm), min_distance)| {
+                    let distance = distance.compute(row, norm, current_values, current_norm);
+                    if distance < *min_distance {
+                        *min_distance = distance;
+                    }
+                });
+
+            // make sure already selected samples are not selected again, even
+            // if they have the same values as some other sample
+            min_distances[current] = f64::NEG_INFINITY;
+            current = argmax(&min_distances);
+        }
+
+        return Ok(selected.finish());
+    }
+}
+
+/// Get the index of the largest value in `values`, using the first one in
+/// case of ties.
+fn argmax(values: &[f64]) -> usize {
+    let mut max_i = 0;
+    for (i, &value) in values.iter().enumerate() {
+        if value > values[max_i] {
+            max_i = i;
+        }
+    }
+    return max_i;
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::descriptor::{IndexesBuilder, IndexValue};
+    use crate::Descriptor;
+
+    use super::{SampleDistance, StructureSplit};
+
+    fn test_descriptor(values: &[[f64; 2]]) -> Descriptor {
+        let mut samples = IndexesBuilder::new(vec!["structure", "center"]);
+        for i in 0..values.len() {
+            samples.add(&[IndexValue::from(0), IndexValue::from(i)]);
+        }
+
+        let mut features = IndexesBuilder::new(vec!["feature"]);
+        features.add(&[IndexValue::from(0)]);
+        features.add(&[IndexValue::from(1)]);
+
+        let mut descriptor = Descriptor::new();
+        descriptor.prepare(samples.finish(), features.finish());
+        for (i, row) in values.iter().enumerate() {
+            descriptor.values[[i, 0]] = row[0];
+            descriptor.values[[i, 1]] = row[1];
+        }
+        return descriptor;
+    }
+
+    fn centers(descriptor: &Descriptor, count: usize, distance: SampleDistance, initial: Option<usize>) -> Vec<usize> {
+        let selected = descriptor.farthest_point_sampling(count, distance, initial).unwrap();
+        assert_eq!(selected.names(), ["structure", "center"]);
+        return selected.iter().map(|sample| sample[1].usize()).collect();
+    }
+
+    #[test]
+    fn euclidean() {
+        let descriptor = test_descriptor(&[
+            [0.0, 0.0],
+            [0.1, 0.0],
+            [5.0, 0.0],
+            [0.0, 3.0],
+            [5.0, 0.1],
+        ]);
+
+        assert_eq!(centers(&descriptor, 3, SampleDistance::Euclidean, Some(0)), [0, 4, 3]);
+        assert_eq!(centers(&descriptor, 5, SampleDistance::Euclidean, Some(1)), [1, 4, 3, 0, 2]);
+        // the sample farthest from the mean is used by default
+        assert_eq!(centers(&descriptor, 2, SampleDistance::Euclidean, None), [3, 2]);
+        assert!(centers(&descriptor, 0, SampleDistance::Euclidean, None).is_empty());
+    }
+
+    #[test]
+    fn cosine() {
+        let descriptor = test_descriptor(&[
+            [1.0, 0.0],
+            [10.0, 0.0],
+            [0.0, 2.0],
+            [1.0, 1.0],
+        ]);
+
+        assert_eq!(centers(&descriptor, 3, SampleDistance::Cosine, Some(0)), [0, 2, 3]);
+    }
+
+    #[test]
+    fn duplicated_samples() {
+        let descriptor = test_descriptor(&[[1.0, 1.0], [1.0, 1.0], [1.0, 1.0]]);
+        assert_eq!(centers(&descriptor, 3, SampleDistance::Euclidean, None), [0, 1, 2]);
+    }
+
+    #[test]
+    fn errors() {
+        let descriptor = test_descriptor(&[[0.0, 0.0], [1.0, 0.0]]);
+
+        let error = descriptor.farthest_point_sampling(3, SampleDistance::Euclidean, None).unwrap_err();
+        assert_eq!(error.to_string(),
+            "invalid parameter: can not select 3 samples with farthest point \
+            sampling, the descriptor only contains 2 samples"
+        );
+
+        let error = descriptor.farthest_point_sampling(1, SampleDistance::Euclidean, Some(2)).unwrap_err();
+        assert_eq!(error.to_string(),
+            "invalid parameter: invalid initial sample 2 for farthest point \
+            sampling, the descriptor only contains 2 samples"
+        );
+    }
+
+    #[test]
+    fn split_structures() {
+        let mut samples = IndexesBuilder::new(vec!["structure", "center"]);
+        for structure in 0..10 {
+            for center in 0..3 {
+                samples.add(&[IndexValue::from(structure), IndexValue::from(center)]);
+            }
+        }
+        let mut features = IndexesBuilder::new(vec!["feature"]);
+        features.add(&[IndexValue::from(0)]);
+
+        let mut descriptor = Descriptor::new();
+        descriptor.prepare(samples.finish(), features.finish());
+
+        let structures = |samples: &crate::descriptor::Indexes| {
+            let mut structures = samples.iter().map(|sample| sample[0].usize()).collect::<Vec<_>>();
+            structures.dedup();
+            structures
+        };
+
+        let (training, test) = descriptor.split_structures(StructureSplit::Structures(&[7, 2])).unwrap();
+        assert_eq!(training.names(), ["structure", "center"]);
+        assert_eq!(training.count(), 6);
+        assert_eq!(structures(&training), [2, 7]);
+        assert_eq!(structures(&test), [0, 1, 3, 4, 5, 6, 8, 9]);
+
+        let split = StructureSplit::Fraction { fraction: 0.74, seed: 42 };
+        let (training, test) = descriptor.split_structures(split).unwrap();
+        assert_eq!(structures(&training).len(), 7);
+        assert_eq!(structures(&test).len(), 3);
+        assert_eq!(training.count() + test.count(), descriptor.samples.count());
+        for sample in test.iter() {
+            assert!(!structures(&training).contains(&sample[0].usize()));
+        }
+
+        // the same seed gives the same split
+        let (other, _) = descriptor.split_structures(split).unwrap();
+        assert_eq!(other, training);
+
+        let error = descriptor.split_structures(StructureSplit::Structures(&[12])).unwrap_err();
+        assert_eq!(error.to_string(), "invalid parameter: structure 12 is not part of the samples of this descriptor");
+
+        let split = StructureSplit::Fraction { fraction: 1.5, seed: 42 };
+        let error = descriptor.split_structures(split).unwrap_err();
+        assert_eq!(error.to_string(),
+            "invalid parameter: the fraction of structures in the training set \
+            must be between 0 and 1, got 1.5"
+        );
+    }
+}
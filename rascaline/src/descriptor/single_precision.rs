@@ -0,0 +1,28 @@
+use ndarray::Array2;
+
+use super::Indexes;
+
+/// A `SinglePrecisionDescriptor` contains the same data as a [`Descriptor`],
+/// with `values` and `gradients` stored in single precision (`f32`). It is
+/// created by `Calculator::compute_single_precision`, which runs the
+/// calculation in double precision on a few systems at the time and converts
+/// the result, so that the full double precision arrays are never allocated.
+///
+/// [`Descriptor`]: super::Descriptor
+#[derive(Clone, Debug)]
+pub struct SinglePrecisionDescriptor {
+    /// An array of size `samples.count()` by `features.count()`, containing the
+    /// representation of the atomistic systems.
+    pub values: Array2<f32>,
+    /// Metadata describing the samples (i.e. rows) in the `values` array
+    pub samples: Indexes,
+    /// An array of size `gradients_samples.count()` by `features.count()`,
+    /// containing the gradients of the representation with respect to the
+    /// atomic positions.
+    pub gradients: Option<Array2<f32>>,
+    /// Metadata describing the samples (i.e. rows) in the `gradients` array
+    pub gradients_samples: Option<Indexes>,
+    /// Metadata describing the features (i.e. columns) in both the `values` and
+    /// `gradients` array
+    pub features: Indexes,
+}
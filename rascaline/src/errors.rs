@@ -7,10 +7,14 @@ pub enum Error {
     InvalidParameter(String),
     /// Error while serializing/deserializing data
     Json(serde_json::Error),
+    /// Error while serializing/deserializing data in the binary CBOR format
+    Cbor(serde_cbor::Error),
     /// Error due to C strings containing non-utf8 data
     Utf8(Utf8Error),
     /// Error related to reading files with chemfiles
     Chemfiles(String),
+    /// Error while reading or writing files
+    Io(std::io::Error),
     /// Errors coming from external callbacks, typically inside the System
     /// implementation
     External {
@@ -23,6 +27,56 @@ pub enum Error {
     /// Error used for failed internal consistency check and panics, i.e. bugs
     /// in rascaline.
     Internal(String),
+    /// The calculation was cancelled by the user, see
+    /// `Calculator::cancellation_handle`
+    Cancelled,
+    /// Another error, with additional context about where it happened (which
+    /// calculator, structure, file, ...). Use [`Error::root`] to get the
+    /// underlying error.
+    Context {
+        context: String,
+        source: Box<Error>,
+    },
+}
+
+impl Error {
+    /// Add `context` to this error, describing what was being done when the
+    /// error happened.
+    pub fn context(self, context: impl Into<String>) -> Error {
+        Error::Context {
+            context: context.into(),
+            source: Box::new(self),
+        }
+    }
+
+    /// Get the root cause of this error, skipping over any context.
+    pub fn root(&self) -> &Error {
+        let mut error = self;
+        while let Error::Context { source, .. } = error {
+            error = source;
+        }
+        return error;
+    }
+}
+
+/// Extension trait to add context to the error in a `Result`
+pub trait ErrorContext<T> {
+    /// Add `context` to the error in this result, if any
+    fn context(self, context: impl Into<String>) -> Result<T, Error>;
+
+    /// Add context to the error in this result, if any. The context is only
+    /// created by calling `context` when there is an error.
+    fn with_context<F: FnOnce() -> String>(self, context: F) -> Result<T, Error>;
+}
+
+impl<T, E> ErrorContext<T> for Result<T, E> where E: Into<Error> {
+    fn context(self, context: impl Into<String>) -> Result<T, Error> {
+        return self.map_err(|error| error.into().context(context));
+    }
+
+    fn with_context<F: FnOnce() -> String>(self, context: F) -> Result<T, Error> {
+        return self.map_err(|error| error.into().context(context()));
+    }
 }
 
 impl std::fmt::Display for Error {
@@ -30,11 +84,15 @@ impl std::fmt::Display for Error {
         match self {
             Error::InvalidParameter(e) => write!(f, "invalid parameter: {}", e),
             Error::Json(e) => write!(f, "json error: {}", e),
+            Error::Cbor(e) => write!(f, "cbor error: {}", e),
             Error::Utf8(e) => write!(f, "utf8 decoding error: {}", e),
             Error::Chemfiles(e) => write!(f, "chemfiles error: {}", e),
+            Error::Io(e) => write!(f, "io error: {}", e),
             Error::BufferSize(e) => write!(f, "buffer is not big enough: {}", e),
             Error::External{status, message} => write!(f, "error from external code (status {}): {}", status, message),
             Error::Internal(e) => write!(f, "internal error: {}", e),
+            Error::Cancelled => write!(f, "the calculation was cancelled"),
+            Error::Context{context, source} => write!(f, "{}: {}", context, source),
         }
     }
 }
@@ -46,9 +104,13 @@ impl std::error::Error for Error {
             Error::Internal(_) |
             Error::Chemfiles(_) |
             Error::BufferSize(_) |
-            Error::External{..} => None,
+            Error::External{..} |
+            Error::Cancelled => None,
             Error::Json(e) => Some(e),
+            Error::Cbor(e) => Some(e),
             Error::Utf8(e) => Some(e),
+            Error::Io(e) => Some(e),
+            Error::Context{source, ..} => Some(&**source),
         }
     }
 }
@@ -59,6 +121,18 @@ impl From<serde_json::Error> for Error {
     }
 }
 
+impl From<serde_cbor::Error> for Error {
+    fn from(error: serde_cbor::Error) -> Error {
+        Error::Cbor(error)
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(error: std::io::Error) -> Error {
+        Error::Io(error)
+    }
+}
+
 impl From<Utf8Error> for Error {
     fn from(error: Utf8Error) -> Error {
         Error::Utf8(error)
@@ -80,3 +154,35 @@ impl From<Box<dyn std::any::Any + Send + 'static>> for Error {
         Error::Internal(message)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error as StdError;
+    use super::{Error, ErrorContext};
+
+    #[test]
+    fn context() {
+        let result: Result<(), Error> = Err(Error::InvalidParameter("bad cutoff".into()));
+        let error = result
+            .context("failed to compute the neighbors list for structure 3")
+            .with_context(|| format!("failed to run calculator '{}'", "dummy_calculator"))
+            .unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "failed to run calculator 'dummy_calculator': failed to compute the \
+            neighbors list for structure 3: invalid parameter: bad cutoff"
+        );
+        assert!(matches!(error.root(), Error::InvalidParameter(_)));
+
+        let source = error.source().unwrap();
+        assert_eq!(
+            source.to_string(),
+            "failed to compute the neighbors list for structure 3: invalid parameter: bad cutoff"
+        );
+
+        let io = std::fs::File::open("not-there").context("failed to open 'not-there'").unwrap_err();
+        assert!(matches!(io.root(), Error::Io(_)));
+        assert!(io.to_string().starts_with("failed to open 'not-there': io error: "));
+    }
+}
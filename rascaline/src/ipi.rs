@@ -0,0 +1,427 @@
+//! Driver for the [i-PI](https://ipi-code.org/) socket protocol.
+//!
+//! i-PI is a universal force engine for advanced molecular dynamics (path
+//! integral, replica exchange, *etc.*). It runs as a server, and delegates the
+//! calculation of energies and forces to drivers connecting over a socket.
+//! This module implements such a driver, computing a descriptor with a
+//! [`Calculator`] and predicting energies and forces with a [`Model`], such as
+//! a [`LinearModel`].
+//!
+//! ```no_run
+//! # use rascaline::Calculator;
+//! # use rascaline::ipi::{Driver, LinearModel};
+//! # fn main() -> Result<(), rascaline::Error> {
+//! let calculator = Calculator::new("soap_power_spectrum", "{...}".into())?;
+//! let model = LinearModel {
+//!     weights: ndarray::Array1::zeros(1200),
+//!     densify: vec!["species_center".into(), "species_neighbor_1".into(), "species_neighbor_2".into()],
+//! };
+//!
+//! // species of the atoms, in the same order as in the i-PI input
+//! let species = vec![8, 1, 1];
+//! let mut driver = Driver::new(calculator, species, model)?;
+//! driver.connect_inet("localhost", 31415)?;
+//! # Ok(())
+//! # }
+//! ```
+use std::convert::TryFrom;
+use std::io::{Read, Write};
+
+use ndarray::Array2;
+
+use crate::{Calculator, CalculationOptions, Descriptor, Error, Matrix3, SimpleSystem, System, Vector3D};
+use crate::systems::UnitCell;
+use crate::models::Model;
+
+pub use crate::models::LinearModel;
+
+/// Conversion factor from Bohr (the length unit used by i-PI) to Angstrom
+const BOHR_TO_ANGSTROM: f64 = 0.529_177_210_903;
+
+/// Size of the messages headers in the i-PI protocol
+const HEADER_SIZE: usize = 12;
+
+/// Results of the last calculation, waiting to be sent to the server
+struct Results {
+    energy: f64,
+    forces: Array2<f64>,
+}
+
+/// Driver implementing the client side of the i-PI protocol.
+///
+/// Since i-PI only sends the positions of the atoms, the atomic species must
+/// be given when creating the driver. The virial is always reported as zero,
+/// so simulations at constant pressure are not supported.
+pub struct Driver {
+    calculator: Calculator,
+    species: Vec<i32>,
+    model: Box<dyn Model>,
+    initialized: bool,
+    results: Option<Results>,
+}
+
+impl Driver {
+    /// Create a new driver using the given `calculator` and `model` for
+    /// systems containing atoms with the given `species`.
+    ///
+    /// The model should produce energies in Hartree (i-PI units) from
+    /// positions in Angstrom, which can be achieved by scaling the weights of
+    /// models fitted with other units.
+    ///
+    /// # Errors
+    ///
+    /// This function returns an error if the calculator does not compute
+    /// gradients, which are required for the forces.
+    pub fn new<M: Model + 'static>(calculator: Calculator, species: Vec<i32>, model: M) -> Result<Driver, Error> {
+        if !calculator.gradients() {
+            return Err(Error::InvalidParameter(
+                "the calculator must compute gradients to be used with i-PI".into()
+            ));
+        }
+
+        return Ok(Driver {
+            calculator: calculator,
+            species: species,
+            model: Box::new(model),
+            initialized: false,
+            results: None,
+        });
+    }
+
+    /// Connect to an i-PI server listening on the given internet `host` and
+    /// `port`, and run until the server asks the driver to exit.
+    pub fn connect_inet(&mut self, host: &str, port: u16) -> Result<(), Error> {
+        let stream = std::net::TcpStream::connect((host, port))?;
+        stream.set_nodelay(true)?;
+        return self.run(stream);
+    }
+
+    /// Connect to an i-PI server listening on the UNIX socket with the given
+    /// `address`, and run until the server asks the driver to exit. Following
+    /// i-PI conventions, the socket is located at `/tmp/ipi_<address>`.
+    #[cfg(unix)]
+    pub fn connect_unix(&mut self, address: &str) -> Result<(), Error> {
+        let stream = std::os::unix::net::UnixStream::connect(format!("/tmp/ipi_{}", address))?;
+        return self.run(stream);
+    }
+
+    /// Run the i-PI protocol over the given `stream`, until the server sends
+    /// the `EXIT` message or closes the connection.
+    pub fn run<S: Read + Write>(&mut self, mut stream: S) -> Result<(), Error> {
+        loop {
+            let mut header = [0; HEADER_SIZE];
+            match stream.read_exact(&mut header) {
+                Ok(()) => {},
+                Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(()),
+                Err(e) => return Err(e.into()),
+            }
+
+            let message = std::str::from_utf8(&header)?.trim_end();
+            match message {
+                "STATUS" => {
+                    if !self.initialized {
+                        write_header(&mut stream, "NEEDINIT")?;
+                    } else if self.results.is_some() {
+                        write_header(&mut stream, "HAVEDATA")?;
+                    } else {
+                        write_header(&mut stream, "READY")?;
+                    }
+                }
+                "INIT" => {
+                    // bead index and initialization string, which are not used
+                    let _bead = read_i32(&mut stream)?;
+                    let size = read_size(&mut stream)?;
+                    let mut init = vec![0; size];
+                    stream.read_exact(&mut init)?;
+                    self.initialized = true;
+                }
+                "POSDATA" => {
+                    let cell = read_f64s(&mut stream, 9)?;
+                    // the inverse of the cell is not needed
+                    let _ = read_f64s(&mut stream, 9)?;
+                    let n_atoms = read_size(&mut stream)?;
+                    let positions = read_f64s(&mut stream, 3 * n_atoms)?;
+
+                    self.results = Some(self.compute(&cell, &positions)?);
+                }
+                "GETFORCE" => {
+                    let results = self.results.take().ok_or_else(|| Error::InvalidParameter(
+                        "i-PI server requested forces before sending positions".into()
+                    ))?;
+
+                    write_header(&mut stream, "FORCEREADY")?;
+                    stream.write_all(&results.energy.to_ne_bytes())?;
+                    write_i32(&mut stream, results.forces.nrows())?;
+                    for &force in &results.forces {
+                        stream.write_all(&force.to_ne_bytes())?;
+                    }
+                    // virial
+                    for _ in 0..9 {
+                        stream.write_all(&0.0_f64.to_ne_bytes())?;
+                    }
+                    // no additional data
+                    write_i32(&mut stream, 0)?;
+                    stream.flush()?;
+                }
+                "EXIT" => return Ok(()),
+                message => {
+                    return Err(Error::InvalidParameter(format!(
+                        "unexpected message from i-PI server: '{}'", message
+                    )));
+                }
+            }
+        }
+    }
+
+    /// Compute energy and forces for the system with the given cell and
+    /// positions, both in Bohr.
+    fn compute(&mut self, cell: &[f64], positions: &[f64]) -> Result<Results, Error> {
+        let n_atoms = positions.len() / 3;
+        if n_atoms != self.species.len() {
+            return Err(Error::InvalidParameter(format!(
+                "i-PI sent positions for {} atoms, but the driver was created with {} atomic species",
+                n_atoms, self.species.len()
+            )));
+        }
+
+        let mut system = SimpleSystem::new(unit_cell(cell)?);
+        for (&species, position) in self.species.iter().zip(positions.chunks_exact(3)) {
+            system.add_atom(species, Vector3D::new(
+                position[0] * BOHR_TO_ANGSTROM,
+                position[1] * BOHR_TO_ANGSTROM,
+                position[2] * BOHR_TO_ANGSTROM,
+            ));
+        }
+
+        let mut systems = vec![Box::new(system) as Box<dyn System>];
+        let mut descriptor = Descriptor::new();
+        self.calculator.compute(&mut systems, &mut descriptor, CalculationOptions::default())?;
+
+        let (energy, mut forces) = self.model.predict(&mut descriptor, n_atoms)?;
+        // convert forces from Hartree/Angstrom to Hartree/Bohr
+        forces.mapv_inplace(|f| f * BOHR_TO_ANGSTROM);
+
+        return Ok(Results {
+            energy: energy,
+            forces: forces,
+        });
+    }
+}
+
+/// Convert the cell matrix sent by i-PI (in Bohr) to a `UnitCell` (in
+/// Angstrom).
+///
+/// i-PI sends the `h` matrix in row-major order, with the lattice vectors as
+/// the columns of the matrix; while `UnitCell` uses rows for the lattice
+/// vectors.
+fn unit_cell(cell: &[f64]) -> Result<UnitCell, Error> {
+    let matrix = Matrix3::new([
+        [cell[0] * BOHR_TO_ANGSTROM, cell[1] * BOHR_TO_ANGSTROM, cell[2] * BOHR_TO_ANGSTROM],
+        [cell[3] * BOHR_TO_ANGSTROM, cell[4] * BOHR_TO_ANGSTROM, cell[5] * BOHR_TO_ANGSTROM],
+        [cell[6] * BOHR_TO_ANGSTROM, cell[7] * BOHR_TO_ANGSTROM, cell[8] * BOHR_TO_ANGSTROM],
+    ]);
+    return UnitCell::try_from(matrix.transposed());
+}
+
+fn write_header<W: Write>(stream: &mut W, message: &str) -> Result<(), Error> {
+    let mut header = [b' '; HEADER_SIZE];
+    header[..message.len()].copy_from_slice(message.as_bytes());
+    stream.write_all(&header)?;
+    stream.flush()?;
+    return Ok(());
+}
+
+fn write_i32<W: Write>(stream: &mut W, value: usize) -> Result<(), Error> {
+    let value = i32::try_from(value).map_err(|_| Error::InvalidParameter(format!(
+        "{} is too large to be sent to i-PI", value
+    )))?;
+    stream.write_all(&value.to_ne_bytes())?;
+    return Ok(());
+}
+
+fn read_i32<R: Read>(stream: &mut R) -> Result<i32, Error> {
+    let mut buffer = [0; 4];
+    stream.read_exact(&mut buffer)?;
+    return Ok(i32::from_ne_bytes(buffer));
+}
+
+fn read_size<R: Read>(stream: &mut R) -> Result<usize, Error> {
+    let value = read_i32(stream)?;
+    return usize::try_from(value).map_err(|_| Error::InvalidParameter(format!(
+        "got a negative size ({}) from i-PI", value
+    )));
+}
+
+fn read_f64s<R: Read>(stream: &mut R, count: usize) -> Result<Vec<f64>, Error> {
+    let mut values = Vec::with_capacity(count);
+    let mut buffer = [0; 8];
+    for _ in 0..count {
+        stream.read_exact(&mut buffer)?;
+        values.push(f64::from_ne_bytes(buffer));
+    }
+    return Ok(values);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Read, Write};
+
+    use approx::assert_relative_eq;
+    use ndarray::Array1;
+
+    use crate::Calculator;
+    use super::{Driver, LinearModel, BOHR_TO_ANGSTROM, unit_cell};
+
+    /// In-memory stream, reading messages from the server and recording the
+    /// messages from the driver
+    struct MockServer {
+        input: Cursor<Vec<u8>>,
+        output: Vec<u8>,
+    }
+
+    impl Read for MockServer {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.input.read(buf)
+        }
+    }
+
+    impl Write for MockServer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.output.write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn header(message: &str) -> Vec<u8> {
+        let mut header = message.as_bytes().to_vec();
+        header.resize(12, b' ');
+        return header;
+    }
+
+    #[test]
+    fn protocol() {
+        let calculator = Calculator::new("dummy_calculator", r#"{
+            "cutoff": 1.2,
+            "delta": 3,
+            "name": "",
+            "gradients": true
+        }"#.to_owned()).unwrap();
+
+        let model = LinearModel {
+            weights: Array1::from(vec![0.5, 2.0]),
+            densify: Vec::new(),
+            features: calculator.default_features(),
+        };
+
+        let mut driver = Driver::new(calculator, vec![8, 1, 1], model).unwrap();
+
+        let mut input = Vec::new();
+        input.extend(header("STATUS"));
+        input.extend(header("INIT"));
+        input.extend(0_i32.to_ne_bytes());
+        input.extend(3_i32.to_ne_bytes());
+        input.extend(b"foo");
+        input.extend(header("STATUS"));
+        input.extend(header("POSDATA"));
+        let cell = [20.0_f64, 0.0, 0.0, 0.0, 20.0, 0.0, 0.0, 0.0, 20.0];
+        for value in cell.iter().chain(cell.iter()) {
+            input.extend(value.to_ne_bytes());
+        }
+        input.extend(3_i32.to_ne_bytes());
+        let positions = [0.0_f64, 0.0, 0.0, 0.0, 1.4, -1.1, 0.0, -1.4, -1.1];
+        for value in &positions {
+            input.extend(value.to_ne_bytes());
+        }
+        input.extend(header("STATUS"));
+        input.extend(header("GETFORCE"));
+        input.extend(header("EXIT"));
+
+        let mut server = MockServer {
+            input: Cursor::new(input),
+            output: Vec::new(),
+        };
+        driver.run(&mut server).unwrap();
+
+        let output = server.output;
+        assert_eq!(&output[..12], &header("NEEDINIT")[..]);
+        assert_eq!(&output[12..24], &header("READY")[..]);
+        assert_eq!(&output[24..36], &header("HAVEDATA")[..]);
+        assert_eq!(&output[36..48], &header("FORCEREADY")[..]);
+
+        let read_f64 = |start: usize| {
+            let mut buffer = [0; 8];
+            buffer.copy_from_slice(&output[start..start + 8]);
+            f64::from_ne_bytes(buffer)
+        };
+
+        // the dummy calculator values are `center + delta` for the first
+        // feature and the sum of positions of the center and its neighbors
+        // for the second one
+        let positions = positions.iter().map(|x| x * BOHR_TO_ANGSTROM).collect::<Vec<_>>();
+        let o_h1 = positions[0..3].iter().sum::<f64>() + positions[3..6].iter().sum::<f64>();
+        let o_h2 = positions[0..3].iter().sum::<f64>() + positions[6..9].iter().sum::<f64>();
+        let sum_all = positions.iter().sum::<f64>();
+        let expected = 0.5 * (3.0 + 4.0 + 5.0) + 2.0 * (sum_all + o_h1 + o_h2);
+        assert_relative_eq!(read_f64(48), expected, max_relative = 1e-12);
+
+        let mut n_atoms = [0; 4];
+        n_atoms.copy_from_slice(&output[56..60]);
+        assert_eq!(i32::from_ne_bytes(n_atoms), 3);
+
+        // forces are the opposite of the energy gradient, in Hartree/Bohr. The
+        // dummy calculator gradients are 1 with respect to each neighbor, and
+        // the oxygen is a neighbor of both hydrogen atoms.
+        let force_oxygen_x = read_f64(60);
+        assert_relative_eq!(force_oxygen_x, -2.0 * 2.0 * BOHR_TO_ANGSTROM, max_relative = 1e-12);
+
+        // 9 forces, 9 virial, 1 extra data size
+        assert_eq!(output.len(), 60 + 9 * 8 + 9 * 8 + 4);
+    }
+
+    #[test]
+    fn triclinic_cell() {
+        // lattice vectors a = (10, 0, 0), b = (2, 9, 0), c = (1, 3, 8) as the
+        // columns of the upper triangular h matrix used by i-PI
+        let h = [
+            10.0, 2.0, 1.0,
+            0.0, 9.0, 3.0,
+            0.0, 0.0, 8.0,
+        ];
+        let cell = unit_cell(&h).unwrap();
+        let matrix = cell.matrix();
+
+        let expected = [[10.0, 0.0, 0.0], [2.0, 9.0, 0.0], [1.0, 3.0, 8.0]];
+        for i in 0..3 {
+            for j in 0..3 {
+                assert_relative_eq!(matrix[i][j], expected[i][j] * BOHR_TO_ANGSTROM, epsilon = 1e-12);
+            }
+        }
+
+        assert_relative_eq!(cell.a(), 10.0 * BOHR_TO_ANGSTROM, max_relative = 1e-12);
+        assert_relative_eq!(cell.b(), f64::sqrt(85.0) * BOHR_TO_ANGSTROM, max_relative = 1e-12);
+        assert_relative_eq!(cell.c(), f64::sqrt(74.0) * BOHR_TO_ANGSTROM, max_relative = 1e-12);
+        assert_relative_eq!(cell.volume(), 720.0 * BOHR_TO_ANGSTROM.powi(3), max_relative = 1e-12);
+    }
+
+    #[test]
+    fn errors() {
+        let calculator = Calculator::new("dummy_calculator", r#"{
+            "cutoff": 1.5,
+            "delta": 3,
+            "name": "",
+            "gradients": false
+        }"#.to_owned()).unwrap();
+
+        let model = LinearModel {
+            weights: Array1::zeros(2),
+            densify: Vec::new(),
+            features: calculator.default_features(),
+        };
+
+        let error = Driver::new(calculator, vec![1], model).err().unwrap();
+        assert_eq!(error.to_string(), "invalid parameter: the calculator must compute gradients to be used with i-PI");
+    }
+}
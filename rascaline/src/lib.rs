@@ -14,13 +14,16 @@
 // Tests lints
 #![cfg_attr(test, allow(clippy::float_cmp))]
 
+/// Version of rascaline, as a `major.minor.patch` string
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
 pub mod types;
 pub use types::*;
 
-pub(crate) mod math;
+pub mod math;
 
 mod errors;
-pub use self::errors::Error;
+pub use self::errors::{Error, ErrorContext};
 
 pub mod systems;
 pub use systems::{System, SimpleSystem};
@@ -29,10 +32,20 @@ pub mod descriptor;
 pub use descriptor::Descriptor;
 
 mod calculator;
-pub use calculator::{Calculator, CalculationOptions, SelectedIndexes};
+pub use calculator::{Calculator, CalculationOptions, CancellationHandle, MemoryEstimate, Parallelism, SelectedIndexes};
+
+mod checkpoint;
 
 pub mod calculators;
 
+pub mod profiling;
+
+pub mod models;
+
+pub mod ipi;
+
+pub mod checks;
+
 
 // only try to build the tutorials in test mode
 #[cfg(test)]
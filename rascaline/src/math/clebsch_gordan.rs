@@ -0,0 +1,278 @@
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+
+use ndarray::{Array3, ArrayView3};
+
+/// Compute `n!` as a floating point number
+fn factorial(n: usize) -> f64 {
+    return (1..=n).fold(1.0, |acc, i| acc * i as f64);
+}
+
+/// Check the triangle condition `|j1 - j2| <= j3 <= j1 + j2`
+fn triangle(j1: usize, j2: usize, j3: usize) -> bool {
+    let min = j1.max(j2) - j1.min(j2);
+    return min <= j3 && j3 <= j1 + j2;
+}
+
+/// Compute the Wigner 3-j symbol
+///
+/// ```text
+/// ⎛ j1 j2 j3 ⎞
+/// ⎝ m1 m2 m3 ⎠
+/// ```
+///
+/// for integer angular momenta, using Racah's formula. This returns zero if
+/// the selection rules (triangle condition, `m1 + m2 + m3 = 0` and `|mi| <=
+/// ji`) are not respected.
+pub fn wigner_3j(j1: usize, j2: usize, j3: usize, m1: isize, m2: isize, m3: isize) -> f64 {
+    let (ij1, ij2, ij3) = (j1 as isize, j2 as isize, j3 as isize);
+    if m1 + m2 + m3 != 0 || m1.abs() > ij1 || m2.abs() > ij2 || m3.abs() > ij3 || !triangle(j1, j2, j3) {
+        return 0.0;
+    }
+
+    let delta = factorial(j1 + j2 - j3) * factorial(j1 + j3 - j2) * factorial(j2 + j3 - j1)
+        / factorial(j1 + j2 + j3 + 1);
+
+    let prefactor = factorial((ij1 + m1) as usize) * factorial((ij1 - m1) as usize)
+        * factorial((ij2 + m2) as usize) * factorial((ij2 - m2) as usize)
+        * factorial((ij3 + m3) as usize) * factorial((ij3 - m3) as usize);
+
+    let k_min = 0.max(ij2 - ij3 - m1).max(ij1 - ij3 + m2);
+    let k_max = (ij1 + ij2 - ij3).min(ij1 - m1).min(ij2 + m2);
+
+    let mut sum = 0.0;
+    for k in k_min..=k_max {
+        let denominator = factorial(k as usize)
+            * factorial((ij3 - ij2 + k + m1) as usize)
+            * factorial((ij3 - ij1 + k - m2) as usize)
+            * factorial((ij1 + ij2 - ij3 - k) as usize)
+            * factorial((ij1 - k - m1) as usize)
+            * factorial((ij2 - k + m2) as usize);
+
+        let sign = if k % 2 == 0 { 1.0 } else { -1.0 };
+        sum += sign / denominator;
+    }
+
+    let sign = if (ij1 - ij2 - m3).rem_euclid(2) == 0 { 1.0 } else { -1.0 };
+    return sign * f64::sqrt(delta * prefactor) * sum;
+}
+
+/// Compute the Clebsch-Gordan coefficient `<l1 m1; l2 m2 | lambda mu>`
+/// coupling complex spherical harmonics (with the Condon-Shortley phase).
+pub fn clebsch_gordan(l1: usize, m1: isize, l2: usize, m2: isize, lambda: usize, mu: isize) -> f64 {
+    let sign = if (l1 as isize - l2 as isize + mu).rem_euclid(2) == 0 { 1.0 } else { -1.0 };
+    let factor = f64::sqrt((2 * lambda + 1) as f64);
+    return sign * factor * wigner_3j(l1, l2, lambda, m1, m2, -mu);
+}
+
+/// Get the non-zero coefficients of the matrix transforming complex spherical
+/// harmonics `Y_l^m` into the real spherical harmonics `Y_{lm}` used in
+/// rascaline, for a given `m`. This returns a list of `(m', real, imaginary)`
+/// such that `Y_{lm} = Σ (real + i imaginary) Y_l^{m'}`.
+pub(crate) fn complex_to_real(m: isize) -> Vec<(isize, f64, f64)> {
+    let sqrt_1_2 = std::f64::consts::FRAC_1_SQRT_2;
+    let sign = if m.rem_euclid(2) == 0 { 1.0 } else { -1.0 };
+    match m.cmp(&0) {
+        Ordering::Greater => vec![(-m, sqrt_1_2, 0.0), (m, sign * sqrt_1_2, 0.0)],
+        Ordering::Less => vec![(m, 0.0, sqrt_1_2), (-m, 0.0, -sign * sqrt_1_2)],
+        Ordering::Equal => vec![(0, 1.0, 0.0)],
+    }
+}
+
+/// Cached provider of Clebsch-Gordan coefficients for the real spherical
+/// harmonics used in rascaline (see [`crate::math::SphericalHarmonics`]).
+///
+/// The coefficients for a given `(l1, l2, lambda)` are computed on first use
+/// and then stored for later calls.
+#[derive(Debug, Clone, Default)]
+pub struct ClebschGordan {
+    cache: BTreeMap<(usize, usize, usize), Array3<f64>>,
+}
+
+impl ClebschGordan {
+    /// Create a new empty `ClebschGordan` cache
+    pub fn new() -> ClebschGordan {
+        ClebschGordan {
+            cache: BTreeMap::new(),
+        }
+    }
+
+    /// Get the coefficients coupling real spherical harmonics of degree `l1`
+    /// and `l2` into real spherical harmonics of degree `lambda`, as an array
+    /// of shape `(2 l1 + 1) x (2 l2 + 1) x (2 lambda + 1)` indexed by `[m1 +
+    /// l1, m2 + l2, mu + lambda]`, such that
+    ///
+    /// ```text
+    /// Σ_{m1, m2} C[m1, m2, mu] A_{l1 m1} B_{l2 m2}
+    /// ```
+    ///
+    /// transforms under rotations like the real spherical harmonic `Y_{lambda
+    /// mu}`.
+    ///
+    /// When `l1 + l2 + lambda` is odd, the coefficients for real spherical
+    /// harmonics are purely imaginary, and this function returns their
+    /// imaginary part.
+    ///
+    /// # Panics
+    ///
+    /// If `l1`, `l2` and `lambda` do not respect the triangle condition
+    /// `|l1 - l2| <= lambda <= l1 + l2`.
+    pub fn real(&mut self, l1: usize, l2: usize, lambda: usize) -> ArrayView3<'_, f64> {
+        assert!(
+            triangle(l1, l2, lambda),
+            "invalid angular momenta for Clebsch-Gordan coefficients: l1={}, l2={}, lambda={}",
+            l1, l2, lambda
+        );
+
+        let coefficients = self.cache.entry((l1, l2, lambda)).or_insert_with(|| {
+            compute_real_clebsch_gordan(l1, l2, lambda)
+        });
+
+        return coefficients.view();
+    }
+}
+
+/// Compute the Clebsch-Gordan coefficients for real spherical harmonics, by
+/// transforming the coefficients for complex spherical harmonics.
+fn compute_real_clebsch_gordan(l1: usize, l2: usize, lambda: usize) -> Array3<f64> {
+    let (il1, il2, il_lambda) = (l1 as isize, l2 as isize, lambda as isize);
+    let imaginary = (l1 + l2 + lambda) % 2 == 1;
+
+    let mut coefficients = Array3::zeros((2 * l1 + 1, 2 * l2 + 1, 2 * lambda + 1));
+    for m1 in -il1..=il1 {
+        for m2 in -il2..=il2 {
+            for mu in -il_lambda..=il_lambda {
+                // C_real[m1, m2, mu] = Σ U[mu, mu'] CG(m1', m2', mu') U*[m1, m1'] U*[m2, m2']
+                let mut real = 0.0;
+                let mut imag = 0.0;
+                for (c_m1, u1_re, u1_im) in complex_to_real(m1) {
+                    for (c_m2, u2_re, u2_im) in complex_to_real(m2) {
+                        let c_mu = c_m1 + c_m2;
+                        if c_mu.abs() > il_lambda {
+                            continue;
+                        }
+
+                        let cg = clebsch_gordan(l1, c_m1, l2, c_m2, lambda, c_mu);
+                        if cg == 0.0 {
+                            continue;
+                        }
+
+                        // conj(U1) * conj(U2)
+                        let re_12 = u1_re * u2_re - u1_im * u2_im;
+                        let im_12 = -(u1_re * u2_im + u1_im * u2_re);
+
+                        for (c_mu_prime, u_re, u_im) in complex_to_real(mu) {
+                            if c_mu_prime != c_mu {
+                                continue;
+                            }
+                            real += cg * (u_re * re_12 - u_im * im_12);
+                            imag += cg * (u_re * im_12 + u_im * re_12);
+                        }
+                    }
+                }
+
+                let index = [(m1 + il1) as usize, (m2 + il2) as usize, (mu + il_lambda) as usize];
+                coefficients[index] = if imaginary { imag } else { real };
+            }
+        }
+    }
+
+    return coefficients;
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use super::*;
+    use crate::Vector3D;
+    use crate::math::SphericalHarmonicsCache;
+
+    #[test]
+    fn reference_values() {
+        assert_relative_eq!(wigner_3j(1, 1, 0, 0, 0, 0), -1.0 / f64::sqrt(3.0), max_relative=1e-14);
+        assert_relative_eq!(wigner_3j(2, 2, 2, 0, 0, 0), -f64::sqrt(2.0 / 35.0), max_relative=1e-14);
+        assert_relative_eq!(wigner_3j(1, 2, 3, 1, -1, 0), -1.0 / f64::sqrt(35.0), max_relative=1e-14);
+        assert_eq!(wigner_3j(1, 1, 3, 0, 0, 0), 0.0);
+        assert_eq!(wigner_3j(1, 1, 2, 1, 1, 0), 0.0);
+
+        assert_relative_eq!(clebsch_gordan(1, 1, 1, -1, 0, 0), 1.0 / f64::sqrt(3.0), max_relative=1e-14);
+        assert_relative_eq!(clebsch_gordan(1, 0, 1, 0, 2, 0), f64::sqrt(2.0 / 3.0), max_relative=1e-14);
+        assert_relative_eq!(clebsch_gordan(2, 2, 1, -1, 2, 1), f64::sqrt(1.0 / 3.0), max_relative=1e-14);
+    }
+
+    #[test]
+    fn orthonormality() {
+        let mut cg = ClebschGordan::new();
+        for l1 in 0..4 {
+            for l2 in 0..4 {
+                for lambda in (l1.max(l2) - l1.min(l2))..=(l1 + l2) {
+                    let coefficients = cg.real(l1, l2, lambda);
+                    for mu in 0..(2 * lambda + 1) {
+                        for nu in 0..(2 * lambda + 1) {
+                            let mut dot = 0.0;
+                            for m1 in 0..(2 * l1 + 1) {
+                                for m2 in 0..(2 * l2 + 1) {
+                                    dot += coefficients[[m1, m2, mu]] * coefficients[[m1, m2, nu]];
+                                }
+                            }
+                            let expected = if mu == nu { 1.0 } else { 0.0 };
+                            assert_relative_eq!(dot, expected, epsilon=1e-12);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn couple_spherical_harmonics() {
+        // coupling Y_{l1}(r) and Y_{l2}(r) for the same direction r gives a
+        // result proportional to Y_{lambda}(r), with a constant independent
+        // of r, or zero if l1 + l2 + lambda is odd
+        let directions = [
+            Vector3D::new(1.0, -3.0, 9.0) / f64::sqrt(91.0),
+            Vector3D::new(-2.0, 0.5, 1.0) / f64::sqrt(5.25),
+        ];
+
+        let max_angular = 4;
+        let mut cache = SphericalHarmonicsCache::new(max_angular, false);
+        let mut cg = ClebschGordan::new();
+
+        for &(l1, l2, lambda) in &[(1, 1, 2), (2, 1, 3), (2, 2, 2), (3, 1, 2), (2, 2, 4), (1, 1, 1), (3, 2, 2)] {
+            let mut constants = Vec::new();
+            for &direction in &directions {
+                cache.compute(direction);
+                let coefficients = cg.real(l1, l2, lambda);
+
+                let (il1, il2, ilambda) = (l1 as isize, l2 as isize, lambda as isize);
+                for mu in -ilambda..=ilambda {
+                    let mut coupled = 0.0;
+                    for m1 in -il1..=il1 {
+                        for m2 in -il2..=il2 {
+                            let c = coefficients[[(m1 + il1) as usize, (m2 + il2) as usize, (mu + ilambda) as usize]];
+                            coupled += c * cache.values[[il1, m1]] * cache.values[[il2, m2]];
+                        }
+                    }
+
+                    if (l1 + l2 + lambda) % 2 == 1 {
+                        assert_relative_eq!(coupled, 0.0, epsilon=1e-12);
+                    } else {
+                        constants.push(coupled / cache.values[[ilambda, mu]]);
+                    }
+                }
+            }
+
+            for constant in &constants {
+                assert_relative_eq!(*constant, constants[0], max_relative=1e-10);
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic = "invalid angular momenta for Clebsch-Gordan coefficients: l1=1, l2=1, lambda=3"]
+    fn invalid_triangle() {
+        let mut cg = ClebschGordan::new();
+        cg.real(1, 1, 3);
+    }
+}
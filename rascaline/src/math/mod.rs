@@ -0,0 +1,151 @@
+//! Mathematical building blocks used by the calculators in rascaline, which
+//! can also be used directly when prototyping new representations.
+
+mod spherical_harmonics;
+pub use self::spherical_harmonics::{SphericalHarmonics, SphericalHarmonicsArray, SphericalHarmonicsCache};
+
+mod clebsch_gordan;
+pub use self::clebsch_gordan::{ClebschGordan, clebsch_gordan, wigner_3j};
+
+mod wigner;
+pub use self::wigner::WignerD;
+
+mod spline;
+pub use self::spline::Spline;
+
+mod random;
+pub(crate) use self::random::Random;
+
+pub mod quadrature;
+
+pub mod voronoi;
+
+pub mod special;
+
+/// Number of independent accumulators/lanes used by the kernels below. Using
+/// multiple independent accumulators breaks the dependency chain between loop
+/// iterations, allowing the compiler to use SIMD instructions.
+const LANES: usize = 4;
+
+/// Compute the dot product between `count` elements of `a` and `b`, taking one
+/// element every `stride` elements, starting at `start_a` and `start_b`
+/// respectively.
+///
+/// # Panics
+///
+/// If any of the required elements is out of bounds for `a` or `b`
+#[inline]
+pub(crate) fn strided_dot(a: &[f64], start_a: usize, b: &[f64], start_b: usize, stride: usize, count: usize) -> f64 {
+    if count == 0 {
+        return 0.0;
+    }
+    assert!(stride > 0 || count == 1);
+    assert!(start_a + (count - 1) * stride < a.len());
+    assert!(start_b + (count - 1) * stride < b.len());
+
+    let mut sums = [0.0; LANES];
+    let chunks = count / LANES;
+    for chunk in 0..chunks {
+        for (lane, sum) in sums.iter_mut().enumerate() {
+            let i = (chunk * LANES + lane) * stride;
+            // SAFETY: we checked the bounds for the last element above
+            unsafe {
+                *sum += a.get_unchecked(start_a + i) * b.get_unchecked(start_b + i);
+            }
+        }
+    }
+
+    let mut sum = (sums[0] + sums[1]) + (sums[2] + sums[3]);
+    for k in (chunks * LANES)..count {
+        let i = k * stride;
+        // SAFETY: we checked the bounds for the last element above
+        unsafe {
+            sum += a.get_unchecked(start_a + i) * b.get_unchecked(start_b + i);
+        }
+    }
+
+    return sum;
+}
+
+/// Compute `output[i] = Σ_j coefficients[j] * inputs[j][i]` for all `i`.
+///
+/// The loop is written over fixed-size chunks to let the compiler generate
+/// SIMD code for it.
+///
+/// # Panics
+///
+/// If any of the `inputs` does not have the same length as `output`
+#[inline]
+pub(crate) fn linear_combination(output: &mut [f64], coefficients: [f64; 4], inputs: [&[f64]; 4]) {
+    let n = output.len();
+    for input in &inputs {
+        assert_eq!(input.len(), n);
+    }
+
+    let [c0, c1, c2, c3] = coefficients;
+    let [x0, x1, x2, x3] = inputs;
+
+    let mut output_chunks = output.chunks_exact_mut(LANES);
+    let mut x0_chunks = x0.chunks_exact(LANES);
+    let mut x1_chunks = x1.chunks_exact(LANES);
+    let mut x2_chunks = x2.chunks_exact(LANES);
+    let mut x3_chunks = x3.chunks_exact(LANES);
+    for out in &mut output_chunks {
+        let x0 = x0_chunks.next().expect("inputs have the same size");
+        let x1 = x1_chunks.next().expect("inputs have the same size");
+        let x2 = x2_chunks.next().expect("inputs have the same size");
+        let x3 = x3_chunks.next().expect("inputs have the same size");
+        for lane in 0..LANES {
+            out[lane] = c0 * x0[lane] + c1 * x1[lane] + c2 * x2[lane] + c3 * x3[lane];
+        }
+    }
+
+    let out = output_chunks.into_remainder();
+    let (x0, x1) = (x0_chunks.remainder(), x1_chunks.remainder());
+    let (x2, x3) = (x2_chunks.remainder(), x3_chunks.remainder());
+    for i in 0..out.len() {
+        out[i] = c0 * x0[i] + c1 * x1[i] + c2 * x2[i] + c3 * x3[i];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn test_strided_dot() {
+        let a = (0..40).map(|i| i as f64 * 0.25).collect::<Vec<_>>();
+        let b = (0..40).map(|i| 1.0 - i as f64 * 0.5).collect::<Vec<_>>();
+
+        for &(start_a, start_b, stride, count) in &[(0, 0, 1, 0), (0, 0, 1, 40), (1, 3, 3, 11), (2, 0, 7, 5), (5, 5, 2, 3)] {
+            let expected = (0..count).map(|k| a[start_a + k * stride] * b[start_b + k * stride]).sum::<f64>();
+            assert_relative_eq!(strided_dot(&a, start_a, &b, start_b, stride, count), expected, max_relative=1e-15);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "assertion failed")]
+    fn strided_dot_out_of_bounds() {
+        let a = vec![1.0; 10];
+        strided_dot(&a, 0, &a, 1, 3, 4);
+    }
+
+    #[test]
+    fn test_linear_combination() {
+        for &n in &[0, 3, 4, 17] {
+            let inputs = (0..4).map(|j| {
+                (0..n).map(|i| (i * j) as f64 - 0.5 * j as f64).collect::<Vec<_>>()
+            }).collect::<Vec<_>>();
+            let coefficients = [0.5, -1.25, 3.0, 0.125];
+
+            let mut output = vec![0.0; n];
+            linear_combination(&mut output, coefficients, [&inputs[0], &inputs[1], &inputs[2], &inputs[3]]);
+
+            for i in 0..n {
+                let expected = (0..4).map(|j| coefficients[j] * inputs[j][i]).sum::<f64>();
+                assert_relative_eq!(output[i], expected, max_relative=1e-15);
+            }
+        }
+    }
+}
@@ -0,0 +1,240 @@
+//! Gaussian quadrature rules, to compute integrals as a weighted sum of the
+//! integrand values at specific nodes: `∫ w(x) f(x) dx ≈ Σ_i w_i f(x_i)`.
+
+/// Maximal number of Newton iterations used to find the nodes
+const MAX_ITERATIONS: usize = 100;
+
+/// A quadrature rule, containing nodes and corresponding weights, sorted by
+/// increasing node position.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Quadrature {
+    /// Position of the nodes of this quadrature
+    pub nodes: Vec<f64>,
+    /// Weights associated with each node
+    pub weights: Vec<f64>,
+}
+
+impl Quadrature {
+    /// Get the Gauss-Legendre quadrature rule with `order` nodes, to
+    /// integrate functions over `[-1, 1]`. This rule is exact for polynomials
+    /// up to degree `2 * order - 1`.
+    ///
+    /// # Panics
+    ///
+    /// If `order` is zero
+    pub fn gauss_legendre(order: usize) -> Quadrature {
+        assert!(order > 0, "quadrature order must be at least 1");
+
+        let n = order as f64;
+        let mut nodes = vec![0.0; order];
+        let mut weights = vec![0.0; order];
+        for i in 0..(order / 2 + order % 2) {
+            // initial guess for the i-th root, then refine with Newton's method
+            let mut z = f64::cos(std::f64::consts::PI * (i as f64 + 0.75) / (n + 0.5));
+            let mut derivative = 0.0;
+            for _ in 0..MAX_ITERATIONS {
+                let (p, dp) = legendre_polynomial(order, z);
+                derivative = dp;
+
+                let previous = z;
+                z = previous - p / dp;
+                if (z - previous).abs() < 1e-15 {
+                    let (_, dp) = legendre_polynomial(order, z);
+                    derivative = dp;
+                    break;
+                }
+            }
+
+            let weight = 2.0 / ((1.0 - z * z) * derivative * derivative);
+            nodes[i] = -z;
+            nodes[order - 1 - i] = z;
+            weights[i] = weight;
+            weights[order - 1 - i] = weight;
+        }
+
+        return Quadrature {
+            nodes: nodes,
+            weights: weights,
+        };
+    }
+
+    /// Get the Gauss-Hermite quadrature rule with `order` nodes, to integrate
+    /// functions over `]-∞, ∞[` with a weight function `exp(-x^2)`. This rule
+    /// is exact for `exp(-x^2) P(x)` where `P` is a polynomial up to degree
+    /// `2 * order - 1`.
+    ///
+    /// # Panics
+    ///
+    /// If `order` is zero
+    pub fn gauss_hermite(order: usize) -> Quadrature {
+        assert!(order > 0, "quadrature order must be at least 1");
+
+        let n = order as f64;
+        // roots are found in decreasing order, starting from the largest one
+        let mut roots = vec![0.0; order / 2 + order % 2];
+        let mut weights = vec![0.0; order / 2 + order % 2];
+        let mut z: f64 = 0.0;
+        for i in 0..roots.len() {
+            // initial guesses from Numerical Recipes, 3rd edition, section 4.6
+            z = match i {
+                0 => f64::sqrt(2.0 * n + 1.0) - 1.85575 * f64::powf(2.0 * n + 1.0, -1.0 / 6.0),
+                1 => z - 1.14 * f64::powf(n, 0.426) / z,
+                2 => 1.86 * z - 0.86 * roots[0],
+                3 => 1.91 * z - 0.91 * roots[1],
+                _ => 2.0 * z - roots[i - 2],
+            };
+
+            let mut derivative = 0.0;
+            for _ in 0..MAX_ITERATIONS {
+                let (p, dp) = hermite_function(order, z);
+                derivative = dp;
+
+                let previous = z;
+                z = previous - p / dp;
+                if (z - previous).abs() < 1e-15 {
+                    let (_, dp) = hermite_function(order, z);
+                    derivative = dp;
+                    break;
+                }
+            }
+
+            roots[i] = z;
+            weights[i] = 2.0 / (derivative * derivative);
+        }
+
+        let mut quadrature = Quadrature {
+            nodes: vec![0.0; order],
+            weights: vec![0.0; order],
+        };
+        for (i, (&root, &weight)) in roots.iter().zip(&weights).enumerate() {
+            quadrature.nodes[i] = -root;
+            quadrature.nodes[order - 1 - i] = root;
+            quadrature.weights[i] = weight;
+            quadrature.weights[order - 1 - i] = weight;
+        }
+
+        return quadrature;
+    }
+
+    /// Get the number of nodes in this quadrature
+    pub fn order(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Transform a quadrature rule over `[-1, 1]` (such as the Gauss-Legendre
+    /// rule) into a quadrature over `[start, stop]`.
+    pub fn rescaled(&self, start: f64, stop: f64) -> Quadrature {
+        let half_width = 0.5 * (stop - start);
+        let center = 0.5 * (stop + start);
+        return Quadrature {
+            nodes: self.nodes.iter().map(|x| center + half_width * x).collect(),
+            weights: self.weights.iter().map(|w| half_width * w).collect(),
+        };
+    }
+
+    /// Integrate `function` using this quadrature rule
+    pub fn integrate(&self, function: impl Fn(f64) -> f64) -> f64 {
+        return self.nodes.iter()
+            .zip(&self.weights)
+            .map(|(&x, &w)| w * function(x))
+            .sum();
+    }
+}
+
+/// Evaluate the Legendre polynomial of degree `n` and its derivative at `x`,
+/// using the three terms recurrence relation.
+fn legendre_polynomial(n: usize, x: f64) -> (f64, f64) {
+    let mut p_1 = 1.0;
+    let mut p_2 = 0.0;
+    for j in 1..=n {
+        let p_3 = p_2;
+        p_2 = p_1;
+        p_1 = ((2 * j - 1) as f64 * x * p_2 - (j - 1) as f64 * p_3) / j as f64;
+    }
+
+    let derivative = n as f64 * (x * p_1 - p_2) / (x * x - 1.0);
+    return (p_1, derivative);
+}
+
+/// Evaluate the normalized Hermite polynomial of degree `n` (such that the
+/// Hermite polynomials are orthonormal for the weight `exp(-x^2)`) and its
+/// derivative at `x`, using the three terms recurrence relation.
+fn hermite_function(n: usize, x: f64) -> (f64, f64) {
+    // π^{-1/4}
+    const PI_M1_4: f64 = 0.751_125_544_464_942_5;
+
+    let mut p_1 = PI_M1_4;
+    let mut p_2 = 0.0;
+    for j in 0..n {
+        let p_3 = p_2;
+        p_2 = p_1;
+        let j = j as f64;
+        p_1 = x * f64::sqrt(2.0 / (j + 1.0)) * p_2 - f64::sqrt(j / (j + 1.0)) * p_3;
+    }
+
+    let derivative = f64::sqrt(2.0 * n as f64) * p_2;
+    return (p_1, derivative);
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use super::*;
+
+    #[test]
+    fn gauss_legendre() {
+        let quadrature = Quadrature::gauss_legendre(1);
+        assert_eq!(quadrature.nodes, [0.0]);
+        assert_eq!(quadrature.weights, [2.0]);
+
+        let quadrature = Quadrature::gauss_legendre(5);
+        assert_eq!(quadrature.order(), 5);
+        assert_relative_eq!(quadrature.nodes[4], 0.906_179_845_938_664, max_relative=1e-14);
+        assert_relative_eq!(quadrature.weights[4], 0.236_926_885_056_189_1, max_relative=1e-14);
+        assert!(quadrature.nodes.windows(2).all(|w| w[0] < w[1]));
+
+        // exact for polynomials up to degree 2n - 1
+        for order in &[3, 8, 25, 200] {
+            let quadrature = Quadrature::gauss_legendre(*order);
+            assert_relative_eq!(quadrature.weights.iter().sum::<f64>(), 2.0, max_relative=1e-13);
+
+            let degree = 2 * order - 1;
+            let integral = quadrature.integrate(|x| x.powi(degree as i32 - 1));
+            assert_relative_eq!(integral, 2.0 / degree as f64, max_relative=1e-12);
+        }
+
+        let quadrature = Quadrature::gauss_legendre(20).rescaled(0.0, std::f64::consts::PI);
+        assert_relative_eq!(quadrature.integrate(f64::sin), 2.0, max_relative=1e-14);
+    }
+
+    #[test]
+    fn gauss_hermite() {
+        let sqrt_pi = f64::sqrt(std::f64::consts::PI);
+
+        let quadrature = Quadrature::gauss_hermite(1);
+        assert_relative_eq!(quadrature.nodes[0], 0.0, epsilon=1e-15);
+        assert_relative_eq!(quadrature.weights[0], sqrt_pi, max_relative=1e-14);
+
+        let quadrature = Quadrature::gauss_hermite(2);
+        assert_relative_eq!(quadrature.nodes[1], f64::sqrt(0.5), max_relative=1e-14);
+        assert_relative_eq!(quadrature.nodes[0], -f64::sqrt(0.5), max_relative=1e-14);
+
+        for order in &[3, 10, 40, 150] {
+            let quadrature = Quadrature::gauss_hermite(*order);
+            assert!(quadrature.nodes.windows(2).all(|w| w[0] < w[1]));
+            assert_relative_eq!(quadrature.weights.iter().sum::<f64>(), sqrt_pi, max_relative=1e-12);
+            assert_relative_eq!(quadrature.integrate(|x| x * x), 0.5 * sqrt_pi, max_relative=1e-12);
+        }
+
+        // ∫ cos(x) exp(-x^2) dx = sqrt(π) exp(-1/4)
+        let quadrature = Quadrature::gauss_hermite(30);
+        assert_relative_eq!(quadrature.integrate(f64::cos), sqrt_pi * f64::exp(-0.25), max_relative=1e-13);
+    }
+
+    #[test]
+    #[should_panic = "quadrature order must be at least 1"]
+    fn zero_order() {
+        Quadrature::gauss_legendre(0);
+    }
+}
@@ -0,0 +1,52 @@
+/// Simple pseudo-random number generator (`SplitMix64`), used to make the
+/// checks and other random operations reproducible without depending on an
+/// external crate.
+pub(crate) struct Random {
+    state: u64,
+}
+
+impl Random {
+    pub(crate) fn new(seed: u64) -> Random {
+        Random { state: seed }
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        return z ^ (z >> 31);
+    }
+
+    /// Get a random number uniformly distributed in `[0, 1)`
+    pub(crate) fn next_f64(&mut self) -> f64 {
+        return (self.next_u64() >> 11) as f64 / (1_u64 << 53) as f64;
+    }
+
+    /// Randomly shuffle `values` in place, using the Fisher-Yates shuffle
+    pub(crate) fn shuffle<T>(&mut self, values: &mut [T]) {
+        for i in (1..values.len()).rev() {
+            let j = (self.next_u64() % (i as u64 + 1)) as usize;
+            values.swap(i, j);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Random;
+
+    #[test]
+    fn random() {
+        let mut random = Random::new(42);
+        let values = (0..1000).map(|_| random.next_f64()).collect::<Vec<_>>();
+        assert!(values.iter().all(|&v| (0.0..1.0).contains(&v)));
+
+        let mean = values.iter().sum::<f64>() / 1000.0;
+        assert!((mean - 0.5).abs() < 0.05);
+
+        // the sequence is reproducible
+        let mut other = Random::new(42);
+        assert_eq!(other.next_f64(), values[0]);
+    }
+}
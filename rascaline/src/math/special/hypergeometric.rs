@@ -0,0 +1,519 @@
+//! This module implement Kummer's confluent hypergeometric function 1F1 and
+//! its derivative, switching between the power series and the asymptotic
+//! expansion depending on the arguments domain.
+//!
+//! This function is used to compute the radial integral in SOAP spherical
+//! expansion when using GTO radial basis.
+//!
+//! This code was originally written by Félix Musil @ COSMO/EPFL
+use std::f64;
+
+use log::warn;
+
+use super::gamma;
+
+// The precision up to which we converge our implementation of 1F1
+const HYPERGEOMETRIC_PRECISION: f64 = 1e-13;
+
+/// Compute 2F0 as a the corresponding series truncated to the first n terms.
+#[derive(Debug, Clone)]
+struct Series2F0 {
+    coefficients: Vec<f64>,
+}
+
+impl Series2F0 {
+    /// Create a new `Series2F0` computing the function itself
+    pub fn new(a: f64, b: f64, n_terms: usize) -> Series2F0 {
+        assert!(a > 0.0 && b > 0.0, "a and b must be positive");
+
+        let mut coefficients = Vec::with_capacity(n_terms);
+
+        let mut coefficient = (b - a) * (1.0 - a);
+        coefficients.push(coefficient);
+        for i in (1..n_terms).map(|i| i as f64) {
+            coefficient *= (b - a + i) * (1.0 - a + i) / (i + 1.0);
+            coefficients.push(coefficient);
+        }
+
+        return Series2F0 {
+            coefficients: coefficients,
+        }
+    }
+
+    /// Create a new `Series2F0` computing the function derivative
+    pub fn derivative(a: f64, b: f64, n_terms: usize) -> Series2F0 {
+        assert!(a > 0.0 && b > 0.0, "a and b must be positive");
+
+        let mut coefficients = Vec::with_capacity(n_terms);
+
+        let mut coefficient = (b - a) * -a;
+        coefficients.push(coefficient);
+        for i in (1..n_terms).map(|i| i as f64) {
+            coefficient *= (b - a + i) * (i - a) / (i + 1.0);
+            coefficients.push(coefficient);
+        }
+
+        return Series2F0 {
+            coefficients: coefficients,
+        }
+    }
+
+    /// Actually compute the function for input parameter `z`
+    pub fn compute(&self, z: f64) -> f64 {
+        let mut result = 1.0;
+        let iz = 1.0 / z;
+        let mut iz_pow = 1.0;
+        for coefficient in &self.coefficients {
+            iz_pow *= iz;
+            result += coefficient * iz_pow;
+        }
+        return result;
+    }
+}
+
+/// Compute 1F1 as a the corresponding series truncated to the first n terms.
+#[derive(Debug, Clone)]
+struct Series1F1 {
+    coefficients: Vec<f64>,
+}
+
+impl Series1F1 {
+    /// Create a new `Series1F1` computing the function itself
+    pub fn new(a: f64, b: f64, n_terms: usize) -> Series1F1 {
+        assert!(a > 0.0 && b > 0.0, "a and b must be positive");
+
+        let mut coefficients = Vec::with_capacity(n_terms);
+
+        let mut coefficient = a / b;
+        coefficients.push(coefficient);
+        for i in (1..n_terms).map(|i| i as f64) {
+            coefficient *= (a + i) / ((i + 1.0) * (b + i));
+            coefficients.push(coefficient);
+        }
+
+        return Series1F1 {
+            coefficients: coefficients,
+        }
+    }
+
+    /// Create a new `Series1F1` computing the function derivative
+    pub fn derivative(a: f64, b: f64, n_terms: usize) -> Series1F1 {
+        assert!(a > 0.0 && b > 0.0, "a and b must be positive");
+
+        let mut coefficients = Vec::with_capacity(n_terms);
+
+        let mut coefficient = (a + 1.0) / (b + 1.0);
+        coefficients.push(coefficient);
+        for i in (1..n_terms).map(|i| i as f64) {
+            coefficient *= (a + i + 1.0) / ((i + 1.0) * (b + i + 1.0));
+            coefficients.push(coefficient);
+        }
+
+        return Series1F1 {
+            coefficients: coefficients,
+        }
+    }
+
+    /// Actually compute the function for input parameter `z`
+    #[allow(clippy::identity_op)]
+    pub fn compute(&self, z: f64) -> f64 {
+        let mut result = 1.0;
+        let mut z_pow = z;
+        let z4 = z * z * z * z;
+        // Use an adaptive summation: computes several terms at a time and check
+        // if required accuracy have been reached (typical n. of terms needed is
+        // ~20)
+        for i in (0..self.coefficients.len()).step_by(4)  {
+            let mut term = self.coefficients[i + 3];
+            term = self.coefficients[i + 2] + z * term;
+            term = self.coefficients[i + 1] + z * term;
+            term = self.coefficients[i + 0] + z * term;
+            term *= z_pow;
+
+            if term < HYPERGEOMETRIC_PRECISION * result {
+                result += term;
+                break;
+            }
+
+            z_pow *= z4;
+            result += term;
+        }
+
+        return result;
+    }
+}
+
+/// Compute 1F1 using the direct sum
+///
+/// `1F1(a, b, z) = \sum_{j=0}^{\infty} \frac{(a)_j}{(b)_jj!} z^{j}`
+#[derive(Debug, Clone)]
+struct HyperGeometricSeries {
+    /// First parameter to the 1F1 function
+    a: f64,
+    /// Second parameter to the 1F1 function
+    b: f64,
+    /// Actual implementation of 1F1, computing the function
+    series_1f1: Series1F1,
+    /// Actual implementation of 1F1, computing the function derivative
+    series_1f1_derivative: Series1F1,
+}
+
+impl HyperGeometricSeries {
+    /// Create a new `HyperGeometricSeries` for the given a and b parameters
+    pub fn new(a: f64, b: f64) -> HyperGeometricSeries {
+        assert!(a > 0.0 && b > 0.0, "a and b must be positive");
+
+        HyperGeometricSeries {
+            a,
+            b,
+            series_1f1: Series1F1::new(a, b, 200),
+            series_1f1_derivative: Series1F1::derivative(a, b, 200),
+        }
+    }
+
+    /// Computes `exp(z2) 1F1(a, b, z)`, or `exp(z2) d1F1/dz (a, b, z)` if
+    /// `derivative` is true
+    pub fn compute(&self, z: f64, z2: f64, derivative: bool) -> f64 {
+        if derivative {
+            self.series_1f1_derivative.compute(z) * f64::exp(z2) * self.a / self.b
+        } else {
+            self.series_1f1.compute(z) * f64::exp(z2)
+        }
+    }
+}
+
+/// Compute 1F1 using the asymptotic expansion as the third argument goes to
+/// infinity:
+///
+/// `1F1(a,b,z) \sim \exp{z} z^{a-b} \frac{\Gamma{b}}{\Gamma{a}} 2F0(a, b, z)`
+///
+/// where `2F0(a, b, z) = \sum_{j=0}^{\infty} \frac{(b-a)_j(1-a)_j}{j!} z^{-j}`
+#[derive(Debug, Clone)]
+struct HyperGeometricAsymptotic {
+    /// First parameter to the 1F1 function
+    a: f64,
+    /// Second parameter to the 1F1 function
+    b: f64,
+    /// Store precomputed `\frac{\Gamma{a}}{\Gamma{b}}`
+    gamma_ratio: f64,
+    /// Actual implementation of 2F0, computing the function
+    series_2f0: Series2F0,
+    /// Actual implementation of 2F0, computing the function derivative
+    series_2f0_derivative: Series2F0,
+}
+
+impl HyperGeometricAsymptotic {
+    /// Create a new `HyperGeometricAsymptotic` for the given a and b parameters
+    pub fn new(a: f64, b: f64) -> HyperGeometricAsymptotic {
+        assert!(a > 0.0 && b > 0.0, "a and b must be positive");
+
+        HyperGeometricAsymptotic {
+            a,
+            b,
+            gamma_ratio: gamma(a) / gamma(b),
+            series_2f0: Series2F0::new(a, b, 20),
+            series_2f0_derivative: Series2F0::derivative(a, b, 20),
+        }
+    }
+
+    /// Computes `exp(z2) 1F1(a, b, z)`, or `exp(z2) d1F1/dz (a, b, z)` if
+    /// `derivative` is true. The exponential factors are combined to prevent
+    /// overflow when `z` is large and `z2` is close to `-z`.
+    pub fn compute(&self, z: f64, z2: f64, derivative: bool) -> f64 {
+        let hyp2f0 = if derivative {
+            self.series_2f0_derivative.compute(z)
+        } else {
+            self.series_2f0.compute(z)
+        };
+        let factor = z.powf(self.a - self.b);
+        return hyp2f0 * f64::exp(z + z2) * factor / self.gamma_ratio;
+    }
+}
+
+/// Kummer's confluent hypergeometric function `1F1(a, b, z)` and its
+/// derivative with respect to `z`, for fixed positive `a` and `b` and
+/// non-negative `z`.
+///
+/// This switches between the power series and the asymptotic expansion of 1F1
+/// depending on the value of `z`. The switching point is determined once, when
+/// creating the function, as the point where both implementations agree.
+#[derive(Debug, Clone)]
+pub struct Hyp1F1 {
+    /// First parameter to the 1F1 function
+    a: f64,
+    /// Second parameter to the 1F1 function
+    b: f64,
+    /// when a == b, 1F1 is an exponential, so we use a fast path in this case
+    is_exponential: bool,
+    /// Asymptotic implementation of 1F1
+    asymptotic: HyperGeometricAsymptotic,
+    /// Series implementation of 1F1
+    series: HyperGeometricSeries,
+    /// For z above this value, use the asymptotic implementation, for z below
+    /// this value use the series implementation
+    switching_point: f64,
+}
+
+impl Hyp1F1 {
+    /// Create a new `Hyp1F1` for the given `a` and `b` parameters.
+    ///
+    /// # Panics
+    ///
+    /// If `a` or `b` are not strictly positive
+    pub fn new(a: f64, b: f64) -> Hyp1F1 {
+        // We try to determine what is the switching point between power series
+        // and asymptotic expansion. We choose the method that requires fewer
+        // terms for a given target accuracy. The asymptotic expansion tends to
+        // overflow at the switching point.
+
+        const MAX_ITERATIONS: usize = 100;
+
+        let series = HyperGeometricSeries::new(a, b);
+        let asymptotic = HyperGeometricAsymptotic::new(a, b);
+
+        if f64::abs(a - b) < 100.0 * f64::EPSILON {
+            return Hyp1F1 {
+                a: a,
+                b: b,
+                is_exponential: true,
+                series: series,
+                asymptotic: asymptotic,
+                switching_point: 0.0,
+            }
+        }
+
+        let series_1f1 = |z| series.compute(z, 0.0, false);
+        let asymptotic_1f1 = |z| asymptotic.compute(z, 0.0, false);
+
+        // Find the largest z for which the 2 definitions agree within tolerance
+        // using bisection.
+
+        // brackets the switching point
+        let mut z_switch = 1.0;
+        let mut z_below  = 1.0;
+        let mut z_above = 1.0;
+
+        let precision = 1e2 * HYPERGEOMETRIC_PRECISION;
+        if f64::abs(1.0 - series_1f1(z_switch) / asymptotic_1f1(z_switch)) > HYPERGEOMETRIC_PRECISION {
+            for _ in 0..MAX_ITERATIONS {
+                z_above *= 1.5;
+                let s = series_1f1(z_above);
+                let a = asymptotic_1f1(z_above);
+                if f64::abs(1.0 - s / a) < precision {
+                    break;
+                }
+            }
+        } else {
+            for _ in 0..MAX_ITERATIONS {
+                z_below *= 0.5;
+                let s = series_1f1(z_below);
+                let a = asymptotic_1f1(z_below);
+
+                if f64::abs(1.0 - s / a) > precision {
+                    break;
+                }
+            }
+        }
+
+        // and now bisects until we are reasonably close to an accurate
+        // determination
+        let mut accuracy_reached = false;
+        z_switch = (z_above + z_below) * 0.5;
+        let mut series_result = series_1f1(z_switch);
+        let mut asymptotic_result = asymptotic_1f1(z_switch);
+        for _ in 0..MAX_ITERATIONS {
+            if f64::abs(1.0 - series_result / asymptotic_result) > HYPERGEOMETRIC_PRECISION {
+                z_below = z_switch;
+            } else {
+                z_above = z_switch;
+            }
+
+            z_switch = (z_above + z_below) * 0.5;
+            series_result = series_1f1(z_switch);
+            asymptotic_result = asymptotic_1f1(z_switch);
+
+            if z_above - z_below < HYPERGEOMETRIC_PRECISION {
+                accuracy_reached = true;
+                break;
+            }
+        }
+
+        if !accuracy_reached {
+            warn!(
+                "failed to reach sufficient accuracy for the \
+                hypergeometric function with parameters a={} and b={}",
+                a, b
+            );
+        }
+
+        Hyp1F1 {
+            a: a,
+            b: b,
+            is_exponential: false,
+            series: series,
+            asymptotic: asymptotic,
+            switching_point: z_switch,
+        }
+    }
+
+    /// Get the `a` parameter of this function
+    pub fn a(&self) -> f64 {
+        self.a
+    }
+
+    /// Get the `b` parameter of this function
+    pub fn b(&self) -> f64 {
+        self.b
+    }
+
+    /// Evaluate `1F1(a, b, z)`
+    ///
+    /// # Panics
+    ///
+    /// If `z` is negative
+    pub fn compute(&self, z: f64) -> f64 {
+        return self.scaled(z, 0.0);
+    }
+
+    /// Evaluate the derivative of `1F1(a, b, z)` with respect to `z`, i.e.
+    /// `a / b 1F1(a + 1, b + 1, z)`
+    ///
+    /// # Panics
+    ///
+    /// If `z` is negative
+    pub fn derivative(&self, z: f64) -> f64 {
+        return self.scaled_derivative(z, 0.0);
+    }
+
+    /// Evaluate `exp(exponent) 1F1(a, b, z)`. For large `z`, 1F1 grows as
+    /// `exp(z)`, and this function allows to compute the product without
+    /// overflow when `exponent` is close to `-z`.
+    ///
+    /// # Panics
+    ///
+    /// If `z` is negative
+    pub fn scaled(&self, z: f64, exponent: f64) -> f64 {
+        return self.compute_impl(z, exponent, false);
+    }
+
+    /// Evaluate `exp(exponent) d1F1/dz (a, b, z)`, see [`Hyp1F1::scaled`].
+    ///
+    /// # Panics
+    ///
+    /// If `z` is negative
+    pub fn scaled_derivative(&self, z: f64, exponent: f64) -> f64 {
+        return self.compute_impl(z, exponent, true);
+    }
+
+    fn compute_impl(&self, z: f64, exponent: f64, derivative: bool) -> f64 {
+        assert!(z >= 0.0, "Hyp1F1 is only implemented for z >= 0, got z={}", z);
+
+        let result;
+        if self.is_exponential {
+            // the derivative of exp(z) is exp(z)
+            result = f64::exp(z + exponent);
+        } else if z > self.switching_point {
+            result = self.asymptotic.compute(z, exponent, derivative);
+        } else {
+            result = self.series.compute(z, exponent, derivative);
+        }
+
+        debug_assert!(
+            result.is_finite(),
+            "Hyp1F1 overflowed with z={}", z
+        );
+
+        return result;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use super::Hyp1F1;
+    use super::super::gamma;
+
+    #[test]
+    fn reference_values() {
+        // reference values computed with mpmath.hyp1f1
+        let function = Hyp1F1::new(1.5, 2.5);
+        assert_eq!(function.a(), 1.5);
+        assert_eq!(function.b(), 2.5);
+        assert_eq!(function.compute(0.0), 1.0);
+        assert_relative_eq!(function.compute(0.5), 1.361_290_826_369_701_6, max_relative=1e-12);
+        assert_relative_eq!(function.compute(30.0), 525_261_785_200.357_7, max_relative=1e-12);
+
+        let function = Hyp1F1::new(4.0, 3.5);
+        assert_relative_eq!(function.compute(2.0), 9.301_389_080_221_309, max_relative=1e-12);
+        assert_relative_eq!(function.compute(80.0), 2.796_059_942_964_005_4e35, max_relative=1e-12);
+
+        // a == b
+        let function = Hyp1F1::new(2.5, 2.5);
+        assert_relative_eq!(function.compute(3.2), f64::exp(3.2), max_relative=1e-14);
+    }
+
+    #[test]
+    fn recurrence_relations() {
+        for &(a, b) in &[(1.5, 1.5), (2.0, 3.5), (4.5, 2.5), (7.0, 9.5)] {
+            let function = Hyp1F1::new(a, b);
+            let a_plus_1 = Hyp1F1::new(a + 1.0, b);
+            let b_minus_1 = Hyp1F1::new(a, b - 1.0);
+
+            for &z in &[0.1, 1.0, 5.0, 20.0, 60.0] {
+                // contiguous relation between 1F1 with neighboring parameters
+                // (b - 1) M(a, b - 1) - a M(a + 1, b) + (a - b + 1) M(a, b) = 0
+                let residual = (b - 1.0) * b_minus_1.compute(z)
+                    - a * a_plus_1.compute(z)
+                    + (a - b + 1.0) * function.compute(z);
+                let scale = a * a_plus_1.compute(z);
+                assert_relative_eq!(residual / scale, 0.0, epsilon=1e-11);
+            }
+        }
+
+        // large z asymptotic behavior: 1F1(a, b, z) ~ Γ(b)/Γ(a) e^z z^(a - b)
+        let (a, b) = (2.5, 4.0);
+        let z: f64 = 600.0;
+        let expected = gamma(b) / gamma(a) * f64::exp(z) * z.powf(a - b);
+        assert_relative_eq!(Hyp1F1::new(a, b).compute(z), expected, max_relative=1e-2);
+    }
+
+    #[test]
+    fn derivative() {
+        for &(a, b) in &[(1.5, 1.5), (1.5, 2.5), (4.0, 3.5), (6.5, 11.5)] {
+            let function = Hyp1F1::new(a, b);
+            let shifted = Hyp1F1::new(a + 1.0, b + 1.0);
+            for &z in &[0.0, 0.3, 2.0, 15.0, 50.0, 200.0] {
+                // d/dz 1F1(a, b, z) = a / b 1F1(a + 1, b + 1, z)
+                let expected = a / b * shifted.compute(z);
+                assert_relative_eq!(function.derivative(z), expected, max_relative=1e-9);
+            }
+
+            let delta = 1e-6;
+            for &z in &[0.5, 3.0, 40.0] {
+                let finite_difference = (function.compute(z + delta) - function.compute(z - delta)) / (2.0 * delta);
+                assert_relative_eq!(function.derivative(z), finite_difference, max_relative=1e-6);
+            }
+        }
+    }
+
+    #[test]
+    fn scaled() {
+        let function = Hyp1F1::new(2.0, 3.5);
+        for &z in &[0.5, 10.0, 60.0] {
+            assert_relative_eq!(function.scaled(z, -z), function.compute(z) * f64::exp(-z), max_relative=1e-12);
+            assert_relative_eq!(function.scaled_derivative(z, -z), function.derivative(z) * f64::exp(-z), max_relative=1e-12);
+        }
+
+        // 1F1 itself would overflow here, but not the scaled version
+        let z: f64 = 1000.0;
+        let expected = gamma(3.5) / gamma(2.0) * z.powf(2.0 - 3.5);
+        assert_relative_eq!(function.scaled(z, -z), expected, max_relative=1e-2);
+    }
+
+    #[test]
+    #[should_panic = "Hyp1F1 is only implemented for z >= 0, got z=-1"]
+    fn negative_z() {
+        Hyp1F1::new(1.5, 2.5).compute(-1.0);
+    }
+}
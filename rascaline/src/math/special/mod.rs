@@ -0,0 +1,303 @@
+//! Special functions used in the calculation of radial integrals, exposed
+//! here so that radial basis defined outside of rascaline can use the exact
+//! same numerical implementation.
+
+#![allow(clippy::excessive_precision)]
+
+mod hypergeometric;
+pub use self::hypergeometric::Hyp1F1;
+
+/// Auxiliary variable when evaluating the `gamma` function
+const GAMMA_R: f64 = 10.900511;
+
+/// Constant value for `2 * sqrt(e / pi)`
+const TWO_SQRT_E_OVER_PI: f64 = 1.860382734205265717;
+
+/// Polynomial coefficients for approximating the `gamma` function
+const GAMMA_DK: &[f64] = &[
+    2.48574089138753565546e-5,
+    1.05142378581721974210,
+    -3.45687097222016235469,
+    4.51227709466894823700,
+    -2.98285225323576655721,
+    1.05639711577126713077,
+    -1.95428773191645869583e-1,
+    1.70970543404441224307e-2,
+    -5.71926117404305781283e-4,
+    4.63399473359905636708e-6,
+    -2.71994908488607703910e-9,
+];
+
+/// Computes the gamma function with an accuracy of 16 floating point digits.
+/// The implementation is derived from "An Analysis of the Lanczos Gamma
+/// Approximation", Glendon Ralph Pugh, 2004 p. 116.
+///
+/// This code was extracted from statrs, which is © 2016 Michael Ma, distributed
+/// under MIT license. Cf <https://github.com/boxtown/statrs/blob/c5536a8c916852259832b2064a9b845b68751c8f/src/function/gamma.rs>
+/// for the original code
+pub fn gamma(x: f64) -> f64 {
+    if x < 0.5 {
+        let s = GAMMA_DK
+            .iter()
+            .enumerate()
+            .skip(1)
+            .fold(GAMMA_DK[0], |s, t| s + t.1 / (t.0 as f64 - x));
+
+        std::f64::consts::PI
+            / ((std::f64::consts::PI * x).sin()
+                * s
+                * TWO_SQRT_E_OVER_PI
+                * ((0.5 - x + GAMMA_R) / std::f64::consts::E).powf(0.5 - x))
+    } else {
+        let s = GAMMA_DK
+            .iter()
+            .enumerate()
+            .skip(1)
+            .fold(GAMMA_DK[0], |s, t| s + t.1 / (x + t.0 as f64 - 1.0));
+
+        s * TWO_SQRT_E_OVER_PI * ((x - 0.5 + GAMMA_R) / std::f64::consts::E).powf(x - 0.5)
+    }
+}
+
+/// Compute the double factorial `n!! = n (n - 2) (n - 4) ...` as a floating
+/// point number, with the convention `0!! = 1!! = 1`.
+pub fn double_factorial(n: usize) -> f64 {
+    let mut result = 1.0;
+    let mut i = n;
+    while i > 1 {
+        result *= i as f64;
+        i -= 2;
+    }
+    return result;
+}
+
+/// Compute the complementary error function `erfc(x) = 1 - erf(x)` with close
+/// to double precision accuracy.
+///
+/// For `|x| < 2.5`, this uses the series `erf(x) = 2 / sqrt(π) exp(-x^2) Σ_n
+/// 2^n x^{2n + 1} / (2n + 1)!!`, where all terms are positive. For larger
+/// values, the continued fraction expansion of `erfc` is used instead, which
+/// converges quickly in this regime and does not suffer from cancellation.
+pub fn erfc(x: f64) -> f64 {
+    if x.is_nan() {
+        return f64::NAN;
+    } else if x < 0.0 {
+        return 2.0 - erfc(-x);
+    } else if x > 27.0 {
+        // the result is smaller than the smallest positive f64
+        return 0.0;
+    }
+
+    let x2 = x * x;
+    let exp_x2 = f64::exp(-x2);
+
+    if x < 2.5 {
+        let mut term = x;
+        let mut sum = x;
+        let mut n = 0.0;
+        while term > 1e-17 * sum {
+            n += 1.0;
+            term *= 2.0 * x2 / (2.0 * n + 1.0);
+            sum += term;
+        }
+        return 1.0 - 2.0 / std::f64::consts::PI.sqrt() * exp_x2 * sum;
+    }
+
+    // erfc(x) = exp(-x^2) / sqrt(π) * 1 / (x + 1/2 / (x + 1 / (x + 3/2 / (x + ...))))
+    // evaluated from the end with a fixed number of terms
+    let mut fraction = x;
+    for n in (1..60).rev() {
+        fraction = x + 0.5 * n as f64 / fraction;
+    }
+    return exp_x2 / (std::f64::consts::PI.sqrt() * fraction);
+}
+
+/// Compute the spherical Bessel functions of the first kind `j_l(x)` for all
+/// `l` from 0 to `max_angular` (included), and store them in `values`.
+///
+/// For `x > max_angular`, the functions are computed with the upward
+/// recurrence `j_{l+1}(x) = (2l + 1) / x j_l(x) - j_{l-1}(x)`, which is stable
+/// in this regime. Otherwise, the downward recurrence (Miller's algorithm) is
+/// used instead, normalized with the exact values of `j_0` or `j_1`; and a
+/// series expansion is used for very small `x`.
+///
+/// # Panics
+///
+/// If `values` does not contain `max_angular + 1` elements, or if `x` is
+/// negative or not finite.
+pub fn spherical_bessel(max_angular: usize, x: f64, values: &mut [f64]) {
+    assert_eq!(
+        values.len(), max_angular + 1,
+        "wrong size for values, expected {} elements, got {}", max_angular + 1, values.len()
+    );
+    assert!(x >= 0.0 && x.is_finite(), "x must be a positive number in spherical_bessel, got {}", x);
+
+    if x < 1e-3 {
+        // j_l(x) = x^l / (2l + 1)!! (1 - x^2 / (2 (2l + 3)) + x^4 / (8 (2l + 3) (2l + 5)) - ...)
+        let x2 = x * x;
+        let mut x_l = 1.0;
+        for (l, value) in values.iter_mut().enumerate() {
+            let a = 2.0 * l as f64 + 3.0;
+            *value = x_l / double_factorial(2 * l + 1) * (1.0 - x2 / (2.0 * a) * (1.0 - x2 / (4.0 * (a + 2.0))));
+            x_l *= x;
+        }
+        return;
+    }
+
+    let (sin, cos) = x.sin_cos();
+    let j_0 = sin / x;
+    let j_1 = sin / (x * x) - cos / x;
+
+    if x > max_angular as f64 {
+        values[0] = j_0;
+        if max_angular > 0 {
+            values[1] = j_1;
+        }
+        for l in 1..max_angular {
+            values[l + 1] = (2 * l + 1) as f64 / x * values[l] - values[l - 1];
+        }
+        return;
+    }
+
+    // Miller's algorithm: start the downward recurrence from an arbitrary
+    // value well above max_angular, and normalize the result at the end
+    let start = max_angular + 30;
+    let mut next = 0.0;
+    let mut current = 1e-300;
+    for l in (1..=start).rev() {
+        let previous = (2 * l + 1) as f64 / x * current - next;
+        next = current;
+        current = previous;
+
+        if l - 1 <= max_angular {
+            values[l - 1] = current;
+        }
+
+        if current.abs() > 1e250 {
+            // rescale everything to prevent overflow
+            next *= 1e-250;
+            current *= 1e-250;
+            for value in values.iter_mut().skip(l - 1) {
+                *value *= 1e-250;
+            }
+        }
+    }
+
+    // `current` is j_0 and `next` is j_1 (up to the normalization)
+    let normalization = if j_0.abs() > j_1.abs() {
+        j_0 / current
+    } else {
+        j_1 / next
+    };
+
+    for value in values.iter_mut() {
+        *value *= normalization;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn test_gamma() {
+        assert!(gamma(f64::NAN).is_nan());
+        assert_relative_eq!(gamma(1.000001e-35), 9.9999900000099999900000099999899999522784235098567139293e+34, max_relative=1e-13); //, 1e20);
+        assert_relative_eq!(gamma(1.000001e-10), 9.99998999943278432519738283781280989934496494539074049002e+9, max_relative=1e-13); //, 1e-5);
+        assert_relative_eq!(gamma(1.000001e-5), 99999.32279432557746387178953902739303931424932435387031653234, max_relative=1e-13); //, 1e-10);
+        assert_relative_eq!(gamma(1.000001e-2), 99.43248512896257405886134437203369035261893114349805309870831, max_relative=1e-13); //, 1e-13);
+        assert_relative_eq!(gamma(-4.8), -0.06242336135475955314181664931547009890495158793105543559676, max_relative=1e-13); //, 1e-13);
+        assert_relative_eq!(gamma(-1.5), 2.363271801207354703064223311121526910396732608163182837618410, max_relative=1e-13); //, 1e-13);
+        assert_relative_eq!(gamma(-0.5), -3.54490770181103205459633496668229036559509891224477425642761, max_relative=1e-13); //, 1e-13);
+        assert_relative_eq!(gamma(1.0e-5 + 1.0e-16), 99999.42279322556767360213300482199406241771308740302819426480, max_relative=1e-13);
+        assert_relative_eq!(gamma(0.1), 9.513507698668731836292487177265402192550578626088377343050000, max_relative=1e-13);
+        assert_relative_eq!(gamma(1.0 - 1.0e-14), 1.000000000000005772156649015427511664653698987042926067639529, max_relative=1e-13);
+        assert_relative_eq!(gamma(1.0), 1.0, max_relative=1e-13);
+        assert_relative_eq!(gamma(1.0 + 1.0e-14), 0.99999999999999422784335098477029953441189552403615306268023, max_relative=1e-13);
+        assert_relative_eq!(gamma(1.5), 0.886226925452758013649083741670572591398774728061193564106903, max_relative=1e-13);
+        assert_relative_eq!(gamma(std::f64::consts::PI / 2.0), 0.890560890381539328010659635359121005933541962884758999762766, max_relative=1e-13);
+        assert_relative_eq!(gamma(2.0), 1.0, max_relative=1e-13);
+        assert_relative_eq!(gamma(2.5), 1.329340388179137020473625612505858887098162092091790346160355, max_relative=1e-13);
+        assert_relative_eq!(gamma(3.0), 2.0, max_relative=1e-13);
+        assert_relative_eq!(gamma(std::f64::consts::PI), 2.288037795340032417959588909060233922889688153356222441199380, max_relative=1e-13);
+        assert_relative_eq!(gamma(3.5), 3.323350970447842551184064031264647217745405230229475865400889, max_relative=1e-13);
+        assert_relative_eq!(gamma(4.0), 6.0, max_relative=1e-13);
+        assert_relative_eq!(gamma(4.5), 11.63172839656744892914422410942626526210891830580316552890311, max_relative=1e-13);
+        assert_relative_eq!(gamma(5.0 - 1.0e-14), 23.99999999999963853175957637087420162718107213574617032780374, max_relative=1e-13);
+        assert_relative_eq!(gamma(5.0), 24.0, max_relative=1e-13);
+        assert_relative_eq!(gamma(5.0 + 1.0e-14), 24.00000000000036146824042363510111050137786752408660789873592, max_relative=1e-13);
+        assert_relative_eq!(gamma(5.5), 52.34277778455352018114900849241819367949013237611424488006401, max_relative=1e-13);
+        assert_relative_eq!(gamma(10.1), 454760.7514415859508673358368319076190405047458218916492282448, max_relative=1e-13);
+        assert_relative_eq!(gamma(150.0 + 1.0e-12), 3.8089226376496421386707466577615064443807882167327097140e+260, max_relative=1e-12);
+    }
+
+    #[test]
+    fn test_double_factorial() {
+        assert_eq!(double_factorial(0), 1.0);
+        assert_eq!(double_factorial(1), 1.0);
+        assert_eq!(double_factorial(2), 2.0);
+        assert_eq!(double_factorial(7), 105.0);
+        assert_eq!(double_factorial(10), 3840.0);
+
+        // (2n - 1)!! = 2^n Γ(n + 1/2) / sqrt(π)
+        for n in 1..30 {
+            let expected = 2.0_f64.powi(n as i32) * gamma(n as f64 + 0.5) / f64::sqrt(std::f64::consts::PI);
+            assert_relative_eq!(double_factorial(2 * n - 1), expected, max_relative=1e-12);
+        }
+    }
+
+    #[test]
+    fn test_erfc() {
+        assert!(erfc(f64::NAN).is_nan());
+        assert_eq!(erfc(0.0), 1.0);
+        assert_relative_eq!(erfc(0.5), 0.4795001221869535, max_relative=1e-14);
+        assert_relative_eq!(erfc(1.0), 0.15729920705028513, max_relative=1e-14);
+        assert_relative_eq!(erfc(2.4), 6.885138966450786e-4, max_relative=1e-13);
+        assert_relative_eq!(erfc(2.5), 4.069520174449590e-4, max_relative=1e-13);
+        assert_relative_eq!(erfc(3.0), 2.209049699858544e-5, max_relative=1e-13);
+        assert_relative_eq!(erfc(5.0), 1.5374597944280349e-12, max_relative=1e-13);
+        assert_relative_eq!(erfc(-1.0), 1.8427007929497148, max_relative=1e-14);
+        assert_eq!(erfc(30.0), 0.0);
+    }
+
+    #[test]
+    fn test_spherical_bessel() {
+        // reference values from the power series, which is accurate enough
+        // for small values of x
+        let series = |l: usize, x: f64| {
+            let mut sum = 0.0;
+            let mut term = 1.0 / double_factorial(2 * l + 1);
+            for k in 0..100 {
+                sum += term;
+                term *= -0.5 * x * x / ((k + 1) as f64 * (2 * (l + k) + 3) as f64);
+            }
+            return x.powi(l as i32) * sum;
+        };
+
+        let max_angular = 10;
+        let mut values = vec![0.0; max_angular + 1];
+        for &x in &[0.0, 1e-4, 2e-3, 0.5, 1.0, 2.5, 4.0, 7.9, 10.5] {
+            spherical_bessel(max_angular, x, &mut values);
+            for (l, &value) in values.iter().enumerate() {
+                assert_relative_eq!(value, series(l, x), max_relative=1e-9, epsilon=1e-14);
+            }
+        }
+
+        // closed form expressions, for larger values of x
+        for &x in &[0.3, 3.3, 12.0, 57.3] {
+            spherical_bessel(3, x, &mut values[..4]);
+            let (sin, cos) = x.sin_cos();
+            assert_relative_eq!(values[0], sin / x, max_relative=1e-12);
+            assert_relative_eq!(values[1], sin / (x * x) - cos / x, max_relative=1e-10, epsilon=1e-14);
+            assert_relative_eq!(
+                values[2], (3.0 / (x * x) - 1.0) * sin / x - 3.0 * cos / (x * x),
+                max_relative=1e-10, epsilon=1e-14
+            );
+            assert_relative_eq!(
+                values[3], (15.0 / (x * x * x) - 6.0 / x) * sin / x - (15.0 / (x * x) - 1.0) * cos / x,
+                max_relative=1e-9, epsilon=1e-14
+            );
+        }
+    }
+}
@@ -88,7 +88,7 @@ impl std::fmt::Debug for LegendreArray {
 /// code like
 ///
 /// ```
-/// # use rascaline::calculators::soap::SphericalHarmonicsArray;
+/// # use rascaline::math::SphericalHarmonicsArray;
 /// let mut array = SphericalHarmonicsArray::new(8);
 /// array[[6, 3]] = 3.0;
 /// array[[6, -3]] = -3.0;
@@ -117,6 +117,22 @@ impl SphericalHarmonicsArray {
         }
     }
 
+    /// Get the maximal angular degree of the data stored in this array
+    pub fn max_angular(&self) -> usize {
+        return self.max_angular as usize;
+    }
+
+    /// Get all the elements of this array as a slice, ordered by increasing
+    /// `l` first, and then by increasing `m` from `-l` to `l`.
+    pub fn as_slice(&self) -> &[f64] {
+        return &self.data;
+    }
+
+    /// Set all the elements of this array to zero
+    pub fn zero(&mut self) {
+        self.data.iter_mut().for_each(|v| *v = 0.0);
+    }
+
     #[inline]
     #[allow(clippy::suspicious_operation_groupings)]
     fn linear_index(&self, index: [isize; 2]) -> usize {
@@ -412,6 +428,79 @@ impl SphericalHarmonics {
     }
 }
 
+/// Reusable workspace for the calculation of spherical harmonics, containing
+/// both the [`SphericalHarmonics`] implementation and the arrays to store the
+/// values and (optionally) gradients.
+///
+/// This should be allocated once per thread and re-used for all the pairs
+/// handled by this thread, instead of allocating new arrays for each pair.
+#[derive(Debug, Clone)]
+pub struct SphericalHarmonicsCache {
+    /// Implementation of the spherical harmonics
+    code: SphericalHarmonics,
+    /// Values of the spherical harmonics from the last call to `compute`
+    pub values: SphericalHarmonicsArray,
+    /// Gradients of the spherical harmonics (one array each for x/y/z) from
+    /// the last call to `compute`, or `None` if this cache was created without
+    /// gradients
+    pub gradients: Option<[SphericalHarmonicsArray; 3]>,
+}
+
+impl SphericalHarmonicsCache {
+    /// Create a new cache for spherical harmonics up to `max_angular`,
+    /// allocating memory for the gradients if `gradients` is `true`
+    pub fn new(max_angular: usize, gradients: bool) -> SphericalHarmonicsCache {
+        let gradients = if gradients {
+            Some([
+                SphericalHarmonicsArray::new(max_angular),
+                SphericalHarmonicsArray::new(max_angular),
+                SphericalHarmonicsArray::new(max_angular),
+            ])
+        } else {
+            None
+        };
+
+        return SphericalHarmonicsCache {
+            code: SphericalHarmonics::new(max_angular),
+            values: SphericalHarmonicsArray::new(max_angular),
+            gradients: gradients,
+        };
+    }
+
+    /// Compute the spherical harmonics for the given `direction`, including
+    /// gradients if this cache was created with gradients.
+    pub fn compute(&mut self, direction: Vector3D) {
+        self.code.compute(direction, &mut self.values, self.gradients.as_mut());
+    }
+
+    /// Compute the spherical harmonics for the given `direction`, without
+    /// computing gradients. The gradients are left untouched.
+    pub fn compute_no_gradients(&mut self, direction: Vector3D) {
+        self.code.compute(direction, &mut self.values, None);
+    }
+
+    /// Compute the spherical harmonics for the given `direction`, and add them
+    /// to `output` multiplied by `weight`, i.e. `output[[l, m]] += weight *
+    /// Y_l^m(direction)`.
+    ///
+    /// This can be used to build densities expanded on spherical harmonics by
+    /// accumulating the contributions of all neighbors in a single array. The
+    /// values and gradients for this `direction` alone are still available in
+    /// `self.values` and `self.gradients` after this call.
+    pub fn accumulate(&mut self, direction: Vector3D, weight: f64, output: &mut SphericalHarmonicsArray) {
+        assert_eq!(
+            output.max_angular, self.values.max_angular,
+            "wrong size for the output array, expected max_angular to be {}, got {}",
+            self.values.max_angular, output.max_angular,
+        );
+
+        self.compute(direction);
+        for (output, value) in output.data.iter_mut().zip(&self.values.data) {
+            *output += weight * value;
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::collections::HashSet;
@@ -566,4 +655,61 @@ mod tests {
             spherical_harmonics.compute(Vector3D::new(1.0, 1.0, 1.0), &mut values, None);
         }
     }
+
+    #[test]
+    fn cache() {
+        let direction = Vector3D::new(1.0, -3.0, 9.0) / f64::sqrt(91.0);
+
+        let max_angular = 8;
+        let mut spherical_harmonics = SphericalHarmonics::new(max_angular);
+        let mut values = SphericalHarmonicsArray::new(max_angular);
+        let mut gradients = [
+            SphericalHarmonicsArray::new(max_angular),
+            SphericalHarmonicsArray::new(max_angular),
+            SphericalHarmonicsArray::new(max_angular),
+        ];
+        spherical_harmonics.compute(direction, &mut values, Some(&mut gradients));
+
+        let mut cache = SphericalHarmonicsCache::new(max_angular, true);
+        cache.compute(direction);
+        assert_eq!(cache.values.data, values.data);
+        let cache_gradients = cache.gradients.as_ref().unwrap();
+        for spatial in 0..3 {
+            assert_eq!(cache_gradients[spatial].data, gradients[spatial].data);
+        }
+
+        let mut cache = SphericalHarmonicsCache::new(max_angular, false);
+        cache.compute_no_gradients(direction);
+        assert_eq!(cache.values.data, values.data);
+        assert!(cache.gradients.is_none());
+    }
+
+    #[test]
+    fn accumulate() {
+        let directions = [
+            Vector3D::new(1.0, -3.0, 9.0) / f64::sqrt(91.0),
+            Vector3D::new(0.0, 0.0, 1.0),
+        ];
+
+        let max_angular = 6;
+        let mut cache = SphericalHarmonicsCache::new(max_angular, false);
+        let mut output = SphericalHarmonicsArray::new(max_angular);
+        cache.accumulate(directions[0], 0.5, &mut output);
+        cache.accumulate(directions[1], -2.0, &mut output);
+
+        let mut expected = SphericalHarmonicsArray::new(max_angular);
+        let mut values = SphericalHarmonicsArray::new(max_angular);
+        let mut spherical_harmonics = SphericalHarmonics::new(max_angular);
+        for (&direction, &weight) in directions.iter().zip(&[0.5, -2.0]) {
+            spherical_harmonics.compute(direction, &mut values, None);
+            for (e, v) in expected.data.iter_mut().zip(&values.data) {
+                *e += weight * v;
+            }
+        }
+        assert_eq!(output.as_slice(), expected.as_slice());
+        assert_eq!(output.max_angular(), max_angular);
+
+        output.zero();
+        assert!(output.as_slice().iter().all(|&v| v == 0.0));
+    }
 }
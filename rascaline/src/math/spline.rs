@@ -0,0 +1,437 @@
+use ndarray::{Array, ArrayViewMut, Dimension, azip};
+use log::info;
+
+use crate::Error;
+use super::linear_combination;
+
+/// Maximal number of points in the splines
+const MAX_SPLINE_SIZE: usize = 10_000;
+
+/// A single control point/knot in the Hermit cubic spline
+#[derive(Debug, Clone)]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct HermitSplinePoint<D: Dimension> {
+    /// Position of the point
+    position: f64,
+    /// Value of the function to interpolate at the position
+    value: Array<f64, D>,
+    /// Derivative of the function to interpolate at the position
+    derivative: Array<f64, D>,
+}
+
+/// `Spline` interpolates a function returning arrays of values of a fixed
+/// shape between two points (`start` and `stop`), using [cubic Hermit
+/// spline][splines-wiki]. The spline can then be used to evaluate both the
+/// function and its derivative.
+///
+/// This is the machinery used by [`crate::calculators::soap::SplinedRadialIntegral`],
+/// and can be used to tabulate any expensive radial function.
+///
+/// [splines-wiki]: https://en.wikipedia.org/wiki/Cubic_Hermite_spline
+#[derive(Debug, Clone)]
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct Spline<D: Dimension> {
+    points: Vec<HermitSplinePoint<D>>,
+}
+
+impl<D: Dimension> Spline<D> {
+    /// Create a new `Spline` from the given control points, containing
+    /// the position, value and derivative of the function at this position.
+    /// The points do not need to be sorted, but they must all have the same
+    /// shape and there must be at least two of them.
+    #[allow(clippy::type_complexity)]
+    pub fn from_points(points: Vec<(f64, Array<f64, D>, Array<f64, D>)>) -> Result<Spline<D>, Error> {
+        let mut points = points.into_iter()
+            .map(|(position, value, derivative)| HermitSplinePoint { position, value, derivative })
+            .collect::<Vec<_>>();
+
+        if points.iter().any(|point| !point.position.is_finite()) {
+            return Err(Error::InvalidParameter(
+                "spline control points positions must be finite".into()
+            ));
+        }
+
+        points.sort_unstable_by(|a, b| {
+            a.position.partial_cmp(&b.position).expect("got NaN while sorting by point position")
+        });
+
+        let spline = Spline {
+            points: points,
+        };
+        spline.check()?;
+
+        return Ok(spline);
+    }
+
+    /// Create a new `Spline` for the given `function` between `start` and
+    /// `stop`. The function is called as `function(x, values, derivatives)`,
+    /// and should fill `values` and `derivatives` (which have the given
+    /// `shape`) with the value and derivative of the function at `x`.
+    ///
+    /// Control points are added to the spline until the requested accuracy
+    /// is reached, where the error is estimated in the middle of each
+    /// interval between control points. New control points are only added in
+    /// the intervals where the error is still too large. We consider that the
+    /// accuracy is reached when either the mean absolute error or the mean
+    /// relative error gets below the `accuracy` threshold.
+    pub fn with_accuracy<F>(
+        start: f64,
+        stop: f64,
+        shape: D,
+        accuracy: f64,
+        function: F,
+    ) -> Result<Spline<D>, Error> where F: Fn(f64, ArrayViewMut<f64, D>, ArrayViewMut<f64, D>) {
+        if accuracy < 0.0 {
+            return Err(Error::InvalidParameter(format!(
+                "got invalid accuracy in spline ({}), it must be positive", accuracy
+            )));
+        }
+
+        if start >= stop || !start.is_finite() || !stop.is_finite() {
+            return Err(Error::InvalidParameter(format!(
+                "got invalid range in spline ({} to {}), start must be smaller than stop",
+                start, stop
+            )));
+        }
+
+        let compute_point = move |position: f64| {
+            let mut value = Array::from_elem(shape.clone(), 0.0);
+            let mut derivative = Array::from_elem(shape.clone(), 0.0);
+            function(position, value.view_mut(), derivative.view_mut());
+            HermitSplinePoint { position, value, derivative }
+        };
+
+        let initial_grid_size = 11;
+        let grid_step = (stop - start) / (initial_grid_size - 1) as f64;
+
+        let mut points = Vec::new();
+        for k in 0..initial_grid_size {
+            let position = if k == initial_grid_size - 1 { stop } else { start + k as f64 * grid_step };
+            points.push(compute_point(position));
+        }
+
+        let mut spline = Spline {
+            points: points,
+        };
+
+        // add more points as required to reach the requested accuracy
+        loop {
+            let mut new_points = Vec::new();
+
+            // evaluate the error at points in between grid points, since these
+            // should have the highest error in average.
+            let mut max_absolute_error = 0.0;
+            let mut mean_absolute_error = 0.0;
+            let mut mean_relative_error = 0.0;
+            let mut error_count = 0;
+            for k in 0..(spline.len() - 1) {
+                let (left, right) = (spline.points[k].position, spline.points[k + 1].position);
+                let position = left + 0.5 * (right - left);
+                let point = compute_point(position);
+
+                let mut interpolated = Array::from_elem(point.value.raw_dim(), 0.0);
+                spline.compute(position, interpolated.view_mut(), None);
+
+                // get the error across all values in the arrays
+                let mut interval_absolute_error = 0.0;
+                let mut interval_relative_error = 0.0;
+                azip!((interpolated in &interpolated, value in &point.value) {
+                    let absolute_error = f64::abs(interpolated - value);
+                    if absolute_error > max_absolute_error {
+                        max_absolute_error = absolute_error;
+                    }
+
+                    interval_absolute_error += absolute_error;
+                    interval_relative_error += f64::abs((interpolated - value) / value);
+                });
+
+                mean_absolute_error += interval_absolute_error;
+                mean_relative_error += interval_relative_error;
+                error_count += point.value.len();
+
+                let interval_size = point.value.len() as f64;
+                let converged = interval_absolute_error / interval_size < accuracy
+                    || interval_relative_error / interval_size < accuracy;
+                new_points.push((point, converged));
+            }
+            mean_absolute_error /= error_count as f64;
+            mean_relative_error /= error_count as f64;
+
+            if mean_absolute_error < accuracy || mean_relative_error < accuracy {
+                info!(
+                    "spline reached requested accuracy ({:.3e}) on average with {} reference points (max absolute error is {:.3e})",
+                    accuracy, spline.len(), max_absolute_error,
+                );
+                break;
+            }
+
+            // only refine the intervals which did not reach the requested
+            // accuracy, or all of them if the error is spread evenly
+            let refine_all = new_points.iter().all(|(_, converged)| *converged);
+            let new_points = new_points.into_iter()
+                .filter(|(_, converged)| refine_all || !converged)
+                .map(|(point, _)| point)
+                .collect::<Vec<_>>();
+
+            if spline.len() + new_points.len() > MAX_SPLINE_SIZE {
+                return Err(Error::Internal(format!(
+                    "failed to reach requested accuracy ({:e}) in spline interpolation, \
+                    the best we got was {:e}",
+                    accuracy, max_absolute_error
+                )));
+            }
+
+            // add more points and continue
+            for point in new_points {
+                spline.add_point(point);
+            }
+        }
+
+        return Ok(spline);
+    }
+
+    /// Add a new control points to this spline. The new point must be between
+    /// the first and last existing points.
+    fn add_point(&mut self, point: HermitSplinePoint<D>) {
+        debug_assert!(point.position > self.start() && point.position < self.stop());
+        match self.points.binary_search_by(
+            |v| v.position.partial_cmp(&point.position).expect("got NaN")
+        ) {
+            Ok(_) => panic!("trying to add the same point twice to the spline"),
+            Err(k) => self.points.insert(k, point)
+        }
+    }
+
+    /// Check that this spline is valid: it should contain at least two
+    /// points, with the same shape and sorted by position. This is used to
+    /// validate splines which have been deserialized from external data.
+    pub fn check(&self) -> Result<(), Error> {
+        if self.points.len() < 2 {
+            return Err(Error::InvalidParameter(format!(
+                "a spline needs at least two control points, got {}", self.points.len()
+            )));
+        }
+
+        let shape = self.points[0].value.shape();
+        for point in &self.points {
+            if point.value.shape() != shape || point.derivative.shape() != shape {
+                return Err(Error::InvalidParameter(format!(
+                    "invalid shape for spline control point at {}: expected {:?}, got {:?}",
+                    point.position, shape, point.value.shape()
+                )));
+            }
+        }
+
+        let sorted = self.points.windows(2).all(|w| w[0].position < w[1].position);
+        if !sorted {
+            return Err(Error::InvalidParameter(
+                "spline control points are not sorted by position".into()
+            ));
+        }
+
+        return Ok(());
+    }
+
+    /// Get the number of control points in this spline
+    pub fn len(&self) -> usize {
+        self.points.len()
+    }
+
+    /// Check if this spline is empty, which can only happen for invalid
+    /// splines (see [`Spline::check`]).
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    /// Get the position of the control points for this spline
+    pub fn positions(&self) -> Vec<f64> {
+        self.points.iter().map(|p| p.position).collect()
+    }
+
+    /// Get the position of the first control point of this spline
+    pub fn start(&self) -> f64 {
+        self.points[0].position
+    }
+
+    /// Get the position of the last control point of this spline
+    pub fn stop(&self) -> f64 {
+        self.points[self.points.len() - 1].position
+    }
+
+    /// Get the shape of the values interpolated by this spline
+    pub fn shape(&self) -> &[usize] {
+        self.points[0].value.shape()
+    }
+
+    /// Evaluate the spline at `x`, storing the interpolated values in
+    /// `values`, and optionally the derivatives of the spline with respect to
+    /// `x` in `derivatives`.
+    ///
+    /// `x` must be between [`Spline::start`] and [`Spline::stop`]. This is only
+    /// checked in debug mode.
+    #[allow(clippy::similar_names)]
+    pub fn compute(&self, x: f64, mut values: ArrayViewMut<f64, D>, derivatives: Option<ArrayViewMut<f64, D>>) {
+        // notation in this function follows
+        // https://en.wikipedia.org/wiki/Cubic_Hermite_spline
+        debug_assert!(x >= self.start() && x <= self.stop() && x.is_finite());
+
+        let k = match self.points.binary_search_by(
+            |v| v.position.partial_cmp(&x).expect("got NaN")
+        ) {
+            // use the last interval when x is exactly on the last point
+            Ok(k) => k.min(self.points.len() - 2),
+            Err(k) => k - 1,
+        };
+
+        let point_k = &self.points[k];
+        let point_k_1 = &self.points[k + 1];
+
+        let x_k = point_k.position;
+        let x_k_1 = point_k_1.position;
+        debug_assert!(x_k <= x && x <= x_k_1);
+
+        let delta = x_k_1 - x_k;
+        let t = (x - x_k) / delta;
+        let t_2 = t * t;
+        let t_3 = t_2 * t;
+
+        // Hermit base polynomials
+        let h00 = 2.0 * t_3 - 3.0 * t_2 + 1.0;
+        let h10 = t_3 - 2.0 * t_2 + t;
+        let h01 = -2.0 * t_3 + 3.0 * t_2;
+        let h11 = t_3 - t_2;
+
+        let p_k = &point_k.value;
+        let p_k_1 = &point_k_1.value;
+
+        let m_k = &point_k.derivative;
+        let m_k_1 = &point_k_1.derivative;
+
+        let h10_delta = h10 * delta;
+        let h11_delta = h11 * delta;
+        if let Some(output) = values.as_slice_mut() {
+            linear_combination(
+                output,
+                [h00, h10_delta, h01, h11_delta],
+                [as_slice(p_k), as_slice(m_k), as_slice(p_k_1), as_slice(m_k_1)],
+            );
+        } else {
+            azip!((v in values, p_k in p_k, p_k_1 in p_k_1, m_k in m_k, m_k_1 in m_k_1) {
+                *v = h00 * p_k + h10_delta * m_k + h01 * p_k_1 + h11_delta * m_k_1;
+            });
+        }
+
+        if let Some(mut derivatives) = derivatives {
+            let d_h00_dt = 6.0 * (t_2 - t);
+            let d_h10_dt = 3.0 * t_2 - 4.0 * t + 1.0;
+            let d_h01_dt = -d_h00_dt;
+            let d_h11_dt = 3.0 * t_2 - 2.0 * t;
+
+            let dx_dt = 1.0 / delta;
+
+            let d_h00_dx = d_h00_dt * dx_dt;
+            let d_h01_dx = d_h01_dt * dx_dt;
+            if let Some(output) = derivatives.as_slice_mut() {
+                linear_combination(
+                    output,
+                    [d_h00_dx, d_h10_dt, d_h01_dx, d_h11_dt],
+                    [as_slice(p_k), as_slice(m_k), as_slice(p_k_1), as_slice(m_k_1)],
+                );
+            } else {
+                azip!((g in derivatives, p_k in p_k, p_k_1 in p_k_1, m_k in m_k, m_k_1 in m_k_1) {
+                    *g = d_h00_dx * p_k + d_h10_dt * m_k + d_h01_dx * p_k_1 + d_h11_dt * m_k_1;
+                });
+            }
+        }
+    }
+}
+
+/// Get the data of an owned array as a slice. The control points arrays are
+/// always created in standard layout.
+fn as_slice<D: Dimension>(array: &Array<f64, D>) -> &[f64] {
+    array.as_slice().expect("spline control points should be contiguous")
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+    use ndarray::{Array1, Ix1, Ix0, arr0, arr1};
+
+    use super::*;
+
+    fn sin_cos(x: f64, mut values: ArrayViewMut<f64, Ix1>, mut derivatives: ArrayViewMut<f64, Ix1>) {
+        values[0] = f64::sin(x);
+        values[1] = f64::cos(x);
+        derivatives[0] = f64::cos(x);
+        derivatives[1] = -f64::sin(x);
+    }
+
+    #[test]
+    fn with_accuracy() {
+        let spline = Spline::with_accuracy(-1.0, 4.0, Ix1(2), 1e-9, sin_cos).unwrap();
+        assert_eq!(spline.start(), -1.0);
+        assert_eq!(spline.stop(), 4.0);
+        assert_eq!(spline.shape(), [2]);
+
+        for &x in &[-1.0, -0.3, 0.000000001, 2.3, 3.2, 3.99999999, 4.0] {
+            let mut values = Array1::zeros(2);
+            let mut derivatives = Array1::zeros(2);
+
+            spline.compute(x, values.view_mut(), Some(derivatives.view_mut()));
+            assert_relative_eq!(values[0], f64::sin(x), epsilon=1e-8, max_relative=1e-5);
+            assert_relative_eq!(values[1], f64::cos(x), epsilon=1e-8, max_relative=1e-5);
+            assert_relative_eq!(derivatives[0], f64::cos(x), epsilon=1e-6, max_relative=1e-5);
+            assert_relative_eq!(derivatives[1], -f64::sin(x), epsilon=1e-6, max_relative=1e-5);
+        }
+
+        let error = Spline::with_accuracy(1.0, -1.0, Ix1(2), 1e-9, sin_cos).unwrap_err();
+        assert_eq!(error.to_string(), "invalid parameter: got invalid range in spline (1 to -1), start must be smaller than stop");
+    }
+
+    #[test]
+    fn adaptive() {
+        // a function which is harder to interpolate close to 0 should get
+        // more control points there
+        let function = |x: f64, mut value: ArrayViewMut<f64, Ix0>, mut derivative: ArrayViewMut<f64, Ix0>| {
+            value[()] = f64::exp(-20.0 * x);
+            derivative[()] = -20.0 * f64::exp(-20.0 * x);
+        };
+        let spline = Spline::with_accuracy(0.0, 10.0, Ix0(), 1e-8, function).unwrap();
+
+        let positions = spline.positions();
+        let first_half = positions.iter().filter(|&&x| x < 5.0).count();
+        assert!(first_half > 2 * (positions.len() - first_half));
+
+        let mut value = arr0(0.0);
+        let mut derivative = arr0(0.0);
+        spline.compute(0.123, value.view_mut(), Some(derivative.view_mut()));
+        assert_relative_eq!(value[()], f64::exp(-20.0 * 0.123), max_relative=1e-5);
+        assert_relative_eq!(derivative[()], -20.0 * f64::exp(-20.0 * 0.123), max_relative=1e-3);
+    }
+
+    #[test]
+    fn from_points() {
+        let points = vec![
+            (1.0, arr1(&[1.0]), arr1(&[2.0])),
+            (0.0, arr1(&[0.0]), arr1(&[0.0])),
+        ];
+        let spline = Spline::from_points(points).unwrap();
+        assert_eq!(spline.positions(), [0.0, 1.0]);
+
+        // f(x) = x^2 is exactly represented by a cubic spline
+        let mut value = arr1(&[0.0]);
+        let mut derivative = arr1(&[0.0]);
+        spline.compute(0.3, value.view_mut(), Some(derivative.view_mut()));
+        assert_relative_eq!(value[0], 0.09, max_relative=1e-14);
+        assert_relative_eq!(derivative[0], 0.6, max_relative=1e-14);
+
+        let error = Spline::from_points(vec![(0.0, arr1(&[0.0]), arr1(&[0.0]))]).unwrap_err();
+        assert_eq!(error.to_string(), "invalid parameter: a spline needs at least two control points, got 1");
+
+        let error = Spline::from_points(vec![
+            (0.0, arr1(&[0.0]), arr1(&[0.0])),
+            (1.0, arr1(&[0.0, 1.0]), arr1(&[0.0, 2.0])),
+        ]).unwrap_err();
+        assert_eq!(error.to_string(), "invalid parameter: invalid shape for spline control point at 1: expected [1], got [2]");
+    }
+}
@@ -0,0 +1,280 @@
+//! Construction of Voronoi cells by successive cuts of a convex polyhedron.
+//!
+//! A Voronoi cell is built by starting from a cube around the central atom,
+//! and cutting it with the bisecting plane between the center and each
+//! neighbor, from the closest neighbor to the furthest one. Positions are
+//! relative to the central atom, which is always inside the cell.
+
+use crate::Vector3D;
+
+/// A single face of a `VoronoiCell`
+#[derive(Debug, Clone)]
+pub struct VoronoiFace {
+    /// Index of the neighbor that created this face, or `None` for the faces
+    /// of the initial cube
+    pub neighbor: Option<usize>,
+    /// Vertices of this face, in order around the face
+    pub vertices: Vec<Vector3D>,
+}
+
+impl VoronoiFace {
+    /// Get the area of this face
+    pub fn area(&self) -> f64 {
+        return 0.5 * polygon_normal(&self.vertices).norm();
+    }
+}
+
+/// A convex polyhedron containing the origin, represented by the list of its
+/// faces.
+#[derive(Debug, Clone)]
+pub struct VoronoiCell {
+    faces: Vec<VoronoiFace>,
+    /// Tolerance used to decide whether a point is on a plane
+    epsilon: f64,
+}
+
+impl VoronoiCell {
+    /// Create a new cell as a cube going from `-half_size` to `half_size`
+    /// along each axis.
+    ///
+    /// # Panics
+    ///
+    /// If `half_size` is not a positive number
+    pub fn cube(half_size: f64) -> VoronoiCell {
+        assert!(half_size > 0.0 && half_size.is_finite(), "half_size must be positive in VoronoiCell::cube");
+
+        let h = half_size;
+        let corner = |x: f64, y: f64, z: f64| Vector3D::new(x * h, y * h, z * h);
+        let faces = [
+            [corner(-1.0, -1.0, -1.0), corner(-1.0, 1.0, -1.0), corner(1.0, 1.0, -1.0), corner(1.0, -1.0, -1.0)],
+            [corner(-1.0, -1.0, 1.0), corner(1.0, -1.0, 1.0), corner(1.0, 1.0, 1.0), corner(-1.0, 1.0, 1.0)],
+            [corner(-1.0, -1.0, -1.0), corner(1.0, -1.0, -1.0), corner(1.0, -1.0, 1.0), corner(-1.0, -1.0, 1.0)],
+            [corner(-1.0, 1.0, -1.0), corner(-1.0, 1.0, 1.0), corner(1.0, 1.0, 1.0), corner(1.0, 1.0, -1.0)],
+            [corner(-1.0, -1.0, -1.0), corner(-1.0, -1.0, 1.0), corner(-1.0, 1.0, 1.0), corner(-1.0, 1.0, -1.0)],
+            [corner(1.0, -1.0, -1.0), corner(1.0, 1.0, -1.0), corner(1.0, 1.0, 1.0), corner(1.0, -1.0, 1.0)],
+        ];
+
+        return VoronoiCell {
+            faces: faces.iter().map(|vertices| VoronoiFace {
+                neighbor: None,
+                vertices: vertices.to_vec(),
+            }).collect(),
+            epsilon: 1e-10 * half_size,
+        };
+    }
+
+    /// Get the faces of this cell
+    pub fn faces(&self) -> &[VoronoiFace] {
+        &self.faces
+    }
+
+    /// Get the largest distance between the origin and a vertex of this cell.
+    /// Neighbors further away than twice this distance can not cut the cell.
+    pub fn max_radius(&self) -> f64 {
+        let mut max = 0.0;
+        for face in &self.faces {
+            for vertex in &face.vertices {
+                max = f64::max(max, vertex.norm());
+            }
+        }
+        return max;
+    }
+
+    /// Get the volume of this cell
+    pub fn volume(&self) -> f64 {
+        // sum of the volumes of the pyramids going from the origin to each
+        // face, the origin being inside the cell
+        let mut volume = 0.0;
+        for face in &self.faces {
+            volume += f64::abs(polygon_normal(&face.vertices) * face.vertices[0]) / 6.0;
+        }
+        return volume;
+    }
+
+    /// Cut this cell with the bisecting plane between the origin and a
+    /// `neighbor` at the given `position`, keeping the half-space containing
+    /// the origin. Returns `true` if the cell was modified.
+    pub fn cut(&mut self, position: Vector3D, neighbor: usize) -> bool {
+        let distance = position.norm();
+        let normal = position / distance;
+        let offset = 0.5 * distance;
+        let epsilon = self.epsilon;
+        let signed_distance = |point: &Vector3D| normal * point - offset;
+
+        let outside = self.faces.iter()
+            .flat_map(|face| &face.vertices)
+            .any(|vertex| signed_distance(vertex) > epsilon);
+        if !outside {
+            // the plane does not intersect the cell, or only touches it
+            return false;
+        }
+
+        // vertices of the new face created by this cut
+        let mut new_vertices = Vec::new();
+        let mut faces = Vec::with_capacity(self.faces.len() + 1);
+        for face in &self.faces {
+            let mut vertices = Vec::with_capacity(face.vertices.len() + 1);
+            let n_vertices = face.vertices.len();
+            for i in 0..n_vertices {
+                let current = face.vertices[i];
+                let next = face.vertices[(i + 1) % n_vertices];
+                let s_current = signed_distance(&current);
+                let s_next = signed_distance(&next);
+
+                if s_current <= epsilon {
+                    vertices.push(current);
+                    if s_current >= -epsilon {
+                        new_vertices.push(current);
+                    }
+                }
+
+                if (s_current < -epsilon && s_next > epsilon) || (s_current > epsilon && s_next < -epsilon) {
+                    let t = s_current / (s_current - s_next);
+                    let intersection = current + t * (next - current);
+                    vertices.push(intersection);
+                    new_vertices.push(intersection);
+                }
+            }
+
+            let vertices = deduplicate(vertices, epsilon);
+            if vertices.len() >= 3 {
+                faces.push(VoronoiFace {
+                    neighbor: face.neighbor,
+                    vertices: vertices,
+                });
+            }
+        }
+
+        let new_vertices = sort_around(deduplicate_all(new_vertices, epsilon), normal);
+        if new_vertices.len() >= 3 {
+            faces.push(VoronoiFace {
+                neighbor: Some(neighbor),
+                vertices: new_vertices,
+            });
+        }
+
+        self.faces = faces;
+        return true;
+    }
+}
+
+/// Get the vector normal to a planar polygon, with a norm equal to twice the
+/// area of the polygon
+fn polygon_normal(vertices: &[Vector3D]) -> Vector3D {
+    let mut normal = Vector3D::zero();
+    let n_vertices = vertices.len();
+    for i in 0..n_vertices {
+        normal += vertices[i] ^ vertices[(i + 1) % n_vertices];
+    }
+    return normal;
+}
+
+/// Remove consecutive duplicated points in a polygon, including between the
+/// last and first point
+fn deduplicate(vertices: Vec<Vector3D>, epsilon: f64) -> Vec<Vector3D> {
+    let mut result: Vec<Vector3D> = Vec::with_capacity(vertices.len());
+    for vertex in vertices {
+        if let Some(last) = result.last() {
+            if (vertex - *last).norm() <= epsilon {
+                continue;
+            }
+        }
+        result.push(vertex);
+    }
+
+    while result.len() > 1 && (result[0] - result[result.len() - 1]).norm() <= epsilon {
+        result.pop();
+    }
+
+    return result;
+}
+
+/// Remove all duplicated points in a set of points
+fn deduplicate_all(vertices: Vec<Vector3D>, epsilon: f64) -> Vec<Vector3D> {
+    let mut result: Vec<Vector3D> = Vec::with_capacity(vertices.len());
+    for vertex in vertices {
+        if result.iter().all(|other| (vertex - *other).norm() > epsilon) {
+            result.push(vertex);
+        }
+    }
+    return result;
+}
+
+/// Sort points in a plane with the given `normal` by their angle around the
+/// center of the points
+fn sort_around(vertices: Vec<Vector3D>, normal: Vector3D) -> Vec<Vector3D> {
+    if vertices.is_empty() {
+        return vertices;
+    }
+
+    let mut center = Vector3D::zero();
+    for vertex in &vertices {
+        center += vertex;
+    }
+    center /= vertices.len() as f64;
+
+    // orthonormal basis of the plane
+    let reference = if normal[0].abs() < 0.9 {
+        Vector3D::new(1.0, 0.0, 0.0)
+    } else {
+        Vector3D::new(0.0, 1.0, 0.0)
+    };
+    let e1 = (reference ^ normal).normalized();
+    let e2 = normal ^ e1;
+
+    let mut vertices = vertices.into_iter().map(|vertex| {
+        let delta = vertex - center;
+        (f64::atan2(delta * e2, delta * e1), vertex)
+    }).collect::<Vec<_>>();
+    vertices.sort_by(|a, b| a.0.partial_cmp(&b.0).expect("got NaN angle"));
+
+    return vertices.into_iter().map(|(_, vertex)| vertex).collect();
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use super::*;
+
+    #[test]
+    fn cube() {
+        let cell = VoronoiCell::cube(2.0);
+        assert_eq!(cell.faces().len(), 6);
+        assert_relative_eq!(cell.volume(), 64.0, max_relative = 1e-12);
+        assert_relative_eq!(cell.max_radius(), 2.0 * f64::sqrt(3.0), max_relative = 1e-12);
+        for face in cell.faces() {
+            assert_relative_eq!(face.area(), 16.0, max_relative = 1e-12);
+        }
+    }
+
+    #[test]
+    fn cuts() {
+        let mut cell = VoronoiCell::cube(2.0);
+
+        // plane outside of the cell
+        assert!(!cell.cut(Vector3D::new(5.0, 0.0, 0.0), 0));
+        // plane touching the cell on one face
+        assert!(!cell.cut(Vector3D::new(4.0, 0.0, 0.0), 0));
+        assert_eq!(cell.faces().len(), 6);
+
+        // cut half of the cube
+        assert!(cell.cut(Vector3D::new(0.0, 0.0, 2.0), 1));
+        assert_eq!(cell.faces().len(), 6);
+        assert_relative_eq!(cell.volume(), 48.0, max_relative = 1e-12);
+
+        // the same cut does nothing
+        assert!(!cell.cut(Vector3D::new(0.0, 0.0, 2.0), 2));
+
+        // cut one of the corners
+        assert!(cell.cut(Vector3D::new(3.0, 3.0, -3.0), 3));
+        assert_eq!(cell.faces().len(), 7);
+        // the removed corner is a tetrahedron with three orthogonal edges of
+        // length 1.5
+        assert_relative_eq!(cell.volume(), 48.0 - 1.5 * 1.5 * 1.5 / 6.0, max_relative = 1e-12);
+
+        let face = cell.faces().iter().find(|face| face.neighbor == Some(3)).unwrap();
+        assert_eq!(face.vertices.len(), 3);
+        assert_relative_eq!(face.area(), 1.125 * f64::sqrt(3.0), max_relative = 1e-12);
+    }
+}
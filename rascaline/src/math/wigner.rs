@@ -0,0 +1,222 @@
+use ndarray::Array2;
+
+use crate::Matrix3;
+use super::clebsch_gordan::complex_to_real;
+
+/// Compute `n!` as a floating point number
+fn factorial(n: usize) -> f64 {
+    return (1..=n).fold(1.0, |acc, i| acc * i as f64);
+}
+
+/// Compute the Wigner small-d matrix element `d^l_{m1 m2}(β)`
+fn wigner_small_d(l: isize, m1: isize, m2: isize, beta: f64) -> f64 {
+    let cos_half = f64::cos(0.5 * beta);
+    let sin_half = f64::sin(0.5 * beta);
+
+    let prefactor = f64::sqrt(
+        factorial((l + m1) as usize) * factorial((l - m1) as usize)
+        * factorial((l + m2) as usize) * factorial((l - m2) as usize)
+    );
+
+    let s_min = 0.max(m2 - m1);
+    let s_max = (l + m2).min(l - m1);
+
+    let mut sum = 0.0;
+    for s in s_min..=s_max {
+        let sign = if (m1 - m2 + s) % 2 == 0 { 1.0 } else { -1.0 };
+        let denominator = factorial((l + m2 - s) as usize) * factorial(s as usize)
+            * factorial((m1 - m2 + s) as usize) * factorial((l - m1 - s) as usize);
+
+        sum += sign / denominator
+            * cos_half.powi((2 * l + m2 - m1 - 2 * s) as i32)
+            * sin_half.powi((m1 - m2 + 2 * s) as i32);
+    }
+
+    return prefactor * sum;
+}
+
+/// Get the `z-y-z` Euler angles `(α, β, γ)` corresponding to the given
+/// rotation matrix, such that `rotation = Rz(α) Ry(β) Rz(γ)`.
+fn euler_angles(rotation: &Matrix3) -> (f64, f64, f64) {
+    let cos_beta = rotation[2][2].clamp(-1.0, 1.0);
+    let beta = f64::acos(cos_beta);
+
+    if f64::sin(beta) > 1e-12 {
+        let alpha = f64::atan2(rotation[1][2], rotation[0][2]);
+        let gamma = f64::atan2(rotation[2][1], -rotation[2][0]);
+        return (alpha, beta, gamma);
+    }
+
+    if cos_beta > 0.0 {
+        // β = 0, only α + γ is defined
+        let alpha = f64::atan2(rotation[1][0], rotation[0][0]);
+        return (alpha, 0.0, 0.0);
+    }
+
+    // β = π, only α - γ is defined
+    let alpha = f64::atan2(-rotation[1][0], rotation[1][1]);
+    return (alpha, std::f64::consts::PI, 0.0);
+}
+
+/// Wigner-D matrices for the real spherical harmonics used in rascaline (see
+/// [`crate::math::SphericalHarmonics`]), for a given rotation.
+///
+/// The matrix for angular degree `l` is defined such that
+///
+/// ```text
+/// Y_{lm}(R r) = Σ_{m'} D^l[m, m'] Y_{lm'}(r)
+/// ```
+///
+/// for any direction `r`. Consequently, if all atoms in a structure are
+/// rotated by `R`, the spherical expansion coefficients `c_{lm}` (and any
+/// other equivariant quantity) are transformed as `c' = D^l c`.
+#[derive(Debug, Clone)]
+pub struct WignerD {
+    matrices: Vec<Array2<f64>>,
+}
+
+impl WignerD {
+    /// Compute the Wigner-D matrices for all angular degrees up to
+    /// `max_angular` for the given `rotation`.
+    ///
+    /// # Panics
+    ///
+    /// If `rotation` is not a proper rotation matrix, i.e. if it is not
+    /// orthogonal or has a negative determinant.
+    pub fn new(max_angular: usize, rotation: &Matrix3) -> WignerD {
+        let identity = *rotation * rotation.transposed() - Matrix3::one();
+        assert!(
+            identity.norm() < 1e-9 && (rotation.determinant() - 1.0).abs() < 1e-9,
+            "expected a proper rotation matrix in WignerD::new"
+        );
+
+        let (alpha, beta, gamma) = euler_angles(rotation);
+        let matrices = (0..=max_angular)
+            .map(|l| real_wigner_d(l, alpha, beta, gamma))
+            .collect();
+
+        return WignerD {
+            matrices: matrices,
+        };
+    }
+
+    /// Get the maximal angular degree for which matrices are available
+    pub fn max_angular(&self) -> usize {
+        return self.matrices.len() - 1;
+    }
+
+    /// Get the Wigner-D matrix for angular degree `l`, as an array of size
+    /// `(2l + 1) x (2l + 1)` indexed by `[m + l, m' + l]`.
+    pub fn matrix(&self, l: usize) -> &Array2<f64> {
+        return &self.matrices[l];
+    }
+}
+
+/// Compute the Wigner-D matrix for real spherical harmonics of degree `l`,
+/// for the rotation with `z-y-z` Euler angles `(alpha, beta, gamma)`.
+fn real_wigner_d(l: usize, alpha: f64, beta: f64, gamma: f64) -> Array2<f64> {
+    let il = l as isize;
+
+    // matrix for complex spherical harmonics, such that
+    // Y_l^m(R r) = Σ_{m'} M[m, m'] Y_l^{m'}(r), stored as (real, imaginary)
+    let mut complex = Array2::from_elem((2 * l + 1, 2 * l + 1), (0.0, 0.0));
+    for m1 in -il..=il {
+        for m2 in -il..=il {
+            let d = wigner_small_d(il, m1, m2, beta);
+            let phase = m1 as f64 * alpha + m2 as f64 * gamma;
+            complex[[(m1 + il) as usize, (m2 + il) as usize]] = (d * f64::cos(phase), d * f64::sin(phase));
+        }
+    }
+
+    // D = U M U^†
+    let mut real = Array2::zeros((2 * l + 1, 2 * l + 1));
+    for m1 in -il..=il {
+        for m2 in -il..=il {
+            let mut value = 0.0;
+            for (c_m1, u1_re, u1_im) in complex_to_real(m1) {
+                for (c_m2, u2_re, u2_im) in complex_to_real(m2) {
+                    let (m_re, m_im) = complex[[(c_m1 + il) as usize, (c_m2 + il) as usize]];
+                    // U[m1, c_m1] * M[c_m1, c_m2] * conj(U[m2, c_m2])
+                    let re = u1_re * m_re - u1_im * m_im;
+                    let im = u1_re * m_im + u1_im * m_re;
+                    value += re * u2_re + im * u2_im;
+                }
+            }
+            real[[(m1 + il) as usize, (m2 + il) as usize]] = value;
+        }
+    }
+
+    return real;
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use super::*;
+    use crate::Vector3D;
+    use crate::math::SphericalHarmonicsCache;
+
+    fn rotations() -> Vec<Matrix3> {
+        vec![
+            Matrix3::one(),
+            Matrix3::rotation(&Vector3D::new(0.0, 0.0, 1.0), 0.3),
+            Matrix3::rotation(&Vector3D::new(1.0, 0.0, 0.0), std::f64::consts::PI),
+            Matrix3::rotation(&Vector3D::new(1.0, -2.0, 0.5), 1.2),
+            Matrix3::rotation(&Vector3D::new(-0.3, 0.8, 2.0), -2.7),
+        ]
+    }
+
+    #[test]
+    fn rotate_spherical_harmonics() {
+        let max_angular = 6;
+        let mut cache = SphericalHarmonicsCache::new(max_angular, false);
+        let direction = Vector3D::new(1.0, -3.0, 9.0) / f64::sqrt(91.0);
+
+        for rotation in rotations() {
+            let wigner = WignerD::new(max_angular, &rotation);
+            assert_eq!(wigner.max_angular(), max_angular);
+
+            cache.compute(direction);
+            let values = cache.values.clone();
+            cache.compute(rotation * direction);
+            let rotated = cache.values.clone();
+
+            for l in 0..=max_angular {
+                let il = l as isize;
+                let matrix = wigner.matrix(l);
+                for m1 in -il..=il {
+                    let mut expected = 0.0;
+                    for m2 in -il..=il {
+                        expected += matrix[[(m1 + il) as usize, (m2 + il) as usize]] * values[[il, m2]];
+                    }
+                    assert_relative_eq!(rotated[[il, m1]], expected, epsilon=1e-12, max_relative=1e-10);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn orthogonal() {
+        for rotation in rotations() {
+            let wigner = WignerD::new(5, &rotation);
+            for l in 0..=5 {
+                let matrix = wigner.matrix(l);
+                let product = matrix.dot(&matrix.t());
+                for i in 0..(2 * l + 1) {
+                    for j in 0..(2 * l + 1) {
+                        let expected = if i == j { 1.0 } else { 0.0 };
+                        assert_relative_eq!(product[[i, j]], expected, epsilon=1e-12);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic = "expected a proper rotation matrix in WignerD::new"]
+    fn improper_rotation() {
+        let inversion = Matrix3::new([[-1.0, 0.0, 0.0], [0.0, -1.0, 0.0], [0.0, 0.0, -1.0]]);
+        WignerD::new(2, &inversion);
+    }
+}
@@ -0,0 +1,827 @@
+//! Simple regression models, predicting the energy of a structure as a sum of
+//! atomic contributions computed from a [`Descriptor`].
+//!
+//! [`RidgeRegression`] fits a [`LinearModel`] on the energies (and optionally
+//! the forces) of a training set, while [`KernelRidgeRegression`] fits a
+//! [`KernelModel`] using a polynomial kernel between atomic environments. Both
+//! models implement the [`Model`] trait, and can be used to run molecular
+//! dynamics with the [i-PI driver](crate::ipi::Driver). A [`Committee`] of
+//! linear models can be used to estimate the uncertainty of the predictions.
+//!
+//! ```no_run
+//! # use rascaline::{Calculator, CalculationOptions, Descriptor};
+//! # use rascaline::models::RidgeRegression;
+//! # fn main() -> Result<(), rascaline::Error> {
+//! # let mut systems = Vec::new();
+//! # let energies = Vec::new();
+//! let mut calculator = Calculator::new("soap_power_spectrum", "{...}".into())?;
+//! let mut descriptor = Descriptor::new();
+//! calculator.compute(&mut systems, &mut descriptor, CalculationOptions::default())?;
+//!
+//! let ridge = RidgeRegression {
+//!     regularizer: 1e-6,
+//!     forces_weight: 0.0,
+//!     densify: vec!["species_center".into(), "species_neighbor_1".into(), "species_neighbor_2".into()],
+//! };
+//! let model = ridge.fit(&mut descriptor, &energies, None)?;
+//! # Ok(())
+//! # }
+//! ```
+use std::collections::BTreeSet;
+
+use nalgebra as na;
+use ndarray::{Array1, Array2, ArrayView2, Axis, s};
+
+use crate::{Descriptor, Error};
+use crate::descriptor::{Indexes, IndexValue};
+
+/// A model predicting the energy of a single structure, and the corresponding
+/// forces, from the descriptor of this structure.
+pub trait Model {
+    /// Predict the energy of a structure containing `n_atoms` atoms and the
+    /// forces acting on these atoms (as an `n_atoms x 3` array), from a
+    /// descriptor containing a single structure. This descriptor must contain
+    /// gradients with respect to positions to compute the forces.
+    ///
+    /// The descriptor can be modified by this function (for example by calling
+    /// [`Descriptor::densify`]). The energy is in the units used when fitting
+    /// the model, and the forces in the same energy unit divided by the unit of
+    /// positions in the descriptor.
+    fn predict(&self, descriptor: &mut Descriptor, n_atoms: usize) -> Result<(f64, Array2<f64>), Error>;
+}
+
+/// A linear model predicting the energy of each atom as the dot product of the
+/// corresponding descriptor row with `weights`. The total energy is the sum of
+/// atomic energies.
+#[derive(Clone, Debug)]
+pub struct LinearModel {
+    /// Weights of the model, one for each feature of the descriptor after
+    /// moving the `densify` variables to the features.
+    pub weights: Array1<f64>,
+    /// Variables to move from the samples to the features (using
+    /// [`Descriptor::densify`]) before applying the model, leaving only
+    /// `structure` and `center` in the samples.
+    pub densify: Vec<String>,
+    /// Features of the descriptor used to fit the model, after moving the
+    /// `densify` variables to the features. The descriptors given to
+    /// [`Model::predict`] are densified to the same features, regardless of
+    /// the values taken by the `densify` variables in the predicted structure.
+    pub features: Indexes,
+}
+
+impl Model for LinearModel {
+    fn predict(&self, descriptor: &mut Descriptor, n_atoms: usize) -> Result<(f64, Array2<f64>), Error> {
+        densify_like(descriptor, &self.densify, &self.features)?;
+
+        if self.features.count() != self.weights.len() {
+            return Err(Error::InvalidParameter(format!(
+                "the linear model has {} weights, but was fitted with {} features",
+                self.weights.len(), self.features.count()
+            )));
+        }
+
+        let values = descriptor.per_atom_values(0, n_atoms)?;
+        let energy = values.dot(&self.weights).sum();
+
+        let weights = self.weights.broadcast((n_atoms, self.weights.len())).expect("failed to broadcast weights");
+        let mut forces = descriptor.scatter_gradients(0, weights)?;
+        // forces are the opposite of the energy gradient
+        forces.mapv_inplace(|f| -f);
+
+        return Ok((energy, forces));
+    }
+}
+
+/// A kernel model predicting the energy of each atom `i` as `Σ_m weights[m]
+/// (x_i · x_m)^zeta`, where `x_i` is the descriptor row for atom `i` and the
+/// sum runs over the atomic environments `x_m` used to fit the model. The
+/// total energy is the sum of atomic energies.
+#[derive(Clone, Debug)]
+pub struct KernelModel {
+    /// Atomic environments used to fit the model, one per row
+    pub environments: Array2<f64>,
+    /// Weight associated with each environment
+    pub weights: Array1<f64>,
+    /// Exponent of the polynomial kernel
+    pub zeta: i32,
+    /// Variables to move from the samples to the features (using
+    /// [`Descriptor::densify`]) before applying the model, leaving only
+    /// `structure` and `center` in the samples.
+    pub densify: Vec<String>,
+    /// Features of the descriptor used to fit the model, see
+    /// [`LinearModel::features`].
+    pub features: Indexes,
+}
+
+impl Model for KernelModel {
+    fn predict(&self, descriptor: &mut Descriptor, n_atoms: usize) -> Result<(f64, Array2<f64>), Error> {
+        densify_like(descriptor, &self.densify, &self.features)?;
+
+        if self.features.count() != self.environments.ncols() {
+            return Err(Error::InvalidParameter(format!(
+                "the kernel model environments have {} features, but the model was fitted with {} features",
+                self.environments.ncols(), self.features.count()
+            )));
+        }
+
+        let values = descriptor.per_atom_values(0, n_atoms)?;
+        let kernel = values.dot(&self.environments.t());
+
+        let energy = kernel.mapv(|k| k.powi(self.zeta)).dot(&self.weights).sum();
+
+        // derivative of the atomic energies with respect to the descriptor:
+        // Σ_m weights[m] zeta (x_i · x_m)^(zeta - 1) x_m
+        let mut derivative = kernel.mapv(|k| self.zeta as f64 * k.powi(self.zeta - 1));
+        derivative *= &self.weights;
+        let derivative = derivative.dot(&self.environments);
+
+        let mut forces = descriptor.scatter_gradients(0, derivative.view())?;
+        // forces are the opposite of the energy gradient
+        forces.mapv_inplace(|f| -f);
+
+        return Ok((energy, forces));
+    }
+}
+
+/// A committee (i.e. an ensemble) of linear models, sharing the same features
+/// but with different weights. The spread of the predictions of the members
+/// of the committee can be used as an estimate of the uncertainty of the mean
+/// prediction.
+///
+/// When used as a [`Model`], the committee predicts the mean energy and forces
+/// of all members.
+#[derive(Clone, Debug)]
+pub struct Committee {
+    /// Weights of the members of the committee, with one row for each member
+    /// and one column for each feature of the descriptor after moving the
+    /// `densify` variables to the features.
+    pub weights: Array2<f64>,
+    /// Variables to move from the samples to the features (using
+    /// [`Descriptor::densify`]) before applying the models, leaving only
+    /// `structure` and `center` in the samples.
+    pub densify: Vec<String>,
+    /// Features of the descriptor used to fit the models, see
+    /// [`LinearModel::features`].
+    pub features: Indexes,
+}
+
+/// Predictions of all the members of a [`Committee`] for each sample (and
+/// gradient sample) of a descriptor
+#[derive(Clone, Debug)]
+pub struct CommitteePredictions {
+    /// Predictions of each member (columns) for each sample (rows)
+    pub values: Array2<f64>,
+    /// Mean of the predictions over the committee members, for each sample
+    pub values_mean: Array1<f64>,
+    /// Variance of the predictions over the committee members, for each
+    /// sample
+    pub values_variance: Array1<f64>,
+    /// Gradients of the predictions of each member (columns) for each gradient
+    /// sample (rows), if the descriptor contains gradients
+    pub gradients: Option<Array2<f64>>,
+    /// Mean of the gradients over the committee members, for each gradient
+    /// sample
+    pub gradients_mean: Option<Array1<f64>>,
+    /// Variance of the gradients over the committee members, for each gradient
+    /// sample
+    pub gradients_variance: Option<Array1<f64>>,
+}
+
+impl Committee {
+    /// Evaluate all the members of this committee on all the samples and
+    /// gradient samples of the `descriptor`, in a single matrix product. The
+    /// variance is the average of the squared difference between the
+    /// prediction of each member and the mean prediction.
+    ///
+    /// The descriptor is densified in place, according to `self.densify` and
+    /// `self.features`.
+    #[time_graph::instrument(name = "Committee::evaluate")]
+    pub fn evaluate(&self, descriptor: &mut Descriptor) -> Result<CommitteePredictions, Error> {
+        if self.weights.nrows() == 0 {
+            return Err(Error::InvalidParameter(
+                "a committee must contain at least one member".into()
+            ));
+        }
+
+        densify_like(descriptor, &self.densify, &self.features)?;
+        if self.features.count() != self.weights.ncols() {
+            return Err(Error::InvalidParameter(format!(
+                "the committee members have {} weights, but were fitted with {} features",
+                self.weights.ncols(), self.features.count()
+            )));
+        }
+
+        let values = descriptor.values.dot(&self.weights.t());
+        let (values_mean, values_variance) = mean_and_variance(&values);
+
+        let (gradients, gradients_mean, gradients_variance) = match descriptor.gradients {
+            Some(ref gradients) => {
+                let gradients = gradients.dot(&self.weights.t());
+                let (mean, variance) = mean_and_variance(&gradients);
+                (Some(gradients), Some(mean), Some(variance))
+            }
+            None => (None, None, None),
+        };
+
+        return Ok(CommitteePredictions {
+            values: values,
+            values_mean: values_mean,
+            values_variance: values_variance,
+            gradients: gradients,
+            gradients_mean: gradients_mean,
+            gradients_variance: gradients_variance,
+        });
+    }
+
+    /// Get the linear model predicting the mean of all the members of this
+    /// committee
+    pub fn mean_model(&self) -> LinearModel {
+        let weights = self.weights.mean_axis(Axis(0)).unwrap_or_else(|| Array1::zeros(self.weights.ncols()));
+        return LinearModel {
+            weights: weights,
+            densify: self.densify.clone(),
+            features: self.features.clone(),
+        };
+    }
+}
+
+impl Model for Committee {
+    fn predict(&self, descriptor: &mut Descriptor, n_atoms: usize) -> Result<(f64, Array2<f64>), Error> {
+        // the models are linear, so the mean prediction is the prediction of
+        // the mean weights
+        return self.mean_model().predict(descriptor, n_atoms);
+    }
+}
+
+/// Compute the mean and (population) variance of each row of `predictions`
+fn mean_and_variance(predictions: &Array2<f64>) -> (Array1<f64>, Array1<f64>) {
+    let mean = predictions.mean_axis(Axis(1)).expect("committee should not be empty");
+    let mut deviations = predictions - &mean.view().insert_axis(Axis(1));
+    deviations.mapv_inplace(|d| d * d);
+    let variance = deviations.mean_axis(Axis(1)).expect("committee should not be empty");
+    return (mean, variance);
+}
+
+/// Ridge regression, fitting the weights of a [`LinearModel`] on the energies
+/// and optionally the forces of a set of structures.
+///
+/// The weights `w` minimize `|X w - E|^2 + forces_weight^2 |G w - F|^2 +
+/// regularizer |w|^2`, where `X` contains the sum of the descriptor values
+/// over the atoms of each structure, and `G` the opposite of the sum of the
+/// gradients of the descriptor with respect to each atomic position.
+pub struct RidgeRegression {
+    /// Strength of the regularization
+    pub regularizer: f64,
+    /// Weight of the forces compared to the energies in the fit. This is only
+    /// used if forces are given to [`RidgeRegression::fit`].
+    pub forces_weight: f64,
+    /// Variables to move from the samples to the features before fitting the
+    /// model, see [`LinearModel::densify`].
+    pub densify: Vec<String>,
+}
+
+impl RidgeRegression {
+    /// Fit a linear model on the given `descriptor`, containing data for
+    /// `energies.len()` structures. If `forces` is given, it should contain
+    /// one `n_atoms x 3` array for each structure, and the descriptor must
+    /// contain gradients with respect to positions.
+    ///
+    /// The descriptor is densified in place, according to `self.densify`.
+    #[time_graph::instrument(name = "RidgeRegression::fit")]
+    pub fn fit(
+        &self,
+        descriptor: &mut Descriptor,
+        energies: &[f64],
+        forces: Option<&[Array2<f64>]>,
+    ) -> Result<LinearModel, Error> {
+        densify(descriptor, &self.densify)?;
+
+        let mut design = structures_sum(descriptor, energies.len())?;
+        let mut targets = Array1::from(energies.to_vec());
+
+        if let Some(forces) = forces {
+            if forces.len() != energies.len() {
+                return Err(Error::InvalidParameter(format!(
+                    "got forces for {} structures, but energies for {} structures",
+                    forces.len(), energies.len()
+                )));
+            }
+
+            let mut gradients = structures_gradients(descriptor, forces)?;
+            // forces are the opposite of the energy gradient
+            gradients.mapv_inplace(|g| -self.forces_weight * g);
+            design.append(ndarray::Axis(0), gradients.view()).expect("invalid design matrix shape");
+
+            let forces = forces.iter()
+                .flat_map(|forces| forces.iter())
+                .map(|&f| self.forces_weight * f);
+            targets.append(ndarray::Axis(0), Array1::from_iter(forces).view()).expect("invalid targets shape");
+        }
+
+        let matrix = design.t().dot(&design);
+        let vector = design.t().dot(&targets);
+        let weights = solve_regularized(matrix.view(), &vector, self.regularizer)?;
+
+        return Ok(LinearModel {
+            weights: weights,
+            densify: self.densify.clone(),
+            features: descriptor.features.clone(),
+        });
+    }
+}
+
+/// Kernel ridge regression, fitting a [`KernelModel`] on the energies of a set
+/// of structures.
+///
+/// The kernel between two structures is the sum of the polynomial kernel
+/// `(x_i · x_j)^zeta` over all pairs of atomic environments `i` and `j` in the
+/// two structures. Fitting on forces is not supported, since it would require
+/// the second derivatives of the kernel.
+pub struct KernelRidgeRegression {
+    /// Strength of the regularization
+    pub regularizer: f64,
+    /// Exponent of the polynomial kernel, which must be at least 1
+    pub zeta: i32,
+    /// Variables to move from the samples to the features before fitting the
+    /// model, see [`KernelModel::densify`].
+    pub densify: Vec<String>,
+}
+
+impl KernelRidgeRegression {
+    /// Fit a kernel model on the given `descriptor`, containing data for
+    /// `energies.len()` structures. All the atomic environments in the
+    /// descriptor are used in the resulting model.
+    ///
+    /// The descriptor is densified in place, according to `self.densify`.
+    #[time_graph::instrument(name = "KernelRidgeRegression::fit")]
+    pub fn fit(&self, descriptor: &mut Descriptor, energies: &[f64]) -> Result<KernelModel, Error> {
+        if self.zeta < 1 {
+            return Err(Error::InvalidParameter(format!(
+                "zeta must be at least 1 for kernel ridge regression, got {}", self.zeta
+            )));
+        }
+
+        densify(descriptor, &self.densify)?;
+        let structures = samples_structures(descriptor, energies.len())?;
+
+        let environments_kernel = descriptor.values.dot(&descriptor.values.t()).mapv(|k| k.powi(self.zeta));
+
+        let n_structures = energies.len();
+        let mut kernel = Array2::zeros((n_structures, n_structures));
+        for (i, &structure_i) in structures.iter().enumerate() {
+            for (j, &structure_j) in structures.iter().enumerate() {
+                kernel[[structure_i, structure_j]] += environments_kernel[[i, j]];
+            }
+        }
+
+        let energies = Array1::from(energies.to_vec());
+        let structures_weights = solve_regularized(kernel.view(), &energies, self.regularizer)?;
+
+        let weights = structures.iter().map(|&structure| structures_weights[structure]).collect();
+        return Ok(KernelModel {
+            environments: descriptor.values.clone(),
+            weights: weights,
+            zeta: self.zeta,
+            densify: self.densify.clone(),
+            features: descriptor.features.clone(),
+        });
+    }
+}
+
+/// Move the given `variables` from the samples to the features of the
+/// descriptor
+fn densify(descriptor: &mut Descriptor, variables: &[String]) -> Result<(), Error> {
+    let variables = variables.iter().map(|v| &**v).collect::<Vec<_>>();
+    descriptor.densify(&variables, None)?;
+    return Ok(());
+}
+
+/// Move the given `variables` from the samples to the features of the
+/// descriptor, using the values these variables take in the `features` of a
+/// fitted model, and check that the descriptor features are the same as the
+/// model `features`. Samples where the variables take values not present in
+/// the model features are removed from the descriptor.
+fn densify_like(descriptor: &mut Descriptor, variables: &[String], features: &Indexes) -> Result<(), Error> {
+    if variables.is_empty() {
+        if descriptor.features != *features {
+            return Err(Error::InvalidParameter(
+                "the features of the descriptor do not match the features used to fit the model".into()
+            ));
+        }
+        return Ok(());
+    }
+
+    if features.names().len() < variables.len() || features.names()[..variables.len()] != *variables {
+        return Err(Error::InvalidParameter(format!(
+            "the model features should start with the densified variables [{}], got [{}]",
+            variables.join(", "), features.names().join(", ")
+        )));
+    }
+
+    let mut requested = BTreeSet::new();
+    for feature in features.iter() {
+        requested.insert(feature[..variables.len()].to_vec());
+    }
+    let n_requested = requested.len();
+    let requested = Array2::from_shape_vec(
+        (n_requested, variables.len()),
+        requested.into_iter().flatten().collect::<Vec<IndexValue>>(),
+    ).expect("invalid shape for requested features");
+
+    let variables = variables.iter().map(|v| &**v).collect::<Vec<_>>();
+    descriptor.densify(&variables, requested.view())?;
+
+    if descriptor.features != *features {
+        return Err(Error::InvalidParameter(
+            "the features of the densified descriptor do not match the features used to fit the model".into()
+        ));
+    }
+
+    return Ok(());
+}
+
+/// Get the structure associated with each sample in the descriptor, checking
+/// that the samples are `[structure, center]` and that all structures are
+/// below `n_structures`.
+fn samples_structures(descriptor: &Descriptor, n_structures: usize) -> Result<Vec<usize>, Error> {
+    if descriptor.samples.names() != ["structure", "center"] {
+        return Err(Error::InvalidParameter(format!(
+            "fitting a model requires samples to be [structure, center], got [{}]. \
+            You might need to add more variables to `densify`.",
+            descriptor.samples.names().join(", ")
+        )));
+    }
+
+    let mut structures = Vec::with_capacity(descriptor.samples.count());
+    for sample in descriptor.samples.iter() {
+        let structure = sample[0].usize();
+        if structure >= n_structures {
+            return Err(Error::InvalidParameter(format!(
+                "got a sample for structure {}, but only {} energies were given",
+                structure, n_structures
+            )));
+        }
+        structures.push(structure);
+    }
+
+    return Ok(structures);
+}
+
+/// Sum the values of the descriptor over the atoms of each structure, giving
+/// an array of shape `n_structures x features.count()`.
+fn structures_sum(descriptor: &Descriptor, n_structures: usize) -> Result<Array2<f64>, Error> {
+    let structures = samples_structures(descriptor, n_structures)?;
+
+    let mut sum = Array2::zeros((n_structures, descriptor.features.count()));
+    for (sample_i, &structure) in structures.iter().enumerate() {
+        let mut row = sum.slice_mut(s![structure, ..]);
+        row += &descriptor.values.slice(s![sample_i, ..]);
+    }
+
+    return Ok(sum);
+}
+
+/// Sum the gradients of the descriptor over the atoms of each structure,
+/// giving an array with one row for each atom and spatial direction in the
+/// structures, i.e. `3 * Σ n_atoms` rows, where the number of atoms in each
+/// structure is taken from the corresponding `forces`.
+fn structures_gradients(descriptor: &Descriptor, forces: &[Array2<f64>]) -> Result<Array2<f64>, Error> {
+    let structures = samples_structures(descriptor, forces.len())?;
+
+    let (gradients, gradients_samples) = match (&descriptor.gradients, &descriptor.gradients_samples) {
+        (Some(gradients), Some(gradients_samples)) => (gradients, gradients_samples),
+        _ => {
+            return Err(Error::InvalidParameter(
+                "the descriptor must contain gradients to fit on forces".into()
+            ));
+        }
+    };
+
+    // index of the first row of each structure in the output
+    let mut first_row = Vec::with_capacity(forces.len());
+    let mut n_rows = 0;
+    for (structure, forces) in forces.iter().enumerate() {
+        if forces.ncols() != 3 {
+            return Err(Error::InvalidParameter(format!(
+                "forces for structure {} should have 3 columns, got {}",
+                structure, forces.ncols()
+            )));
+        }
+        first_row.push(n_rows);
+        n_rows += 3 * forces.nrows();
+    }
+
+    let mut output = Array2::zeros((n_rows, descriptor.features.count()));
+    for (gradient_i, gradient_sample) in gradients_samples.iter().enumerate() {
+        let structure = structures[gradient_sample[0].usize()];
+        let atom = gradient_sample[1].usize();
+        let spatial = gradient_sample[2].usize();
+
+        let n_atoms = forces[structure].nrows();
+        if atom >= n_atoms {
+            return Err(Error::InvalidParameter(format!(
+                "got a gradient with respect to atom {} in structure {}, but forces are only given for {} atoms",
+                atom, structure, n_atoms
+            )));
+        }
+
+        let mut row = output.slice_mut(s![first_row[structure] + 3 * atom + spatial, ..]);
+        row += &gradients.slice(s![gradient_i, ..]);
+    }
+
+    return Ok(output);
+}
+
+/// Solve `(matrix + regularizer * I) x = vector` for `x`, where `matrix` is
+/// symmetric positive semi-definite.
+fn solve_regularized(matrix: ArrayView2<f64>, vector: &Array1<f64>, regularizer: f64) -> Result<Array1<f64>, Error> {
+    let size = vector.len();
+    let matrix = na::DMatrix::from_fn(size, size, |i, j| {
+        if i == j {
+            matrix[[i, j]] + regularizer
+        } else {
+            matrix[[i, j]]
+        }
+    });
+
+    let cholesky = matrix.cholesky().ok_or_else(|| Error::InvalidParameter(
+        "failed to solve the regression problem, try increasing the regularizer".into()
+    ))?;
+
+    let solution = cholesky.solve(&na::DVector::from_iterator(size, vector.iter().copied()));
+    return Ok(solution.iter().copied().collect());
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+    use ndarray::{Array1, Array2};
+
+    use crate::{Calculator, CalculationOptions, Descriptor, SimpleSystem, System};
+    use crate::systems::test_utils::{test_system, test_systems};
+
+    use super::{Model, LinearModel, RidgeRegression, KernelRidgeRegression, Committee};
+
+    fn compute(systems: &[&str]) -> Descriptor {
+        let mut calculator = Calculator::new("dummy_calculator", r#"{
+            "cutoff": 1.5,
+            "delta": 3,
+            "name": "",
+            "gradients": true
+        }"#.to_owned()).unwrap();
+
+        let mut systems = test_systems(systems);
+        let mut descriptor = Descriptor::new();
+        calculator.compute(&mut systems, &mut descriptor, CalculationOptions::default()).unwrap();
+        return descriptor;
+    }
+
+    /// Energies and forces of the given systems, predicted by a linear model
+    /// with known weights
+    fn reference(systems: &[&str], model: &LinearModel) -> (Vec<f64>, Vec<Array2<f64>>) {
+        let mut energies = Vec::new();
+        let mut forces = Vec::new();
+        for &system in systems {
+            let n_atoms = test_system(system).size().unwrap();
+            let (energy, force) = model.predict(&mut compute(&[system]), n_atoms).unwrap();
+            energies.push(energy);
+            forces.push(force);
+        }
+        return (energies, forces);
+    }
+
+    #[test]
+    fn ridge() {
+        let systems = ["water", "methane", "CH"];
+        let expected = LinearModel {
+            weights: Array1::from(vec![0.3, -1.2]),
+            densify: Vec::new(),
+            features: compute(&["water"]).features,
+        };
+        let (energies, forces) = reference(&systems, &expected);
+
+        let ridge = RidgeRegression {
+            regularizer: 1e-12,
+            forces_weight: 0.0,
+            densify: Vec::new(),
+        };
+        let model = ridge.fit(&mut compute(&systems), &energies, None).unwrap();
+        assert_relative_eq!(model.weights, expected.weights, max_relative = 1e-6);
+
+        let ridge = RidgeRegression {
+            regularizer: 1e-12,
+            forces_weight: 2.0,
+            densify: Vec::new(),
+        };
+        let model = ridge.fit(&mut compute(&systems), &energies, Some(&forces)).unwrap();
+        assert_relative_eq!(model.weights, expected.weights, max_relative = 1e-6);
+
+        let error = ridge.fit(&mut compute(&systems), &energies[..1], None).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "invalid parameter: got a sample for structure 1, but only 1 energies were given"
+        );
+    }
+
+    #[test]
+    fn kernel_ridge() {
+        let systems = ["water", "methane"];
+        let linear = LinearModel {
+            weights: Array1::from(vec![0.3, -1.2]),
+            densify: Vec::new(),
+            features: compute(&["water"]).features,
+        };
+        let (energies, _) = reference(&systems, &linear);
+
+        let kernel_ridge = KernelRidgeRegression {
+            regularizer: 1e-12,
+            zeta: 2,
+            densify: Vec::new(),
+        };
+        let model = kernel_ridge.fit(&mut compute(&systems), &energies).unwrap();
+
+        // the model reproduces the training energies
+        for (&system, &energy) in systems.iter().zip(&energies) {
+            let n_atoms = test_system(system).size().unwrap();
+            let (predicted, forces) = model.predict(&mut compute(&[system]), n_atoms).unwrap();
+            assert_relative_eq!(predicted, energy, max_relative = 1e-6);
+            assert_eq!(forces.shape(), [n_atoms, 3]);
+        }
+
+        let kernel_ridge = KernelRidgeRegression {
+            regularizer: 1e-12,
+            zeta: 0,
+            densify: Vec::new(),
+        };
+        let error = kernel_ridge.fit(&mut compute(&systems), &energies).err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "invalid parameter: zeta must be at least 1 for kernel ridge regression, got 0"
+        );
+    }
+
+    #[test]
+    fn kernel_forces() {
+        // check the kernel model forces against finite differences, using a
+        // calculator with consistent values and gradients
+        let mut calculator = Calculator::new("soap_power_spectrum", r#"{
+            "cutoff": 2.4,
+            "max_radial": 2,
+            "max_angular": 2,
+            "atomic_gaussian_width": 0.3,
+            "gradients": true,
+            "radial_basis": {"Gto": {}},
+            "cutoff_function": {"ShiftedCosine": {"width": 0.5}}
+        }"#.to_owned()).unwrap();
+
+        let mut compute = |system: SimpleSystem| {
+            let mut systems = vec![Box::new(system) as Box<dyn System>];
+            let mut descriptor = Descriptor::new();
+            calculator.compute(&mut systems, &mut descriptor, CalculationOptions::default()).unwrap();
+            return descriptor;
+        };
+
+        let kernel_ridge = KernelRidgeRegression {
+            regularizer: 1e-3,
+            zeta: 2,
+            densify: vec!["species_center".into(), "species_neighbor_1".into(), "species_neighbor_2".into()],
+        };
+        let model = kernel_ridge.fit(&mut compute(test_system("water")), &[-2.5]).unwrap();
+
+        let (energy, forces) = model.predict(&mut compute(test_system("water")), 3).unwrap();
+
+        let delta = 1e-6;
+        for atom in 0..3 {
+            for spatial in 0..3 {
+                let mut system = test_system("water");
+                system.positions_mut()[atom][spatial] += delta;
+                let (energy_moved, _) = model.predict(&mut compute(system), 3).unwrap();
+
+                let finite_differences = -(energy_moved - energy) / delta;
+                assert_relative_eq!(forces[[atom, spatial]], finite_differences, epsilon = 1e-6, max_relative = 1e-4);
+            }
+        }
+    }
+
+    #[test]
+    fn committee() {
+        let committee = Committee {
+            weights: ndarray::array![[1.0, 0.5], [3.0, -0.5], [2.0, 0.0]],
+            densify: Vec::new(),
+            features: compute(&["water"]).features,
+        };
+
+        let mut descriptor = compute(&["water"]);
+        let predictions = committee.evaluate(&mut descriptor).unwrap();
+
+        let n_samples = descriptor.samples.count();
+        assert_eq!(predictions.values.shape(), [n_samples, 3]);
+        for sample in 0..n_samples {
+            let row = descriptor.values.row(sample);
+            let members = [
+                row[0] + 0.5 * row[1],
+                3.0 * row[0] - 0.5 * row[1],
+                2.0 * row[0],
+            ];
+            let mean = members.iter().sum::<f64>() / 3.0;
+            let variance = members.iter().map(|m| (m - mean) * (m - mean)).sum::<f64>() / 3.0;
+
+            assert_relative_eq!(predictions.values[[sample, 1]], members[1], max_relative = 1e-12);
+            assert_relative_eq!(predictions.values_mean[sample], mean, max_relative = 1e-12);
+            assert_relative_eq!(predictions.values_variance[sample], variance, max_relative = 1e-12);
+        }
+
+        let gradients = predictions.gradients.unwrap();
+        assert_eq!(gradients.nrows(), descriptor.gradients_samples.as_ref().unwrap().count());
+        assert_eq!(predictions.gradients_variance.unwrap().len(), gradients.nrows());
+
+        // the committee behaves as the mean model
+        let (energy, forces) = committee.predict(&mut compute(&["water"]), 3).unwrap();
+        let (expected_energy, expected_forces) = committee.mean_model().predict(&mut compute(&["water"]), 3).unwrap();
+        assert_relative_eq!(energy, expected_energy);
+        assert_relative_eq!(forces, expected_forces);
+        assert_relative_eq!(energy, predictions.values_mean.sum(), max_relative = 1e-12);
+
+        let committee = Committee {
+            weights: ndarray::array![[1.0, 0.5, 2.0]],
+            densify: Vec::new(),
+            features: compute(&["water"]).features,
+        };
+        let error = committee.evaluate(&mut compute(&["water"])).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "invalid parameter: the committee members have 3 weights, but were fitted with 2 features"
+        );
+    }
+
+    #[test]
+    fn predict_with_fewer_species() {
+        // fit on water and methane, and predict on water alone, which only
+        // contains some of the species seen during training
+        let mut calculator = Calculator::new("soap_power_spectrum", r#"{
+            "cutoff": 2.4,
+            "max_radial": 2,
+            "max_angular": 1,
+            "atomic_gaussian_width": 0.3,
+            "gradients": true,
+            "radial_basis": {"Gto": {}},
+            "cutoff_function": {"ShiftedCosine": {"width": 0.5}}
+        }"#.to_owned()).unwrap();
+
+        let mut compute = |systems: &[&str]| {
+            let mut systems = test_systems(systems);
+            let mut descriptor = Descriptor::new();
+            calculator.compute(&mut systems, &mut descriptor, CalculationOptions::default()).unwrap();
+            return descriptor;
+        };
+
+        let densify = vec!["species_center".to_string(), "species_neighbor_1".into(), "species_neighbor_2".into()];
+        let energies = [-3.0, 2.0];
+
+        let ridge = RidgeRegression {
+            regularizer: 1e-3,
+            forces_weight: 0.0,
+            densify: densify.clone(),
+        };
+        let mut training = compute(&["water", "methane"]);
+        let model = ridge.fit(&mut training, &energies, None).unwrap();
+
+        let mut water = compute(&["water"]);
+        let (energy, forces) = model.predict(&mut water, 3).unwrap();
+        assert_eq!(water.features, model.features);
+        assert_eq!(forces.shape(), [3, 3]);
+
+        // same prediction as for the water molecule in the training set
+        let mut expected = 0.0;
+        for (sample, row) in training.samples.iter().zip(training.values.outer_iter()) {
+            if sample[0].usize() == 0 {
+                expected += row.dot(&model.weights);
+            }
+        }
+        assert_relative_eq!(energy, expected, max_relative = 1e-12);
+
+        let kernel_ridge = KernelRidgeRegression {
+            regularizer: 1e-3,
+            zeta: 2,
+            densify: densify,
+        };
+        let mut training = compute(&["water", "methane"]);
+        let model = kernel_ridge.fit(&mut training, &energies).unwrap();
+
+        let (energy, _) = model.predict(&mut compute(&["water"]), 3).unwrap();
+        let mut expected = 0.0;
+        for (sample, row) in training.samples.iter().zip(training.values.outer_iter()) {
+            if sample[0].usize() == 0 {
+                expected += model.environments.dot(&row).mapv(|k| k.powi(2)).dot(&model.weights);
+            }
+        }
+        assert_relative_eq!(energy, expected, max_relative = 1e-12);
+
+        // descriptors with different features are rejected
+        let error = model.predict(&mut self::compute(&["water"]), 3).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "invalid parameter: can not densify along 'species_center' which is not present in the samples: [structure, center]"
+        );
+    }
+}
@@ -0,0 +1,238 @@
+//! Collection and extraction of profiling data.
+//!
+//! Rascaline uses [`time_graph`](https://docs.rs/time-graph/) to collect timing
+//! information about the most important functions (neighbors list
+//! construction, radial integrals, descriptor densification, *etc.*). Data
+//! collection is disabled by default, and can be enabled with [`enable`]. The
+//! collected data can then be extracted as a call tree with [`report`].
+//!
+//! ```
+//! rascaline::profiling::enable(true);
+//!
+//! // run some calculations here ...
+//!
+//! let report = rascaline::profiling::report();
+//! for root in &report.roots {
+//!     println!("{} took {:?}", root.name, root.elapsed);
+//! }
+//! # rascaline::profiling::enable(false);
+//! ```
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::Duration;
+
+use serde::{Serialize, Serializer};
+
+/// Enable or disable profiling data collection. Data collection is disabled
+/// by default.
+pub fn enable(enabled: bool) {
+    time_graph::enable_data_collection(enabled);
+}
+
+/// Clear all the profiling data collected until now
+pub fn clear() {
+    time_graph::clear_collected_data();
+}
+
+/// Get the profiling data collected until now, as a call tree
+pub fn report() -> ProfilingReport {
+    let graph = time_graph::get_full_graph();
+
+    let mut spans = BTreeMap::new();
+    for span in graph.spans() {
+        spans.insert(span.id, (span.callsite.name().to_owned(), span.elapsed, span.called));
+    }
+
+    // time_graph 0.1 reports the edges of the call graph the wrong way
+    // around, `calls.callee` is the function doing the call.
+    let calls = graph.calls().map(|calls| (calls.callee, calls.caller, calls.count));
+    return build_report(&spans, calls);
+}
+
+/// Build the call tree from the `spans` (id => name, elapsed time and number
+/// of calls) and `calls` (parent id, child id, number of calls) in the call
+/// graph. Calls referring to spans not in `spans` are ignored.
+fn build_report(
+    spans: &BTreeMap<usize, (String, Duration, u32)>,
+    calls: impl Iterator<Item = (usize, usize, usize)>,
+) -> ProfilingReport {
+    let mut children = BTreeMap::<usize, Vec<(usize, usize)>>::new();
+    let mut callees = BTreeSet::new();
+    for (parent, child, count) in calls {
+        if !spans.contains_key(&parent) || !spans.contains_key(&child) {
+            continue;
+        }
+        children.entry(parent).or_default().push((child, count));
+        callees.insert(child);
+    }
+
+    let mut roots = Vec::new();
+    for (&id, &(_, _, called)) in spans {
+        if !callees.contains(&id) {
+            let mut ancestors = Vec::new();
+            roots.push(build_node(id, called as usize, spans, &children, &mut ancestors));
+        }
+    }
+
+    return ProfilingReport { roots: roots };
+}
+
+/// Recursively build the node for `id` in the call tree. `ancestors` is used
+/// to stop the recursion for recursive functions.
+fn build_node(
+    id: usize,
+    calls: usize,
+    spans: &BTreeMap<usize, (String, Duration, u32)>,
+    children: &BTreeMap<usize, Vec<(usize, usize)>>,
+    ancestors: &mut Vec<usize>,
+) -> ProfilingNode {
+    let (name, elapsed, called) = &spans[&id];
+
+    ancestors.push(id);
+    let mut node_children = Vec::new();
+    if let Some(callees) = children.get(&id) {
+        for &(callee, count) in callees {
+            if !ancestors.contains(&callee) {
+                node_children.push(build_node(callee, count, spans, children, ancestors));
+            }
+        }
+    }
+    ancestors.pop();
+
+    return ProfilingNode {
+        name: name.clone(),
+        elapsed: *elapsed,
+        total_calls: *called as usize,
+        calls: calls,
+        children: node_children,
+    };
+}
+
+/// Profiling data collected during calculations, organized as a call tree
+#[derive(Debug, Clone, Serialize)]
+pub struct ProfilingReport {
+    /// Functions that were not called by any other profiled function
+    pub roots: Vec<ProfilingNode>,
+}
+
+impl ProfilingReport {
+    /// Serialize this report to JSON
+    pub fn to_json(&self) -> String {
+        return serde_json::to_string(self).expect("failed to serialize profiling report");
+    }
+}
+
+/// A single function in the profiling call tree
+#[derive(Debug, Clone, Serialize)]
+pub struct ProfilingNode {
+    /// Name of the function
+    pub name: String,
+    /// Total time spent inside this function, over all of its callers
+    #[serde(serialize_with = "serialize_seconds")]
+    pub elapsed: Duration,
+    /// Total number of times this function was called, over all of its callers
+    pub total_calls: usize,
+    /// Number of times this function was called by its parent in the call
+    /// tree. For roots of the tree, this is the same as `total_calls`.
+    pub calls: usize,
+    /// Profiled functions called by this function
+    pub children: Vec<ProfilingNode>,
+}
+
+impl ProfilingNode {
+    /// Find the first node with the given `name` in the subtree starting at
+    /// this node, including this node itself
+    pub fn find(&self, name: &str) -> Option<&ProfilingNode> {
+        if self.name == name {
+            return Some(self);
+        }
+        return self.children.iter().find_map(|child| child.find(name));
+    }
+}
+
+/// Serialize a `Duration` as a floating point number of seconds
+fn serialize_seconds<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    return serializer.serialize_f64(duration.as_secs_f64());
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::time::Duration;
+
+    use crate::{Calculator, CalculationOptions, Descriptor};
+    use crate::systems::test_utils::test_systems;
+
+    #[time_graph::instrument(name = "profiling::tests::outer")]
+    fn outer() {
+        inner();
+        inner();
+    }
+
+    #[time_graph::instrument(name = "profiling::tests::inner")]
+    fn inner() {}
+
+    #[test]
+    fn call_direction() {
+        super::enable(true);
+        outer();
+
+        let report = super::report();
+        let outer = report.roots.iter()
+            .find(|root| root.name == "profiling::tests::outer")
+            .expect("missing outer function in the roots of the profiling report");
+        assert_eq!(outer.calls, 1);
+        assert_eq!(outer.children.len(), 1);
+        assert_eq!(outer.children[0].name, "profiling::tests::inner");
+        assert_eq!(outer.children[0].calls, 2);
+
+        assert!(report.roots.iter().all(|root| root.name != "profiling::tests::inner"));
+    }
+
+    #[test]
+    fn missing_spans() {
+        let mut spans = BTreeMap::new();
+        spans.insert(3, ("parent".to_owned(), Duration::from_millis(10), 1));
+        spans.insert(7, ("child".to_owned(), Duration::from_millis(4), 2));
+
+        let calls = vec![(3, 7, 2), (3, 12, 1), (25, 7, 1)];
+        let report = super::build_report(&spans, calls.into_iter());
+
+        assert_eq!(report.roots.len(), 1);
+        let root = &report.roots[0];
+        assert_eq!(root.name, "parent");
+        assert_eq!(root.calls, 1);
+        assert_eq!(root.children.len(), 1);
+        assert_eq!(root.children[0].name, "child");
+        assert_eq!(root.children[0].calls, 2);
+        assert_eq!(root.children[0].total_calls, 2);
+    }
+
+    #[test]
+    fn call_tree() {
+        super::enable(true);
+
+        let mut calculator = Calculator::new("dummy_calculator", r#"{
+            "cutoff": 1.0,
+            "delta": 9,
+            "name": "",
+            "gradients": false
+        }"#.to_owned()).unwrap();
+
+        let mut systems = test_systems(&["water"]);
+        let mut descriptor = Descriptor::new();
+        calculator.compute(&mut systems, &mut descriptor, CalculationOptions::default()).unwrap();
+
+        let report = super::report();
+        let compute = report.roots.iter()
+            .find_map(|root| root.find("Calculator::compute"))
+            .expect("missing Calculator::compute in the profiling report");
+        assert!(compute.total_calls >= 1);
+
+        let dummy = compute.find("DummyCalculator::compute").unwrap();
+        assert!(dummy.calls >= 1);
+        assert!(dummy.elapsed <= compute.elapsed);
+
+        let json: serde_json::Value = serde_json::from_str(&report.to_json()).unwrap();
+        assert!(json["roots"].is_array());
+    }
+}
@@ -0,0 +1,51 @@
+use std::collections::BTreeSet;
+
+use crate::{Error, System};
+
+/// Get the list of centers in `system` whose atomic environment (up to the
+/// given `cutoff`) contains any of the `moved` atoms. The `moved` atoms
+/// themselves are always part of the list, which is sorted.
+///
+/// The descriptor only needs to be re-computed for these centers after the
+/// `moved` atoms changed positions, and the new values can then be merged
+/// with the previous ones using `Descriptor::update`. Since atoms can both
+/// enter and leave the environment of a center, this function should be
+/// called with the positions before and after the move, and the union of both
+/// lists used for the calculation.
+pub fn affected_centers(system: &mut dyn System, cutoff: f64, moved: &[usize]) -> Result<Vec<usize>, Error> {
+    let size = system.size()?;
+    system.compute_neighbors(cutoff)?;
+
+    let mut centers = BTreeSet::new();
+    for &atom in moved {
+        if atom >= size {
+            return Err(Error::InvalidParameter(format!(
+                "moved atom {} is out of bounds for a system with {} atoms", atom, size
+            )));
+        }
+
+        centers.insert(atom);
+        for pair in system.pairs_containing(atom)? {
+            centers.insert(pair.first);
+            centers.insert(pair.second);
+        }
+    }
+
+    return Ok(centers.into_iter().collect());
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::systems::test_utils::test_system;
+
+    #[test]
+    fn affected_centers() {
+        let mut system = test_system("water");
+        assert_eq!(super::affected_centers(&mut system, 1.5, &[1]).unwrap(), [0, 1]);
+        assert_eq!(super::affected_centers(&mut system, 1.5, &[0]).unwrap(), [0, 1, 2]);
+        assert_eq!(super::affected_centers(&mut system, 1.5, &[]).unwrap(), Vec::<usize>::new());
+
+        let error = super::affected_centers(&mut system, 1.5, &[3]).unwrap_err();
+        assert_eq!(error.to_string(), "invalid parameter: moved atom 3 is out of bounds for a system with 3 atoms");
+    }
+}
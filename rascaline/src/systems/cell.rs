@@ -1,7 +1,9 @@
 //! The `UnitCell` type represents the enclosing box of a simulated system, with
 //! some type of periodic condition.
 use std::f64;
-use crate::{Matrix3, Vector3D};
+use std::convert::TryFrom;
+
+use crate::{Error, Matrix3, Vector3D};
 
 /// The shape of a cell determine how we will be able to compute the periodic
 /// boundaries condition.
@@ -33,13 +35,43 @@ pub struct UnitCell {
     shape: CellShape,
 }
 
-impl From<Matrix3> for UnitCell {
-    fn from(matrix: Matrix3) -> UnitCell {
+impl TryFrom<Matrix3> for UnitCell {
+    type Error = Error;
+
+    /// Create a unit cell from its matrix, where the rows of the matrix are
+    /// the cell vectors. A matrix full of zeros corresponds to an infinite
+    /// cell. This returns an error for left-handed cells and for singular (or
+    /// close to singular) matrices, i.e. when the cell vectors are (close to
+    /// being) coplanar.
+    fn try_from(matrix: Matrix3) -> Result<UnitCell, Error> {
         if matrix == Matrix3::zero() {
-            return UnitCell::infinite()
+            return Ok(UnitCell::infinite());
+        }
+
+        if !matrix.iter().flatten().all(|x| x.is_finite()) {
+            return Err(Error::InvalidParameter(format!(
+                "invalid unit cell {:?}: all values must be finite", *matrix
+            )));
         }
 
-        assert!(matrix.determinant() > 1e-6, "matrix is not invertible");
+        let lengths = Vector3D::from(matrix[0]).norm()
+            * Vector3D::from(matrix[1]).norm()
+            * Vector3D::from(matrix[2]).norm();
+
+        // the ratio of the volume with the product of the cell lengths is
+        // zero for singular matrices and one for orthorhombic cells
+        let determinant = matrix.determinant();
+        if f64::abs(determinant) <= 1e-6 || f64::abs(determinant) / lengths < 1e-6 {
+            return Err(Error::InvalidParameter(format!(
+                "invalid unit cell {:?}: the cell vectors are (close to being) coplanar", *matrix
+            )));
+        }
+
+        if determinant < 0.0 {
+            return Err(Error::InvalidParameter(format!(
+                "invalid unit cell {:?}: left-handed cells are not supported", *matrix
+            )));
+        }
 
         let is_close_0 = |value| f64::abs(value) < 1e-6;
         let is_diagonal = |matrix: Matrix3| {
@@ -54,12 +86,12 @@ impl From<Matrix3> for UnitCell {
             CellShape::Triclinic
         };
 
-        return UnitCell {
+        return Ok(UnitCell {
             matrix: matrix,
             transpose: matrix.transposed(),
             inverse: matrix.transposed().inverse(),
             shape: shape
-        }
+        });
     }
 }
 
@@ -110,11 +142,11 @@ impl UnitCell {
         let c_y = c * (cos_alpha - cos_beta * cos_gamma) / sin_gamma;
         let c_z = f64::sqrt(c * c - c_y * c_y - c_x * c_x);
 
-        return UnitCell::from(Matrix3::new([
+        return UnitCell::try_from(Matrix3::new([
             [a,   0.0, 0.0],
             [b_x, b_y, 0.0],
             [c_x, c_y, c_z],
-        ]));
+        ])).expect("invalid cell angles");
     }
 
     /// Get the cell shape
@@ -235,6 +267,45 @@ impl UnitCell {
         // we only have code to multiply a vector by a matrix on the left
         return self.transpose * fractional;
     }
+
+    /// Get all the reciprocal lattice vectors `k` of this cell with a norm
+    /// below `k_cutoff`, excluding `k = 0`. Only one of `k` and `-k` is
+    /// included in the output, since they usually give the same contribution
+    /// to sums over reciprocal space.
+    pub(crate) fn reciprocal_half_space(&self, k_cutoff: f64) -> Vec<Vector3D> {
+        assert!(!self.is_infinite(), "can not get reciprocal vectors of an infinite cell");
+
+        // rows of `inverse` are the reciprocal vectors, divided by 2π
+        let reciprocal = [
+            2.0 * f64::consts::PI * Vector3D::from(self.inverse[0]),
+            2.0 * f64::consts::PI * Vector3D::from(self.inverse[1]),
+            2.0 * f64::consts::PI * Vector3D::from(self.inverse[2]),
+        ];
+
+        let max_n = |i: usize| {
+            let length = Vector3D::from(self.matrix[i]).norm();
+            (k_cutoff * length / (2.0 * f64::consts::PI)) as isize
+        };
+        let (max_n1, max_n2, max_n3) = (max_n(0), max_n(1), max_n(2));
+
+        let mut kvectors = Vec::new();
+        for n1 in 0..=max_n1 {
+            for n2 in -max_n2..=max_n2 {
+                for n3 in -max_n3..=max_n3 {
+                    if n1 == 0 && (n2 < 0 || (n2 == 0 && n3 <= 0)) {
+                        continue;
+                    }
+
+                    let k = n1 as f64 * reciprocal[0] + n2 as f64 * reciprocal[1] + n3 as f64 * reciprocal[2];
+                    if k.norm() < k_cutoff {
+                        kvectors.push(k);
+                    }
+                }
+            }
+        }
+
+        return kvectors;
+    }
 }
 
 /// Get the angles between the vectors `u` and `v`.
@@ -268,6 +339,57 @@ mod tests {
         let _ = UnitCell::triclinic(3.0, 0.0, -5.0, 90.0, 90.0, 90.0);
     }
 
+    #[test]
+    fn from_matrix() {
+        let cell = UnitCell::try_from(Matrix3::zero()).unwrap();
+        assert!(cell.is_infinite());
+
+        let cell = UnitCell::try_from(Matrix3::new([
+            [3.0, 0.0, 0.0],
+            [0.0, 4.0, 0.0],
+            [0.0, 0.0, 5.0],
+        ])).unwrap();
+        assert_eq!(cell.shape(), CellShape::Orthorhombic);
+
+        let cell = UnitCell::try_from(Matrix3::new([
+            [0.0, 1.5, 1.5],
+            [1.5, 0.0, 1.5],
+            [1.5, 1.5, 0.0],
+        ])).unwrap();
+        assert_eq!(cell.shape(), CellShape::Triclinic);
+
+        let error = UnitCell::try_from(Matrix3::new([
+            [3.0, 0.0, 0.0],
+            [0.0, 4.0, 0.0],
+            [3.0, 4.0, 0.0],
+        ])).unwrap_err();
+        assert_eq!(error.to_string(),
+            "invalid parameter: invalid unit cell [[3.0, 0.0, 0.0], [0.0, 4.0, 0.0], \
+            [3.0, 4.0, 0.0]]: the cell vectors are (close to being) coplanar"
+        );
+
+        let error = UnitCell::try_from(Matrix3::new([
+            [3.0, 0.0, 0.0],
+            [0.0, 4.0, 0.0],
+            [0.0, 0.0, 1e-8],
+        ])).unwrap_err();
+        assert!(error.to_string().ends_with("the cell vectors are (close to being) coplanar"));
+
+        let error = UnitCell::try_from(Matrix3::new([
+            [0.0, 4.0, 0.0],
+            [3.0, 0.0, 0.0],
+            [0.0, 0.0, 5.0],
+        ])).unwrap_err();
+        assert!(error.to_string().ends_with("left-handed cells are not supported"));
+
+        let error = UnitCell::try_from(Matrix3::new([
+            [f64::NAN, 0.0, 0.0],
+            [0.0, 4.0, 0.0],
+            [0.0, 0.0, 5.0],
+        ])).unwrap_err();
+        assert!(error.to_string().ends_with("all values must be finite"));
+    }
+
     #[test]
     fn infinite() {
         let cell = UnitCell::infinite();
@@ -3,6 +3,9 @@ use std::path::Path;
 use super::SimpleSystem;
 use crate::Error;
 
+/// Is rascaline compiled with support for reading files with chemfiles?
+pub const CHEMFILES_SUPPORT: bool = cfg!(feature = "chemfiles");
+
 #[cfg(feature = "chemfiles")]
 impl From<chemfiles::Error> for Error {
     fn from(error: chemfiles::Error) -> Error {
@@ -19,12 +22,16 @@ impl From<chemfiles::Error> for Error {
 #[allow(clippy::needless_range_loop)]
 pub fn read_from_file(path: impl AsRef<Path>) -> Result<Vec<SimpleSystem>, Error> {
     use std::collections::HashMap;
-    use crate::Matrix3;
+    use std::convert::TryFrom;
+    use crate::{ErrorContext, Matrix3};
     use crate::systems::UnitCell;
 
     let mut systems = Vec::new();
 
-    let mut trajectory = chemfiles::Trajectory::open(path, 'r')?;
+    let path = path.as_ref();
+    let mut trajectory = chemfiles::Trajectory::open(path, 'r').with_context(|| format!(
+        "failed to open '{}'", path.display()
+    ))?;
     let mut frame = chemfiles::Frame::new();
 
     let mut assigned_species = HashMap::new();
@@ -41,8 +48,10 @@ pub fn read_from_file(path: impl AsRef<Path>) -> Result<Vec<SimpleSystem>, Error
         }
     };
 
-    for _ in 0..trajectory.nsteps() {
-        trajectory.read(&mut frame)?;
+    for step in 0..trajectory.nsteps() {
+        trajectory.read(&mut frame).with_context(|| format!(
+            "failed to read step {} from '{}'", step, path.display()
+        ))?;
 
         let positions = frame.positions();
 
@@ -51,7 +60,7 @@ pub fn read_from_file(path: impl AsRef<Path>) -> Result<Vec<SimpleSystem>, Error
         } else {
             // transpose since chemfiles is using columns for the cell vectors and
             // we want rows as cell vectors
-            UnitCell::from(Matrix3::from(frame.cell().matrix()).transposed())
+            UnitCell::try_from(Matrix3::from(frame.cell().matrix()).transposed())?
         };
         let mut system = SimpleSystem::new(cell);
         for i in 0..frame.size() {
@@ -4,13 +4,16 @@ mod cell;
 pub use self::cell::UnitCell;
 
 mod neighbors;
-pub use self::neighbors::NeighborsList;
+pub use self::neighbors::{NeighborsList, PeriodicImagesCheck};
 
 mod simple_system;
 pub use self::simple_system::SimpleSystem;
 
 mod chemfiles;
-pub use self::chemfiles::read_from_file;
+pub use self::chemfiles::{read_from_file, CHEMFILES_SUPPORT};
+
+mod affected;
+pub use self::affected::affected_centers;
 
 #[cfg(test)]
 pub(crate) mod test_utils;
@@ -30,6 +33,24 @@ pub struct Pair {
     /// vector from the first atom to the second atom, wrapped inside the unit
     /// cell as required
     pub vector: Vector3D,
+    /// number of unit cell vectors that have to be added to the difference of
+    /// the positions to get `vector`, i.e. `vector = positions[second] -
+    /// positions[first] + cell_shift * cell`. This is `[0, 0, 0]` for
+    /// non-periodic systems.
+    pub cell_shift: [i32; 3],
+}
+
+/// Triplet of atoms `first-center-second` around a given center, built from
+/// two pairs containing this center.
+// WARNING: any change to this definition MUST be reflected in rascal_triplet_t
+// as well
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Triplet {
+    /// index of the first neighbor of the center
+    pub first: usize,
+    /// index of the second neighbor of the center
+    pub second: usize,
 }
 
 /// A `System` deals with the storage of atoms and related information, as well
@@ -70,4 +91,50 @@ pub trait System {
     /// included both in the return of `pairs_containing(i)` and
     /// `pairs_containing(j)`.
     fn pairs_containing(&self, center: usize) -> Result<&[Pair], Error>;
+
+    /// Get the indexes in the list returned by `pairs` of all the pairs which
+    /// include the atom at index `center`, or `None` if this system does not
+    /// provide them. These indexes should refer to the same pairs as
+    /// `pairs_containing(center)`, allowing to associate per-pair data with
+    /// each center without going through all pairs. This function is only
+    /// valid to call after a call to `compute_neighbors`.
+    ///
+    /// The default implementation does not provide these indexes.
+    #[allow(unused_variables)]
+    fn pairs_indexes_containing(&self, center: usize) -> Result<Option<&[usize]>, Error> {
+        Ok(None)
+    }
+
+    /// Get the per-atom data with the given `name` (for example partial
+    /// charges or site occupancies), or `None` if this system does not contain
+    /// such data. The returned value must be a slice of length `self.size()`.
+    ///
+    /// The default implementation does not provide any data.
+    #[allow(unused_variables)]
+    fn data(&self, name: &str) -> Result<Option<&[f64]>, Error> {
+        Ok(None)
+    }
+
+    /// Check whether the `cell_shift` of the pairs returned by `pairs` and
+    /// `pairs_containing` are set by this system. If this returns `false`,
+    /// code using the neighbor list must not rely on them.
+    ///
+    /// The default implementation assumes the cell shifts are set.
+    fn has_cell_shifts(&self) -> Result<bool, Error> {
+        Ok(true)
+    }
+
+    /// Get the list of triplets `i-center-j` around the atom at index
+    /// `center`, or `None` if this system does not provide them. Each triplet
+    /// should be included once (either as `i-j` or `j-i`), including the
+    /// triplets where `i == j`; and only contain atoms in the pairs returned
+    /// by `pairs_containing(center)`. This function is only valid to call
+    /// after a call to `compute_neighbors`.
+    ///
+    /// The default implementation does not provide triplets, and rascaline
+    /// builds them from `pairs_containing`.
+    #[allow(unused_variables)]
+    fn triplets_containing(&self, center: usize) -> Result<Option<&[Triplet]>, Error> {
+        Ok(None)
+    }
 }
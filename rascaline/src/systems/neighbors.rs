@@ -1,7 +1,10 @@
+use std::collections::BTreeSet;
+use std::convert::TryFrom;
+
 use log::warn;
 use ndarray::Array3;
 
-use crate::{Matrix3, Vector3D};
+use crate::{Error, Matrix3, Vector3D};
 use super::{UnitCell, Pair};
 
 /// `f64::clamp` backported to rust 1.45
@@ -16,8 +19,8 @@ fn f64_clamp(mut x: f64, min: f64, max: f64) -> f64 {
     return x;
 }
 
-/// `usize::clamp` backported to rust 1.45
-fn usize_clamp(mut x: usize, min: usize, max: usize) -> usize {
+/// `isize::clamp` backported to rust 1.45
+fn isize_clamp(mut x: isize, min: isize, max: isize) -> isize {
     debug_assert!(min <= max);
     if x < min {
         x = min;
@@ -32,6 +35,62 @@ fn usize_clamp(mut x: usize, min: usize, max: usize) -> usize {
 /// cells with a small unit cell and a large cutoff
 const MAX_NUMBER_OF_CELLS: f64 = 1e5;
 
+/// What to do when the cutoff is too large for the unit cell, i.e. when more
+/// than `MAX_NUMBER_OF_CELLS` periodic images of the unit cell need to be
+/// searched to find all the neighbors of an atom.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PeriodicImagesCheck {
+    /// Do not check the number of periodic images
+    Ignore,
+    /// Emit a warning through the `log` crate, and continue with the
+    /// calculation
+    Warn,
+    /// Return an error
+    Error,
+}
+
+impl Default for PeriodicImagesCheck {
+    fn default() -> PeriodicImagesCheck {
+        PeriodicImagesCheck::Warn
+    }
+}
+
+/// Check the number of periodic images of `unit_cell` required to find all
+/// neighbors up to `cutoff`, and warn or error according to `check` if there
+/// are too many of them.
+fn check_periodic_images(unit_cell: &UnitCell, cutoff: f64, check: PeriodicImagesCheck) -> Result<(), Error> {
+    if check == PeriodicImagesCheck::Ignore || unit_cell.is_infinite() {
+        return Ok(());
+    }
+
+    let distances_between_faces = unit_cell.distances_between_faces();
+    // number of periodic images in each direction, on both sides of the
+    // unit cell, plus the unit cell itself
+    let n_images = [
+        2.0 * f64::ceil(cutoff / distances_between_faces[0]) + 1.0,
+        2.0 * f64::ceil(cutoff / distances_between_faces[1]) + 1.0,
+        2.0 * f64::ceil(cutoff / distances_between_faces[2]) + 1.0,
+    ];
+
+    let n_images_total = n_images[0] * n_images[1] * n_images[2];
+    if n_images_total > MAX_NUMBER_OF_CELLS {
+        let message = format!(
+            "the cutoff ({}) is too large for the unit cell (distances between faces: {}, {}, {}), \
+            the neighbor list would need to search {} periodic images of the cell",
+            cutoff,
+            distances_between_faces[0], distances_between_faces[1], distances_between_faces[2],
+            n_images_total,
+        );
+
+        if check == PeriodicImagesCheck::Error {
+            return Err(Error::InvalidParameter(message));
+        }
+        warn!("{}", message);
+    }
+
+    return Ok(());
+}
+
 /// A cell shift represents the displacement along cell axis between the actual
 /// position of an atom and a periodic image of this atom.
 ///
@@ -71,6 +130,15 @@ impl std::ops::Index<usize> for CellShift {
 }
 
 impl CellShift {
+    /// Convert this cell shift to the 32-bit integers used in `Pair`, returning
+    /// an error if any of the components does not fit in an `i32`.
+    pub fn to_i32(&self) -> Result<[i32; 3], Error> {
+        let convert = |value: isize| i32::try_from(value).map_err(|_| Error::InvalidParameter(format!(
+            "cell shift {:?} is too large to be stored in 32-bit integers", self.0
+        )));
+        return Ok([convert(self[0])?, convert(self[1])?, convert(self[2])?]);
+    }
+
     /// Compute the shift vector in cartesian coordinates, using the given cell
     /// matrix (stored in row major order).
     pub fn cartesian(&self, cell: &Matrix3) -> Vector3D {
@@ -115,8 +183,12 @@ pub struct CellList {
     n_search: [isize; 3],
     /// the cells themselves
     cells: ndarray::Array3<Vec<AtomData>>,
+    /// Index of the cell containing each atom, indexed by the atom index
+    locations: Vec<Option<[usize; 3]>>,
     /// Unit cell defining periodic boundary conditions
     unit_cell: UnitCell,
+    /// Cutoff used to determine the number of cells
+    cutoff: f64,
 }
 
 impl CellList {
@@ -151,14 +223,25 @@ impl CellList {
             n_cells[2] = f64::trunc(f64::cbrt(MAX_NUMBER_OF_CELLS / (ratio_x_y * ratio_y_z * ratio_y_z)));
             n_cells[1] = f64::trunc(ratio_y_z * n_cells[2]);
             n_cells[0] = f64::trunc(ratio_x_y * n_cells[1]);
+
+            // very anisotropic cells can end up with zero cells in some
+            // directions after the truncation above
+            for n in &mut n_cells {
+                *n = f64_clamp(*n, 1.0, f64::INFINITY);
+            }
         }
 
         // number of cells to search in each direction to make sure all possible
-        // pairs below the cutoff are accounted for.
+        // pairs below the cutoff are accounted for. When the cells are smaller
+        // than the cutoff (which always happens if the unit cell is smaller
+        // than the cutoff), this needs to go through more than one neighboring
+        // cell, potentially including multiple periodic images of the unit
+        // cell. This must be rounded up, otherwise pairs between atoms close
+        // to opposite faces of the cells would be missed.
         let mut n_search = [
-            f64::trunc(cutoff * n_cells[0] / distances_between_faces[0]) as isize,
-            f64::trunc(cutoff * n_cells[1] / distances_between_faces[1]) as isize,
-            f64::trunc(cutoff * n_cells[2] / distances_between_faces[2]) as isize,
+            f64::ceil(cutoff * n_cells[0] / distances_between_faces[0]) as isize,
+            f64::ceil(cutoff * n_cells[1] / distances_between_faces[1]) as isize,
+            f64::ceil(cutoff * n_cells[2] / distances_between_faces[2]) as isize,
         ];
 
         let n_cells = [
@@ -182,13 +265,89 @@ impl CellList {
         CellList {
             n_search: n_search,
             cells: Array3::from_elem(n_cells, Default::default()),
+            locations: Vec::new(),
             unit_cell: unit_cell,
+            cutoff: cutoff,
         }
     }
 
+    /// Create a new `CellList` for the given unit cell and cutoff, containing
+    /// all the atoms at the given `positions`. The `check` controls what
+    /// happens if the cutoff is too large compared to the unit cell.
+    pub fn with_positions(
+        positions: &[Vector3D],
+        unit_cell: UnitCell,
+        cutoff: f64,
+        check: PeriodicImagesCheck,
+    ) -> Result<CellList, Error> {
+        check_periodic_images(&unit_cell, cutoff, check)?;
+
+        let mut cell_list = CellList::new(unit_cell, cutoff);
+        for (index, &position) in positions.iter().enumerate() {
+            cell_list.add_atom(index, position);
+        }
+
+        return Ok(cell_list);
+    }
+
+    /// Get the cutoff used to create this cell list
+    pub fn cutoff(&self) -> f64 {
+        self.cutoff
+    }
+
     /// Add a single atom to the cell list at the given `position`. The atom is
     /// uniquely identified by its `index`.
     pub fn add_atom(&mut self, index: usize, position: Vector3D) {
+        let (shift, cell_index) = self.find_cell(position);
+        self.cells[cell_index].push(AtomData {
+            index: index,
+            shift: CellShift(shift),
+        });
+
+        if self.locations.len() <= index {
+            self.locations.resize(index + 1, None);
+        }
+        self.locations[index] = Some(cell_index);
+    }
+
+    /// Update the cell list after the atoms with indexes in
+    /// `positions_changed` moved to new `positions`. `positions` contains the
+    /// positions of all atoms, indexed by atom index.
+    ///
+    /// Only the atoms which changed cell are moved from one cell to the other,
+    /// which is much faster than re-building the cell list from scratch when
+    /// only a few atoms moved, or when all atoms moved a little bit (for
+    /// example between two steps of a molecular dynamics simulation).
+    pub fn update(&mut self, positions: &[Vector3D], positions_changed: &[usize]) {
+        for &index in positions_changed {
+            let old_cell = self.locations.get(index).copied().flatten().expect(
+                "trying to update an atom which is not in the cell list"
+            );
+            let (shift, new_cell) = self.find_cell(positions[index]);
+
+            let atoms = &mut self.cells[old_cell];
+            let position = atoms.iter()
+                .position(|atom| atom.index == index)
+                .expect("missing atom in the cell list");
+
+            if new_cell == old_cell {
+                // the atom might still have crossed a periodic boundary
+                atoms[position].shift = CellShift(shift);
+            } else {
+                atoms.swap_remove(position);
+                self.cells[new_cell].push(AtomData {
+                    index: index,
+                    shift: CellShift(shift),
+                });
+                self.locations[index] = Some(new_cell);
+            }
+        }
+    }
+
+    /// Find the cell containing an atom at the given `position`, returning
+    /// the shift from the position to the image inside the unit cell, and the
+    /// index of the cell
+    fn find_cell(&self, position: Vector3D) -> ([isize; 3], [usize; 3]) {
         let fractional = if self.unit_cell.is_infinite() {
             position
         } else {
@@ -207,21 +366,18 @@ impl CellList {
 
         // deal with pbc by wrapping the atom inside if it was outside of the
         // cell
-        let (shift, cell_index) = if self.unit_cell.is_infinite() {
+        if self.unit_cell.is_infinite() {
+            // atoms outside of the pseudo-cell go in the closest cell, making
+            // sure negative indexes end up in the first cell
             let cell_index = [
-                usize_clamp(cell_index[0] as usize, 0, n_cells[0] - 1),
-                usize_clamp(cell_index[1] as usize, 0, n_cells[1] - 1),
-                usize_clamp(cell_index[2] as usize, 0, n_cells[2] - 1),
+                isize_clamp(cell_index[0], 0, n_cells[0] as isize - 1) as usize,
+                isize_clamp(cell_index[1], 0, n_cells[1] as isize - 1) as usize,
+                isize_clamp(cell_index[2], 0, n_cells[2] as isize - 1) as usize,
             ];
-            ([0, 0, 0], cell_index)
-        } else {
-            divmod_vec(cell_index, n_cells)
-        };
+            return ([0, 0, 0], cell_index);
+        }
 
-        self.cells[cell_index].push(AtomData {
-            index: index,
-            shift: CellShift(shift),
-        });
+        return divmod_vec(cell_index, n_cells);
     }
 
     /// Get the list of candidate pair. Some pairs might be separated by more
@@ -238,9 +394,24 @@ impl CellList {
     /// distances/directions are still included. Using the example above and
     /// with a cutoff of 5 Å, we can have a pair between atoms 33-64 at 2.6 Å
     /// and another pair between atoms 33-64 at 4.8 Å.
+    ///
+    /// The list of candidate pairs can be very large for big systems, prefer
+    /// [`CellList::for_each_pair`] to process the pairs one at the time
+    /// without storing all of them.
     pub fn pairs(&self) -> Vec<CellPair> {
         let mut pairs = Vec::new();
+        self.for_each_pair(|pair| pairs.push(pair));
+        return pairs;
+    }
 
+    /// Call `callback` on each candidate pair, in the same order as the pairs
+    /// returned by [`CellList::pairs`]. The same caveats apply: some pairs
+    /// might be separated by more than `cutoff`, and this produces a "half"
+    /// neighbors list.
+    ///
+    /// Contrary to [`CellList::pairs`], the pairs are never all stored in
+    /// memory at the same time.
+    pub fn for_each_pair<F>(&self, mut callback: F) where F: FnMut(CellPair) {
         let n_cells = self.cells.shape();
         let n_cells = [n_cells[0], n_cells[1], n_cells[2]];
 
@@ -286,7 +457,7 @@ impl CellList {
                                     continue;
                                 }
 
-                                pairs.push(CellPair {
+                                callback(CellPair {
                                     first: atom_i.index,
                                     second: atom_j.index,
                                     shift: shift,
@@ -299,8 +470,6 @@ impl CellList {
             } // loop over neighboring cells
 
         }
-
-        return pairs;
     }
 }
 
@@ -336,18 +505,80 @@ pub struct NeighborsList {
     pub pairs: Vec<Pair>,
     /// all pairs in the system, classified by associated center
     pub pairs_by_center: Vec<Vec<Pair>>,
+    /// offsets of each center in `pairs_indexes`: the indexes in `pairs` of
+    /// all the pairs containing center `i` are
+    /// `pairs_indexes[centers_offsets[i]..centers_offsets[i + 1]]`
+    pub centers_offsets: Vec<usize>,
+    /// indexes in `pairs` of the pairs containing each center, see
+    /// `centers_offsets`
+    pub pairs_indexes: Vec<usize>,
 }
 
 impl NeighborsList {
-    #[time_graph::instrument(name = "NeighborsList")]
-    pub fn new(positions: &[Vector3D], unit_cell: UnitCell, cutoff: f64) -> NeighborsList {
-        let mut cell_list = CellList::new(unit_cell, cutoff);
+    /// Create a neighbor list with the given `pairs` (sorted by first and
+    /// second atom), and the same pairs already classified by center in
+    /// `pairs_by_center`, building the indexes of the pairs containing each
+    /// center.
+    fn with_pairs(cutoff: f64, pairs: Vec<Pair>, pairs_by_center: Vec<Vec<Pair>>) -> NeighborsList {
+        let mut centers_offsets = vec![0; pairs_by_center.len() + 1];
+        for pair in &pairs {
+            centers_offsets[pair.first + 1] += 1;
+            centers_offsets[pair.second + 1] += 1;
+        }
 
-        for (index, &position) in positions.iter().enumerate() {
-            cell_list.add_atom(index, position);
+        for center in 0..pairs_by_center.len() {
+            centers_offsets[center + 1] += centers_offsets[center];
+        }
+
+        // pairs are sorted, so the indexes of the pairs containing a given
+        // center end up sorted as well
+        let mut positions = centers_offsets.clone();
+        let mut pairs_indexes = vec![0; 2 * pairs.len()];
+        for (pair_i, pair) in pairs.iter().enumerate() {
+            pairs_indexes[positions[pair.first]] = pair_i;
+            positions[pair.first] += 1;
+
+            pairs_indexes[positions[pair.second]] = pair_i;
+            positions[pair.second] += 1;
         }
 
-        let cell_matrix = unit_cell.matrix();
+        return NeighborsList {
+            cutoff: cutoff,
+            pairs: pairs,
+            pairs_by_center: pairs_by_center,
+            centers_offsets: centers_offsets,
+            pairs_indexes: pairs_indexes,
+        };
+    }
+
+    /// Get the indexes in `self.pairs` of all the pairs containing the given
+    /// `center`
+    pub fn pairs_indexes_containing(&self, center: usize) -> &[usize] {
+        let start = self.centers_offsets[center];
+        let stop = self.centers_offsets[center + 1];
+        return &self.pairs_indexes[start..stop];
+    }
+
+    /// Compute the neighbor list for atoms at the given `positions` in the
+    /// given `unit_cell` with the given `cutoff`. The `check` controls what
+    /// happens if the cutoff is too large compared to the unit cell.
+    #[time_graph::instrument(name = "NeighborsList")]
+    pub fn new(
+        positions: &[Vector3D],
+        unit_cell: UnitCell,
+        cutoff: f64,
+        check: PeriodicImagesCheck,
+    ) -> Result<NeighborsList, Error> {
+        let cell_list = CellList::with_positions(positions, unit_cell, cutoff, check)?;
+        return NeighborsList::from_cell_list(positions, &cell_list);
+    }
+
+    /// Compute the neighbor list for atoms at the given `positions` using an
+    /// existing `cell_list` containing the same atoms, with the cutoff of the
+    /// cell list.
+    pub fn from_cell_list(positions: &[Vector3D], cell_list: &CellList) -> Result<NeighborsList, Error> {
+        let cutoff = cell_list.cutoff;
+        let cell_matrix = cell_list.unit_cell.matrix();
         let cutoff2 = cutoff * cutoff;
 
         // the cell list creates too many pairs, we only need to keep the one where
@@ -355,7 +586,12 @@ impl NeighborsList {
         let mut pairs = Vec::new();
         let mut pairs_by_center = vec![Vec::new(); positions.len()];
 
-        for pair in cell_list.pairs() {
+        let mut error = None;
+        cell_list.for_each_pair(|pair| {
+            if error.is_some() {
+                return;
+            }
+
             let mut vector = positions[pair.second] - positions[pair.first];
             vector += pair.shift.cartesian(&cell_matrix);
 
@@ -368,36 +604,133 @@ impl NeighborsList {
                     );
                 }
 
+                let cell_shift = match pair.shift.to_i32() {
+                    Ok(cell_shift) => cell_shift,
+                    Err(e) => {
+                        error = Some(e);
+                        return;
+                    }
+                };
+
                 let pair = Pair {
                     first: pair.first,
                     second: pair.second,
                     distance: distance2.sqrt(),
                     vector: vector,
+                    cell_shift: cell_shift,
                 };
 
                 pairs.push(pair);
                 pairs_by_center[pair.first].push(pair);
                 pairs_by_center[pair.second].push(pair);
             }
-        }
+        });
 
         // sort the pairs to make sure the final output of rascaline is ordered
         // naturally
+        if let Some(error) = error {
+            return Err(error);
+        }
+
         pairs.sort_unstable_by_key(|pair| (pair.first, pair.second));
         for pairs in &mut pairs_by_center {
             pairs.sort_unstable_by_key(|pair| (pair.first, pair.second));
         }
 
-        return NeighborsList {
-            cutoff: cutoff,
-            pairs: pairs,
-            pairs_by_center: pairs_by_center,
+        return Ok(NeighborsList::with_pairs(cutoff, pairs, pairs_by_center));
+    }
+
+    /// Create a neighbor list from a list of `pairs` computed somewhere else,
+    /// for a system containing `n_atoms` atoms.
+    ///
+    /// The pairs should only contain each pair once, should not contain self
+    /// pairs, and all pairs should have a distance below `cutoff`.
+    pub fn from_pairs(n_atoms: usize, cutoff: f64, mut pairs: Vec<Pair>) -> Result<NeighborsList, Error> {
+        let mut pairs_by_center = vec![Vec::new(); n_atoms];
+        for pair in &pairs {
+            if pair.first >= n_atoms || pair.second >= n_atoms {
+                return Err(Error::InvalidParameter(format!(
+                    "got a pair between atoms {} and {}, but the system only contains {} atoms",
+                    pair.first, pair.second, n_atoms
+                )));
+            }
+
+            if pair.first == pair.second && pair.cell_shift == [0, 0, 0] {
+                return Err(Error::InvalidParameter(format!(
+                    "got a self pair for atom {} in the neighbor list", pair.first
+                )));
+            }
+
+            if !pair.distance.is_finite() || pair.distance >= cutoff {
+                return Err(Error::InvalidParameter(format!(
+                    "got a pair between atoms {} and {} with distance {}, above the cutoff ({})",
+                    pair.first, pair.second, pair.distance, cutoff
+                )));
+            }
+
+            pairs_by_center[pair.first].push(*pair);
+            pairs_by_center[pair.second].push(*pair);
+        }
+
+        pairs.sort_unstable_by_key(|pair| (pair.first, pair.second));
+        for pairs in &mut pairs_by_center {
+            pairs.sort_unstable_by_key(|pair| (pair.first, pair.second));
+        }
+
+        return Ok(NeighborsList::with_pairs(cutoff, pairs, pairs_by_center));
+    }
+
+    /// Create a new neighbor list containing only the pairs of this neighbor
+    /// list with a distance below `cutoff`, which should be smaller than the
+    /// cutoff of this neighbor list. This is faster than re-computing the
+    /// neighbor list from scratch.
+    pub fn filtered(&self, cutoff: f64) -> NeighborsList {
+        assert!(cutoff <= self.cutoff, "can not filter a neighbor list to a larger cutoff");
+
+        let filter = |pairs: &[Pair]| {
+            pairs.iter().filter(|pair| pair.distance < cutoff).copied().collect::<Vec<_>>()
+        };
+
+        return NeighborsList::with_pairs(
+            cutoff,
+            filter(&self.pairs),
+            self.pairs_by_center.iter().map(|pairs| filter(pairs)).collect(),
+        );
+    }
+
+    /// Create a new neighbor list containing only the pairs of this neighbor
+    /// list between atoms with one of the given combinations of species.
+    /// `species` contains the species of all atoms in the system, and
+    /// `species_pairs` the combinations of species to keep, sorted such that
+    /// the first species is smaller than the second one.
+    pub fn filtered_species(&self, species: &[i32], species_pairs: &BTreeSet<(i32, i32)>) -> NeighborsList {
+        let keep = |pair: &Pair| {
+            let species_first = species[pair.first];
+            let species_second = species[pair.second];
+            let key = if species_first <= species_second {
+                (species_first, species_second)
+            } else {
+                (species_second, species_first)
+            };
+            species_pairs.contains(&key)
+        };
+
+        let filter = |pairs: &[Pair]| {
+            pairs.iter().filter(|pair| keep(pair)).copied().collect::<Vec<_>>()
         };
+
+        return NeighborsList::with_pairs(
+            self.cutoff,
+            filter(&self.pairs),
+            self.pairs_by_center.iter().map(|pairs| filter(pairs)).collect(),
+        );
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::convert::TryFrom;
+
     use approx::assert_ulps_eq;
 
     use crate::Matrix3;
@@ -414,7 +747,7 @@ mod tests {
             Vector3D::new(0.149, 1.865, 0.635),
         ];
 
-        let neighbors = NeighborsList::new(&positions, UnitCell::infinite(), 3.42);
+        let neighbors = NeighborsList::new(&positions, UnitCell::infinite(), 3.42, PeriodicImagesCheck::Error).unwrap();
 
         // reference computed with ASE
         let reference = [
@@ -434,18 +767,19 @@ mod tests {
             assert_eq!(pair.first, reference.0);
             assert_eq!(pair.second, reference.1);
             assert_ulps_eq!(pair.distance, reference.2);
+            assert_eq!(pair.cell_shift, [0, 0, 0]);
         }
     }
 
     #[test]
     fn fcc_cell() {
-        let cell = UnitCell::from(Matrix3::from([
+        let cell = UnitCell::try_from(Matrix3::from([
             [0.0, 1.5, 1.5],
             [1.5, 0.0, 1.5],
             [1.5, 1.5, 0.0],
-        ]));
+        ])).unwrap();
         let positions = [Vector3D::new(0.0, 0.0, 0.0)];
-        let neighbors = NeighborsList::new(&positions, cell, 3.0);
+        let neighbors = NeighborsList::new(&positions, cell, 3.0, PeriodicImagesCheck::Error).unwrap();
 
         let expected = [
             Vector3D::new(0.0, -1.0, -1.0),
@@ -468,6 +802,15 @@ mod tests {
             assert_eq!(pair.second, 0);
             assert_ulps_eq!(pair.distance, 2.1213203435596424);
             assert_ulps_eq!(pair.vector / 1.5, vector);
+
+            // the only atom is at the origin, so the vector is the cell shift
+            let matrix = cell.matrix();
+            let shift = pair.cell_shift;
+            let mut from_shift = Vector3D::zero();
+            for i in 0..3 {
+                from_shift += f64::from(shift[i]) * Vector3D::from(matrix[i]);
+            }
+            assert_ulps_eq!(pair.vector, from_shift);
         }
     }
 
@@ -484,7 +827,7 @@ mod tests {
             Vector3D::new(-6.0, 0.0, -2.0),
         ];
 
-        let neighbors = NeighborsList::new(&positions, cell, 2.1);
+        let neighbors = NeighborsList::new(&positions, cell, 2.1, PeriodicImagesCheck::Error).unwrap();
 
         let expected = [
             (0, 1),
@@ -500,4 +843,196 @@ mod tests {
             assert_ulps_eq!(pair.distance, 2.0);
         }
     }
+
+    #[test]
+    fn cell_list_for_each_pair() {
+        let cell = UnitCell::cubic(4.0);
+        let mut cell_list = CellList::new(cell, 1.5);
+        cell_list.add_atom(0, Vector3D::new(0.0, 0.0, 0.0));
+        cell_list.add_atom(1, Vector3D::new(1.0, 0.5, 0.0));
+        cell_list.add_atom(2, Vector3D::new(3.5, 3.5, 3.5));
+        cell_list.add_atom(3, Vector3D::new(2.0, 2.0, 2.0));
+
+        let mut streamed = Vec::new();
+        cell_list.for_each_pair(|pair| streamed.push((pair.first, pair.second, pair.shift)));
+
+        let pairs = cell_list.pairs().into_iter()
+            .map(|pair| (pair.first, pair.second, pair.shift))
+            .collect::<Vec<_>>();
+
+        assert!(!pairs.is_empty());
+        assert_eq!(streamed, pairs);
+    }
+
+    #[test]
+    fn non_periodic_negative_positions() {
+        // with a cutoff below 1, the cell list uses multiple cells for
+        // infinite unit cells, and atoms at negative positions must go to the
+        // first cell
+        let positions = [
+            Vector3D::new(-0.1, 0.0, 0.0),
+            Vector3D::new(0.1, 0.0, 0.0),
+        ];
+
+        let neighbors = NeighborsList::new(&positions, UnitCell::infinite(), 0.3, PeriodicImagesCheck::Error).unwrap();
+        assert_eq!(neighbors.pairs.len(), 1);
+        assert_ulps_eq!(neighbors.pairs[0].distance, 0.2);
+    }
+
+    #[test]
+    fn cutoff_larger_than_cell() {
+        let cell = UnitCell::triclinic(1.0, 1.2, 0.9, 80.0, 95.0, 110.0);
+        let positions = [
+            cell.cartesian(Vector3D::new(0.02, 0.1, 0.3)),
+            cell.cartesian(Vector3D::new(0.97, 0.8, 0.95)),
+        ];
+        let cutoff = 2.3;
+
+        let neighbors = NeighborsList::new(&positions, cell, cutoff, PeriodicImagesCheck::Error).unwrap();
+
+        // brute force search through all periodic images
+        let matrix = cell.matrix();
+        let mut expected = 0;
+        for first in 0..positions.len() {
+            for second in first..positions.len() {
+                for a in -6..=6 {
+                    for b in -6..=6 {
+                        for c in -6..=6 {
+                            if first == second && a == 0 && b == 0 && c == 0 {
+                                continue;
+                            }
+
+                            let shift = CellShift([a, b, c]).cartesian(&matrix);
+                            let vector = positions[second] - positions[first] + shift;
+                            if vector.norm() < cutoff {
+                                expected += 1;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        assert_eq!(neighbors.pairs.len(), expected);
+    }
+
+    #[test]
+    fn anisotropic_cell() {
+        // this cell would require a lot of cells along x, and less than one
+        // cell along y and z once the total number of cells is limited
+        let cell = UnitCell::orthorhombic(1e6, 2.0, 2.0);
+        let positions = [
+            Vector3D::new(0.0, 0.0, 0.0),
+            Vector3D::new(0.5, 1.9, 0.0),
+        ];
+
+        let neighbors = NeighborsList::new(&positions, cell, 1.0, PeriodicImagesCheck::Error).unwrap();
+        assert_eq!(neighbors.pairs.len(), 1);
+        assert_ulps_eq!(neighbors.pairs[0].distance, f64::sqrt(0.5 * 0.5 + 0.1 * 0.1));
+    }
+
+    #[test]
+    fn pairs_indexes_containing() {
+        let cell = UnitCell::cubic(3.0);
+        let positions = [
+            Vector3D::new(0.0, 0.0, 0.0),
+            Vector3D::new(1.0, 0.5, 0.0),
+            Vector3D::new(2.5, 2.0, 1.2),
+        ];
+        let neighbors = NeighborsList::new(&positions, cell, 3.2, PeriodicImagesCheck::Error).unwrap();
+        assert_eq!(neighbors.pairs_indexes.len(), 2 * neighbors.pairs.len());
+
+        for center in 0..positions.len() {
+            let indexes = neighbors.pairs_indexes_containing(center);
+            assert!(indexes.windows(2).all(|w| w[0] <= w[1]));
+
+            let mut expected = neighbors.pairs_by_center[center].iter()
+                .map(|pair| (pair.first, pair.second, pair.cell_shift))
+                .collect::<Vec<_>>();
+            let mut actual = indexes.iter()
+                .map(|&i| &neighbors.pairs[i])
+                .map(|pair| (pair.first, pair.second, pair.cell_shift))
+                .collect::<Vec<_>>();
+
+            expected.sort_unstable();
+            actual.sort_unstable();
+            assert_eq!(actual, expected);
+        }
+
+        // the indexes are updated when filtering the neighbor list
+        let filtered = neighbors.filtered(1.5);
+        assert_eq!(filtered.pairs_indexes.len(), 2 * filtered.pairs.len());
+        for center in 0..positions.len() {
+            for &i in filtered.pairs_indexes_containing(center) {
+                let pair = &filtered.pairs[i];
+                assert!(pair.first == center || pair.second == center);
+            }
+        }
+    }
+
+    #[test]
+    fn cell_list_update() {
+        let cell = UnitCell::triclinic(5.0, 6.0, 5.5, 85.0, 95.0, 100.0);
+        let mut positions = vec![
+            Vector3D::new(0.0, 0.0, 0.0),
+            Vector3D::new(1.0, 0.5, 0.0),
+            Vector3D::new(3.5, 3.5, 3.5),
+            Vector3D::new(2.0, 2.0, 2.0),
+            Vector3D::new(4.8, 0.2, 5.1),
+        ];
+
+        let mut cell_list = CellList::with_positions(&positions, cell, 1.5, PeriodicImagesCheck::Error).unwrap();
+
+        // small displacement, large displacement and crossing the periodic
+        // boundaries
+        positions[1] += Vector3D::new(0.1, 0.0, 0.0);
+        positions[3] += Vector3D::new(-1.6, 2.1, 0.3);
+        positions[4] += Vector3D::new(0.5, -0.5, 0.6);
+        cell_list.update(&positions, &[1, 3, 4]);
+
+        let reference = CellList::with_positions(&positions, cell, 1.5, PeriodicImagesCheck::Error).unwrap();
+
+        let sorted_pairs = |cell_list: &CellList| {
+            let mut pairs = cell_list.pairs().into_iter()
+                .map(|pair| (pair.first, pair.second, pair.shift.0))
+                .collect::<Vec<_>>();
+            pairs.sort_unstable();
+            return pairs;
+        };
+        assert_eq!(sorted_pairs(&cell_list), sorted_pairs(&reference));
+
+        let neighbors = NeighborsList::from_cell_list(&positions, &cell_list).unwrap();
+        let expected = NeighborsList::new(&positions, cell, 1.5, PeriodicImagesCheck::Error).unwrap();
+        assert_eq!(neighbors.pairs.len(), expected.pairs.len());
+    }
+
+    #[test]
+    fn cell_shift_overflow() {
+        assert_eq!(CellShift([3, -2, 0]).to_i32().unwrap(), [3, -2, 0]);
+
+        let shift = CellShift([0, isize::try_from(i64::from(i32::MAX) + 1).unwrap(), 0]);
+        let error = shift.to_i32().unwrap_err();
+        assert_eq!(error.to_string(), "invalid parameter: cell shift [0, 2147483648, 0] is too large to be stored in 32-bit integers");
+    }
+
+    #[test]
+    fn small_cell_large_cutoff() {
+        let cell = UnitCell::cubic(0.5);
+        let positions = [Vector3D::new(0.0, 0.0, 0.0)];
+
+        let error = NeighborsList::new(&positions, cell, 20.0, PeriodicImagesCheck::Error).unwrap_err();
+        assert_eq!(error.to_string(),
+            "invalid parameter: the cutoff (20) is too large for the unit cell \
+            (distances between faces: 0.5, 0.5, 0.5), the neighbor list would \
+            need to search 531441 periodic images of the cell"
+        );
+
+        // all periodic images of the atom are found as neighbors
+        let neighbors = NeighborsList::new(&positions, cell, 20.0, PeriodicImagesCheck::Ignore).unwrap();
+        assert!(!neighbors.pairs.is_empty());
+        assert!(neighbors.pairs.iter().all(|pair| pair.distance < 20.0));
+
+        // infinite cells are never checked
+        NeighborsList::new(&positions, UnitCell::infinite(), 20.0, PeriodicImagesCheck::Error).unwrap();
+    }
 }
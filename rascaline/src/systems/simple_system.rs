@@ -1,8 +1,10 @@
+use std::collections::{BTreeMap, BTreeSet};
+
 use crate::Error;
 
 use super::{UnitCell, System, Vector3D, Pair};
 
-use super::neighbors::NeighborsList;
+use super::neighbors::{CellList, NeighborsList, PeriodicImagesCheck};
 
 /// A simple implementation of `System` to use when no other is available
 #[derive(Clone, Debug)]
@@ -11,6 +13,22 @@ pub struct SimpleSystem {
     species: Vec<i32>,
     positions: Vec<Vector3D>,
     neighbors: Option<NeighborsList>,
+    /// Neighbor list computed with `precompute_neighbors` or given to
+    /// `set_pairs`, used to create the neighbor lists for smaller
+    /// cutoffs
+    largest_neighbors: Option<NeighborsList>,
+    /// Does `largest_neighbors` come from `set_pairs`? In this
+    /// case, rascaline never computes the neighbor list itself.
+    precomputed_pairs: bool,
+    /// Cell list used to compute the last neighbor list, updated when the
+    /// positions change instead of being re-built from scratch
+    cell_list: Option<CellList>,
+    periodic_images_check: PeriodicImagesCheck,
+    /// Combinations of species to include in the neighbor list, sorted such
+    /// that the first species is smaller than the second one. All pairs are
+    /// included if this is `None`.
+    species_pairs: Option<BTreeSet<(i32, i32)>>,
+    data: BTreeMap<String, Vec<f64>>,
 }
 
 impl SimpleSystem {
@@ -21,19 +39,174 @@ impl SimpleSystem {
             species: Vec::new(),
             positions: Vec::new(),
             neighbors: None,
+            largest_neighbors: None,
+            precomputed_pairs: false,
+            cell_list: None,
+            periodic_images_check: PeriodicImagesCheck::default(),
+            species_pairs: None,
+            data: BTreeMap::new(),
         }
     }
 
     /// Add an atom with the given species and position to this system
     pub fn add_atom(&mut self, species: i32, position: Vector3D) {
+        // adding atoms invalidates the neighbor list
+        self.neighbors = None;
+        self.largest_neighbors = None;
+        self.precomputed_pairs = false;
+        self.cell_list = None;
         self.species.push(species);
         self.positions.push(position);
     }
 
+    /// Set new `positions` for all the atoms in this system, for example
+    /// between two steps of a molecular dynamics simulation.
+    ///
+    /// The neighbor list is re-computed on the next call to
+    /// `compute_neighbors`, but the cell list used to compute it is updated by
+    /// only moving the atoms which changed cell instead of being re-built from
+    /// scratch.
+    pub fn set_positions(&mut self, positions: &[Vector3D]) -> Result<(), Error> {
+        if positions.len() != self.positions.len() {
+            return Err(Error::InvalidParameter(format!(
+                "expected {} positions, got {}", self.positions.len(), positions.len()
+            )));
+        }
+
+        let changed = self.positions.iter()
+            .zip(positions)
+            .enumerate()
+            .filter(|(_, (old, new))| old != new)
+            .map(|(i, _)| i)
+            .collect::<Vec<_>>();
+
+        if changed.is_empty() {
+            return Ok(());
+        }
+
+        self.positions.copy_from_slice(positions);
+        if let Some(ref mut cell_list) = self.cell_list {
+            cell_list.update(&self.positions, &changed);
+        }
+
+        self.neighbors = None;
+        self.largest_neighbors = None;
+        self.precomputed_pairs = false;
+        return Ok(());
+    }
+
+    /// Compute the neighbor list of this system once with the given `cutoff`,
+    /// and use it for all later calls to `compute_neighbors` with a smaller or
+    /// equal cutoff, filtering the pairs instead of re-computing the neighbor
+    /// list from scratch.
+    ///
+    /// This is useful when running multiple calculators with different
+    /// cutoffs on the same system, for example in a pipeline of calculators.
+    pub fn precompute_neighbors(&mut self, cutoff: f64) -> Result<(), Error> {
+        let neighbors = NeighborsList::new(
+            self.positions()?, self.cell()?, cutoff, self.periodic_images_check
+        )?;
+        self.neighbors = Some(self.restrict_species(neighbors.clone()));
+        self.largest_neighbors = Some(neighbors);
+        self.precomputed_pairs = false;
+        return Ok(());
+    }
+
+    /// Use the given `pairs`, containing all pairs up to the given `cutoff`,
+    /// for all the neighbor lists of this system, instead of computing them
+    /// with rascaline's own neighbor list.
+    ///
+    /// This is useful when the neighbor list is already available, for example
+    /// from the simulation engine, or to inject exact pair lists (including
+    /// cell shifts) from tests and benchmarks. Calls to `compute_neighbors`
+    /// with the same cutoff use the pairs directly, calls with a smaller cutoff
+    /// filter the pairs, and calls with a larger cutoff return an error. The
+    /// pairs should contain each pair only once, should not contain self pairs
+    /// and should only contain pairs with a distance below the cutoff.
+    pub fn set_pairs(&mut self, cutoff: f64, pairs: Vec<Pair>) -> Result<(), Error> {
+        if !(cutoff > 0.0 && cutoff.is_finite()) {
+            return Err(Error::InvalidParameter(format!(
+                "cutoff for pre-computed pairs must be a positive number, got {}", cutoff
+            )));
+        }
+
+        let neighbors = NeighborsList::from_pairs(self.size()?, cutoff, pairs)?;
+        self.neighbors = Some(self.restrict_species(neighbors.clone()));
+        self.largest_neighbors = Some(neighbors);
+        self.precomputed_pairs = true;
+        return Ok(());
+    }
+
+    /// Only include pairs between atoms with one of the given combinations of
+    /// species in the neighbor lists of this system, for example `[(8, 1)]`
+    /// to only include pairs between oxygen and hydrogen. The order of the
+    /// species in each combination does not matter. Calculators using this
+    /// system will then only see these pairs, which can save a lot of time for
+    /// large systems with many species when only a few of them are relevant.
+    ///
+    /// Setting `species_pairs` to `None` includes all pairs again.
+    #[allow(clippy::float_cmp)]
+    pub fn set_species_pairs(&mut self, species_pairs: Option<&[(i32, i32)]>) {
+        self.species_pairs = species_pairs.map(|species_pairs| {
+            species_pairs.iter()
+                .map(|&(a, b)| if a <= b { (a, b) } else { (b, a) })
+                .collect()
+        });
+
+        // the current neighbor list might contain other pairs, but the
+        // neighbor list for the largest cutoff (including pairs given to
+        // `set_pairs`) is stored without filtering and can be used to re-create
+        // the current neighbor list with the new species pairs
+        let neighbors = match (&self.neighbors, &self.largest_neighbors) {
+            (Some(neighbors), Some(largest)) if neighbors.cutoff == largest.cutoff => {
+                Some(self.restrict_species(largest.clone()))
+            }
+            (Some(neighbors), Some(largest)) if neighbors.cutoff < largest.cutoff => {
+                Some(self.restrict_species(largest.filtered(neighbors.cutoff)))
+            }
+            _ => None,
+        };
+        self.neighbors = neighbors;
+    }
+
+    /// Remove the pairs not matching `self.species_pairs` from `neighbors`
+    fn restrict_species(&self, neighbors: NeighborsList) -> NeighborsList {
+        match self.species_pairs {
+            Some(ref species_pairs) => neighbors.filtered_species(&self.species, species_pairs),
+            None => neighbors,
+        }
+    }
+
+    /// Set what to do when the cutoff used to compute the neighbor list is too
+    /// large for the unit cell of this system, requiring to search through a
+    /// very large number of periodic images. The default is to emit a warning.
+    pub fn set_periodic_images_check(&mut self, check: PeriodicImagesCheck) {
+        self.periodic_images_check = check;
+    }
+
+    /// Set the per-atom data with the given `name` to `values`, replacing any
+    /// existing data with the same name. `values` must contain one entry for
+    /// each atom in this system, so this should be called after adding all
+    /// atoms.
+    pub fn set_data(&mut self, name: &str, values: Vec<f64>) -> Result<(), Error> {
+        if values.len() != self.species.len() {
+            return Err(Error::InvalidParameter(format!(
+                "expected {} values for per-atom data '{}', got {}",
+                self.species.len(), name, values.len()
+            )));
+        }
+
+        self.data.insert(name.into(), values);
+        return Ok(());
+    }
+
     #[cfg(test)]
     pub(crate) fn positions_mut(&mut self) -> &mut [Vector3D] {
         // any position access invalidates the neighbor list
         self.neighbors = None;
+        self.largest_neighbors = None;
+        self.precomputed_pairs = false;
+        self.cell_list = None;
         return &mut self.positions;
     }
 }
@@ -64,7 +237,31 @@ impl System for SimpleSystem {
             }
         }
 
-        self.neighbors = Some(NeighborsList::new(self.positions()?, self.cell()?, cutoff));
+        if let Some(ref largest) = self.largest_neighbors {
+            if cutoff <= largest.cutoff {
+                self.neighbors = Some(self.restrict_species(largest.filtered(cutoff)));
+                return Ok(());
+            }
+
+            if self.precomputed_pairs {
+                return Err(Error::InvalidParameter(format!(
+                    "can not compute the neighbor list with a cutoff of {}, \
+                    the pre-computed pairs of this system only go up to {}",
+                    cutoff, largest.cutoff
+                )));
+            }
+        }
+
+        let reuse_cell_list = matches!(self.cell_list, Some(ref cell_list) if cell_list.cutoff() == cutoff);
+        if !reuse_cell_list {
+            self.cell_list = Some(CellList::with_positions(
+                &self.positions, self.cell, cutoff, self.periodic_images_check
+            )?);
+        }
+
+        let cell_list = self.cell_list.as_ref().expect("missing cell list");
+        let neighbors = NeighborsList::from_cell_list(&self.positions, cell_list)?;
+        self.neighbors = Some(self.restrict_species(neighbors));
         Ok(())
     }
 
@@ -81,6 +278,17 @@ impl System for SimpleSystem {
         ))?;
         Ok(&neighbors.pairs_by_center[center])
     }
+
+    fn pairs_indexes_containing(&self, center: usize) -> Result<Option<&[usize]>, Error> {
+        let neighbors = self.neighbors.as_ref().ok_or_else(|| Error::Internal(
+            "neighbor list is not initialized".into()
+        ))?;
+        Ok(Some(neighbors.pairs_indexes_containing(center)))
+    }
+
+    fn data(&self, name: &str) -> Result<Option<&[f64]>, Error> {
+        Ok(self.data.get(name).map(|values| &**values))
+    }
 }
 
 impl std::convert::TryFrom<&dyn System> for SimpleSystem {
@@ -117,4 +325,256 @@ mod tests {
             Vector3D::new(5.0, 3.0, 4.0),
         ]);
     }
+
+    #[test]
+    fn external_pairs() {
+        let mut system = SimpleSystem::new(UnitCell::cubic(10.0));
+        system.add_atom(3, Vector3D::new(2.0, 3.0, 4.0));
+        system.add_atom(1, Vector3D::new(1.0, 3.0, 4.0));
+        system.add_atom(3, Vector3D::new(5.0, 3.0, 4.0));
+
+        let pair = Pair {
+            first: 0,
+            second: 2,
+            distance: 3.0,
+            vector: Vector3D::new(3.0, 0.0, 0.0),
+            cell_shift: [0, 0, 0],
+        };
+        system.set_pairs(3.5, vec![pair]).unwrap();
+
+        // the external pairs are used for the same cutoff ...
+        system.compute_neighbors(3.5).unwrap();
+        assert_eq!(system.pairs().unwrap().len(), 1);
+        assert_eq!(system.pairs_containing(1).unwrap().len(), 0);
+        assert_eq!(system.pairs_containing(2).unwrap().len(), 1);
+
+        // ... and filtered for smaller cutoffs
+        system.compute_neighbors(3.2).unwrap();
+        assert_eq!(system.pairs().unwrap().len(), 1);
+        system.compute_neighbors(2.5).unwrap();
+        assert_eq!(system.pairs().unwrap().len(), 0);
+
+        let error = system.set_pairs(2.5, vec![pair]).unwrap_err();
+        assert_eq!(error.to_string(), "invalid parameter: got a pair between atoms 0 and 2 with distance 3, above the cutoff (2.5)");
+
+        let bad_pair = Pair { first: 0, second: 3, ..pair };
+        let error = system.set_pairs(3.5, vec![bad_pair]).unwrap_err();
+        assert_eq!(error.to_string(), "invalid parameter: got a pair between atoms 0 and 3, but the system only contains 3 atoms");
+    }
+
+    #[test]
+    fn precomputed_pairs() {
+        let mut system = SimpleSystem::new(UnitCell::cubic(10.0));
+        system.add_atom(3, Vector3D::new(2.0, 3.0, 4.0));
+        system.add_atom(1, Vector3D::new(1.0, 3.0, 4.0));
+        system.add_atom(3, Vector3D::new(5.0, 3.0, 4.0));
+
+        // only include one of the pairs, and a pair with a cell shift
+        let pairs = vec![Pair {
+            first: 0,
+            second: 2,
+            distance: 3.0,
+            vector: Vector3D::new(3.0, 0.0, 0.0),
+            cell_shift: [0, 0, 0],
+        }, Pair {
+            first: 1,
+            second: 2,
+            distance: 6.0,
+            vector: Vector3D::new(-6.0, 0.0, 0.0),
+            cell_shift: [-1, 0, 0],
+        }];
+        system.set_pairs(6.5, pairs).unwrap();
+
+        system.compute_neighbors(6.5).unwrap();
+        assert_eq!(system.pairs().unwrap().len(), 2);
+        assert_eq!(system.pairs().unwrap()[1].cell_shift, [-1, 0, 0]);
+
+        // smaller cutoffs filter the pairs instead of using the cell list
+        system.compute_neighbors(4.0).unwrap();
+        assert_eq!(system.pairs().unwrap().len(), 1);
+        assert_eq!(system.pairs_containing(1).unwrap().len(), 0);
+
+        let error = system.compute_neighbors(7.0).unwrap_err();
+        assert_eq!(error.to_string(),
+            "invalid parameter: can not compute the neighbor list with a \
+            cutoff of 7, the pre-computed pairs of this system only go up to 6.5"
+        );
+
+        let error = system.set_pairs(-1.0, Vec::new()).unwrap_err();
+        assert_eq!(error.to_string(), "invalid parameter: cutoff for pre-computed pairs must be a positive number, got -1");
+
+        // computing the neighbor list explicitly goes back to the cell list
+        system.precompute_neighbors(7.0).unwrap();
+        system.compute_neighbors(5.0).unwrap();
+        assert_eq!(system.pairs().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn precomputed_neighbors() {
+        let mut system = SimpleSystem::new(UnitCell::cubic(4.0));
+        system.add_atom(3, Vector3D::new(0.0, 0.0, 0.0));
+        system.add_atom(1, Vector3D::new(1.0, 0.5, 0.0));
+        system.add_atom(3, Vector3D::new(2.5, 3.0, 1.2));
+
+        let mut reference = system.clone();
+        system.precompute_neighbors(5.0).unwrap();
+        assert!(system.pairs().unwrap().iter().any(|pair| pair.distance > 3.0));
+
+        for &cutoff in &[5.0, 3.0, 1.5] {
+            system.compute_neighbors(cutoff).unwrap();
+            reference.compute_neighbors(cutoff).unwrap();
+
+            // pairs between the same atoms through different periodic images
+            // can be in any order
+            let pairs = |pairs: &[Pair]| {
+                let mut pairs = pairs.iter().map(|pair| (pair.first, pair.second, pair.cell_shift)).collect::<Vec<_>>();
+                pairs.sort_unstable();
+                return pairs;
+            };
+
+            assert_eq!(pairs(system.pairs().unwrap()), pairs(reference.pairs().unwrap()));
+            for center in 0..3 {
+                assert_eq!(
+                    pairs(system.pairs_containing(center).unwrap()),
+                    pairs(reference.pairs_containing(center).unwrap())
+                );
+            }
+        }
+
+        // larger cutoffs re-compute the neighbor list
+        system.compute_neighbors(6.0).unwrap();
+        assert!(system.pairs().unwrap().iter().any(|pair| pair.distance > 5.0));
+    }
+
+    #[test]
+    fn species_pairs() {
+        let mut system = SimpleSystem::new(UnitCell::cubic(10.0));
+        system.add_atom(8, Vector3D::new(0.0, 0.0, 0.0));
+        system.add_atom(1, Vector3D::new(1.0, 0.0, 0.0));
+        system.add_atom(1, Vector3D::new(0.0, 1.0, 0.0));
+        system.add_atom(6, Vector3D::new(0.0, 0.0, 1.0));
+
+        system.compute_neighbors(2.0).unwrap();
+        assert_eq!(system.pairs().unwrap().len(), 6);
+
+        system.set_species_pairs(Some(&[(1, 8)][..]));
+        system.compute_neighbors(2.0).unwrap();
+        let pairs = system.pairs().unwrap().iter()
+            .map(|pair| (pair.first, pair.second))
+            .collect::<Vec<_>>();
+        assert_eq!(pairs, [(0, 1), (0, 2)]);
+        assert_eq!(system.pairs_containing(0).unwrap().len(), 2);
+        assert_eq!(system.pairs_containing(3).unwrap().len(), 0);
+
+        // filtering also applies to pre-computed neighbor lists
+        system.set_species_pairs(Some(&[(1, 1), (6, 8)][..]));
+        system.precompute_neighbors(3.0).unwrap();
+        system.compute_neighbors(2.0).unwrap();
+        let pairs = system.pairs().unwrap().iter()
+            .map(|pair| (pair.first, pair.second))
+            .collect::<Vec<_>>();
+        assert_eq!(pairs, [(0, 3), (1, 2)]);
+
+        system.set_species_pairs(None);
+        system.compute_neighbors(2.0).unwrap();
+        assert_eq!(system.pairs().unwrap().len(), 6);
+    }
+
+    #[test]
+    fn species_pairs_with_external_pairs() {
+        let mut system = SimpleSystem::new(UnitCell::infinite());
+        system.add_atom(8, Vector3D::new(0.0, 0.0, 0.0));
+        system.add_atom(1, Vector3D::new(1.0, 0.0, 0.0));
+        system.add_atom(1, Vector3D::new(0.0, 1.0, 0.0));
+        system.add_atom(6, Vector3D::new(0.0, 0.0, 1.0));
+
+        let pair = |first, second, vector: Vector3D| Pair {
+            first: first,
+            second: second,
+            distance: vector.norm(),
+            vector: vector,
+            cell_shift: [0, 0, 0],
+        };
+        let pairs = vec![
+            pair(0, 1, Vector3D::new(1.0, 0.0, 0.0)),
+            pair(0, 3, Vector3D::new(0.0, 0.0, 1.0)),
+            pair(1, 2, Vector3D::new(-1.0, 1.0, 0.0)),
+        ];
+        system.set_pairs(2.0, pairs).unwrap();
+        system.compute_neighbors(2.0).unwrap();
+        assert_eq!(system.pairs().unwrap().len(), 3);
+
+        // restricting the species filters the external pairs instead of
+        // dropping them
+        system.set_species_pairs(Some(&[(1, 8)][..]));
+        let pairs = system.pairs().unwrap().iter()
+            .map(|pair| (pair.first, pair.second))
+            .collect::<Vec<_>>();
+        assert_eq!(pairs, [(0, 1)]);
+        assert_eq!(system.pairs_containing(1).unwrap().len(), 1);
+        assert_eq!(system.pairs_containing(2).unwrap().len(), 0);
+
+        // the external pairs are still used for smaller cutoffs
+        system.set_species_pairs(Some(&[(1, 1)][..]));
+        system.compute_neighbors(1.5).unwrap();
+        let pairs = system.pairs().unwrap().iter()
+            .map(|pair| (pair.first, pair.second))
+            .collect::<Vec<_>>();
+        assert_eq!(pairs, [(1, 2)]);
+
+        system.set_species_pairs(None);
+        assert_eq!(system.pairs().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn set_positions() {
+        let mut system = SimpleSystem::new(UnitCell::cubic(4.0));
+        system.add_atom(3, Vector3D::new(0.0, 0.0, 0.0));
+        system.add_atom(1, Vector3D::new(1.0, 0.5, 0.0));
+        system.add_atom(3, Vector3D::new(2.5, 3.0, 1.2));
+        system.add_atom(1, Vector3D::new(3.9, 3.9, 0.1));
+        system.compute_neighbors(1.5).unwrap();
+
+        // move atoms to other cells and across periodic boundaries
+        let positions = [
+            Vector3D::new(0.0, 0.0, 0.0),
+            Vector3D::new(2.2, 0.5, 0.0),
+            Vector3D::new(-0.3, 3.0, 1.2),
+            Vector3D::new(4.1, 3.9, 0.1),
+        ];
+        system.set_positions(&positions).unwrap();
+        system.compute_neighbors(1.5).unwrap();
+
+        let mut reference = SimpleSystem::new(UnitCell::cubic(4.0));
+        for (&species, &position) in system.species().unwrap().iter().zip(&positions) {
+            reference.add_atom(species, position);
+        }
+        reference.compute_neighbors(1.5).unwrap();
+
+        let pairs = |pairs: &[Pair]| {
+            let mut pairs = pairs.iter().map(|pair| (pair.first, pair.second, pair.cell_shift)).collect::<Vec<_>>();
+            pairs.sort_unstable();
+            return pairs;
+        };
+        assert!(!reference.pairs().unwrap().is_empty());
+        assert_eq!(pairs(system.pairs().unwrap()), pairs(reference.pairs().unwrap()));
+
+        let error = system.set_positions(&positions[..2]).unwrap_err();
+        assert_eq!(error.to_string(), "invalid parameter: expected 4 positions, got 2");
+    }
+
+    #[test]
+    fn data() {
+        let mut system = SimpleSystem::new(UnitCell::cubic(10.0));
+        system.add_atom(3, Vector3D::new(2.0, 3.0, 4.0));
+        system.add_atom(1, Vector3D::new(1.0, 3.0, 4.0));
+
+        assert_eq!(system.data("charges").unwrap(), None);
+
+        system.set_data("charges", vec![0.5, -0.5]).unwrap();
+        assert_eq!(system.data("charges").unwrap(), Some(&[0.5, -0.5][..]));
+
+        let error = system.set_data("charges", vec![0.5]).unwrap_err();
+        assert_eq!(error.to_string(), "invalid parameter: expected 2 values for per-atom data 'charges', got 1");
+    }
 }
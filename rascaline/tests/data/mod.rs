@@ -1,5 +1,7 @@
 #![allow(dead_code)]
 
+use std::convert::TryFrom;
+
 use serde_json::Value;
 use ndarray_npy::ReadNpyExt;
 use flate2::read::GzDecoder;
@@ -50,11 +52,7 @@ fn read_cell(cell: &Value) -> UnitCell {
         [cell[6].as_f64().unwrap(), cell[7].as_f64().unwrap(), cell[8].as_f64().unwrap()],
     ]);
 
-    if matrix == Matrix3::zero() {
-        UnitCell::infinite()
-    } else {
-        UnitCell::from(matrix)
-    }
+    UnitCell::try_from(matrix).expect("invalid unit cell")
 }
 
 pub fn load_expected_values<T: ReadNpyExt>(path: &str) -> T {
@@ -1,6 +1,8 @@
 #[test]
 fn file_size_below_200k() {
-    for entry in glob::glob("tests/data/generated/*").unwrap() {
+    let generated = glob::glob("tests/data/generated/*").unwrap();
+    let reference = glob::glob("tests/data/reference/*").unwrap();
+    for entry in generated.chain(reference) {
         let path = entry.unwrap();
         let metadata = std::fs::metadata(&path).unwrap();
         assert!(
@@ -0,0 +1,72 @@
+//! Compare the output of the calculators with reference data stored in
+//! `tests/data/reference`. Set the `RASCALINE_UPDATE_REFERENCE` environment
+//! variable when running these tests to re-create the reference data after an
+//! intentional change in the output of a calculator.
+use rascaline::{Calculator, SimpleSystem, System, Vector3D};
+use rascaline::checks::Reference;
+use rascaline::systems::UnitCell;
+
+fn systems() -> Vec<Box<dyn System>> {
+    let mut water = SimpleSystem::new(UnitCell::infinite());
+    water.add_atom(8, Vector3D::new(0.0, 0.0, 0.0));
+    water.add_atom(1, Vector3D::new(0.0, 0.75545, -0.58895));
+    water.add_atom(1, Vector3D::new(0.0, -0.75545, -0.58895));
+
+    let mut methane = SimpleSystem::new(UnitCell::cubic(5.0));
+    methane.add_atom(6, Vector3D::new(5.0000, 5.0000, 5.0000));
+    methane.add_atom(1, Vector3D::new(5.5288, 5.1610, 5.9359));
+    methane.add_atom(1, Vector3D::new(5.2051, 5.8240, 4.3214));
+    methane.add_atom(1, Vector3D::new(5.3345, 4.0686, 4.5504));
+    methane.add_atom(1, Vector3D::new(3.9315, 4.9463, 5.1921));
+
+    vec![Box::new(water), Box::new(methane)]
+}
+
+fn check_reference(name: &str, calculator: &str, parameters: &str) {
+    let path = format!("tests/data/reference/{}.json", name);
+    let mut calculator = Calculator::new(calculator, parameters.into()).unwrap();
+
+    if std::env::var_os("RASCALINE_UPDATE_REFERENCE").is_some() {
+        let reference = Reference::record(&mut calculator, &mut systems()).unwrap();
+        let file = std::fs::File::create(&path).expect("failed to create reference file");
+        reference.save(std::io::BufWriter::new(file)).unwrap();
+    }
+
+    let file = std::fs::File::open(&path).expect("failed to open reference file");
+    let reference = Reference::load(std::io::BufReader::new(file)).unwrap();
+    rascaline::checks::regression(&mut calculator, &reference, 1e-10).unwrap();
+}
+
+#[test]
+fn sorted_distances() {
+    check_reference("sorted-distances", "sorted_distances", r#"{
+        "cutoff": 2.4,
+        "max_neighbors": 4
+    }"#);
+}
+
+#[test]
+fn spherical_expansion() {
+    check_reference("spherical-expansion", "spherical_expansion", r#"{
+        "cutoff": 2.4,
+        "max_radial": 3,
+        "max_angular": 2,
+        "atomic_gaussian_width": 0.3,
+        "gradients": true,
+        "radial_basis": {"Gto": {}},
+        "cutoff_function": {"ShiftedCosine": {"width": 0.5}}
+    }"#);
+}
+
+#[test]
+fn soap_power_spectrum() {
+    check_reference("soap-power-spectrum", "soap_power_spectrum", r#"{
+        "cutoff": 2.4,
+        "max_radial": 2,
+        "max_angular": 2,
+        "atomic_gaussian_width": 0.3,
+        "gradients": true,
+        "radial_basis": {"Gto": {}},
+        "cutoff_function": {"ShiftedCosine": {"width": 0.5}}
+    }"#);
+}